@@ -0,0 +1,56 @@
+use crate::{AppState, MessageType};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::time::{Duration, Instant};
+
+/// Key handling state behind the accessibility settings
+#[derive(Debug, Default)]
+pub struct KeyFilter {
+    /// The last key that got through and when
+    last_key: Option<(KeyCode, KeyModifiers, Instant)>,
+    /// The sticky prefix was pressed; the next key gets Ctrl
+    sticky_armed: bool,
+}
+
+impl AppState {
+    /// Apply the accessibility settings to a key press: drop it when it
+    /// repeats the previous key too soon, and add Ctrl to the key after the
+    /// sticky prefix. `None` means the key is used up.
+    pub fn filter_key(&mut self, mut key: KeyEvent) -> Option<KeyEvent> {
+        let settings = &self.config.settings.accessibility;
+        let now = Instant::now();
+
+        if settings.repeat_filter_ms > 0 {
+            let window = Duration::from_millis(settings.repeat_filter_ms);
+            if let Some((code, modifiers, at)) = self.key_filter.last_key {
+                if (code, modifiers) == (key.code, key.modifiers) && now.duration_since(at) < window {
+                    return None;
+                }
+            }
+        }
+        self.key_filter.last_key = Some((key.code, key.modifiers, now));
+
+        let Some(prefix) = settings.sticky_prefix else {
+            return Some(key);
+        };
+        if self.key_filter.sticky_armed {
+            self.key_filter.sticky_armed = false;
+            match key.code {
+                // Esc lets go of Ctrl; the prefix again types itself
+                KeyCode::Esc => {
+                    self.set_message("Sticky Ctrl released".to_string(), MessageType::Info);
+                    return None;
+                },
+                KeyCode::Char(c) if c == prefix => {},
+                _ => key.modifiers |= KeyModifiers::CONTROL,
+            }
+            self.clear_message();
+            return Some(key);
+        }
+        if key.code == KeyCode::Char(prefix) && matches!(key.modifiers, KeyModifiers::NONE | KeyModifiers::SHIFT) {
+            self.key_filter.sticky_armed = true;
+            self.set_message(format!("Ctrl held: press a key (Esc to release, {} again to type it)", prefix), MessageType::Info);
+            return None;
+        }
+        Some(key)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{AppState, CommandPickerForm, MessageType, ModalState};
+
+impl AppState {
+    /// Commands the live session is restricted to; None when free typing is
+    /// allowed
+    pub fn allowed_commands(&self) -> Option<&[String]> {
+        if !self.ssh_client.is_connected() {
+            return None;
+        }
+        self.ssh_client.get_host()
+            .map(|host| host.allowed_commands.as_slice())
+            .filter(|commands| !commands.is_empty())
+    }
+
+    pub fn input_locked(&self) -> bool {
+        // The sidebar never types into the session, so it stays usable
+        !self.sidebar_focused && self.allowed_commands().is_some()
+    }
+
+    /// List the approved commands in place of a prompt to type into
+    pub fn open_command_picker(&mut self) {
+        let Some(commands) = self.allowed_commands() else {
+            return;
+        };
+        self.modal_state = ModalState::Commands(CommandPickerForm {
+            commands: commands.to_vec(),
+            cursor: 0,
+        });
+    }
+
+    /// Type an approved command into the session and run it
+    pub fn send_allowed_command(&mut self, form: CommandPickerForm) {
+        let Some(command) = form.commands.get(form.cursor) else {
+            return;
+        };
+        if !self.allowed_commands().is_some_and(|commands| commands.contains(command)) {
+            self.set_message("That command is not allowed on this host".to_string(), MessageType::Error);
+            return;
+        }
+
+        self.terminal_panel.reset_predictions();
+        self.touch_session();
+        let data = format!("{}\r", command).into_bytes();
+        let client = self.ssh_client.clone();
+        tokio::spawn(async move {
+            let _ = client.send_input(&data).await;
+        });
+    }
+}
@@ -0,0 +1,682 @@
+use crate::{AppState, MessageType};
+use crate::config::{Config, Group, GroupDefaults, Host, GROUP_SEPARATOR};
+use crate::import::Importer;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fs;
+
+/// How an exported inventory is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Ansible INI inventory
+    Ansible,
+    /// Ansible YAML inventory
+    AnsibleYaml,
+}
+
+/// A host as Ansible knows it: its inventory name and connection variables
+struct InventoryHost {
+    name: String,
+    vars: Vec<(&'static str, Value)>,
+}
+
+/// Ansible group names may only hold letters, digits and underscores, so
+/// "clients/acme-prod" becomes "clients_acme_prod"
+fn group_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// The hosts of a group that Ansible can reach over ssh. Host names used
+/// in more than one group get the group in front, since Ansible would
+/// otherwise take them for one host.
+fn inventory_hosts(config: &Config, group: &Group) -> Vec<InventoryHost> {
+    let taken_elsewhere = |name: &str| {
+        config.groups.iter()
+            .skip(1)
+            .filter(|other| other.hosts.iter().any(|host| host.name == name))
+            .count() > 1
+    };
+    let default_key = config.get_default_key().map(|key| key.path.clone());
+
+    group.hosts.iter()
+        .filter(|host| host.kind.uses_ssh())
+        .map(|host| {
+            let base = host.name.split_whitespace().collect::<Vec<_>>().join("-");
+            let name = if taken_elsewhere(&host.name) { format!("{}-{}", group_name(&group.name), base) } else { base };
+            let host = config.effective_host(host);
+            let mut vars = vec![("ansible_host", Value::from(host.host))];
+            // Without a user anywhere Ansible picks one as ssh would
+            if !host.user.is_empty() {
+                vars.push(("ansible_user", Value::from(host.user)));
+            }
+            vars.push(("ansible_port", Value::from(host.port)));
+            if let Some(key_path) = host.key_path.or_else(|| default_key.clone()) {
+                vars.push(("ansible_ssh_private_key_file", Value::from(key_path)));
+            }
+            InventoryHost { name, vars }
+        })
+        .collect()
+}
+
+/// Groups nested directly in `parent`, or the top-level groups for None
+fn child_groups<'a>(config: &'a Config, parent: Option<&'a str>) -> impl Iterator<Item = &'a Group> {
+    config.groups.iter().skip(1).filter(move |group| group.parent_name() == parent)
+}
+
+/// The config's groups and hosts as an Ansible INI inventory, with nested
+/// groups listed under `[parent:children]`
+pub fn to_ini(config: &Config) -> String {
+    let mut ini = String::from("# Ansible inventory exported by sshtui\n");
+    for group in config.groups.iter().skip(1) {
+        ini.push_str(&format!("\n[{}]\n", group_name(&group.name)));
+        for host in inventory_hosts(config, group) {
+            let vars: Vec<String> = host.vars.iter().map(|(name, value)| format!("{}={}", name, ini_value(value))).collect();
+            ini.push_str(&format!("{} {}\n", host.name, vars.join(" ")));
+        }
+
+        let children: Vec<String> = child_groups(config, Some(&group.name)).map(|child| group_name(&child.name)).collect();
+        if !children.is_empty() {
+            ini.push_str(&format!("\n[{}:children]\n{}\n", group_name(&group.name), children.join("\n")));
+        }
+    }
+    ini
+}
+
+/// A variable's value on an INI host line, quoted when it has spaces
+fn ini_value(value: &Value) -> String {
+    match value {
+        Value::String(text) if text.chars().any(char::is_whitespace) => format!("\"{}\"", text.replace('"', "\\\"")),
+        Value::String(text) => text.clone(),
+        Value::Number(number) => number.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// The config's groups and hosts as an Ansible YAML inventory, with nested
+/// groups under their parent's `children`
+pub fn to_yaml(config: &Config) -> Result<String, serde_yaml::Error> {
+    let mut all = Mapping::new();
+    all.insert("children".into(), yaml_groups(config, None).into());
+    let mut inventory = Mapping::new();
+    inventory.insert("all".into(), all.into());
+    Ok(format!("# Ansible inventory exported by sshtui\n{}", serde_yaml::to_string(&inventory)?))
+}
+
+fn yaml_groups(config: &Config, parent: Option<&str>) -> Mapping {
+    let mut groups = Mapping::new();
+    for group in child_groups(config, parent) {
+        let mut entry = Mapping::new();
+        let hosts = inventory_hosts(config, group);
+        if !hosts.is_empty() {
+            let hosts: Mapping = hosts.into_iter()
+                .map(|host| {
+                    let vars: Mapping = host.vars.into_iter().map(|(name, value)| (Value::from(name), value)).collect();
+                    (Value::from(host.name), Value::from(vars))
+                })
+                .collect();
+            entry.insert("hosts".into(), hosts.into());
+        }
+        let children = yaml_groups(config, Some(&group.name));
+        if !children.is_empty() {
+            entry.insert("children".into(), children.into());
+        }
+        // An empty group is written as `name: {}`
+        groups.insert(group_name(&group.name).into(), entry.into());
+    }
+    groups
+}
+
+/// The inventory in the given format
+pub fn export(config: &Config, format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Ansible => Ok(to_ini(config)),
+        ExportFormat::AnsibleYaml => to_yaml(config).map_err(|e| e.to_string()),
+    }
+}
+
+/// Hosts and groups as an Ansible inventory lists them, before they become
+/// sshtui groups
+#[derive(Debug, Default)]
+struct Inventory {
+    /// Group names in the order they first appear
+    groups: Vec<String>,
+    /// Every (group, host) listing, in order; a host may be in several groups
+    members: Vec<(String, String)>,
+    /// (parent, child) for each `[parent:children]` entry
+    children: Vec<(String, String)>,
+    host_vars: HashMap<String, HashMap<String, String>>,
+    group_vars: HashMap<String, HashMap<String, String>>,
+}
+
+/// Which part of a group an INI section lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Hosts,
+    Vars,
+    Children,
+}
+
+/// Connection types sshtui can open sessions for; hosts using others, such
+/// as `local` or `winrm`, are left out
+const SSH_CONNECTIONS: [&str; 3] = ["ssh", "paramiko", "smart"];
+
+impl Inventory {
+    fn add_group(&mut self, name: &str) {
+        if !self.groups.iter().any(|group| group == name) {
+            self.groups.push(name.to_string());
+        }
+    }
+
+    /// List `pattern`'s hosts in `group`; "db:2222" sets the port
+    fn add_hosts(&mut self, group: &str, pattern: &str, vars: Vec<(String, String)>) -> Result<(), String> {
+        self.add_group(group);
+        let (pattern, port) = match pattern.split_once(':') {
+            Some((name, port)) if !port.contains(':') && port.parse::<u16>().is_ok() => (name, Some(port)),
+            _ => (pattern, None),
+        };
+        for host in expand_hosts(pattern)? {
+            let member = (group.to_string(), host.clone());
+            if !self.members.contains(&member) {
+                self.members.push(member);
+            }
+            let host_vars = self.host_vars.entry(host).or_default();
+            host_vars.extend(vars.iter().cloned());
+            if let Some(port) = port {
+                host_vars.insert("ansible_port".to_string(), port.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// The group a group is nested under; a group listed as the child of
+    /// several is nested under the first
+    fn parent_of(&self, name: &str) -> Option<&str> {
+        self.children.iter()
+            .find(|(parent, child)| child == name && parent != "all")
+            .map(|(parent, _)| parent.as_str())
+    }
+
+    /// `name` and the groups it is nested in, innermost first
+    fn ancestry<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        let mut chain = vec![name];
+        let mut current = name;
+        while let Some(parent) = self.parent_of(current) {
+            // A loop of children sections ends where it started
+            if chain.contains(&parent) {
+                break;
+            }
+            chain.push(parent);
+            current = parent;
+        }
+        chain
+    }
+
+    /// The sshtui name of a group, such as "prod/web" for `web` listed
+    /// under `[prod:children]`
+    fn full_name(&self, name: &str) -> String {
+        let mut chain = self.ancestry(name);
+        chain.reverse();
+        chain.join(&GROUP_SEPARATOR.to_string())
+    }
+
+    /// The connection variables a group's hosts get, from the group itself,
+    /// then the groups it is nested in, then `all`
+    fn group_defaults(&self, name: &str) -> GroupDefaults {
+        let mut defaults = GroupDefaults::default();
+        for group in self.ancestry(name).into_iter().chain(["all"]) {
+            if let Some(vars) = self.group_vars.get(group) {
+                defaults.user = defaults.user.or_else(|| var(vars, &["ansible_user", "ansible_ssh_user"]));
+                defaults.port = defaults.port.or_else(|| var(vars, &["ansible_port", "ansible_ssh_port"]).and_then(|port| port.parse().ok()));
+                defaults.key_path = defaults.key_path.or_else(|| var(vars, &["ansible_ssh_private_key_file", "ansible_private_key_file"]));
+            }
+        }
+        defaults
+    }
+
+    /// Whether sshtui can reach the host, given its own and its groups' vars
+    fn uses_ssh(&self, group: &str, host: &str) -> bool {
+        let connection = self.host_vars.get(host)
+            .and_then(|vars| var(vars, &["ansible_connection"]))
+            .or_else(|| {
+                self.ancestry(group).into_iter().chain(["all"])
+                    .find_map(|group| self.group_vars.get(group).and_then(|vars| var(vars, &["ansible_connection"])))
+            });
+        connection.is_none_or(|connection| SSH_CONNECTIONS.contains(&connection.as_str()))
+    }
+
+    /// The inventory as sshtui groups, sorted so parents come before the
+    /// groups nested in them. Each host goes in the first group listing it,
+    /// and the other groups it is in become its tags; hosts outside any
+    /// group go in "ungrouped".
+    fn into_groups(self) -> Vec<Group> {
+        let group_of = |group: &str| if group == "all" { "ungrouped".to_string() } else { group.to_string() };
+        let mut groups: Vec<Group> = Vec::new();
+        let mut placed: HashMap<String, (usize, usize)> = HashMap::new();
+
+        for name in self.groups.iter().filter(|name| *name != "all") {
+            groups.push(Group { defaults: self.group_defaults(name), ..Group::new(self.full_name(name)) });
+        }
+        for (group, name) in &self.members {
+            let group = group_of(group);
+            if !self.uses_ssh(&group, name) {
+                continue;
+            }
+            if let Some(&(group_index, host_index)) = placed.get(name) {
+                let host = &mut groups[group_index].hosts[host_index];
+                if !host.tags.contains(&group) {
+                    host.tags.push(group);
+                }
+                continue;
+            }
+            let full_name = self.full_name(&group);
+            let group_index = match groups.iter().position(|existing| existing.name == full_name) {
+                Some(index) => index,
+                None => {
+                    groups.push(Group { defaults: self.group_defaults(&group), ..Group::new(full_name) });
+                    groups.len() - 1
+                },
+            };
+            let vars = self.host_vars.get(name).cloned().unwrap_or_default();
+            let host = Host {
+                user: var(&vars, &["ansible_user", "ansible_ssh_user"]).unwrap_or_default(),
+                port: var(&vars, &["ansible_port", "ansible_ssh_port"]).and_then(|port| port.parse().ok()).unwrap_or(0),
+                key_path: var(&vars, &["ansible_ssh_private_key_file", "ansible_private_key_file"]),
+                ..Host::new(name.as_str(), var(&vars, &["ansible_host", "ansible_ssh_host"]).unwrap_or_else(|| name.clone()))
+            };
+            groups[group_index].hosts.push(host);
+            placed.insert(name.clone(), (group_index, groups[group_index].hosts.len() - 1));
+        }
+
+        // "ungrouped" is only worth keeping with hosts in it
+        groups.retain(|group| group.name != "ungrouped" || !group.hosts.is_empty());
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        groups
+    }
+}
+
+/// The first of `names` that is set, as text
+fn var(vars: &HashMap<String, String>, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| vars.get(*name)).filter(|value| !value.is_empty()).cloned()
+}
+
+/// Most hosts a single pattern may stand for, ranges in one name included
+const MAX_EXPANDED_HOSTS: usize = 10_000;
+
+/// Host patterns such as "web[01:03]" or "db-[a:c]" stand for a run of
+/// hosts; an optional third part is the step, as in "[0:10:2]"
+fn expand_hosts(pattern: &str) -> Result<Vec<String>, String> {
+    let (Some(open), Some(close)) = (pattern.find('['), pattern.find(']')) else {
+        return Ok(vec![pattern.to_string()]);
+    };
+    if close < open {
+        return Ok(vec![pattern.to_string()]);
+    }
+    let (prefix, range, suffix) = (&pattern[..open], &pattern[open + 1..close], &pattern[close + 1..]);
+    let bad_range = || format!("'{}' is not a host range such as web[01:03]", pattern);
+    let mut parts = range.split(':');
+    let (Some(start), Some(end)) = (parts.next(), parts.next()) else {
+        return Err(bad_range());
+    };
+    let step = match parts.next() {
+        Some(step) => step.parse::<usize>().ok().filter(|step| *step > 0).ok_or_else(bad_range)?,
+        None => 1,
+    };
+
+    let names: Vec<String> = if let (Ok(first), Ok(last)) = (start.parse::<u32>(), end.parse::<u32>()) {
+        if last < first || (last - first) as usize >= MAX_EXPANDED_HOSTS {
+            return Err(bad_range());
+        }
+        // "01" keeps the numbers two digits wide
+        let width = if start.starts_with('0') { start.len() } else { 0 };
+        (first..=last).step_by(step).map(|n| format!("{}{:0width$}{}", prefix, n, suffix, width = width)).collect()
+    } else {
+        let letter = |text: &str| {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+                _ => None,
+            }
+        };
+        match (letter(start), letter(end)) {
+            (Some(first), Some(last)) if first <= last => {
+                (first..=last).step_by(step).map(|c| format!("{}{}{}", prefix, c, suffix)).collect()
+            },
+            _ => return Err(bad_range()),
+        }
+    };
+    // Later ranges in the same name multiply the count
+    let mut hosts = Vec::new();
+    for name in names {
+        hosts.extend(expand_hosts(&name)?);
+        if hosts.len() > MAX_EXPANDED_HOSTS {
+            return Err(format!("'{}' stands for more than {} hosts", pattern, MAX_EXPANDED_HOSTS));
+        }
+    }
+    Ok(hosts)
+}
+
+/// Words of an INI line, with quotes around a value keeping its spaces
+/// together and a `#` starting a comment
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && word.is_empty() => break,
+            None if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            },
+            None => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// `key=value` words as variables; words without `=` are ignored
+fn word_vars(words: impl IntoIterator<Item = String>) -> Vec<(String, String)> {
+    words.into_iter()
+        .filter_map(|word| word.split_once('=').map(|(key, value)| (key.to_string(), value.to_string())))
+        .collect()
+}
+
+fn parse_ini(text: &str) -> Result<Inventory, String> {
+    let mut inventory = Inventory::default();
+    let mut group = "ungrouped".to_string();
+    let mut section = Section::Hosts;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.split_once(']').map(|(header, _)| header.trim())
+                .ok_or_else(|| format!("Line {}: the section name has no closing ]", number + 1))?;
+            (group, section) = match header.split_once(':') {
+                Some((name, "vars")) => (name.to_string(), Section::Vars),
+                Some((name, "children")) => (name.to_string(), Section::Children),
+                Some((_, kind)) => return Err(format!("Line {}: unknown section type '{}'", number + 1, kind)),
+                None => (header.to_string(), Section::Hosts),
+            };
+            inventory.add_group(&group);
+            continue;
+        }
+
+        match section {
+            Section::Hosts => {
+                let mut words = split_words(line).into_iter();
+                if let Some(pattern) = words.next() {
+                    inventory.add_hosts(&group, &pattern, word_vars(words))
+                        .map_err(|e| format!("Line {}: {}", number + 1, e))?;
+                }
+            },
+            Section::Vars => {
+                let (key, value) = line.split_once('=')
+                    .ok_or_else(|| format!("Line {}: expected name=value under [{}:vars]", number + 1, group))?;
+                let value = split_words(value).join(" ");
+                inventory.group_vars.entry(group.clone()).or_default().insert(key.trim().to_string(), value);
+            },
+            Section::Children => {
+                let child = line.split_whitespace().next().unwrap_or(line).to_string();
+                inventory.add_group(&child);
+                inventory.children.push((group.clone(), child));
+            },
+        }
+    }
+    Ok(inventory)
+}
+
+/// A YAML scalar as the text an INI inventory would hold
+fn yaml_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// The scalar variables of a YAML mapping; lists and nested maps are ignored
+fn yaml_vars(value: &Value) -> Vec<(String, String)> {
+    let Value::Mapping(vars) = value else {
+        return Vec::new();
+    };
+    vars.iter()
+        .filter_map(|(key, value)| Some((yaml_text(key)?, yaml_text(value)?)))
+        .collect()
+}
+
+fn read_yaml_group(inventory: &mut Inventory, name: &str, group: &Value) -> Result<(), String> {
+    inventory.add_group(name);
+    // `web:` with nothing under it is an empty group
+    let Value::Mapping(group) = group else {
+        return Ok(());
+    };
+    if let Some(Value::Mapping(hosts)) = group.get("hosts") {
+        for (pattern, vars) in hosts {
+            let pattern = yaml_text(pattern).ok_or_else(|| format!("A host of group '{}' has no name", name))?;
+            inventory.add_hosts(name, &pattern, yaml_vars(vars))?;
+        }
+    }
+    if let Some(vars) = group.get("vars") {
+        inventory.group_vars.entry(name.to_string()).or_default().extend(yaml_vars(vars));
+    }
+    if let Some(Value::Mapping(children)) = group.get("children") {
+        for (child, body) in children {
+            let child = yaml_text(child).ok_or_else(|| format!("A child group of '{}' has no name", name))?;
+            inventory.children.push((name.to_string(), child.clone()));
+            read_yaml_group(inventory, &child, body)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_yaml(text: &str) -> Result<Inventory, String> {
+    let value: Value = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    let Value::Mapping(groups) = value else {
+        return Err("Expected groups such as `all:` at the top of the inventory".to_string());
+    };
+    let mut inventory = Inventory::default();
+    for (name, group) in &groups {
+        let name = yaml_text(name).ok_or("A group has no name")?;
+        read_yaml_group(&mut inventory, &name, group)?;
+    }
+    Ok(inventory)
+}
+
+/// Whether an inventory is YAML rather than INI: by its extension, or else
+/// by whether it opens with a section header
+fn is_yaml(path: &str, text: &str) -> bool {
+    if path.ends_with(".yml") || path.ends_with(".yaml") {
+        return true;
+    }
+    if path.ends_with(".ini") {
+        return false;
+    }
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .is_some_and(|line| !line.starts_with('['))
+        && serde_yaml::from_str::<Mapping>(text).is_ok()
+}
+
+/// Reads Ansible inventories, INI or YAML
+pub struct AnsibleInventory;
+
+impl Importer for AnsibleInventory {
+    fn parse(&self, path: &str, text: &str) -> Result<Vec<Group>, String> {
+        let inventory = if is_yaml(path, text) { parse_yaml(text)? } else { parse_ini(text)? };
+        Ok(inventory.into_groups())
+    }
+}
+
+impl AppState {
+    /// Write the hosts as an Ansible inventory next to the config
+    pub fn export_ansible_inventory(&mut self) {
+        let path = match Config::config_dir().map(|dir| dir.join("inventory.ini")) {
+            Ok(path) => path,
+            Err(e) => {
+                self.set_message(format!("Could not export the inventory: {}", e), MessageType::Error);
+                return;
+            }
+        };
+        if let Err(e) = fs::write(&path, to_ini(&self.config)) {
+            self.set_message(format!("Could not write {:?}: {}", path, e), MessageType::Error);
+            return;
+        }
+        self.set_message(format!("Wrote the Ansible inventory to {}", path.display()), MessageType::Success);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(name: &str, address: &str, user: &str, port: u16) -> Host {
+        Host { user: user.to_string(), port, ..Host::new(name, address) }
+    }
+
+    fn sample_config() -> Config {
+        let mut web = Group::new("web");
+        web.hosts.push(host("web one", "10.0.0.1", "deploy", 2222));
+        let mut clients = Group::new("clients");
+        clients.hosts.push(host("gateway", "gw.example.com", "admin", 22));
+        let mut acme = Group::new("clients/acme-prod");
+        acme.hosts.push(host("db", "db.acme.test", "postgres", 5432));
+        Config { groups: vec![Group::new("All"), web, clients, acme], ..Config::default() }
+    }
+
+    fn parse(path: &str, text: &str) -> Vec<Group> {
+        AnsibleInventory.parse(path, text).unwrap()
+    }
+
+    #[test]
+    fn exports_ini_with_children_sections() {
+        let ini = to_ini(&sample_config());
+        assert!(ini.contains("[web]\nweb-one ansible_host=10.0.0.1 ansible_user=deploy ansible_port=2222\n"));
+        assert!(ini.contains("[clients:children]\nclients_acme_prod\n"));
+        assert!(ini.contains("[clients_acme_prod]\ndb ansible_host=db.acme.test ansible_user=postgres ansible_port=5432\n"));
+    }
+
+    #[test]
+    fn exported_ini_imports_back() {
+        let groups = parse("inventory.ini", &to_ini(&sample_config()));
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["clients", "clients/clients_acme_prod", "web"]);
+        let web = &groups[2].hosts[0];
+        assert_eq!((web.name.as_str(), web.host.as_str(), web.user.as_str(), web.port), ("web-one", "10.0.0.1", "deploy", 2222));
+    }
+
+    #[test]
+    fn exported_yaml_imports_back() {
+        let yaml = to_yaml(&sample_config()).unwrap();
+        let groups = parse("inventory.yml", &yaml);
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["clients", "clients/clients_acme_prod", "web"]);
+        let db = &groups[1].hosts[0];
+        assert_eq!((db.host.as_str(), db.user.as_str(), db.port), ("db.acme.test", "postgres", 5432));
+    }
+
+    #[test]
+    fn imports_ini_vars_ports_and_tags() {
+        let groups = parse("hosts", "\
+# comment
+[web]
+web1 ansible_host=10.0.0.1 ansible_user=\"deploy user\"
+web2:2200
+
+[db]
+web1
+db1 ansible_connection=local
+
+[web:vars]
+ansible_user=www
+ansible_port=2022
+");
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["db", "web"]);
+        // web1 stays in web and is tagged with db; the local host is left out
+        assert!(groups[0].hosts.is_empty());
+        let web = &groups[1];
+        assert_eq!(web.defaults.user.as_deref(), Some("www"));
+        assert_eq!(web.defaults.port, Some(2022));
+        assert_eq!(web.hosts[0].user, "deploy user");
+        assert_eq!(web.hosts[0].tags, ["db"]);
+        assert_eq!(web.hosts[1].port, 2200);
+    }
+
+    #[test]
+    fn imports_yaml_children_and_inherited_vars() {
+        let groups = parse("inventory.yaml", "\
+all:
+  vars:
+    ansible_user: root
+  hosts:
+    loose.example.com:
+  children:
+    prod:
+      vars:
+        ansible_port: 2222
+      children:
+        web:
+          hosts:
+            web[1:2].example.com:
+");
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["prod", "prod/web", "ungrouped"]);
+        let web = &groups[1];
+        assert_eq!(web.defaults.user.as_deref(), Some("root"));
+        assert_eq!(web.defaults.port, Some(2222));
+        let hosts: Vec<&str> = web.hosts.iter().map(|host| host.host.as_str()).collect();
+        assert_eq!(hosts, ["web1.example.com", "web2.example.com"]);
+        assert_eq!(groups[2].hosts[0].name, "loose.example.com");
+    }
+
+    #[test]
+    fn rejects_unknown_ini_sections() {
+        assert!(AnsibleInventory.parse("hosts.ini", "[web:other]\n").is_err());
+    }
+
+    #[test]
+    fn expands_numeric_ranges_keeping_width() {
+        assert_eq!(expand_hosts("web[08:10].lan").unwrap(), ["web08.lan", "web09.lan", "web10.lan"]);
+        assert_eq!(expand_hosts("node[1:3]").unwrap(), ["node1", "node2", "node3"]);
+        assert_eq!(expand_hosts("plain").unwrap(), ["plain"]);
+    }
+
+    #[test]
+    fn expands_letter_ranges_and_steps() {
+        assert_eq!(expand_hosts("db-[a:c]").unwrap(), ["db-a", "db-b", "db-c"]);
+        assert_eq!(expand_hosts("h[0:6:3]").unwrap(), ["h0", "h3", "h6"]);
+    }
+
+    #[test]
+    fn expands_several_ranges_in_one_name() {
+        assert_eq!(expand_hosts("r[1:2]-[a:b]").unwrap(), ["r1-a", "r1-b", "r2-a", "r2-b"]);
+    }
+
+    #[test]
+    fn rejects_bad_ranges() {
+        assert!(expand_hosts("web[3:1]").is_err());
+        assert!(expand_hosts("web[1]").is_err());
+        assert!(expand_hosts("web[1:3:0]").is_err());
+        assert!(expand_hosts("web[a:3]").is_err());
+    }
+
+    #[test]
+    fn caps_the_hosts_a_pattern_stands_for() {
+        assert_eq!(expand_hosts("h[1:10000]").unwrap().len(), MAX_EXPANDED_HOSTS);
+        assert!(expand_hosts("h[0:10000]").is_err());
+        assert!(expand_hosts("h[0:999]-[0:999]").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{AppState, AuthorizedKeysForm, MessageType, ModalState};
+use crate::config::{Config, Host, SshKey};
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+/// Appends every line read from stdin to authorized_keys unless it is
+/// already there, creating the file with safe permissions if needed
+const APPEND_SCRIPT: &str = "umask 077; mkdir -p ~/.ssh && touch ~/.ssh/authorized_keys && \
+    while IFS= read -r line; do grep -qxF \"$line\" ~/.ssh/authorized_keys || printf '%s\\n' \"$line\" >> ~/.ssh/authorized_keys; done";
+
+/// Outcome of pushing the snippet to one host
+pub type PushResult = (String, Result<(), String>);
+
+/// authorized_keys lines for the public halves of the given keys, read from
+/// the `.pub` file next to each private key
+pub fn snippet(keys: &[SshKey]) -> Result<String> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
+    let mut snippet = String::new();
+    for key in keys {
+        let path = match key.path.strip_prefix("~/") {
+            Some(rest) => home.join(rest),
+            None => key.path.clone().into(),
+        };
+        let path = if path.extension().is_some_and(|extension| extension == "pub") {
+            path
+        } else {
+            path.with_file_name(format!("{}.pub", path.file_name().unwrap_or_default().to_string_lossy()))
+        };
+        let public = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read public key of '{}' ({:?})", key.name, path))?;
+        for line in public.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            snippet.push_str(line);
+            snippet.push('\n');
+        }
+    }
+    Ok(snippet)
+}
+
+/// Add the snippet to the host's authorized_keys through the system ssh
+/// binary. Runs non-interactively, so the host must accept a key (or agent
+/// identity) it already trusts.
+async fn push(host: Host, key_path: Option<String>, snippet: String) -> Result<(), String> {
+    let mut command = Command::new("ssh");
+    if let Some(key_path) = &key_path {
+        command.args(["-i", key_path]);
+    }
+    command
+        .args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"])
+        .args(["-p", &host.port.to_string()])
+        .arg(format!("{}@{}", host.user, host.host))
+        .arg(APPEND_SCRIPT)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(snippet.as_bytes()).await.map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(stderr.lines().last().map_or_else(|| output.status.to_string(), |line| line.trim().to_string()))
+}
+
+impl AppState {
+    pub fn open_authorized_keys(&mut self) {
+        if self.config.keys.is_empty() {
+            self.set_message("No SSH keys configured yet".to_string(), MessageType::Info);
+            return;
+        }
+
+        let keys = self.config.keys.iter()
+            .enumerate()
+            .map(|(i, key)| (key.name.clone(), i == self.selected_key))
+            .collect();
+        let hosts = self.config.get_hosts_for_group(0)
+            .into_iter()
+            .map(|host| (host.name, false))
+            .collect();
+        self.modal_state = ModalState::AuthorizedKeys(AuthorizedKeysForm { keys, hosts, cursor: 0 });
+    }
+
+    /// Write the snippet for the chosen keys next to the config and start
+    /// pushing it to the chosen hosts
+    pub fn export_authorized_keys(&mut self, form: AuthorizedKeysForm) {
+        let keys: Vec<SshKey> = self.config.keys.iter()
+            .filter(|key| form.keys.iter().any(|(name, selected)| *selected && name == &key.name))
+            .cloned()
+            .collect();
+        if keys.is_empty() {
+            self.set_message("Select at least one key to export".to_string(), MessageType::Error);
+            return;
+        }
+
+        let snippet = match snippet(&keys) {
+            Ok(snippet) => snippet,
+            Err(e) => {
+                self.set_message(format!("{:#}", e), MessageType::Error);
+                return;
+            }
+        };
+        let path = match Config::config_dir().map(|dir| dir.join("authorized_keys")) {
+            Ok(path) => path,
+            Err(e) => {
+                self.set_message(format!("Could not export keys: {}", e), MessageType::Error);
+                return;
+            }
+        };
+        if let Err(e) = fs::write(&path, &snippet) {
+            self.set_message(format!("Could not write {:?}: {}", path, e), MessageType::Error);
+            return;
+        }
+
+        let hosts: Vec<Host> = self.config.get_hosts_for_group(0)
+            .iter()
+            .filter(|host| form.hosts.iter().any(|(name, selected)| *selected && name == &host.name))
+            .map(|host| self.config.effective_host(host))
+            .collect();
+        if hosts.is_empty() {
+            self.set_message(format!("Wrote {} key(s) to {}", keys.len(), path.display()), MessageType::Success);
+            return;
+        }
+
+        let default_key = self.config.get_default_key().map(|key| key.path.clone());
+        let (sender, receiver) = oneshot::channel();
+        self.key_push = Some(receiver);
+        self.set_message(
+            format!("Wrote {} key(s) to {}; pushing to {} host(s)...", keys.len(), path.display(), hosts.len()),
+            MessageType::Info
+        );
+        tokio::spawn(async move {
+            let pushes = hosts.into_iter().map(|host| {
+                let key_path = host.key_path.clone().or_else(|| default_key.clone());
+                let snippet = snippet.clone();
+                async move {
+                    let name = host.name.clone();
+                    (name, push(host, key_path, snippet).await)
+                }
+            });
+            let results: Vec<PushResult> = futures::future::join_all(pushes).await;
+            let _ = sender.send(results);
+        });
+    }
+
+    /// Report the outcome of a push once every host has answered
+    pub fn poll_key_push(&mut self) {
+        let Some(receiver) = &mut self.key_push else {
+            return;
+        };
+        let Ok(results) = receiver.try_recv() else {
+            return;
+        };
+        self.key_push = None;
+
+        let failed: Vec<String> = results.iter()
+            .filter_map(|(host, result)| result.as_ref().err().map(|error| format!("{} ({})", host, error)))
+            .collect();
+        let pushed = results.len() - failed.len();
+        if failed.is_empty() {
+            self.set_message(format!("Keys pushed to {} host(s)", pushed), MessageType::Success);
+        } else {
+            self.set_message(
+                format!("Keys pushed to {}/{} host(s); failed: {}", pushed, results.len(), failed.join(", ")),
+                MessageType::Error
+            );
+        }
+    }
+}
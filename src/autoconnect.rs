@@ -0,0 +1,79 @@
+use crate::{AppState, MessageType};
+use crate::notify;
+use crate::probe::{Probe, ProbeStatus, TcpPing};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How often hosts with an auto-connect rule are probed
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+impl AppState {
+    /// Probe hosts that have an auto-connect rule and open a session for any
+    /// host that went from unreachable to reachable since the previous check
+    pub async fn poll_autoconnect(&mut self) {
+        let mut results = Vec::new();
+        while let Ok(result) = self.reachability_receiver.try_recv() {
+            results.push(result);
+        }
+
+        for (host_id, reachable) in results {
+            let was_reachable = self.reachability.insert(host_id, reachable);
+            if reachable && was_reachable == Some(false) {
+                self.trigger_autoconnect(host_id).await;
+            }
+        }
+
+        // The placeholder config has no hosts to check yet
+        if self.loading_for().is_some() {
+            return;
+        }
+        if self.last_reachability_check.is_some_and(|checked| checked.elapsed() < CHECK_INTERVAL) {
+            return;
+        }
+        self.last_reachability_check = Some(Instant::now());
+
+        for host in self.config.get_hosts_for_group(0) {
+            if host.autoconnect.is_none() {
+                continue;
+            }
+            let host = self.config.effective_host(&host);
+            let sender = self.reachability_sender.clone();
+            tokio::spawn(async move {
+                let reachable = TcpPing { port: None }.run(&host).await.status == ProbeStatus::Up;
+                let _ = sender.send((host.id, reachable));
+            });
+        }
+    }
+
+    async fn trigger_autoconnect(&mut self, host_id: Uuid) {
+        let Some(host) = self.config.find_host(host_id).cloned() else {
+            return;
+        };
+        let Some(rule) = host.autoconnect.clone() else {
+            return;
+        };
+
+        notify::send(
+            &self.config.settings.notifications,
+            notify::Severity::Info,
+            "Host reachable",
+            &format!("{} is reachable again", host.name),
+        );
+
+        if self.ssh_client.is_connected() || self.ssh_client.is_connecting() {
+            self.set_message(
+                format!("'{}' is reachable again (auto-connect skipped, a session is already open)", host.name),
+                MessageType::Info
+            );
+            return;
+        }
+
+        self.pending_startup_command = if rule.command.trim().is_empty() {
+            None
+        } else {
+            Some(rule.command.clone())
+        };
+        let _ = self.connect_to_host(host.clone()).await;
+        self.set_message(format!("'{}' is reachable again, auto-connecting...", host.name), MessageType::Info);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::AppState;
+use crate::config::{BellMode, Host};
+use crate::notify;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Shortest gap between two bell notifications, so a remote that keeps
+/// ringing does not flood the desktop or webhook
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(10);
+
+impl AppState {
+    fn bell_mode(&self, host: Option<&Host>) -> BellMode {
+        host.and_then(|host| host.bell).unwrap_or(self.config.settings.bell)
+    }
+
+    /// Act on bells rung by the live and background sessions since the last
+    /// tick
+    pub fn poll_bells(&mut self) {
+        if self.terminal_panel.take_bell() {
+            let host = self.ssh_client.get_host().cloned();
+            match self.bell_mode(host.as_ref()) {
+                BellMode::None => self.terminal_panel.clear_bell(),
+                BellMode::Visual => self.terminal_panel.flash(),
+                BellMode::Notification => self.notify_bell(host.as_ref()),
+                BellMode::Sound => ring_terminal_bell(),
+            }
+        }
+
+        // Background sessions are off screen, so their visual bell is the
+        // sidebar marker alone
+        for index in 0..self.background_sessions.len() {
+            if !self.background_sessions[index].panel.take_bell() {
+                continue;
+            }
+            let host = self.background_sessions[index].client.get_host().cloned();
+            match self.bell_mode(host.as_ref()) {
+                BellMode::None => self.background_sessions[index].panel.clear_bell(),
+                BellMode::Visual => {},
+                BellMode::Notification => self.notify_bell(host.as_ref()),
+                BellMode::Sound => ring_terminal_bell(),
+            }
+        }
+    }
+
+    fn notify_bell(&mut self, host: Option<&Host>) {
+        if self.last_bell_notification.is_some_and(|sent| sent.elapsed() < NOTIFY_INTERVAL) {
+            return;
+        }
+        self.last_bell_notification = Some(Instant::now());
+        let host_name = host.map_or("?", |host| host.name.as_str());
+        notify::send(
+            &self.config.settings.notifications,
+            notify::Severity::Warning,
+            "SSH bell",
+            &format!("Bell from {}", host_name),
+        );
+    }
+}
+
+/// Pass a bell on to the terminal sshtui runs in
+fn ring_terminal_bell() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
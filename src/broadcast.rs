@@ -0,0 +1,418 @@
+use crate::{AppState, BroadcastForm, MessageType, ModalState};
+use crate::config_crypto;
+use crate::config::{Config, Host};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use uuid::Uuid;
+
+/// Maximum number of broadcasts kept on disk
+const MAX_ENTRIES: usize = 100;
+
+/// How long a broadcast command may run on one host before its ssh is
+/// killed, so a hung host cannot hold up the broadcast
+pub const HOST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What one host returned for a broadcast command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostResult {
+    /// Nil in broadcasts saved before hosts were told apart by id
+    #[serde(default)]
+    pub host_id: Uuid,
+    pub host_name: String,
+    /// None when ssh itself could not be run or the command was killed
+    pub exit_code: Option<i32>,
+    /// stdout followed by stderr
+    pub output: String,
+}
+
+impl HostResult {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// The host's id, or its name for results saved before hosts had one
+    fn key(&self) -> String {
+        if self.host_id.is_nil() { self.host_name.clone() } else { self.host_id.to_string() }
+    }
+}
+
+/// A command sent to every host of a group, with the outcome on each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Broadcast {
+    pub command: String,
+    pub group: String,
+    /// Host names the command was sent to
+    pub targets: Vec<String>,
+    /// Ids of the same hosts, in the same order; empty in broadcasts saved
+    /// before hosts were told apart by id
+    #[serde(default)]
+    pub target_ids: Vec<Uuid>,
+    pub started_at: DateTime<Local>,
+    pub results: Vec<HostResult>,
+}
+
+impl Broadcast {
+    pub fn summary(&self) -> String {
+        let ok = self.results.iter().filter(|result| result.succeeded()).count();
+        let pending = self.targets.len().saturating_sub(self.results.len());
+        if pending > 0 {
+            format!("{} ok, {} failed, {} pending", ok, self.results.len() - ok, pending)
+        } else {
+            format!("{} ok, {} failed", ok, self.results.len() - ok)
+        }
+    }
+
+    /// Result of target `index`
+    pub fn result_for(&self, index: usize) -> Option<&HostResult> {
+        let name = self.targets.get(index)?;
+        match self.target_ids.get(index) {
+            Some(id) if !id.is_nil() => self.results.iter().find(|result| result.host_id == *id),
+            _ => self.results.iter().find(|result| &result.host_name == name),
+        }
+    }
+
+    /// Whether `host` was one of the targets
+    pub fn targeted(&self, host: &Host) -> bool {
+        if self.target_ids.is_empty() {
+            self.targets.contains(&host.name)
+        } else {
+            self.target_ids.contains(&host.id)
+        }
+    }
+
+    /// Targets as (key, name), keyed by id where one was saved
+    fn target_keys(&self) -> Vec<(String, &String)> {
+        self.targets.iter()
+            .enumerate()
+            .map(|(i, name)| match self.target_ids.get(i) {
+                Some(id) if !id.is_nil() => (id.to_string(), name),
+                _ => (name.clone(), name),
+            })
+            .collect()
+    }
+}
+
+/// Hosts picked some other way than by group, such as a facts search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetSet {
+    /// Shown in place of the group name
+    pub label: String,
+    pub hosts: Vec<Uuid>,
+}
+
+/// A broadcast whose results are still coming in, filling its history entry
+pub struct BroadcastRun {
+    index: usize,
+    receiver: mpsc::UnboundedReceiver<HostResult>,
+}
+
+/// How a broadcast differs from the previous run of the same command on
+/// the same group
+#[derive(Debug, Default)]
+pub struct BroadcastDiff {
+    /// Targeted now but not last time
+    pub added: Vec<String>,
+    /// Targeted last time but not now
+    pub removed: Vec<String>,
+    /// Targeted both times with a different exit code or output
+    pub changed: Vec<String>,
+}
+
+impl BroadcastDiff {
+    pub fn between(previous: &Broadcast, current: &Broadcast) -> Self {
+        let mut diff = Self::default();
+        let current_keys = current.target_keys();
+        let previous_keys = previous.target_keys();
+        for (key, host_name) in &current_keys {
+            if !previous_keys.iter().any(|(previous, _)| previous == key) {
+                diff.added.push((*host_name).clone());
+            }
+        }
+        for (key, host_name) in &previous_keys {
+            if !current_keys.iter().any(|(current, _)| current == key) {
+                diff.removed.push((*host_name).clone());
+            }
+        }
+        for result in &current.results {
+            let before = previous.results.iter().find(|before| before.key() == result.key());
+            if before.is_some_and(|before| before.exit_code != result.exit_code || before.output != result.output) {
+                diff.changed.push(result.host_name.clone());
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Broadcasts that were run, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BroadcastHistory {
+    pub entries: Vec<Broadcast>,
+}
+
+impl BroadcastHistory {
+    pub fn load() -> Result<Self> {
+        let path = Self::history_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read broadcast history: {:?}", path))?;
+        let contents = config_crypto::unseal(contents)?;
+
+        serde_json::from_str(&contents)
+            .with_context(|| "Failed to parse broadcast history JSON")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::history_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize broadcast history")?;
+        config_crypto::write_sealed(&path, contents)
+            .with_context(|| format!("Failed to write broadcast history: {:?}", path))?;
+
+        Ok(())
+    }
+
+    fn history_path() -> Result<PathBuf> {
+        Ok(Config::config_dir()?.join("broadcasts.json"))
+    }
+
+    pub fn record(&mut self, broadcast: Broadcast) {
+        self.entries.push(broadcast);
+
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+
+    /// The run of the same command on the same group before entry `index`
+    pub fn previous_run(&self, index: usize) -> Option<&Broadcast> {
+        let current = self.entries.get(index)?;
+        self.entries[..index].iter()
+            .rev()
+            .find(|entry| entry.command == current.command && entry.group == current.group)
+    }
+
+    pub fn diff(&self, index: usize) -> Option<BroadcastDiff> {
+        Some(BroadcastDiff::between(self.previous_run(index)?, self.entries.get(index)?))
+    }
+}
+
+/// Run the command on one host through the system ssh binary, without a
+/// terminal and without prompting; ssh is killed after `timeout`
+pub async fn run_on_host(host: Host, key_path: Option<String>, options: Vec<String>, command: String, timeout: Duration) -> HostResult {
+    let mut ssh = Command::new("ssh");
+    // ssh keeps the first value given for an option: the host's, then the group's
+    ssh.args(host.ssh_option_args());
+    if let Some(key_path) = &key_path {
+        ssh.args(["-i", key_path]);
+    }
+    for option in &options {
+        ssh.args(["-o", option]);
+    }
+    ssh.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+    ssh.args(["-p", &host.port.to_string()])
+        .arg(format!("{}@{}", host.user, host.host))
+        .arg(&command)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    match tokio::time::timeout(timeout, ssh.output()).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            HostResult { host_id: host.id, host_name: host.name, exit_code: output.status.code(), output: text }
+        },
+        Ok(Err(e)) => HostResult { host_id: host.id, host_name: host.name, exit_code: None, output: format!("Could not run ssh: {}", e) },
+        Err(_) => HostResult {
+            host_id: host.id,
+            host_name: host.name,
+            exit_code: None,
+            output: format!("Timed out after {} seconds; ssh was stopped", timeout.as_secs()),
+        },
+    }
+}
+
+/// Run `command` on `host` with its group's ssh options and its own key,
+/// or the default key, for at most `HOST_TIMEOUT`
+pub fn run_with_config(config: &Config, host: &Host, command: String) -> impl Future<Output = HostResult> {
+    let options = config.group_of(host)
+        .map(|group| group.defaults.options.clone())
+        .unwrap_or_default();
+    let host = config.effective_host(host);
+    let key_path = host.key_path.clone()
+        .or_else(|| config.get_default_key().map(|key| key.path.clone()));
+    run_on_host(host, key_path, options, command, HOST_TIMEOUT)
+}
+
+impl AppState {
+    pub fn open_broadcasts(&mut self) {
+        // From Favorites or Recent, run on the hosts listed there rather than "All"
+        let targets = self.virtual_group.map(|view| TargetSet {
+            label: view.name().to_string(),
+            hosts: self.virtual_group_hosts(view).into_iter().map(|host| host.id).collect(),
+        });
+        self.modal_state = ModalState::Broadcast(BroadcastForm {
+            command: String::new(),
+            cursor: 0,
+            targets,
+            viewing: None,
+            host: 0,
+            scroll: 0,
+        });
+    }
+
+    /// Whether history entry `index` is still collecting results
+    pub fn broadcast_running(&self, index: usize) -> bool {
+        self.broadcast_run.as_ref().is_some_and(|run| run.index == index)
+    }
+
+    /// Hosts of a group as it is configured now
+    fn group_hosts(&self, group: &str) -> Option<Vec<Host>> {
+        let index = self.config.groups.iter().position(|g| g.name == group)?;
+        Some(self.config.get_hosts_for_group(index))
+    }
+
+    pub fn run_command_on(&self, host: &Host, command: String) -> impl Future<Output = HostResult> {
+        run_with_config(&self.config, host, command)
+    }
+
+    /// Send a new command to every host of the selected group, or to the
+    /// hosts of a target set
+    pub fn start_broadcast(&mut self, command: &str, targets: Option<TargetSet>) {
+        if let Some(targets) = targets {
+            let hosts = self.config.get_hosts_for_group(0)
+                .into_iter()
+                .filter(|host| targets.hosts.contains(&host.id))
+                .collect();
+            self.run_broadcast(command.to_string(), targets.label, hosts);
+            return;
+        }
+        let Some(group) = self.config.groups.get(self.selected_group).map(|group| group.name.clone()) else {
+            return;
+        };
+        let targets = self.group_hosts(&group).unwrap_or_default();
+        self.run_broadcast(command.to_string(), group, targets);
+    }
+
+    /// Run a past broadcast again, on the hosts it went to (`same_targets`)
+    /// or on its group's current members
+    pub fn replay_broadcast(&mut self, index: usize, same_targets: bool) {
+        let Some(entry) = self.broadcasts.entries.get(index).cloned() else {
+            return;
+        };
+        let targets = if same_targets {
+            self.config.get_hosts_for_group(0)
+                .into_iter()
+                .filter(|host| entry.targeted(host))
+                .collect()
+        } else {
+            match self.group_hosts(&entry.group) {
+                Some(hosts) => hosts,
+                None => {
+                    self.set_message(format!("Group '{}' no longer exists", entry.group), MessageType::Error);
+                    return;
+                }
+            }
+        };
+        self.run_broadcast(entry.command, entry.group, targets);
+    }
+
+    fn run_broadcast(&mut self, command: String, group: String, hosts: Vec<Host>) {
+        if self.broadcast_run.is_some() {
+            self.set_message("A broadcast is already running".to_string(), MessageType::Error);
+            return;
+        }
+        if command.trim().is_empty() {
+            self.set_message("Enter a command to broadcast".to_string(), MessageType::Error);
+            return;
+        }
+        if hosts.is_empty() {
+            self.set_message(format!("Group '{}' has no hosts to broadcast to", group), MessageType::Error);
+            return;
+        }
+
+        let runs: Vec<_> = hosts.iter()
+            .map(|host| self.run_command_on(host, command.clone()))
+            .collect();
+        let target_ids: Vec<Uuid> = hosts.iter().map(|host| host.id).collect();
+        let targets: Vec<String> = hosts.into_iter().map(|host| host.name).collect();
+        let parallelism = self.config.settings.broadcast_parallelism.max(1);
+
+        // The entry fills in as hosts answer and is saved once all have
+        self.set_message(
+            format!("Running '{}' on {} host(s) of {}, {} at a time...", command, targets.len(), group, parallelism),
+            MessageType::Info
+        );
+        self.broadcasts.record(Broadcast { command, group, targets, target_ids, started_at: Local::now(), results: Vec::new() });
+        let index = self.broadcasts.entries.len() - 1;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.broadcast_run = Some(BroadcastRun { index, receiver });
+        self.modal_state = ModalState::Broadcast(BroadcastForm {
+            command: String::new(),
+            cursor: 1,
+            targets: None,
+            viewing: Some(index),
+            host: 0,
+            scroll: 0,
+        });
+
+        tokio::spawn(async move {
+            let mut results = futures::stream::iter(runs).buffer_unordered(parallelism);
+            while let Some(result) = results.next().await {
+                if sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Collect results of the running broadcast and save it once done
+    pub fn poll_broadcast(&mut self) {
+        let Some(run) = &mut self.broadcast_run else {
+            return;
+        };
+        loop {
+            match run.receiver.try_recv() {
+                Ok(result) => {
+                    if let Some(entry) = self.broadcasts.entries.get_mut(run.index) {
+                        entry.results.push(result);
+                    }
+                },
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        let index = run.index;
+        self.broadcast_run = None;
+
+        let summary = self.broadcasts.entries.get(index).map(|entry| entry.summary()).unwrap_or_default();
+        if let Err(e) = self.broadcasts.save() {
+            self.set_message(format!("Broadcast finished ({}) but could not be saved: {}", summary, e), MessageType::Error);
+        } else {
+            self.set_message(format!("Broadcast finished: {}", summary), MessageType::Success);
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use crate::ansible::{self, ExportFormat};
+use crate::broadcast::{self, BroadcastHistory};
+use crate::config::{self, Config, ConnectionKind, Ec2Source, Group, Host, Trashed};
+use crate::config_crypto;
+use crate::ec2;
+use crate::facts::FactsIndex;
+use crate::history::History;
+use crate::hostkeys::PinnedKeys;
+use crate::import::{self, ImportFormat};
+use crate::passphrase;
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand};
+use futures::StreamExt;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Terminal SSH manager. Without a command the TUI opens as usual.
+#[derive(Debug, Parser)]
+#[command(name = "sshtui", version, about)]
+pub struct Cli {
+    /// Profile to use, such as "work" for ~/.config/sshtui/profiles/work.json;
+    /// without it the TUI asks when there are several
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open the TUI already connected to a host
+    Connect {
+        /// Host name as shown in the sidebar
+        host: String,
+    },
+    /// Print the configured hosts and their groups
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Add a host to a group without opening the TUI
+    AddHost {
+        /// Group the host is added to
+        #[arg(long, short)]
+        group: String,
+        /// Name shown in the sidebar
+        #[arg(long, short)]
+        name: String,
+        /// Address or hostname to connect to
+        #[arg(long = "host", short = 'H')]
+        address: String,
+        /// User name; inherited from the group when omitted
+        #[arg(long, short)]
+        user: Option<String>,
+        /// Port; inherited from the group when omitted
+        #[arg(long, short)]
+        port: Option<u16>,
+        /// Private key path; the group's or the default key when omitted
+        #[arg(long, short)]
+        key: Option<String>,
+        /// Tag to attach, may be repeated
+        #[arg(long = "tag", short)]
+        tags: Vec<String>,
+    },
+    /// Run a command on every host of a group over ssh and print what each
+    /// one returned, prefixed with its name
+    Exec {
+        /// Group whose hosts run the command; "All" for every host
+        #[arg(long, short)]
+        group: String,
+        /// Command to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Write the groups and hosts in another tool's format
+    Export {
+        /// `ansible` for an INI inventory, `ansible-yaml` for YAML
+        #[arg(long, short, value_enum)]
+        format: ExportFormat,
+        /// File to write; standard output when omitted
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Add the groups and hosts another tool keeps, after showing what
+    /// would be added
+    Import {
+        /// `ansible` (INI or YAML), `termius` (CSV), `putty` (.reg export or
+        /// ~/.putty/sessions) or `securecrt` (XML)
+        #[arg(long, short, value_enum, default_value = "ansible")]
+        format: ImportFormat,
+        /// File to read
+        file: PathBuf,
+        /// Merge without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Fill a group with the running EC2 instances of a region, creating
+    /// the group if needed; run again, or refresh from the action palette,
+    /// as instances come and go. Needs a build with `--features aws`.
+    Ec2 {
+        /// Group the instances go in, such as "aws/prod"
+        #[arg(long, short)]
+        group: String,
+        /// Region to look in; without it the group is refreshed as set up before
+        #[arg(long, short)]
+        region: Option<String>,
+        /// Only instances with this tag, "Key=Value" or just "Key"; may be repeated
+        #[arg(long = "tag", short)]
+        tags: Vec<String>,
+        /// Connect to private addresses rather than public ones
+        #[arg(long)]
+        private_ip: bool,
+        /// Profile from ~/.aws/config
+        #[arg(long)]
+        aws_profile: Option<String>,
+        /// Empty the group without asking when no instances are found
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Encrypt the config file with a passphrase asked for at every start
+    EncryptConfig,
+    /// Store the config file as plain JSON again
+    DecryptConfig,
+}
+
+/// One line of `sshtui list`, with group defaults filled in
+#[derive(Serialize)]
+struct HostRow {
+    group: String,
+    name: String,
+    host: String,
+    user: String,
+    port: u16,
+    key_path: Option<String>,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "ConnectionKind::is_ssh")]
+    kind: ConnectionKind,
+    #[serde(skip)]
+    address: String,
+}
+
+/// Look up a configured host by name for `sshtui connect`
+pub fn find_host(config: &Config, name: &str) -> Result<Host> {
+    config.get_hosts_for_group(0)
+        .into_iter()
+        .find(|host| host.name == name)
+        .ok_or_else(|| anyhow!("No host named '{}' (see `sshtui list`)", name))
+}
+
+/// Run a command that does not need the TUI
+pub async fn run(command: Command) -> Result<()> {
+    let mut config = Config::load()?;
+    match command {
+        Command::Connect { .. } => unreachable!("connect is handled by the TUI"),
+        Command::List { json } => list(&config, json),
+        Command::Exec { group, command } => exec(&config, &group, &command.join(" ")).await,
+        Command::AddHost { group, name, address, user, port, key, tags } => {
+            let host = Host {
+                user: user.unwrap_or_default().trim().to_string(),
+                port: port.unwrap_or(0),
+                key_path: key,
+                tags,
+                ..Host::new(name.trim(), address.trim())
+            };
+            add_host(&mut config, &group, host)?;
+            config.save()?;
+            println!("Added host '{}' to group '{}'", name, group);
+            Ok(())
+        },
+        Command::Export { format, output } => {
+            let exported = ansible::export(&config, format).map_err(|e| anyhow!("Could not export: {}", e))?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, exported)?;
+                    eprintln!("Wrote {}", path.display());
+                },
+                None => print!("{}", exported),
+            }
+            Ok(())
+        },
+        Command::Import { format, file, yes } => {
+            let path = file.to_string_lossy();
+            let groups = import::read(format, &path).map_err(|e| anyhow!(e))?;
+            let plan = import::plan(&config, &path, groups);
+            for line in &plan.lines {
+                println!("{}", line);
+            }
+            if plan.is_empty() {
+                println!("Nothing to import: every host is already in the config");
+                return Ok(());
+            }
+            if !yes && !confirm("Add these to the config?")? {
+                println!("Nothing imported");
+                return Ok(());
+            }
+            let summary = plan.summary();
+            import::merge(&mut config, plan);
+            config.save()?;
+            println!("Imported {}", summary);
+            Ok(())
+        },
+        Command::Ec2 { group, region, tags, private_ip, aws_profile, yes } => {
+            let group = config::group_name(&group);
+            if group.is_empty() || group == "All" {
+                bail!("Give the group the instances go in, other than All");
+            }
+            let source = match region {
+                Some(region) => Ec2Source { region, tags, private_ip, aws_profile },
+                None => config.groups.iter()
+                    .find(|existing| existing.name == group)
+                    .and_then(|existing| existing.ec2.clone())
+                    .ok_or_else(|| anyhow!("Group '{}' is not filled from EC2 yet; give the --region", group))?,
+            };
+            let instances = ec2::describe(&source).await.map_err(|e| anyhow!("Could not look up EC2 instances: {}", e))?;
+
+            if !config.group_name_taken(&group) {
+                config.add_group(Group::new(group.clone()));
+                config.ensure_parent_groups();
+            }
+            let target = config.groups.iter_mut()
+                .find(|existing| existing.name == group)
+                .ok_or_else(|| anyhow!("No group named '{}'", group))?;
+            if ec2::empties_group(target, &instances) && !yes
+                && !confirm(&format!("EC2 returned no instances; move all of the EC2 hosts of '{}' to the trash?", group))? {
+                println!("Group '{}' left as it was", group);
+                return Ok(());
+            }
+            target.ec2 = Some(source.clone());
+            let summary = ec2::update_group(target, &source, instances);
+            let description = summary.describe();
+            for host in summary.gone {
+                config.add_to_trash(Trashed::Host { group: group.clone(), host: Box::new(host) });
+            }
+            config.save()?;
+            println!("Group '{}' refreshed from EC2 {}: {}", group, source.region, description);
+            Ok(())
+        },
+        Command::EncryptConfig => {
+            if config_crypto::is_enabled() {
+                bail!("The config is already encrypted");
+            }
+            let passphrase = passphrase::prompt("New config passphrase")?;
+            if passphrase.is_empty() {
+                bail!("The passphrase cannot be empty");
+            }
+            if passphrase::prompt("Repeat the passphrase")? != passphrase {
+                bail!("The passphrases do not match");
+            }
+            reseal_stores(|| {
+                config_crypto::enable(&passphrase)?;
+                config.save()?;
+                Ok(())
+            })?;
+            println!("Config encrypted; sshtui will ask for the passphrase when it starts");
+            Ok(())
+        },
+        Command::DecryptConfig => {
+            if !config_crypto::is_enabled() {
+                bail!("The config is not encrypted");
+            }
+            reseal_stores(|| {
+                config_crypto::disable();
+                config.save()?;
+                Ok(())
+            })?;
+            println!("Config stored as plain JSON again");
+            Ok(())
+        },
+    }
+}
+
+/// Read the history, facts, broadcasts and pinned host keys, run `change`
+/// to the encryption and write them back, so they are encrypted exactly
+/// when the config is. One that cannot be read is left as it is.
+fn reseal_stores(change: impl FnOnce() -> Result<()>) -> Result<()> {
+    fn read<T>(what: &str, loaded: Result<T>) -> Option<T> {
+        loaded.map_err(|e| eprintln!("Leaving the {} as it is: {:#}", what, e)).ok()
+    }
+    let history = read("connection history", History::load());
+    let facts = read("facts index", FactsIndex::load());
+    let broadcasts = read("broadcast history", BroadcastHistory::load());
+    let host_keys = read("pinned host keys", PinnedKeys::load());
+    change()?;
+    if let Some(history) = history {
+        history.save()?;
+    }
+    if let Some(facts) = facts {
+        facts.save()?;
+    }
+    if let Some(broadcasts) = broadcasts {
+        broadcasts.save()?;
+    }
+    if let Some(host_keys) = host_keys {
+        host_keys.save()?;
+    }
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal; anything but y is no
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn list(config: &Config, json: bool) -> Result<()> {
+    let rows: Vec<HostRow> = config.groups.iter()
+        .skip(1)
+        .flat_map(|group| group.hosts.iter().map(move |host| (group, host)))
+        .map(|(group, host)| {
+            let host = config.effective_host(host);
+            let address = host.address();
+            HostRow {
+                group: group.name.clone(),
+                name: host.name,
+                host: host.host,
+                user: host.user,
+                port: host.port,
+                key_path: host.key_path,
+                tags: host.tags,
+                kind: host.kind,
+                address,
+            }
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    let header = ["GROUP", "NAME", "ADDRESS", "TAGS"].map(String::from);
+    let lines: Vec<[String; 4]> = rows.iter()
+        .map(|row| [
+            row.group.clone(),
+            row.name.clone(),
+            row.address.clone(),
+            row.tags.join(","),
+        ])
+        .collect();
+    let mut widths = header.clone().map(|cell| cell.chars().count());
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for line in std::iter::once(&header).chain(&lines) {
+        let cells: Vec<String> = line.iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+    Ok(())
+}
+
+/// Run `command` on the group's hosts as the broadcast panel does, the
+/// broadcast parallelism setting at a time. Each host's output is printed
+/// once it is done, every line prefixed with the host's name and followed
+/// by its exit status; the run fails when any host did.
+async fn exec(config: &Config, group: &str, command: &str) -> Result<()> {
+    if command.trim().is_empty() {
+        bail!("Give the command to run after --");
+    }
+    let index = config.groups.iter()
+        .position(|g| g.name == group)
+        .ok_or_else(|| anyhow!("No group named '{}'", group))?;
+    let (hosts, skipped): (Vec<Host>, Vec<Host>) = config.get_hosts_for_group(index)
+        .into_iter()
+        .partition(|host| host.kind.uses_ssh());
+    for host in &skipped {
+        eprintln!("{}: skipped, it is not reached over ssh", host.name);
+    }
+    if hosts.is_empty() {
+        bail!("Group '{}' has no hosts reached over ssh", group);
+    }
+
+    let width = hosts.iter().map(|host| host.name.chars().count()).max().unwrap_or(0);
+    let runs: Vec<_> = hosts.iter()
+        .map(|host| broadcast::run_with_config(config, host, command.to_string()))
+        .collect();
+    let mut results = futures::stream::iter(runs).buffer_unordered(config.settings.broadcast_parallelism.max(1));
+    let mut failed = 0;
+    while let Some(result) = results.next().await {
+        for line in result.output.lines() {
+            println!("{:<width$} | {}", result.host_name, line, width = width);
+        }
+        let status = result.exit_code.map_or_else(|| "no exit status".to_string(), |code| format!("exit {}", code));
+        println!("{:<width$} | [{}]", result.host_name, status, width = width);
+        if !result.succeeded() {
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} host(s) failed", failed, hosts.len());
+    }
+    Ok(())
+}
+
+fn add_host(config: &mut Config, group_name: &str, host: Host) -> Result<()> {
+    if host.name.is_empty() {
+        bail!("Host name cannot be empty");
+    }
+    if host.host.is_empty() {
+        bail!("Host address cannot be empty");
+    }
+    if config.get_hosts_for_group(0).iter().any(|existing| existing.name == host.name) {
+        bail!("A host named '{}' already exists", host.name);
+    }
+    let group_user = config.groups.iter()
+        .find(|group| group.name == group_name)
+        .and_then(|group| group.defaults.user.as_ref());
+    if host.user.is_empty() && group_user.is_none() {
+        bail!("Username cannot be empty (the group has no default user)");
+    }
+    config.add_host_to_group(group_name, host)?;
+    Ok(())
+}
@@ -1,38 +1,949 @@
+use crate::config_crypto;
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
-use anyhow::{Result, Context};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use crate::error::{ConfigContext, Error, Result};
+use chrono::{DateTime, Local, NaiveTime};
+use std::fmt;
+use uuid::Uuid;
+
+/// Profile kept in config.json; the others are files under profiles/
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Profile whose file is read and saved
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub groups: Vec<Group>,
     pub keys: Vec<SshKey>,
+    #[serde(default)]
+    pub settings: Settings,
+    /// Deleted hosts and groups, newest first, until they are restored or
+    /// kept longer than `settings.trash_days`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trash: Vec<TrashedItem>,
+}
+
+/// A deleted host or group, as it was when deleted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedItem {
+    pub deleted_at: DateTime<Local>,
+    pub item: Trashed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Trashed {
+    /// With the name of the group it was in
+    Host { group: String, host: Box<Host> },
+    /// With the hosts it had
+    Group { group: Box<Group> },
+}
+
+impl TrashedItem {
+    pub fn describe(&self) -> String {
+        match &self.item {
+            Trashed::Host { group, host } => format!("host '{}' from group '{}'", host.name, group),
+            Trashed::Group { group } => format!("group '{}' with {} host(s)", group.name, group.hosts.len()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Group {
+    /// Identifies it for edits and deletes while its name changes; configs
+    /// written before ids get new ones when loaded
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
     pub name: String,
     pub color: String,
     pub hosts: Vec<Host>,
+    #[serde(default, skip_serializing_if = "GroupDefaults::is_empty")]
+    pub defaults: GroupDefaults,
+    /// Free text shown with each of the group's hosts, such as a reboot window
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// The EC2 instances the group's hosts are discovered from, when they
+    /// come from AWS rather than being added by hand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ec2: Option<Ec2Source>,
+}
+
+/// Separates the levels of a nested group's name, as in "clients/acme/prod"
+pub const GROUP_SEPARATOR: char = '/';
+
+impl Group {
+    /// An empty group with nothing set but its name
+    pub fn new(name: impl Into<String>) -> Self {
+        Group {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            color: "green".to_string(),
+            hosts: Vec::new(),
+            defaults: GroupDefaults::default(),
+            notes: String::new(),
+            ec2: None,
+        }
+    }
+
+    /// Name of the group this one is nested in, if any
+    pub fn parent_name(&self) -> Option<&str> {
+        self.name.rsplit_once(GROUP_SEPARATOR).map(|(parent, _)| parent)
+    }
+
+    /// Last level of the name, as shown under the parent group
+    pub fn short_name(&self) -> &str {
+        self.name.rsplit_once(GROUP_SEPARATOR).map_or(self.name.as_str(), |(_, name)| name)
+    }
+
+    /// Whether this is the group named `ancestor` or one nested below it
+    pub fn is_within(&self, ancestor: &str) -> bool {
+        self.name.strip_prefix(ancestor)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(GROUP_SEPARATOR))
+    }
+}
+
+/// A group name as typed, with blank levels and spaces around the
+/// separators dropped: " clients/ acme//prod " is "clients/acme/prod"
+pub fn group_name(typed: &str) -> String {
+    typed.split(GROUP_SEPARATOR)
+        .map(str::trim)
+        .filter(|level| !level.is_empty())
+        .collect::<Vec<_>>()
+        .join(&GROUP_SEPARATOR.to_string())
+}
+
+/// Arguments as typed, split on spaces outside quotes:
+/// `-o "ProxyCommand=ssh -W %h:%p bastion"` is two arguments
+pub fn split_arguments(typed: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    for c in typed.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => arg.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            },
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            },
+            None => {
+                arg.push(c);
+                in_arg = true;
+            },
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+/// Arguments joined the way `split_arguments` reads them back
+pub fn join_arguments(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| if arg.is_empty() || arg.contains(char::is_whitespace) || arg.contains(['"', '\'']) {
+            let quote = if arg.contains('"') { '\'' } else { '"' };
+            format!("{}{}{}", quote, arg, quote)
+        } else {
+            arg.clone()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Connection settings the hosts of a group inherit unless they set their own
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<String>,
+    /// Extra `-o` options for the system ssh binary, e.g. "ProxyJump=bastion"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+}
+
+impl GroupDefaults {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Which EC2 instances fill a group, refreshed on demand
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Ec2Source {
+    /// Such as "eu-west-1"
+    pub region: String,
+    /// "Key=Value" for instances with that tag, or "Key" for instances
+    /// that have the tag at all; every one must match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Connect to private addresses, e.g. over a VPN; public ones are used
+    /// otherwise, and private ones for instances without
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private_ip: bool,
+    /// Profile from ~/.aws/config; the default credentials without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aws_profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Host {
+    /// Identifies it for edits and deletes while its name changes; configs
+    /// written before ids get new ones when loaded
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
     pub name: String,
     pub host: String,
+    /// Empty to use the group's default user
+    #[serde(default)]
     pub user: String,
+    /// 0 to use the group's default port (22 without one)
+    #[serde(default)]
     pub port: u16,
+    /// None to use the group's default key, or else the default key
     pub key_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoconnect: Option<AutoConnectRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Overrides the global SSH backend for this host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<SshBackend>,
+    /// Tunnels opened with every session to this host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forwards: Vec<PortForward>,
+    /// Bytes typed into the session while it is idle, for NATs that drop
+    /// quiet connections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<KeepAlive>,
+    /// When set, free typing is disabled and only these commands can be
+    /// sent to the host's sessions. A guard against mistakes in sshtui, not
+    /// a restriction the server enforces
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_commands: Vec<String>,
+    /// Overrides the global bell behaviour for this host's sessions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bell: Option<BellMode>,
+    /// Run in place of the login shell, e.g. `tmux new -A -s main`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_command: Option<String>,
+    /// Typed into every new session once it is open, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub startup_commands: Vec<String>,
+    /// Status checks shown as badges in the host list
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub probes: Vec<ProbeConfig>,
+    /// Hosts whose sessions must be up first when connecting a whole group,
+    /// e.g. a bastion or a host carrying a tunnel, by id
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<Uuid>,
+    /// What the host's sessions run: ssh unless set
+    #[serde(default, skip_serializing_if = "ConnectionKind::is_ssh")]
+    pub kind: ConnectionKind,
+    /// Extra arguments for ssh, one per entry, such as `-4`, `-o` and
+    /// `ProxyCommand=ssh -W %h:%p bastion`; given before the group's options
+    /// and sshtui's own, so that they win
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ssh_options: Vec<String>,
+    /// Cap in KB/s on session output and scp copies, for links such as LTE
+    /// routers that a bulk transfer would saturate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth_limit: Option<u32>,
+    /// Free text shown in the host's detail card, such as where its root
+    /// password is kept; may span several lines
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// Starred: listed under Favorites in the Groups panel and on the
+    /// dashboard
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    /// The EC2 instance the host was discovered as; refreshes of its group
+    /// match it by this, so its name and settings can be changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ec2_instance: Option<String>,
+    /// TERM sessions report instead of xterm-256color, e.g. `vt100` for
+    /// appliances that break with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub term: Option<String>,
+    /// LANG and LC_ALL for sessions, such as `C` or `en_US.UTF-8`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(default, skip_serializing_if = "TerminalSize::is_fit")]
+    pub terminal_size: TerminalSize,
+}
+
+impl Host {
+    /// A plain ssh host with everything else left to its group's defaults
+    pub fn new(name: impl Into<String>, address: impl Into<String>) -> Self {
+        Host {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            host: address.into(),
+            user: String::new(),
+            port: 0,
+            key_path: None,
+            autoconnect: None,
+            tags: Vec::new(),
+            backend: None,
+            forwards: Vec::new(),
+            keepalive: None,
+            allowed_commands: Vec::new(),
+            bell: None,
+            remote_command: None,
+            startup_commands: Vec::new(),
+            probes: Vec::new(),
+            depends_on: Vec::new(),
+            kind: ConnectionKind::Ssh,
+            ssh_options: Vec::new(),
+            bandwidth_limit: None,
+            notes: String::new(),
+            favorite: false,
+            ec2_instance: None,
+            term: None,
+            locale: None,
+            terminal_size: TerminalSize::Fit,
+        }
+    }
+
+    /// Where sessions go, as shown in host lists
+    pub fn address(&self) -> String {
+        match &self.kind {
+            ConnectionKind::Ssh => format!("{}@{}:{}", self.user, self.host, self.port),
+            ConnectionKind::Mosh => format!("mosh {}@{}", self.user, self.host),
+            ConnectionKind::Telnet => format!("telnet {}:{}", self.host, self.port),
+            ConnectionKind::Custom(command) => format!("$ {}", command),
+        }
+    }
+
+    /// `ssh_options` as ssh arguments
+    pub fn ssh_option_args(&self) -> Vec<String> {
+        self.ssh_options.clone()
+    }
+
+    /// `ssh_options` as scp takes them: `-o` options and the flags the two
+    /// share are kept, ssh's `-p PORT` becomes `-P PORT`, and the rest, such
+    /// as forwards, are left out
+    pub fn scp_option_args(&self) -> Vec<String> {
+        // Flags of ssh that take a value, so that a dropped flag drops it too
+        const SSH_VALUE_FLAGS: &str = "BDEFIJLOPQRSWbceilmopw";
+        const SHARED_FLAGS: &str = "46ACqv";
+        const SHARED_VALUE_FLAGS: &str = "FJcio";
+
+        let mut args = Vec::new();
+        let mut options = self.ssh_options.iter();
+        while let Some(option) = options.next() {
+            let mut chars = option.chars();
+            let flag = match (chars.next(), chars.next()) {
+                (Some('-'), Some(flag)) => flag,
+                _ => continue,
+            };
+            let glued = chars.as_str();
+            let takes_value = SSH_VALUE_FLAGS.contains(flag);
+            let value = if takes_value && glued.is_empty() {
+                options.next().cloned()
+            } else {
+                None
+            };
+            let scp_flag = if flag == 'p' {
+                'P'
+            } else if SHARED_VALUE_FLAGS.contains(flag) || (!takes_value && SHARED_FLAGS.contains(flag) && glued.is_empty()) {
+                flag
+            } else {
+                warn!("scp to {} leaves out ssh argument {}", self.name, option);
+                continue;
+            };
+            args.push(format!("-{}{}", scp_flag, glued));
+            args.extend(value);
+        }
+        args
+    }
+
+    /// Whether the host carries every one of the given tags
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+}
+
+/// A status check run against a host; `name` replaces the default badge
+/// label
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ProbeConfig {
+    /// TCP connect to the SSH port, or to `port`
+    TcpPing {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+    },
+    /// Command run on the host over ssh; up when it exits 0
+    SshExec {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        command: String,
+    },
+    /// HTTP GET of `url` (`{host}` is replaced by the address); up on
+    /// `status`, or on any non-error status without one
+    HttpCheck {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<u16>,
+    },
+    /// Local shell command with SSHTUI_HOST/SSHTUI_PORT/SSHTUI_USER set;
+    /// exit 0 is up, 1 degraded, anything else down
+    Command {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        command: String,
+    },
+}
+
+/// Open a session automatically when a host comes back online
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoConnectRule {
+    /// Command typed into the session once it is open (may be empty)
+    #[serde(default)]
+    pub command: String,
+}
+
+/// Keep-alive for links that drop idle connections: ssh's own keep-alive
+/// messages at the interval, or bytes the user chose sent through the session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeepAlive {
+    /// Seconds without traffic before the keep-alive is sent
+    pub interval_secs: u64,
+    /// Sent through the session as typed, so the remote program reads it;
+    /// `\0`, `\e` and `\xNN` escapes are decoded. Empty leaves the
+    /// keep-alive to ssh, which the remote side never sees
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub payload: String,
+}
+
+impl KeepAlive {
+    /// Whether the payload goes through the session rather than ssh's own
+    /// keep-alive messages
+    pub fn through_session(&self) -> bool {
+        !self.payload.is_empty()
+    }
+
+    /// Payload with its escapes decoded
+    pub fn payload_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut chars = self.payload.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            match chars.next() {
+                Some('0') => bytes.push(0),
+                Some('e') => bytes.push(0x1b),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    match u8::from_str_radix(&hex, 16) {
+                        Ok(byte) if hex.len() == 2 && hex.chars().all(|c| c.is_ascii_hexdigit()) => bytes.push(byte),
+                        _ => bytes.extend_from_slice(format!("\\x{}", hex).as_bytes()),
+                    }
+                },
+                Some(other) => {
+                    bytes.push(b'\\');
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+                },
+                None => bytes.push(b'\\'),
+            }
+        }
+        bytes
+    }
+}
+
+/// Kind of tunnel, mirroring ssh's -L, -R and -D flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardKind {
+    Local,
+    Remote,
+    Dynamic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortForward {
+    pub kind: ForwardKind,
+    /// Port listened on: locally for local and dynamic forwards, on the
+    /// server for remote ones
+    pub bind_port: u16,
+    /// "host:port" connections are forwarded to; unused for dynamic forwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl PortForward {
+    /// Parse ssh-style specs such as "-L 8080:db:5432", "R9000:localhost:3000" or "-D 1080"
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim().trim_start_matches('-');
+        let mut chars = spec.chars();
+        let kind = match chars.next()?.to_ascii_uppercase() {
+            'L' => ForwardKind::Local,
+            'R' => ForwardKind::Remote,
+            'D' => ForwardKind::Dynamic,
+            _ => return None,
+        };
+        let rest = chars.as_str().trim();
+
+        if kind == ForwardKind::Dynamic {
+            return Some(Self { kind, bind_port: rest.parse().ok()?, target: None });
+        }
+
+        let (bind_port, target) = rest.split_once(':')?;
+        let forward = Self {
+            kind,
+            bind_port: bind_port.trim().parse().ok()?,
+            target: Some(target.trim().to_string()),
+        };
+        forward.target_addr()?;
+        Some(forward)
+    }
+
+    /// Target split into host and port
+    pub fn target_addr(&self) -> Option<(String, u16)> {
+        let (host, port) = self.target.as_deref()?.rsplit_once(':')?;
+        if host.is_empty() {
+            return None;
+        }
+        Some((host.to_string(), port.parse().ok()?))
+    }
+
+    /// ssh flag opening the forward
+    pub fn flag(&self) -> &'static str {
+        match self.kind {
+            ForwardKind::Local => "-L",
+            ForwardKind::Remote => "-R",
+            ForwardKind::Dynamic => "-D",
+        }
+    }
+
+    /// Argument following the flag, e.g. "8080:db:5432"
+    pub fn spec(&self) -> String {
+        match &self.target {
+            Some(target) if self.kind != ForwardKind::Dynamic => format!("{}:{}", self.bind_port, target),
+            _ => self.bind_port.to_string(),
+        }
+    }
+
+    /// Whether both listen on the same port on the same side of the
+    /// connection, so only one of them can be open at a time
+    pub fn same_listener(&self, other: &PortForward) -> bool {
+        self.bind_port == other.bind_port && (self.kind == ForwardKind::Remote) == (other.kind == ForwardKind::Remote)
+    }
+
+    /// ssh command line (`~C`) request that cancels the forward
+    pub fn cancel_command(&self) -> String {
+        format!("-K{} {}", self.flag().trim_start_matches('-'), self.bind_port)
+    }
+}
+
+impl fmt::Display for PortForward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.flag(), self.spec())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SshKey {
+    /// Identifies it for edits and deletes while its name changes; configs
+    /// written before ids get new ones when loaded
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
     pub name: String,
     pub path: String,
     pub is_default: bool,
 }
 
+/// Where host passwords are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsBackend {
+    /// Private vault file next to the config
+    #[default]
+    File,
+    /// OS keyring (Secret Service, Keychain or Windows Credential Manager)
+    Keyring,
+}
+
+/// Program a host's sessions run in the terminal panel
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionKind {
+    #[default]
+    Ssh,
+    /// `mosh`, bootstrapped over ssh with the host's key and options
+    Mosh,
+    /// `telnet`, e.g. to a serial console server
+    Telnet,
+    /// A command run through `sh -c`, such as `kubectl exec -it {name} -- bash`;
+    /// `{host}`, `{port}`, `{user}` and `{name}` are filled in
+    Custom(String),
+}
+
+impl ConnectionKind {
+    pub fn is_ssh(&self) -> bool {
+        *self == ConnectionKind::Ssh
+    }
+
+    /// Whether sessions log in over ssh, so keys, passwords and host keys apply
+    pub fn uses_ssh(&self) -> bool {
+        matches!(self, ConnectionKind::Ssh | ConnectionKind::Mosh)
+    }
+
+    /// Port used when neither the host nor its group sets one
+    pub fn default_port(&self) -> u16 {
+        match self {
+            ConnectionKind::Telnet => 23,
+            _ => 22,
+        }
+    }
+
+    /// Binary spawned in the PTY
+    pub fn program(&self) -> &'static str {
+        match self {
+            ConnectionKind::Ssh => "ssh",
+            ConnectionKind::Mosh => "mosh",
+            ConnectionKind::Telnet => "telnet",
+            ConnectionKind::Custom(_) => "sh",
+        }
+    }
+}
+
+/// Implementation used to open SSH sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SshBackend {
+    /// The system `ssh` binary running in a PTY
+    #[default]
+    System,
+    /// Built-in SSH client (russh), no `ssh` binary required
+    Embedded,
+}
+
+/// Local multiplexer system-backend sessions run inside, so they keep
+/// running when sshtui quits and can be attached to again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionWrapper {
+    /// Sessions end with sshtui
+    #[default]
+    None,
+    /// A dedicated tmux server (`tmux -L sshtui`) with its status bar and
+    /// prefix key turned off
+    Tmux,
+    /// abduco, which passes the session through untouched
+    Abduco,
+}
+
+/// How big the terminal of a host's sessions is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalSize {
+    /// Follows the terminal panel as the window is resized
+    #[default]
+    Fit,
+    /// Always 80x24 (less if the panel is smaller), for devices that
+    /// assume a classic terminal
+    Fixed,
+}
+
+impl TerminalSize {
+    pub fn is_fit(&self) -> bool {
+        *self == TerminalSize::Fit
+    }
+
+    /// Columns and rows sessions are held at, None to follow the panel
+    pub fn fixed(self) -> Option<(u16, u16)> {
+        match self {
+            TerminalSize::Fit => None,
+            TerminalSize::Fixed => Some((80, 24)),
+        }
+    }
+}
+
+/// What a bell (BEL) rung by the remote side does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BellMode {
+    /// Dropped, without even a sidebar marker
+    None,
+    /// The terminal panel flashes
+    #[default]
+    Visual,
+    /// A notification goes out through the configured channels
+    Notification,
+    /// The bell is passed on to the terminal sshtui runs in
+    Sound,
+}
+
+/// Order the hosts of a group are listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostSort {
+    /// Config order, rearranged with Ctrl+Up/Down
+    #[default]
+    Manual,
+    Alphabetical,
+    /// Most recently connected first
+    Recent,
+    /// Most often connected first
+    Frequent,
+    /// Hosts used often and lately first, by frecency
+    Frecency,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub secrets_backend: SecretsBackend,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    /// Reconnect with exponential backoff when a session drops
+    #[serde(default)]
+    pub auto_reconnect: bool,
+    /// Minutes without input after which open sessions are hidden until
+    /// the user returns; 0 never locks them
+    #[serde(default)]
+    pub lock_after_mins: u64,
+    /// Backend used for hosts that do not override it
+    #[serde(default)]
+    pub ssh_backend: SshBackend,
+    /// Ask before sending a multi-line paste to the remote shell
+    #[serde(default = "default_paste_protection")]
+    pub paste_protection: bool,
+    /// Show typed characters before the remote echo arrives
+    #[serde(default)]
+    pub local_echo: bool,
+    /// Let system ssh sessions act as ControlMaster so later sessions to
+    /// the same host (from any sshtui instance) can reuse the connection.
+    /// Off unless turned on, since any program running as the user can use
+    /// a master's logged-in connection; masters from the ssh config are
+    /// reused either way
+    #[serde(default)]
+    pub connection_sharing: bool,
+    /// Run system-backend sessions inside tmux or abduco so they survive
+    /// quitting sshtui
+    #[serde(default)]
+    pub session_wrapper: SessionWrapper,
+    /// Record sessions as asciicast v2 files under recordings/
+    #[serde(default)]
+    pub record_sessions: bool,
+    /// Bell behaviour for hosts that do not override it
+    #[serde(default)]
+    pub bell: BellMode,
+    /// Order of the host list
+    #[serde(default)]
+    pub host_sort: HostSort,
+    #[serde(default)]
+    pub accessibility: AccessibilitySettings,
+    /// Hosts a broadcast runs its command on at the same time
+    #[serde(default = "default_broadcast_parallelism")]
+    pub broadcast_parallelism: usize,
+    /// Name of the color theme the interface is drawn with
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    /// User-defined themes, selectable by name like the built-in ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_themes: Vec<CustomTheme>,
+    /// Sections of the dashboard, top to bottom: built-in widgets by name
+    /// or the names of `dashboard_commands`
+    #[serde(default = "default_dashboard_widgets")]
+    pub dashboard_widgets: Vec<String>,
+    /// Dashboard widgets that show the output of a local command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dashboard_commands: Vec<CommandWidget>,
+    /// Git repository the config directory is pulled from and pushed to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_remote: Option<String>,
+    /// Shared host inventory: "sftp://user@host/path" or an http(s) URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory_sync_url: Option<String>,
+    /// Moves the keyboard from a live session to the sidebar and back,
+    /// such as "F12", "Ctrl+B" or "Alt+S"
+    #[serde(default = "default_sidebar_key")]
+    pub sidebar_key: String,
+    /// Days deleted hosts and groups stay in the trash; 0 keeps them until
+    /// the trash is emptied by hand
+    #[serde(default = "default_trash_days")]
+    pub trash_days: u32,
+}
+
+fn default_broadcast_parallelism() -> usize {
+    8
+}
+
+fn default_theme() -> String {
+    "default".to_string()
+}
+
+fn default_sidebar_key() -> String {
+    "F12".to_string()
+}
+
+fn default_trash_days() -> u32 {
+    30
+}
+
+/// A built-in theme with some of its colors replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomTheme {
+    pub name: String,
+    /// Built-in theme to start from (default when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// Colors by role (accent, text, modal_bg, ...), as names like
+    /// "lightblue", "#rrggbb" or 256-color indices
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+}
+
+/// Built-in dashboard widgets, in the order shown by default
+pub const DASHBOARD_WIDGETS: [&str; 11] = [
+    "welcome", "stats", "actions", "favorites", "host", "recent", "heatmap", "health", "focus", "clock", "quote",
+];
+
+pub fn default_dashboard_widgets() -> Vec<String> {
+    ["welcome", "stats", "actions", "favorites", "host", "recent", "focus", "clock"].iter().map(|name| name.to_string()).collect()
+}
+
+/// A dashboard widget showing what a shell command prints, run again every
+/// `interval_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandWidget {
+    pub name: String,
+    pub command: String,
+    #[serde(default = "default_widget_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_widget_interval_secs() -> u64 {
+    60
+}
+
+fn default_paste_protection() -> bool {
+    true
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            secrets_backend: SecretsBackend::default(),
+            notifications: NotificationSettings::default(),
+            auto_reconnect: false,
+            lock_after_mins: 0,
+            ssh_backend: SshBackend::default(),
+            paste_protection: default_paste_protection(),
+            local_echo: false,
+            connection_sharing: false,
+            session_wrapper: SessionWrapper::default(),
+            record_sessions: false,
+            bell: BellMode::default(),
+            host_sort: HostSort::default(),
+            accessibility: AccessibilitySettings::default(),
+            broadcast_parallelism: default_broadcast_parallelism(),
+            theme: default_theme(),
+            custom_themes: Vec::new(),
+            dashboard_widgets: default_dashboard_widgets(),
+            dashboard_commands: Vec::new(),
+            git_remote: None,
+            inventory_sync_url: None,
+            sidebar_key: default_sidebar_key(),
+            trash_days: default_trash_days(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    /// Show desktop notifications
+    #[serde(default)]
+    pub desktop: bool,
+    /// URL that receives a JSON POST for every notification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// Daily window during which only errors are delivered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// Input handling for users who find fast or chorded key presses hard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessibilitySettings {
+    /// A key pressed again within this many milliseconds of itself is
+    /// ignored; 0 turns the filter off
+    #[serde(default)]
+    pub repeat_filter_ms: u64,
+    /// Key that makes the next key press count as if Ctrl were held;
+    /// pressed twice, it types itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticky_prefix: Option<char>,
+    /// How long timed prompts, such as the undo toast, stay up
+    #[serde(default = "default_prompt_timeout_secs")]
+    pub prompt_timeout_secs: u64,
+}
+
+fn default_prompt_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            repeat_filter_ms: 0,
+            sticky_prefix: None,
+            prompt_timeout_secs: default_prompt_timeout_secs(),
+        }
+    }
+}
+
+/// Local time window in "HH:MM" format; may wrap past midnight (e.g. 22:00-07:00)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl QuietHours {
+    pub fn parse_time(value: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let (Some(start), Some(end)) = (Self::parse_time(&self.start), Self::parse_time(&self.end)) else {
+            return false;
+        };
+        if start <= end {
+            time >= start && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         let config_path = Self::config_path()?;
@@ -43,53 +954,314 @@ impl Config {
             return Ok(default_config);
         }
 
-        let contents = fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
-        
-        let mut config: Config = serde_json::from_str(&contents)
-            .with_context(|| "Failed to parse config JSON")?;
+        let contents = Self::read_raw()?;
+        Self::from_json(&contents)
+            .config_context(|| "Failed to parse config JSON")
+    }
+
+    /// Like `load`, with the passphrase of an encrypted config given rather
+    /// than asked for
+    pub fn load_with_passphrase(passphrase: &str) -> Result<Self> {
+        let config_path = Self::config_path()?;
+        if let Ok(contents) = fs::read_to_string(&config_path) {
+            if config_crypto::is_encrypted(&contents) {
+                config_crypto::unlock(&contents, passphrase).map_err(|e| Error::config(format!("{:#}", e)))?;
+            }
+        }
+        Self::load()
+    }
+
+    /// Parse config JSON as it is stored in the file
+    pub fn from_json(contents: &str) -> serde_json::Result<Self> {
+        // Dependencies used to be saved by host name
+        let (mut config, dependency_names) = if contents.contains("\"depends_on\"") {
+            let mut value: serde_json::Value = serde_json::from_str(contents)?;
+            let names = take_dependency_names(&mut value);
+            (serde_json::from_value::<Config>(value)?, names)
+        } else {
+            (serde_json::from_str(contents)?, Vec::new())
+        };
+        config.resolve_dependency_names(dependency_names);
 
         // Ensure "All" group exists
         config.ensure_all_group();
+        config.ensure_unique_ids();
+        config.ensure_parent_groups();
         
         Ok(config)
     }
 
+    /// Whether the config file is encrypted, so loading it may ask for the
+    /// passphrase
+    pub fn is_encrypted_on_disk() -> bool {
+        Self::config_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .is_some_and(|contents| config_crypto::is_encrypted(&contents))
+    }
+
+    /// The config file's JSON, decrypted when it is encrypted
+    pub fn read_raw() -> Result<String> {
+        let config_path = Self::config_path()?;
+        let contents = fs::read_to_string(&config_path)
+            .config_context(|| format!("Failed to read config file: {:?}", config_path))?;
+        if config_crypto::is_encrypted(&contents) {
+            return config_crypto::decrypt(&contents).map_err(|e| Error::config(format!("{:#}", e)));
+        }
+        Ok(contents)
+    }
+
+    /// Replace the config file with hand-edited contents that already parse
+    pub fn write_raw(contents: &str) -> Result<()> {
+        let contents = if config_crypto::is_enabled() {
+            config_crypto::encrypt(contents).map_err(|e| Error::config(format!("{:#}", e)))?
+        } else {
+            contents.to_string()
+        };
+        Self::replace_file(&contents)
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = Self::config_path()?;
         
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+                .config_context(|| format!("Failed to create config directory: {:?}", parent))?;
         }
 
-        let contents = serde_json::to_string_pretty(self)
-            .with_context(|| "Failed to serialize config")?;
-        
-        fs::write(&config_path, contents)
-            .with_context(|| format!("Failed to write config file: {:?}", config_path))?;
-        
+        let mut contents = serde_json::to_string_pretty(self)
+            .config_context(|| "Failed to serialize config")?;
+        if config_crypto::is_enabled() {
+            contents = config_crypto::encrypt(&contents).map_err(|e| Error::config(format!("{:#}", e)))?;
+        }
+        Self::replace_file(&contents)
+    }
+
+    /// Write the config file through a temporary file renamed over it, so a
+    /// crash mid-write never leaves a truncated config behind. The file
+    /// being replaced is kept as the backup, readable by the owner only,
+    /// unless it is damaged itself or plaintext replaced by an encrypted
+    /// config, which also removes a plaintext backup left from before.
+    fn replace_file(contents: &str) -> Result<()> {
+        let config_path = Self::config_path()?;
+        let temp_path = config_path.with_extension("json.tmp");
+        // Created afresh so it is readable by the owner only from the start
+        let _ = fs::remove_file(&temp_path);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&temp_path)
+            .and_then(|mut file| {
+                file.write_all(contents.as_bytes())?;
+                file.sync_all()
+            })
+            .config_context(|| format!("Failed to write config file: {:?}", temp_path))?;
+
+        let current = fs::read_to_string(&config_path).unwrap_or_default();
+        let backup_path = Self::backup_path()?;
+        if config_crypto::is_encrypted(contents) && !config_crypto::is_encrypted(&current) {
+            if backup_path.exists() {
+                if let Err(e) = fs::remove_file(&backup_path) {
+                    warn!("Could not remove the plaintext config backup {:?}: {}", backup_path, e);
+                }
+            }
+        } else if serde_json::from_str::<serde_json::Value>(&current).is_ok() {
+            if let Err(e) = Self::write_backup(&config_path, &backup_path) {
+                warn!("Could not back up the config to {:?}: {}", backup_path, e);
+            }
+        }
+        fs::rename(&temp_path, &config_path)
+            .config_context(|| format!("Failed to replace config file: {:?}", config_path))?;
         Ok(())
     }
 
-    fn config_path() -> Result<PathBuf> {
+    /// Copy the config to the backup, which is never readable by others,
+    /// even while being written
+    pub fn write_backup(config_path: &Path, backup_path: &Path) -> std::io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // A backup left by an older version keeps its mode otherwise
+            if backup_path.exists() {
+                fs::set_permissions(backup_path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        let mut backup = options.open(backup_path)?;
+        std::io::copy(&mut fs::File::open(config_path)?, &mut backup)?;
+        backup.sync_all()
+    }
+
+    /// The config as it was before the last save
+    pub fn backup_path() -> Result<PathBuf> {
+        Ok(Self::config_path()?.with_extension("json.bak"))
+    }
+
+    /// Move a config file that does not parse out of the way, to
+    /// `config.json.corrupt`, so it can be looked at later
+    pub fn set_aside_corrupt() -> Result<PathBuf> {
+        let config_path = Self::config_path()?;
+        let corrupt_path = config_path.with_extension("json.corrupt");
+        fs::rename(&config_path, &corrupt_path)
+            .config_context(|| format!("Failed to move {:?} aside", config_path))?;
+        Ok(corrupt_path)
+    }
+
+    /// Put the backup in place of a config file that does not parse
+    pub fn restore_backup() -> Result<()> {
+        let backup_path = Self::backup_path()?;
+        if !backup_path.exists() {
+            return Err(Error::config("There is no backup of the config"));
+        }
+        Self::set_aside_corrupt()?;
+        fs::copy(&backup_path, Self::config_path()?)
+            .config_context(|| format!("Failed to restore {:?}", backup_path))?;
+        Ok(())
+    }
+
+    pub fn config_dir() -> Result<PathBuf> {
         let home = dirs::home_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-        Ok(home.join(".config").join("sshtui").join("config.json"))
+            .ok_or_else(|| Error::config("Could not find home directory"))?;
+        Ok(home.join(".config").join("sshtui"))
+    }
+
+    fn config_path() -> Result<PathBuf> {
+        Self::profile_path(&Self::profile())
+    }
+
+    /// Read and save the file of another profile from now on, such as
+    /// "work" for `profiles/work.json`
+    pub fn set_profile(name: &str) {
+        let profile = (name != DEFAULT_PROFILE).then(|| name.to_string());
+        *PROFILE.write().unwrap_or_else(|e| e.into_inner()) = profile;
+    }
+
+    /// The profile in use
+    pub fn profile() -> String {
+        PROFILE.read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+    }
+
+    pub fn profile_path(name: &str) -> Result<PathBuf> {
+        if name == DEFAULT_PROFILE {
+            return Ok(Self::config_dir()?.join("config.json"));
+        }
+        Ok(Self::config_dir()?.join("profiles").join(format!("{}.json", name)))
+    }
+
+    /// Every profile, the default first and the others by name
+    pub fn profiles() -> Vec<String> {
+        let mut profiles: Vec<String> = Self::config_dir()
+            .and_then(|dir| fs::read_dir(dir.join("profiles")).config_context(|| "Failed to list profiles"))
+            .map(|entries| {
+                entries.flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+                    .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+                    .filter(|name| name != DEFAULT_PROFILE)
+                    .collect()
+            })
+            .unwrap_or_default();
+        profiles.sort();
+        profiles.insert(0, DEFAULT_PROFILE.to_string());
+        profiles
+    }
+
+    /// Profile names become file names, so they are kept to letters,
+    /// digits, '-' and '_'
+    pub fn is_valid_profile_name(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    }
+
+    /// A profile's config, without making it the one in use; None when the
+    /// profile has no file yet
+    pub fn load_profile(name: &str) -> Result<Option<Self>> {
+        let path = Self::profile_path(name)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| Error::config(format!("Could not read {:?}: {}", path, e)))?;
+        // The passphrase can only be typed before the interface takes the terminal
+        if config_crypto::is_encrypted(&contents) {
+            return Err(Error::config(format!("Profile '{}' is encrypted; open it with `sshtui --profile {}`", name, name)));
+        }
+        Self::from_json(&contents)
+            .map(Some)
+            .map_err(|e| Error::config(format!("Profile '{}' does not parse: {}", name, e)))
     }
 
     fn ensure_all_group(&mut self) {
         // Check if "All" group exists as first group
         if self.groups.is_empty() || self.groups[0].name != "All" {
             let all_group = Group {
+                id: Uuid::new_v4(),
                 name: "All".to_string(),
                 color: "blue".to_string(),
                 hosts: vec![],
+                defaults: GroupDefaults::default(),
+                notes: String::new(),
+                ec2: None,
             };
             self.groups.insert(0, all_group);
         }
     }
 
+    /// Give a fresh id to any entry whose id was already taken, as when a
+    /// host was copied by hand in the JSON
+    fn ensure_unique_ids(&mut self) {
+        let mut seen = std::collections::HashSet::new();
+        for key in &mut self.keys {
+            if !seen.insert(key.id) {
+                key.id = Uuid::new_v4();
+            }
+        }
+        for group in &mut self.groups {
+            if !seen.insert(group.id) {
+                group.id = Uuid::new_v4();
+            }
+            for host in &mut group.hosts {
+                if !seen.insert(host.id) {
+                    host.id = Uuid::new_v4();
+                }
+            }
+        }
+    }
+
+    /// Create the groups that nested names refer to, such as "clients" and
+    /// "clients/acme" for "clients/acme/prod", each just before its first
+    /// sub-group and in its color
+    pub fn ensure_parent_groups(&mut self) {
+        let mut index = 1;
+        while index < self.groups.len() {
+            let missing = self.groups[index].parent_name()
+                .filter(|parent| !self.groups.iter().any(|group| group.name == *parent))
+                .map(str::to_string);
+            match missing {
+                // The new group's own parent is checked next
+                Some(parent) => self.groups.insert(index, Group {
+                    id: Uuid::new_v4(),
+                    name: parent,
+                    color: self.groups[index].color.clone(),
+                    hosts: vec![],
+                    defaults: GroupDefaults::default(),
+                    notes: String::new(),
+                    ec2: None,
+                }),
+                None => index += 1,
+            }
+        }
+    }
+
+    /// Hosts of a group and of the groups nested in it; every host for "All"
     pub fn get_hosts_for_group(&self, group_index: usize) -> Vec<Host> {
         if group_index >= self.groups.len() {
             return vec![];
@@ -104,8 +1276,51 @@ impl Config {
             }
             all_hosts
         } else {
-            self.groups[group_index].hosts.clone()
+            let name = &self.groups[group_index].name;
+            self.groups.iter()
+                .skip(1)
+                .filter(|group| group.is_within(name))
+                .flat_map(|group| group.hosts.iter().cloned())
+                .collect()
+        }
+    }
+
+    /// Group a host belongs to
+    pub fn group_of(&self, host: &Host) -> Option<&Group> {
+        self.groups.iter()
+            .skip(1)
+            .find(|group| group.hosts.iter().any(|h| h.id == host.id))
+    }
+
+    /// The host with its group's defaults filled in wherever it does not set
+    /// its own. Without a user anywhere the local user name is used, as ssh does.
+    pub fn effective_host(&self, host: &Host) -> Host {
+        let defaults = self.group_of(host).map(|group| group.defaults.clone()).unwrap_or_default();
+        let mut host = host.clone();
+        if host.user.is_empty() {
+            host.user = defaults.user
+                .or_else(|| std::env::var("USER").ok())
+                .unwrap_or_default();
+        }
+        if host.port == 0 {
+            host.port = defaults.port.unwrap_or(host.kind.default_port());
+        }
+        if host.key_path.is_none() {
+            host.key_path = defaults.key_path;
         }
+        host
+    }
+
+    /// Every tag used by any host, sorted and deduplicated
+    pub fn all_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.groups.iter()
+            .skip(1)
+            .flat_map(|group| group.hosts.iter())
+            .flat_map(|host| host.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
     }
 
     pub fn add_key(&mut self, key: SshKey) {
@@ -129,42 +1344,123 @@ impl Config {
 
     pub fn add_host_to_group(&mut self, group_name: &str, host: Host) -> Result<()> {
         if group_name == "All" {
-            return Err(anyhow::anyhow!("Cannot add hosts directly to 'All' group"));
+            return Err(Error::config("Cannot add hosts directly to 'All' group"));
         }
 
         let group = self.groups.iter_mut()
             .find(|g| g.name == group_name)
-            .ok_or_else(|| anyhow::anyhow!("Group '{}' not found", group_name))?;
-        
+            .ok_or_else(|| Error::config(format!("Group '{}' not found", group_name)))?;
+        if group.hosts.iter().any(|existing| existing.name == host.name) {
+            return Err(Error::config(format!("Group '{}' already has a host named '{}'", group_name, host.name)));
+        }
+
         group.hosts.push(host);
         Ok(())
     }
 
+    /// Whether a group of this name exists, "All" included
+    pub fn group_name_taken(&self, name: &str) -> bool {
+        self.groups.iter().any(|group| group.name == name)
+    }
+
+    /// Whether `group_name` already has a host called `host_name`
+    pub fn host_name_taken(&self, group_name: &str, host_name: &str) -> bool {
+        self.groups.iter()
+            .find(|group| group.name == group_name)
+            .is_some_and(|group| group.hosts.iter().any(|host| host.name == host_name))
+    }
+
+    /// Keep a deleted host or group in the trash, newest first
+    pub fn add_to_trash(&mut self, item: Trashed) {
+        self.trash.insert(0, TrashedItem { deleted_at: Local::now(), item });
+    }
+
     pub fn get_default_key(&self) -> Option<&SshKey> {
         self.keys.iter().find(|key| key.is_default)
     }
 
-    pub fn remove_key(&mut self, name: &str) {
-        self.keys.retain(|key| key.name != name);
+    pub fn remove_key(&mut self, id: Uuid) {
+        self.keys.retain(|key| key.id != id);
     }
 
-    pub fn remove_group(&mut self, name: &str) {
-        if name == "All" {
-            return; // Cannot remove "All" group
+    pub fn remove_group(&mut self, id: Uuid) {
+        // The "All" group cannot be removed
+        if self.groups.first().is_some_and(|group| group.id == id) {
+            return;
         }
-        self.groups.retain(|group| group.name != name);
+        self.groups.retain(|group| group.id != id);
     }
 
-    pub fn remove_host(&mut self, group_name: &str, host_name: &str) -> Result<()> {
-        if group_name == "All" {
-            return Err(anyhow::anyhow!("Cannot remove hosts from 'All' group directly"));
+    /// Id of the host called `name`, preferring one in `group_name`
+    pub fn host_id_by_name(&self, group_name: &str, name: &str) -> Option<Uuid> {
+        let in_group = self.groups.iter().filter(|group| group.name == group_name).flat_map(|group| group.hosts.iter());
+        let anywhere = self.groups.iter().flat_map(|group| group.hosts.iter());
+        in_group.chain(anywhere).find(|host| host.name == name).map(|host| host.id)
+    }
+
+    /// Names of the hosts with these ids, skipping any that are gone
+    pub fn host_names(&self, ids: &[Uuid]) -> Vec<String> {
+        ids.iter().filter_map(|id| self.find_host(*id)).map(|host| host.name.clone()).collect()
+    }
+
+    /// Give hosts whose dependencies were saved by name the ids of the
+    /// hosts with those names; `names` comes from `take_dependency_names`
+    fn resolve_dependency_names(&mut self, names: Vec<((usize, usize), Vec<String>)>) {
+        for ((group_index, host_index), names) in names {
+            let Some(group_name) = self.groups.get(group_index).map(|group| group.name.clone()) else {
+                continue;
+            };
+            let ids: Vec<Uuid> = names.iter()
+                .filter_map(|name| {
+                    let id = self.host_id_by_name(&group_name, name);
+                    if id.is_none() {
+                        warn!("Dropping dependency on unknown host '{}'", name);
+                    }
+                    id
+                })
+                .collect();
+            if let Some(host) = self.groups[group_index].hosts.get_mut(host_index) {
+                host.depends_on.extend(ids);
+            }
         }
+    }
+
+    pub fn find_host(&self, id: Uuid) -> Option<&Host> {
+        self.groups.iter()
+            .skip(1)
+            .flat_map(|group| group.hosts.iter())
+            .find(|host| host.id == id)
+    }
+
+    pub fn find_host_mut(&mut self, id: Uuid) -> Option<&mut Host> {
+        self.groups.iter_mut()
+            .skip(1)
+            .flat_map(|group| group.hosts.iter_mut())
+            .find(|host| host.id == id)
+    }
 
+    pub fn remove_host(&mut self, id: Uuid) -> Result<()> {
         let group = self.groups.iter_mut()
-            .find(|g| g.name == group_name)
-            .ok_or_else(|| anyhow::anyhow!("Group '{}' not found", group_name))?;
-        
-        group.hosts.retain(|host| host.name != host_name);
+            .skip(1)
+            .find(|group| group.hosts.iter().any(|host| host.id == id))
+            .ok_or_else(|| Error::config("Host not found"))?;
+        group.hosts.retain(|host| host.id != id);
+        Ok(())
+    }
+
+    /// Put `host` in place of the host with its id, keeping its position
+    /// in the group
+    pub fn replace_host(&mut self, host: Host) -> Result<()> {
+        let group = self.groups.iter()
+            .skip(1)
+            .find(|group| group.hosts.iter().any(|h| h.id == host.id))
+            .ok_or_else(|| Error::config(format!("Host '{}' not found", host.name)))?;
+        if group.hosts.iter().any(|h| h.id != host.id && h.name == host.name) {
+            return Err(Error::config(format!("Group '{}' already has a host named '{}'", group.name, host.name)));
+        }
+        if let Some(existing) = self.find_host_mut(host.id) {
+            *existing = host;
+        }
         Ok(())
     }
 }
@@ -172,20 +1468,120 @@ impl Config {
 impl Default for Config {
     fn default() -> Self {
         let all_group = Group {
+            id: Uuid::new_v4(),
             name: "All".to_string(),
             color: "blue".to_string(),
             hosts: vec![],
+            defaults: GroupDefaults::default(),
+            notes: String::new(),
+            ec2: None,
         };
 
         let default_group = Group {
+            id: Uuid::new_v4(),
             name: "Default".to_string(),
             color: "green".to_string(),
             hosts: vec![],
+            defaults: GroupDefaults::default(),
+            notes: String::new(),
+            ec2: None,
         };
 
         Config {
             groups: vec![all_group, default_group],
             keys: vec![],
+            settings: Settings::default(),
+            trash: Vec::new(),
+        }
+    }
+}
+
+/// Take the dependencies that are host names rather than ids out of
+/// config JSON, by group and host index
+fn take_dependency_names(value: &mut serde_json::Value) -> Vec<((usize, usize), Vec<String>)> {
+    let mut found = Vec::new();
+    let Some(groups) = value.get_mut("groups").and_then(|groups| groups.as_array_mut()) else {
+        return found;
+    };
+    for (group_index, group) in groups.iter_mut().enumerate() {
+        let Some(hosts) = group.get_mut("hosts").and_then(|hosts| hosts.as_array_mut()) else {
+            continue;
+        };
+        for (host_index, host) in hosts.iter_mut().enumerate() {
+            let Some(dependencies) = host.get_mut("depends_on").and_then(|list| list.as_array_mut()) else {
+                continue;
+            };
+            let mut names = Vec::new();
+            dependencies.retain(|dependency| match dependency.as_str() {
+                Some(text) if Uuid::parse_str(text).is_err() => {
+                    names.push(text.to_string());
+                    false
+                },
+                _ => true,
+            });
+            if !names.is_empty() {
+                found.push(((group_index, host_index), names));
+            }
         }
     }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(payload: &str) -> Vec<u8> {
+        KeepAlive { interval_secs: 60, payload: payload.to_string() }.payload_bytes()
+    }
+
+    #[test]
+    fn keepalive_payload_is_sent_as_typed() {
+        assert_eq!(payload(" \r"), b" \r");
+        assert_eq!(payload("é"), "é".as_bytes());
+    }
+
+    #[test]
+    fn keepalive_payload_decodes_escapes() {
+        assert_eq!(payload("\\0"), [0]);
+        assert_eq!(payload("\\e[0n"), b"\x1b[0n");
+        assert_eq!(payload("a\\x7fb\\x0D"), b"a\x7fb\r");
+    }
+
+    #[test]
+    fn keepalive_payload_keeps_unknown_escapes() {
+        assert_eq!(payload("\\n"), b"\\n");
+        assert_eq!(payload("\\xzz"), b"\\xzz");
+        assert_eq!(payload("\\x4"), b"\\x4");
+        assert_eq!(payload("\\x+1"), b"\\x+1");
+        assert_eq!(payload("end\\"), b"end\\");
+    }
+
+    #[test]
+    fn keepalive_goes_through_the_session_only_with_a_payload() {
+        assert!(!KeepAlive { interval_secs: 60, payload: String::new() }.through_session());
+        assert!(KeepAlive { interval_secs: 60, payload: "\\0".to_string() }.through_session());
+    }
+
+    #[test]
+    fn dependencies_saved_by_name_become_ids() {
+        let contents = r#"{"groups": [
+            {"name": "All", "color": "white", "hosts": []},
+            {"name": "prod", "color": "red", "hosts": [
+                {"name": "bastion", "host": "10.0.0.1", "user": "ops"},
+                {"name": "db", "host": "10.0.0.2", "user": "ops", "depends_on": ["bastion", "gone"]}
+            ]},
+            {"name": "test", "color": "blue", "hosts": [
+                {"name": "bastion", "host": "10.1.0.1", "user": "ops"}
+            ]}
+        ], "keys": []}"#;
+        let config = Config::from_json(contents).unwrap();
+        let prod = config.groups.iter().find(|group| group.name == "prod").unwrap();
+        assert_eq!(prod.hosts[1].depends_on, vec![prod.hosts[0].id]);
+
+        // Saved again, the ids load as they are
+        let again = Config::from_json(&serde_json::to_string(&config).unwrap()).unwrap();
+        let prod = again.groups.iter().find(|group| group.name == "prod").unwrap();
+        assert_eq!(prod.hosts[1].depends_on, vec![prod.hosts[0].id]);
+    }
 }
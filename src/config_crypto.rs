@@ -0,0 +1,215 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Read instead of prompting, for scripts
+pub const PASSPHRASE_ENV: &str = "SSHTUI_CONFIG_PASSPHRASE";
+
+/// Asks for the config passphrase, given what to ask with
+pub type PassphrasePrompt = fn(&str) -> Result<String>;
+
+/// Wrong passphrases accepted before giving up
+const PROMPT_ATTEMPTS: usize = 3;
+
+/// What an encrypted config file holds in place of the config
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    sshtui_encrypted: u32,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Key derived from the passphrase, kept for saving while the app runs
+struct ConfigKey {
+    key: [u8; 32],
+    salt: [u8; 16],
+}
+
+static KEY: Mutex<Option<ConfigKey>> = Mutex::new(None);
+
+static PROMPT: Mutex<Option<PassphrasePrompt>> = Mutex::new(None);
+
+/// Key saves were encrypted with before `disable`, still used to read the
+/// files written with it
+static RETIRED: Mutex<Option<ConfigKey>> = Mutex::new(None);
+
+fn derive(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the config key: {}", e))?;
+    Ok(key)
+}
+
+/// Ask for the passphrase with `prompt` when an encrypted config is loaded.
+/// Without one, loading it fails unless SSHTUI_CONFIG_PASSPHRASE is set or
+/// the passphrase is unlocked first.
+pub fn set_passphrase_prompt(prompt: PassphrasePrompt) {
+    *PROMPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(prompt);
+}
+
+/// Whether `contents` is an encrypted config rather than plain JSON
+pub fn is_encrypted(contents: &str) -> bool {
+    serde_json::from_str::<Envelope>(contents).is_ok()
+}
+
+/// Whether saves are encrypted
+pub fn is_enabled() -> bool {
+    KEY.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Encrypt every later save with `passphrase`
+pub fn enable(passphrase: &str) -> Result<()> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive(passphrase, &salt)?;
+    *KEY.lock().unwrap_or_else(|e| e.into_inner()) = Some(ConfigKey { key, salt });
+    Ok(())
+}
+
+/// Whether `passphrase` is the one saves are encrypted with
+pub fn verify_passphrase(passphrase: &str) -> bool {
+    let guard = KEY.lock().unwrap_or_else(|e| e.into_inner());
+    guard.as_ref().is_some_and(|config_key| {
+        derive(passphrase, &config_key.salt).is_ok_and(|key| key == config_key.key)
+    })
+}
+
+/// Save in plain JSON again
+pub fn disable() {
+    let key = KEY.lock().unwrap_or_else(|e| e.into_inner()).take();
+    *RETIRED.lock().unwrap_or_else(|e| e.into_inner()) = key;
+}
+
+pub fn encrypt(plaintext: &str) -> Result<String> {
+    let guard = KEY.lock().unwrap_or_else(|e| e.into_inner());
+    let config_key = guard.as_ref().ok_or_else(|| anyhow!("Config encryption is not enabled"))?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&config_key.key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt the config"))?;
+
+    let envelope = Envelope {
+        sshtui_encrypted: 1,
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(config_key.salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&envelope).with_context(|| "Failed to serialize encrypted config")
+}
+
+/// Salt, nonce and ciphertext of an encrypted file
+fn open_envelope(contents: &str) -> Result<([u8; 16], Vec<u8>, Vec<u8>)> {
+    let envelope: Envelope = serde_json::from_str(contents)
+        .with_context(|| "Encrypted config is malformed")?;
+    if envelope.sshtui_encrypted != 1 || envelope.kdf != "argon2id" {
+        bail!("Encrypted config uses an unsupported format");
+    }
+    let salt: [u8; 16] = BASE64.decode(&envelope.salt)?
+        .try_into()
+        .map_err(|_| anyhow!("Encrypted config has a malformed salt"))?;
+    let nonce = BASE64.decode(&envelope.nonce)?;
+    if nonce.len() != 12 {
+        bail!("Encrypted config has a malformed nonce");
+    }
+    let ciphertext = BASE64.decode(&envelope.ciphertext)?;
+    Ok((salt, nonce, ciphertext))
+}
+
+fn open_with(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+/// The plaintext when `passphrase` is the one it was encrypted with,
+/// keeping the key so later saves stay encrypted
+fn open_with_passphrase(passphrase: &str, salt: [u8; 16], nonce: &[u8], ciphertext: &[u8]) -> Result<Option<String>> {
+    let key = derive(passphrase, &salt)?;
+    let Some(plaintext) = open_with(&key, nonce, ciphertext) else {
+        return Ok(None);
+    };
+    *KEY.lock().unwrap_or_else(|e| e.into_inner()) = Some(ConfigKey { key, salt });
+    String::from_utf8(plaintext).map(Some).with_context(|| "Decrypted config is not UTF-8")
+}
+
+/// Keep the key of an encrypted config for loading and saving it, when
+/// `passphrase` is the one it was encrypted with
+pub fn unlock(contents: &str, passphrase: &str) -> Result<()> {
+    let (salt, nonce, ciphertext) = open_envelope(contents)?;
+    match open_with_passphrase(passphrase, salt, &nonce, &ciphertext)? {
+        Some(_) => Ok(()),
+        None => bail!("Wrong passphrase for the encrypted config"),
+    }
+}
+
+/// Decrypt an encrypted config, asking for the passphrase the first time
+/// and keeping the key so later saves stay encrypted
+pub fn decrypt(contents: &str) -> Result<String> {
+    let (salt, nonce, ciphertext) = open_envelope(contents)?;
+
+    // Reuse the key from an earlier load, such as before a reload
+    {
+        let guard = KEY.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(plaintext) = guard.as_ref().filter(|k| k.salt == salt).and_then(|k| open_with(&k.key, &nonce, &ciphertext)) {
+            return String::from_utf8(plaintext).with_context(|| "Decrypted config is not UTF-8");
+        }
+    }
+
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return open_with_passphrase(&passphrase, salt, &nonce, &ciphertext)?
+            .ok_or_else(|| anyhow!("Wrong passphrase for the encrypted config"));
+    }
+    let Some(prompt) = *PROMPT.lock().unwrap_or_else(|e| e.into_inner()) else {
+        bail!("The config is encrypted; set {} or unlock it with its passphrase first", PASSPHRASE_ENV);
+    };
+    let mut question = "Config passphrase";
+    for _ in 0..PROMPT_ATTEMPTS {
+        let passphrase = prompt(question)?;
+        if let Some(plaintext) = open_with_passphrase(&passphrase, salt, &nonce, &ciphertext)? {
+            return Ok(plaintext);
+        }
+        question = "Wrong passphrase; config passphrase";
+    }
+    bail!("Wrong passphrase for the encrypted config")
+}
+
+/// Write a file kept next to the config, such as the connection history,
+/// encrypted when saves are. A file encrypted with a key that is not
+/// loaded, as by another profile, is left as it is rather than replaced.
+pub fn write_sealed(path: &Path, contents: String) -> Result<()> {
+    if let Ok(current) = fs::read_to_string(path) {
+        if is_encrypted(&current) && unseal(current).is_err() {
+            bail!("It is encrypted with a config passphrase that is not loaded");
+        }
+    }
+    let contents = if is_enabled() { encrypt(&contents)? } else { contents };
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Read back what `write_sealed` wrote, with the key the config was loaded with.
+/// Never asks for the passphrase.
+pub fn unseal(contents: String) -> Result<String> {
+    if !is_encrypted(&contents) {
+        return Ok(contents);
+    }
+    let (salt, nonce, ciphertext) = open_envelope(&contents)?;
+    let key = [&KEY, &RETIRED].into_iter()
+        .find_map(|slot| slot.lock().unwrap_or_else(|e| e.into_inner()).as_ref().filter(|k| k.salt == salt).map(|k| k.key))
+        .ok_or_else(|| anyhow!("Encrypted with a config passphrase that is not loaded"))?;
+    let plaintext = open_with(&key, &nonce, &ciphertext)
+        .ok_or_else(|| anyhow!("Could not decrypt with the config key"))?;
+    String::from_utf8(plaintext).with_context(|| "Decrypted file is not UTF-8")
+}
@@ -0,0 +1,123 @@
+use crate::{AppState, MessageType, ModalState, ReviewChangesForm};
+use crate::config::Config;
+use chrono::Local;
+use log::warn;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// A setting an edit changes, with its values as shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    /// Config name of the setting, such as `port` or `defaults.user`
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Settings that differ between two versions of a host or group, by name
+pub fn changes<T: Serialize>(old: &T, new: &T) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(old), Ok(new)) => compare("", &old, &new, &mut changes),
+        (Err(e), _) | (_, Err(e)) => warn!("Could not compare config versions: {}", e),
+    }
+    changes
+}
+
+/// Nested settings such as a group's defaults are compared one by one;
+/// settings left out of the config count as unset
+fn compare(prefix: &str, old: &Value, new: &Value, changes: &mut Vec<FieldChange>) {
+    let unset = Map::new();
+    match (old, new) {
+        (Value::Object(old_fields), Value::Null) => compare_fields(prefix, old_fields, &unset, changes),
+        (Value::Null, Value::Object(new_fields)) => compare_fields(prefix, &unset, new_fields, changes),
+        (Value::Object(old_fields), Value::Object(new_fields)) => compare_fields(prefix, old_fields, new_fields, changes),
+        _ if old != new => changes.push(FieldChange { field: prefix.to_string(), old: show(old), new: show(new) }),
+        _ => {},
+    }
+}
+
+fn compare_fields(
+    prefix: &str,
+    old_fields: &Map<String, Value>,
+    new_fields: &Map<String, Value>,
+    changes: &mut Vec<FieldChange>,
+) {
+    let mut names: Vec<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let field = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+        let old = old_fields.get(name).unwrap_or(&Value::Null);
+        let new = new_fields.get(name).unwrap_or(&Value::Null);
+        compare(&field, old, new, changes);
+    }
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::Null => "(none)".to_string(),
+        Value::String(text) if text.is_empty() => "(empty)".to_string(),
+        // Several lines, such as notes, are kept on one
+        Value::String(text) => text.replace('\n', " ↵ "),
+        Value::Array(items) if items.is_empty() => "(none)".to_string(),
+        Value::Array(items) => items.iter()
+            .map(|item| match item {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// The changes on one line, for the audit log
+pub fn describe(changes: &[FieldChange]) -> String {
+    changes.iter()
+        .map(|change| format!("{}: {} -> {}", change.field, change.old, change.new))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Add a line to `audit.log` next to the config, for edits saved from the UI
+pub fn audit(action: &str, changes: &[FieldChange]) {
+    let line = format!("{} {}: {}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), action, describe(changes));
+    let result = Config::config_dir().map_err(|e| e.to_string()).and_then(|dir| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("audit.log"))
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        warn!("Could not write the audit log: {}", e);
+    }
+}
+
+impl AppState {
+    /// Whether the open edit form was confirmed on the review screen; when
+    /// not, its changes are shown there first. Returns false as well when
+    /// nothing changed.
+    pub fn edit_reviewed(&mut self, subject: String, changes: &[FieldChange]) -> bool {
+        if self.approved_edit.take().is_some_and(|approved| approved == self.modal_state) {
+            return true;
+        }
+        if changes.is_empty() {
+            self.set_message(format!("No changes to {}", subject), MessageType::Info);
+            self.modal_state = ModalState::None;
+            return false;
+        }
+        let edit = Box::new(std::mem::replace(&mut self.modal_state, ModalState::None));
+        self.modal_state = ModalState::ReviewChanges(ReviewChangesForm {
+            subject,
+            changes: changes.to_vec(),
+            edit,
+            confirm_selected: true,
+        });
+        false
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{AppState, ConfigEditorForm, MessageType, ModalState};
+use crate::config::Config;
+use crate::theme;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ratatui::prelude::*;
+
+/// Inserted by Tab
+const INDENT: &str = "  ";
+
+/// Byte offset of character `col` in `line`, or its end
+fn byte_at(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+impl ConfigEditorForm {
+    fn new(text: &str) -> Self {
+        let mut lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        Self { lines, row: 0, col: 0, scroll: 0, status: None, dirty: false, discard_armed: false }
+    }
+
+    fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    /// Type a character at the cursor; a newline keeps the indentation
+    pub fn insert(&mut self, c: char) {
+        let at = byte_at(&self.lines[self.row], self.col);
+        if c == '\n' {
+            let rest = self.lines[self.row].split_off(at);
+            let indent: String = self.lines[self.row].chars().take_while(|c| *c == ' ').collect();
+            self.col = indent.chars().count();
+            self.row += 1;
+            self.lines.insert(self.row, indent + &rest);
+        } else {
+            self.lines[self.row].insert(at, c);
+            self.col += 1;
+        }
+        self.dirty = true;
+    }
+
+    fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let at = byte_at(&self.lines[self.row], self.col);
+            self.lines[self.row].remove(at);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len();
+            self.lines[self.row].push_str(&line);
+        } else {
+            return;
+        }
+        self.dirty = true;
+    }
+
+    fn delete(&mut self) {
+        if self.col < self.line_len() {
+            let at = byte_at(&self.lines[self.row], self.col);
+            self.lines[self.row].remove(at);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        } else {
+            return;
+        }
+        self.dirty = true;
+    }
+
+    /// Move to `row`, keeping the column where the line allows
+    fn go_to_row(&mut self, row: usize) {
+        self.row = row.min(self.lines.len() - 1);
+        self.col = self.col.min(self.line_len());
+    }
+
+    /// First line shown when `rows` lines fit, keeping the cursor in view
+    pub fn first_visible_row(&self, rows: usize) -> usize {
+        self.scroll.min(self.row).max((self.row + 1).saturating_sub(rows.max(1)))
+    }
+}
+
+/// Color a line of JSON: keys, strings, numbers, literals and punctuation
+pub fn highlight_json(line: &str) -> Line<'static> {
+    let theme = theme::current();
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let color = match chars[i] {
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(chars.len());
+                // A string followed by ':' is an object key
+                if chars[i..].iter().find(|c| !c.is_whitespace()) == Some(&':') {
+                    theme.info
+                } else {
+                    theme.success
+                }
+            },
+            c if c == '-' || c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '.' | '-' | '+')) {
+                    i += 1;
+                }
+                theme.highlight
+            },
+            c if c.is_ascii_alphabetic() => {
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                theme.accent
+            },
+            c if c.is_whitespace() => {
+                while i < chars.len() && chars[i].is_whitespace() {
+                    i += 1;
+                }
+                theme.text
+            },
+            _ => {
+                i += 1;
+                theme.subtle
+            },
+        };
+        spans.push(Span::styled(chars[start..i].iter().collect::<String>(), Style::default().fg(color)));
+    }
+    Line::from(spans)
+}
+
+impl AppState {
+    pub fn open_config_editor(&mut self) {
+        match Config::read_raw() {
+            Ok(text) => self.modal_state = ModalState::ConfigEditor(ConfigEditorForm::new(&text)),
+            Err(e) => self.set_message(format!("Could not open the config: {}", e), MessageType::Error),
+        }
+    }
+
+    /// Lines of the file that fit in the editor
+    pub fn config_editor_rows(&self) -> usize {
+        self.terminal_size.1.saturating_sub(6) as usize
+    }
+
+    /// The editor takes every key while it is open
+    pub fn handle_config_editor_key(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+        if (key, modifiers) == (KeyCode::Char('s'), KeyModifiers::CONTROL) {
+            self.save_config_editor();
+            return;
+        }
+
+        let rows = self.config_editor_rows();
+        let ModalState::ConfigEditor(form) = &mut self.modal_state else {
+            return;
+        };
+        if key == KeyCode::Esc {
+            if form.dirty && !form.discard_armed {
+                form.discard_armed = true;
+                form.status = Some("Unsaved changes: Esc again to discard them, Ctrl+S to save".to_string());
+            } else {
+                self.modal_state = ModalState::None;
+            }
+            return;
+        }
+        form.discard_armed = false;
+        form.status = None;
+
+        match (key, modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => form.insert(c),
+            (KeyCode::Enter, _) => form.insert('\n'),
+            (KeyCode::Tab, _) => INDENT.chars().for_each(|c| form.insert(c)),
+            (KeyCode::Backspace, _) => form.backspace(),
+            (KeyCode::Delete, _) => form.delete(),
+            (KeyCode::Left, _) if form.col > 0 => form.col -= 1,
+            (KeyCode::Left, _) if form.row > 0 => {
+                form.row -= 1;
+                form.col = form.line_len();
+            },
+            (KeyCode::Right, _) if form.col < form.line_len() => form.col += 1,
+            (KeyCode::Right, _) if form.row + 1 < form.lines.len() => {
+                form.row += 1;
+                form.col = 0;
+            },
+            (KeyCode::Up, _) => form.go_to_row(form.row.saturating_sub(1)),
+            (KeyCode::Down, _) => form.go_to_row(form.row + 1),
+            (KeyCode::PageUp, _) => form.go_to_row(form.row.saturating_sub(rows)),
+            (KeyCode::PageDown, _) => form.go_to_row(form.row + rows),
+            (KeyCode::Home, _) => form.col = 0,
+            (KeyCode::End, _) => form.col = form.line_len(),
+            _ => {}
+        }
+        form.scroll = form.first_visible_row(rows);
+    }
+
+    /// Check the edited file and, when it parses, write it and switch to it
+    fn save_config_editor(&mut self) {
+        let ModalState::ConfigEditor(form) = &mut self.modal_state else {
+            return;
+        };
+        let text = form.text();
+        let config = match Config::from_json(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                // Point at the error
+                form.go_to_row(e.line().saturating_sub(1));
+                form.col = e.column().saturating_sub(1).min(form.line_len());
+                form.status = Some(format!("Not saved: {}", e));
+                return;
+            },
+        };
+        if let Err(e) = Config::write_raw(&text) {
+            form.status = Some(format!("Not saved: {}", e));
+            return;
+        }
+
+        self.modal_state = ModalState::None;
+        self.reload_config(config);
+        self.clear_history();
+        self.set_message("Config saved and reloaded".to_string(), MessageType::Success);
+    }
+
+    /// Switch to a config that changed on disk
+    fn reload_config(&mut self, config: Config) {
+        self.config = config;
+        self.selected_key = self.selected_key.min(self.config.keys.len().saturating_sub(1));
+        self.selected_group = self.selected_group.min(self.config.groups.len().saturating_sub(1));
+        let tags = self.config.all_tags();
+        self.tag_filter.retain(|tag| tags.contains(tag));
+        self.selected_host = self.selected_host.min(self.visible_hosts().len().saturating_sub(1));
+        self.probe_reports.clear();
+        self.last_probe_run = None;
+        self.apply_theme();
+    }
+}
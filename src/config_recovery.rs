@@ -0,0 +1,87 @@
+use crate::{AppState, ConfigRecoveryForm, MessageType, ModalState};
+use crate::config::Config;
+use crate::config_crypto;
+use std::fs;
+
+/// What to do about a config file that does not parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryChoice {
+    LoadBackup,
+    StartFresh,
+    Quit,
+}
+
+impl RecoveryChoice {
+    pub fn label(self) -> &'static str {
+        match self {
+            RecoveryChoice::LoadBackup => "Load the backup from before the last save",
+            RecoveryChoice::StartFresh => "Start with an empty config",
+            RecoveryChoice::Quit => "Quit and fix config.json by hand",
+        }
+    }
+}
+
+impl AppState {
+    /// Ask what to do about a config file that does not parse. The config
+    /// in use is an empty stand-in until then, and is never saved.
+    pub fn offer_config_recovery(&mut self, reason: String) {
+        let has_backup = Config::backup_path().is_ok_and(|path| path.exists());
+        let choices = [RecoveryChoice::LoadBackup, RecoveryChoice::StartFresh, RecoveryChoice::Quit]
+            .into_iter()
+            .filter(|choice| has_backup || *choice != RecoveryChoice::LoadBackup)
+            .collect();
+        self.modal_state = ModalState::ConfigRecovery(ConfigRecoveryForm { reason, choices, cursor: 0 });
+    }
+
+    pub fn recovering_config(&self) -> bool {
+        matches!(self.modal_state, ModalState::ConfigRecovery(_))
+    }
+
+    pub fn recover_config(&mut self, form: ConfigRecoveryForm) {
+        let Some(&choice) = form.choices.get(form.cursor) else {
+            return;
+        };
+        let result = match choice {
+            RecoveryChoice::LoadBackup => self.load_config_backup(),
+            RecoveryChoice::StartFresh => self.start_fresh_config(),
+            RecoveryChoice::Quit => {
+                self.quit_requested = true;
+                return;
+            },
+        };
+        match result {
+            Ok(message) => {
+                self.modal_state = ModalState::None;
+                self.set_message(message, MessageType::Success);
+                self.apply_theme();
+            },
+            Err(e) => self.set_message(e, MessageType::Error),
+        }
+    }
+
+    fn load_config_backup(&mut self) -> Result<String, String> {
+        let backup_path = Config::backup_path().map_err(|e| e.to_string())?;
+        let backup = fs::read_to_string(&backup_path)
+            .map_err(|e| format!("Could not read {}: {}", backup_path.display(), e))?;
+        if !config_crypto::is_encrypted(&backup) {
+            Config::from_json(&backup).map_err(|e| format!("The backup is damaged too: {}", e))?;
+        }
+        Config::restore_backup().map_err(|e| e.to_string())?;
+        // The passphrase can only be typed before the interface takes the terminal
+        if config_crypto::is_encrypted(&backup) && !config_crypto::is_enabled() {
+            self.quit_note = Some("Restored config.json from its backup; start sshtui again to enter its passphrase".to_string());
+            self.quit_requested = true;
+            return Ok(String::new());
+        }
+        self.config = Config::load().map_err(|e| format!("The backup does not load either: {}", e))?;
+        Ok("Config restored from its backup; the damaged file is kept as config.json.corrupt".to_string())
+    }
+
+    fn start_fresh_config(&mut self) -> Result<String, String> {
+        Config::set_aside_corrupt().map_err(|e| e.to_string())?;
+        let config = Config::default();
+        config.save().map_err(|e| e.to_string())?;
+        self.config = config;
+        Ok("Started with an empty config; the damaged file is kept as config.json.corrupt".to_string())
+    }
+}
@@ -1,40 +1,256 @@
-use chrono::Local;
+use crate::AppState;
+use crate::config::{CommandWidget, Host, DASHBOARD_WIDGETS};
+use crate::favorites::DASHBOARD_FAVORITES;
+use crate::history::RECENT_FAILURE_DAYS;
+use crate::probe::ProbeStatus;
+use crate::theme;
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
 use ratatui::prelude::*;
+use std::process::Stdio;
+use std::time::{Duration as StdDuration, Instant};
+use tokio::process::Command;
+use tokio::sync::oneshot;
 
-// Simple demo function
-pub fn render_simple_dashboard(_width: u16, _height: u16) -> Text<'static> {
+/// Hosts listed under recent connections
+const RECENT_HOSTS: usize = 5;
+
+/// Days of history the heatmap covers
+const HEATMAP_DAYS: i64 = 28;
+
+/// Shades of a heatmap cell, from no connections to the busiest hour
+const HEATMAP_SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Hosts named under fleet health as down
+const DOWN_HOSTS: usize = 5;
+
+/// How long a command widget's command may run
+const COMMAND_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+/// Lines of a command widget's output shown
+const COMMAND_LINES: usize = 10;
+
+/// One is shown a day, in turn
+const QUOTES: [(&str, &str); 8] = [
+    ("There is no place like 127.0.0.1.", "Anonymous"),
+    ("Simplicity is prerequisite for reliability.", "Edsger W. Dijkstra"),
+    ("Hope is not a strategy.", "Traditional SRE saying"),
+    ("Everything fails, all the time.", "Werner Vogels"),
+    ("If it hurts, do it more often.", "Martin Fowler"),
+    ("Weeks of coding can save you hours of planning.", "Anonymous"),
+    ("Make it work, make it right, make it fast.", "Kent Beck"),
+    ("It's not DNS. There's no way it's DNS. It was DNS.", "Anonymous"),
+];
+
+/// Short form of a duration, such as "45s", "12m" or "3h 5m"
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// When something happened, such as "Oct 14 09:30 (2d 3h ago)"
+fn format_when(time: DateTime<Local>) -> String {
+    format!("{} ({} ago)", time.format("%b %d %H:%M"), format_duration(Local::now() - time))
+}
+
+
+/// Latest output of a command widget, and the run that will replace it
+#[derive(Default)]
+pub struct CommandOutput {
+    lines: Vec<String>,
+    failed: bool,
+    refreshed_at: Option<Instant>,
+    pending: Option<oneshot::Receiver<(Vec<String>, bool)>>,
+}
+
+/// Run a widget's command through the shell; the flag is set when it failed
+async fn run_widget_command(command: String) -> (Vec<String>, bool) {
+    let output = Command::new("sh")
+        .args(["-c", &command])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(COMMAND_TIMEOUT, output).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            let lines = text.lines().take(COMMAND_LINES).map(|line| line.trim_end().to_string()).collect();
+            (lines, !output.status.success())
+        },
+        Ok(Err(e)) => (vec![format!("Could not run: {}", e)], true),
+        Err(_) => (vec!["timed out".to_string()], true),
+    }
+}
+
+impl AppState {
+    /// Command widgets on the dashboard, in the order they are listed
+    fn dashboard_commands(&self) -> Vec<&CommandWidget> {
+        let settings = &self.config.settings;
+        settings.dashboard_widgets.iter()
+            .filter_map(|name| settings.dashboard_commands.iter().find(|widget| &widget.name == name))
+            .collect()
+    }
+
+    /// Collect finished command widget runs and start the ones that are due,
+    /// while the dashboard is on screen
+    pub fn poll_dashboard_commands(&mut self) {
+        let widgets: Vec<CommandWidget> = self.dashboard_commands().into_iter().cloned().collect();
+        self.command_widgets.retain(|name, _| widgets.iter().any(|widget| &widget.name == name));
+        for output in self.command_widgets.values_mut() {
+            let Some(pending) = &mut output.pending else {
+                continue;
+            };
+            if let Ok((lines, failed)) = pending.try_recv() {
+                output.lines = lines;
+                output.failed = failed;
+                output.pending = None;
+            }
+        }
+
+        if self.ssh_client.is_connected() {
+            return;
+        }
+        for widget in widgets {
+            let output = self.command_widgets.entry(widget.name.clone()).or_default();
+            let interval = StdDuration::from_secs(widget.interval_secs.max(1));
+            if output.pending.is_some() || output.refreshed_at.is_some_and(|at| at.elapsed() < interval) {
+                continue;
+            }
+            let (sender, receiver) = oneshot::channel();
+            tokio::spawn(async move {
+                let _ = sender.send(run_widget_command(widget.command).await);
+            });
+            output.refreshed_at = Some(Instant::now());
+            output.pending = Some(receiver);
+        }
+    }
+}
+
+/// Whether `name` can go in the dashboard widget list
+pub fn is_widget(app: &AppState, name: &str) -> bool {
+    DASHBOARD_WIDGETS.contains(&name) || app.config.settings.dashboard_commands.iter().any(|widget| widget.name == name)
+}
+
+fn heading(text: String, color: Color) -> Line<'static> {
+    Line::from(Span::styled(text, Style::default().fg(color).add_modifier(Modifier::BOLD)))
+}
+
+pub fn render_dashboard(app: &AppState, _width: u16, height: u16) -> Text<'_> {
+    let theme = theme::current();
     let mut lines = Vec::new();
-    
-    lines.push(Line::from(vec![
-        Span::styled(
-            "🦀 Rust SSH TUI Demo",
-            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
-        )
-    ]));
-    
-    lines.push(Line::from("Raw terminal in panel concept demonstrated!"));
-    
+
+    for name in &app.config.settings.dashboard_widgets {
+        let before = lines.len();
+        match name.as_str() {
+            "welcome" => welcome(&mut lines),
+            "stats" => stats(app, &mut lines),
+            "actions" => actions(app, &mut lines),
+            "favorites" => favorites(app, &mut lines),
+            "host" => selected_host(app, &mut lines),
+            "recent" => recent(app, &mut lines),
+            "heatmap" => heatmap(app, &mut lines),
+            "health" => health(app, &mut lines),
+            "focus" => focus(app, &mut lines),
+            "clock" => clock(&mut lines),
+            "quote" => quote(&mut lines),
+            name => command(app, name, &mut lines),
+        }
+        // Widgets with something to show are set apart by a blank line
+        if lines.len() > before {
+            lines.push(Line::from(""));
+        }
+    }
+    if lines.last().is_some_and(|line| line.width() == 0) {
+        lines.pop();
+    }
+
+    // Truncate if needed to fit in panel
+    if lines.len() > height as usize {
+        lines.truncate(height as usize - 1);
+        lines.push(Line::from(vec![
+            Span::styled("... (content truncated)", Style::default().fg(theme.muted))
+        ]));
+    }
+
     Text::from(lines)
 }
 
-// Original function with conditional compilation
-pub fn render_dashboard(app: &crate::AppState, width: u16, height: u16) -> Text {
-    let mut lines = Vec::new();
-    
-    // Welcome message
+/// Everything about the selected host, to check before connecting: where
+/// it goes and as whom, where it is filed, how the last attempt went, and
+/// its notes and its group's
+pub fn render_host_card(app: &AppState, host: &Host) -> Text<'static> {
+    let theme = theme::current();
+    let effective = app.config.effective_host(host);
+    let inherited = |own: bool| if own { String::new() } else { " (group default)".to_string() };
+    let mut lines = vec![heading(format!("🔎 {}", host.name.to_uppercase()), theme.highlight), Line::from("")];
+    let mut row = |label: &str, value: String, color: Color| {
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<12}", label), Style::default().fg(theme.subtle)),
+            Span::styled(value, Style::default().fg(color)),
+        ]));
+    };
+
+    row("Address", effective.address(), theme.accent);
+    if effective.kind.uses_ssh() {
+        row("User", format!("{}{}", effective.user, inherited(!host.user.is_empty())), theme.text);
+        row("Port", format!("{}{}", effective.port, inherited(host.port != 0)), theme.text);
+        let key = match (&effective.key_path, app.config.get_default_key()) {
+            (Some(path), _) => format!("{}{}", path, inherited(host.key_path.is_some())),
+            (None, Some(key)) => format!("{} (default key)", key.path),
+            (None, None) => "ssh agent".to_string(),
+        };
+        row("Key", key, theme.text);
+    }
+    let group = app.config.group_of(host);
+    row("Group", group.map_or_else(|| "none".to_string(), |group| group.name.clone()), theme.text);
+    let tags = if host.tags.is_empty() { "none".to_string() } else { host.tags.join(", ") };
+    row("Tags", tags, theme.info);
+    if let Some(instance) = &host.ec2_instance {
+        row("EC2", instance.clone(), theme.text);
+    }
+
+    let stats = app.history.stats(host.id);
+    let failed_last = stats.last_failure
+        .filter(|failure| stats.last_connected.is_none_or(|connected| failure.failed_at > connected));
+    let (result, color) = match (failed_last, stats.last_connected) {
+        (Some(failure), _) => (format!("✗ failed {}: {}", format_when(failure.failed_at), failure.reason), theme.error),
+        (None, Some(connected)) => (format!("✓ connected {}", format_when(connected)), theme.success),
+        (None, None) => ("never connected".to_string(), theme.muted),
+    };
+    row("Last result", result, color);
+
+    let group_notes = group.map(|group| (format!("📝 NOTES FOR {}", group.name.to_uppercase()), &group.notes));
+    let notes = [Some(("📝 NOTES".to_string(), &host.notes)), group_notes];
+    for (title, text) in notes.into_iter().flatten().filter(|(_, text)| !text.is_empty()) {
+        lines.push(Line::from(""));
+        lines.push(heading(title, theme.info));
+        lines.extend(text.lines().map(|line| Line::from(Span::styled(line.to_string(), Style::default().fg(theme.text)))));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Enter to connect", Style::default().fg(theme.muted))));
+    Text::from(lines)
+}
+
+fn welcome(lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
     lines.push(Line::from(vec![
         Span::styled(
             "🚀 Welcome to SSH TUI Manager (Rust)!",
-            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
         )
     ]));
-    lines.push(Line::from(""));
-    
-    // Statistics section
+}
+
+fn stats(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
     lines.push(Line::from(vec![
         Span::styled(
             "📊 CURRENT STATISTICS",
-            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.success).add_modifier(Modifier::BOLD)
         )
     ]));
     
@@ -43,36 +259,39 @@ pub fn render_dashboard(app: &crate::AppState, width: u16, height: u16) -> Text
     let total_hosts: usize = app.config.groups.iter().skip(1).map(|g| g.hosts.len()).sum();
     
     lines.push(Line::from(vec![
-        Span::styled("🔑 SSH Keys: ", Style::default().fg(Color::Gray)),
+        Span::styled("🔑 SSH Keys: ", Style::default().fg(theme.subtle)),
         Span::styled(
             format!("{}", total_keys),
-            Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.info).add_modifier(Modifier::BOLD)
         )
     ]));
     
     lines.push(Line::from(vec![
-        Span::styled("📁 Groups: ", Style::default().fg(Color::Gray)),
+        Span::styled("📁 Groups: ", Style::default().fg(theme.subtle)),
         Span::styled(
             format!("{}", total_groups),
-            Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.info).add_modifier(Modifier::BOLD)
         )
     ]));
     
     lines.push(Line::from(vec![
-        Span::styled("🖥️  Total Hosts: ", Style::default().fg(Color::Gray)),
+        Span::styled("🖥️  Total Hosts: ", Style::default().fg(theme.subtle)),
         Span::styled(
             format!("{}", total_hosts),
-            Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.info).add_modifier(Modifier::BOLD)
         )
     ]));
-    lines.push(Line::from(""));
-    
-    // Action guidance
+}
+
+/// Quick actions, or the first steps while there are no hosts
+fn actions(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let total_hosts: usize = app.config.groups.iter().skip(1).map(|g| g.hosts.len()).sum();
     if total_hosts > 0 {
         lines.push(Line::from(vec![
             Span::styled(
                 "⚡ QUICK ACTIONS",
-                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+                Style::default().fg(theme.info).add_modifier(Modifier::BOLD)
             )
         ]));
         
@@ -85,14 +304,14 @@ pub fn render_dashboard(app: &crate::AppState, width: u16, height: u16) -> Text
         
         for action in &actions {
             lines.push(Line::from(vec![
-                Span::styled(*action, Style::default().fg(Color::Gray))
+                Span::styled(*action, Style::default().fg(theme.subtle))
             ]));
         }
     } else {
         lines.push(Line::from(vec![
             Span::styled(
                 "🎯 GET STARTED",
-                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
             )
         ]));
         
@@ -105,17 +324,210 @@ pub fn render_dashboard(app: &crate::AppState, width: u16, height: u16) -> Text
         
         for step in &steps {
             lines.push(Line::from(vec![
-                Span::styled(*step, Style::default().fg(Color::Gray))
+                Span::styled(*step, Style::default().fg(theme.subtle))
             ]));
         }
     }
-    lines.push(Line::from(""));
+}
+
+/// Probe results and connection history of the selected host
+fn selected_host(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let Some(host) = app.selected_visible_host() else {
+        return;
+    };
+    if let Some(reports) = app.probe_reports.get(&host.id) {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("🩺 STATUS OF {}", host.name.to_uppercase()),
+                Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)
+            )
+        ]));
+        for report in reports.iter().flatten() {
+            lines.push(Line::from(vec![
+                Span::styled(format!("● {} ", report.label), Style::default().fg(crate::ui::probe_color(report.result.status))),
+                Span::styled(
+                    format!("{} ({}s ago)", report.result.detail, report.checked_at.elapsed().as_secs()),
+                    Style::default().fg(theme.subtle)
+                ),
+            ]));
+        }
+        lines.push(Line::from(""));
+    }
     
-    // Current focus info
+    let stats = app.history.stats(host.id);
+    lines.push(Line::from(vec![
+        Span::styled(
+            format!("📈 CONNECTIONS TO {}", host.name.to_uppercase()),
+            Style::default().fg(theme.info).add_modifier(Modifier::BOLD)
+        )
+    ]));
+    lines.push(Line::from(vec![
+        Span::styled("Last connected: ", Style::default().fg(theme.subtle)),
+        Span::styled(
+            stats.last_connected.map(format_when).unwrap_or_else(|| "never".to_string()),
+            Style::default().fg(theme.text)
+        ),
+    ]));
+    let average = stats.average_session
+        .map(|average| format!(", sessions last {} on average", format_duration(average)))
+        .unwrap_or_default();
+    lines.push(Line::from(vec![
+        Span::styled("Connections: ", Style::default().fg(theme.subtle)),
+        Span::styled(format!("{}{}", stats.connections, average), Style::default().fg(theme.text)),
+    ]));
+    let failures = match stats.last_failure {
+        Some(last) => Span::styled(
+            format!("{}, last {}: {}", stats.recent_failures, format_when(last.failed_at), last.reason),
+            Style::default().fg(theme.error)
+        ),
+        None => Span::styled("none", Style::default().fg(theme.success)),
+    };
+    lines.push(Line::from(vec![
+        Span::styled(format!("Failures in the last {} days: ", RECENT_FAILURE_DAYS), Style::default().fg(theme.subtle)),
+        failures,
+    ]));
+}
+
+/// Hosts used most recently
+fn recent(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let recent = app.history.recent_hosts(RECENT_HOSTS);
+    if recent.is_empty() {
+        return;
+    }
+    lines.push(Line::from(vec![
+        Span::styled(
+            "🕘 RECENT CONNECTIONS",
+            Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)
+        )
+    ]));
+    for entry in recent {
+        lines.push(Line::from(vec![
+            Span::styled(format!("• {} ", entry.host_name), Style::default().fg(theme.text)),
+            Span::styled(format_when(entry.connected_at), Style::default().fg(theme.subtle)),
+        ]));
+    }
+}
+
+/// Starred hosts, each with the number key that connects to it
+fn favorites(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let favorites = app.favorite_hosts();
+    if favorites.is_empty() {
+        return;
+    }
+    lines.push(heading("★ FAVORITES".to_string(), theme.highlight));
+    for (i, host) in favorites.iter().take(DASHBOARD_FAVORITES).enumerate() {
+        lines.push(Line::from(vec![
+            Span::styled(format!("[{}] ", i + 1), Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{} ", host.name), Style::default().fg(theme.text)),
+            Span::styled(app.config.effective_host(host).address(), Style::default().fg(theme.subtle)),
+        ]));
+    }
+    if favorites.len() > DASHBOARD_FAVORITES {
+        lines.push(Line::from(Span::styled(
+            format!("+{} more under Favorites in the Groups panel", favorites.len() - DASHBOARD_FAVORITES),
+            Style::default().fg(theme.muted)
+        )));
+    }
+}
+
+/// Connections of the last weeks by weekday and hour, darker for busier
+fn heatmap(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let since = Local::now() - Duration::days(HEATMAP_DAYS);
+    let mut counts = [[0usize; 24]; 7];
+    for entry in app.history.entries.iter().filter(|entry| entry.connected_at >= since) {
+        let day = entry.connected_at.weekday().num_days_from_monday() as usize;
+        counts[day][entry.connected_at.hour() as usize] += 1;
+    }
+    let busiest = counts.iter().flatten().copied().max().unwrap_or(0);
+
+    lines.push(heading(format!("📅 CONNECTIONS BY HOUR (last {} days)", HEATMAP_DAYS), theme.info));
+    if busiest == 0 {
+        lines.push(Line::from(Span::styled("No connections yet", Style::default().fg(theme.subtle))));
+        return;
+    }
+    lines.push(Line::from(Span::styled(
+        format!("    {:<6}{:<6}{:<6}{:<6}", 0, 6, 12, 18),
+        Style::default().fg(theme.muted)
+    )));
+    for (day, hours) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().zip(counts) {
+        let cells: String = hours.iter()
+            .map(|&count| match count {
+                0 => HEATMAP_SHADES[0],
+                // Any connection at all shows, however quiet the hour
+                count => HEATMAP_SHADES[(count * (HEATMAP_SHADES.len() - 1)).div_ceil(busiest)],
+            })
+            .collect();
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} ", day), Style::default().fg(theme.subtle)),
+            Span::styled(cells, Style::default().fg(theme.accent)),
+        ]));
+    }
+}
+
+/// How many hosts are up by their probes, and which are down
+fn health(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let (mut up, mut degraded, mut unchecked) = (0, 0, 0);
+    let mut down = Vec::new();
+    for host in app.config.get_hosts_for_group(0) {
+        let statuses = app.probe_reports.get(&host.id)
+            .map(|reports| reports.iter().flatten().map(|report| report.result.status).collect::<Vec<_>>())
+            .unwrap_or_default();
+        // Hosts without probes count as up once a reachability check saw them
+        let status = if statuses.contains(&ProbeStatus::Down) {
+            Some(ProbeStatus::Down)
+        } else if statuses.contains(&ProbeStatus::Degraded) {
+            Some(ProbeStatus::Degraded)
+        } else if !statuses.is_empty() {
+            Some(ProbeStatus::Up)
+        } else {
+            app.reachability.get(&host.id)
+                .map(|&reachable| if reachable { ProbeStatus::Up } else { ProbeStatus::Down })
+        };
+        match status {
+            Some(ProbeStatus::Up) => up += 1,
+            Some(ProbeStatus::Degraded) => degraded += 1,
+            Some(ProbeStatus::Down) => down.push(host.name),
+            None => unchecked += 1,
+        }
+    }
+
+    lines.push(heading("🩺 FLEET HEALTH".to_string(), theme.highlight));
+    let count = |label: &str, value: usize, color: Color| {
+        vec![
+            Span::styled(format!("{} ", value), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{}  ", label), Style::default().fg(theme.subtle)),
+        ]
+    };
+    let mut spans = count("up", up, theme.success);
+    spans.extend(count("degraded", degraded, crate::ui::probe_color(ProbeStatus::Degraded)));
+    spans.extend(count("down", down.len(), theme.error));
+    spans.extend(count("unchecked", unchecked, theme.muted));
+    lines.push(Line::from(spans));
+    if !down.is_empty() {
+        let more = down.len().saturating_sub(DOWN_HOSTS);
+        let mut names = down.into_iter().take(DOWN_HOSTS).collect::<Vec<_>>().join(", ");
+        if more > 0 {
+            names.push_str(&format!(" and {} more", more));
+        }
+        lines.push(Line::from(vec![
+            Span::styled("Down: ", Style::default().fg(theme.subtle)),
+            Span::styled(names, Style::default().fg(theme.error)),
+        ]));
+    }
+}
+
+/// Current focus info
+fn focus(app: &AppState, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
     lines.push(Line::from(vec![
         Span::styled(
             "🎯 CURRENT FOCUS",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.error).add_modifier(Modifier::BOLD)
         )
     ]));
     
@@ -135,61 +547,46 @@ pub fn render_dashboard(app: &crate::AppState, width: u16, height: u16) -> Text
     lines.push(Line::from(vec![
         Span::styled(
             format!("Panel: {} | Sub-focus: {}", focus_area, focus_sub_area),
-            Style::default().fg(Color::Gray)
-        )
-    ]));
-    lines.push(Line::from(""));
-    
-    // Inspirational quote
-    let quotes = [
-        "\"Secure connections, infinite possibilities.\"",
-        "\"SSH: Your gateway to remote worlds.\"", 
-        "\"Connect securely, work efficiently.\"",
-        "\"Remote access made simple and secure.\"",
-        "\"One key, many doors.\"",
-        "\"Raw terminal power in the palm of your hand.\"",
-    ];
-    
-    let quote_index = (Local::now().timestamp() as usize) % quotes.len();
-    lines.push(Line::from(vec![
-        Span::styled(
-            quotes[quote_index],
-            Style::default().fg(Color::Magenta).add_modifier(Modifier::ITALIC)
+            Style::default().fg(theme.subtle)
         )
     ]));
-    lines.push(Line::from(""));
-    
-    // Current time
+}
+
+fn clock(lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
     let current_time = Local::now().format("%a %b %d, %Y %H:%M:%S").to_string();
     lines.push(Line::from(vec![
         Span::styled(
             format!("🕒 {}", current_time),
-            Style::default().fg(Color::LightBlue).add_modifier(Modifier::ITALIC)
+            Style::default().fg(theme.info).add_modifier(Modifier::ITALIC)
         )
     ]));
-    lines.push(Line::from(""));
-    
-    // Rust advantage note
-    lines.push(Line::from(vec![
-        Span::styled(
-            "⚡ This Rust version features raw SSH terminal in-panel!",
-            Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)
-        )
-    ]));
-    lines.push(Line::from(vec![
-        Span::styled(
-            "Perfect for vim, htop, and other TUI apps without mode switching!",
-            Style::default().fg(Color::LightGreen)
-        )
-    ]));
-    
-    // Truncate if needed to fit in panel
-    if lines.len() > height as usize {
-        lines.truncate(height as usize - 1);
-        lines.push(Line::from(vec![
-            Span::styled("... (content truncated)", Style::default().fg(Color::DarkGray))
-        ]));
+}
+
+fn quote(lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    let (text, author) = QUOTES[Local::now().ordinal0() as usize % QUOTES.len()];
+    lines.push(Line::from(Span::styled(format!("💬 \"{}\"", text), Style::default().fg(theme.text).add_modifier(Modifier::ITALIC))));
+    lines.push(Line::from(Span::styled(format!("   — {}", author), Style::default().fg(theme.subtle))));
+}
+
+/// Output of the command widget called `name`
+fn command(app: &AppState, name: &str, lines: &mut Vec<Line<'static>>) {
+    let theme = theme::current();
+    if !app.config.settings.dashboard_commands.iter().any(|widget| widget.name == name) {
+        return;
+    }
+    lines.push(heading(format!("▶ {}", name.to_uppercase()), theme.accent));
+    let Some(output) = app.command_widgets.get(name).filter(|output| !output.lines.is_empty()) else {
+        let status = match app.command_widgets.get(name) {
+            Some(output) if output.pending.is_none() => "(no output)",
+            _ => "running...",
+        };
+        lines.push(Line::from(Span::styled(status, Style::default().fg(theme.muted))));
+        return;
+    };
+    let color = if output.failed { theme.error } else { theme.text };
+    for line in &output.lines {
+        lines.push(Line::from(Span::styled(line.clone(), Style::default().fg(color))));
     }
-    
-    Text::from(lines)
 }
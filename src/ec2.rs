@@ -0,0 +1,251 @@
+use crate::{AppState, ConfirmAction, ConfirmForm, MessageType, ModalState};
+use crate::config::{Ec2Source, Group, Host, Trashed};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use uuid::Uuid;
+
+/// An instance as EC2 describes it
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub id: String,
+    /// Its Name tag
+    pub name: Option<String>,
+    pub public_ip: Option<String>,
+    pub private_ip: Option<String>,
+}
+
+/// A lookup of a group's instances in progress
+pub struct Ec2Refresh {
+    group: Uuid,
+    receiver: oneshot::Receiver<Result<Vec<Instance>, String>>,
+}
+
+/// The EC2 API filter for one of the source's tags
+#[cfg(feature = "aws")]
+fn tag_filter(tag: &str) -> aws_sdk_ec2::types::Filter {
+    let filter = aws_sdk_ec2::types::Filter::builder();
+    match tag.split_once('=') {
+        Some((key, value)) => filter.name(format!("tag:{}", key.trim())).values(value.trim()),
+        None => filter.name("tag-key").values(tag.trim()),
+    }
+    .build()
+}
+
+/// The running instances the source selects, with the credentials the AWS
+/// CLI would use
+#[cfg(feature = "aws")]
+pub async fn describe(source: &Ec2Source) -> Result<Vec<Instance>, String> {
+    use aws_sdk_ec2::error::DisplayErrorContext;
+    use aws_sdk_ec2::types::Filter;
+
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        .region(aws_config::Region::new(source.region.clone()));
+    if let Some(profile) = &source.aws_profile {
+        loader = loader.profile_name(profile);
+    }
+    let client = aws_sdk_ec2::Client::new(&loader.load().await);
+
+    let mut request = client.describe_instances()
+        .filters(Filter::builder().name("instance-state-name").values("pending").values("running").build());
+    for tag in source.tags.iter().filter(|tag| !tag.trim().is_empty()) {
+        request = request.filters(tag_filter(tag));
+    }
+
+    let mut instances = Vec::new();
+    let mut pages = request.into_paginator().send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(|e| DisplayErrorContext(e).to_string())?;
+        for instance in page.reservations().iter().flat_map(|reservation| reservation.instances()) {
+            let Some(id) = instance.instance_id() else {
+                continue;
+            };
+            let name = instance.tags().iter()
+                .find(|tag| tag.key() == Some("Name"))
+                .and_then(|tag| tag.value())
+                .filter(|name| !name.trim().is_empty());
+            instances.push(Instance {
+                id: id.to_string(),
+                name: name.map(|name| name.trim().to_string()),
+                public_ip: instance.public_ip_address().map(str::to_string),
+                private_ip: instance.private_ip_address().map(str::to_string),
+            });
+        }
+    }
+    Ok(instances)
+}
+
+#[cfg(not(feature = "aws"))]
+pub async fn describe(_source: &Ec2Source) -> Result<Vec<Instance>, String> {
+    Err("This sshtui was built without AWS support; build it with `--features aws`".to_string())
+}
+
+/// What a refresh changed in a group
+#[derive(Debug, Clone, Default)]
+pub struct RefreshSummary {
+    pub added: usize,
+    pub moved: usize,
+    /// Hosts whose instance is gone, taken out of the group for the trash
+    pub gone: Vec<Host>,
+}
+
+impl RefreshSummary {
+    /// Such as "2 added, 1 with a new address, 3 gone to the trash"
+    pub fn describe(&self) -> String {
+        if self.added + self.moved + self.gone.len() == 0 {
+            return "no changes".to_string();
+        }
+        let mut parts = Vec::new();
+        if self.added > 0 {
+            parts.push(format!("{} added", self.added));
+        }
+        if self.moved > 0 {
+            parts.push(format!("{} with a new address", self.moved));
+        }
+        if !self.gone.is_empty() {
+            parts.push(format!("{} gone to the trash", self.gone.len()));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Whether applying the lookup would leave none of the group's EC2 hosts,
+/// which more often means wrong credentials or tags than no instances
+pub fn empties_group(group: &Group, instances: &[Instance]) -> bool {
+    instances.is_empty() && group.hosts.iter().any(|host| host.ec2_instance.is_some())
+}
+
+/// Make the group's EC2 hosts the instances. Hosts are matched by the
+/// instance they were discovered as, so their names, notes and other
+/// settings are kept and only their address follows the instance; hosts
+/// added by hand are left alone. New instances are named by their Name tag,
+/// with the instance id added when several share one or the name is taken,
+/// or by the id alone without one.
+pub fn update_group(group: &mut Group, source: &Ec2Source, mut instances: Vec<Instance>) -> RefreshSummary {
+    let base_name = |instance: &Instance| instance.name.clone().unwrap_or_else(|| instance.id.clone());
+    instances.sort_by_key(|instance| (base_name(instance), instance.id.clone()));
+
+    let mut summary = RefreshSummary::default();
+    for instance in &instances {
+        let address = if source.private_ip {
+            instance.private_ip.clone().or_else(|| instance.public_ip.clone())
+        } else {
+            instance.public_ip.clone().or_else(|| instance.private_ip.clone())
+        };
+        let Some(address) = address else {
+            continue;
+        };
+        if let Some(host) = group.hosts.iter_mut().find(|host| host.ec2_instance.as_ref() == Some(&instance.id)) {
+            if host.host != address {
+                host.host = address;
+                summary.moved += 1;
+            }
+            continue;
+        }
+
+        let base = base_name(instance);
+        let shared = instances.iter().filter(|other| base_name(other) == base).count() > 1;
+        let taken = group.hosts.iter().any(|host| host.name == base);
+        let name = if (shared && instance.name.is_some()) || taken { format!("{} ({})", base, instance.id) } else { base };
+        group.hosts.push(Host { ec2_instance: Some(instance.id.clone()), ..Host::new(name, address) });
+        summary.added += 1;
+    }
+
+    let (gone, kept) = std::mem::take(&mut group.hosts).into_iter()
+        .partition(|host| host.ec2_instance.as_ref().is_some_and(|id| !instances.iter().any(|instance| &instance.id == id)));
+    group.hosts = kept;
+    summary.gone = gone;
+    summary
+}
+
+impl AppState {
+    /// Look up the selected group's EC2 instances again in the background
+    pub fn refresh_ec2_group(&mut self) {
+        if self.ec2_refresh.is_some() {
+            self.set_message("Already looking up EC2 instances".to_string(), MessageType::Info);
+            return;
+        }
+        let group = match self.config.groups.get(self.selected_group) {
+            Some(group) if self.selected_group > 0 && self.virtual_group.is_none() => group,
+            _ => {
+                self.set_message("Select a group filled from EC2 first".to_string(), MessageType::Error);
+                return;
+            },
+        };
+        let Some(source) = group.ec2.clone() else {
+            let hint = format!("Group '{}' is not filled from EC2; set it up with `sshtui ec2 --group \"{}\" --region ...`", group.name, group.name);
+            self.set_message(hint, MessageType::Error);
+            return;
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let _ = sender.send(describe(&source).await);
+        });
+        self.ec2_refresh = Some(Ec2Refresh { group: group.id, receiver });
+        self.set_message(format!("Looking up EC2 instances for '{}'...", group.name), MessageType::Info);
+    }
+
+    pub fn poll_ec2_refresh(&mut self) {
+        let Some(refresh) = &mut self.ec2_refresh else {
+            return;
+        };
+        let outcome = match refresh.receiver.try_recv() {
+            Ok(outcome) => outcome,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Closed) => Err("The lookup stopped unexpectedly".to_string()),
+        };
+        let group_id = refresh.group;
+        self.ec2_refresh = None;
+
+        let instances = match outcome {
+            Ok(instances) => instances,
+            Err(e) => {
+                self.set_message(format!("Could not look up EC2 instances: {}", e), MessageType::Error);
+                return;
+            },
+        };
+        // The group may have been deleted while waiting
+        let Some(group) = self.config.groups.iter().find(|group| group.id == group_id) else {
+            return;
+        };
+        if empties_group(group, &instances) {
+            self.ec2_empty = Some(group_id);
+            self.modal_state = ModalState::Confirm(ConfirmForm {
+                message: format!("EC2 returned no instances for '{}'. Move all of its EC2 hosts to the trash?", group.name),
+                action: ConfirmAction::EmptyEc2Group,
+                confirm_selected: false,
+            });
+            return;
+        }
+        self.apply_ec2_refresh(group_id, instances);
+    }
+
+    /// Empty the group the user agreed to after a lookup found no instances
+    pub fn accept_empty_ec2_refresh(&mut self) {
+        if let Some(group_id) = self.ec2_empty.take() {
+            self.apply_ec2_refresh(group_id, Vec::new());
+        }
+    }
+
+    fn apply_ec2_refresh(&mut self, group_id: Uuid, instances: Vec<Instance>) {
+        // The group may have been deleted or changed while waiting
+        let Some(index) = self.config.groups.iter().position(|group| group.id == group_id) else {
+            return;
+        };
+        let Some(source) = self.config.groups[index].ec2.clone() else {
+            return;
+        };
+
+        self.empty_old_trash();
+        let before = self.snapshot();
+        let summary = update_group(&mut self.config.groups[index], &source, instances);
+        let name = self.config.groups[index].name.clone();
+        let description = summary.describe();
+        for host in summary.gone {
+            self.move_to_trash(Trashed::Host { group: name.clone(), host: Box::new(host) });
+        }
+        self.record_change(before, format!("Refreshed group '{}' from EC2", name));
+        self.selected_host = self.selected_host.min(self.visible_hosts().len().saturating_sub(1));
+        let _ = self.config.save();
+        self.set_message(format!("Refreshed '{}' from EC2: {}", name, description), MessageType::Success);
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the config and ssh modules, so callers can tell what
+/// can be done about them without reading the message
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The config file could not be read, decrypted, parsed or written, or
+    /// the change asked of it makes no sense
+    #[error("{message}")]
+    Config {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// The server rejected the credentials offered
+    #[error("{0}")]
+    Auth(String),
+    /// The connection could not be made, is gone, or cannot take the request yet
+    #[error("{0}")]
+    Ssh(String),
+    /// No pseudo-terminal could be opened, or ssh could not be started in it
+    #[error("PTY error: {0}")]
+    Pty(String),
+    /// Reading or writing a live session failed
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// What can be done about an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Trying again may work, e.g. after a dropped connection
+    Retryable,
+    /// The user has to change something first, such as a key or the config
+    UserFixable,
+    /// Retrying or editing settings won't help
+    Fatal,
+}
+
+impl Error {
+    pub fn config(message: impl Into<String>) -> Self {
+        Error::Config { message: message.into(), source: None }
+    }
+
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Error::Io { context: context.into(), source }
+    }
+
+    /// Whether the config file was read but holds no valid config, as after
+    /// a crash cut a write short
+    pub fn is_corrupt_config(&self) -> bool {
+        matches!(self, Error::Config { source: Some(source), .. } if source.is::<serde_json::Error>())
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Config { .. } | Error::Auth(_) => ErrorKind::UserFixable,
+            Error::Ssh(_) => ErrorKind::Retryable,
+            Error::Pty(_) => ErrorKind::Fatal,
+            Error::Io { source, .. } => match source.kind() {
+                io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => ErrorKind::UserFixable,
+                _ => ErrorKind::Retryable,
+            },
+        }
+    }
+}
+
+/// `with_context` for results that become `Error::Config`
+pub trait ConfigContext<T> {
+    fn config_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: std::error::Error + Send + Sync + 'static> ConfigContext<T> for std::result::Result<T, E> {
+    fn config_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|e| Error::Config { message: context().to_string(), source: Some(Box::new(e)) })
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{AppState, BroadcastForm, FactsForm, MessageType, ModalState};
+use crate::broadcast::TargetSet;
+use crate::config_crypto;
+use crate::config::{Config, Host};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+/// Facts older than this are collected again on the next sync
+const MAX_AGE_HOURS: i64 = 24;
+
+/// Prints one `key=value` line per fact; tools a host lacks are skipped
+const COLLECT_SCRIPT: &str = r#"[ -r /etc/os-release ] && . /etc/os-release
+echo "os=$ID"
+echo "version=$VERSION_ID"
+echo "pretty_name=$PRETTY_NAME"
+echo "kernel=$(uname -sr)"
+echo "ips=$( (hostname -I 2>/dev/null || ip -o -4 addr show scope global 2>/dev/null | awk '{print $4}' | cut -d/ -f1) | tr '\n' ' ')"
+if command -v dpkg-query >/dev/null 2>&1; then echo "packages=$(dpkg-query -f '.\n' -W | wc -l)"
+elif command -v rpm >/dev/null 2>&1; then echo "packages=$(rpm -qa | wc -l)"
+elif command -v apk >/dev/null 2>&1; then echo "packages=$(apk info | wc -l)"
+fi"#;
+
+/// Words a search can contain without meaning anything
+const FILLER_WORDS: [&str; 10] = ["hosts", "host", "running", "with", "on", "in", "the", "that", "are", "is"];
+
+/// What a host reported about itself
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostFacts {
+    /// os-release ID, such as ubuntu or rhel
+    pub os: String,
+    pub version: String,
+    pub pretty_name: String,
+    pub kernel: String,
+    pub ips: Vec<String>,
+    /// Installed packages, when the package manager is known
+    pub packages: Option<u32>,
+}
+
+impl HostFacts {
+    fn parse(output: &str) -> Option<Self> {
+        let mut facts = Self::default();
+        for line in output.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().to_string();
+            match key {
+                "os" => facts.os = value,
+                "version" => facts.version = value,
+                "pretty_name" => facts.pretty_name = value,
+                "kernel" => facts.kernel = value,
+                "ips" => facts.ips = value.split_whitespace().map(|ip| ip.to_string()).collect(),
+                "packages" => facts.packages = value.parse().ok(),
+                _ => {}
+            }
+        }
+        (!facts.kernel.is_empty()).then_some(facts)
+    }
+
+    /// One line for lists, such as "Ubuntu 20.04.6 LTS, Linux 5.4.0, 10.0.0.5"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![
+            if self.pretty_name.is_empty() { self.os.clone() } else { self.pretty_name.clone() },
+            self.kernel.clone(),
+        ];
+        if !self.ips.is_empty() {
+            parts.push(self.ips.join(" "));
+        }
+        if let Some(packages) = self.packages {
+            parts.push(format!("{} packages", packages));
+        }
+        parts.retain(|part| !part.is_empty());
+        parts.join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactsEntry {
+    pub facts: HostFacts,
+    pub collected_at: DateTime<Local>,
+    /// When the facts last differed from the previous collection
+    pub changed_at: DateTime<Local>,
+}
+
+/// Facts of every host, by host id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FactsIndex {
+    pub hosts: BTreeMap<Uuid, FactsEntry>,
+}
+
+impl FactsIndex {
+    pub fn load() -> Result<Self> {
+        let path = Self::index_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read facts index: {:?}", path))?;
+        let contents = config_crypto::unseal(contents)?;
+
+        // Files from before hosts had ids list them by name; those facts
+        // are collected again
+        #[derive(Deserialize)]
+        struct Stored {
+            hosts: BTreeMap<String, FactsEntry>,
+        }
+        let stored: Stored = serde_json::from_str(&contents)
+            .with_context(|| "Failed to parse facts index JSON")?;
+        let hosts = stored.hosts.into_iter()
+            .filter_map(|(key, entry)| Uuid::parse_str(&key).ok().map(|id| (id, entry)))
+            .collect();
+        Ok(Self { hosts })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::index_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize facts index")?;
+        config_crypto::write_sealed(&path, contents)
+            .with_context(|| format!("Failed to write facts index: {:?}", path))?;
+
+        Ok(())
+    }
+
+    fn index_path() -> Result<PathBuf> {
+        Ok(Config::config_dir()?.join("facts.json"))
+    }
+
+    fn is_stale(&self, host_id: Uuid) -> bool {
+        self.hosts.get(&host_id)
+            .is_none_or(|entry| Local::now() - entry.collected_at > chrono::Duration::hours(MAX_AGE_HOURS))
+    }
+
+    /// Store freshly collected facts; returns whether they changed
+    fn update(&mut self, host_id: Uuid, facts: HostFacts) -> bool {
+        let now = Local::now();
+        let changed = self.hosts.get(&host_id).is_none_or(|entry| entry.facts != facts);
+        let changed_at = match self.hosts.get(&host_id) {
+            Some(entry) if !changed => entry.changed_at,
+            _ => now,
+        };
+        self.hosts.insert(host_id, FactsEntry { facts, collected_at: now, changed_at });
+        changed
+    }
+}
+
+/// Whether the characters of `word` appear in `name` in order, so that
+/// "wbprd" finds "web-prod"
+fn is_subsequence(word: &str, name: &str) -> bool {
+    let mut name = name.chars();
+    word.chars().all(|c| name.any(|n| n == c))
+}
+
+/// Whether every word of `query` appears in the host's name, address,
+/// tags or facts; words of three or more letters may also be abbreviations
+/// of the name
+fn matches(host: &Host, facts: Option<&HostFacts>, query: &str) -> bool {
+    let mut haystack = vec![host.name.clone(), host.host.clone(), host.user.clone()];
+    haystack.extend(host.tags.iter().map(|tag| format!("#{}", tag)));
+    if let Some(facts) = facts {
+        haystack.extend([facts.os.clone(), facts.version.clone(), facts.pretty_name.clone(), facts.kernel.clone()]);
+        haystack.extend(facts.ips.iter().cloned());
+    }
+    let haystack = haystack.join(" ").to_lowercase();
+    let name = host.name.to_lowercase();
+
+    query.to_lowercase()
+        .split_whitespace()
+        .filter(|word| !FILLER_WORDS.contains(word))
+        .all(|word| haystack.contains(word) || (word.chars().count() >= 3 && is_subsequence(word, &name)))
+}
+
+/// A facts collection in progress
+pub struct FactsSync {
+    receiver: mpsc::UnboundedReceiver<(Uuid, Option<HostFacts>)>,
+    pub pending: usize,
+    changed: usize,
+    failed: usize,
+}
+
+impl AppState {
+    pub fn open_facts(&mut self) {
+        self.modal_state = ModalState::Facts(FactsForm { query: String::new(), cursor: 0 });
+        self.sync_facts();
+    }
+
+    /// Collect facts from hosts that have none or only old ones, and drop
+    /// hosts that are no longer configured
+    fn sync_facts(&mut self) {
+        if self.facts_sync.is_some() {
+            return;
+        }
+        let hosts = self.config.get_hosts_for_group(0);
+        self.facts.hosts.retain(|id, _| hosts.iter().any(|host| host.id == *id));
+
+        let stale: Vec<Host> = hosts.into_iter().filter(|host| self.facts.is_stale(host.id)).collect();
+        if stale.is_empty() {
+            return;
+        }
+        let runs: Vec<_> = stale.iter()
+            .map(|host| self.run_command_on(host, COLLECT_SCRIPT.to_string()))
+            .collect();
+        let parallelism = self.config.settings.broadcast_parallelism.max(1);
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.facts_sync = Some(FactsSync { receiver, pending: stale.len(), changed: 0, failed: 0 });
+        tokio::spawn(async move {
+            let mut results = futures::stream::iter(runs).buffer_unordered(parallelism);
+            while let Some(result) = results.next().await {
+                let facts = if result.succeeded() { HostFacts::parse(&result.output) } else { None };
+                if sender.send((result.host_id, facts)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Fold collected facts into the index and save it once all are in
+    pub fn poll_facts(&mut self) {
+        let Some(sync) = &mut self.facts_sync else {
+            return;
+        };
+        loop {
+            match sync.receiver.try_recv() {
+                Ok((host_id, Some(facts))) => {
+                    sync.pending = sync.pending.saturating_sub(1);
+                    if self.facts.update(host_id, facts) {
+                        sync.changed += 1;
+                    }
+                },
+                Ok((_, None)) => {
+                    // Keep what is known about hosts that could not be reached
+                    sync.pending = sync.pending.saturating_sub(1);
+                    sync.failed += 1;
+                },
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        let (changed, failed) = (sync.changed, sync.failed);
+        self.facts_sync = None;
+
+        match self.facts.save() {
+            Ok(()) if failed > 0 => self.set_message(
+                format!("Host facts updated: {} changed, {} host(s) could not be reached", changed, failed),
+                MessageType::Error
+            ),
+            Ok(()) => self.set_message(format!("Host facts updated: {} changed", changed), MessageType::Success),
+            Err(e) => self.set_message(format!("Could not save host facts: {}", e), MessageType::Error),
+        }
+    }
+
+    /// Hosts matching a facts search: one named exactly as searched first,
+    /// then the hosts used most often and lately
+    pub fn fact_matches(&self, query: &str) -> Vec<Host> {
+        let mut hosts: Vec<Host> = self.config.get_hosts_for_group(0)
+            .into_iter()
+            .filter(|host| matches(host, self.facts.hosts.get(&host.id).map(|entry| &entry.facts), query))
+            .collect();
+        let scores = self.history.frecency();
+        let query = query.trim();
+        hosts.sort_by_key(|host| (
+            !host.name.eq_ignore_ascii_case(query),
+            Reverse(scores.get(&host.id).copied().unwrap_or(0)),
+        ));
+        hosts
+    }
+
+    /// Use the hosts a search found as the targets of a broadcast
+    pub fn broadcast_to_matches(&mut self, form: FactsForm) {
+        let hosts: Vec<Uuid> = self.fact_matches(&form.query).into_iter().map(|host| host.id).collect();
+        if hosts.is_empty() {
+            self.set_message(format!("No hosts match '{}'", form.query), MessageType::Error);
+            return;
+        }
+        let label = if form.query.trim().is_empty() { "all hosts".to_string() } else { format!("'{}'", form.query.trim()) };
+        self.modal_state = ModalState::Broadcast(BroadcastForm {
+            command: String::new(),
+            cursor: 0,
+            targets: Some(TargetSet { label, hosts }),
+            viewing: None,
+            host: 0,
+            scroll: 0,
+        });
+    }
+}
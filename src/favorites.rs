@@ -0,0 +1,72 @@
+use crate::{AppState, MessageType};
+use crate::config::Host;
+use crate::group_tree::{GroupLine, VirtualGroup};
+
+/// Favorites listed on the dashboard, each connected to with its number key
+pub const DASHBOARD_FAVORITES: usize = 5;
+
+impl AppState {
+    /// Starred hosts, in the order of the config
+    pub fn favorite_hosts(&self) -> Vec<Host> {
+        self.config.get_hosts_for_group(0).into_iter().filter(|host| host.favorite).collect()
+    }
+
+    pub fn has_favorites(&self) -> bool {
+        self.config.groups.iter().skip(1).any(|group| group.hosts.iter().any(|host| host.favorite))
+    }
+
+    /// Whether the listed hosts can be edited and deleted in place: a
+    /// group's, the favorites or the recent ones, but not those of the "All"
+    /// view
+    pub fn hosts_editable(&self) -> bool {
+        self.selected_group > 0 || self.virtual_group.is_some()
+    }
+
+    /// Star the selected host, or unstar it
+    pub fn toggle_selected_host_favorite(&mut self) {
+        let Some((id, name)) = self.selected_visible_host().map(|host| (host.id, host.name)) else {
+            return;
+        };
+        let before = self.snapshot();
+        let Some(host) = self.config.find_host_mut(id) else {
+            return;
+        };
+        host.favorite = !host.favorite;
+        let favorite = host.favorite;
+        let description = if favorite { "Starred" } else { "Unstarred" };
+        self.record_change(before, format!("{} host '{}'", description, name));
+        if let Err(e) = self.config.save() {
+            self.set_message(format!("Failed to save favorites: {}", e), MessageType::Error);
+            return;
+        }
+
+        // An unstarred host leaves the favorites list
+        if self.virtual_group == Some(VirtualGroup::Favorites) {
+            let count = self.visible_hosts().len();
+            if count == 0 {
+                self.select_group(GroupLine::Group(0));
+            } else {
+                self.selected_host = self.selected_host.min(count - 1);
+            }
+        }
+        let message = if favorite {
+            format!("Added '{}' to favorites", name)
+        } else {
+            format!("Removed '{}' from favorites", name)
+        };
+        self.set_message(message, MessageType::Info);
+    }
+
+    /// The favorite a number key connects to from the dashboard, when the
+    /// dashboard lists favorites
+    pub fn favorite_for_key(&self, key: char) -> Option<Host> {
+        if !self.config.settings.dashboard_widgets.iter().any(|name| name == "favorites") {
+            return None;
+        }
+        let number = key.to_digit(10)? as usize;
+        if !(1..=DASHBOARD_FAVORITES).contains(&number) {
+            return None;
+        }
+        self.favorite_hosts().into_iter().nth(number - 1)
+    }
+}
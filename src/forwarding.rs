@@ -0,0 +1,229 @@
+use crate::{AppState, ForwardConflictForm, ForwardsForm, MessageType, ModalState};
+use crate::config::{ForwardKind, PortForward};
+use std::net::TcpListener;
+use std::time::Duration;
+
+/// Ports tried after a taken one when looking for a free one
+const FREE_PORT_SEARCH: u16 = 100;
+
+/// Time given to another session to close its tunnel before ours retries
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A tunnel of the live session
+#[derive(Debug, Clone)]
+pub struct Tunnel {
+    pub forward: PortForward,
+    /// Set when the tunnel could not be opened
+    pub error: Option<String>,
+    /// Port asked for, when the tunnel was moved to another one because it
+    /// was taken
+    pub requested_port: Option<u16>,
+}
+
+impl Tunnel {
+    fn new(forward: PortForward) -> Self {
+        Self { forward, error: None, requested_port: None }
+    }
+}
+
+/// Ways out of a tunnel that could not listen on its port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictChoice {
+    /// Listen on this port instead
+    MovePort(u16),
+    /// Close the same tunnel in this host's background session, then retry
+    StopOther(String),
+    Leave,
+}
+
+impl ConflictChoice {
+    pub fn label(&self, forward: &PortForward) -> String {
+        match self {
+            ConflictChoice::MovePort(port) if forward.kind == ForwardKind::Remote => format!("Try port {} on the server", port),
+            ConflictChoice::MovePort(port) => format!("Use port {} instead (free)", port),
+            ConflictChoice::StopOther(host_name) => format!("Close the tunnel in the {} session and retry", host_name),
+            ConflictChoice::Leave => "Leave the tunnel closed".to_string(),
+        }
+    }
+}
+
+/// Whether nothing listens on the local port
+fn port_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+impl AppState {
+    /// Edit the live session's tunnels, or the selected host's configured
+    /// forwards when not connected
+    pub fn open_forwards(&mut self) {
+        let (forwards, live) = if self.ssh_client.is_connected() {
+            (self.tunnels.iter().map(|tunnel| tunnel.forward.clone()).collect::<Vec<_>>(), true)
+        } else {
+            if self.selected_group == 0 {
+                self.set_message("Select the host's group to edit its forwards".to_string(), MessageType::Info);
+                return;
+            }
+            let hosts = self.visible_hosts();
+            let Some(host) = hosts.get(self.selected_host) else {
+                return;
+            };
+            (host.forwards.clone(), false)
+        };
+
+        self.modal_state = ModalState::Forwards(ForwardsForm {
+            cursor: forwards.len(),
+            forwards,
+            new_forward: String::new(),
+            live,
+        });
+    }
+
+    /// Apply the forwards modal, to the live session or the host's config
+    pub fn apply_forwards(&mut self, form: ForwardsForm) {
+        if form.live {
+            self.update_tunnels(form.forwards);
+            return;
+        }
+
+        let hosts = self.visible_hosts();
+        let Some((host_id, host_name)) = hosts.get(self.selected_host).map(|host| (host.id, host.name.clone())) else {
+            return;
+        };
+        let before = self.snapshot();
+        if let Some(host) = self.config.find_host_mut(host_id) {
+            host.forwards = form.forwards;
+            self.record_change(before, format!("Edited the forwards of host '{}'", host_name));
+            let _ = self.config.save();
+            self.set_message(format!("Forwards for '{}' saved", host_name), MessageType::Success);
+        }
+    }
+
+    /// Open and close tunnels on the live session so it matches `wanted`
+    fn update_tunnels(&mut self, wanted: Vec<PortForward>) {
+        let current: Vec<PortForward> = self.tunnels.iter().map(|tunnel| tunnel.forward.clone()).collect();
+        let start: Vec<PortForward> = wanted.iter().filter(|forward| !current.contains(forward)).cloned().collect();
+        let cancel: Vec<PortForward> = current.iter().filter(|forward| !wanted.contains(forward)).cloned().collect();
+        if start.is_empty() && cancel.is_empty() {
+            return;
+        }
+
+        match self.ssh_client.update_forwards(&start, &cancel) {
+            Ok(()) => {
+                self.tunnels.retain(|tunnel| !cancel.contains(&tunnel.forward));
+                self.tunnels.extend(start.into_iter().map(Tunnel::new));
+                self.set_message("Tunnels updated".to_string(), MessageType::Success);
+            },
+            Err(e) => {
+                self.set_message(format!("Could not update tunnels: {}", e), MessageType::Error);
+            }
+        }
+    }
+
+    /// Track the forwards a session was opened with
+    pub fn start_tunnels(&mut self, forwards: &[PortForward]) {
+        self.tunnels = forwards.iter().cloned().map(Tunnel::new).collect();
+    }
+
+    /// Mark a tunnel as failed; when its port is taken, offer another one
+    /// or closing the tunnel that holds it. Failures of tunnels that are not
+    /// ours, such as from an ssh run inside the session, are ignored.
+    pub fn tunnel_failed(&mut self, forward: &PortForward, error: &str) {
+        let Some(tunnel) = self.tunnels.iter_mut().find(|tunnel| tunnel.forward.same_listener(forward)) else {
+            return;
+        };
+        tunnel.error = Some(error.to_string());
+        let forward = tunnel.forward.clone();
+        self.set_message(format!("Tunnel {} failed: {}", forward, error), MessageType::Error);
+
+        // A local port can be checked; on the server any failure is most
+        // likely the port being taken
+        let taken = match forward.kind {
+            ForwardKind::Remote => true,
+            _ => !port_free(forward.bind_port),
+        };
+        if !taken || self.modal_state != ModalState::None {
+            return;
+        }
+
+        let mut choices = Vec::new();
+        if let Some(port) = self.next_port(&forward) {
+            choices.push(ConflictChoice::MovePort(port));
+        }
+        let owner = self.background_sessions.iter().find(|session| {
+            session.tunnels.iter().any(|tunnel| tunnel.error.is_none() && tunnel.forward.same_listener(&forward))
+        });
+        if let Some(session) = owner {
+            choices.push(ConflictChoice::StopOther(session.host_name().to_string()));
+        }
+        choices.push(ConflictChoice::Leave);
+        self.modal_state = ModalState::ForwardConflict(ForwardConflictForm {
+            forward,
+            error: error.to_string(),
+            choices,
+            cursor: 0,
+        });
+    }
+
+    /// The first port after the forward's that no tunnel of ours uses and,
+    /// for local ones, nothing else listens on
+    fn next_port(&self, forward: &PortForward) -> Option<u16> {
+        let in_use = |port: u16| {
+            let moved = PortForward { bind_port: port, ..forward.clone() };
+            self.tunnels.iter()
+                .chain(self.background_sessions.iter().flat_map(|session| session.tunnels.iter()))
+                .any(|tunnel| tunnel.forward.same_listener(&moved))
+        };
+        (1..=FREE_PORT_SEARCH)
+            .filter_map(|offset| forward.bind_port.checked_add(offset))
+            .find(|&port| !in_use(port) && (forward.kind == ForwardKind::Remote || port_free(port)))
+    }
+
+    pub fn resolve_forward_conflict(&mut self, form: ForwardConflictForm) {
+        let Some(choice) = form.choices.get(form.cursor).cloned() else {
+            return;
+        };
+        let forward = form.forward;
+        match choice {
+            ConflictChoice::MovePort(port) => {
+                // The failed tunnel never opened, so there is nothing to cancel
+                let moved = PortForward { bind_port: port, ..forward.clone() };
+                if let Err(e) = self.ssh_client.update_forwards(std::slice::from_ref(&moved), &[]) {
+                    self.set_message(format!("Could not open {}: {}", moved, e), MessageType::Error);
+                    return;
+                }
+                if let Some(tunnel) = self.tunnels.iter_mut().find(|tunnel| tunnel.forward == forward) {
+                    tunnel.requested_port = tunnel.requested_port.or(Some(forward.bind_port));
+                    tunnel.forward = moved.clone();
+                    tunnel.error = None;
+                }
+                self.set_message(format!("Tunnel moved to {}", moved), MessageType::Success);
+            },
+            ConflictChoice::StopOther(host_name) => {
+                let Some(session) = self.background_sessions.iter_mut().find(|session| session.host_name() == host_name) else {
+                    self.set_message(format!("The {} session is gone", host_name), MessageType::Error);
+                    return;
+                };
+                let held: Vec<PortForward> = session.tunnels.iter()
+                    .filter(|tunnel| tunnel.forward.same_listener(&forward))
+                    .map(|tunnel| tunnel.forward.clone())
+                    .collect();
+                if let Err(e) = session.client.update_forwards(&[], &held) {
+                    self.set_message(format!("Could not close the tunnel in the {} session: {}", host_name, e), MessageType::Error);
+                    return;
+                }
+                session.tunnels.retain(|tunnel| !held.contains(&tunnel.forward));
+
+                if let Some(tunnel) = self.tunnels.iter_mut().find(|tunnel| tunnel.forward == forward) {
+                    tunnel.error = None;
+                }
+                let client = self.ssh_client.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    let _ = client.update_forwards(&[forward], &[]);
+                });
+                self.set_message(format!("Closed the tunnel in the {} session; retrying", host_name), MessageType::Info);
+            },
+            ConflictChoice::Leave => {},
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{AppState, MessageType};
+use crate::config::Config;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::process::Command;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// How often the config files are checked for saves to commit
+const COMMIT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What is being done with the config repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitAction {
+    Commit,
+    Pull,
+    Push,
+}
+
+/// Git state of the config directory: when the config files were last
+/// committed, and the git run in progress
+#[derive(Default)]
+pub struct GitSync {
+    /// Modification times of the config files as last handed to git
+    committed: Vec<(PathBuf, Option<SystemTime>)>,
+    last_check: Option<Instant>,
+    /// Whether the run changed anything, or why it failed
+    running: Option<(GitAction, oneshot::Receiver<Result<bool, String>>)>,
+}
+
+/// Config files kept in git, the default profile's and every other
+/// profile's; history, secrets and backups stay on this machine
+fn synced_files() -> Vec<PathBuf> {
+    Config::profiles().iter()
+        .filter_map(|name| Config::profile_path(name).ok())
+        .filter(|path| path.exists())
+        .collect()
+}
+
+fn modification_times(files: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files.iter()
+        .map(|path| (path.clone(), fs::metadata(path).and_then(|metadata| metadata.modified()).ok()))
+        .collect()
+}
+
+/// The line of git's complaints that says what went wrong
+fn git_error(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    lines.iter()
+        .find(|line| line.contains("[rejected]") || line.starts_with("fatal:") || line.starts_with("error:"))
+        .or(lines.last())
+        .map_or_else(|| "git failed".to_string(), |line| line.to_string())
+}
+
+/// Run git in the config directory, returning what it printed. It must
+/// never ask for anything, as the interface has the terminal.
+async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if std::env::var_os("GIT_SSH_COMMAND").is_none() {
+        command.env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes -o ConnectTimeout=10");
+    }
+    let output = command.output().await.map_err(|e| format!("Could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(git_error(&String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn with_files<'a>(args: &[&'a str], files: &'a [String]) -> Vec<&'a str> {
+    args.iter().copied().chain(["--"]).chain(files.iter().map(String::as_str)).collect()
+}
+
+/// Commit the config files if they changed since the last commit
+async fn commit(dir: &Path, files: &[String]) -> Result<bool, String> {
+    if files.is_empty() || git(dir, &with_files(&["status", "--porcelain"], files)).await?.is_empty() {
+        return Ok(false);
+    }
+    git(dir, &with_files(&["add"], files)).await?;
+    git(dir, &with_files(&["commit", "--quiet", "-m", "Update sshtui config"], files)).await?;
+    Ok(true)
+}
+
+/// Make the config directory a repository whose origin is `remote`, when
+/// one is set in the settings
+async fn set_up(dir: &Path, remote: Option<&str>) -> Result<(), String> {
+    let Some(remote) = remote else {
+        if !dir.join(".git").exists() {
+            return Err(format!("Set a git remote in the settings (Ctrl+S) or make {} a git repository", dir.display()));
+        }
+        return Ok(());
+    };
+    if !dir.join(".git").exists() {
+        git(dir, &["init", "--quiet"]).await?;
+    }
+    let remotes = git(dir, &["remote"]).await?;
+    if !remotes.lines().any(|name| name == "origin") {
+        git(dir, &["remote", "add", "origin", remote]).await?;
+    } else if git(dir, &["remote", "get-url", "origin"]).await? != remote {
+        git(dir, &["remote", "set-url", "origin", remote]).await?;
+    }
+    Ok(())
+}
+
+async fn current_branch(dir: &Path) -> Result<String, String> {
+    git(dir, &["symbolic-ref", "--short", "HEAD"]).await
+}
+
+/// Bring in the remote's config. Changes made on both sides are merged when
+/// they touch different lines; when they conflict the merge is abandoned
+/// and nothing changes here.
+async fn pull(dir: &Path, remote: Option<&str>, files: &[String]) -> Result<bool, String> {
+    set_up(dir, remote).await?;
+    commit(dir, files).await?;
+    git(dir, &["fetch", "--quiet", "origin"]).await?;
+    let upstream = format!("origin/{}", current_branch(dir).await?);
+    if git(dir, &["rev-parse", "--verify", "--quiet", &upstream]).await.is_err() {
+        // Nothing pushed from any machine yet
+        return Ok(false);
+    }
+    let head = git(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).await.ok();
+    if head.is_none() || git(dir, &["merge-base", "HEAD", &upstream]).await.is_err() {
+        // First sync of a machine with a config of its own: the remote's
+        // is taken, and this one kept where a save would have left it
+        for file in files {
+            let path = dir.join(file);
+            Config::write_backup(&path, &path.with_extension("json.bak"))
+                .map_err(|e| format!("Could not back up {}: {}", path.display(), e))?;
+        }
+        git(dir, &["reset", "--quiet", "--hard", &upstream]).await?;
+        return Ok(true);
+    }
+    if git(dir, &["merge", "--quiet", "--ff-only", &upstream]).await.is_ok() {
+        return Ok(git(dir, &["rev-parse", "HEAD"]).await.ok() != head);
+    }
+    if git(dir, &["merge", "--quiet", "--no-edit", &upstream]).await.is_ok() {
+        return Ok(true);
+    }
+    let conflicts = git(dir, &["diff", "--name-only", "--diff-filter=U"]).await.unwrap_or_default();
+    let _ = git(dir, &["merge", "--abort"]).await;
+    if conflicts.is_empty() {
+        return Err(format!("Could not merge the remote's config; resolve it with git in {}", dir.display()));
+    }
+    Err(format!(
+        "This machine and the remote changed the same lines of {}; nothing was pulled. Resolve it with git in {}",
+        conflicts.lines().collect::<Vec<_>>().join(", "),
+        dir.display()
+    ))
+}
+
+async fn push(dir: &Path, remote: Option<&str>, files: &[String]) -> Result<bool, String> {
+    set_up(dir, remote).await?;
+    commit(dir, files).await?;
+    let branch = current_branch(dir).await?;
+    git(dir, &["push", "--quiet", "--set-upstream", "origin", &branch]).await.map_err(|e| {
+        if e.contains("[rejected]") {
+            "The remote has config changes this machine lacks; pull them first".to_string()
+        } else {
+            e
+        }
+    })?;
+    Ok(true)
+}
+
+impl AppState {
+    /// Commit the config files shortly after they are saved, when the
+    /// config directory is a git repository, and report pulls and pushes
+    pub fn poll_git_sync(&mut self) {
+        if let Some((action, receiver)) = &mut self.git_sync.running {
+            let action = *action;
+            match receiver.try_recv() {
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Closed) => self.git_sync.running = None,
+                Ok(result) => {
+                    self.git_sync.running = None;
+                    self.finish_git_sync(action, result);
+                },
+            }
+        }
+
+        if self.git_sync.last_check.is_some_and(|checked| checked.elapsed() < COMMIT_INTERVAL) {
+            return;
+        }
+        self.git_sync.last_check = Some(Instant::now());
+        let Ok(dir) = Config::config_dir() else {
+            return;
+        };
+        if !dir.join(".git").exists() {
+            return;
+        }
+        let files = synced_files();
+        let times = modification_times(&files);
+        if times != self.git_sync.committed {
+            self.git_sync.committed = times;
+            self.start_git_sync(GitAction::Commit);
+        }
+    }
+
+    /// Run git in the background: commit, pull or push the config files
+    pub fn start_git_sync(&mut self, action: GitAction) {
+        if self.git_sync.running.is_some() {
+            if action != GitAction::Commit {
+                self.set_message("Still busy with git; try again in a moment".to_string(), MessageType::Info);
+            }
+            return;
+        }
+        let dir = match Config::config_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                self.set_message(e.to_string(), MessageType::Error);
+                return;
+            },
+        };
+        let files: Vec<String> = synced_files().iter()
+            .filter_map(|path| path.strip_prefix(&dir).ok())
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        let remote = self.config.settings.git_remote.clone();
+        if action != GitAction::Commit {
+            self.set_message(format!("{} config...", if action == GitAction::Pull { "Pulling" } else { "Pushing" }), MessageType::Info);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let result = match action {
+                GitAction::Commit => commit(&dir, &files).await,
+                GitAction::Pull => pull(&dir, remote.as_deref(), &files).await,
+                GitAction::Push => push(&dir, remote.as_deref(), &files).await,
+            };
+            let _ = sender.send(result);
+        });
+        self.git_sync.running = Some((action, receiver));
+    }
+
+    fn finish_git_sync(&mut self, action: GitAction, result: Result<bool, String>) {
+        match (action, result) {
+            (GitAction::Commit, Ok(_)) => {},
+            (GitAction::Commit, Err(e)) => self.set_message(format!("Could not commit the config: {}", e), MessageType::Error),
+            (GitAction::Pull, Ok(false)) => self.set_message("Config is up to date with the remote".to_string(), MessageType::Info),
+            (GitAction::Pull, Ok(true)) => match Config::load() {
+                Ok(config) => {
+                    self.use_config(config);
+                    self.set_message("Pulled config changes from the remote".to_string(), MessageType::Success);
+                },
+                Err(e) => self.set_message(format!("The pulled config does not load: {}", e), MessageType::Error),
+            },
+            (GitAction::Push, Ok(_)) => self.set_message("Pushed config to the remote".to_string(), MessageType::Success),
+            (_, Err(e)) => self.set_message(e, MessageType::Error),
+        }
+        if action == GitAction::Pull {
+            // What git wrote is not a save to commit again
+            self.git_sync.committed = modification_times(&synced_files());
+        }
+    }
+}
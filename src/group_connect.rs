@@ -0,0 +1,187 @@
+use crate::{AppState, MessageType, ModalState};
+use crate::config::Host;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepStatus {
+    Waiting,
+    Connecting,
+    /// Connected and sent to the background
+    Up,
+    Failed(String),
+    /// Not attempted because a dependency is not up
+    Skipped(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectStep {
+    pub host: Host,
+    pub status: StepStatus,
+}
+
+/// Sessions being opened one after another for a group, dependencies first
+#[derive(Debug, Clone)]
+pub struct GroupConnect {
+    pub group: String,
+    pub steps: Vec<ConnectStep>,
+}
+
+impl GroupConnect {
+    pub fn is_done(&self) -> bool {
+        !self.steps.iter().any(|step| matches!(step.status, StepStatus::Waiting | StepStatus::Connecting))
+    }
+
+    fn summary(&self) -> String {
+        let up = self.steps.iter().filter(|step| step.status == StepStatus::Up).count();
+        format!("{} of {} session(s) up", up, self.steps.len())
+    }
+}
+
+/// Order `hosts` so every host comes after the hosts it depends on.
+/// Dependencies outside `hosts` are looked up in `all` and added.
+fn connect_order(hosts: Vec<Host>, all: &[Host]) -> Result<Vec<Host>, String> {
+    fn visit<'a>(host: &'a Host, all: &'a [Host], visiting: &mut Vec<&'a Host>, done: &mut HashSet<Uuid>, order: &mut Vec<Host>) -> Result<(), String> {
+        if done.contains(&host.id) {
+            return Ok(());
+        }
+        if visiting.iter().any(|visited| visited.id == host.id) {
+            let path: Vec<&str> = visiting.iter().map(|visited| visited.name.as_str()).collect();
+            return Err(format!("Dependency cycle: {} -> {}", path.join(" -> "), host.name));
+        }
+        visiting.push(host);
+        for dependency in &host.depends_on {
+            let Some(dependency) = all.iter().find(|candidate| candidate.id == *dependency) else {
+                return Err(format!("'{}' depends on a host that no longer exists", host.name));
+            };
+            visit(dependency, all, visiting, done, order)?;
+        }
+        visiting.pop();
+        done.insert(host.id);
+        order.push(host.clone());
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    let mut done = HashSet::new();
+    for host in &hosts {
+        visit(host, all, &mut Vec::new(), &mut done, &mut order)?;
+    }
+    Ok(order)
+}
+
+impl AppState {
+    /// Open a background session to every visible host of the selected
+    /// group, each after the hosts it depends on
+    pub fn start_group_connect(&mut self) {
+        if self.group_connect.as_ref().is_some_and(|run| !run.is_done()) {
+            self.modal_state = ModalState::GroupConnect;
+            return;
+        }
+        let group = match self.virtual_group {
+            Some(view) => view.name().to_string(),
+            None => {
+                let Some(group) = self.config.groups.get(self.selected_group).map(|group| group.name.clone()) else {
+                    return;
+                };
+                group
+            },
+        };
+        let hosts = self.visible_hosts();
+        if hosts.is_empty() {
+            self.set_message(format!("Group '{}' has no hosts to connect", group), MessageType::Error);
+            return;
+        }
+
+        let order = match connect_order(hosts, &self.config.get_hosts_for_group(0)) {
+            Ok(order) => order,
+            Err(e) => {
+                self.set_message(e, MessageType::Error);
+                return;
+            }
+        };
+        let steps = order.into_iter()
+            .map(|host| {
+                // Hosts that already have a session count as up
+                let running = self.background_sessions.iter().any(|session| session.host_name() == host.name);
+                let status = if running { StepStatus::Up } else { StepStatus::Waiting };
+                ConnectStep { host, status }
+            })
+            .collect();
+
+        self.group_connect = Some(GroupConnect { group, steps });
+        self.modal_state = ModalState::GroupConnect;
+    }
+
+    /// Follow the session being opened and start the next one once it is up
+    pub async fn poll_group_connect(&mut self) {
+        let Some(mut run) = self.group_connect.take() else {
+            return;
+        };
+        if run.is_done() {
+            self.group_connect = Some(run);
+            return;
+        }
+
+        if let Some(step) = run.steps.iter_mut().find(|step| step.status == StepStatus::Connecting) {
+            // Hosts depending on this one start only once it is logged in
+            if self.ssh_client.is_logged_in() {
+                step.status = StepStatus::Up;
+                self.detach_session();
+            } else if self.ssh_client.is_connected() || self.ssh_client.is_connecting() || !matches!(self.modal_state, ModalState::None | ModalState::GroupConnect) {
+                // Still connecting or logging in, or waiting on a host key
+                // or identity prompt
+                self.group_connect = Some(run);
+                return;
+            } else {
+                self.cancel_reconnect();
+                step.status = StepStatus::Failed(self.failure_reason());
+            }
+        }
+
+        for index in 0..run.steps.len() {
+            if run.steps[index].status != StepStatus::Waiting {
+                continue;
+            }
+            let blocked = run.steps[index].host.depends_on.iter()
+                .find_map(|dependency| run.steps.iter().find(|step| {
+                    step.host.id == *dependency && matches!(step.status, StepStatus::Failed(_) | StepStatus::Skipped(_))
+                }))
+                .map(|step| step.host.name.clone());
+            if let Some(dependency) = blocked {
+                run.steps[index].status = StepStatus::Skipped(format!("{} is not up", dependency));
+                continue;
+            }
+
+            let host = run.steps[index].host.clone();
+            let _ = self.connect_to_host(host.clone()).await;
+            run.steps[index].status = if self.ssh_client.is_connecting() {
+                self.set_message(format!("Group connect: connecting to {}...", host.name), MessageType::Info);
+                StepStatus::Connecting
+            } else {
+                StepStatus::Failed(self.failure_reason())
+            };
+            if run.steps[index].status == StepStatus::Connecting {
+                break;
+            }
+        }
+
+        if run.is_done() {
+            let kind = if run.steps.iter().all(|step| step.status == StepStatus::Up) {
+                MessageType::Success
+            } else {
+                MessageType::Error
+            };
+            self.set_message(format!("Group connect of {} finished: {} (Ctrl+G to list sessions)", run.group, run.summary()), kind);
+        }
+        self.group_connect = Some(run);
+    }
+
+    /// Why the last connection attempt did not come up, from the status bar
+    fn failure_reason(&self) -> String {
+        match self.message_type {
+            MessageType::Error if !self.message.is_empty() => self.message.clone(),
+            _ => "connection closed".to_string(),
+        }
+    }
+}
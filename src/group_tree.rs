@@ -0,0 +1,161 @@
+use crate::AppState;
+use crate::config::Host;
+
+/// Lines of the Groups panel listing hosts from every group, rather than a
+/// group of the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualGroup {
+    /// Starred hosts
+    Favorites,
+    /// Hosts connected to lately, newest first
+    Recent,
+}
+
+impl VirtualGroup {
+    pub fn name(self) -> &'static str {
+        match self {
+            VirtualGroup::Favorites => "Favorites",
+            VirtualGroup::Recent => "Recent",
+        }
+    }
+}
+
+/// What a line of the Groups panel lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupLine {
+    Virtual(VirtualGroup),
+    /// Index into the config's groups
+    Group(usize),
+}
+
+/// A line of the Groups panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupRow {
+    pub group: GroupLine,
+    /// How deep the group is nested, 0 for top-level groups
+    pub depth: usize,
+    /// Whether other groups are nested in it
+    pub folder: bool,
+    pub collapsed: bool,
+}
+
+impl AppState {
+    /// Lines of the Groups panel: Favorites when hosts are starred, Recent
+    /// once hosts were connected to, "All", then each group followed by the
+    /// ones nested in it, unless it is folded
+    pub fn group_rows(&self) -> Vec<GroupRow> {
+        if self.config.groups.is_empty() {
+            return Vec::new();
+        }
+        let mut rows = Vec::new();
+        let line = |group| GroupRow { group, depth: 0, folder: false, collapsed: false };
+        if self.has_favorites() {
+            rows.push(line(GroupLine::Virtual(VirtualGroup::Favorites)));
+        }
+        if !self.history.entries.is_empty() {
+            rows.push(line(GroupLine::Virtual(VirtualGroup::Recent)));
+        }
+        rows.push(line(GroupLine::Group(0)));
+        self.push_group_rows(None, 0, &mut rows);
+        rows
+    }
+
+    /// Hosts a virtual group lists, in its own order
+    pub fn virtual_group_hosts(&self, view: VirtualGroup) -> Vec<Host> {
+        match view {
+            VirtualGroup::Favorites => self.favorite_hosts(),
+            VirtualGroup::Recent => self.recent_group_hosts(),
+        }
+    }
+
+    /// The Groups panel line the cursor is on
+    pub fn current_group_row(&self) -> GroupLine {
+        match self.virtual_group {
+            Some(view) => GroupLine::Virtual(view),
+            None => GroupLine::Group(self.selected_group),
+        }
+    }
+
+    fn push_group_rows(&self, parent: Option<&str>, depth: usize, rows: &mut Vec<GroupRow>) {
+        for (index, group) in self.config.groups.iter().enumerate().skip(1) {
+            if group.parent_name() != parent {
+                continue;
+            }
+            let folder = self.config.groups.iter().any(|other| other.parent_name() == Some(group.name.as_str()));
+            let collapsed = folder && self.collapsed_folders.contains(&group.name);
+            rows.push(GroupRow { group: GroupLine::Group(index), depth, folder, collapsed });
+            if folder && !collapsed {
+                self.push_group_rows(Some(&group.name), depth + 1, rows);
+            }
+        }
+    }
+
+    /// Move the Groups panel cursor one line
+    pub fn move_group_cursor(&mut self, down: bool) {
+        let rows = self.group_rows();
+        let target = match rows.iter().position(|row| row.group == self.current_group_row()) {
+            Some(current) if down => rows.get(current + 1),
+            Some(current) => current.checked_sub(1).and_then(|previous| rows.get(previous)),
+            None => rows.first(),
+        };
+        if let Some(row) = target {
+            self.select_group(row.group);
+        }
+    }
+
+    /// Show the hosts of a group or virtual group
+    pub fn select_group(&mut self, group: GroupLine) {
+        if group != self.current_group_row() {
+            (self.virtual_group, self.selected_group) = match group {
+                GroupLine::Virtual(view) => (Some(view), 0),
+                GroupLine::Group(index) => (None, index),
+            };
+            self.selected_host = 0;
+            self.host_header = None;
+        }
+    }
+
+    /// Fold or unfold the selected group's sub-groups. Folding a group
+    /// with none, or one already folded, moves to the group it is in.
+    pub fn set_folder_collapsed(&mut self, collapse: bool) {
+        let Some(row) = self.group_rows().into_iter().find(|row| row.group == self.current_group_row()) else {
+            return;
+        };
+        let GroupLine::Group(index) = row.group else {
+            return;
+        };
+        let name = self.config.groups[index].name.clone();
+        if !collapse {
+            self.collapsed_folders.remove(&name);
+            return;
+        }
+        if row.folder && !row.collapsed {
+            self.collapsed_folders.insert(name);
+            return;
+        }
+        let parent = self.config.groups[index].parent_name()
+            .and_then(|parent| self.config.groups.iter().position(|group| group.name == parent));
+        if let Some(parent) = parent {
+            self.select_group(GroupLine::Group(parent));
+        }
+    }
+
+    /// Whether other groups are nested in group `index`
+    pub fn has_sub_groups(&self, index: usize) -> bool {
+        self.config.groups.get(index)
+            .is_some_and(|group| self.config.groups.iter().any(|other| other.parent_name() == Some(group.name.as_str())))
+    }
+
+    /// Rename a group along with the groups nested in it
+    pub fn rename_group(&mut self, index: usize, name: &str) {
+        let old_name = self.config.groups[index].name.clone();
+        for group in self.config.groups.iter_mut().skip(1) {
+            if group.is_within(&old_name) {
+                group.name = format!("{}{}", name, &group.name[old_name.len()..]);
+            }
+        }
+        if self.collapsed_folders.remove(&old_name) {
+            self.collapsed_folders.insert(name.to_string());
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::AppState;
+use crate::config_crypto;
+use crate::config::{Config, Host};
+use crate::ssh::ConnectionProfile;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Maximum number of connection records kept on disk
+const MAX_ENTRIES: usize = 500;
+/// Maximum number of failed attempts kept on disk
+const MAX_FAILURES: usize = 200;
+/// Failures older than this no longer count as recent
+pub const RECENT_FAILURE_DAYS: i64 = 7;
+
+/// Hosts listed by the Recent group of the Groups panel
+const RECENT_GROUP_HOSTS: usize = 15;
+
+/// Points a connection adds to its host's frecency, by the most days ago it
+/// can have been made; older connections add the last value
+const FRECENCY_WEIGHTS: [(i64, u32); 4] = [(1, 100), (7, 70), (30, 40), (90, 20)];
+const FRECENCY_OLD_WEIGHT: u32 = 5;
+
+/// Record of connections that were successfully established, and of
+/// attempts that were not
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<FailedConnection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The host entry the connection was started from; nil in files
+    /// written before hosts had ids, until `adopt_ids` finds it by name
+    #[serde(default)]
+    pub host_id: Uuid,
+    /// Its name at the time, as shown in lists
+    pub host_name: String,
+    pub connected_at: DateTime<Local>,
+    /// When the session ended; unset while it runs or if sshtui exited first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disconnected_at: Option<DateTime<Local>>,
+    pub profile: ConnectionProfile,
+}
+
+/// A connection attempt that ended before a session was up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedConnection {
+    #[serde(default)]
+    pub host_id: Uuid,
+    pub host_name: String,
+    pub failed_at: DateTime<Local>,
+    pub reason: String,
+}
+
+/// What the history says about one host entry
+#[derive(Debug, Default)]
+pub struct HostStats<'a> {
+    pub connections: usize,
+    pub last_connected: Option<DateTime<Local>>,
+    /// Over the sessions whose end was seen
+    pub average_session: Option<Duration>,
+    /// Failures in the last RECENT_FAILURE_DAYS days
+    pub recent_failures: usize,
+    pub last_failure: Option<&'a FailedConnection>,
+}
+
+impl History {
+    pub fn load() -> Result<Self> {
+        let history_path = Self::history_path()?;
+
+        if !history_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&history_path)
+            .with_context(|| format!("Failed to read history file: {:?}", history_path))?;
+        let contents = config_crypto::unseal(contents)?;
+
+        serde_json::from_str(&contents)
+            .with_context(|| "Failed to parse history JSON")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let history_path = Self::history_path()?;
+
+        if let Some(parent) = history_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize history")?;
+        config_crypto::write_sealed(&history_path, contents)
+            .with_context(|| format!("Failed to write history file: {:?}", history_path))?;
+
+        Ok(())
+    }
+
+    fn history_path() -> Result<PathBuf> {
+        Ok(Config::config_dir()?.join("history.json"))
+    }
+
+    /// Give records from before hosts had ids the id of the host with
+    /// their name, where there is one
+    pub fn adopt_ids(&mut self, config: &Config) {
+        let hosts = config.get_hosts_for_group(0);
+        let id_of = |name: &str| hosts.iter().find(|host| host.name == name).map(|host| host.id);
+        for entry in self.entries.iter_mut().filter(|entry| entry.host_id.is_nil()) {
+            entry.host_id = id_of(&entry.host_name).unwrap_or_default();
+        }
+        for failure in self.failures.iter_mut().filter(|failure| failure.host_id.is_nil()) {
+            failure.host_id = id_of(&failure.host_name).unwrap_or_default();
+        }
+    }
+
+    pub fn record(&mut self, profile: ConnectionProfile) {
+        self.entries.push(HistoryEntry {
+            host_id: profile.host.id,
+            host_name: profile.host.name.clone(),
+            connected_at: Local::now(),
+            disconnected_at: None,
+            profile,
+        });
+
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+
+    /// Most recent successful connection started from the given host entry
+    pub fn last_for_host(&self, host_id: Uuid) -> Option<&HistoryEntry> {
+        self.entries.iter().rev().find(|entry| entry.host_id == host_id)
+    }
+
+    /// Mark the newest running session of the host as ended now
+    pub fn end_session(&mut self, host_id: Uuid) {
+        let open = self.entries.iter_mut()
+            .rev()
+            .find(|entry| entry.host_id == host_id && entry.disconnected_at.is_none());
+        if let Some(entry) = open {
+            entry.disconnected_at = Some(Local::now());
+        }
+    }
+
+    pub fn record_failure(&mut self, host: &Host, reason: String) {
+        self.failures.push(FailedConnection {
+            host_id: host.id,
+            host_name: host.name.clone(),
+            failed_at: Local::now(),
+            reason,
+        });
+
+        if self.failures.len() > MAX_FAILURES {
+            let excess = self.failures.len() - MAX_FAILURES;
+            self.failures.drain(..excess);
+        }
+    }
+
+    pub fn stats(&self, host_id: Uuid) -> HostStats<'_> {
+        let mut stats = HostStats::default();
+        let mut total = Duration::zero();
+        let mut ended = 0;
+        for entry in self.entries.iter().filter(|entry| entry.host_id == host_id) {
+            stats.connections += 1;
+            stats.last_connected = Some(entry.connected_at);
+            if let Some(disconnected_at) = entry.disconnected_at {
+                total += disconnected_at - entry.connected_at;
+                ended += 1;
+            }
+        }
+        if ended > 0 {
+            stats.average_session = Some(total / ended);
+        }
+
+        let since = Local::now() - Duration::days(RECENT_FAILURE_DAYS);
+        for failure in self.failures.iter().filter(|failure| failure.host_id == host_id && failure.failed_at > since) {
+            stats.recent_failures += 1;
+            stats.last_failure = Some(failure);
+        }
+        stats
+    }
+
+    /// Score of every host connected to, by how often and how lately: a
+    /// host used every day outranks one used many times months ago
+    pub fn frecency(&self) -> HashMap<Uuid, u32> {
+        let now = Local::now();
+        let mut scores = HashMap::new();
+        for entry in &self.entries {
+            let days = (now - entry.connected_at).num_days();
+            let weight = FRECENCY_WEIGHTS.iter()
+                .find(|(max_days, _)| days < *max_days)
+                .map_or(FRECENCY_OLD_WEIGHT, |(_, weight)| *weight);
+            *scores.entry(entry.host_id).or_default() += weight;
+        }
+        scores
+    }
+
+    /// The newest connection of each of the last `count` hosts used
+    pub fn recent_hosts(&self, count: usize) -> Vec<&HistoryEntry> {
+        let mut recent: Vec<&HistoryEntry> = Vec::new();
+        for entry in self.entries.iter().rev() {
+            if recent.len() == count {
+                break;
+            }
+            if !recent.iter().any(|seen| seen.host_id == entry.host_id) {
+                recent.push(entry);
+            }
+        }
+        recent
+    }
+}
+
+impl AppState {
+    /// Hosts of the config connected to lately, the last one used first
+    pub fn recent_group_hosts(&self) -> Vec<Host> {
+        let hosts = self.config.get_hosts_for_group(0);
+        self.history.recent_hosts(RECENT_GROUP_HOSTS)
+            .into_iter()
+            .filter_map(|entry| hosts.iter().find(|host| host.id == entry.host_id).cloned())
+            .collect()
+    }
+
+    /// Note a connection whose session got past its login
+    pub fn record_connection(&mut self, profile: ConnectionProfile) {
+        self.history.record(profile);
+        if let Err(e) = self.history.save() {
+            warn!("Could not save connection history: {}", e);
+        }
+    }
+
+    /// Note that the session to the host is over
+    pub fn record_session_end(&mut self, host_id: Uuid) {
+        self.history.end_session(host_id);
+        if let Err(e) = self.history.save() {
+            warn!("Could not save connection history: {}", e);
+        }
+    }
+
+    /// Note a connection attempt that failed before its session was up
+    pub fn record_connection_failure(&mut self, host: &Host, reason: String) {
+        self.history.record_failure(host, reason);
+        if let Err(e) = self.history.save() {
+            warn!("Could not save connection history: {}", e);
+        }
+    }
+}
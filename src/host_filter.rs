@@ -0,0 +1,160 @@
+use crate::{AppState, FocusArea, FocusSubArea};
+use crate::config::Host;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+/// Whether `text` matches the shell pattern `pattern` as a whole: `*` is any
+/// run of characters, `?` one character and `[...]` one of a set, with
+/// ranges like `a-z` and `!` or `^` to negate
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+    match first {
+        '*' => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+        '?' => !text.is_empty() && glob_matches(rest, &text[1..]),
+        '[' => {
+            let Some(end) = rest.iter().skip(1).position(|c| *c == ']').map(|i| i + 1) else {
+                // An unclosed bracket is taken literally
+                return text.first() == Some(&'[') && glob_matches(rest, &text[1..]);
+            };
+            let Some(&c) = text.first() else {
+                return false;
+            };
+            let (negate, set) = match rest[0] {
+                '!' | '^' => (true, &rest[1..end]),
+                _ => (false, &rest[..end]),
+            };
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    in_set |= set[i] == c;
+                    i += 1;
+                }
+            }
+            in_set != negate && glob_matches(&rest[end + 1..], &text[1..])
+        },
+        c => text.first() == Some(&c) && glob_matches(rest, &text[1..]),
+    }
+}
+
+impl AppState {
+    /// Start typing into the filter box above the hosts
+    pub fn open_host_filter(&mut self) {
+        self.host_filter_editing = true;
+        self.focus_area = FocusArea::Hosts;
+        self.focus_sub_area = FocusSubArea::Items;
+    }
+
+    /// Keys for the filter box while it is being typed into; arrows still
+    /// move through the hosts
+    pub fn handle_host_filter_key(&mut self, key: KeyCode, modifiers: KeyModifiers) -> bool {
+        if !self.host_filter_editing {
+            return false;
+        }
+        match (key, modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.host_filter.push(c),
+            (KeyCode::Backspace, _) => {
+                self.host_filter.pop();
+            },
+            (KeyCode::Enter, _) => {
+                self.host_filter_editing = false;
+                return true;
+            },
+            (KeyCode::Esc, _) => {
+                self.clear_host_filter();
+                return true;
+            },
+            _ => return false,
+        }
+        self.selected_host = 0;
+        self.host_header = None;
+        true
+    }
+
+    pub fn clear_host_filter(&mut self) {
+        self.host_filter_editing = false;
+        self.host_filter.clear();
+        self.selected_host = 0;
+        self.host_header = None;
+    }
+
+    /// Whether a host passes the tag filter and the filter box: a pattern
+    /// with `*`, `?` or `[` must match the whole name, anything else is
+    /// looked for in the name, user and address
+    pub fn host_passes_filters(&self, host: &Host) -> bool {
+        if !host.has_tags(&self.tag_filter) {
+            return false;
+        }
+        let filter = self.host_filter.trim().to_lowercase();
+        if filter.is_empty() {
+            return true;
+        }
+        if filter.contains(['*', '?', '[']) {
+            let pattern: Vec<char> = filter.chars().collect();
+            let name: Vec<char> = host.name.to_lowercase().chars().collect();
+            return glob_matches(&pattern, &name);
+        }
+        format!("{} {}@{}", host.name, host.user, host.host).to_lowercase().contains(&filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob_matches(&pattern, &text)
+    }
+
+    #[test]
+    fn matches_the_whole_name() {
+        assert!(matches("web", "web"));
+        assert!(!matches("web", "web1"));
+        assert!(!matches("web", "my-web"));
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(matches("web*", "web"));
+        assert!(matches("web*", "web-prod-01"));
+        assert!(matches("*prod*", "eu-prod-db"));
+        assert!(matches("a*b*c", "axxbyyc"));
+        assert!(!matches("web*", "db-web"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("db?", "db1"));
+        assert!(!matches("db?", "db"));
+        assert!(!matches("db?", "db12"));
+    }
+
+    #[test]
+    fn brackets_match_sets_and_ranges() {
+        assert!(matches("web[0-9]", "web7"));
+        assert!(!matches("web[0-9]", "webx"));
+        assert!(matches("[abc]-host", "b-host"));
+        assert!(matches("node[a-c1]", "node1"));
+        // A trailing dash is taken literally
+        assert!(matches("x[a-]", "x-"));
+    }
+
+    #[test]
+    fn brackets_can_be_negated() {
+        assert!(matches("web[!0-9]", "webx"));
+        assert!(!matches("web[!0-9]", "web3"));
+        assert!(matches("web[^0-9]", "web-"));
+    }
+
+    #[test]
+    fn unclosed_bracket_is_literal() {
+        assert!(matches("web[1", "web[1"));
+        assert!(!matches("web[1", "web1"));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::AppState;
+use crate::config::Host;
+
+/// A line of the host list in the "All" view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostRow {
+    /// Header of a group with `hosts` hosts passing the filters
+    Header { group: usize, hosts: usize, collapsed: bool },
+    /// Index into the visible hosts
+    Host(usize),
+}
+
+impl AppState {
+    /// Whether the host list shows every group under its own header
+    pub fn grouped_host_view(&self) -> bool {
+        self.virtual_group.is_none() && self.selected_group == 0 && self.config.groups.first().is_some_and(|group| group.name == "All")
+    }
+
+    /// Hosts of the expanded groups in the "All" view, group by group
+    pub fn grouped_hosts(&self) -> Vec<Host> {
+        let mut hosts = Vec::new();
+        for group in self.config.groups.iter().skip(1) {
+            if self.collapsed_groups.contains(&group.name) {
+                continue;
+            }
+            let mut group_hosts: Vec<Host> = group.hosts.iter()
+                .filter(|host| self.host_passes_filters(host))
+                .cloned()
+                .collect();
+            self.sort_hosts(&mut group_hosts);
+            hosts.extend(group_hosts);
+        }
+        hosts
+    }
+
+    /// Lines of the "All" view: each group that has matching hosts, followed
+    /// by its hosts unless it is collapsed
+    pub fn host_rows(&self) -> Vec<HostRow> {
+        let mut rows = Vec::new();
+        let mut next_host = 0;
+        for (index, group) in self.config.groups.iter().enumerate().skip(1) {
+            let hosts = group.hosts.iter().filter(|host| self.host_passes_filters(host)).count();
+            if hosts == 0 {
+                continue;
+            }
+            let collapsed = self.collapsed_groups.contains(&group.name);
+            rows.push(HostRow::Header { group: index, hosts, collapsed });
+            if !collapsed {
+                rows.extend((next_host..next_host + hosts).map(HostRow::Host));
+                next_host += hosts;
+            }
+        }
+        rows
+    }
+
+    /// Group whose header the cursor is on, in the "All" view
+    pub fn selected_host_header(&self) -> Option<usize> {
+        let group = self.host_header.filter(|_| self.grouped_host_view())?;
+        self.host_rows()
+            .iter()
+            .any(|row| matches!(row, HostRow::Header { group: g, .. } if *g == group))
+            .then_some(group)
+    }
+
+    /// The host under the cursor; none while it is on a group header
+    pub fn selected_visible_host(&self) -> Option<Host> {
+        if self.selected_host_header().is_some() {
+            return None;
+        }
+        self.visible_hosts().into_iter().nth(self.selected_host)
+    }
+
+    /// Move the cursor of the "All" view one line, headers included
+    pub fn move_host_cursor(&mut self, down: bool) {
+        let rows = self.host_rows();
+        let current = match self.selected_host_header() {
+            Some(group) => rows.iter().position(|row| matches!(row, HostRow::Header { group: g, .. } if *g == group)),
+            None => rows.iter().position(|row| *row == HostRow::Host(self.selected_host)),
+        };
+        let target = match current {
+            Some(current) if down => current + 1,
+            Some(current) => match current.checked_sub(1) {
+                Some(target) => target,
+                None => return,
+            },
+            None => 0,
+        };
+        match rows.get(target) {
+            Some(HostRow::Header { group, .. }) => self.host_header = Some(*group),
+            Some(HostRow::Host(index)) => {
+                self.host_header = None;
+                self.selected_host = *index;
+            },
+            None => {}
+        }
+    }
+
+    /// Select what is drawn on `line` of the "All" view; a click on a
+    /// header also folds or unfolds its group
+    pub fn click_host_row(&mut self, line: usize) {
+        let mut top = 0;
+        for row in self.host_rows() {
+            // Hosts take two lines, headers one
+            let height = if matches!(row, HostRow::Host(_)) { 2 } else { 1 };
+            if line < top + height {
+                match row {
+                    HostRow::Header { group, .. } => {
+                        self.host_header = Some(group);
+                        self.set_group_collapsed(None);
+                    },
+                    HostRow::Host(index) => {
+                        self.host_header = None;
+                        self.selected_host = index;
+                    },
+                }
+                return;
+            }
+            top += height;
+        }
+    }
+
+    /// Fold or unfold a group of the "All" view: the one whose header the
+    /// cursor is on, or the selected host's when folding
+    pub fn set_group_collapsed(&mut self, collapsed: Option<bool>) {
+        let group = match self.selected_host_header() {
+            Some(group) => group,
+            None if self.selected_visible_host().is_none() => return,
+            None => {
+                // The header above the selected host
+                let mut header = None;
+                for row in self.host_rows() {
+                    match row {
+                        HostRow::Header { group, .. } => header = Some(group),
+                        HostRow::Host(index) if index == self.selected_host => break,
+                        HostRow::Host(_) => {},
+                    }
+                }
+                let Some(group) = header else {
+                    return;
+                };
+                group
+            },
+        };
+        let name = self.config.groups[group].name.clone();
+        let collapse = collapsed.unwrap_or(!self.collapsed_groups.contains(&name));
+        if collapse {
+            self.collapsed_groups.insert(name);
+            // The cursor stays on the folded group
+            self.host_header = Some(group);
+        } else {
+            self.collapsed_groups.remove(&name);
+        }
+        self.selected_host = self.selected_host.min(self.visible_hosts().len().saturating_sub(1));
+    }
+}
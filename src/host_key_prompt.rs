@@ -0,0 +1,36 @@
+use crate::{AppState, HostKeyForm, MessageType, ModalState};
+use crate::hostkeys::{trust, HostKeyPrompt};
+use crate::notify;
+
+impl AppState {
+    /// Hold the connection and ask whether to trust the presented key
+    pub fn prompt_host_key(&mut self, prompt: Box<HostKeyPrompt>) {
+        if prompt.known.is_some() {
+            notify::send(
+                &self.config.settings.notifications,
+                notify::Severity::Error,
+                "Host key changed",
+                &format!("The host key of {} ({}) changed; connection blocked", prompt.host_name, prompt.address()),
+            );
+            self.set_message(
+                format!("WARNING: host key of {} changed - connection blocked", prompt.host_name),
+                MessageType::Error
+            );
+        } else {
+            self.set_message(format!("Verify the host key of {}", prompt.host_name), MessageType::Info);
+        }
+        self.cancel_reconnect();
+        self.modal_state = ModalState::HostKey(HostKeyForm { prompt, trust_selected: false });
+    }
+
+    /// Trust the presented key and connect again
+    pub fn trust_host_key(&mut self, prompt: HostKeyPrompt) {
+        if let Err(e) = trust(&prompt) {
+            self.set_message(format!("Could not trust host key: {}", e), MessageType::Error);
+            return;
+        }
+
+        self.set_message(format!("Trusted host key of {}", prompt.host_name), MessageType::Success);
+        self.retry_pending = true;
+    }
+}
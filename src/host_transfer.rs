@@ -0,0 +1,96 @@
+use crate::{AppState, MessageType, ModalState, MoveHostForm};
+use uuid::Uuid;
+
+impl AppState {
+    /// A name no host uses yet, for a copy of `name`
+    fn copy_name(&self, name: &str) -> String {
+        let taken: Vec<String> = self.config.get_hosts_for_group(0).into_iter().map(|host| host.name).collect();
+        let mut candidate = format!("{}-copy", name);
+        let mut n = 2;
+        while taken.contains(&candidate) {
+            candidate = format!("{}-copy-{}", name, n);
+            n += 1;
+        }
+        candidate
+    }
+
+    /// Open the add form filled in from the selected host, under a new name
+    pub fn duplicate_selected_host(&mut self) {
+        if self.selected_group == 0 {
+            self.set_message("Select the host's own group to duplicate it, not 'All'.".to_string(), MessageType::Error);
+            return;
+        }
+        let Some(host) = self.visible_hosts().get(self.selected_host).cloned() else {
+            return;
+        };
+        let mut form = self.host_edit_form(&host);
+        form.name = self.copy_name(&host.name);
+        self.modal_state = ModalState::AddHost(form);
+    }
+
+    /// Pick a group to move or copy the selected host to
+    pub fn open_move_host(&mut self) {
+        let Some(host) = self.selected_visible_host() else {
+            return;
+        };
+        let Some(from_group) = self.config.group_of(&host).map(|group| group.name.clone()) else {
+            return;
+        };
+        let groups: Vec<String> = self.config.groups.iter()
+            .skip(1)
+            .map(|group| group.name.clone())
+            .filter(|name| *name != from_group)
+            .collect();
+        if groups.is_empty() {
+            self.set_message("There is no other group to move the host to. Add one with Ctrl+N in Groups.".to_string(), MessageType::Info);
+            return;
+        }
+        self.modal_state = ModalState::MoveHost(MoveHostForm {
+            host_id: host.id,
+            host_name: host.name,
+            from_group,
+            groups,
+            cursor: 0,
+            copy: false,
+        });
+    }
+
+    pub fn move_host(&mut self, form: MoveHostForm) {
+        let Some(to_group) = form.groups.get(form.cursor).cloned() else {
+            return;
+        };
+        let Some(host) = self.config.get_hosts_for_group(0).into_iter().find(|host| host.id == form.host_id) else {
+            self.set_message(format!("Host '{}' no longer exists", form.host_name), MessageType::Error);
+            return;
+        };
+
+        if !form.copy && self.config.host_name_taken(&to_group, &host.name) {
+            self.set_message(format!("Group '{}' already has a host named '{}'", to_group, host.name), MessageType::Error);
+            return;
+        }
+
+        let before = self.snapshot();
+        let result = if form.copy {
+            let mut copy = host.clone();
+            copy.id = Uuid::new_v4();
+            copy.name = self.copy_name(&host.name);
+            let copy_name = copy.name.clone();
+            self.config.add_host_to_group(&to_group, copy)
+                .map(|()| format!("Copied '{}' to '{}' as '{}'", host.name, to_group, copy_name))
+        } else {
+            self.config.remove_host(host.id)
+                .and_then(|()| self.config.add_host_to_group(&to_group, host.clone()))
+                .map(|()| format!("Moved '{}' from '{}' to '{}'", host.name, form.from_group, to_group))
+        };
+
+        match result {
+            Ok(message) => {
+                self.record_change(before, message.clone());
+                let _ = self.config.save();
+                self.selected_host = self.selected_host.min(self.visible_hosts().len().saturating_sub(1));
+                self.set_message(message, MessageType::Success);
+            },
+            Err(e) => self.set_message(format!("Failed to move host: {}", e), MessageType::Error),
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use crate::config_crypto;
+use crate::config::{Config, Host, SshBackend};
+use crate::trust_report::{self, TrustReport};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use russh::client;
+use russh::keys::known_hosts;
+use russh::keys::{HashAlg, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::task::{self, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+/// How long fetching a server's key may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server keys pinned on first connection, keyed by "host:port"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PinnedKeys {
+    pub hosts: HashMap<String, HostKeyRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostKeyRecord {
+    pub pinned: PinnedKey,
+    /// Keys trusted before the current one, most recent last
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous: Vec<PinnedKey>,
+    /// What the server showed when its current key was trusted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<TrustReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedKey {
+    pub key_type: String,
+    /// SHA256 fingerprint as printed by `ssh-keygen -l`
+    pub fingerprint: String,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+}
+
+/// Key presented by a server during the handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObservedKey {
+    pub key: PublicKey,
+    pub key_type: String,
+    pub fingerprint: String,
+    /// Set when the key was trusted for this host at some point before
+    pub first_seen: Option<DateTime<Local>>,
+}
+
+impl ObservedKey {
+    fn from_public_key(key: PublicKey) -> Self {
+        Self {
+            key_type: key.algorithm().as_str().to_string(),
+            fingerprint: fingerprint(&key),
+            first_seen: None,
+            key,
+        }
+    }
+}
+
+/// Where the key a host is already known by was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    /// Entry of `~/.ssh/known_hosts`, numbered the way russh counts them
+    /// (comment lines are skipped)
+    KnownHosts { line: usize },
+    /// sshtui's own pin
+    Pinned,
+}
+
+/// Key a host is already known by, for comparison with the presented one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownKey {
+    pub key_type: String,
+    pub fingerprint: String,
+    pub first_seen: Option<DateTime<Local>>,
+    pub last_seen: Option<DateTime<Local>>,
+    pub source: KeySource,
+}
+
+/// A server key that needs the user's decision before anything is sent to
+/// the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKeyPrompt {
+    pub host_name: String,
+    pub host: String,
+    pub port: u16,
+    /// The key the host is known by; `None` on the first connection
+    pub known: Option<KnownKey>,
+    pub observed: ObservedKey,
+    /// What the server shows of itself now
+    pub report: TrustReport,
+    /// What it showed when the known key was trusted, if recorded
+    pub previous_report: Option<TrustReport>,
+}
+
+impl HostKeyPrompt {
+    /// Identifier the key is pinned under
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+pub enum Verification {
+    /// The key is in known_hosts or matches the pin; carries its fingerprint
+    Trusted(String),
+    /// The key is new or differs from the known one; ask the user
+    Prompt(Box<HostKeyPrompt>),
+    /// The key could not be fetched, so nothing was checked
+    Unavailable(String),
+}
+
+impl PinnedKeys {
+    pub fn load() -> Result<Self> {
+        let path = Self::pinned_keys_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read pinned host keys: {:?}", path))?;
+        let contents = config_crypto::unseal(contents)?;
+
+        serde_json::from_str(&contents)
+            .with_context(|| "Failed to parse pinned host keys JSON")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::pinned_keys_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize pinned host keys")?;
+        config_crypto::write_sealed(&path, contents)
+            .with_context(|| format!("Failed to write pinned host keys: {:?}", path))?;
+
+        Ok(())
+    }
+
+    fn pinned_keys_path() -> Result<PathBuf> {
+        Ok(Config::config_dir()?.join("host_keys.json"))
+    }
+
+    /// Replace the pin for `address` with a newly trusted key, keeping the
+    /// old one on record
+    pub fn trust(&mut self, address: &str, key: &ObservedKey, report: Option<TrustReport>) {
+        let now = Local::now();
+        let pinned = PinnedKey {
+            key_type: key.key_type.clone(),
+            fingerprint: key.fingerprint.clone(),
+            first_seen: key.first_seen.unwrap_or(now),
+            last_seen: now,
+        };
+        match self.hosts.get_mut(address) {
+            Some(record) => {
+                let old = std::mem::replace(&mut record.pinned, pinned);
+                record.previous.retain(|previous| previous.fingerprint != key.fingerprint);
+                record.previous.push(old);
+                record.report = report;
+            },
+            None => {
+                self.hosts.insert(address.to_string(), HostKeyRecord { pinned, previous: Vec::new(), report });
+            }
+        }
+    }
+
+    /// Note that `address` presented `key`: refresh the pin when it matches,
+    /// otherwise pin the key (it was trusted elsewhere, e.g. in known_hosts)
+    fn seen(&mut self, address: &str, key: &ObservedKey) {
+        match self.hosts.get_mut(address) {
+            Some(record) if record.pinned.fingerprint == key.fingerprint => {
+                record.pinned.last_seen = Local::now();
+            },
+            _ => self.trust(address, key, None),
+        }
+    }
+}
+
+/// How the system ssh reaches a host, from its arguments and ~/.ssh/config
+pub(crate) struct Route {
+    hostname: String,
+    port: u16,
+    user: String,
+    /// Name the key is listed under in known_hosts: HostKeyAlias, or else
+    /// the HostName
+    known_as: String,
+    /// Hosts to jump through, first hop first
+    jump: Vec<String>,
+    proxy_command: Option<String>,
+}
+
+impl Route {
+    /// Identifier pinned keys are stored under
+    fn address(&self) -> String {
+        format!("{}:{}", self.known_as, self.port)
+    }
+}
+
+/// The host's HostName, Port, HostKeyAlias and ProxyJump or ProxyCommand as
+/// `ssh -G` resolves them, or its own address when that fails
+pub(crate) async fn route(host: &Host) -> Route {
+    let mut route = Route {
+        hostname: host.host.clone(),
+        port: host.port,
+        user: host.user.clone(),
+        known_as: host.host.clone(),
+        jump: Vec::new(),
+        proxy_command: None,
+    };
+    let mut command = tokio::process::Command::new("ssh");
+    command.arg("-G").args(host.ssh_option_args()).args(["-p", &host.port.to_string()]);
+    if !host.user.is_empty() {
+        command.args(["-l", &host.user]);
+    }
+    let output = match command.arg(&host.host).stdin(Stdio::null()).stderr(Stdio::null()).output().await {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            warn!("Could not resolve {} with ssh -G; fetching its key directly", host.host);
+            return route;
+        },
+    };
+
+    let mut alias = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((name, value)) = line.split_once(' ') else {
+            continue;
+        };
+        match name {
+            "hostname" => route.hostname = value.to_string(),
+            "port" => route.port = value.parse().unwrap_or(route.port),
+            "user" => route.user = value.to_string(),
+            "hostkeyalias" => alias = Some(value.to_string()),
+            "proxyjump" if value != "none" => route.jump = value.split(',').map(str::to_string).collect(),
+            "proxycommand" if value != "none" => route.proxy_command = Some(value.to_string()),
+            _ => {},
+        }
+    }
+    route.known_as = alias.unwrap_or_else(|| route.hostname.clone());
+    route
+}
+
+/// Check the key the host presents against `~/.ssh/known_hosts` and, for
+/// hosts not listed there, against sshtui's pin. Keys that need a decision
+/// come with a trust report on what `backend` would connect to.
+pub async fn verify(host: &Host, backend: SshBackend) -> Verification {
+    let route = route(host).await;
+    let mut observed = match fetch(&route).await {
+        Ok(observed) => observed,
+        Err(e) => {
+            warn!("Could not fetch host key of {}: {}", host.host, e);
+            return Verification::Unavailable(e.to_string());
+        }
+    };
+
+    let loaded = PinnedKeys::load();
+    if let Err(e) = &loaded {
+        warn!("Could not load pinned host keys: {}", e);
+    }
+    // Pins that could not be read, such as encrypted ones, are not written over
+    let readable = loaded.is_ok();
+    let mut keys = loaded.unwrap_or_default();
+    let address = route.address();
+    let record = keys.hosts.get(&address);
+    let pin = record.map(|record| &record.pinned);
+    let recorded = known_hosts::known_host_keys(&route.known_as, route.port).unwrap_or_else(|e| {
+        warn!("Could not read known_hosts: {}", e);
+        Vec::new()
+    });
+
+    // known_hosts is authoritative for the key types it lists
+    let known = match recorded.into_iter().find(|(_, key)| key.algorithm() == observed.key.algorithm()) {
+        Some((_, key)) if key == observed.key => None,
+        Some((line, key)) => {
+            let fingerprint = fingerprint(&key);
+            let pin = pin.filter(|pin| pin.fingerprint == fingerprint);
+            Some(KnownKey {
+                key_type: key.algorithm().as_str().to_string(),
+                fingerprint,
+                first_seen: pin.map(|pin| pin.first_seen),
+                last_seen: pin.map(|pin| pin.last_seen),
+                source: KeySource::KnownHosts { line },
+            })
+        },
+        None => match pin {
+            Some(pin) if pin.fingerprint == observed.fingerprint => None,
+            Some(pin) => Some(KnownKey {
+                key_type: pin.key_type.clone(),
+                fingerprint: pin.fingerprint.clone(),
+                first_seen: Some(pin.first_seen),
+                last_seen: Some(pin.last_seen),
+                source: KeySource::Pinned,
+            }),
+            None => {
+                let report = trust_report::collect(host, &observed.key, backend).await;
+                return Verification::Prompt(Box::new(HostKeyPrompt {
+                    host_name: host.name.clone(),
+                    host: route.known_as.clone(),
+                    port: route.port,
+                    known: None,
+                    observed,
+                    report,
+                    previous_report: None,
+                }));
+            }
+        },
+    };
+
+    if let Some(known) = known {
+        observed.first_seen = record.and_then(|record| {
+            record.previous.iter()
+                .find(|previous| previous.fingerprint == observed.fingerprint)
+                .map(|previous| previous.first_seen)
+        });
+        let report = trust_report::collect(host, &observed.key, backend).await;
+        return Verification::Prompt(Box::new(HostKeyPrompt {
+            host_name: host.name.clone(),
+            host: route.known_as.clone(),
+            port: route.port,
+            known: Some(known),
+            observed,
+            report,
+            previous_report: record.and_then(|record| record.report.clone()),
+        }));
+    }
+
+    keys.seen(&address, &observed);
+    if readable {
+        if let Err(e) = keys.save() {
+            warn!("Could not save pinned host keys: {}", e);
+        }
+    }
+    Verification::Trusted(observed.fingerprint)
+}
+
+/// Record a key the user chose to trust: in known_hosts (replacing the entry
+/// it conflicts with) so the system ssh binary accepts it too, and as the pin
+pub fn trust(prompt: &HostKeyPrompt) -> Result<()> {
+    if let Some(KnownKey { source: KeySource::KnownHosts { line }, .. }) = &prompt.known {
+        remove_known_hosts_entry(*line)?;
+    }
+    known_hosts::learn_known_hosts(&prompt.host, prompt.port, &prompt.observed.key)
+        .with_context(|| "Failed to update known_hosts")?;
+
+    info!("Trusting {} key {} for {}", prompt.observed.key_type, prompt.observed.fingerprint, prompt.address());
+    let mut keys = PinnedKeys::load()?;
+    keys.trust(&prompt.address(), &prompt.observed, Some(prompt.report.clone()));
+    keys.save()
+}
+
+/// Drop a known_hosts entry, counting lines the way russh reports them
+fn remove_known_hosts_entry(line: usize) -> Result<()> {
+    let path = dirs::home_dir()
+        .ok_or_else(|| anyhow!("Could not find home directory"))?
+        .join(".ssh")
+        .join("known_hosts");
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+
+    let mut entry = 0;
+    let mut kept = String::new();
+    for text in contents.lines() {
+        if !text.starts_with('#') {
+            entry += 1;
+            if entry == line {
+                continue;
+            }
+        }
+        kept.push_str(text);
+        kept.push('\n');
+    }
+
+    // Written beside it and renamed over it, so a failed write leaves it whole
+    let temp_path = path.with_extension("sshtui.tmp");
+    let _ = fs::remove_file(&temp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        let mode = fs::metadata(&path).map(|metadata| metadata.permissions().mode()).unwrap_or(0o600);
+        options.mode(mode & 0o777);
+    }
+    options.open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(kept.as_bytes())?;
+            file.sync_all()
+        })
+        .with_context(|| format!("Failed to write {:?}", temp_path))?;
+    fs::rename(&temp_path, &path).with_context(|| format!("Failed to replace {:?}", path))
+}
+
+/// Records the server key and then aborts the handshake
+struct KeyGrabber(Arc<Mutex<Option<PublicKey>>>);
+
+impl client::Handler for KeyGrabber {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, server_public_key: &PublicKey) -> Result<bool, Self::Error> {
+        *self.0.lock().unwrap() = Some(server_public_key.clone());
+        Ok(false)
+    }
+}
+
+/// Key the host presents, read from a handshake that is dropped before
+/// authentication
+async fn fetch(route: &Route) -> Result<ObservedKey> {
+    let key = Arc::new(Mutex::new(None));
+    let config = Arc::new(client::Config::default());
+    let fetched = async {
+        let stream = route.connect().await?;
+        // The handshake always fails since the key is rejected; only the key matters
+        let _ = client::connect_stream(config, stream, KeyGrabber(key.clone())).await;
+        anyhow::Ok(())
+    };
+    tokio::time::timeout(FETCH_TIMEOUT, fetched).await
+        .map_err(|_| anyhow!("timed out"))??;
+
+    let key = key.lock().unwrap().take();
+    key.map(ObservedKey::from_public_key)
+        .ok_or_else(|| anyhow!("no key received from {}:{}", route.hostname, route.port))
+}
+
+/// A byte stream both ways
+trait Duplex: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Duplex for T {}
+
+/// A connection to a host's ssh server, through its jump host or proxy
+/// command when it has one
+pub(crate) struct HostStream {
+    io: Box<dyn Duplex>,
+    /// Killed when the stream is dropped
+    _proxy: Option<tokio::process::Child>,
+}
+
+impl AsyncRead for HostStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for HostStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+impl Route {
+    /// Connect to the host's ssh server the way ssh does. A jump host is
+    /// used in batch mode, so one that needs a password fails rather than
+    /// waiting for it.
+    pub(crate) async fn connect(&self) -> Result<HostStream> {
+        let mut command = if let Some((last, first)) = self.jump.split_last() {
+            let mut command = tokio::process::Command::new("ssh");
+            command.args(["-o", "BatchMode=yes", "-W", &format!("[{}]:{}", self.hostname, self.port)]);
+            if !first.is_empty() {
+                command.args(["-J", &first.join(",")]);
+            }
+            command.arg(last);
+            command
+        } else if let Some(proxy_command) = &self.proxy_command {
+            let mut command = tokio::process::Command::new("sh");
+            command.args(["-c", &expand_proxy_command(proxy_command, self)]);
+            command
+        } else {
+            let stream = TcpStream::connect((self.hostname.as_str(), self.port)).await?;
+            return Ok(HostStream { io: Box::new(stream), _proxy: None });
+        };
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| "Failed to start the proxy to the host")?;
+        let (Some(stdout), Some(stdin)) = (child.stdout.take(), child.stdin.take()) else {
+            return Err(anyhow!("could not talk to the proxy"));
+        };
+        Ok(HostStream { io: Box::new(tokio::io::join(stdout, stdin)), _proxy: Some(child) })
+    }
+}
+
+/// `command` with the %h, %p, %r and %% tokens ssh expands in a ProxyCommand
+/// filled in
+fn expand_proxy_command(command: &str, route: &Route) -> String {
+    let mut expanded = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => expanded.push_str(&route.hostname),
+            Some('p') => expanded.push_str(&route.port.to_string()),
+            Some('r') => expanded.push_str(&route.user),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            },
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// SHA256 fingerprint as printed by `ssh-keygen -l`
+pub fn fingerprint(key: &PublicKey) -> String {
+    key.fingerprint(HashAlg::Sha256).to_string()
+}
+
+/// The key's fingerprint drawn as `ssh-keygen -lv` does
+pub fn randomart(key: &PublicKey) -> String {
+    key.fingerprint(HashAlg::Sha256).to_randomart(&format!("[{}]", key.algorithm().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_command_tokens_are_expanded() {
+        let route = Route {
+            hostname: "10.0.0.5".to_string(),
+            port: 2222,
+            user: "ops".to_string(),
+            known_as: "10.0.0.5".to_string(),
+            jump: Vec::new(),
+            proxy_command: None,
+        };
+        assert_eq!(
+            expand_proxy_command("nc -X connect -x proxy:3128 %h %p # %r 100%% %n", &route),
+            "nc -X connect -x proxy:3128 10.0.0.5 2222 # ops 100% %n"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{AppState, IdentityForm, MessageType, ModalState};
+use crate::config::{Config, Host};
+use crate::hostkeys;
+use crate::ssh::{self, ConnectionProfile};
+use anyhow::{Context, Result};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::PublicKey;
+use std::fs;
+use std::path::PathBuf;
+
+/// Something a session can sign in with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identity {
+    /// Private key from the Keys panel
+    Key { name: String, path: String },
+    /// Key held by ssh-agent
+    Agent { label: String, public_key: String },
+}
+
+impl Identity {
+    pub fn label(&self) -> String {
+        match self {
+            Identity::Key { name, path } => format!("{} ({})", name, path),
+            Identity::Agent { label, .. } => format!("agent: {}", label),
+        }
+    }
+
+    /// Key path to connect with. Agent keys are written out as a `.pub`
+    /// file; ssh (and the embedded backend) then sign with the agent's key.
+    fn key_path(&self) -> Result<String> {
+        match self {
+            Identity::Key { path, .. } => Ok(path.clone()),
+            Identity::Agent { public_key, .. } => {
+                let path = agent_key_path(public_key)?;
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&path, format!("{}\n", public_key))
+                    .with_context(|| format!("Could not write {:?}", path))?;
+                Ok(path.to_string_lossy().into_owned())
+            }
+        }
+    }
+}
+
+/// Where an agent key's `.pub` file is kept, named after its fingerprint
+fn agent_key_path(public_key: &str) -> Result<PathBuf> {
+    let key = PublicKey::from_openssh(public_key).context("Invalid agent key")?;
+    let name = hostkeys::fingerprint(&key).replace([':', '/', '+'], "_");
+    Ok(Config::config_dir()?.join("agent").join(format!("{}.pub", name)))
+}
+
+/// Keys offered by the running ssh-agent; empty without one
+async fn agent_identities() -> Vec<Identity> {
+    let Ok(mut agent) = AgentClient::connect_env().await else {
+        return Vec::new();
+    };
+    agent.request_identities().await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|key| {
+            let label = if key.comment().is_empty() { hostkeys::fingerprint(&key) } else { key.comment().to_string() };
+            let public_key = key.to_openssh().ok()?;
+            Some(Identity::Agent { label, public_key })
+        })
+        .collect()
+}
+
+impl AppState {
+    /// After a rejected login, list the other keys and agent identities the
+    /// connection could be retried with
+    pub async fn offer_identities(&mut self, profile: &ConnectionProfile, reason: &str) {
+        self.identity_trial = None;
+        let tried = profile.key_path.as_deref();
+        let mut identities: Vec<Identity> = self.config.keys.iter()
+            .filter(|key| Some(ssh::expand_key_path(&key.path).as_str()) != tried)
+            .map(|key| Identity::Key { name: key.name.clone(), path: key.path.clone() })
+            .collect();
+        identities.extend(agent_identities().await.into_iter().filter(|identity| match identity {
+            Identity::Agent { public_key, .. } => {
+                agent_key_path(public_key).ok().as_deref().and_then(|path| path.to_str()) != tried
+            },
+            Identity::Key { .. } => true,
+        }));
+
+        if identities.is_empty() {
+            self.set_message(format!("SSH error: {}", reason), MessageType::Error);
+            return;
+        }
+        self.set_message(format!("{} - pick another identity", reason), MessageType::Error);
+        self.modal_state = ModalState::Identities(IdentityForm {
+            host_name: profile.host.name.clone(),
+            identities,
+            cursor: 0,
+        });
+    }
+
+    /// Queue a new attempt with the chosen identity for the next tick
+    pub fn retry_with_identity(&mut self, form: IdentityForm) {
+        let Some(identity) = form.identities.get(form.cursor) else {
+            return;
+        };
+        let key_path = match identity.key_path() {
+            Ok(key_path) => key_path,
+            Err(e) => {
+                self.set_message(format!("Could not use {}: {:#}", identity.label(), e), MessageType::Error);
+                return;
+            }
+        };
+
+        // The host as configured, or as last connected if it was deleted since
+        let host = self.config.get_hosts_for_group(0)
+            .into_iter()
+            .find(|host| host.name == form.host_name)
+            .or_else(|| self.ssh_client.profile.as_ref().map(|profile| profile.host.clone()))
+            .filter(|host| host.name == form.host_name);
+        let Some(mut host) = host else {
+            return;
+        };
+        host.key_path = Some(key_path);
+        self.set_message(format!("Retrying {} with {}", host.name, identity.label()), MessageType::Info);
+        self.identity_trial = Some(host.name.clone());
+        self.identity_retry = Some(host);
+    }
+
+    pub async fn poll_identity_retry(&mut self) {
+        if let Some(host) = self.identity_retry.take() {
+            self.cancel_reconnect();
+            let _ = self.connect_to_host(host).await;
+        }
+    }
+
+    /// Keep the identity that just worked as the host's key, once the
+    /// server accepted it and the session got past its login
+    pub fn remember_identity(&mut self, host: &Host) {
+        if self.identity_trial.take().as_deref() != Some(host.name.as_str()) {
+            return;
+        }
+        let before = self.snapshot();
+        if let Some(configured) = self.config.find_host_mut(host.id) {
+            configured.key_path = host.key_path.clone();
+            self.record_change(before, format!("Changed the key of host '{}'", host.name));
+        }
+        match self.config.save() {
+            Ok(()) => self.set_message(
+                format!("Connected to {}; its key is now {}", host.name, host.key_path.as_deref().unwrap_or_default()),
+                MessageType::Success
+            ),
+            Err(e) => self.set_message(format!("Could not save the key for {}: {}", host.name, e), MessageType::Error),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{AppState, MessageType};
+use crate::config_crypto;
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::time::{Duration, Instant};
+
+/// Idle time after which the status bar starts showing it
+const SHOW_IDLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Sessions hidden after the user walked away; typing resumes them
+#[derive(Debug, Clone, Default)]
+pub struct SessionLock {
+    /// Typed so far, when the config passphrase is asked for
+    pub passphrase: String,
+    pub wrong_passphrase: bool,
+}
+
+impl AppState {
+    /// Note the user at the keyboard or mouse
+    pub fn note_activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// How long the user has been away from the keyboard and mouse, once
+    /// that is long enough to be worth showing
+    pub fn idle_time(&self) -> Option<Duration> {
+        Some(self.last_activity.elapsed()).filter(|idle| *idle >= SHOW_IDLE_AFTER)
+    }
+
+    pub fn sessions_locked(&self) -> bool {
+        self.session_lock.is_some()
+    }
+
+    /// Whether resuming needs the config passphrase rather than any key
+    pub fn lock_needs_passphrase(&self) -> bool {
+        config_crypto::is_enabled()
+    }
+
+    /// Hide the sessions once nothing was typed for the configured time.
+    /// They stay connected meanwhile, ssh's ServerAlive probes keeping
+    /// quiet connections up.
+    pub fn poll_idle_lock(&mut self) {
+        let minutes = self.config.settings.lock_after_mins;
+        if minutes == 0 || self.sessions_locked() || self.last_activity.elapsed() < Duration::from_secs(minutes * 60) {
+            return;
+        }
+        if !self.ssh_client.is_connected() && self.background_sessions.is_empty() {
+            return;
+        }
+        self.session_lock = Some(SessionLock::default());
+    }
+
+    /// Keys while the sessions are locked: any key resumes them, or the
+    /// config passphrase and Enter when the config is encrypted. Returns
+    /// false when they are not locked.
+    pub fn handle_lock_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let needs_passphrase = self.lock_needs_passphrase();
+        let Some(lock) = &mut self.session_lock else {
+            return false;
+        };
+        if !needs_passphrase {
+            self.unlock_sessions();
+            return true;
+        }
+        match code {
+            KeyCode::Enter => {
+                if config_crypto::verify_passphrase(&lock.passphrase) {
+                    self.unlock_sessions();
+                } else {
+                    lock.passphrase.clear();
+                    lock.wrong_passphrase = true;
+                }
+            },
+            KeyCode::Backspace => {
+                lock.passphrase.pop();
+            },
+            KeyCode::Esc => lock.passphrase.clear(),
+            KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) => lock.passphrase.push(c),
+            _ => {},
+        }
+        true
+    }
+
+    fn unlock_sessions(&mut self) {
+        self.session_lock = None;
+        self.note_activity();
+        self.set_message("Sessions unlocked".to_string(), MessageType::Info);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{AppState, ImportForm, ImportPreviewForm, MessageType, ModalState};
+use crate::{ansible, putty, securecrt, termius};
+use crate::config::{Config, Group, Host};
+use crate::line_editor::LineEditor;
+use crate::ssh::expand_key_path;
+use std::fs;
+
+/// Reads the hosts another tool keeps, for `sshtui import` and the import
+/// form
+pub trait Importer {
+    /// The groups and hosts in an export's text; `path` is the file it
+    /// was read from
+    fn parse(&self, path: &str, text: &str) -> Result<Vec<Group>, String>;
+
+    /// The groups and hosts of the export at `path`
+    fn read(&self, path: &str) -> Result<Vec<Group>, String> {
+        let text = fs::read_to_string(expand_key_path(path)).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        self.parse(path, &text)
+    }
+}
+
+/// Tools whose host lists can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// Ansible inventory, INI or YAML
+    Ansible,
+    /// CSV exported from Termius
+    Termius,
+    /// PuTTY sessions: a `reg export` of them, or ~/.putty/sessions
+    Putty,
+    /// XML exported from SecureCRT
+    #[value(name = "securecrt")]
+    SecureCrt,
+}
+
+impl ImportFormat {
+    pub const ALL: [ImportFormat; 4] = [ImportFormat::Ansible, ImportFormat::Termius, ImportFormat::Putty, ImportFormat::SecureCrt];
+
+    pub fn importer(self) -> &'static dyn Importer {
+        match self {
+            ImportFormat::Ansible => &ansible::AnsibleInventory,
+            ImportFormat::Termius => &termius::TermiusCsv,
+            ImportFormat::Putty => &putty::PuttySessions,
+            ImportFormat::SecureCrt => &securecrt::SecureCrtXml,
+        }
+    }
+
+    /// As shown in the import form
+    pub fn label(self) -> &'static str {
+        match self {
+            ImportFormat::Ansible => "Ansible inventory (INI or YAML)",
+            ImportFormat::Termius => "Termius CSV export",
+            ImportFormat::Putty => "PuTTY sessions (.reg export or ~/.putty/sessions)",
+            ImportFormat::SecureCrt => "SecureCRT XML export",
+        }
+    }
+
+    /// The next format, or the previous one going back
+    pub fn cycle(self, forward: bool) -> Self {
+        let index = Self::ALL.iter().position(|format| *format == self).unwrap_or(0);
+        let count = Self::ALL.len();
+        Self::ALL[if forward { (index + 1) % count } else { (index + count - 1) % count }]
+    }
+}
+
+/// Groups and hosts read from another tool, with what merging them would
+/// change, shown before anything is added
+#[derive(Debug, Clone)]
+pub struct ImportPlan {
+    /// File they were read from
+    pub source: String,
+    /// New groups in full, and existing groups with only their new hosts
+    groups: Vec<Group>,
+    /// One per change, such as "+ host 'web1' in 'prod' (10.0.0.5)"
+    pub lines: Vec<String>,
+}
+
+impl ImportPlan {
+    /// Whether merging it would change nothing
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Such as "3 hosts from hosts.ini"
+    pub fn summary(&self) -> String {
+        let hosts: usize = self.groups.iter().map(|group| group.hosts.len()).sum();
+        let file = self.source.rsplit('/').next().unwrap_or(&self.source);
+        format!("{} host{} from {}", hosts, if hosts == 1 { "" } else { "s" }, file)
+    }
+}
+
+/// The groups and hosts of the export at `path`
+pub fn read(format: ImportFormat, path: &str) -> Result<Vec<Group>, String> {
+    format.importer().read(path)
+}
+
+/// Hosts paired with the name of their group, gathered into groups sorted
+/// by name. Tools that keep no groups put everything in one named after them.
+pub fn into_groups(hosts: Vec<(String, Host)>) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for (group_name, host) in hosts {
+        match groups.iter_mut().find(|group| group.name == group_name) {
+            Some(group) => group.hosts.push(host),
+            None => groups.push(Group { hosts: vec![host], ..Group::new(group_name) }),
+        }
+    }
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+    groups
+}
+
+fn host_line(host: &Host, group: &str) -> String {
+    format!("+ host '{}' in '{}' ({})", host.name, group, host.host)
+}
+
+/// What merging `imported` into the config would add. Groups are matched
+/// by name and hosts already in their group are skipped; a host joining an
+/// existing group keeps the user, port and key its imported group gave it.
+pub fn plan(config: &Config, source: &str, imported: Vec<Group>) -> ImportPlan {
+    let mut groups = Vec::new();
+    let mut lines = Vec::new();
+    for mut group in imported {
+        if group.name == "All" {
+            lines.push("= group 'All' is the list of every host, skipped".to_string());
+            continue;
+        }
+        let existing = config.groups.iter().find(|existing| existing.name == group.name);
+        if existing.is_none() {
+            lines.push(format!("+ group '{}'", group.name));
+        }
+
+        let defaults = group.defaults.clone();
+        let mut hosts: Vec<Host> = Vec::new();
+        for mut host in std::mem::take(&mut group.hosts) {
+            if existing.is_some_and(|existing| existing.hosts.iter().any(|other| other.name == host.name)) {
+                lines.push(format!("= host '{}' in '{}' is already there, skipped", host.name, group.name));
+                continue;
+            }
+            if hosts.iter().any(|other| other.name == host.name) {
+                lines.push(format!("= host '{}' in '{}' is listed twice, skipped", host.name, group.name));
+                continue;
+            }
+            // The existing group's own defaults would apply otherwise
+            if existing.is_some() {
+                if host.user.is_empty() {
+                    host.user = defaults.user.clone().unwrap_or_default();
+                }
+                if host.port == 0 {
+                    host.port = defaults.port.unwrap_or(0);
+                }
+                host.key_path = host.key_path.or_else(|| defaults.key_path.clone());
+            }
+            lines.push(host_line(&host, &group.name));
+            hosts.push(host);
+        }
+        if existing.is_none() || !hosts.is_empty() {
+            group.hosts = hosts;
+            groups.push(group);
+        }
+    }
+    ImportPlan { source: source.to_string(), groups, lines }
+}
+
+/// Add the plan's groups and hosts to the config
+pub fn merge(config: &mut Config, plan: ImportPlan) {
+    for group in plan.groups {
+        match config.groups.iter_mut().find(|existing| existing.name == group.name) {
+            Some(existing) => existing.hosts.extend(group.hosts),
+            None => config.groups.push(group),
+        }
+    }
+    config.ensure_parent_groups();
+}
+
+impl AppState {
+    pub fn open_import(&mut self) {
+        self.modal_state = ModalState::Import(ImportForm {
+            format: ImportFormat::Ansible,
+            path: String::new(),
+            field_focus: 1,
+            editor: LineEditor::default(),
+        });
+    }
+
+    /// Read the export named in the form and show what merging it would
+    /// add; nothing changes until that is confirmed
+    pub fn preview_import(&mut self, form: ImportForm) {
+        let path = form.path.trim().to_string();
+        if path.is_empty() {
+            self.set_message("Give the file to import".to_string(), MessageType::Error);
+            self.modal_state = ModalState::Import(form);
+            return;
+        }
+        let groups = match read(form.format, &path) {
+            Ok(groups) => groups,
+            Err(e) => {
+                self.set_message(format!("Could not import {}: {}", path, e), MessageType::Error);
+                self.modal_state = ModalState::Import(form);
+                return;
+            },
+        };
+        let plan = plan(&self.config, &path, groups);
+        if plan.is_empty() {
+            self.set_message(format!("Nothing to import: every host in {} is already here", path), MessageType::Info);
+            return;
+        }
+        self.modal_state = ModalState::ImportPreview(ImportPreviewForm {
+            format: form.format,
+            path,
+            lines: plan.lines.clone(),
+            scroll: 0,
+            confirm_selected: true,
+        });
+        self.pending_import = Some(plan);
+    }
+
+    /// Back from the preview to the file name, to pick another
+    pub fn back_to_import(&mut self, form: ImportPreviewForm) {
+        self.pending_import = None;
+        self.modal_state = ModalState::Import(ImportForm {
+            format: form.format,
+            path: form.path,
+            field_focus: 1,
+            editor: LineEditor::default(),
+        });
+    }
+
+    /// Merge the previewed import into the config
+    pub fn apply_import(&mut self) {
+        let Some(plan) = self.pending_import.take() else {
+            return;
+        };
+        let before = self.snapshot();
+        let summary = plan.summary();
+        merge(&mut self.config, plan);
+        self.record_change(before, format!("Imported {}", summary));
+        if let Err(e) = self.config.save() {
+            self.set_message(format!("Imported {} but could not save: {}", summary, e), MessageType::Error);
+            return;
+        }
+        self.set_message(format!("Imported {}", summary), MessageType::Success);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{AppState, ModalState};
+use crate::config::Config;
+use crate::config_crypto;
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// The lines `keep_unsent_input` would write out now, for the panic hook
+static ON_PANIC: Mutex<Option<UnsentInput>> = Mutex::new(None);
+
+/// Write out the unfinished lines if sshtui panics, as when it exits
+pub fn keep_on_panic() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // The lock may be held by the code that panicked
+        if let Ok(guard) = ON_PANIC.try_lock() {
+            if let Some(input) = guard.as_ref() {
+                let _ = input.save();
+            }
+        }
+        previous(info);
+    }));
+}
+
+/// The line typed into each host's shell since the last Enter. It is kept
+/// in memory while typing and written out only when a session drops or
+/// sshtui exits or panics, so it can be offered again on the next connection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnsentInput {
+    /// By host id
+    pub lines: BTreeMap<Uuid, String>,
+    /// Hosts whose line the remote echoed back as it was typed; a line
+    /// that was never echoed may be a password and is not kept
+    #[serde(skip)]
+    echoed: BTreeSet<Uuid>,
+}
+
+impl UnsentInput {
+    pub fn load() -> Result<Self> {
+        let path = Self::swap_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read unsent input: {:?}", path))?;
+
+        // Lines saved by host name, before hosts had ids, are let go
+        #[derive(Deserialize)]
+        struct Stored {
+            lines: BTreeMap<String, String>,
+        }
+        let stored: Stored = serde_json::from_str(&contents)
+            .with_context(|| "Failed to parse unsent input JSON")?;
+        let lines = stored.lines.into_iter()
+            .filter_map(|(key, line)| Uuid::parse_str(&key).ok().map(|id| (id, line)))
+            .collect();
+        Ok(Self { lines, echoed: BTreeSet::new() })
+    }
+
+    /// Write the lines out, readable by the owner only, removing the file
+    /// once nothing is left. Nothing is written while the config is
+    /// encrypted, as the lines would be the only plaintext on disk.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::swap_path()?;
+
+        if self.lines.is_empty() || config_crypto::is_enabled() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove unsent input: {:?}", path))?;
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize unsent input")?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .with_context(|| format!("Failed to write unsent input: {:?}", path))?;
+
+        // A file left by an older version may still be readable by others
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to set unsent input permissions: {:?}", path))?;
+        }
+
+        Ok(())
+    }
+
+    fn swap_path() -> Result<PathBuf> {
+        Ok(Config::config_dir()?.join("unsent_input.json"))
+    }
+}
+
+/// Replay what `data` does to a shell's input line: printable characters
+/// are added, Backspace takes one back, Enter, Ctrl+C and Ctrl+U start a new
+/// line and escape sequences such as cursor keys are skipped
+fn apply(line: &mut String, data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' | '\n' | '\x03' | '\x15' => line.clear(),
+            '\x7f' | '\x08' => {
+                line.pop();
+            },
+            '\x1b' => {
+                // CSI sequences run up to a final byte in @..~, SS3 ones
+                // (application cursor keys) are one character longer than
+                // Alt+key
+                match chars.next() {
+                    Some('[') => {
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                break;
+                            }
+                        }
+                    },
+                    Some('O') => {
+                        chars.next();
+                    },
+                    _ => {}
+                }
+            },
+            c if !c.is_control() => line.push(c),
+            _ => {}
+        }
+    }
+}
+
+impl AppState {
+    /// Follow input sent to the live session, keeping the unfinished line
+    /// in memory. Nothing is kept while the remote does not echo what is
+    /// typed, as at a password prompt.
+    pub fn track_input(&mut self, data: &[u8]) {
+        let Some(host_id) = self.ssh_client.get_host().map(|host| host.id) else {
+            return;
+        };
+        if self.terminal_panel.echo_missing() {
+            self.unsent_input.lines.remove(&host_id);
+            self.unsent_input.echoed.remove(&host_id);
+            keep_for_panic(self.unsent_input.clone());
+            return;
+        }
+        if self.terminal_panel.remote_echoes() {
+            self.unsent_input.echoed.insert(host_id);
+        }
+
+        let mut line = self.unsent_input.lines.get(&host_id).cloned().unwrap_or_default();
+        apply(&mut line, data);
+        if line.is_empty() {
+            self.unsent_input.lines.remove(&host_id);
+            self.unsent_input.echoed.remove(&host_id);
+        } else {
+            self.unsent_input.lines.insert(host_id, line);
+        }
+
+        let mut kept = self.unsent_input.clone();
+        if !kept.echoed.contains(&host_id) {
+            kept.lines.remove(&host_id);
+        }
+        keep_for_panic(kept);
+    }
+
+    /// Write out the live session's unfinished line as the session ends or
+    /// sshtui exits, if the remote was echoing it
+    pub fn keep_unsent_input(&mut self) {
+        if let Some(host_id) = self.ssh_client.get_host().map(|host| host.id) {
+            let echoed = self.unsent_input.echoed.remove(&host_id) || self.terminal_panel.remote_echoes();
+            if !echoed {
+                self.unsent_input.lines.remove(&host_id);
+            }
+        }
+        if let Err(e) = self.unsent_input.save() {
+            warn!("Could not save unsent input: {}", e);
+        }
+        keep_for_panic(self.unsent_input.clone());
+    }
+
+    /// Offer to type again what was left unsent in the last session to
+    /// the host with id `host_id`
+    pub fn offer_unsent_input(&mut self, host_id: Uuid) {
+        if !matches!(self.modal_state, ModalState::None) {
+            return;
+        }
+        let Some(line) = self.unsent_input.lines.remove(&host_id) else {
+            return;
+        };
+        if let Err(e) = self.unsent_input.save() {
+            warn!("Could not save unsent input: {}", e);
+        }
+        keep_for_panic(self.unsent_input.clone());
+        self.modal_state = ModalState::RecoverInput(line);
+    }
+
+    /// Forget the live session's unfinished line, for a disconnect the user
+    /// asked for
+    pub fn discard_unsent_input(&mut self) {
+        let Some(host) = self.ssh_client.get_host() else {
+            return;
+        };
+        self.unsent_input.echoed.remove(&host.id);
+        if self.unsent_input.lines.remove(&host.id).is_some() {
+            if let Err(e) = self.unsent_input.save() {
+                warn!("Could not save unsent input: {}", e);
+            }
+            keep_for_panic(self.unsent_input.clone());
+        }
+    }
+}
+
+fn keep_for_panic(input: UnsentInput) {
+    *ON_PANIC.lock().unwrap_or_else(|e| e.into_inner()) = Some(input);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(inputs: &[&[u8]]) -> String {
+        let mut line = String::new();
+        for data in inputs {
+            apply(&mut line, data);
+        }
+        line
+    }
+
+    #[test]
+    fn keeps_typed_characters() {
+        assert_eq!(replay(&[b"l", b"s -la ", "é".as_bytes()]), "ls -la é");
+    }
+
+    #[test]
+    fn backspace_takes_one_back() {
+        assert_eq!(replay(&[b"gti", b"\x7f\x7f", b"it"]), "git");
+        assert_eq!(replay(&[b"a\x08b"]), "b");
+        assert_eq!(replay(&[b"\x7f"]), "");
+    }
+
+    #[test]
+    fn enter_and_line_kills_start_a_new_line() {
+        assert_eq!(replay(&[b"make\r", b"cargo"]), "cargo");
+        assert_eq!(replay(&[b"rm -rf /\x03"]), "");
+        assert_eq!(replay(&[b"oops\x15", b"ok"]), "ok");
+        assert_eq!(replay(&[b"one\ntwo"]), "two");
+    }
+
+    #[test]
+    fn skips_escape_sequences() {
+        // Cursor keys in normal and application mode, and a modified arrow
+        assert_eq!(replay(&[b"ab", b"\x1b[D", b"\x1bOA", b"\x1b[1;5C", b"c"]), "abc");
+        // Bracketed paste markers around pasted text
+        assert_eq!(replay(&[b"\x1b[200~echo hi\x1b[201~"]), "echo hi");
+        // Alt+key
+        assert_eq!(replay(&[b"x\x1bby"]), "xy");
+    }
+
+    #[test]
+    fn ignores_other_control_characters() {
+        assert_eq!(replay(&[b"ls\t\x07"]), "ls");
+    }
+}
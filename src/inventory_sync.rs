@@ -0,0 +1,532 @@
+use crate::{AppState, ConfirmAction, ConfirmForm, MessageType, ModalState, SharedInventoryForm};
+use crate::config::{self, Config, ConnectionKind, Group, Host, ProbeConfig};
+use crate::dashboard::format_duration;
+use crate::secrets;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// How long a request to an HTTP endpoint may take
+const HTTP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// What team members share: the groups and their hosts. Keys, settings and
+/// passwords stay on each machine.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SharedInventory {
+    groups: Vec<Group>,
+}
+
+impl SharedInventory {
+    fn of(config: &Config) -> Self {
+        // The first group is "All", which only lists the others' hosts
+        Self { groups: config.groups.iter().skip(1).cloned().collect() }
+    }
+
+    /// Fingerprint of the inventory, the same however its file is laid out.
+    /// Ids are left out, so a shared file written before groups and hosts
+    /// had them matches the same inventory with them.
+    fn digest(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        for group in value["groups"].as_array_mut().into_iter().flatten() {
+            let Some(group) = group.as_object_mut() else {
+                continue;
+            };
+            group.remove("id");
+            for host in group.get_mut("hosts").and_then(Value::as_array_mut).into_iter().flatten() {
+                if let Some(host) = host.as_object_mut() {
+                    host.remove("id");
+                }
+            }
+        }
+        let digest = md5::compute(value.to_string());
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn has_hosts(&self) -> bool {
+        self.groups.iter().any(|group| !group.hosts.is_empty())
+    }
+
+    /// Settings of the shared groups and hosts that run commands, on this
+    /// machine or in their sessions, and that this machine does not have
+    /// yet. Anyone who can write the shared file could otherwise run code
+    /// on every teammate's machine, so these are shown before they are taken.
+    fn incoming_commands(&self, config: &Config) -> Vec<String> {
+        let mut lines = Vec::new();
+        for group in &self.groups {
+            let local = config.groups.iter().find(|local| local.id == group.id);
+            if !group.defaults.options.is_empty() && local.is_none_or(|local| local.defaults.options != group.defaults.options) {
+                lines.push(format!("! group '{}': ssh options {}", group.name, group.defaults.options.join(" ")));
+            }
+            for host in &group.hosts {
+                let known = config.find_host(host.id).map(command_settings).unwrap_or_default();
+                for setting in command_settings(host).into_iter().filter(|setting| !known.contains(setting)) {
+                    lines.push(format!("! host '{}' in '{}': {}", host.name, group.name, setting));
+                }
+            }
+        }
+        lines
+    }
+}
+
+/// What a host runs besides a plain ssh login, one line per setting
+fn command_settings(host: &Host) -> Vec<String> {
+    let mut settings = Vec::new();
+    if let ConnectionKind::Custom(command) = &host.kind {
+        settings.push(format!("runs `{}` here instead of ssh", command));
+    }
+    if !host.ssh_options.is_empty() {
+        settings.push(format!("ssh arguments {}", config::join_arguments(&host.ssh_options)));
+    }
+    for probe in &host.probes {
+        if let ProbeConfig::Command { command, .. } = probe {
+            settings.push(format!("probe runs `{}` here", command));
+        }
+    }
+    if let Some(command) = &host.remote_command {
+        settings.push(format!("runs `{}` on the host at login", command));
+    }
+    for command in &host.startup_commands {
+        settings.push(format!("types `{}` into new sessions", command));
+    }
+    if let Some(rule) = host.autoconnect.as_ref().filter(|rule| !rule.command.is_empty()) {
+        settings.push(format!("types `{}` when it comes back online", rule.command));
+    }
+    settings
+}
+
+/// Where the shared inventory is kept
+enum Endpoint {
+    /// A file reached with scp: `sftp://[user@]host[:port]/path`, where
+    /// `/~/path` is relative to the remote home
+    Sftp { target: String, port: Option<u16>, path: String },
+    /// Read with GET and written with PUT, with the token as a bearer token
+    Http(String),
+}
+
+impl Endpoint {
+    /// `with_token` refuses plain http, which would send the token in
+    /// cleartext
+    fn parse(url: &str, with_token: bool) -> Result<Self, String> {
+        if url.starts_with("http://") && with_token {
+            return Err(format!("{} is plain http; use https:// so the sync token is not sent in cleartext", url));
+        }
+        if url.starts_with("http://") || url.starts_with("https://") {
+            return Ok(Endpoint::Http(url.to_string()));
+        }
+        let invalid = || format!("{} is not an sftp:// or http(s):// location", url);
+        let rest = url.strip_prefix("sftp://").ok_or_else(invalid)?;
+        let (authority, path) = rest.split_once('/').ok_or_else(invalid)?;
+        if authority.is_empty() || path.is_empty() {
+            return Err(invalid());
+        }
+        let (target, port) = match authority.rsplit_once(':') {
+            Some((target, port)) => (target, Some(port.parse::<u16>().map_err(|_| invalid())?)),
+            None => (authority, None),
+        };
+        let path = path.strip_prefix("~/").map_or_else(|| format!("/{}", path), str::to_string);
+        Ok(Endpoint::Sftp { target: target.to_string(), port, path })
+    }
+}
+
+/// The shared inventory as last read, and the version the server gave it
+struct Fetched {
+    contents: Option<String>,
+    etag: Option<String>,
+}
+
+async fn run_scp(port: Option<u16>, from: &str, to: &str) -> Result<(), String> {
+    let mut scp = Command::new("scp");
+    scp.args(["-q", "-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+    if let Some(port) = port {
+        scp.args(["-P", &port.to_string()]);
+    }
+    scp.arg(from).arg(to).stdin(Stdio::null());
+    match scp.output().await {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(if error.is_empty() { format!("scp exited with {}", output.status) } else { error })
+        },
+        Err(e) => Err(format!("Could not run scp: {}", e)),
+    }
+}
+
+/// Scratch file for what scp reads or writes
+fn transfer_path() -> PathBuf {
+    std::env::temp_dir().join(format!("sshtui-inventory-{}.json", std::process::id()))
+}
+
+async fn fetch(endpoint: &Endpoint, token: Option<&str>) -> Result<Fetched, String> {
+    match endpoint {
+        Endpoint::Sftp { target, port, path } => {
+            let local = transfer_path();
+            let result = run_scp(*port, &format!("{}:{}", target, path), &local.to_string_lossy()).await;
+            let contents = fs::read_to_string(&local);
+            let _ = fs::remove_file(&local);
+            match result {
+                Ok(()) => Ok(Fetched { contents: Some(contents.map_err(|e| e.to_string())?), etag: None }),
+                // Nobody has shared an inventory there yet
+                Err(e) if e.contains("No such file") => Ok(Fetched { contents: None, etag: None }),
+                Err(e) => Err(e),
+            }
+        },
+        Endpoint::Http(url) => {
+            let url = url.clone();
+            let token = token.map(str::to_string);
+            tokio::task::spawn_blocking(move || {
+                let mut request = ureq::get(&url).timeout(HTTP_TIMEOUT);
+                if let Some(token) = &token {
+                    request = request.set("Authorization", &format!("Bearer {}", token));
+                }
+                match request.call() {
+                    Ok(response) => {
+                        let etag = response.header("ETag").map(str::to_string);
+                        let contents = response.into_string().map_err(|e| e.to_string())?;
+                        Ok(Fetched { contents: Some(contents), etag })
+                    },
+                    Err(ureq::Error::Status(404, _)) => Ok(Fetched { contents: None, etag: None }),
+                    Err(ureq::Error::Status(401 | 403, _)) => Err("The sync endpoint refused the token".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            })
+            .await
+            .map_err(|e| e.to_string())?
+        },
+    }
+}
+
+async fn store(endpoint: &Endpoint, token: Option<&str>, etag: Option<String>, inventory: &SharedInventory) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(inventory).map_err(|e| e.to_string())?;
+    match endpoint {
+        Endpoint::Sftp { target, port, path } => {
+            let local = transfer_path();
+            fs::write(&local, contents).map_err(|e| e.to_string())?;
+            let result = run_scp(*port, &local.to_string_lossy(), &format!("{}:{}", target, path)).await;
+            let _ = fs::remove_file(&local);
+            result
+        },
+        Endpoint::Http(url) => {
+            let url = url.clone();
+            let token = token.map(str::to_string);
+            tokio::task::spawn_blocking(move || {
+                let mut request = ureq::put(&url)
+                    .timeout(HTTP_TIMEOUT)
+                    .set("Content-Type", "application/json");
+                if let Some(token) = &token {
+                    request = request.set("Authorization", &format!("Bearer {}", token));
+                }
+                // Refused when someone else wrote it since it was read
+                if let Some(etag) = &etag {
+                    request = request.set("If-Match", etag);
+                }
+                match request.send_string(&contents) {
+                    Ok(_) => Ok(()),
+                    Err(ureq::Error::Status(412, _)) => Err("Someone else synced meanwhile; sync again".to_string()),
+                    Err(ureq::Error::Status(401 | 403, _)) => Err("The sync endpoint refused the token".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            })
+            .await
+            .map_err(|e| e.to_string())?
+        },
+    }
+}
+
+/// How a sync ended
+pub enum SyncOutcome {
+    /// Both sides already had this inventory
+    InSync(String),
+    /// The shared inventory was replaced with this machine's
+    Uploaded(String),
+    /// This machine takes the shared inventory
+    Downloaded(SharedInventory),
+    /// Both changed since the last sync
+    Conflict(SharedInventory),
+}
+
+/// Compare this machine's inventory, the shared one and the one both had
+/// at the last sync (`base`), and move it the way only one side changed
+async fn sync(endpoint: Endpoint, token: Option<String>, local: SharedInventory, base: Option<String>) -> Result<SyncOutcome, String> {
+    let local_digest = local.digest();
+    let fetched = fetch(&endpoint, token.as_deref()).await?;
+    let Some(contents) = fetched.contents else {
+        store(&endpoint, token.as_deref(), None, &local).await?;
+        return Ok(SyncOutcome::Uploaded(local_digest));
+    };
+    let shared: SharedInventory = serde_json::from_str(&contents)
+        .map_err(|e| format!("The shared inventory does not parse: {}", e))?;
+    let shared_digest = shared.digest();
+
+    if shared_digest == local_digest {
+        Ok(SyncOutcome::InSync(local_digest))
+    } else if base.as_deref() == Some(shared_digest.as_str()) {
+        store(&endpoint, token.as_deref(), fetched.etag, &local).await?;
+        Ok(SyncOutcome::Uploaded(local_digest))
+    } else if base.as_deref() == Some(local_digest.as_str()) || (base.is_none() && !local.has_hosts()) {
+        Ok(SyncOutcome::Downloaded(shared))
+    } else {
+        Ok(SyncOutcome::Conflict(shared))
+    }
+}
+
+/// The last sync of a profile's inventory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncMark {
+    /// Where it was synced with; a mark for another location does not count
+    url: String,
+    /// What both sides had then
+    digest: String,
+    synced_at: DateTime<Local>,
+}
+
+/// Last syncs, by profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncMarks {
+    pub marks: HashMap<String, SyncMark>,
+}
+
+impl SyncMarks {
+    pub fn load() -> Result<Self> {
+        let path = Self::marks_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read inventory sync state: {:?}", path))?;
+
+        serde_json::from_str(&contents)
+            .with_context(|| "Failed to parse inventory sync state JSON")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::marks_path()?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+        }
+
+        let contents = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize inventory sync state")?;
+
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write inventory sync state: {:?}", path))?;
+
+        Ok(())
+    }
+
+    fn marks_path() -> Result<PathBuf> {
+        Ok(Config::config_dir()?.join("inventory_sync.json"))
+    }
+
+    /// The current profile's last sync with `url`
+    fn get(&self, url: &str) -> Option<&SyncMark> {
+        self.marks.get(&Config::profile()).filter(|mark| mark.url == url)
+    }
+}
+
+/// A sync in progress, or the shared inventory waiting for the user to
+/// take it over their own or to accept the commands it brings
+#[derive(Default)]
+pub struct InventorySync {
+    running: Option<(String, oneshot::Receiver<Result<SyncOutcome, String>>)>,
+    pending: Option<(String, SharedInventory)>,
+}
+
+impl AppState {
+    /// Compare the host inventory with the shared one and bring whichever
+    /// side is behind up to date
+    pub fn sync_inventory(&mut self) {
+        let Some(url) = self.config.settings.inventory_sync_url.clone() else {
+            self.set_message("Set where the shared inventory is kept in the settings (Ctrl+S)".to_string(), MessageType::Error);
+            return;
+        };
+        if self.inventory_sync.running.is_some() {
+            self.set_message("Already syncing the inventory".to_string(), MessageType::Info);
+            return;
+        }
+        let token = secrets::get(self.config.settings.secrets_backend, secrets::INVENTORY_SYNC_ACCOUNT).ok().flatten();
+        let endpoint = match Endpoint::parse(&url, token.is_some()) {
+            Ok(endpoint) => endpoint,
+            Err(e) => {
+                self.set_message(e, MessageType::Error);
+                return;
+            },
+        };
+        let local = SharedInventory::of(&self.config);
+        let base = self.sync_marks.get(&url).map(|mark| mark.digest.clone());
+
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let _ = sender.send(sync(endpoint, token, local, base).await);
+        });
+        self.inventory_sync.running = Some((url, receiver));
+        self.set_message("Syncing the host inventory...".to_string(), MessageType::Info);
+    }
+
+    pub fn poll_inventory_sync(&mut self) {
+        let Some((url, receiver)) = &mut self.inventory_sync.running else {
+            return;
+        };
+        let url = url.clone();
+        let outcome = match receiver.try_recv() {
+            Ok(outcome) => outcome,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Closed) => Err("The sync stopped unexpectedly".to_string()),
+        };
+        self.inventory_sync.running = None;
+
+        match outcome {
+            Ok(SyncOutcome::InSync(digest)) => {
+                self.mark_synced(&url, digest);
+                self.set_message("The host inventory is up to date".to_string(), MessageType::Success);
+            },
+            Ok(SyncOutcome::Uploaded(digest)) => {
+                self.mark_synced(&url, digest);
+                self.set_message("Shared your host inventory".to_string(), MessageType::Success);
+            },
+            Ok(SyncOutcome::Downloaded(shared)) => {
+                let commands = shared.incoming_commands(&self.config);
+                if commands.is_empty() {
+                    self.take_shared_inventory(url, shared);
+                    return;
+                }
+                self.inventory_sync.pending = Some((url, shared));
+                self.set_message("The shared inventory runs commands; nothing was synced yet".to_string(), MessageType::Info);
+                self.modal_state = ModalState::SharedInventory(SharedInventoryForm {
+                    heading: "Take the shared inventory? It brings settings that run commands:".to_string(),
+                    lines: commands,
+                    scroll: 0,
+                    confirm_selected: false,
+                });
+            },
+            Ok(SyncOutcome::Conflict(shared)) => {
+                let commands = shared.incoming_commands(&self.config);
+                self.inventory_sync.pending = Some((url, shared));
+                self.set_message("Both sides changed; nothing was synced yet".to_string(), MessageType::Info);
+                self.modal_state = if commands.is_empty() {
+                    ModalState::Confirm(ConfirmForm {
+                        message: "Your hosts and the shared inventory both changed since the last sync. Replace yours with the shared inventory?".to_string(),
+                        action: ConfirmAction::TakeSharedInventory,
+                        confirm_selected: false,
+                    })
+                } else {
+                    ModalState::SharedInventory(SharedInventoryForm {
+                        heading: "Both sides changed. Replace your hosts with the shared inventory? It brings settings that run commands:".to_string(),
+                        lines: commands,
+                        scroll: 0,
+                        confirm_selected: false,
+                    })
+                };
+            },
+            Err(e) => self.set_message(format!("Could not sync the host inventory: {}", e), MessageType::Error),
+        }
+    }
+
+    /// Replace the groups and hosts with the shared inventory the user
+    /// agreed to take over their own changes, or with the commands it brings
+    pub fn accept_shared_inventory(&mut self) {
+        if let Some((url, shared)) = self.inventory_sync.pending.take() {
+            self.take_shared_inventory(url, shared);
+        }
+    }
+
+    /// Keep the hosts as they are; the next sync asks again
+    pub fn decline_shared_inventory(&mut self) {
+        if self.inventory_sync.pending.take().is_some() {
+            self.set_message("Kept your hosts; the shared inventory was not taken".to_string(), MessageType::Info);
+        }
+    }
+
+    fn take_shared_inventory(&mut self, url: String, shared: SharedInventory) {
+        let digest = shared.digest();
+        let mut config = self.config.clone();
+        config.groups.truncate(1);
+        config.groups.extend(shared.groups);
+        if let Err(e) = config.save() {
+            self.set_message(format!("Could not save the shared inventory: {}", e), MessageType::Error);
+            return;
+        }
+        self.use_config(config);
+        self.mark_synced(&url, digest);
+        let backup = Config::backup_path().ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+            .unwrap_or_default();
+        self.set_message(format!("Took the shared host inventory; the config from before is kept as {}", backup), MessageType::Success);
+    }
+
+    fn mark_synced(&mut self, url: &str, digest: String) {
+        let mark = SyncMark { url: url.to_string(), digest, synced_at: Local::now() };
+        self.sync_marks.marks.insert(Config::profile(), mark);
+        if let Err(e) = self.sync_marks.save() {
+            self.set_message(format!("Could not save the inventory sync state: {}", e), MessageType::Error);
+        }
+    }
+
+    /// When the inventory was last synced, for the status bar; None when no
+    /// shared inventory is set up
+    pub fn inventory_sync_status(&self) -> Option<String> {
+        let url = self.config.settings.inventory_sync_url.as_deref()?;
+        if self.inventory_sync.running.is_some() {
+            return Some("⇅ Syncing inventory".to_string());
+        }
+        Some(match self.sync_marks.get(url) {
+            Some(mark) => format!("⇅ Inventory synced {} ago", format_duration(Local::now() - mark.synced_at)),
+            None => "⇅ Inventory never synced".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared(config: &Config) -> SharedInventory {
+        SharedInventory::of(config)
+    }
+
+    #[test]
+    fn refuses_plain_http_with_a_token() {
+        assert!(Endpoint::parse("http://inventory.lan/hosts.json", true).is_err());
+        assert!(matches!(Endpoint::parse("http://inventory.lan/hosts.json", false), Ok(Endpoint::Http(_))));
+        assert!(matches!(Endpoint::parse("https://inventory.lan/hosts.json", true), Ok(Endpoint::Http(_))));
+    }
+
+    #[test]
+    fn parses_sftp_locations() {
+        let Ok(Endpoint::Sftp { target, port, path }) = Endpoint::parse("sftp://me@files:2222/~/team/hosts.json", false) else {
+            panic!("not an sftp location");
+        };
+        assert_eq!((target.as_str(), port, path.as_str()), ("me@files", Some(2222), "team/hosts.json"));
+    }
+
+    #[test]
+    fn lists_commands_this_machine_does_not_have() {
+        let mut local = Config::default();
+        local.groups[1].hosts.push(Host::new("web", "10.0.0.1"));
+        let mut remote = local.clone();
+        let host = &mut remote.groups[1].hosts[0];
+        host.probes.push(ProbeConfig::Command { name: None, command: "touch /tmp/owned".to_string() });
+        host.ssh_options = vec!["-o".to_string(), "ProxyCommand=sh -c id".to_string()];
+        remote.groups[1].hosts.push(Host { kind: ConnectionKind::Custom("nc evil 1".to_string()), ..Host::new("box", "box") });
+
+        let lines = shared(&remote).incoming_commands(&local);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("ProxyCommand=sh -c id"));
+        assert!(lines[1].contains("touch /tmp/owned"));
+        assert!(lines[2].contains("nc evil 1"));
+
+        // Settings this machine already has are not asked about again
+        assert!(shared(&remote).incoming_commands(&remote).is_empty());
+    }
+}
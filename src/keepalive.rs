@@ -0,0 +1,31 @@
+use crate::AppState;
+use crate::config::{ConnectionKind, KeepAlive};
+use std::time::{Duration, Instant};
+
+impl AppState {
+    /// Note traffic in either direction on the live session
+    pub fn touch_session(&mut self) {
+        self.last_session_traffic = Instant::now();
+    }
+
+    /// Send the host's keep-alive payload once the session has been quiet
+    /// for its interval; without a payload ssh keeps the connection alive
+    pub async fn poll_keepalive(&mut self) {
+        if !self.ssh_client.is_connected() {
+            return;
+        }
+        // mosh keeps its own connection alive
+        let Some(keepalive) = self.ssh_client.profile.as_ref()
+            .filter(|profile| profile.host.kind != ConnectionKind::Mosh)
+            .and_then(|profile| profile.host.keepalive.clone())
+            .filter(KeepAlive::through_session) else {
+            return;
+        };
+        if self.last_session_traffic.elapsed() < Duration::from_secs(keepalive.interval_secs) {
+            return;
+        }
+
+        self.touch_session();
+        let _ = self.ssh_client.send_input(&keepalive.payload_bytes()).await;
+    }
+}
@@ -0,0 +1,92 @@
+use crate::AppState;
+use crate::ssh::expand_key_path;
+use russh::keys::{self, HashAlg, PrivateKey, PublicKey};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+/// What a private key file turned out to be when it was checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDetails {
+    /// Such as "ssh-ed25519"; None when the key is encrypted in a format
+    /// that hides it and there is no .pub file next to it
+    pub key_type: Option<String>,
+    /// SHA256 fingerprint as printed by `ssh-keygen -l`
+    pub fingerprint: Option<String>,
+    /// Needs a passphrase to be used
+    pub encrypted: bool,
+    /// Permission bits, when others than the owner may read or write it
+    pub loose_mode: Option<u32>,
+}
+
+impl KeyDetails {
+    /// Type and shortened fingerprint, for the Keys panel
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(key_type) = &self.key_type {
+            parts.push(key_type.trim_start_matches("ssh-").to_string());
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            parts.push(fingerprint.chars().take(19).collect());
+        }
+        if self.encrypted {
+            parts.push("🔒".to_string());
+        }
+        if let Some(mode) = self.loose_mode {
+            parts.push(format!("⚠ mode {:o}", mode));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Check that `path` is a private key ssh can use, returning what it is or
+/// why it cannot be used
+pub fn inspect(path: &str) -> Result<KeyDetails, String> {
+    let full_path = expand_key_path(path);
+    let metadata = fs::metadata(&full_path).map_err(|_| format!("No key file at {}", path))?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a file", path));
+    }
+    let contents = fs::read_to_string(&full_path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let (public, encrypted) = match keys::decode_secret_key(&contents, None) {
+        Ok(key) => (Some(key.public_key().clone()), false),
+        Err(keys::Error::KeyIsEncrypted) => {
+            // OpenSSH keys keep the public half readable
+            let public = PrivateKey::from_openssh(&contents).ok().map(|key| key.public_key().clone());
+            (public, true)
+        },
+        Err(_) if contents.contains("ENCRYPTED") => (None, true),
+        Err(_) if PublicKey::from_openssh(contents.trim()).is_ok() => {
+            return Err(format!("{} is a public key; pick the private key next to it", path));
+        },
+        Err(_) => return Err(format!("{} is not a private key", path)),
+    };
+    let public = public.or_else(|| {
+        let sidecar = fs::read_to_string(format!("{}.pub", full_path)).ok()?;
+        PublicKey::from_openssh(sidecar.trim()).ok()
+    });
+
+    let mode = metadata.permissions().mode() & 0o777;
+    Ok(KeyDetails {
+        key_type: public.as_ref().map(|key| key.algorithm().as_str().to_string()),
+        fingerprint: public.as_ref().map(|key| key.fingerprint(HashAlg::Sha256).to_string()),
+        encrypted,
+        loose_mode: (mode & 0o077 != 0).then_some(mode),
+    })
+}
+
+impl AppState {
+    /// The checked details of a configured key, looked up once per path
+    pub fn key_details(&self, path: &str) -> Option<KeyDetails> {
+        self.key_details
+            .borrow_mut()
+            .entry(path.to_string())
+            .or_insert_with(|| inspect(path).ok())
+            .clone()
+    }
+
+    /// Check the key at `path` again the next time it is shown
+    pub fn forget_key_details(&mut self, path: &str) {
+        self.key_details.get_mut().remove(path);
+    }
+}
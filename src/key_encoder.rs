@@ -0,0 +1,176 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+
+/// Bytes an xterm-compatible terminal sends for a key press, or None for
+/// keys that have no encoding. `application_cursor` is DECCKM, under which
+/// unmodified arrows, Home and End are sent as SS3 sequences.
+pub fn encode(code: KeyCode, modifiers: KeyModifiers, application_cursor: bool) -> Option<Vec<u8>> {
+    let alt = modifiers.contains(KeyModifiers::ALT);
+    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+
+    let bytes = match code {
+        KeyCode::Char(c) => return Some(encode_char(c, ctrl, alt)),
+        KeyCode::Enter => with_alt(b"\r", alt),
+        KeyCode::Tab => with_alt(b"\t", alt),
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace if ctrl => with_alt(b"\x08", alt),
+        KeyCode::Backspace => with_alt(b"\x7f", alt),
+        KeyCode::Esc => with_alt(b"\x1b", alt),
+        KeyCode::Up => cursor_key(b'A', modifiers, application_cursor),
+        KeyCode::Down => cursor_key(b'B', modifiers, application_cursor),
+        KeyCode::Right => cursor_key(b'C', modifiers, application_cursor),
+        KeyCode::Left => cursor_key(b'D', modifiers, application_cursor),
+        KeyCode::Home => cursor_key(b'H', modifiers, application_cursor),
+        KeyCode::End => cursor_key(b'F', modifiers, application_cursor),
+        KeyCode::Insert => tilde_key(2, modifiers),
+        KeyCode::Delete => tilde_key(3, modifiers),
+        KeyCode::PageUp => tilde_key(5, modifiers),
+        KeyCode::PageDown => tilde_key(6, modifiers),
+        KeyCode::F(n @ 1..=4) => {
+            let letter = b"PQRS"[usize::from(n - 1)];
+            match modifier_param(modifiers) {
+                Some(param) => format!("\x1b[1;{}{}", param, letter as char).into_bytes(),
+                None => vec![0x1b, b'O', letter],
+            }
+        },
+        KeyCode::F(n @ 5..=12) => {
+            // The numbering skips 16 and 22, as on a VT220
+            const CODES: [u8; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+            tilde_key(CODES[usize::from(n - 5)], modifiers)
+        },
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+/// A typed character, turned into its control code with Ctrl and prefixed
+/// with ESC with Alt
+fn encode_char(c: char, ctrl: bool, alt: bool) -> Vec<u8> {
+    let control = if ctrl { control_code(c) } else { None };
+    let mut bytes = Vec::new();
+    if alt {
+        bytes.push(0x1b);
+    }
+    match control {
+        Some(byte) => bytes.push(byte),
+        None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+    }
+    bytes
+}
+
+/// Control code for Ctrl+`c`, following xterm for the non-letter keys
+fn control_code(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' | 'A'..='Z' => Some(c.to_ascii_lowercase() as u8 - b'a' + 1),
+        ' ' | '@' | '2' => Some(0x00),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '/' | '7' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
+fn with_alt(bytes: &[u8], alt: bool) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len() + 1);
+    if alt {
+        encoded.push(0x1b);
+    }
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// xterm modifier parameter (1 + Shift + 2*Alt + 4*Ctrl), None without
+/// modifiers
+fn modifier_param(modifiers: KeyModifiers) -> Option<u8> {
+    let mut param = 1;
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        param += 1;
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        param += 2;
+    }
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        param += 4;
+    }
+    (param > 1).then_some(param)
+}
+
+/// Arrows, Home and End: `CSI 1;m X` with modifiers, otherwise `SS3 X` in
+/// application cursor mode and `CSI X` in normal mode
+fn cursor_key(letter: u8, modifiers: KeyModifiers, application_cursor: bool) -> Vec<u8> {
+    match modifier_param(modifiers) {
+        Some(param) => format!("\x1b[1;{}{}", param, letter as char).into_bytes(),
+        None if application_cursor => vec![0x1b, b'O', letter],
+        None => vec![0x1b, b'[', letter],
+    }
+}
+
+/// Editing and function keys of the `CSI n ~` form
+fn tilde_key(number: u8, modifiers: KeyModifiers) -> Vec<u8> {
+    match modifier_param(modifiers) {
+        Some(param) => format!("\x1b[{};{}~", number, param).into_bytes(),
+        None => format!("\x1b[{}~", number).into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Vec<u8> {
+        encode(code, modifiers, false).unwrap()
+    }
+
+    #[test]
+    fn encodes_characters_with_ctrl_and_alt() {
+        assert_eq!(key(KeyCode::Char('a'), KeyModifiers::NONE), b"a");
+        assert_eq!(key(KeyCode::Char('é'), KeyModifiers::NONE), "é".as_bytes());
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL), [0x03]);
+        assert_eq!(key(KeyCode::Char('C'), KeyModifiers::CONTROL), [0x03]);
+        assert_eq!(key(KeyCode::Char(' '), KeyModifiers::CONTROL), [0x00]);
+        assert_eq!(key(KeyCode::Char('['), KeyModifiers::CONTROL), [0x1b]);
+        assert_eq!(key(KeyCode::Char('x'), KeyModifiers::ALT), b"\x1bx");
+        assert_eq!(key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT), [0x1b, 0x18]);
+        // Ctrl with a key that has no control code sends the key itself
+        assert_eq!(key(KeyCode::Char('1'), KeyModifiers::CONTROL), b"1");
+    }
+
+    #[test]
+    fn encodes_editing_keys() {
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), b"\r");
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::ALT), b"\x1b\r");
+        assert_eq!(key(KeyCode::Backspace, KeyModifiers::NONE), [0x7f]);
+        assert_eq!(key(KeyCode::Backspace, KeyModifiers::CONTROL), [0x08]);
+        assert_eq!(key(KeyCode::BackTab, KeyModifiers::SHIFT), b"\x1b[Z");
+        assert_eq!(key(KeyCode::Delete, KeyModifiers::NONE), b"\x1b[3~");
+        assert_eq!(key(KeyCode::PageUp, KeyModifiers::CONTROL), b"\x1b[5;5~");
+    }
+
+    #[test]
+    fn encodes_cursor_keys_by_mode() {
+        assert_eq!(key(KeyCode::Up, KeyModifiers::NONE), b"\x1b[A");
+        assert_eq!(encode(KeyCode::Up, KeyModifiers::NONE, true).unwrap(), b"\x1bOA");
+        assert_eq!(encode(KeyCode::Home, KeyModifiers::NONE, true).unwrap(), b"\x1bOH");
+        // Modifiers win over application cursor mode
+        assert_eq!(encode(KeyCode::Left, KeyModifiers::CONTROL, true).unwrap(), b"\x1b[1;5D");
+        assert_eq!(key(KeyCode::Right, KeyModifiers::SHIFT | KeyModifiers::ALT), b"\x1b[1;4C");
+    }
+
+    #[test]
+    fn encodes_function_keys() {
+        assert_eq!(key(KeyCode::F(1), KeyModifiers::NONE), b"\x1bOP");
+        assert_eq!(key(KeyCode::F(4), KeyModifiers::SHIFT), b"\x1b[1;2S");
+        assert_eq!(key(KeyCode::F(5), KeyModifiers::NONE), b"\x1b[15~");
+        assert_eq!(key(KeyCode::F(6), KeyModifiers::NONE), b"\x1b[17~");
+        assert_eq!(key(KeyCode::F(12), KeyModifiers::CONTROL), b"\x1b[24;5~");
+        assert_eq!(encode(KeyCode::F(13), KeyModifiers::NONE, false), None);
+    }
+
+    #[test]
+    fn skips_keys_without_an_encoding() {
+        assert_eq!(encode(KeyCode::CapsLock, KeyModifiers::NONE, false), None);
+        assert_eq!(encode(KeyCode::Null, KeyModifiers::NONE, false), None);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{AppState, KeyGenForm, MessageType, ModalState};
+use crate::line_editor::LineEditor;
+use crate::config::SshKey;
+use crate::ssh::expand_key_path;
+use std::fs;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Size of generated RSA keys
+const RSA_BITS: &str = "4096";
+
+/// Holds the passphrase for sshtui run as ssh-keygen's askpass program.
+/// Unlike the command line, a process's environment can only be read by
+/// its own user.
+const ASKPASS_PASSPHRASE: &str = "SSHTUI_ASKPASS_PASSPHRASE";
+
+/// The passphrase to print when sshtui was started by ssh-keygen as its
+/// askpass program
+pub fn askpass_answer() -> Option<String> {
+    std::env::var(ASKPASS_PASSPHRASE).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Ed25519,
+    Rsa,
+}
+
+impl KeyType {
+    /// Name `ssh-keygen -t` takes
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "ed25519",
+            KeyType::Rsa => "rsa",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            KeyType::Ed25519 => KeyType::Rsa,
+            KeyType::Rsa => KeyType::Ed25519,
+        }
+    }
+}
+
+/// Where a new key of `key_type` goes by default: the usual OpenSSH file
+/// name, numbered when that one is taken
+pub fn suggested_path(key_type: KeyType) -> String {
+    let base = format!("~/.ssh/id_{}", key_type.name());
+    (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{}_{}", base, n) })
+        .find(|path| !Path::new(&expand_key_path(path)).exists())
+        .unwrap_or(base)
+}
+
+/// A key being generated in the background
+pub struct KeyGeneration {
+    key: SshKey,
+    receiver: oneshot::Receiver<Result<(), String>>,
+}
+
+/// Run ssh-keygen without a terminal. With stdin not a terminal it asks its
+/// askpass program for the passphrase (twice), which is sshtui itself, so
+/// the passphrase never shows in the process list as `-N` would.
+async fn generate(key_type: KeyType, path: String, comment: String, passphrase: String) -> Result<(), String> {
+    let mut command = Command::new("ssh-keygen");
+    command.args(["-q", "-t", key_type.name(), "-f", &path]);
+    if passphrase.is_empty() {
+        command.args(["-N", ""]);
+    } else {
+        let askpass = std::env::current_exe().map_err(|e| format!("Could not find the sshtui program: {}", e))?;
+        command.env("SSH_ASKPASS", askpass)
+            .env("SSH_ASKPASS_REQUIRE", "force")
+            .env(ASKPASS_PASSPHRASE, &passphrase);
+    }
+    if key_type == KeyType::Rsa {
+        command.args(["-b", RSA_BITS]);
+    }
+    // Without -C, ssh-keygen uses user@hostname
+    if !comment.is_empty() {
+        command.args(["-C", &comment]);
+    }
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| format!("Could not run ssh-keygen: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(stderr.lines().last().map_or_else(|| output.status.to_string(), |line| line.trim().to_string()))
+}
+
+impl AppState {
+    pub fn open_key_generation(&mut self) {
+        if self.key_generation.is_some() {
+            self.set_message("A key is already being generated".to_string(), MessageType::Info);
+            return;
+        }
+        self.modal_state = ModalState::GenerateKey(KeyGenForm {
+            key_type: KeyType::Ed25519,
+            path: suggested_path(KeyType::Ed25519),
+            comment: String::new(),
+            passphrase: String::new(),
+            field_focus: 0,
+            editor: LineEditor::default(),
+        });
+    }
+
+    /// Start ssh-keygen for the form; the key is added to the config once
+    /// it has written both files
+    pub fn start_key_generation(&mut self, form: KeyGenForm) {
+        let path = form.path.trim().to_string();
+        if path.is_empty() {
+            self.set_message("Key path cannot be empty".to_string(), MessageType::Error);
+            return;
+        }
+        let file = expand_key_path(&path);
+        if Path::new(&file).exists() {
+            self.set_message(format!("{} already exists; pick another path", path), MessageType::Error);
+            return;
+        }
+        // ssh-keygen does not create the directory, and ~/.ssh must stay private
+        if let Some(dir) = Path::new(&file).parent().filter(|dir| !dir.as_os_str().is_empty() && !dir.exists()) {
+            if let Err(e) = fs::DirBuilder::new().recursive(true).mode(0o700).create(dir) {
+                self.set_message(format!("Could not create {}: {}", dir.display(), e), MessageType::Error);
+                return;
+            }
+        }
+
+        let name = Path::new(&file).file_name().map_or_else(|| path.clone(), |name| name.to_string_lossy().to_string());
+        let key = SshKey { id: Uuid::new_v4(), name, path, is_default: self.config.keys.is_empty() };
+        let (sender, receiver) = oneshot::channel();
+        let (key_type, comment, passphrase) = (form.key_type, form.comment.trim().to_string(), form.passphrase);
+        tokio::spawn(async move {
+            let _ = sender.send(generate(key_type, file, comment, passphrase).await);
+        });
+        self.set_message(format!("Generating {} key {}...", key_type.name(), key.path), MessageType::Info);
+        self.key_generation = Some(KeyGeneration { key, receiver });
+        self.modal_state = ModalState::None;
+    }
+
+    /// Add the generated key to the Keys panel once ssh-keygen is done
+    pub fn poll_key_generation(&mut self) {
+        let Some(generation) = &mut self.key_generation else {
+            return;
+        };
+        let Ok(result) = generation.receiver.try_recv() else {
+            return;
+        };
+        let Some(KeyGeneration { key, .. }) = self.key_generation.take() else {
+            return;
+        };
+
+        match result {
+            Ok(()) => {
+                let message = format!("Generated {} and added it to the keys", key.path);
+                let before = self.snapshot();
+                let description = format!("Added SSH key '{}'", key.name);
+                self.config.add_key(key);
+                self.record_change(before, description);
+                self.selected_key = self.config.keys.len() - 1;
+                let _ = self.config.save();
+                self.set_message(message, MessageType::Success);
+            },
+            Err(e) => self.set_message(format!("Key generation failed: {}", e), MessageType::Error),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! The inventory and session engine behind the sshtui terminal UI, for tools
+//! that want to reuse them without the interface, such as a GUI frontend or
+//! automation scripts.
+//!
+//! - [`config`] reads and writes the host inventory (`~/.config/sshtui`),
+//!   including encrypted configs, group defaults and per-host settings. An
+//!   encrypted config is opened with [`config::Config::load_with_passphrase`],
+//!   or with a prompt given to [`config_crypto::set_passphrase_prompt`].
+//! - [`ssh`] runs a session: [`ssh::ConnectionProfile`] turns a host into
+//!   the command line to launch, [`ssh::SshClient`] starts it in a PTY (or
+//!   over the embedded backend) and reports output as [`ssh::SshEvent`]s.
+//! - [`terminal_panel`] is the VT100/xterm emulator sessions are drawn with,
+//!   and [`key_encoder`] turns key presses into the bytes a terminal sends.
+//! - [`hostkeys`] and [`multiplex`] verify host keys and share connections
+//!   the way sessions started from the UI do, [`trust_report`] describes a
+//!   server met for the first time, and [`session_wrapper`] keeps sessions
+//!   running in tmux or abduco after the app quits.
+//!
+//! Errors from the config and session modules are [`error::Error`], whose
+//! [`kind`](error::Error::kind) tells whether retrying can help.
+//!
+//! ```no_run
+//! use sshtui::config::Config;
+//! use sshtui::ssh::{self, ConnectionProfile, SshClient, SshEvent};
+//!
+//! # async fn run() -> sshtui::error::Result<()> {
+//! let config = Config::load()?;
+//! let Some(host) = config.get_hosts_for_group(0).first().map(|host| config.effective_host(host)) else {
+//!     return Ok(());
+//! };
+//! let key_path = host.key_path.clone();
+//! let profile = ConnectionProfile::new(host, key_path.as_deref(), config.settings.ssh_backend, &[]);
+//!
+//! let (sender, mut events) = ssh::event_channel();
+//! let mut client = SshClient::new();
+//! client.connect(profile, None, sender, 80, 24).await?;
+//! while let Some(event) = events.recv().await {
+//!     if let SshEvent::Data(bytes) = &event {
+//!         print!("{}", String::from_utf8_lossy(bytes));
+//!     }
+//!     client.handle_event(event);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod config;
+pub mod config_crypto;
+pub mod error;
+pub mod hostkeys;
+pub mod key_encoder;
+pub mod mouse;
+pub mod multiplex;
+pub mod session_wrapper;
+pub mod ssh;
+pub mod ssh_embedded;
+pub mod terminal_panel;
+pub mod trust_report;
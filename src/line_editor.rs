@@ -0,0 +1,137 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::Paragraph,
+};
+
+/// Cursor editing for the one-line text inputs of forms. The text stays in
+/// the form's own field; the editor only knows where the cursor is in it,
+/// and forms keep one for whichever of their fields is focused.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineEditor {
+    /// Characters before the cursor; None keeps it at the end of the text
+    cursor: Option<usize>,
+    /// Choices offered by Tab completion, shown in a dropdown under the field
+    completion: Option<Completion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// Whole texts to put in the field
+    pub choices: Vec<String>,
+    pub selected: usize,
+}
+
+impl LineEditor {
+    /// Back to the end of the text with no dropdown, for a field that was
+    /// just focused or filled in
+    pub fn reset(&mut self) {
+        self.cursor = None;
+        self.completion = None;
+    }
+
+    /// Show `choices` in a dropdown under the field
+    pub fn offer(&mut self, choices: Vec<String>) {
+        self.completion = Some(Completion { choices, selected: 0 });
+    }
+
+    pub fn completion(&self) -> Option<&Completion> {
+        self.completion.as_ref()
+    }
+
+    pub fn completion_mut(&mut self) -> Option<&mut Completion> {
+        self.completion.as_mut()
+    }
+
+    pub fn close_completion(&mut self) {
+        self.completion = None;
+    }
+
+    /// Characters before the cursor in `text`
+    pub fn cursor(&self, text: &str) -> usize {
+        let len = text.chars().count();
+        self.cursor.map_or(len, |cursor| cursor.min(len))
+    }
+
+    fn move_to(&mut self, position: usize, text: &str) {
+        self.cursor = if position >= text.chars().count() { None } else { Some(position) };
+    }
+
+    pub fn insert(&mut self, text: &mut String, c: char) {
+        let at = self.cursor(text);
+        text.insert(byte_index(text, at), c);
+        self.move_to(at + 1, text);
+    }
+
+    /// Delete the character before the cursor
+    pub fn backspace(&mut self, text: &mut String) {
+        let at = self.cursor(text);
+        if at == 0 {
+            return;
+        }
+        text.remove(byte_index(text, at - 1));
+        self.move_to(at - 1, text);
+    }
+
+    /// Move or delete for an editing key; returns false for keys that are
+    /// not editing keys, which the form then handles as before
+    pub fn handle_key(&mut self, key: KeyCode, modifiers: KeyModifiers, text: &mut String) -> bool {
+        let at = self.cursor(text);
+        match (key, modifiers) {
+            (KeyCode::Left, KeyModifiers::NONE) => self.move_to(at.saturating_sub(1), text),
+            (KeyCode::Right, KeyModifiers::NONE) => self.move_to(at + 1, text),
+            (KeyCode::Home, _) => self.move_to(0, text),
+            (KeyCode::End, _) => self.cursor = None,
+            (KeyCode::Delete, _) => {
+                if at < text.chars().count() {
+                    text.remove(byte_index(text, at));
+                }
+                self.move_to(at, text);
+            },
+            // Ctrl+U: Delete everything before the cursor
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                text.replace_range(..byte_index(text, at), "");
+                self.move_to(0, text);
+            },
+            // Ctrl+W: Delete the word before the cursor
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => {
+                let before: Vec<char> = text.chars().take(at).collect();
+                let mut start = at;
+                while start > 0 && before[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !before[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                text.replace_range(byte_index(text, start)..byte_index(text, at), "");
+                self.move_to(start, text);
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draw `text` as a one-line input. When `focused` it is scrolled to
+    /// keep the cursor in view and the terminal cursor is shown there.
+    pub fn render(&self, frame: &mut Frame, text: &str, area: Rect, style: Style, focused: bool) {
+        let scroll = if focused { self.show_cursor(frame, text, area) } else { 0 };
+        frame.render_widget(Paragraph::new(text).style(style).scroll((0, scroll)), area);
+    }
+
+    /// Show the terminal cursor in an input drawn at `area`, returning how
+    /// far the text has to be scrolled for it to be in view
+    pub fn show_cursor(&self, frame: &mut Frame, text: &str, area: Rect) -> u16 {
+        if area.width == 0 || area.height == 0 {
+            return 0;
+        }
+        let cursor = self.cursor(text) as u16;
+        let scroll = cursor.saturating_sub(area.width - 1);
+        frame.set_cursor(area.x + cursor - scroll, area.y);
+        scroll
+    }
+}
+
+/// Where the character `chars` characters into `text` starts
+fn byte_index(text: &str, chars: usize) -> usize {
+    text.char_indices().nth(chars).map_or(text.len(), |(index, _)| index)
+}
@@ -0,0 +1,27 @@
+use crate::{AppState, LoginPromptForm, MessageType, ModalState};
+use crate::line_editor::LineEditor;
+
+impl AppState {
+    /// Ask for the second factor ssh is waiting on. The terminal panel only
+    /// shows once the session is up, so the prompt would go unseen there.
+    pub fn prompt_login_code(&mut self, prompt: String) {
+        // The stand-in config must not be used without a choice
+        if self.recovering_config() {
+            return;
+        }
+        self.modal_state = ModalState::LoginPrompt(LoginPromptForm { prompt, answer: String::new(), editor: LineEditor::default() });
+    }
+
+    /// Type the answer into the session; it is never kept or recorded
+    pub fn answer_login_prompt(&mut self, form: LoginPromptForm) {
+        self.modal_state = ModalState::None;
+        if !self.ssh_client.is_connected() && !self.ssh_client.is_connecting() {
+            self.set_message("The connection closed before the code was sent".to_string(), MessageType::Error);
+            return;
+        }
+        match self.ssh_client.answer_prompt(&form.answer) {
+            Ok(()) => self.set_message("Code sent".to_string(), MessageType::Info),
+            Err(e) => self.set_message(format!("Could not send the code: {}", e), MessageType::Error),
+        }
+    }
+}
@@ -1,24 +1,120 @@
-mod config;
-mod ssh;
-mod terminal_panel;
+mod accessibility;
+mod allowlist;
+mod ansible;
+mod authorized_keys;
+mod bell;
+mod autoconnect;
+mod broadcast;
+mod cli;
+mod config_diff;
+mod config_recovery;
+mod config_editor;
 mod ui;
 mod dashboard;
+mod ec2;
+mod facts;
+mod favorites;
+mod forwarding;
+mod git_sync;
+mod group_connect;
+mod group_tree;
+mod history;
+mod host_key_prompt;
+mod host_filter;
+mod host_headers;
+mod host_transfer;
+mod identities;
+mod idle_lock;
+mod import;
+mod inventory_sync;
+mod input_recovery;
+mod keepalive;
+mod key_check;
+mod keygen;
+mod line_editor;
+mod login_prompt;
 mod modal;
+mod mosh;
+mod notify;
+mod orphans;
+mod palette;
+mod passphrase;
+mod path_completion;
+mod profiles;
+mod putty;
+mod ordering;
+mod probe;
+mod reconnect;
+mod recording;
+mod secrets;
+mod split;
+mod sidebar_focus;
+mod status_bar;
+mod sweep;
+mod scp;
+mod securecrt;
+mod sessions;
+mod startup;
+mod termius;
+mod theme;
+mod theme_picker;
+mod trash;
+mod undo;
 
+use sshtui::{config, config_crypto, error, hostkeys, key_encoder, multiplex, session_wrapper, ssh, terminal_panel, trust_report};
 use anyhow::Result;
-use config::{Config, Host};
+use clap::Parser;
+use authorized_keys::PushResult;
+use broadcast::{BroadcastHistory, BroadcastRun, TargetSet};
+use config_diff::FieldChange;
+use config_recovery::RecoveryChoice;
+use dashboard::CommandOutput;
+use facts::{FactsIndex, FactsSync};
+use error::ErrorKind;
+use config::{AutoConnectRule, BellMode, Config, ConnectionKind, GroupDefaults, Host, HostSort, KeepAlive, PortForward, ProbeConfig, SecretsBackend, SessionWrapper, SshBackend, TerminalSize};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyModifiers, MouseEvent, MouseEventKind},
+    event::{self, Event, KeyCode, KeyModifiers, KeyboardEnhancementFlags, MouseEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{prelude::*, widgets::*};
-use ssh::{SshClient, SshEvent};
+use ratatui::prelude::*;
+use forwarding::{ConflictChoice, Tunnel};
+use git_sync::GitSync;
+use group_connect::GroupConnect;
+use accessibility::KeyFilter;
+use group_tree::VirtualGroup;
+use history::History;
+use input_recovery::UnsentInput;
+use hostkeys::HostKeyPrompt;
+use identities::Identity;
+use ec2::Ec2Refresh;
+use idle_lock::SessionLock;
+use import::{ImportFormat, ImportPlan};
+use inventory_sync::{InventorySync, SyncMarks};
+use key_check::KeyDetails;
+use keygen::{KeyGeneration, KeyType};
+use line_editor::LineEditor;
+use modal::ModalGeometry;
+use orphans::OrphanScan;
+use palette::PaletteAction;
+use status_bar::LatencyCheck;
+use sweep::Sweep;
+use probe::ProbeReport;
+use reconnect::ReconnectState;
+use recording::{Playback, Recorder};
+use session_wrapper::WrappedSession;
+use sessions::BackgroundSession;
+use split::{Split, SplitDirection};
+use startup::{Inventory, Startup};
+use ssh::{ConnectionProfile, SshClient, SshEvent};
+use undo::UndoHistory;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::time::{Duration, Instant};
 use terminal_panel::RawTerminalPanel;
-use tokio::sync::mpsc;
-use log::{debug, error, info, warn};
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FocusArea {
@@ -40,11 +136,41 @@ enum ModalState {
     None,
     AddKey(KeyEditForm),
     EditKey(usize, KeyEditForm),
+    GenerateKey(KeyGenForm),
     AddGroup(GroupEditForm),
     EditGroup(usize, GroupEditForm),
     AddHost(HostEditForm),
     EditHost(usize, HostEditForm),
-    Confirm(String, ConfirmAction),
+    TagFilter(TagFilterForm),
+    Settings(SettingsForm),
+    Forwards(ForwardsForm),
+    ForwardConflict(ForwardConflictForm), // A tunnel of the live session found its port taken
+    PastePreview(String), // Multi-line paste waiting for confirmation
+    RecoverInput(String), // Line left unsent when the last session to the host ended
+    Sessions(SessionsForm),
+    Orphans(OrphansForm), // Wrapped sessions left running by an earlier sshtui
+    Trash(TrashForm), // Deleted hosts and groups that can still be restored
+    Import(ImportForm), // Export of another tool to read hosts from
+    ImportPreview(ImportPreviewForm), // What an import adds, shown before it is merged
+    SharedInventory(SharedInventoryForm), // Commands a shared inventory brings, shown before it is taken
+    Confirm(ConfirmForm),
+    ReviewChanges(ReviewChangesForm), // Changes of a host or group edit, shown before they are saved
+    HostKey(HostKeyForm),
+    AuthorizedKeys(AuthorizedKeysForm),
+    Identities(IdentityForm),
+    Commands(CommandPickerForm),
+    Broadcast(BroadcastForm),
+    Recordings(RecordingsForm),
+    Themes(ThemeForm),
+    MoveHost(MoveHostForm),
+    Scp(ScpForm),
+    ConfigEditor(ConfigEditorForm),
+    Facts(FactsForm),
+    Palette(PaletteForm), // Every app action, searchable
+    ConfigRecovery(ConfigRecoveryForm), // config.json does not parse; nothing is saved until a choice is made
+    Profiles(ProfilesForm), // Switch to another set of keys, groups and hosts, or create one
+    LoginPrompt(LoginPromptForm), // ssh asks for a verification code or other second factor
+    GroupConnect, // Progress of the running group connect
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -53,13 +179,69 @@ struct KeyEditForm {
     path: String,
     is_default: bool,
     field_focus: usize, // 0=name, 1=path, 2=is_default
+    editor: LineEditor, // Cursor in the focused text field
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyGenForm {
+    key_type: KeyType,
+    path: String, // Private key file; the public key goes next to it as .pub
+    comment: String, // Empty for ssh-keygen's user@hostname
+    passphrase: String, // Empty for an unencrypted key
+    field_focus: usize, // 0=key_type, 1=path, 2=comment, 3=passphrase
+    editor: LineEditor, // Cursor in the focused text field
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct GroupEditForm {
     name: String,
     color: String,
-    field_focus: usize, // 0=name, 1=color
+    user: String, // Defaults inherited by the group's hosts; empty for none
+    port: String,
+    key_path: String,
+    options: String, // ssh -o options separated by ';'
+    notes: String, // May span several lines
+    field_focus: usize, // 0=name, 1=color, 2=user, 3=port, 4=key_path, 5=options, 6=notes
+    editor: LineEditor, // Cursor in the focused text field
+}
+
+impl KeyGenForm {
+    /// Switch between ed25519 and RSA, moving a suggested path along
+    fn toggle_key_type(&mut self) {
+        if self.path == keygen::suggested_path(self.key_type) {
+            self.path = keygen::suggested_path(self.key_type.toggled());
+        }
+        self.key_type = self.key_type.toggled();
+    }
+}
+
+impl GroupEditForm {
+    fn defaults(&self) -> GroupDefaults {
+        let text = |value: &str| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        GroupDefaults {
+            user: text(&self.user),
+            port: self.port.trim().parse().ok(),
+            key_path: text(&self.key_path),
+            options: self.options.split(';')
+                .map(|option| option.trim().to_string())
+                .filter(|option| !option.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Pick the next (or previous) color of the palette; a color set in
+    /// the config file that is not on it moves to the palette's first
+    fn cycle_color(&mut self, forward: bool) {
+        let colors = &theme::GROUP_COLORS;
+        let current = colors.iter().position(|color| color.eq_ignore_ascii_case(self.color.trim()));
+        let next = match (current, forward) {
+            (Some(index), true) => (index + 1) % colors.len(),
+            (Some(index), false) => (index + colors.len() - 1) % colors.len(),
+            (None, true) => 0,
+            (None, false) => colors.len() - 1,
+        };
+        self.color = colors[next].to_string();
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -71,30 +253,415 @@ struct HostEditForm {
     key_path: String,
     use_key_selector: bool, // If true, show key selector instead of path input
     selected_key_index: usize, // Index of selected key from config.keys
-    field_focus: usize, // 0=name, 1=host, 2=port, 3=user, 4=key_selector_or_path
+    password: String, // Kept in the secrets backend, never in the config file
+    autoconnect: bool, // Open a session when the host becomes reachable again
+    autoconnect_command: String, // Command run after auto-connecting
+    tags: String, // Comma separated
+    backend: Option<SshBackend>, // None follows the global setting
+    forwards: Vec<PortForward>, // Edited with Ctrl+F, carried through unchanged
+    keepalive_interval: String, // Seconds, empty disables the keep-alive
+    keepalive_payload: String,
+    group_defaults: GroupDefaults, // Shown for fields left empty to inherit them
+    allowed_commands: String, // One command per line, empty allows free typing
+    bell: Option<BellMode>, // None uses the global setting
+    remote_command: String, // Empty starts the login shell
+    startup_commands: String, // One command per line
+    probes: Vec<ProbeConfig>, // Defined in the config file, carried through unchanged
+    depends_on: String, // Comma separated host names
+    connection: String, // Empty for ssh, "mosh", "telnet", or a custom command
+    ssh_options: String, // Extra ssh arguments separated by spaces, quoted where they hold one
+    bandwidth_limit: String, // KB/s, empty for no limit
+    notes: String, // May span several lines
+    term: String, // Empty for xterm-256color
+    locale: String, // Empty leaves LANG and LC_ALL alone
+    terminal_size: TerminalSize,
+    field_focus: usize, // 0=name, 1=host, 2=port, 3=user, 4=key_selector_or_path, 5=password, 6=autoconnect, 7=autoconnect_command, 8=tags, 9=backend, 10=keepalive_interval, 11=keepalive_payload, 12=allowed_commands, 13=bell, 14=remote_command, 15=startup_commands, 16=depends_on, 17=connection, 18=ssh_options, 19=bandwidth_limit, 20=notes, 21=term, 22=locale, 23=terminal_size
+    editor: LineEditor, // Cursor in the focused text field
+}
+
+impl HostEditForm {
+    fn autoconnect_rule(&self) -> Option<AutoConnectRule> {
+        self.autoconnect.then(|| AutoConnectRule {
+            command: self.autoconnect_command.trim().to_string(),
+        })
+    }
+
+    fn keepalive(&self) -> Option<KeepAlive> {
+        let interval_secs = self.keepalive_interval.trim().parse::<u64>().ok().filter(|secs| *secs > 0)?;
+        Some(KeepAlive { interval_secs, payload: self.keepalive_payload.clone() })
+    }
+
+    fn command_list(&self) -> Vec<String> {
+        self.allowed_commands.lines()
+            .map(|command| command.trim().to_string())
+            .filter(|command| !command.is_empty())
+            .collect()
+    }
+
+    fn remote_command(&self) -> Option<String> {
+        Some(self.remote_command.trim().to_string()).filter(|command| !command.is_empty())
+    }
+
+    fn startup_command_list(&self) -> Vec<String> {
+        self.startup_commands.lines()
+            .map(|command| command.trim().to_string())
+            .filter(|command| !command.is_empty())
+            .collect()
+    }
+
+    fn connection_kind(&self) -> ConnectionKind {
+        let connection = self.connection.trim();
+        match connection.to_lowercase().as_str() {
+            "" | "ssh" => ConnectionKind::Ssh,
+            "mosh" => ConnectionKind::Mosh,
+            "telnet" => ConnectionKind::Telnet,
+            _ => ConnectionKind::Custom(connection.to_string()),
+        }
+    }
+
+    fn ssh_option_list(&self) -> Vec<String> {
+        config::split_arguments(&self.ssh_options)
+    }
+
+    fn bandwidth_limit(&self) -> Option<u32> {
+        self.bandwidth_limit.trim().parse().ok().filter(|limit| *limit > 0)
+    }
+
+    fn term(&self) -> Option<String> {
+        Some(self.term.trim().to_string()).filter(|term| !term.is_empty())
+    }
+
+    fn locale(&self) -> Option<String> {
+        Some(self.locale.trim().to_string()).filter(|locale| !locale.is_empty())
+    }
+
+    /// Ids of the hosts named as dependencies, preferring hosts of
+    /// `group_name`, or the first name that matches no host
+    fn dependency_list(&self, config: &Config, group_name: &str) -> std::result::Result<Vec<Uuid>, String> {
+        self.depends_on.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| config.host_id_by_name(group_name, name).ok_or_else(|| name.to_string()))
+            .collect()
+    }
+
+    fn tag_list(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.tags.split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.dedup();
+        tags
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TagFilterForm {
+    tags: Vec<(String, bool)>, // Every known tag and whether it is part of the filter
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SettingsForm {
+    secrets_backend: SecretsBackend,
+    desktop_notifications: bool,
+    webhook_url: String,
+    quiet_hours_start: String, // HH:MM, empty disables quiet hours
+    quiet_hours_end: String,
+    auto_reconnect: bool,
+    ssh_backend: SshBackend,
+    paste_protection: bool,
+    local_echo: bool,
+    connection_sharing: bool,
+    record_sessions: bool,
+    bell: BellMode,
+    host_sort: HostSort,
+    repeat_filter_ms: String, // Empty or 0 turns the filter off
+    sticky_prefix: String, // One character, empty for none
+    prompt_timeout_secs: String,
+    session_wrapper: SessionWrapper,
+    dashboard_widgets: String, // Comma-separated, empty for the default set
+    git_remote: String, // Empty when the config is not synced
+    inventory_sync_url: String, // Empty when no inventory is shared
+    inventory_sync_token: String, // HTTP bearer token, kept in the secrets backend
+    lock_after_mins: String, // 0 never locks
+    sidebar_key: String, // Such as "F12" or "Ctrl+B"
+    trash_days: String, // 0 keeps deleted items until emptied by hand
+    field_focus: usize, // 0=secrets_backend, 1=desktop_notifications, 2=webhook_url, 3=quiet_hours_start, 4=quiet_hours_end, 5=auto_reconnect, 6=ssh_backend, 7=paste_protection, 8=local_echo, 9=connection_sharing, 10=record_sessions, 11=bell, 12=host_sort, 13=repeat_filter_ms, 14=sticky_prefix, 15=prompt_timeout_secs, 16=session_wrapper, 17=dashboard_widgets, 18=git_remote, 19=inventory_sync_url, 20=inventory_sync_token, 21=lock_after_mins, 22=sidebar_key, 23=trash_days
+    editor: LineEditor, // Cursor in the focused text field
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ForwardsForm {
+    forwards: Vec<PortForward>,
+    new_forward: String, // ssh-style spec, e.g. "-L 8080:db:5432"
+    cursor: usize, // forwards.len() is the input row
+    live: bool, // Editing the running session rather than the host's config
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ForwardConflictForm {
+    forward: PortForward, // The tunnel that could not listen
+    error: String,
+    choices: Vec<ConflictChoice>,
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfigRecoveryForm {
+    reason: String, // Parse error of the config file
+    choices: Vec<RecoveryChoice>,
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SessionsForm {
+    cursor: usize, // Index into the background sessions
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OrphansForm {
+    sessions: Vec<WrappedSession>, // Newest first
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TrashForm {
+    cursor: usize, // Index into config.trash
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImportForm {
+    format: ImportFormat, // Tool the file comes from
+    path: String, // Export file, or directory of PuTTY sessions
+    field_focus: usize, // 0=format, 1=path
+    editor: LineEditor, // Cursor in the path
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImportPreviewForm {
+    format: ImportFormat, // Kept for Back, with the path
+    path: String, // File the plan was read from, kept for Back
+    lines: Vec<String>, // One per group or host added or skipped
+    scroll: usize, // First line shown
+    confirm_selected: bool, // Import is highlighted; starts on Import
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SharedInventoryForm {
+    heading: String, // Question above the list
+    lines: Vec<String>, // One per setting that runs a command
+    scroll: usize, // First line shown
+    confirm_selected: bool, // Take is highlighted; starts on Keep mine
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfirmForm {
+    message: String,
+    action: ConfirmAction,
+    confirm_selected: bool, // Yes is highlighted; starts on No
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ReviewChangesForm {
+    subject: String, // "host 'web' in group 'Prod'"
+    changes: Vec<FieldChange>,
+    edit: Box<ModalState>, // Edit form the changes come from, reopened on Back
+    confirm_selected: bool, // Save is highlighted; starts on Save
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AuthorizedKeysForm {
+    keys: Vec<(String, bool)>, // Every configured key and whether it is exported
+    hosts: Vec<(String, bool)>, // Every host and whether the snippet is pushed to it
+    cursor: usize, // Keys first, then hosts
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostKeyForm {
+    prompt: Box<HostKeyPrompt>,
+    trust_selected: bool, // Trust is highlighted; starts on Deny/Cancel
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IdentityForm {
+    host_name: String, // Host whose login was rejected
+    identities: Vec<Identity>, // Other keys and agent identities to retry with
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BroadcastForm {
+    command: String, // New command for the selected group
+    cursor: usize, // 0 is the command input, then past broadcasts newest first
+    targets: Option<TargetSet>, // Hosts to run on instead of the selected group
+    viewing: Option<usize>, // History entry whose results are shown
+    host: usize, // Host selected in the results matrix
+    scroll: u16, // Scroll offset of the selected host's output
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordingsForm {
+    recordings: Vec<String>, // File names in the recordings directory, newest first
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FactsForm {
+    query: String, // Words every listed host must match, such as "ubuntu 20.04"
+    cursor: usize, // Highlighted match
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+struct PaletteForm {
+    query: String, // Matched against the action names, letters in order at least
+    cursor: usize, // Highlighted match
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LoginPromptForm {
+    prompt: String, // As ssh printed it, such as "Verification code:"
+    answer: String, // Shown masked
+    editor: LineEditor, // Cursor in the answer
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProfilesForm {
+    profiles: Vec<String>, // The default first
+    cursor: usize, // One past the profiles for the new profile row
+    new_name: String, // Typed on the new profile row
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfigEditorForm {
+    lines: Vec<String>, // The config file being edited
+    row: usize, // Cursor line
+    col: usize, // Cursor position in characters
+    scroll: usize, // First line shown
+    status: Option<String>, // Why the last save failed
+    dirty: bool, // Edited since it was opened
+    discard_armed: bool, // Esc was pressed once with unsaved changes
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MoveHostForm {
+    host_id: Uuid,
+    host_name: String,
+    from_group: String,
+    groups: Vec<String>, // Real groups other than the host's own
+    cursor: usize,
+    copy: bool, // Add a renamed copy instead of moving the host
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScpForm {
+    upload: bool, // Copy to the host rather than from it
+    local: String,
+    remote: String, // Relative to the remote home directory
+    field_focus: usize, // 0=local, 1=remote
+    editor: LineEditor, // Cursor in the focused text field
+    master: Option<String>, // Control socket of the session's shared connection, which the copy rides on
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ThemeForm {
+    themes: Vec<String>, // Built-in themes, then custom ones
+    cursor: usize, // Theme being previewed
+    original: String, // Theme to go back to on Esc
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CommandPickerForm {
+    commands: Vec<String>, // The live host's allowlist
+    cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 enum ConfirmAction {
     DeleteKey(usize),
     DeleteGroup(usize),
     DeleteHost(usize),
+    TakeSharedInventory, // Replace the groups and hosts with the shared inventory
+    EmptyEc2Group, // Trash the EC2 hosts of a group whose lookup found no instances
 }
 
 struct AppState {
     config: Config,
+    startup: Option<Startup>, // Set while the config is still being read; `config` is a placeholder until then
     focus_area: FocusArea,
     focus_sub_area: FocusSubArea,
+    sidebar_focused: bool, // Keys go to the sidebar while a session stays live
     selected_key: usize,
+    key_details: RefCell<HashMap<String, Option<KeyDetails>>>, // Checked key files by path, None when unusable
     selected_group: usize,
+    virtual_group: Option<VirtualGroup>, // Favorites or Recent is selected in the Groups panel; `selected_group` is then 0
     selected_host: usize,
+    host_header: Option<usize>, // Group header the host cursor is on in the "All" view
+    collapsed_groups: HashSet<String>, // Groups folded in the "All" view
+    collapsed_folders: HashSet<String>, // Groups whose sub-groups are folded in the Groups panel
+    host_filter: String, // Substring or glob typed into the filter box above the hosts
+    host_filter_editing: bool,
     ssh_client: SshClient,
+    history: History,
+    unsent_input: UnsentInput, // Unfinished input lines by host, for re-sending after a drop
     terminal_panel: RawTerminalPanel,
-    ssh_event_receiver: Option<mpsc::UnboundedReceiver<SshEvent>>,
+    ssh_event_receiver: Option<mpsc::Receiver<SshEvent>>,
     message: String,
     message_type: MessageType,
     terminal_size: (u16, u16),
     modal_state: ModalState,
+    modal_geometry: RefCell<ModalGeometry>, // Where the open modal was last drawn, for mouse clicks
+    tag_filter: Vec<String>, // Hosts panel only shows hosts carrying all of these tags
+    reachability: HashMap<Uuid, bool>, // Last probe result per host id
+    last_reachability_check: Option<Instant>,
+    reachability_sender: mpsc::UnboundedSender<(Uuid, bool)>,
+    reachability_receiver: mpsc::UnboundedReceiver<(Uuid, bool)>,
+    pending_startup_command: Option<String>, // Sent once the next session is past its login
+    reconnect: Option<ReconnectState>, // Set while a dropped session is being re-established
+    tunnels: Vec<Tunnel>, // Port forwards of the live session
+    undo: UndoHistory, // Changes to groups, hosts and keys that can be undone
+    last_session_traffic: Instant, // Drives the per-host keep-alive
+    last_activity: Instant, // Last key, click or paste, for the idle lock
+    session_lock: Option<SessionLock>, // Set while sessions are hidden after going idle
+    latency_check: Option<LatencyCheck>, // Latency of the live session, measured now and then
+    background_sessions: Vec<BackgroundSession>, // Detached sessions, still running
+    split: Option<Split>, // Terminal area shared with a background session
+    orphan_scan: Option<OrphanScan>, // Looking for wrapped sessions an earlier sshtui left running
+    pending_reattach: Option<WrappedSession>, // Orphaned session picked, attached to on the next tick
+    approved_edit: Option<ModalState>, // Edit form whose changes were confirmed, saved without review
+    pending_action: Option<PaletteAction>, // Picked from the action palette, run on the next tick
+    git_sync: GitSync, // Commits, pulls and pushes of the config directory
+    sync_marks: SyncMarks, // When each profile's inventory was last synced
+    inventory_sync: InventorySync, // Shared inventory sync in progress
+    pending_import: Option<ImportPlan>, // Read from another tool's inventory, merged once the preview is confirmed
+    ec2_refresh: Option<Ec2Refresh>, // EC2 instances of a group being looked up
+    ec2_empty: Option<Uuid>, // Group whose lookup found no instances, until the user agrees to empty it
+    retry_pending: bool, // Retry the last connection on the next tick, e.g. after trusting a new host key
+    key_push: Option<oneshot::Receiver<Vec<PushResult>>>, // authorized_keys push in progress
+    key_generation: Option<KeyGeneration>, // ssh-keygen run started from the Keys panel
+    scp_transfers: Vec<scp::Transfer>, // Uploads and downloads running in the background
+    identity_retry: Option<Host>, // Connected on the next tick with the identity picked after a rejected login
+    identity_trial: Option<String>, // Host being tried with another identity; it is kept if the login works
+    broadcasts: BroadcastHistory, // Commands sent to whole groups and what each host returned
+    broadcast_run: Option<BroadcastRun>, // Broadcast in progress
+    facts: FactsIndex, // What each host reported about itself, for searches
+    facts_sync: Option<FactsSync>, // Facts collection in progress
+    recorder: Option<Recorder>, // asciicast recording of the live session
+    playback: Option<Playback>, // Recording being replayed in place of the terminal panel
+    last_bell_notification: Option<Instant>, // Throttles notifications for ringing sessions
+    probe_reports: HashMap<Uuid, Vec<Option<ProbeReport>>>, // Latest result of each host's probes, in config order
+    command_widgets: HashMap<String, CommandOutput>, // Output of the dashboard's command widgets, by name
+    last_probe_run: Option<Instant>,
+    probes_running: HashSet<(Uuid, usize)>, // Host id and index of the probes not finished yet
+    probes_deferred: bool, // Probes wait until the Hosts panel is first looked at
+    key_filter: KeyFilter,
+    probe_sender: mpsc::UnboundedSender<(Uuid, usize, ProbeReport)>,
+    probe_receiver: mpsc::UnboundedReceiver<(Uuid, usize, ProbeReport)>,
+    group_connect: Option<GroupConnect>, // Sessions being opened for a whole group, kept for the report when done
+    sweep: Option<Sweep>, // Last reachability check of a group, shown in the host list
+    quit_requested: bool, // Leave the main loop, as Ctrl+Q would
+    quit_note: Option<String>, // Printed once the terminal is restored
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -105,8 +672,8 @@ enum MessageType {
 }
 
 impl AppState {
-    fn new() -> Result<Self> {
-        let config = Config::load()?;
+    fn new(inventory: Inventory) -> Self {
+        let Inventory { config, history, broadcasts, facts, sync_marks, unsent_input, corrupt_config } = inventory;
         
         // Initialize terminal panel with default size
         let terminal_bounds = Rect {
@@ -117,22 +684,108 @@ impl AppState {
         };
         
         let terminal_panel = RawTerminalPanel::new(terminal_bounds);
+        let (reachability_sender, reachability_receiver) = mpsc::unbounded_channel();
+        let (probe_sender, probe_receiver) = mpsc::unbounded_channel();
         
-        Ok(Self {
+        let mut app = Self {
             config,
+            startup: None,
             focus_area: FocusArea::Keys,
             focus_sub_area: FocusSubArea::Items,
+            sidebar_focused: false,
             selected_key: 0,
+            key_details: RefCell::default(),
             selected_group: 0,
+            virtual_group: None,
             selected_host: 0,
+            host_header: None,
+            collapsed_groups: HashSet::new(),
+            collapsed_folders: HashSet::new(),
+            host_filter: String::new(),
+            host_filter_editing: false,
             ssh_client: SshClient::new(),
+            history,
+            unsent_input,
             terminal_panel,
             ssh_event_receiver: None,
             message: String::new(),
             message_type: MessageType::Info,
             terminal_size: (120, 40),
             modal_state: ModalState::None,
-        })
+            modal_geometry: RefCell::default(),
+            tag_filter: Vec::new(),
+            reachability: HashMap::new(),
+            last_reachability_check: None,
+            reachability_sender,
+            reachability_receiver,
+            pending_startup_command: None,
+            reconnect: None,
+            tunnels: Vec::new(),
+            undo: UndoHistory::default(),
+            last_session_traffic: Instant::now(),
+            last_activity: Instant::now(),
+            session_lock: None,
+            latency_check: None,
+            background_sessions: Vec::new(),
+            split: None,
+            orphan_scan: None,
+            pending_reattach: None,
+            approved_edit: None,
+            pending_action: None,
+            git_sync: GitSync::default(),
+            sync_marks,
+            inventory_sync: InventorySync::default(),
+            pending_import: None,
+            ec2_refresh: None,
+            ec2_empty: None,
+            retry_pending: false,
+            key_push: None,
+            key_generation: None,
+            scp_transfers: Vec::new(),
+            identity_retry: None,
+            identity_trial: None,
+            broadcasts,
+            broadcast_run: None,
+            facts,
+            facts_sync: None,
+            recorder: None,
+            playback: None,
+            last_bell_notification: None,
+            probe_reports: HashMap::new(),
+            command_widgets: HashMap::new(),
+            last_probe_run: None,
+            probes_running: HashSet::new(),
+            probes_deferred: true,
+            key_filter: KeyFilter::default(),
+            probe_sender,
+            probe_receiver,
+            group_connect: None,
+            sweep: None,
+            quit_requested: false,
+            quit_note: None,
+        };
+        if let Some(reason) = corrupt_config {
+            app.offer_config_recovery(reason);
+        }
+        app
+    }
+
+    /// Hosts of the selected group that pass the tag filter and the filter
+    /// box, in the configured order
+    fn visible_hosts(&self) -> Vec<Host> {
+        if self.grouped_host_view() {
+            return self.grouped_hosts();
+        }
+        let mut hosts = match self.virtual_group {
+            Some(view) => self.virtual_group_hosts(view),
+            None => self.config.get_hosts_for_group(self.selected_group),
+        };
+        hosts.retain(|host| self.host_passes_filters(host));
+        // Recent keeps the order they were last connected in
+        if self.virtual_group != Some(VirtualGroup::Recent) {
+            self.sort_hosts(&mut hosts);
+        }
+        hosts
     }
 
     fn set_message(&mut self, message: String, msg_type: MessageType) {
@@ -196,7 +849,7 @@ impl AppState {
                 FocusArea::Hosts => match self.focus_sub_area {
                     FocusSubArea::Items => self.focus_sub_area = FocusSubArea::AddButton,
                     FocusSubArea::AddButton => {
-                        let hosts = self.config.get_hosts_for_group(self.selected_group);
+                        let hosts = self.visible_hosts();
                         if !hosts.is_empty() {
                             self.focus_sub_area = FocusSubArea::EditButton;
                         } else {
@@ -205,7 +858,7 @@ impl AppState {
                         }
                     },
                     FocusSubArea::EditButton => {
-                        let hosts = self.config.get_hosts_for_group(self.selected_group);
+                        let hosts = self.visible_hosts();
                         if !hosts.is_empty() {
                             self.focus_sub_area = FocusSubArea::DeleteButton;
                         } else {
@@ -240,77 +893,253 @@ impl AppState {
 
     async fn connect_to_host(&mut self, host: Host) -> Result<()> {
         if self.ssh_client.is_connecting() || self.ssh_client.is_connected() {
+            self.pending_startup_command = None;
             return Ok(());
         }
 
+        // Fill in what the host inherits from its group
+        let options = self.config.group_of(&host)
+            .map(|group| group.defaults.options.clone())
+            .unwrap_or_default();
+        let host = self.config.effective_host(&host);
+
         // Find key path
         let key_path = if let Some(key_path) = &host.key_path {
-            key_path.clone()
-        } else if let Some(default_key) = self.config.get_default_key() {
-            default_key.path.clone()
+            Some(key_path.clone())
         } else {
-            self.set_message("No SSH key configured for this host".to_string(), MessageType::Error);
-            return Ok(());
+            self.config.get_default_key().map(|key| key.path.clone())
+        };
+
+        // Only ssh itself can use the built-in client
+        let backend = match host.kind {
+            ConnectionKind::Ssh => host.backend.unwrap_or(self.config.settings.ssh_backend),
+            _ => SshBackend::System,
         };
+        let profile = ConnectionProfile::new(host, key_path.as_deref(), backend, &options);
+        self.connect_with_profile(profile).await
+    }
+
+    /// Secrets account of a host; passwords are stored under the host entry
+    /// as configured, before group defaults are filled in
+    fn password_account(&self, host: &Host) -> String {
+        let configured = self.config.get_hosts_for_group(0).into_iter().find(|h| h.id == host.id);
+        secrets::host_account(configured.as_ref().unwrap_or(host))
+    }
+
+    async fn connect_with_profile(&mut self, profile: ConnectionProfile) -> Result<()> {
+        let started = self.start_connection(profile).await;
+        // The command a connection was opened for, such as changing into
+        // a directory, is never typed into a later one
+        if !matches!(started, Ok(true)) {
+            self.pending_startup_command = None;
+        }
+        started.map(|_| ())
+    }
+
+    /// Whether a connection was started
+    async fn start_connection(&mut self, profile: ConnectionProfile) -> Result<bool> {
+        if self.ssh_client.is_connecting() || self.ssh_client.is_connected() {
+            return Ok(false);
+        }
+
+        let host = profile.host.clone();
+        let password = match secrets::get(self.config.settings.secrets_backend, &self.password_account(&host)) {
+            Ok(password) => password,
+            Err(e) => {
+                self.set_message(format!("Could not read password: {}", e), MessageType::Error);
+                None
+            }
+        };
+
+        if host.kind.uses_ssh() && profile.key_path.is_none() && password.is_none() {
+            self.set_message("No SSH key or password configured for this host".to_string(), MessageType::Error);
+            return Ok(false);
+        }
 
         // Create SSH event channel
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (tx, rx) = ssh::event_channel();
         self.ssh_event_receiver = Some(rx);
 
+        // Modes and titles the new session sets before Connected arrives
+        // are its own
+        self.terminal_panel.reset_session_info();
+
         // Get terminal panel size for PTY
+        self.terminal_panel.set_fixed_size(host.terminal_size.fixed());
         let (width, height) = self.terminal_panel.get_size();
 
         // Start SSH connection
-        self.ssh_client.connect(host.clone(), &key_path, tx, width, height).await?;
+        self.ssh_client.share_connections = self.config.settings.connection_sharing;
+        self.ssh_client.session_wrapper = self.config.settings.session_wrapper;
+        self.ssh_client.connect(profile, password, tx, width, height).await?;
         
-        self.set_message(
-            format!("Connecting to {}@{}...", host.user, host.host),
-            MessageType::Info
-        );
+        let message = match host.kind {
+            ConnectionKind::Custom(_) => format!("Starting {}...", host.name),
+            _ => format!("Connecting to {}@{}...", host.user, host.host),
+        };
+        self.set_message(message, MessageType::Info);
 
-        Ok(())
+        Ok(true)
     }
 
-    async fn handle_ssh_events(&mut self) {
-        let mut events_to_process = Vec::new();
-        
-        // Collect events first to avoid borrowing issues
-        if let Some(receiver) = &mut self.ssh_event_receiver {
-            while let Ok(event) = receiver.try_recv() {
-                events_to_process.push(event);
+    /// Open a new session with the last connection's profile and change into
+    /// the directory the remote shell last reported via OSC 7
+    async fn reopen_in_same_directory(&mut self) {
+        let Some(cwd) = self.terminal_panel.cwd().map(str::to_string) else {
+            self.set_message("The remote shell has not reported a working directory".to_string(), MessageType::Error);
+            return;
+        };
+        let Some(profile) = self.ssh_client.profile.clone() else {
+            self.set_message("No previous connection to reopen".to_string(), MessageType::Error);
+            return;
+        };
+
+        self.cancel_reconnect();
+        self.pending_startup_command = Some(format!("cd '{}'", cwd.replace('\'', "'\\''")));
+        let _ = self.connect_with_profile(profile).await;
+    }
+
+    /// Replay the last successful connection of the selected host exactly as it
+    /// was recorded, even if the host entry has been edited since
+    async fn reconnect_as_before(&mut self) {
+        let Some(host) = self.selected_visible_host() else {
+            return;
+        };
+
+        match self.history.last_for_host(host.id).map(|entry| entry.profile.clone()) {
+            Some(profile) => {
+                let _ = self.connect_with_profile(profile).await;
+            },
+            None => {
+                self.set_message(format!("No previous connection recorded for '{}'", host.name), MessageType::Error);
             }
         }
+    }
+
+    async fn handle_ssh_events(&mut self) {
+        // Collect events first to avoid borrowing issues; a flood of output
+        // is taken a frame's worth at a time
+        let events_to_process = match &mut self.ssh_event_receiver {
+            Some(receiver) => ssh::take_events(receiver, ssh::OUTPUT_PER_FRAME),
+            None => Vec::new(),
+        };
         
         // Process collected events
         let mut should_clear_receiver = false;
+        let mut should_reconnect = false;
         for event in events_to_process {
             match &event {
                 SshEvent::Data(data) => {
                     // Feed SSH data directly to the raw terminal panel
                     self.terminal_panel.write_ssh_data(data);
+                    self.record_output(data);
+                    self.touch_session();
                 },
-                SshEvent::Connected { host } => {
+                SshEvent::Connected { host, control_path } => {
+                    let status = if control_path.is_some() { " (multiplexed)" } else { "" };
                     self.set_message(
-                        format!("Connected to {}", host.name),
+                        format!("Connected to {}{}", host.name, status),
                         MessageType::Success
                     );
                     self.terminal_panel.set_active(true);
+                    self.sidebar_focused = false;
+                    self.terminal_panel.set_multiplexed(control_path.is_some());
+                    let group_color = self.config.group_of(host).and_then(|group| theme::parse_color(&group.color));
+                    self.terminal_panel.set_border_color(group_color);
+                    self.touch_session();
                     self.ssh_client.connected = true;
                     self.ssh_client.connecting = false;
+                    self.start_recording(host);
+                    if let Some(profile) = self.ssh_client.profile.clone() {
+                        self.start_tunnels(&profile.host.forwards);
+                    }
+                    self.offer_unsent_input(host.id);
+                    // The login may have finished first
+                    if self.ssh_client.logged_in {
+                        self.session_ready().await;
+                    }
+                },
+                SshEvent::Ready => {
+                    // Only a session that got past the login ends the backoff
+                    self.finish_reconnect();
+                    if let Some(host) = self.ssh_client.get_host().cloned() {
+                        self.remember_identity(&host);
+                    }
+                    // Failed logins are recorded as failures instead
+                    if let Some(profile) = self.ssh_client.profile.clone() {
+                        self.record_connection(profile);
+                    }
+                    if self.ssh_client.is_connected() {
+                        self.session_ready().await;
+                    }
                 },
-                SshEvent::Disconnected => {
+                SshEvent::Disconnected { clean } => {
+                    self.keep_unsent_input();
+                    self.note_session_over("Connection closed before the session started".to_string());
+                    if let Some(host) = self.ssh_client.get_host() {
+                        notify::send(
+                            &self.config.settings.notifications,
+                            notify::Severity::Warning,
+                            "SSH session closed",
+                            &format!("Connection to {} closed", host.name),
+                        );
+                    }
                     self.set_message("SSH connection closed".to_string(), MessageType::Info);
                     self.terminal_panel.set_active(false);
+                    self.tunnels.clear();
+                    self.recorder = None;
+                    self.pending_startup_command = None;
+                    // Leaving the remote shell is not a dropped connection
+                    if *clean {
+                        self.cancel_reconnect();
+                    } else {
+                        should_reconnect = true;
+                    }
                     should_clear_receiver = true;
                 },
                 SshEvent::Error(err) => {
+                    self.keep_unsent_input();
+                    self.note_session_over(err.to_string());
+                    notify::send(
+                        &self.config.settings.notifications,
+                        notify::Severity::Error,
+                        "SSH error",
+                        &err.to_string(),
+                    );
                     self.set_message(
                         format!("SSH error: {}", err),
                         MessageType::Error
                     );
                     self.terminal_panel.set_active(false);
+                    self.tunnels.clear();
+                    self.recorder = None;
+                    self.pending_startup_command = None;
+                    should_clear_receiver = true;
+                    // Retrying won't get past a missing PTY or a bad host key
+                    should_reconnect = err.kind() == ErrorKind::Retryable;
+                },
+                SshEvent::ForwardFailed { forward, error } => {
+                    self.tunnel_failed(forward, error);
+                },
+                SshEvent::HostKeyPrompt(prompt) => {
+                    self.prompt_host_key(prompt.clone());
                     should_clear_receiver = true;
                 },
+                SshEvent::LoginPrompt(prompt) => {
+                    self.prompt_login_code(prompt.clone());
+                },
+                SshEvent::AuthFailed(reason) => {
+                    self.note_session_over(reason.clone());
+                    self.terminal_panel.set_active(false);
+                    self.tunnels.clear();
+                    self.recorder = None;
+                    self.pending_startup_command = None;
+                    self.cancel_reconnect();
+                    should_clear_receiver = true;
+                    if let Some(profile) = self.ssh_client.profile.clone() {
+                        self.offer_identities(&profile, reason).await;
+                    }
+                },
             }
             
             self.ssh_client.handle_event(event);
@@ -319,12 +1148,157 @@ impl AppState {
         if should_clear_receiver {
             self.ssh_event_receiver = None;
         }
+        if should_reconnect {
+            self.schedule_reconnect();
+        }
     }
 
-    async fn send_ssh_input(&self, data: &[u8]) -> Result<()> {
+    /// Type what the session was opened for, once it is connected and past
+    /// its login, so nothing lands in a password or host key prompt. Only
+    /// ssh sessions are followed well enough to know when that is.
+    async fn session_ready(&mut self) {
+        let Some(host) = self.ssh_client.get_host().cloned() else {
+            return;
+        };
+        if !host.kind.is_ssh() {
+            if !host.startup_commands.is_empty() || self.pending_startup_command.take().is_some() {
+                self.set_message(format!("Startup commands are only typed into ssh sessions, not into {}", host.name), MessageType::Info);
+            }
+            return;
+        }
+        // The host's own commands first, then the one this connection was
+        // opened for
+        let mut commands = host.startup_commands.clone();
+        commands.extend(self.pending_startup_command.take());
+        for command in commands {
+            let _ = self.send_ssh_input(format!("{}\r", command).as_bytes()).await;
+        }
+    }
+
+    /// Record the end of the live session in the history, or the failure
+    /// of the attempt when the session never got past its login
+    fn note_session_over(&mut self, reason: String) {
+        let Some(host) = self.ssh_client.get_host().cloned() else {
+            return;
+        };
+        if self.ssh_client.is_logged_in() {
+            self.record_session_end(host.id);
+        } else {
+            self.record_connection_failure(&host, reason);
+        }
+    }
+
+    /// User-initiated disconnect; never triggers auto-reconnect
+    async fn disconnect(&mut self) {
+        self.discard_unsent_input();
+        if self.ssh_client.is_logged_in() {
+            if let Some(host_id) = self.ssh_client.get_host().map(|host| host.id) {
+                self.record_session_end(host_id);
+            }
+        }
+        // Dropped first, so a reader waiting for room in it sees the end
+        self.ssh_event_receiver = None;
+        let _ = self.ssh_client.disconnect().await;
+        self.terminal_panel.set_active(false);
+        self.tunnels.clear();
+        self.recorder = None;
+        self.cancel_reconnect();
+    }
+
+    async fn send_ssh_input(&mut self, data: &[u8]) -> error::Result<()> {
+        // Anything but plain typing may move the cursor or redraw the line
+        self.terminal_panel.reset_predictions();
+        self.follow_output();
+        self.touch_session();
+        self.track_input(data);
+        self.ssh_client.send_input(data).await
+    }
+
+    /// Send a key press to the session, encoded as an xterm would
+    async fn send_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let application_cursor = self.terminal_panel.application_cursor();
+        if let Some(data) = key_encoder::encode(code, modifiers, application_cursor) {
+            let _ = self.send_ssh_input(&data).await;
+        }
+    }
+
+    /// Send a typed character, drawing it right away when local echo
+    /// prediction is on
+    async fn send_typed_char(&mut self, c: char) -> error::Result<()> {
+        let mut buffer = [0; 4];
+        let data = c.encode_utf8(&mut buffer).as_bytes();
+        if !(c.is_ascii_graphic() || c == ' ') {
+            return self.send_ssh_input(data).await;
+        }
+        self.follow_output();
+        self.touch_session();
+        self.track_input(data);
+        // Followed even when not drawn, to tell whether the remote echoes
+        self.terminal_panel.predict(c, self.config.settings.local_echo);
         self.ssh_client.send_input(data).await
     }
 
+    /// Send pasted text to the session, asking first for multi-line pastes
+    /// when paste protection is on
+    async fn paste(&mut self, text: String) {
+        if self.config.settings.paste_protection && text.trim_end().contains('\n') {
+            self.modal_state = ModalState::PastePreview(text);
+        } else {
+            let bytes = self.paste_bytes(&text);
+            let _ = self.send_ssh_input(&bytes).await;
+        }
+    }
+
+    /// Encode a paste the way a terminal would: newlines become carriage
+    /// returns, wrapped in bracketed-paste markers if the remote asked for them
+    fn paste_bytes(&self, text: &str) -> Vec<u8> {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if self.terminal_panel.bracketed_paste() {
+            // Markers inside the text would end the paste early and run the rest
+            let text = text.replace("\x1b[200~", "").replace("\x1b[201~", "");
+            format!("\x1b[200~{}\x1b[201~", text).into_bytes()
+        } else {
+            text.into_bytes()
+        }
+    }
+
+    /// Activity and bell markers for the live session ("●" for unseen
+    /// output, "🔔" for a bell), empty when there is nothing new
+    fn session_markers(&self) -> String {
+        sessions::panel_markers(&self.terminal_panel)
+    }
+
+    /// Markers for a host's session, live or in the background; `None` when
+    /// the host has no session
+    fn host_markers(&self, host_name: &str) -> Option<String> {
+        match self.ssh_client.get_host() {
+            Some(live) if live.name == host_name && self.ssh_client.is_connected() => Some(self.session_markers()),
+            _ => self.background_sessions.iter()
+                .find(|session| session.host_name() == host_name)
+                .map(BackgroundSession::markers),
+        }
+    }
+
+    /// Title for the hosting terminal window, carrying the session markers so
+    /// they are visible while sshtui is in the background
+    fn window_title(&self) -> String {
+        match self.ssh_client.get_host() {
+            Some(host) if self.ssh_client.is_connected() => {
+                let mut title = format!("sshtui - {}", host.name);
+                if let Some(label) = self.terminal_panel.session_label() {
+                    title = format!("{}: {}", title, label);
+                }
+                let markers = self.session_markers();
+                if !markers.is_empty() {
+                    title = format!("{} {}", markers, title);
+                }
+                title
+            },
+            _ if self.background_sessions.iter().any(|session| !session.markers().is_empty()) => "● sshtui".to_string(),
+            _ => "sshtui".to_string(),
+        }
+    }
+
     fn update_layout(&mut self, terminal_size: (u16, u16)) {
         self.terminal_size = terminal_size;
         
@@ -337,15 +1311,21 @@ impl AppState {
             height: terminal_size.1 - 6, // Account for title, message, and help
         };
         
-        self.terminal_panel.set_bounds(terminal_bounds);
+        let previous_size = self.terminal_panel.get_size();
+        let (live_bounds, pane_bounds) = self.split_bounds(terminal_bounds);
+        self.terminal_panel.set_bounds(live_bounds);
+        self.layout_split(pane_bounds);
+        if let Some(playback) = &mut self.playback {
+            playback.panel.set_bounds(terminal_bounds);
+        }
         
         // Resize SSH PTY if connected
-        if self.ssh_client.is_connected() {
-            let (width, height) = self.terminal_panel.get_size();
-            tokio::spawn(async move {
-                // Note: In a real implementation, you'd want to keep a reference to send this resize
-                // For now, this is a placeholder to show the concept
-            });
+        let (width, height) = self.terminal_panel.get_size();
+        if self.ssh_client.is_connected() && (width, height) != previous_size {
+            self.ssh_client.resize_pty(width, height);
+            if let Some(recorder) = &mut self.recorder {
+                let _ = recorder.resize(width, height);
+            }
         }
     }
     
@@ -357,6 +1337,7 @@ impl AppState {
                     path: "~/.ssh/id_rsa".to_string(),
                     is_default: self.config.keys.is_empty(),
                     field_focus: 0,
+                    editor: LineEditor::default(),
                 };
                 self.modal_state = ModalState::AddKey(form);
             },
@@ -364,7 +1345,13 @@ impl AppState {
                 let form = GroupEditForm {
                     name: "New Group".to_string(),
                     color: "green".to_string(),
+                    user: String::new(),
+                    port: String::new(),
+                    key_path: String::new(),
+                    options: String::new(),
+                    notes: String::new(),
                     field_focus: 0,
+                    editor: LineEditor::default(),
                 };
                 self.modal_state = ModalState::AddGroup(form);
             },
@@ -375,17 +1362,46 @@ impl AppState {
                         .position(|k| k.is_default)
                         .unwrap_or(0);
                     
+                    // Leave fields the group provides empty so they are inherited
+                    let group_defaults = self.config.groups[self.selected_group].defaults.clone();
                     let form = HostEditForm {
                         name: "New Host".to_string(),
                         host: "example.com".to_string(),
-                        port: "22".to_string(),
-                        user: "user".to_string(),
+                        port: if group_defaults.port.is_some() { String::new() } else { "22".to_string() },
+                        user: if group_defaults.user.is_some() { String::new() } else { "user".to_string() },
                         key_path: String::new(),
-                        use_key_selector: !self.config.keys.is_empty(), // Use selector if keys available
+                        use_key_selector: !self.config.keys.is_empty() && group_defaults.key_path.is_none(), // Use selector if keys available
                         selected_key_index: default_key_index,
+                        password: String::new(),
+                        autoconnect: false,
+                        autoconnect_command: String::new(),
+                        tags: self.tag_filter.join(", "),
+                        backend: None,
+                        forwards: Vec::new(),
+                        keepalive_interval: String::new(),
+                        keepalive_payload: String::new(),
+                        group_defaults,
+                        allowed_commands: String::new(),
+                        bell: None,
+                        remote_command: String::new(),
+                        startup_commands: String::new(),
+                        probes: Vec::new(),
+                        depends_on: String::new(),
+                        connection: String::new(),
+                        ssh_options: String::new(),
+                        bandwidth_limit: String::new(),
+                        notes: String::new(),
+                        term: String::new(),
+                        locale: String::new(),
+                        terminal_size: TerminalSize::Fit,
                         field_focus: 0,
+                        editor: LineEditor::default(),
                     };
                     self.modal_state = ModalState::AddHost(form);
+                } else if self.virtual_group == Some(VirtualGroup::Favorites) {
+                    self.set_message("Add hosts to a group, then star them with * to list them here.".to_string(), MessageType::Error);
+                } else if self.virtual_group == Some(VirtualGroup::Recent) {
+                    self.set_message("Recent lists the hosts last connected to. Select a group to add hosts.".to_string(), MessageType::Error);
                 } else {
                     self.set_message("Cannot add hosts to 'All' group. Select a specific group first.".to_string(), MessageType::Error);
                 }
@@ -403,6 +1419,7 @@ impl AppState {
                         path: key.path.clone(),
                         is_default: key.is_default,
                         field_focus: 0,
+                        editor: LineEditor::default(),
                     };
                     self.modal_state = ModalState::EditKey(self.selected_key, form);
                 }
@@ -410,10 +1427,17 @@ impl AppState {
             FocusArea::Groups => {
                 if self.config.groups.len() > 1 && self.selected_group < self.config.groups.len() && self.selected_group > 0 {
                     let group = &self.config.groups[self.selected_group];
+                    let defaults = &group.defaults;
                     let form = GroupEditForm {
                         name: group.name.clone(),
                         color: group.color.clone(),
+                        user: defaults.user.clone().unwrap_or_default(),
+                        port: defaults.port.map(|port| port.to_string()).unwrap_or_default(),
+                        key_path: defaults.key_path.clone().unwrap_or_default(),
+                        options: defaults.options.join("; "),
+                        notes: group.notes.clone(),
                         field_focus: 0,
+                        editor: LineEditor::default(),
                     };
                     self.modal_state = ModalState::EditGroup(self.selected_group, form);
                 } else {
@@ -421,92 +1445,196 @@ impl AppState {
                 }
             },
             FocusArea::Hosts => {
-                let hosts = self.config.get_hosts_for_group(self.selected_group);
-                if !hosts.is_empty() && self.selected_host < hosts.len() && self.selected_group > 0 {
-                    let host = &hosts[self.selected_host];
-                    
-                    // Try to find the key index if host has a specific key path
-                    let (use_selector, selected_key_index) = if let Some(key_path) = &host.key_path {
-                        let key_index = self.config.keys.iter()
-                            .position(|k| &k.path == key_path)
-                            .unwrap_or(0);
-                        (true, key_index)
-                    } else {
-                        // Use default key
-                        let default_key_index = self.config.keys.iter()
-                            .position(|k| k.is_default)
-                            .unwrap_or(0);
-                        (true, default_key_index)
-                    };
-                    
-                    let form = HostEditForm {
-                        name: host.name.clone(),
-                        host: host.host.clone(),
-                        port: host.port.to_string(),
-                        user: host.user.clone(),
-                        key_path: host.key_path.as_ref().unwrap_or(&String::new()).clone(),
-                        use_key_selector: use_selector && !self.config.keys.is_empty(),
-                        selected_key_index,
-                        field_focus: 0,
-                    };
+                let hosts = self.visible_hosts();
+                if !hosts.is_empty() && self.selected_host < hosts.len() && self.hosts_editable() {
+                    let form = self.host_edit_form(&hosts[self.selected_host]);
                     self.modal_state = ModalState::EditHost(self.selected_host, form);
                 }
             },
         }
     }
     
+    /// Host form pre-filled from `host`, including its stored password
+    fn host_edit_form(&self, host: &Host) -> HostEditForm {
+        // Try to find the key index if host has a specific key path
+        let (use_selector, selected_key_index) = if let Some(key_path) = &host.key_path {
+            let key_index = self.config.keys.iter()
+                .position(|k| &k.path == key_path)
+                .unwrap_or(0);
+            (true, key_index)
+        } else {
+            // Inherited; an empty key path keeps it that way
+            let default_key_index = self.config.keys.iter()
+                .position(|k| k.is_default)
+                .unwrap_or(0);
+            (false, default_key_index)
+        };
+        
+        HostEditForm {
+            name: host.name.clone(),
+            host: host.host.clone(),
+            port: if host.port == 0 { String::new() } else { host.port.to_string() },
+            user: host.user.clone(),
+            key_path: host.key_path.as_ref().unwrap_or(&String::new()).clone(),
+            use_key_selector: use_selector && !self.config.keys.is_empty(),
+            selected_key_index,
+            password: secrets::get(self.config.settings.secrets_backend, &secrets::host_account(host))
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            autoconnect: host.autoconnect.is_some(),
+            autoconnect_command: host.autoconnect.as_ref().map(|rule| rule.command.clone()).unwrap_or_default(),
+            tags: host.tags.join(", "),
+            backend: host.backend,
+            forwards: host.forwards.clone(),
+            keepalive_interval: host.keepalive.as_ref().map(|k| k.interval_secs.to_string()).unwrap_or_default(),
+            keepalive_payload: host.keepalive.as_ref().map(|k| k.payload.clone()).unwrap_or_default(),
+            group_defaults: self.config.group_of(host).map(|group| group.defaults.clone()).unwrap_or_default(),
+            allowed_commands: host.allowed_commands.join("\n"),
+            bell: host.bell,
+            remote_command: host.remote_command.clone().unwrap_or_default(),
+            startup_commands: host.startup_commands.join("\n"),
+            probes: host.probes.clone(),
+            depends_on: self.config.host_names(&host.depends_on).join(", "),
+            connection: match &host.kind {
+                ConnectionKind::Ssh => String::new(),
+                ConnectionKind::Mosh => "mosh".to_string(),
+                ConnectionKind::Telnet => "telnet".to_string(),
+                ConnectionKind::Custom(command) => command.clone(),
+            },
+            ssh_options: config::join_arguments(&host.ssh_options),
+            bandwidth_limit: host.bandwidth_limit.map_or(String::new(), |limit| limit.to_string()),
+            notes: host.notes.clone(),
+            term: host.term.clone().unwrap_or_default(),
+            locale: host.locale.clone().unwrap_or_default(),
+            terminal_size: host.terminal_size,
+            field_focus: 0,
+            editor: LineEditor::default(),
+        }
+    }
+
     async fn handle_delete_button_press(&mut self) {
-        match self.focus_area {
+        let (message, action) = match self.focus_area {
             FocusArea::Keys => {
-                if !self.config.keys.is_empty() && self.selected_key < self.config.keys.len() {
-                    let key_name = self.config.keys[self.selected_key].name.clone();
-                    self.config.remove_key(&key_name);
-                    // Adjust selection if necessary
-                    if self.selected_key > 0 && self.selected_key >= self.config.keys.len() {
-                        self.selected_key = self.config.keys.len() - 1;
-                    }
-                    self.set_message(format!("SSH key '{}' deleted.", key_name), MessageType::Success);
-                    let _ = self.config.save(); // Save changes
+                if self.config.keys.is_empty() || self.selected_key >= self.config.keys.len() {
+                    return;
                 }
+                let key_name = &self.config.keys[self.selected_key].name;
+                (format!("Delete SSH key '{}'?", key_name), ConfirmAction::DeleteKey(self.selected_key))
             },
             FocusArea::Groups => {
-                if self.config.groups.len() > 1 && self.selected_group < self.config.groups.len() && self.selected_group > 0 {
-                    let group_name = self.config.groups[self.selected_group].name.clone();
-                    self.config.remove_group(&group_name);
-                    // Adjust selection if necessary
-                    if self.selected_group > 0 && self.selected_group >= self.config.groups.len() {
-                        self.selected_group = self.config.groups.len() - 1;
-                    }
-                    self.selected_host = 0; // Reset host selection
-                    self.set_message(format!("Group '{}' deleted.", group_name), MessageType::Success);
-                    let _ = self.config.save(); // Save changes
-                } else {
+                match self.virtual_group {
+                    Some(VirtualGroup::Favorites) => {
+                        self.set_message("Favorites lists the starred hosts; unstar them with * instead.".to_string(), MessageType::Error);
+                        return;
+                    },
+                    Some(VirtualGroup::Recent) => {
+                        self.set_message("Recent lists the hosts last connected to and cannot be deleted.".to_string(), MessageType::Error);
+                        return;
+                    },
+                    None => {},
+                }
+                if self.selected_group == 0 || self.selected_group >= self.config.groups.len() {
                     self.set_message("Cannot delete the 'All' group.".to_string(), MessageType::Error);
+                    return;
+                }
+                let group = &self.config.groups[self.selected_group];
+                if self.has_sub_groups(self.selected_group) {
+                    let message = format!("Group '{}' has sub-groups; delete or move them first", group.name);
+                    self.set_message(message, MessageType::Error);
+                    return;
                 }
+                (
+                    format!("Delete group '{}' and its {} host(s)?", group.name, group.hosts.len()),
+                    ConfirmAction::DeleteGroup(self.selected_group),
+                )
             },
             FocusArea::Hosts => {
-                let hosts = self.config.get_hosts_for_group(self.selected_group);
-                if !hosts.is_empty() && self.selected_host < hosts.len() && self.selected_group > 0 {
-                    let host_name = hosts[self.selected_host].name.clone();
-                    let group_name = self.config.groups[self.selected_group].name.clone();
-                    if let Ok(()) = self.config.remove_host(&group_name, &host_name) {
-                        // Adjust selection if necessary
-                        if self.selected_host > 0 && self.selected_host >= hosts.len() - 1 {
-                            self.selected_host = hosts.len().saturating_sub(2);
-                        }
-                        self.set_message(format!("Host '{}' deleted from group '{}'.", host_name, group_name), MessageType::Success);
-                        let _ = self.config.save(); // Save changes
-                    } else {
-                        self.set_message("Failed to delete host".to_string(), MessageType::Error);
-                    }
-                } else {
+                if !self.hosts_editable() {
                     self.set_message("Cannot delete hosts from 'All' group.".to_string(), MessageType::Error);
+                    return;
                 }
+                let hosts = self.visible_hosts();
+                let Some(host) = hosts.get(self.selected_host) else {
+                    return;
+                };
+                // The host may belong to a group nested in the selected one
+                let group_name = self.config.group_of(host).map_or("?", |group| group.name.as_str());
+                (
+                    format!("Delete host '{}' from group '{}'?", host.name, group_name),
+                    ConfirmAction::DeleteHost(self.selected_host),
+                )
             },
-        }
+        };
+
+        self.modal_state = ModalState::Confirm(ConfirmForm { message, action, confirm_selected: false });
     }
     
+    fn open_tag_filter(&mut self) {
+        let tags: Vec<(String, bool)> = self.config.all_tags()
+            .into_iter()
+            .map(|tag| {
+                let active = self.tag_filter.contains(&tag);
+                (tag, active)
+            })
+            .collect();
+
+        if tags.is_empty() {
+            self.set_message("No tags yet. Add tags to hosts from the host editor.".to_string(), MessageType::Info);
+            return;
+        }
+        self.modal_state = ModalState::TagFilter(TagFilterForm { tags, cursor: 0 });
+    }
+
+    fn open_settings(&mut self) {
+        let notifications = &self.config.settings.notifications;
+        let accessibility = &self.config.settings.accessibility;
+        let form = SettingsForm {
+            secrets_backend: self.config.settings.secrets_backend,
+            desktop_notifications: notifications.desktop,
+            webhook_url: notifications.webhook_url.clone().unwrap_or_default(),
+            quiet_hours_start: notifications.quiet_hours.as_ref().map(|q| q.start.clone()).unwrap_or_default(),
+            quiet_hours_end: notifications.quiet_hours.as_ref().map(|q| q.end.clone()).unwrap_or_default(),
+            auto_reconnect: self.config.settings.auto_reconnect,
+            ssh_backend: self.config.settings.ssh_backend,
+            paste_protection: self.config.settings.paste_protection,
+            local_echo: self.config.settings.local_echo,
+            connection_sharing: self.config.settings.connection_sharing,
+            record_sessions: self.config.settings.record_sessions,
+            bell: self.config.settings.bell,
+            host_sort: self.config.settings.host_sort,
+            repeat_filter_ms: accessibility.repeat_filter_ms.to_string(),
+            sticky_prefix: accessibility.sticky_prefix.map(String::from).unwrap_or_default(),
+            prompt_timeout_secs: accessibility.prompt_timeout_secs.to_string(),
+            session_wrapper: self.config.settings.session_wrapper,
+            dashboard_widgets: self.config.settings.dashboard_widgets.join(", "),
+            git_remote: self.config.settings.git_remote.clone().unwrap_or_default(),
+            inventory_sync_url: self.config.settings.inventory_sync_url.clone().unwrap_or_default(),
+            inventory_sync_token: secrets::get(self.config.settings.secrets_backend, secrets::INVENTORY_SYNC_ACCOUNT)
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            lock_after_mins: self.config.settings.lock_after_mins.to_string(),
+            sidebar_key: self.config.settings.sidebar_key.clone(),
+            trash_days: self.config.settings.trash_days.to_string(),
+            field_focus: 0,
+            editor: LineEditor::default(),
+        };
+        self.modal_state = ModalState::Settings(form);
+    }
+
     async fn handle_mouse_event(&mut self, mouse: MouseEvent) {
+        // Remote applications that enabled mouse mode (tmux, htop, ...) get
+        // the events inside the terminal panel
+        if matches!(self.modal_state, ModalState::None) && self.session_has_keys() && !self.input_locked() {
+            if let Some((column, row)) = self.terminal_panel.cell_at(mouse.column, mouse.row) {
+                if let Some(data) = self.terminal_panel.mouse_reporting().encode(&mouse, column, row) {
+                    let _ = self.send_ssh_input(&data).await;
+                    return;
+                }
+            }
+        }
+
         match mouse.kind {
             MouseEventKind::Down(crossterm::event::MouseButton::Left) => {
                 let col = mouse.column;
@@ -525,14 +1653,27 @@ impl AppState {
                 if col < sidebar_width {
                     self.handle_sidebar_click(col, row);
                 } else {
-                    // Click is in the terminal panel area
-                    if self.ssh_client.is_connected() {
+                    // Click is in the terminal panel area; one on the split
+                    // pane moves the keyboard there
+                    let in_pane = self.split_pane()
+                        .is_some_and(|index| self.background_sessions[index].panel.bounds().intersects(Rect::new(col, row, 1, 1)));
+                    if in_pane {
+                        self.focus_other_pane();
+                    } else if self.ssh_client.is_connected() {
                         // For now, just focus on the terminal when clicked
                         // In the future, we could send mouse events to SSH if the remote supports it
                         self.focus_area = FocusArea::Hosts; // Keep current focus structure
                     }
                 }
             },
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown
+                if matches!(self.modal_state, ModalState::None) && self.ssh_client.is_connected() && mouse.column >= self.terminal_size.0 / 3 => {
+                // The wheel over a session scrolls its scrollback
+                let in_pane = self.split_pane()
+                    .is_some_and(|index| self.background_sessions[index].panel.bounds().intersects(Rect::new(mouse.column, mouse.row, 1, 1)));
+                let lines = if mouse.kind == MouseEventKind::ScrollUp { 3 } else { -3 };
+                self.scroll_panes(lines, in_pane);
+            },
             MouseEventKind::ScrollUp => {
                 // Handle scroll up in lists
                 match self.focus_area {
@@ -541,12 +1682,7 @@ impl AppState {
                             self.selected_key -= 1;
                         }
                     },
-                    FocusArea::Groups => {
-                        if self.selected_group > 0 {
-                            self.selected_group -= 1;
-                            self.selected_host = 0;
-                        }
-                    },
+                    FocusArea::Groups => self.move_group_cursor(false),
                     FocusArea::Hosts => {
                         if self.selected_host > 0 {
                             self.selected_host -= 1;
@@ -562,14 +1698,9 @@ impl AppState {
                             self.selected_key += 1;
                         }
                     },
-                    FocusArea::Groups => {
-                        if self.selected_group < self.config.groups.len().saturating_sub(1) {
-                            self.selected_group += 1;
-                            self.selected_host = 0;
-                        }
-                    },
+                    FocusArea::Groups => self.move_group_cursor(true),
                     FocusArea::Hosts => {
-                        let hosts = self.config.get_hosts_for_group(self.selected_group);
+                        let hosts = self.visible_hosts();
                         if self.selected_host < hosts.len().saturating_sub(1) {
                             self.selected_host += 1;
                         }
@@ -602,11 +1733,11 @@ impl AppState {
             // Check if it's a button click (last few rows of the panel)
             if relative_row >= panel_height.saturating_sub(4) {
                 // Button area - focus on the button (actions are handled separately)
-                if col >= 2 && col <= 8 {
+                if (2..=8).contains(&col) {
                     self.focus_sub_area = FocusSubArea::AddButton;
-                } else if col >= 10 && col <= 16 {
+                } else if (10..=16).contains(&col) {
                     self.focus_sub_area = FocusSubArea::EditButton;
-                } else if col >= 18 && col <= 24 {
+                } else if (18..=24).contains(&col) {
                     self.focus_sub_area = FocusSubArea::DeleteButton;
                 }
             } else {
@@ -624,20 +1755,19 @@ impl AppState {
             
             if relative_row >= panel_height.saturating_sub(4) {
                 // Button area
-                if col >= 2 && col <= 8 {
+                if (2..=8).contains(&col) {
                     self.focus_sub_area = FocusSubArea::AddButton;
-                } else if col >= 10 && col <= 16 {
+                } else if (10..=16).contains(&col) {
                     self.focus_sub_area = FocusSubArea::EditButton;
-                } else if col >= 18 && col <= 24 {
+                } else if (18..=24).contains(&col) {
                     self.focus_sub_area = FocusSubArea::DeleteButton;
                 }
             } else {
                 // List area
                 self.focus_sub_area = FocusSubArea::Items;
                 let item_row = relative_row.saturating_sub(2);
-                if item_row < self.config.groups.len() as u16 {
-                    self.selected_group = item_row as usize;
-                    self.selected_host = 0; // Reset host selection when group changes
+                if let Some(row) = self.group_rows().get(item_row as usize) {
+                    self.select_group(row.group);
                 }
             }
         } else if row >= 2 + 2 * panel_height {
@@ -647,76 +1777,202 @@ impl AppState {
             
             if relative_row >= panel_height.saturating_sub(4) {
                 // Button area
-                if col >= 2 && col <= 8 {
+                if (2..=8).contains(&col) {
                     self.focus_sub_area = FocusSubArea::AddButton;
-                } else if col >= 10 && col <= 16 {
+                } else if (10..=16).contains(&col) {
                     self.focus_sub_area = FocusSubArea::EditButton;
-                } else if col >= 18 && col <= 24 {
+                } else if (18..=24).contains(&col) {
                     self.focus_sub_area = FocusSubArea::DeleteButton;
                 }
             } else {
                 // List area
                 self.focus_sub_area = FocusSubArea::Items;
                 let item_row = relative_row.saturating_sub(2);
-                let hosts = self.config.get_hosts_for_group(self.selected_group);
-                if item_row < hosts.len() as u16 {
+                let hosts = self.visible_hosts();
+                if self.grouped_host_view() {
+                    self.click_host_row(item_row as usize);
+                } else if item_row < hosts.len() as u16 {
                     self.selected_host = item_row as usize;
                 }
             }
         }
     }
-    
-    fn handle_modal_mouse_click(&mut self, col: u16, row: u16) {
-        // This is a simplified modal click handler
-        // In a real implementation, you'd calculate the exact modal bounds
-        let center_x = self.terminal_size.0 / 2;
-        let center_y = self.terminal_size.1 / 2;
-        
-        // Check if click is outside modal bounds - if so, close modal
-        if col < center_x.saturating_sub(30) || col > center_x + 30 ||
-           row < center_y.saturating_sub(8) || row > center_y + 8 {
-            self.modal_state = ModalState::None;
-            return;
-        }
-        
-        // TODO: Handle clicks on modal fields and buttons
-        // This would require more precise coordinate calculations
-        // based on the modal layout in modal.rs
-    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Started by ssh-keygen to ask for the passphrase of a key being generated
+    if let Some(passphrase) = keygen::askpass_answer() {
+        println!("{}", passphrase);
+        return Ok(());
+    }
     env_logger::init();
+    config_crypto::set_passphrase_prompt(passphrase::prompt);
+    input_recovery::keep_on_panic();
+
+    let cli = cli::Cli::parse();
+    if let Some(profile) = &cli.profile {
+        if !Config::is_valid_profile_name(profile) {
+            anyhow::bail!("Profile names take letters, digits, '-' and '_'");
+        }
+        Config::set_profile(profile);
+    }
+
+    // Commands other than `connect` run without the TUI
+    let connect_to = match cli.command {
+        None => None,
+        Some(cli::Command::Connect { host }) => Some(host),
+        Some(command) => return cli::run(command).await,
+    };
+
+    // Create app state. The inventory is read in the background so the first
+    // frame is drawn at once, unless a host has to be looked up first or the
+    // config needs its passphrase typed before the TUI takes the terminal
+    let mut app = if connect_to.is_some() || Config::is_encrypted_on_disk() {
+        AppState::new(Inventory::load()?)
+    } else {
+        AppState::loading()
+    };
+    app.apply_theme();
+    app.scan_orphaned_sessions(false);
+    if cli.profile.is_none() && connect_to.is_none() {
+        app.offer_profiles();
+    }
+    // Hosts cannot be looked up in a config that has to be recovered first
+    let connect_to = connect_to.filter(|_| !app.recovering_config())
+        .map(|name| cli::find_host(&app.config, &name))
+        .transpose()?;
     
     // Initialize terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, crossterm::event::EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        crossterm::event::EnableMouseCapture,
+        crossterm::event::EnableFocusChange,
+        crossterm::event::EnableBracketedPaste
+    )?;
+    // Terminals that support it report Ctrl+Shift+Z apart from Ctrl+Z
+    let keyboard_enhanced = matches!(crossterm::terminal::supports_keyboard_enhancement(), Ok(true));
+    if keyboard_enhanced {
+        execute!(stdout, event::PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
-    
-    // Create app state
-    let mut app = AppState::new()?;
+
+    if let Some(host) = connect_to {
+        // Size the PTY for the real window before the first frame is drawn
+        if let Ok(size) = terminal.size() {
+            app.update_layout((size.width, size.height));
+        }
+        // Reported in the UI, since the terminal is already taken over
+        if let Err(e) = app.connect_to_host(host).await {
+            app.set_message(format!("Could not connect: {}", e), MessageType::Error);
+        }
+    }
     
     // Main event loop
     let mut last_tick = Instant::now();
     let tick_rate = Duration::from_millis(16); // ~60 FPS
+    let mut window_title = String::new();
+    
+    let mut startup_error = None;
     
     loop {
+        // Swap in the inventory once it has been read
+        if let Err(e) = app.poll_startup() {
+            startup_error = Some(e);
+            break;
+        }
+
         // Handle SSH events
         app.handle_ssh_events().await;
+
+        // Probe hosts with auto-connect rules
+        app.poll_autoconnect().await;
+        app.poll_probes();
+        app.poll_sweep();
+        app.poll_dashboard_commands();
+
+        // Re-establish dropped sessions
+        app.poll_reconnect().await;
+        app.poll_key_push();
+        app.poll_key_generation();
+        app.poll_scp_transfers();
+        app.poll_broadcast();
+        app.poll_facts();
+        if std::mem::take(&mut app.retry_pending) {
+            app.retry_last_connection().await;
+        }
+        app.poll_identity_retry().await;
+        app.poll_group_connect().await;
+        app.poll_playback();
+
+        // Keep idle sessions warm for hosts that ask for it
+        app.poll_keepalive().await;
+        app.poll_latency();
+
+        // Follow detached sessions
+        app.poll_background_sessions().await;
+        app.poll_orphans().await;
+        app.poll_palette().await;
+        app.poll_git_sync();
+        app.poll_inventory_sync();
+        app.poll_ec2_refresh();
+        app.poll_idle_lock();
+        app.poll_bells();
+        if app.quit_requested {
+            break;
+        }
         
         // Handle terminal events
         if event::poll(Duration::from_millis(1))? {
             match event::read()? {
+                // Only quitting works until the inventory is loaded
+                Event::Key(key) if app.loading_for().is_some() => {
+                    if key.modifiers == KeyModifiers::CONTROL && matches!(key.code, KeyCode::Char('c' | 'q')) {
+                        break;
+                    }
+                },
+                Event::Paste(_) | Event::Mouse(_) if app.loading_for().is_some() => {},
+                Event::Paste(_) | Event::Mouse(_) if app.sessions_locked() => {},
                 Event::Key(key) => {
+                    // Repeat filtering and the sticky Ctrl prefix come first
+                    let Some(key) = app.filter_key(key) else {
+                        continue;
+                    };
+
+                    // Locked sessions take every key until they are resumed
+                    if app.handle_lock_key(key.code, key.modifiers) {
+                        continue;
+                    }
+                    app.note_activity();
+
                     // Check if modal is active and handle modal events first
                     if app.handle_modal_key_event(key.code, key.modifiers) {
                         continue; // Modal handled the event
                     }
+
+                    // A recording being played takes the keys until it is stopped
+                    if app.handle_playback_key(key.code) {
+                        continue;
+                    }
+
+                    // So does the host filter box while it is being typed into
+                    if !app.session_has_keys() && app.handle_host_filter_key(key.code, key.modifiers) {
+                        continue;
+                    }
                     
                     match (key.code, key.modifiers) {
+                        (code, modifiers) if app.ssh_client.is_connected() && app.is_sidebar_key(code, modifiers) => {
+                            // Sidebar key: Move the keyboard between the session and the sidebar
+                            app.toggle_sidebar_focus();
+                        },
+                        (KeyCode::Esc, _) if app.ssh_client.is_connected() && app.sidebar_focused && app.host_filter.is_empty() => {
+                            // Esc: Give the keyboard back to the session
+                            app.focus_session();
+                        },
                         (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
                             if app.ssh_client.is_connected() {
                                 let _ = app.send_ssh_input(b"\x03").await;
@@ -725,18 +1981,64 @@ async fn main() -> Result<()> {
                             }
                         },
                         (KeyCode::Char('q'), KeyModifiers::CONTROL) => {
-                            if app.ssh_client.is_connected() {
-                                let _ = app.ssh_client.disconnect().await;
+                            if app.ssh_client.is_connected() || app.reconnect.is_some() {
+                                app.disconnect().await;
                             } else {
                                 break;
                             }
                         },
+                        (KeyCode::Char('g'), KeyModifiers::CONTROL) if !app.session_has_keys() => {
+                            // Ctrl+G: Detach the live session from the sidebar (the session
+                            // itself gets the key), or list background sessions
+                            if app.ssh_client.is_connected() {
+                                app.detach_session();
+                            } else {
+                                app.open_sessions();
+                            }
+                        },
+                        (KeyCode::Char('f'), KeyModifiers::CONTROL)
+                            if !app.session_has_keys() && (app.ssh_client.is_connected() || app.focus_area == FocusArea::Hosts) => {
+                            // Ctrl+F: Port forwards of the live session (from the sidebar, so
+                            // the session keeps the key) or the selected host
+                            app.open_forwards();
+                        },
+                        // The session gets the keys that move around the sidebar
+                        (KeyCode::Up | KeyCode::Down | KeyCode::Tab | KeyCode::BackTab, _)
+                            if app.session_has_keys() && !app.input_locked() => {
+                            app.send_key(key.code, key.modifiers).await;
+                        },
                         (KeyCode::Tab, KeyModifiers::NONE) => {
                             app.advance_focus(true);
                         },
                         (KeyCode::BackTab, _) => {
                             app.advance_focus(false);
                         },
+                        // Hosts with an allowlist take commands from the picker only
+                        (code, _) if app.input_locked()
+                            && !(matches!(code, KeyCode::Up | KeyCode::Down) && app.focus_sub_area == FocusSubArea::Items) => {
+                            if matches!(code, KeyCode::Enter | KeyCode::Char(_)) {
+                                app.open_command_picker();
+                            } else {
+                                app.set_message("Free typing is disabled for this host; press Enter to pick a command".to_string(), MessageType::Info);
+                            }
+                        },
+                        (KeyCode::Left | KeyCode::Right, KeyModifiers::NONE)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts
+                                && app.focus_sub_area == FocusSubArea::Items && app.grouped_host_view() => {
+                            // ←/→: Fold or unfold a group of the "All" view
+                            app.set_group_collapsed(Some(key.code == KeyCode::Left));
+                        },
+                        (KeyCode::Left | KeyCode::Right, KeyModifiers::NONE)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Groups
+                                && app.focus_sub_area == FocusSubArea::Items => {
+                            // ←/→: Fold or unfold the sub-groups of a group
+                            app.set_folder_collapsed(key.code == KeyCode::Left);
+                        },
+                        (KeyCode::Up | KeyCode::Down, KeyModifiers::CONTROL)
+                            if !app.session_has_keys() && app.focus_sub_area == FocusSubArea::Items => {
+                            // Ctrl+Up/Down: Reorder the selected key, group or host
+                            app.move_selected_item(key.code == KeyCode::Up);
+                        },
                         (KeyCode::Up, _) => {
                             if app.focus_sub_area == FocusSubArea::Items {
                                 match app.focus_area {
@@ -745,11 +2047,9 @@ async fn main() -> Result<()> {
                                             app.selected_key -= 1;
                                         }
                                     },
-                                    FocusArea::Groups => {
-                                        if app.selected_group > 0 {
-                                            app.selected_group -= 1;
-                                            app.selected_host = 0;
-                                        }
+                                    FocusArea::Groups => app.move_group_cursor(false),
+                                    FocusArea::Hosts if app.grouped_host_view() => {
+                                        app.move_host_cursor(false);
                                     },
                                     FocusArea::Hosts => {
                                         if app.selected_host > 0 {
@@ -758,7 +2058,7 @@ async fn main() -> Result<()> {
                                     },
                                 }
                             } else if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(b"\x1b[A").await;
+                                app.send_key(key.code, key.modifiers).await;
                             }
                         },
                         (KeyCode::Down, _) => {
@@ -769,43 +2069,34 @@ async fn main() -> Result<()> {
                                             app.selected_key += 1;
                                         }
                                     },
-                                    FocusArea::Groups => {
-                                        if app.selected_group < app.config.groups.len().saturating_sub(1) {
-                                            app.selected_group += 1;
-                                            app.selected_host = 0;
-                                        }
+                                    FocusArea::Groups => app.move_group_cursor(true),
+                                    FocusArea::Hosts if app.grouped_host_view() => {
+                                        app.move_host_cursor(true);
                                     },
                                     FocusArea::Hosts => {
-                                        let hosts = app.config.get_hosts_for_group(app.selected_group);
+                                        let hosts = app.visible_hosts();
                                         if app.selected_host < hosts.len().saturating_sub(1) {
                                             app.selected_host += 1;
                                         }
                                     },
                                 }
                             } else if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(b"\x1b[B").await;
-                            }
-                        },
-                        (KeyCode::Left, _) => {
-                            if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(b"\x1b[D").await;
-                            }
-                        },
-                        (KeyCode::Right, _) => {
-                            if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(b"\x1b[C").await;
+                                app.send_key(key.code, key.modifiers).await;
                             }
                         },
                         (KeyCode::Enter, _) => {
-                            if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(b"\r").await;
+                            if app.session_has_keys() {
+                                app.send_key(key.code, key.modifiers).await;
                             } else {
                                 match app.focus_sub_area {
                                     FocusSubArea::Items => {
                                         if app.focus_area == FocusArea::Hosts {
-                                            let hosts = app.config.get_hosts_for_group(app.selected_group);
-                                            if let Some(host) = hosts.get(app.selected_host) {
-                                                let _ = app.connect_to_host(host.clone()).await;
+                                            if app.selected_host_header().is_some() {
+                                                app.set_group_collapsed(None);
+                                            } else if app.ssh_client.is_connected() {
+                                                app.set_message("Detach the session with Ctrl+G to connect to another host".to_string(), MessageType::Info);
+                                            } else if let Some(host) = app.selected_visible_host() {
+                                                let _ = app.connect_to_host(host).await;
                                             }
                                         }
                                     },
@@ -821,29 +2112,202 @@ async fn main() -> Result<()> {
                                 }
                             }
                         },
-                        (KeyCode::Backspace, _) => {
-                            if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(b"\x7f").await;
-                            }
+                        (KeyCode::Char('n'), KeyModifiers::CONTROL) if !app.session_has_keys() => {
+                            // Ctrl+N: Add new item in current panel
+                            app.handle_add_button_press().await;
                         },
-                        (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
-                            if !app.ssh_client.is_connected() {
-                                // Ctrl+N: Add new item in current panel
-                                app.handle_add_button_press().await;
-                            }
+                        (KeyCode::Char('r'), KeyModifiers::CONTROL)
+                            if !app.ssh_client.is_connected() && app.focus_area == FocusArea::Hosts => {
+                            // Ctrl+R: Reconnect to the selected host exactly as last time
+                            app.reconnect_as_before().await;
                         },
-                        (KeyCode::Char(c), _) => {
-                            if app.ssh_client.is_connected() {
-                                let _ = app.send_ssh_input(&[c as u8]).await;
+                        (KeyCode::Char('t'), KeyModifiers::CONTROL)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts => {
+                            // Ctrl+T: Filter hosts by tag
+                            app.open_tag_filter();
+                        },
+                        (KeyCode::Char('r'), KeyModifiers::NONE)
+                            if !app.ssh_client.is_connected() && !app.ssh_client.is_connecting() => {
+                            // r: Retry the last connection
+                            app.retry_last_connection().await;
+                        },
+                        (KeyCode::Char('p'), KeyModifiers::NONE)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts => {
+                            // p: Run the host probes now
+                            app.refresh_probes();
+                        },
+                        (KeyCode::Char('C'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                            if !app.session_has_keys() && matches!(app.focus_area, FocusArea::Groups | FocusArea::Hosts) => {
+                            // Shift+C: Check which hosts of the group are reachable
+                            app.start_sweep();
+                        },
+                        (KeyCode::Char('f'), KeyModifiers::NONE) if !app.session_has_keys() => {
+                            // f: Type into the host filter box
+                            app.open_host_filter();
+                        },
+                        (KeyCode::Esc, _) if !app.session_has_keys() && !app.host_filter.is_empty() => {
+                            // Esc: Clear the host filter
+                            app.clear_host_filter();
+                        },
+                        (KeyCode::Char('/'), KeyModifiers::NONE) if !app.session_has_keys() => {
+                            // /: Search hosts by their facts
+                            app.open_facts();
+                        },
+                        (KeyCode::Char('g'), KeyModifiers::NONE)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Keys => {
+                            // g: Generate a new key with ssh-keygen
+                            app.open_key_generation();
+                        },
+                        (KeyCode::Char('c'), KeyModifiers::NONE)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts => {
+                            // c: Duplicate the selected host
+                            app.duplicate_selected_host();
+                        },
+                        (KeyCode::Char('m'), KeyModifiers::NONE)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts => {
+                            // m: Move or copy the selected host to another group
+                            app.open_move_host();
+                        },
+                        (KeyCode::Char('t'), KeyModifiers::NONE)
+                            if !app.session_has_keys() && matches!(app.focus_area, FocusArea::Groups | FocusArea::Hosts) => {
+                            // t: Open the trash of deleted hosts and groups
+                            app.open_trash();
+                        },
+                        (KeyCode::Char('M'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts => {
+                            // Shift+M: Switch the selected host between ssh and mosh
+                            app.toggle_selected_host_mosh();
+                        },
+                        (KeyCode::Char('*'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                            if !app.session_has_keys() && app.focus_area == FocusArea::Hosts => {
+                            // *: Star or unstar the selected host
+                            app.toggle_selected_host_favorite();
+                        },
+                        (KeyCode::Char(c), KeyModifiers::NONE)
+                            if !app.ssh_client.is_connected() && !app.ssh_client.is_connecting() && app.favorite_for_key(c).is_some() => {
+                            // 1-5: Connect to a favorite listed on the dashboard
+                            if let Some(host) = app.favorite_for_key(c) {
+                                let _ = app.connect_to_host(host).await;
                             }
                         },
+                        (KeyCode::Char('o'), KeyModifiers::CONTROL)
+                            if !app.ssh_client.is_connected() && !app.ssh_client.is_connecting() => {
+                            // Ctrl+O: Reopen the last session in the same remote directory
+                            app.reopen_in_same_directory().await;
+                        },
+                        (KeyCode::Char('z') | KeyCode::Char('Z'), modifiers)
+                            if modifiers.contains(KeyModifiers::CONTROL) && (modifiers.contains(KeyModifiers::SHIFT) || key.code == KeyCode::Char('Z'))
+                                && !app.session_has_keys() => {
+                            // Ctrl+Shift+Z: Redo the last undone change
+                            app.redo_change();
+                        },
+                        (KeyCode::Char('z'), KeyModifiers::ALT) if !app.session_has_keys() => {
+                            // Alt+Z: Redo, for terminals that send Ctrl+Shift+Z as Ctrl+Z
+                            app.redo_change();
+                        },
+                        (KeyCode::Char('z'), KeyModifiers::CONTROL) if !app.session_has_keys() => {
+                            // Ctrl+Z: Undo the last change to groups, hosts or keys
+                            app.undo_change();
+                        },
+                        (KeyCode::Char('a'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+A: Open sessions to every host of the group, dependencies first
+                            app.start_group_connect();
+                        },
+                        (KeyCode::Char('b'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+B: Broadcast a command to the selected group
+                            app.open_broadcasts();
+                        },
+                        (KeyCode::Char('p'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+P: Play back a recorded session
+                            app.open_recordings();
+                        },
+                        (KeyCode::Char('k'), KeyModifiers::CONTROL) if !app.session_has_keys() => {
+                            // Ctrl+K: Export public keys as an authorized_keys snippet
+                            app.open_authorized_keys();
+                        },
+                        (KeyCode::Char('l'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+L: Search every action and run one
+                            app.open_palette();
+                        },
+                        (KeyCode::Char('s'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+S: Open settings
+                            app.open_settings();
+                        },
+                        (KeyCode::Char('x'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+X: Edit the raw config file
+                            app.open_config_editor();
+                        },
+                        (KeyCode::Char('e'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+E: Switch profiles
+                            app.open_profiles();
+                        },
+                        (KeyCode::Char('y'), KeyModifiers::CONTROL) if !app.ssh_client.is_connected() => {
+                            // Ctrl+Y: Pick a color theme
+                            app.open_themes();
+                        },
+                        (KeyCode::Char('u'), KeyModifiers::CONTROL) if app.ssh_client.is_connected() && !app.session_has_keys() => {
+                            // Ctrl+U: Upload a file or directory to the host with scp, from
+                            // the sidebar so the session keeps the key
+                            app.open_scp(true).await;
+                        },
+                        (KeyCode::Char('y'), KeyModifiers::CONTROL) if app.ssh_client.is_connected() && !app.session_has_keys() => {
+                            // Ctrl+Y: Download from the host with scp, from the sidebar
+                            app.open_scp(false).await;
+                        },
+                        (KeyCode::Char('\\'), KeyModifiers::ALT) if app.ssh_client.is_connected() => {
+                            // Alt+\: Show a background session beside the live one
+                            app.toggle_split(SplitDirection::Vertical);
+                        },
+                        (KeyCode::Char('-'), KeyModifiers::ALT) if app.ssh_client.is_connected() => {
+                            // Alt+-: Show a background session below the live one
+                            app.toggle_split(SplitDirection::Horizontal);
+                        },
+                        (KeyCode::Char('o'), KeyModifiers::ALT) if app.split_pane().is_some() => {
+                            // Alt+O: Type into the other pane of the split
+                            app.focus_other_pane();
+                        },
+                        (KeyCode::Char('l'), KeyModifiers::ALT) if app.split_pane().is_some() => {
+                            // Alt+L: Lock the scroll positions of the split's panes together
+                            app.toggle_scroll_lock();
+                        },
+                        (KeyCode::PageUp | KeyCode::PageDown, KeyModifiers::SHIFT) if app.ssh_client.is_connected() => {
+                            // Shift+PageUp/PageDown: Scroll the session's scrollback by half a screen
+                            let half = (app.terminal_panel.get_size().1 / 2).max(1) as isize;
+                            app.scroll_panes(if key.code == KeyCode::PageUp { half } else { -half }, false);
+                        },
+                        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) if app.session_has_keys() => {
+                            let _ = app.send_typed_char(c).await;
+                        },
+                        // Everything else goes to the session as the terminal would send it
+                        (code, modifiers) if app.session_has_keys() => {
+                            app.send_key(code, modifiers).await;
+                        },
                         _ => {}
                     }
                 },
                 Event::Resize(width, height) => {
                     app.update_layout((width, height));
                 },
+                Event::Paste(text) => {
+                    app.note_activity();
+                    // Open forms take pastes as typed text; otherwise they go to the session
+                    let typed_into_modal = app.handle_modal_paste(&text);
+                    if !typed_into_modal && app.input_locked() {
+                        app.set_message("Pasting is disabled for this host".to_string(), MessageType::Error);
+                    } else if !typed_into_modal && app.session_has_keys() {
+                        app.paste(text).await;
+                    }
+                },
+                // Output arriving while sshtui is in the background is flagged
+                // until the window is focused again
+                Event::FocusGained => {
+                    app.terminal_panel.set_viewed(true);
+                },
+                Event::FocusLost => {
+                    app.terminal_panel.set_viewed(false);
+                },
                 Event::Mouse(mouse) => {
+                    app.note_activity();
                     // Store the previous focus state to detect button clicks
                     let prev_focus_area = app.focus_area;
                     let prev_focus_sub_area = app.focus_sub_area;
@@ -869,10 +2333,16 @@ async fn main() -> Result<()> {
                         }
                     }
                 },
-                _ => {}
             }
         }
         
+        // Keep the window title in sync with the session markers
+        let title = app.window_title();
+        if title != window_title {
+            execute!(terminal.backend_mut(), crossterm::terminal::SetTitle(&title))?;
+            window_title = title;
+        }
+
         // Render UI
         terminal.draw(|frame| {
             ui::render(frame, &mut app);
@@ -886,8 +2356,22 @@ async fn main() -> Result<()> {
     }
     
     // Cleanup
+    app.keep_unsent_input();
+    app.stop_sessions();
+    if keyboard_enhanced {
+        execute!(terminal.backend_mut(), event::PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, crossterm::event::DisableMouseCapture)?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        crossterm::event::DisableMouseCapture,
+        crossterm::event::DisableFocusChange,
+        crossterm::event::DisableBracketedPaste
+    )?;
+    if let Some(note) = app.quit_note {
+        eprintln!("{}", note);
+    }
     
-    Ok(())
+    startup_error.map_or(Ok(()), Err)
 }
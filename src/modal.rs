@@ -1,22 +1,88 @@
-use crate::{AppState, ModalState, KeyEditForm, GroupEditForm, HostEditForm, ConfirmAction, MessageType};
-use crate::config::{SshKey, Group, Host};
+use crate::{AppState, ModalState, KeyEditForm, KeyGenForm, GroupEditForm, HostEditForm, TagFilterForm, SettingsForm, ForwardsForm, ForwardConflictForm, ConfigRecoveryForm, SessionsForm, OrphansForm, TrashForm, ImportForm, ImportPreviewForm, SharedInventoryForm, ConfirmForm, ConfirmAction, ReviewChangesForm, HostKeyForm, AuthorizedKeysForm, IdentityForm, CommandPickerForm, BroadcastForm, RecordingsForm, ThemeForm, MoveHostForm, ScpForm, ConfigEditorForm, FactsForm, PaletteForm, ProfilesForm, LoginPromptForm, MessageType};
+use crate::theme;
+use crate::config::{self, default_dashboard_widgets, BellMode, Config, ConnectionKind, SshKey, Group, Host, HostSort, PortForward, QuietHours, SecretsBackend, SessionWrapper, SshBackend, TerminalSize, DASHBOARD_WIDGETS};
+use crate::config_diff::{self, FieldChange};
+use crate::config_editor;
+use crate::dashboard;
+use crate::group_connect::StepStatus;
+use crate::hostkeys::{self, KeySource};
+use crate::key_check::{self, KeyDetails};
+use crate::keygen;
+use crate::line_editor::{Completion, LineEditor};
+use crate::secrets;
+use crate::sidebar_focus;
+use crate::trust_report::TrustReport;
 use crossterm::event::{KeyCode, KeyModifiers};
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Clear, Paragraph, List, ListItem},
+    widgets::{Block, Borders, Clear, Paragraph},
 };
+use uuid::Uuid;
+
+/// A button drawn on the bottom border of every modal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModalButton {
+    /// Does what Enter does
+    Save,
+    /// Does what Esc does
+    Cancel,
+}
+
+/// Where the open modal was last drawn, so clicks can be matched to what
+/// is under them
+#[derive(Debug, Clone, Default)]
+pub struct ModalGeometry {
+    pub area: Rect,
+    /// Form fields by their `field_focus` number; one field may span a few
+    pub fields: Vec<(usize, Rect)>,
+    pub buttons: Vec<(ModalButton, Rect)>,
+}
 
 impl AppState {
     pub fn handle_modal_key_event(&mut self, key: KeyCode, modifiers: KeyModifiers) -> bool {
         if let ModalState::None = self.modal_state {
             return false; // Not handled
         }
+        if let ModalState::ConfigEditor(_) = self.modal_state {
+            self.handle_config_editor_key(key, modifiers);
+            return true;
+        }
+        // Tab completes file paths in the fields that take them
+        if self.handle_path_completion_key(key) {
+            return true;
+        }
+        // The focused text field takes the cursor and editing keys
+        if let Some((editor, text)) = self.focused_input() {
+            if editor.handle_key(key, modifiers, text) {
+                return true;
+            }
+        }
 
         match (key, modifiers) {
             (KeyCode::Esc, _) => {
+                match self.modal_state.clone() {
+                    ModalState::Themes(form) => self.cancel_theme_preview(&form),
+                    // Back to the edit form rather than losing it
+                    ModalState::ReviewChanges(form) => {
+                        self.modal_state = *form.edit;
+                        return true;
+                    },
+                    ModalState::ImportPreview(form) => {
+                        self.back_to_import(form);
+                        return true;
+                    },
+                    ModalState::SharedInventory(_) => self.decline_shared_inventory(),
+                    // The stand-in config must not be used without a choice
+                    ModalState::ConfigRecovery(_) => return true,
+                    _ => {},
+                }
                 self.modal_state = ModalState::None;
                 true
             },
+            (KeyCode::Enter, _) if self.modal_takes_newlines() => {
+                self.handle_modal_char_input('\n');
+                true
+            },
             (KeyCode::Enter, _) => {
                 self.handle_modal_submit();
                 true
@@ -37,6 +103,57 @@ impl AppState {
                 self.advance_modal_field(true);
                 true
             },
+            (KeyCode::PageUp, _) | (KeyCode::PageDown, _) => {
+                // Scroll the selected host's output in the results matrix
+                match &mut self.modal_state {
+                    ModalState::Broadcast(form) if form.viewing.is_some() => {
+                        form.scroll = if key == KeyCode::PageDown { form.scroll.saturating_add(10) } else { form.scroll.saturating_sub(10) };
+                        true
+                    },
+                    _ => false
+                }
+            },
+            (KeyCode::Left, _) | (KeyCode::Right, _) => {
+                match &mut self.modal_state {
+                    ModalState::Confirm(form) => {
+                        form.confirm_selected = !form.confirm_selected;
+                        true
+                    },
+                    ModalState::ReviewChanges(form) => {
+                        form.confirm_selected = !form.confirm_selected;
+                        true
+                    },
+                    ModalState::ImportPreview(form) => {
+                        form.confirm_selected = !form.confirm_selected;
+                        true
+                    },
+                    ModalState::SharedInventory(form) => {
+                        form.confirm_selected = !form.confirm_selected;
+                        true
+                    },
+                    ModalState::HostKey(form) => {
+                        form.trust_selected = !form.trust_selected;
+                        true
+                    },
+                    ModalState::MoveHost(form) => {
+                        form.copy = !form.copy;
+                        true
+                    },
+                    ModalState::AddGroup(form) | ModalState::EditGroup(_, form) if form.field_focus == 1 => {
+                        form.cycle_color(key == KeyCode::Right);
+                        true
+                    },
+                    ModalState::GenerateKey(form) if form.field_focus == 0 => {
+                        form.toggle_key_type();
+                        true
+                    },
+                    ModalState::Import(form) if form.field_focus == 0 => {
+                        form.format = form.format.cycle(key == KeyCode::Right);
+                        true
+                    },
+                    _ => false
+                }
+            },
             (KeyCode::Char(c), _) => {
                 self.handle_modal_char_input(c);
                 true
@@ -49,7 +166,79 @@ impl AppState {
         }
     }
 
+    /// Type pasted text into the open modal; returns false when none is open
+    pub fn handle_modal_paste(&mut self, text: &str) -> bool {
+        if let ModalState::None = self.modal_state {
+            return false;
+        }
+
+        let newlines = self.modal_takes_newlines();
+        for c in text.chars().filter(|c| !c.is_control() || (*c == '\n' && newlines)) {
+            self.handle_modal_char_input(c);
+        }
+        true
+    }
+
+    /// A click while a modal is open: fields take the focus and the buttons
+    /// save or cancel. Clicks outside the modal are ignored, so a stray one
+    /// does not throw away what was typed
+    pub fn handle_modal_mouse_click(&mut self, col: u16, row: u16) {
+        let geometry = self.modal_geometry.borrow().clone();
+        let clicked = Rect::new(col, row, 1, 1);
+        match geometry.buttons.iter().find(|(_, rect)| rect.intersects(clicked)) {
+            Some((ModalButton::Save, _)) => {
+                if let ModalState::ConfigEditor(_) = self.modal_state {
+                    self.handle_config_editor_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+                } else {
+                    // Not through Enter, which starts a new line in notes
+                    self.handle_modal_submit();
+                }
+            },
+            Some((ModalButton::Cancel, _)) => {
+                self.handle_modal_key_event(KeyCode::Esc, KeyModifiers::NONE);
+            },
+            None if !geometry.area.intersects(clicked) => {},
+            None => {
+                if let Some(&(field, _)) = geometry.fields.iter().find(|(_, rect)| rect.intersects(clicked)) {
+                    self.focus_modal_field(field);
+                }
+            },
+        }
+    }
+
+    fn focus_modal_field(&mut self, field: usize) {
+        match &mut self.modal_state {
+            ModalState::AddKey(form) | ModalState::EditKey(_, form) => form.field_focus = field,
+            ModalState::GenerateKey(form) => form.field_focus = field,
+            ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => form.field_focus = field,
+            ModalState::AddHost(form) | ModalState::EditHost(_, form) => form.field_focus = field,
+            ModalState::Settings(form) => form.field_focus = field,
+            ModalState::Scp(form) => form.field_focus = field,
+            ModalState::Import(form) => form.field_focus = field,
+            _ => {}
+        }
+        if let Some((editor, _)) = self.focused_input() {
+            editor.reset();
+        }
+    }
+
+    /// Whether the focused field is multi-line, so Enter starts a new line
+    fn modal_takes_newlines(&self) -> bool {
+        match &self.modal_state {
+            ModalState::AddHost(form) | ModalState::EditHost(_, form) => matches!(form.field_focus, 12 | 15 | 20),
+            ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => form.field_focus == 6,
+            ModalState::ConfigEditor(_) => true,
+            _ => false,
+        }
+    }
+
     fn advance_modal_field(&mut self, forward: bool) {
+        // Searchable lists, whose length depends on the query
+        let match_count = match &self.modal_state {
+            ModalState::Facts(form) => self.fact_matches(&form.query).len(),
+            ModalState::Palette(form) => self.palette_matches(&form.query).len(),
+            _ => 0,
+        };
         match &mut self.modal_state {
             ModalState::AddKey(form) | ModalState::EditKey(_, form) => {
                 let max_fields = 3;
@@ -59,8 +248,22 @@ impl AppState {
                     form.field_focus = if form.field_focus == 0 { max_fields - 1 } else { form.field_focus - 1 };
                 }
             },
+            ModalState::GenerateKey(form) => {
+                let max_fields = 4;
+                if forward {
+                    form.field_focus = (form.field_focus + 1) % max_fields;
+                } else {
+                    form.field_focus = if form.field_focus == 0 { max_fields - 1 } else { form.field_focus - 1 };
+                }
+            },
+            ModalState::Scp(form) => {
+                form.field_focus = 1 - form.field_focus;
+            },
+            ModalState::Import(form) => {
+                form.field_focus = 1 - form.field_focus;
+            },
             ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => {
-                let max_fields = 2;
+                let max_fields = 7;
                 if forward {
                     form.field_focus = (form.field_focus + 1) % max_fields;
                 } else {
@@ -68,23 +271,262 @@ impl AppState {
                 }
             },
             ModalState::AddHost(form) | ModalState::EditHost(_, form) => {
-                let max_fields = 5;
+                let max_fields = 24;
+                if forward {
+                    form.field_focus = (form.field_focus + 1) % max_fields;
+                } else {
+                    form.field_focus = if form.field_focus == 0 { max_fields - 1 } else { form.field_focus - 1 };
+                }
+            },
+            ModalState::TagFilter(form) => {
+                let max_fields = form.tags.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Forwards(form) => {
+                let max_fields = form.forwards.len() + 1;
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Settings(form) => {
+                let max_fields = 24;
                 if forward {
                     form.field_focus = (form.field_focus + 1) % max_fields;
                 } else {
                     form.field_focus = if form.field_focus == 0 { max_fields - 1 } else { form.field_focus - 1 };
                 }
             },
+            ModalState::Sessions(form) => {
+                let max_fields = self.background_sessions.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Profiles(form) => {
+                let max_fields = form.profiles.len() + 1;
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::ConfigRecovery(form) => {
+                let max_fields = form.choices.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::ForwardConflict(form) => {
+                let max_fields = form.choices.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Orphans(form) => {
+                let max_fields = form.sessions.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Trash(form) => {
+                let max_fields = self.config.trash.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Confirm(form) => {
+                form.confirm_selected = !form.confirm_selected;
+            },
+            ModalState::ImportPreview(form) => {
+                let last = form.lines.len().saturating_sub(1);
+                form.scroll = if forward { (form.scroll + 1).min(last) } else { form.scroll.saturating_sub(1) };
+            },
+            ModalState::SharedInventory(form) => {
+                let last = form.lines.len().saturating_sub(1);
+                form.scroll = if forward { (form.scroll + 1).min(last) } else { form.scroll.saturating_sub(1) };
+            },
+            ModalState::ReviewChanges(form) => {
+                form.confirm_selected = !form.confirm_selected;
+            },
+            ModalState::HostKey(form) => {
+                form.trust_selected = !form.trust_selected;
+            },
+            ModalState::AuthorizedKeys(form) => {
+                let max_fields = form.keys.len() + form.hosts.len();
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Broadcast(form) if form.viewing.is_some() => {
+                // Move through the hosts of the results matrix
+                let max_fields = form.viewing
+                    .and_then(|index| self.broadcasts.entries.get(index))
+                    .map_or(1, |entry| entry.targets.len().max(1));
+                if forward {
+                    form.host = (form.host + 1) % max_fields;
+                } else {
+                    form.host = if form.host == 0 { max_fields - 1 } else { form.host - 1 };
+                }
+                form.scroll = 0;
+            },
+            ModalState::Broadcast(form) => {
+                let max_fields = self.broadcasts.entries.len() + 1;
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Commands(form) => {
+                let max_fields = form.commands.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Recordings(form) => {
+                let max_fields = form.recordings.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Facts(form) => {
+                let max_fields = match_count.max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Palette(form) => {
+                let max_fields = match_count.max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::MoveHost(form) => {
+                let max_fields = form.groups.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Themes(form) => {
+                let max_fields = form.themes.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
+            ModalState::Identities(form) => {
+                let max_fields = form.identities.len().max(1);
+                if forward {
+                    form.cursor = (form.cursor + 1) % max_fields;
+                } else {
+                    form.cursor = if form.cursor == 0 { max_fields - 1 } else { form.cursor - 1 };
+                }
+            },
             _ => {}
         }
+
+        if let Some((editor, _)) = self.focused_input() {
+            editor.reset();
+        }
+
+        // Show the theme under the cursor straight away
+        if let ModalState::Themes(form) = &self.modal_state {
+            if let Some(name) = form.themes.get(form.cursor).cloned() {
+                if let Err(e) = self.preview_theme(&name) {
+                    self.set_message(e, MessageType::Error);
+                }
+            }
+        }
     }
 
     fn handle_modal_char_input(&mut self, c: char) {
         match &mut self.modal_state {
+            ModalState::ConfigEditor(form) => form.insert(c),
+            ModalState::Facts(form) => {
+                form.query.push(c);
+                form.cursor = 0;
+            },
+            ModalState::Palette(form) => {
+                form.query.push(c);
+                form.cursor = 0;
+            },
+            ModalState::Confirm(form) => {
+                match c {
+                    'y' | 'Y' => {
+                        form.confirm_selected = true;
+                        self.handle_modal_submit();
+                    },
+                    'n' | 'N' => self.modal_state = ModalState::None,
+                    _ => {}
+                }
+            },
+            ModalState::ImportPreview(form) => {
+                match c {
+                    'y' | 'Y' => {
+                        form.confirm_selected = true;
+                        self.handle_modal_submit();
+                    },
+                    'n' | 'N' => {
+                        let form = form.clone();
+                        self.back_to_import(form);
+                    },
+                    _ => {}
+                }
+            },
+            ModalState::SharedInventory(form) => {
+                match c {
+                    'y' | 'Y' => {
+                        form.confirm_selected = true;
+                        self.handle_modal_submit();
+                    },
+                    'n' | 'N' => {
+                        self.modal_state = ModalState::None;
+                        self.decline_shared_inventory();
+                    },
+                    _ => {}
+                }
+            },
+            ModalState::LoginPrompt(form) => form.editor.insert(&mut form.answer, c),
+            ModalState::Import(form) if form.field_focus == 1 => form.editor.insert(&mut form.path, c),
+            ModalState::Scp(form) => {
+                match form.field_focus {
+                    0 => form.editor.insert(&mut form.local, c),
+                    _ => form.editor.insert(&mut form.remote, c),
+                }
+            },
             ModalState::AddKey(form) | ModalState::EditKey(_, form) => {
                 match form.field_focus {
-                    0 => form.name.push(c),
-                    1 => form.path.push(c),
+                    0 => form.editor.insert(&mut form.name, c),
+                    1 => form.editor.insert(&mut form.path, c),
                     2 => {
                         if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
                             form.is_default = true;
@@ -95,37 +537,47 @@ impl AppState {
                     _ => {}
                 }
             },
+            ModalState::GenerateKey(form) => {
+                match form.field_focus {
+                    0 if c == ' ' => form.toggle_key_type(),
+                    1 => form.editor.insert(&mut form.path, c),
+                    2 => form.editor.insert(&mut form.comment, c),
+                    3 => form.editor.insert(&mut form.passphrase, c),
+                    _ => {}
+                }
+            },
             ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => {
                 match form.field_focus {
-                    0 => form.name.push(c),
-                    1 => form.color.push(c),
+                    0 => form.editor.insert(&mut form.name, c),
+                    1 => match c {
+                        ' ' | 'l' | 'L' => form.cycle_color(true),
+                        'h' | 'H' => form.cycle_color(false),
+                        _ => {}
+                    },
+                    2 => form.editor.insert(&mut form.user, c),
+                    3 if c.is_ascii_digit() => form.editor.insert(&mut form.port, c),
+                    4 => form.editor.insert(&mut form.key_path, c),
+                    5 => form.editor.insert(&mut form.options, c),
+                    6 => form.notes.push(c),
                     _ => {}
                 }
             },
             ModalState::AddHost(form) | ModalState::EditHost(_, form) => {
                 match form.field_focus {
-                    0 => form.name.push(c),
-                    1 => form.host.push(c),
-                    2 => {
-                        if c.is_ascii_digit() {
-                            form.port.push(c);
-                        }
-                    },
-                    3 => form.user.push(c),
+                    0 => form.editor.insert(&mut form.name, c),
+                    1 => form.editor.insert(&mut form.host, c),
+                    2 if c.is_ascii_digit() => form.editor.insert(&mut form.port, c),
+                    3 => form.editor.insert(&mut form.user, c),
                     4 => {
                         if form.use_key_selector {
                             // In key selector mode, handle selection
                             match c {
-                                '↑' | 'k' => {
-                                    if form.selected_key_index > 0 {
-                                        form.selected_key_index -= 1;
-                                    }
+                                '↑' | 'k' if form.selected_key_index > 0 => {
+                                    form.selected_key_index -= 1;
                                 },
-                                '↓' | 'j' => {
-                                    // Bound check against available keys
-                                    if form.selected_key_index + 1 < self.config.keys.len() {
-                                        form.selected_key_index += 1;
-                                    }
+                                // Bound check against available keys
+                                '↓' | 'j' if form.selected_key_index + 1 < self.config.keys.len() => {
+                                    form.selected_key_index += 1;
                                 },
                                 's' | 'S' => {
                                     // Switch to manual key path input
@@ -140,46 +592,245 @@ impl AppState {
                                     // Switch back to key selector
                                     form.use_key_selector = true;
                                 }
-                                _ => form.key_path.push(c),
+                                _ => form.editor.insert(&mut form.key_path, c),
                             }
                         }
                     },
+                    5 => form.editor.insert(&mut form.password, c),
+                    6 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.autoconnect = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.autoconnect = false;
+                        }
+                    },
+                    7 => form.editor.insert(&mut form.autoconnect_command, c),
+                    8 => form.editor.insert(&mut form.tags, c),
+                    9 => match c {
+                        'd' | 'D' => form.backend = None,
+                        's' | 'S' => form.backend = Some(SshBackend::System),
+                        'e' | 'E' => form.backend = Some(SshBackend::Embedded),
+                        ' ' => {
+                            form.backend = match form.backend {
+                                None => Some(SshBackend::System),
+                                Some(SshBackend::System) => Some(SshBackend::Embedded),
+                                Some(SshBackend::Embedded) => None,
+                            };
+                        },
+                        _ => {}
+                    },
+                    10 if c.is_ascii_digit() => form.editor.insert(&mut form.keepalive_interval, c),
+                    11 => form.editor.insert(&mut form.keepalive_payload, c),
+                    12 => form.allowed_commands.push(c),
+                    14 => form.editor.insert(&mut form.remote_command, c),
+                    15 => form.startup_commands.push(c),
+                    16 => form.editor.insert(&mut form.depends_on, c),
+                    17 => form.editor.insert(&mut form.connection, c),
+                    18 => form.editor.insert(&mut form.ssh_options, c),
+                    19 if c.is_ascii_digit() => form.editor.insert(&mut form.bandwidth_limit, c),
+                    20 => form.notes.push(c),
+                    21 => form.editor.insert(&mut form.term, c),
+                    22 => form.editor.insert(&mut form.locale, c),
+                    23 => match c {
+                        'f' | 'F' => form.terminal_size = TerminalSize::Fit,
+                        'x' | 'X' => form.terminal_size = TerminalSize::Fixed,
+                        ' ' => {
+                            form.terminal_size = match form.terminal_size {
+                                TerminalSize::Fit => TerminalSize::Fixed,
+                                TerminalSize::Fixed => TerminalSize::Fit,
+                            };
+                        },
+                        _ => {}
+                    },
+                    13 => match c {
+                        'd' | 'D' => form.bell = None,
+                        ' ' => {
+                            form.bell = match form.bell {
+                                None => Some(BellMode::None),
+                                Some(mode) => next_bell_mode(mode),
+                            };
+                        },
+                        c => {
+                            if let Some(mode) = bell_mode_for_key(c) {
+                                form.bell = Some(mode);
+                            }
+                        },
+                    },
                     _ => {}
                 }
             },
-            _ => {}
-        }
-    }
-
-    fn handle_modal_backspace(&mut self) {
-        match &mut self.modal_state {
-            ModalState::AddKey(form) | ModalState::EditKey(_, form) => {
-                match form.field_focus {
-                    0 => { form.name.pop(); },
-                    1 => { form.path.pop(); },
-                    2 => {}, // Boolean field, no backspace
+            ModalState::TagFilter(form) => {
+                match c {
+                    ' ' => {
+                        if let Some((_, active)) = form.tags.get_mut(form.cursor) {
+                            *active = !*active;
+                        }
+                    },
+                    'c' | 'C' => {
+                        for (_, active) in &mut form.tags {
+                            *active = false;
+                        }
+                    },
                     _ => {}
                 }
             },
-            ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => {
-                match form.field_focus {
-                    0 => { form.name.pop(); },
-                    1 => { form.color.pop(); },
-                    _ => {}
+            ModalState::AuthorizedKeys(form) if c == ' ' => {
+                let cursor = form.cursor;
+                let row = if cursor < form.keys.len() {
+                    form.keys.get_mut(cursor)
+                } else {
+                    form.hosts.get_mut(cursor - form.keys.len())
+                };
+                if let Some((_, selected)) = row {
+                    *selected = !*selected;
                 }
             },
-            ModalState::AddHost(form) | ModalState::EditHost(_, form) => {
+            ModalState::Broadcast(form) if form.viewing.is_none() && form.cursor == 0 => {
+                form.command.push(c);
+            },
+            ModalState::Broadcast(form) => {
+                // r replays on the group as it is now, s on the same hosts as before
+                let index = form.viewing.or_else(|| self.broadcasts.entries.len().checked_sub(form.cursor));
+                if let (Some(index), 'r' | 's') = (index, c) {
+                    self.modal_state = ModalState::None;
+                    self.replay_broadcast(index, c == 's');
+                }
+            },
+            ModalState::Sessions(form) => {
+                let cursor = form.cursor;
+                if c == 'd' || c == 'D' || c == 'x' || c == 'X' {
+                    self.close_background_session(cursor);
+                    if self.background_sessions.is_empty() {
+                        self.modal_state = ModalState::None;
+                    } else {
+                        self.modal_state = ModalState::Sessions(SessionsForm {
+                            cursor: cursor.min(self.background_sessions.len() - 1),
+                        });
+                    }
+                }
+            },
+            ModalState::Orphans(form) if matches!(c, 'd' | 'D' | 'x' | 'X') => {
+                let form = form.clone();
+                self.modal_state = ModalState::None;
+                self.end_orphan(form);
+            },
+            ModalState::Trash(form) if matches!(c, 'd' | 'D' | 'x' | 'X') => {
+                let form = form.clone();
+                self.modal_state = ModalState::None;
+                self.delete_from_trash(form);
+            },
+            ModalState::Profiles(form) if form.cursor == form.profiles.len() => {
+                form.new_name.push(c);
+            },
+            ModalState::Forwards(form) => {
+                if form.cursor == form.forwards.len() {
+                    form.new_forward.push(c);
+                } else if c == 'd' || c == 'D' || c == 'x' || c == 'X' {
+                    form.forwards.remove(form.cursor);
+                }
+            },
+            ModalState::Settings(form) => {
                 match form.field_focus {
-                    0 => { form.name.pop(); },
-                    1 => { form.host.pop(); },
-                    2 => { form.port.pop(); },
-                    3 => { form.user.pop(); },
-                    4 => {
-                        // Only allow backspace in manual key path input mode
-                        if !form.use_key_selector {
-                            form.key_path.pop();
+                    0 => match c {
+                        'f' | 'F' => form.secrets_backend = SecretsBackend::File,
+                        'k' | 'K' => form.secrets_backend = SecretsBackend::Keyring,
+                        ' ' => {
+                            form.secrets_backend = match form.secrets_backend {
+                                SecretsBackend::File => SecretsBackend::Keyring,
+                                SecretsBackend::Keyring => SecretsBackend::File,
+                            };
+                        },
+                        _ => {}
+                    },
+                    1 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.desktop_notifications = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.desktop_notifications = false;
+                        }
+                    },
+                    2 => form.editor.insert(&mut form.webhook_url, c),
+                    3 if c.is_ascii_digit() || c == ':' => form.editor.insert(&mut form.quiet_hours_start, c),
+                    4 if c.is_ascii_digit() || c == ':' => form.editor.insert(&mut form.quiet_hours_end, c),
+                    5 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.auto_reconnect = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.auto_reconnect = false;
+                        }
+                    },
+                    7 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.paste_protection = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.paste_protection = false;
+                        }
+                    },
+                    8 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.local_echo = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.local_echo = false;
+                        }
+                    },
+                    9 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.connection_sharing = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.connection_sharing = false;
+                        }
+                    },
+                    10 => {
+                        if c == 'y' || c == 'Y' || c == 't' || c == 'T' {
+                            form.record_sessions = true;
+                        } else if c == 'n' || c == 'N' || c == 'f' || c == 'F' {
+                            form.record_sessions = false;
                         }
                     },
+                    11 => match c {
+                        ' ' => form.bell = next_bell_mode(form.bell).unwrap_or(BellMode::None),
+                        c => {
+                            if let Some(mode) = bell_mode_for_key(c) {
+                                form.bell = mode;
+                            }
+                        },
+                    },
+                    12 => match c {
+                        ' ' => form.host_sort = next_host_sort(form.host_sort).unwrap_or(HostSort::Manual),
+                        c => {
+                            if let Some(sort) = host_sort_for_key(c) {
+                                form.host_sort = sort;
+                            }
+                        },
+                    },
+                    13 if c.is_ascii_digit() => form.editor.insert(&mut form.repeat_filter_ms, c),
+                    14 if !c.is_whitespace() => form.sticky_prefix = c.to_string(),
+                    15 if c.is_ascii_digit() => form.editor.insert(&mut form.prompt_timeout_secs, c),
+                    17 => form.editor.insert(&mut form.dashboard_widgets, c),
+                    18 => form.editor.insert(&mut form.git_remote, c),
+                    19 => form.editor.insert(&mut form.inventory_sync_url, c),
+                    20 => form.editor.insert(&mut form.inventory_sync_token, c),
+                    21 if c.is_ascii_digit() => form.editor.insert(&mut form.lock_after_mins, c),
+                    22 => form.editor.insert(&mut form.sidebar_key, c),
+                    23 if c.is_ascii_digit() => form.editor.insert(&mut form.trash_days, c),
+                    16 => match c {
+                        ' ' => form.session_wrapper = next_session_wrapper(form.session_wrapper),
+                        'n' | 'N' => form.session_wrapper = SessionWrapper::None,
+                        't' | 'T' => form.session_wrapper = SessionWrapper::Tmux,
+                        'a' | 'A' => form.session_wrapper = SessionWrapper::Abduco,
+                        _ => {}
+                    },
+                    6 => match c {
+                        's' | 'S' => form.ssh_backend = SshBackend::System,
+                        'e' | 'E' => form.ssh_backend = SshBackend::Embedded,
+                        ' ' => {
+                            form.ssh_backend = match form.ssh_backend {
+                                SshBackend::System => SshBackend::Embedded,
+                                SshBackend::Embedded => SshBackend::System,
+                            };
+                        },
+                        _ => {}
+                    },
                     _ => {}
                 }
             },
@@ -187,29 +838,168 @@ impl AppState {
         }
     }
 
-    fn handle_modal_submit(&mut self) {
-        match self.modal_state.clone() {
-            ModalState::AddKey(form) => {
-                if form.name.trim().is_empty() {
-                    self.set_message("Key name cannot be empty".to_string(), MessageType::Error);
-                    return;
-                }
-                if form.path.trim().is_empty() {
-                    self.set_message("Key path cannot be empty".to_string(), MessageType::Error);
-                    return;
-                }
-
+    fn handle_modal_backspace(&mut self) {
+        if let Some((editor, text)) = self.focused_input() {
+            editor.backspace(text);
+            return;
+        }
+        match &mut self.modal_state {
+            ModalState::Facts(form) => {
+                form.query.pop();
+                form.cursor = 0;
+            },
+            ModalState::Palette(form) => {
+                form.query.pop();
+                form.cursor = 0;
+            },
+            ModalState::Broadcast(form) if form.viewing.is_none() && form.cursor == 0 => {
+                form.command.pop();
+            },
+            ModalState::AddGroup(form) | ModalState::EditGroup(_, form) if form.field_focus == 6 => {
+                form.notes.pop();
+            },
+            ModalState::AddHost(form) | ModalState::EditHost(_, form) => {
+                match form.field_focus {
+                    12 => { form.allowed_commands.pop(); },
+                    15 => { form.startup_commands.pop(); },
+                    20 => { form.notes.pop(); },
+                    _ => {}
+                }
+            },
+            ModalState::Forwards(form) if form.cursor == form.forwards.len() => {
+                form.new_forward.pop();
+            },
+            ModalState::Profiles(form) if form.cursor == form.profiles.len() => {
+                form.new_name.pop();
+            },
+            ModalState::Settings(form) if form.field_focus == 14 => form.sticky_prefix.clear(),
+            _ => {}
+        }
+    }
+
+    /// The focused one-line text field of a form, along with the form's
+    /// editor; None for choices, multi-line fields and list modals
+    fn focused_input(&mut self) -> Option<(&mut LineEditor, &mut String)> {
+        let text = match &mut self.modal_state {
+            ModalState::LoginPrompt(form) => return Some((&mut form.editor, &mut form.answer)),
+            ModalState::Import(form) if form.field_focus == 1 => return Some((&mut form.editor, &mut form.path)),
+            ModalState::Scp(form) => {
+                let text = match form.field_focus {
+                    0 => &mut form.local,
+                    _ => &mut form.remote,
+                };
+                return Some((&mut form.editor, text));
+            },
+            ModalState::AddKey(form) | ModalState::EditKey(_, form) => {
+                let text = match form.field_focus {
+                    0 => &mut form.name,
+                    1 => &mut form.path,
+                    _ => return None,
+                };
+                (&mut form.editor, text)
+            },
+            ModalState::GenerateKey(form) => {
+                let text = match form.field_focus {
+                    1 => &mut form.path,
+                    2 => &mut form.comment,
+                    3 => &mut form.passphrase,
+                    _ => return None,
+                };
+                (&mut form.editor, text)
+            },
+            ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => {
+                let text = match form.field_focus {
+                    0 => &mut form.name,
+                    2 => &mut form.user,
+                    3 => &mut form.port,
+                    4 => &mut form.key_path,
+                    5 => &mut form.options,
+                    _ => return None,
+                };
+                (&mut form.editor, text)
+            },
+            ModalState::AddHost(form) | ModalState::EditHost(_, form) => {
+                let text = match form.field_focus {
+                    0 => &mut form.name,
+                    1 => &mut form.host,
+                    2 => &mut form.port,
+                    3 => &mut form.user,
+                    // Only in manual key path input mode
+                    4 if !form.use_key_selector => &mut form.key_path,
+                    5 => &mut form.password,
+                    7 => &mut form.autoconnect_command,
+                    8 => &mut form.tags,
+                    10 => &mut form.keepalive_interval,
+                    11 => &mut form.keepalive_payload,
+                    14 => &mut form.remote_command,
+                    16 => &mut form.depends_on,
+                    17 => &mut form.connection,
+                    18 => &mut form.ssh_options,
+                    19 => &mut form.bandwidth_limit,
+                    21 => &mut form.term,
+                    22 => &mut form.locale,
+                    _ => return None,
+                };
+                (&mut form.editor, text)
+            },
+            ModalState::Settings(form) => {
+                let text = match form.field_focus {
+                    2 => &mut form.webhook_url,
+                    3 => &mut form.quiet_hours_start,
+                    4 => &mut form.quiet_hours_end,
+                    13 => &mut form.repeat_filter_ms,
+                    15 => &mut form.prompt_timeout_secs,
+                    17 => &mut form.dashboard_widgets,
+                    18 => &mut form.git_remote,
+                    19 => &mut form.inventory_sync_url,
+                    20 => &mut form.inventory_sync_token,
+                    21 => &mut form.lock_after_mins,
+                    22 => &mut form.sidebar_key,
+                    23 => &mut form.trash_days,
+                    _ => return None,
+                };
+                (&mut form.editor, text)
+            },
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    fn handle_modal_submit(&mut self) {
+        match self.modal_state.clone() {
+            ModalState::AddKey(form) => {
+                if form.name.trim().is_empty() {
+                    self.set_message("Key name cannot be empty".to_string(), MessageType::Error);
+                    return;
+                }
+                if form.path.trim().is_empty() {
+                    self.set_message("Key path cannot be empty".to_string(), MessageType::Error);
+                    return;
+                }
+                let details = match key_check::inspect(form.path.trim()) {
+                    Ok(details) => details,
+                    Err(e) => {
+                        self.set_message(e, MessageType::Error);
+                        return;
+                    },
+                };
+
                 let new_key = SshKey {
+                    id: Uuid::new_v4(),
                     name: form.name.trim().to_string(),
                     path: form.path.trim().to_string(),
                     is_default: form.is_default,
                 };
 
+                self.forget_key_details(&new_key.path);
+                let before = self.snapshot();
+                let description = format!("Added SSH key '{}'", new_key.name);
                 self.config.add_key(new_key);
+                self.record_change(before, description);
                 self.selected_key = self.config.keys.len() - 1;
                 let _ = self.config.save();
                 
-                self.set_message("SSH key added successfully!".to_string(), MessageType::Success);
+                self.report_key_saved("added", form.path.trim(), &details);
                 self.modal_state = ModalState::None;
             },
             ModalState::EditKey(index, form) => {
@@ -222,32 +1012,59 @@ impl AppState {
                         self.set_message("Key path cannot be empty".to_string(), MessageType::Error);
                         return;
                     }
+                    let details = match key_check::inspect(form.path.trim()) {
+                        Ok(details) => details,
+                        Err(e) => {
+                            self.set_message(e, MessageType::Error);
+                            return;
+                        },
+                    };
 
+                    let before = self.snapshot();
                     self.config.keys[index] = SshKey {
+                        id: self.config.keys[index].id,
                         name: form.name.trim().to_string(),
                         path: form.path.trim().to_string(),
                         is_default: form.is_default,
                     };
+                    self.forget_key_details(form.path.trim());
+                    self.record_change(before, format!("Edited SSH key '{}'", form.name.trim()));
                     
                     let _ = self.config.save();
-                    self.set_message("SSH key updated successfully!".to_string(), MessageType::Success);
+                    self.report_key_saved("updated", form.path.trim(), &details);
                 }
                 self.modal_state = ModalState::None;
             },
+            ModalState::GenerateKey(form) => {
+                self.start_key_generation(form);
+            },
             ModalState::AddGroup(form) => {
-                if form.name.trim().is_empty() {
+                if config::group_name(&form.name).is_empty() {
                     self.set_message("Group name cannot be empty".to_string(), MessageType::Error);
                     return;
                 }
+                if self.config.group_name_taken(&config::group_name(&form.name)) {
+                    self.set_message(format!("A group named '{}' already exists", config::group_name(&form.name)), MessageType::Error);
+                    return;
+                }
 
                 let new_group = Group {
-                    name: form.name.trim().to_string(),
+                    id: Uuid::new_v4(),
+                    name: config::group_name(&form.name),
                     color: if form.color.trim().is_empty() { "green".to_string() } else { form.color.trim().to_string() },
                     hosts: Vec::new(),
+                    defaults: form.defaults(),
+                    notes: form.notes.trim().to_string(),
+                    ec2: None,
                 };
 
+                let name = new_group.name.clone();
+                let before = self.snapshot();
                 self.config.add_group(new_group);
-                self.selected_group = self.config.groups.len() - 1;
+                self.config.ensure_parent_groups();
+                self.record_change(before, format!("Added group '{}'", name));
+                self.selected_group = self.config.groups.iter().position(|group| group.name == name).unwrap_or(0);
+                self.virtual_group = None;
                 self.selected_host = 0;
                 let _ = self.config.save();
                 
@@ -256,15 +1073,44 @@ impl AppState {
             },
             ModalState::EditGroup(index, form) => {
                 if index < self.config.groups.len() && index > 0 {
-                    if form.name.trim().is_empty() {
+                    if config::group_name(&form.name).is_empty() {
                         self.set_message("Group name cannot be empty".to_string(), MessageType::Error);
                         return;
                     }
+                    let name = config::group_name(&form.name);
+                    if name != self.config.groups[index].name && self.config.group_name_taken(&name) {
+                        self.set_message(format!("A group named '{}' already exists", name), MessageType::Error);
+                        return;
+                    }
+
+                    let old_group = Group { hosts: Vec::new(), ..self.config.groups[index].clone() };
+                    let updated_group = Group {
+                        id: old_group.id,
+                        name: config::group_name(&form.name),
+                        color: if form.color.trim().is_empty() { "green".to_string() } else { form.color.trim().to_string() },
+                        hosts: Vec::new(),
+                        defaults: form.defaults(),
+                        notes: form.notes.trim().to_string(),
+                        ec2: old_group.ec2.clone(),
+                    };
+                    let changes = config_diff::changes(&old_group, &updated_group);
+                    let subject = format!("group '{}'", old_group.name);
+                    if !self.edit_reviewed(subject.clone(), &changes) {
+                        return;
+                    }
 
-                    self.config.groups[index].name = form.name.trim().to_string();
-                    self.config.groups[index].color = if form.color.trim().is_empty() { "green".to_string() } else { form.color.trim().to_string() };
+                    // Groups nested in it move along with a new name
+                    let before = self.snapshot();
+                    self.rename_group(index, &updated_group.name);
+                    self.config.groups[index].color = updated_group.color;
+                    self.config.groups[index].defaults = updated_group.defaults;
+                    self.config.groups[index].notes = updated_group.notes;
+                    self.config.ensure_parent_groups();
+                    self.record_change(before, format!("Edited {}", subject));
+                    self.selected_group = self.config.groups.iter().position(|group| group.name == updated_group.name).unwrap_or(0);
                     
                     let _ = self.config.save();
+                    config_diff::audit(&format!("Edited {}", subject), &changes);
                     self.set_message("Group updated successfully!".to_string(), MessageType::Success);
                 }
                 self.modal_state = ModalState::None;
@@ -274,16 +1120,24 @@ impl AppState {
                     self.set_message("Host name cannot be empty".to_string(), MessageType::Error);
                     return;
                 }
-                if form.host.trim().is_empty() {
+                if let Some(group) = self.config.groups.get(self.selected_group) {
+                    if self.config.host_name_taken(&group.name, form.name.trim()) {
+                        self.set_message(format!("Group '{}' already has a host named '{}'", group.name, form.name.trim()), MessageType::Error);
+                        return;
+                    }
+                }
+                let kind = form.connection_kind();
+                if form.host.trim().is_empty() && !matches!(kind, ConnectionKind::Custom(_)) {
                     self.set_message("Host address cannot be empty".to_string(), MessageType::Error);
                     return;
                 }
-                if form.user.trim().is_empty() {
-                    self.set_message("Username cannot be empty".to_string(), MessageType::Error);
+                if form.user.trim().is_empty() && form.group_defaults.user.is_none() && kind.uses_ssh() {
+                    self.set_message("Username cannot be empty (the group has no default user)".to_string(), MessageType::Error);
                     return;
                 }
 
-                let port = form.port.parse::<u16>().unwrap_or(22);
+                // Empty port inherits the group's
+                let port = form.port.parse::<u16>().unwrap_or(0);
                 let key_path = if form.use_key_selector {
                     // Use selected key from dropdown
                     if form.selected_key_index < self.config.keys.len() {
@@ -296,21 +1150,57 @@ impl AppState {
                     if form.key_path.trim().is_empty() { None } else { Some(form.key_path.trim().to_string()) }
                 };
 
+                let group_name = self.config.groups.get(self.selected_group).map(|group| group.name.clone()).unwrap_or_default();
+                let depends_on = match form.dependency_list(&self.config, &group_name) {
+                    Ok(depends_on) => depends_on,
+                    Err(name) => {
+                        self.set_message(format!("No host named '{}' to depend on", name), MessageType::Error);
+                        return;
+                    }
+                };
+
                 let new_host = Host {
+                    id: Uuid::new_v4(),
                     name: form.name.trim().to_string(),
                     host: form.host.trim().to_string(),
                     port,
                     user: form.user.trim().to_string(),
                     key_path,
+                    autoconnect: form.autoconnect_rule(),
+                    tags: form.tag_list(),
+                    backend: form.backend,
+                    forwards: form.forwards.clone(),
+                    keepalive: form.keepalive(),
+                    allowed_commands: form.command_list(),
+                    bell: form.bell,
+                    remote_command: form.remote_command(),
+                    startup_commands: form.startup_command_list(),
+                    probes: form.probes.clone(),
+                    depends_on,
+                    kind: form.connection_kind(),
+                    ssh_options: form.ssh_option_list(),
+                    bandwidth_limit: form.bandwidth_limit(),
+                    notes: form.notes.trim().to_string(),
+                    favorite: false,
+                    ec2_instance: None,
+                    term: form.term(),
+                    locale: form.locale(),
+                    terminal_size: form.terminal_size,
                 };
+                let new_host_account = secrets::host_account(&new_host);
 
                 if self.selected_group > 0 && self.selected_group < self.config.groups.len() {
                     let group_name = self.config.groups[self.selected_group].name.clone();
+                    // Undoing the add takes its password out again
+                    let before = self.snapshot_with_passwords(std::slice::from_ref(&new_host_account));
+                    let description = format!("Added host '{}'", new_host.name);
                     if let Ok(()) = self.config.add_host_to_group(&group_name, new_host) {
-                        let hosts = self.config.get_hosts_for_group(self.selected_group);
-                        self.selected_host = hosts.len() - 1;
+                        self.record_change(before, description);
+                        let hosts = self.visible_hosts();
+                        self.selected_host = hosts.len().saturating_sub(1);
                         let _ = self.config.save();
                         self.set_message("Host added successfully!".to_string(), MessageType::Success);
+                        self.store_host_password(None, &new_host_account, &form.password);
                     } else {
                         self.set_message("Failed to add host to group".to_string(), MessageType::Error);
                     }
@@ -318,22 +1208,24 @@ impl AppState {
                 self.modal_state = ModalState::None;
             },
             ModalState::EditHost(index, form) => {
-                let hosts = self.config.get_hosts_for_group(self.selected_group);
-                if index < hosts.len() && self.selected_group > 0 {
+                let hosts = self.visible_hosts();
+                if index < hosts.len() && self.hosts_editable() {
                     if form.name.trim().is_empty() {
                         self.set_message("Host name cannot be empty".to_string(), MessageType::Error);
                         return;
                     }
-                    if form.host.trim().is_empty() {
+                    let kind = form.connection_kind();
+                    if form.host.trim().is_empty() && !matches!(kind, ConnectionKind::Custom(_)) {
                         self.set_message("Host address cannot be empty".to_string(), MessageType::Error);
                         return;
                     }
-                    if form.user.trim().is_empty() {
-                        self.set_message("Username cannot be empty".to_string(), MessageType::Error);
+                    if form.user.trim().is_empty() && form.group_defaults.user.is_none() && kind.uses_ssh() {
+                        self.set_message("Username cannot be empty (the group has no default user)".to_string(), MessageType::Error);
                         return;
                     }
 
-                    let port = form.port.parse::<u16>().unwrap_or(22);
+                    // Empty port inherits the group's
+                    let port = form.port.parse::<u16>().unwrap_or(0);
                     let key_path = if form.use_key_selector {
                         // Use selected key from dropdown
                         if form.selected_key_index < self.config.keys.len() {
@@ -346,92 +1238,626 @@ impl AppState {
                         if form.key_path.trim().is_empty() { None } else { Some(form.key_path.trim().to_string()) }
                     };
 
+                    let group_name = self.config.group_of(&hosts[index]).map(|group| group.name.clone()).unwrap_or_default();
+                    let depends_on = match form.dependency_list(&self.config, &group_name) {
+                        Ok(depends_on) => depends_on,
+                        Err(name) => {
+                            self.set_message(format!("No host named '{}' to depend on", name), MessageType::Error);
+                            return;
+                        }
+                    };
+
                     let updated_host = Host {
+                        id: hosts[index].id,
                         name: form.name.trim().to_string(),
                         host: form.host.trim().to_string(),
                         port,
                         user: form.user.trim().to_string(),
                         key_path,
+                        autoconnect: form.autoconnect_rule(),
+                        tags: form.tag_list(),
+                        backend: form.backend,
+                        forwards: form.forwards.clone(),
+                        keepalive: form.keepalive(),
+                        allowed_commands: form.command_list(),
+                        bell: form.bell,
+                        remote_command: form.remote_command(),
+                        startup_commands: form.startup_command_list(),
+                        probes: form.probes.clone(),
+                        depends_on,
+                        kind: form.connection_kind(),
+                        ssh_options: form.ssh_option_list(),
+                        bandwidth_limit: form.bandwidth_limit(),
+                        notes: form.notes.trim().to_string(),
+                        favorite: hosts[index].favorite,
+                        ec2_instance: hosts[index].ec2_instance.clone(),
+                        term: form.term(),
+                        locale: form.locale(),
+                        terminal_size: form.terminal_size,
                     };
+                    let old_host_account = secrets::host_account(&hosts[index]);
+                    let updated_host_account = secrets::host_account(&updated_host);
 
-                    let group_name = self.config.groups[self.selected_group].name.clone();
+                    // The host may belong to a group nested in the selected one
+                    let group_name = self.config.group_of(&hosts[index])
+                        .map_or_else(|| self.config.groups[self.selected_group].name.clone(), |group| group.name.clone());
                     let old_host_name = hosts[index].name.clone();
+                    if updated_host.name != old_host_name && self.config.host_name_taken(&group_name, &updated_host.name) {
+                        self.set_message(format!("Group '{}' already has a host named '{}'", group_name, updated_host.name), MessageType::Error);
+                        return;
+                    }
+
+                    let mut changes = config_diff::changes(&hosts[index], &updated_host);
+                    let old_password = secrets::get(self.config.settings.secrets_backend, &old_host_account)
+                        .ok()
+                        .flatten()
+                        .unwrap_or_default();
+                    if old_password != form.password {
+                        // Never shown or logged, only whether one is set
+                        let shown = |password: &str| if password.is_empty() { "(none)" } else { "(set)" }.to_string();
+                        changes.push(FieldChange { field: "password".to_string(), old: shown(&old_password), new: shown(&form.password) });
+                    }
+                    let subject = format!("host '{}' in group '{}'", old_host_name, group_name);
+                    if !self.edit_reviewed(subject.clone(), &changes) {
+                        return;
+                    }
                     
-                    // Remove old host and add updated one
-                    if let Ok(()) = self.config.remove_host(&group_name, &old_host_name) {
-                        if let Ok(()) = self.config.add_host_to_group(&group_name, updated_host) {
-                            let _ = self.config.save();
-                            self.set_message("Host updated successfully!".to_string(), MessageType::Success);
-                        } else {
-                            self.set_message("Failed to update host".to_string(), MessageType::Error);
-                        }
+                    let before = self.snapshot_with_passwords(&[old_host_account.clone(), updated_host_account.clone()]);
+                    if let Ok(()) = self.config.replace_host(updated_host) {
+                        self.record_change(before, format!("Edited {}", subject));
+                        let _ = self.config.save();
+                        config_diff::audit(&format!("Edited {}", subject), &changes);
+                        self.set_message("Host updated successfully!".to_string(), MessageType::Success);
+                        self.store_host_password(Some(old_host_account), &updated_host_account, &form.password);
                     } else {
                         self.set_message("Failed to update host".to_string(), MessageType::Error);
                     }
                 }
                 self.modal_state = ModalState::None;
             },
-            ModalState::Confirm(_, action) => {
-                match action {
-                    ConfirmAction::DeleteKey(index) => {
-                        if index < self.config.keys.len() {
-                            let key_name = self.config.keys[index].name.clone();
-                            self.config.remove_key(&key_name);
-                            if self.selected_key >= self.config.keys.len() && self.selected_key > 0 {
-                                self.selected_key = self.config.keys.len() - 1;
-                            }
-                            let _ = self.config.save();
-                            self.set_message(format!("SSH key '{}' deleted", key_name), MessageType::Success);
+            ModalState::Confirm(form) => {
+                self.modal_state = ModalState::None;
+                if form.confirm_selected {
+                    match form.action {
+                        ConfirmAction::TakeSharedInventory => self.accept_shared_inventory(),
+                        ConfirmAction::EmptyEc2Group => self.accept_empty_ec2_refresh(),
+                        action => self.perform_delete(action),
+                    }
+                }
+            },
+            ModalState::ReviewChanges(form) => {
+                self.modal_state = *form.edit;
+                if form.confirm_selected {
+                    self.approved_edit = Some(self.modal_state.clone());
+                    self.handle_modal_submit();
+                }
+            },
+            ModalState::HostKey(form) => {
+                self.modal_state = ModalState::None;
+                if form.trust_selected {
+                    self.trust_host_key(*form.prompt);
+                } else {
+                    self.pending_startup_command = None;
+                    self.set_message(format!("Host key of {} not trusted; connection cancelled", form.prompt.host_name), MessageType::Info);
+                }
+            },
+            ModalState::TagFilter(form) => {
+                self.tag_filter = form.tags.into_iter()
+                    .filter(|(_, active)| *active)
+                    .map(|(tag, _)| tag)
+                    .collect();
+                self.selected_host = 0;
+                if self.tag_filter.is_empty() {
+                    self.set_message("Tag filter cleared".to_string(), MessageType::Info);
+                } else {
+                    self.set_message(format!("Filtering hosts by tags: {}", self.tag_filter.join(", ")), MessageType::Info);
+                }
+                self.modal_state = ModalState::None;
+            },
+            ModalState::Settings(form) => {
+                let quiet_hours = match (form.quiet_hours_start.trim(), form.quiet_hours_end.trim()) {
+                    ("", "") => None,
+                    (start, end) => {
+                        if QuietHours::parse_time(start).is_none() || QuietHours::parse_time(end).is_none() {
+                            self.set_message("Quiet hours must be given as HH:MM".to_string(), MessageType::Error);
+                            return;
+                        }
+                        Some(QuietHours { start: start.to_string(), end: end.to_string() })
+                    }
+                };
+                let repeat_filter_ms = match form.repeat_filter_ms.trim() {
+                    "" => 0,
+                    value => match value.parse::<u64>() {
+                        Ok(ms) if ms <= 2000 => ms,
+                        _ => {
+                            self.set_message("Key repeat filter must be 0 to 2000 ms".to_string(), MessageType::Error);
+                            return;
                         }
                     },
-                    ConfirmAction::DeleteGroup(index) => {
-                        if index < self.config.groups.len() && index > 0 {
-                            let group_name = self.config.groups[index].name.clone();
-                            self.config.remove_group(&group_name);
-                            if self.selected_group >= self.config.groups.len() && self.selected_group > 0 {
-                                self.selected_group = self.config.groups.len() - 1;
-                            }
-                            self.selected_host = 0;
-                            let _ = self.config.save();
-                            self.set_message(format!("Group '{}' deleted", group_name), MessageType::Success);
+                };
+                let prompt_timeout_secs = match form.prompt_timeout_secs.trim().parse::<u64>() {
+                    Ok(secs) if (1..=600).contains(&secs) => secs,
+                    _ => {
+                        self.set_message("Prompt timeout must be 1 to 600 seconds".to_string(), MessageType::Error);
+                        return;
+                    }
+                };
+                let lock_after_mins = match form.lock_after_mins.trim() {
+                    "" => 0,
+                    value => match value.parse::<u64>() {
+                        Ok(minutes) if minutes <= 1440 => minutes,
+                        _ => {
+                            self.set_message("Lock after must be 0 to 1440 minutes".to_string(), MessageType::Error);
+                            return;
                         }
                     },
-                    ConfirmAction::DeleteHost(index) => {
-                        let hosts = self.config.get_hosts_for_group(self.selected_group);
-                        if index < hosts.len() && self.selected_group > 0 {
-                            let host_name = hosts[index].name.clone();
-                            let group_name = self.config.groups[self.selected_group].name.clone();
-                            if let Ok(()) = self.config.remove_host(&group_name, &host_name) {
-                                if self.selected_host >= hosts.len() - 1 && self.selected_host > 0 {
-                                    self.selected_host = hosts.len() - 2;
-                                }
-                                let _ = self.config.save();
-                                self.set_message(format!("Host '{}' deleted", host_name), MessageType::Success);
-                            }
+                };
+                let trash_days = match form.trash_days.trim() {
+                    "" => 0,
+                    value => match value.parse::<u32>() {
+                        Ok(days) if days <= 3650 => days,
+                        _ => {
+                            self.set_message("Trash days must be 0 to 3650".to_string(), MessageType::Error);
+                            return;
                         }
                     },
+                };
+                let sidebar_key = form.sidebar_key.trim().to_string();
+                if sidebar_focus::parse_key(&sidebar_key).is_none() {
+                    self.set_message("Sidebar key must be F1-F12 or Ctrl/Alt with a key, e.g. Ctrl+B".to_string(), MessageType::Error);
+                    return;
+                }
+                let dashboard_widgets: Vec<String> = form.dashboard_widgets.split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                if let Some(unknown) = dashboard_widgets.iter().find(|name| !dashboard::is_widget(self, name)) {
+                    self.set_message(
+                        format!("Unknown dashboard widget {}; choose from {} or a command widget", unknown, DASHBOARD_WIDGETS.join(", ")),
+                        MessageType::Error,
+                    );
+                    return;
+                }
+                let old_backend = self.config.settings.secrets_backend;
+                if form.secrets_backend != old_backend {
+                    match secrets::migrate(&self.config, old_backend, form.secrets_backend) {
+                        Ok(moved) => {
+                            self.config.settings.secrets_backend = form.secrets_backend;
+                            self.set_message(format!("Settings saved ({} secrets moved)", moved), MessageType::Success);
+                        },
+                        Err(e) => {
+                            self.set_message(format!("Failed to switch secrets backend: {}", e), MessageType::Error);
+                            return;
+                        }
+                    }
+                } else {
+                    self.set_message("Settings saved".to_string(), MessageType::Success);
+                }
+                let backend = self.config.settings.secrets_backend;
+                let token = form.inventory_sync_token.trim();
+                let stored_token = secrets::get(backend, secrets::INVENTORY_SYNC_ACCOUNT).ok().flatten().unwrap_or_default();
+                if token != stored_token {
+                    let result = if token.is_empty() {
+                        secrets::delete(backend, secrets::INVENTORY_SYNC_ACCOUNT)
+                    } else {
+                        secrets::set(backend, secrets::INVENTORY_SYNC_ACCOUNT, token)
+                    };
+                    if let Err(e) = result {
+                        self.set_message(format!("Failed to save the inventory token: {}", e), MessageType::Error);
+                        return;
+                    }
+                }
+
+                let notifications = &mut self.config.settings.notifications;
+                notifications.desktop = form.desktop_notifications;
+                notifications.webhook_url = Some(form.webhook_url.trim().to_string()).filter(|url| !url.is_empty());
+                notifications.quiet_hours = quiet_hours;
+                self.config.settings.auto_reconnect = form.auto_reconnect;
+                self.config.settings.lock_after_mins = lock_after_mins;
+                self.config.settings.sidebar_key = sidebar_key;
+                self.config.settings.trash_days = trash_days;
+                self.config.settings.ssh_backend = form.ssh_backend;
+                self.config.settings.paste_protection = form.paste_protection;
+                self.config.settings.local_echo = form.local_echo;
+                self.config.settings.connection_sharing = form.connection_sharing;
+                self.config.settings.record_sessions = form.record_sessions;
+                self.config.settings.bell = form.bell;
+                self.config.settings.host_sort = form.host_sort;
+                self.config.settings.session_wrapper = form.session_wrapper;
+                self.config.settings.dashboard_widgets = if dashboard_widgets.is_empty() {
+                    default_dashboard_widgets()
+                } else {
+                    dashboard_widgets
+                };
+                let accessibility = &mut self.config.settings.accessibility;
+                accessibility.repeat_filter_ms = repeat_filter_ms;
+                accessibility.sticky_prefix = form.sticky_prefix.chars().next();
+                accessibility.prompt_timeout_secs = prompt_timeout_secs;
+                self.config.settings.git_remote = Some(form.git_remote.trim().to_string()).filter(|remote| !remote.is_empty());
+                self.config.settings.inventory_sync_url = Some(form.inventory_sync_url.trim().to_string()).filter(|url| !url.is_empty());
+                self.selected_host = self.selected_host.min(self.visible_hosts().len().saturating_sub(1));
+                let _ = self.config.save();
+                self.modal_state = ModalState::None;
+            },
+            ModalState::Sessions(form) => {
+                self.modal_state = ModalState::None;
+                self.resume_session(form.cursor);
+            },
+            ModalState::ForwardConflict(form) => {
+                self.modal_state = ModalState::None;
+                self.resolve_forward_conflict(form);
+            },
+            ModalState::ConfigRecovery(form) => self.recover_config(form),
+            ModalState::Profiles(form) => self.choose_profile(form),
+            ModalState::Orphans(form) => {
+                self.modal_state = ModalState::None;
+                self.pending_reattach = form.sessions.get(form.cursor).cloned();
+            },
+            ModalState::Trash(form) => {
+                self.modal_state = ModalState::None;
+                self.restore_from_trash(form);
+            },
+            ModalState::Import(form) => {
+                self.modal_state = ModalState::None;
+                self.preview_import(form);
+            },
+            ModalState::ImportPreview(form) => {
+                self.modal_state = ModalState::None;
+                if form.confirm_selected {
+                    self.apply_import();
+                } else {
+                    self.back_to_import(form);
+                }
+            },
+            ModalState::SharedInventory(form) => {
+                self.modal_state = ModalState::None;
+                if form.confirm_selected {
+                    self.accept_shared_inventory();
+                } else {
+                    self.decline_shared_inventory();
+                }
+            },
+            ModalState::AuthorizedKeys(form) => {
+                self.modal_state = ModalState::None;
+                self.export_authorized_keys(form);
+            },
+            ModalState::Identities(form) => {
+                self.modal_state = ModalState::None;
+                self.retry_with_identity(form);
+            },
+            ModalState::Commands(form) => {
+                self.modal_state = ModalState::None;
+                self.send_allowed_command(form);
+            },
+            ModalState::Recordings(form) => {
+                self.modal_state = ModalState::None;
+                self.play_recording(form);
+            },
+            ModalState::Themes(form) => {
+                self.modal_state = ModalState::None;
+                self.choose_theme(form);
+            },
+            ModalState::MoveHost(form) => {
+                self.modal_state = ModalState::None;
+                self.move_host(form);
+            },
+            ModalState::Scp(form) => {
+                self.modal_state = ModalState::None;
+                self.start_scp(form);
+            },
+            ModalState::LoginPrompt(form) => self.answer_login_prompt(form),
+            ModalState::Facts(form) => {
+                self.modal_state = ModalState::None;
+                self.broadcast_to_matches(form);
+            },
+            ModalState::Palette(form) => {
+                self.choose_palette_action(form);
+            },
+            ModalState::GroupConnect => {
+                self.modal_state = ModalState::None;
+            },
+            ModalState::Broadcast(mut form) => {
+                // Enter runs a typed command, opens a past one, or goes back to the list
+                if form.viewing.is_some() {
+                    form.viewing = None;
+                    self.modal_state = ModalState::Broadcast(form);
+                } else if form.cursor == 0 {
+                    self.modal_state = ModalState::None;
+                    self.start_broadcast(&form.command, form.targets);
+                } else {
+                    form.viewing = self.broadcasts.entries.len().checked_sub(form.cursor);
+                    form.host = 0;
+                    form.scroll = 0;
+                    self.modal_state = ModalState::Broadcast(form);
+                }
+            },
+            ModalState::PastePreview(text) => {
+                self.modal_state = ModalState::None;
+                let data = self.paste_bytes(&text);
+                let client = self.ssh_client.clone();
+                tokio::spawn(async move {
+                    let _ = client.send_input(&data).await;
+                });
+            },
+            ModalState::RecoverInput(line) => {
+                // Typed without Enter, so it can be checked before running
+                self.modal_state = ModalState::None;
+                self.track_input(line.as_bytes());
+                let client = self.ssh_client.clone();
+                tokio::spawn(async move {
+                    let _ = client.send_input(line.as_bytes()).await;
+                });
+            },
+            ModalState::Forwards(mut form) => {
+                // Enter on a typed spec adds it; Enter otherwise applies the list
+                if !form.new_forward.trim().is_empty() {
+                    match PortForward::parse(&form.new_forward) {
+                        Some(forward) if form.forwards.iter().any(|f| f.kind == forward.kind && f.bind_port == forward.bind_port) => {
+                            self.set_message(format!("Port {} is already forwarded", forward.bind_port), MessageType::Error);
+                        },
+                        Some(forward) => {
+                            form.forwards.push(forward);
+                            form.new_forward.clear();
+                            form.cursor = form.forwards.len();
+                            self.modal_state = ModalState::Forwards(form);
+                        },
+                        None => {
+                            self.set_message("Forwards look like -L 8080:db:5432, -R 9000:localhost:3000 or -D 1080".to_string(), MessageType::Error);
+                        }
+                    }
+                    return;
                 }
                 self.modal_state = ModalState::None;
+                self.apply_forwards(form);
             },
             _ => {}
         }
     }
+
+    /// Say what kind of key was saved, or that ssh will refuse it until
+    /// its permissions are tightened
+    fn report_key_saved(&mut self, action: &str, path: &str, details: &KeyDetails) {
+        if let Some(mode) = details.loose_mode {
+            let message = format!("SSH key {}, but ssh refuses it while others can read it (mode {:o}): run chmod 600 {}", action, mode, path);
+            self.set_message(message, MessageType::Error);
+            return;
+        }
+        let kind = details.key_type.as_deref().unwrap_or("key of unknown type");
+        let encrypted = if details.encrypted { ", passphrase protected" } else { "" };
+        self.set_message(format!("SSH key {}: {}{}", action, kind, encrypted), MessageType::Success);
+    }
+
+    /// Save or clear a host's password in the configured secrets backend,
+    /// dropping the entry stored under its previous account if that changed
+    fn store_host_password(&mut self, old_account: Option<String>, account: &str, password: &str) {
+        let backend = self.config.settings.secrets_backend;
+        if let Some(old_account) = old_account.filter(|old| old != account) {
+            let _ = secrets::delete(backend, &old_account);
+        }
+
+        let result = if password.is_empty() {
+            secrets::delete(backend, account)
+        } else {
+            secrets::set(backend, account, password)
+        };
+        if let Err(e) = result {
+            self.set_message(format!("Failed to save password: {}", e), MessageType::Error);
+        }
+    }
+}
+
+/// Bell mode after `mode` when cycling with Space; None after the last one
+fn next_bell_mode(mode: BellMode) -> Option<BellMode> {
+    match mode {
+        BellMode::None => Some(BellMode::Visual),
+        BellMode::Visual => Some(BellMode::Notification),
+        BellMode::Notification => Some(BellMode::Sound),
+        BellMode::Sound => None,
+    }
+}
+
+fn bell_mode_for_key(c: char) -> Option<BellMode> {
+    match c {
+        'n' | 'N' => Some(BellMode::None),
+        'v' | 'V' => Some(BellMode::Visual),
+        'o' | 'O' => Some(BellMode::Notification),
+        's' | 'S' => Some(BellMode::Sound),
+        _ => None,
+    }
+}
+
+fn bell_mode_text(mode: BellMode) -> &'static str {
+    match mode {
+        BellMode::None => "Ignore",
+        BellMode::Visual => "Flash the terminal panel",
+        BellMode::Notification => "Send a notification",
+        BellMode::Sound => "Ring this terminal's bell",
+    }
+}
+
+/// Host order after `sort` when cycling with Space; None after the last one
+fn next_host_sort(sort: HostSort) -> Option<HostSort> {
+    match sort {
+        HostSort::Manual => Some(HostSort::Alphabetical),
+        HostSort::Alphabetical => Some(HostSort::Recent),
+        HostSort::Recent => Some(HostSort::Frequent),
+        HostSort::Frequent => Some(HostSort::Frecency),
+        HostSort::Frecency => None,
+    }
+}
+
+fn host_sort_for_key(c: char) -> Option<HostSort> {
+    match c {
+        'm' | 'M' => Some(HostSort::Manual),
+        'a' | 'A' => Some(HostSort::Alphabetical),
+        'r' | 'R' => Some(HostSort::Recent),
+        'f' | 'F' => Some(HostSort::Frequent),
+        'd' | 'D' => Some(HostSort::Frecency),
+        _ => None,
+    }
+}
+
+fn next_session_wrapper(wrapper: SessionWrapper) -> SessionWrapper {
+    match wrapper {
+        SessionWrapper::None => SessionWrapper::Tmux,
+        SessionWrapper::Tmux => SessionWrapper::Abduco,
+        SessionWrapper::Abduco => SessionWrapper::None,
+    }
+}
+
+fn host_sort_text(sort: HostSort) -> &'static str {
+    match sort {
+        HostSort::Manual => "Manual (Ctrl+Up/Down to move)",
+        HostSort::Alphabetical => "Alphabetical",
+        HostSort::Recent => "Most recently used first",
+        HostSort::Frequent => "Most frequently used first",
+        HostSort::Frecency => "Daily hosts first (frequent and recent)",
+    }
 }
 
 pub fn render_modal(frame: &mut Frame, app: &AppState) {
-    match &app.modal_state {
-        ModalState::AddKey(form) => render_key_modal(frame, "Add SSH Key", form, true),
-        ModalState::EditKey(_, form) => render_key_modal(frame, "Edit SSH Key", form, false),
-        ModalState::AddGroup(form) => render_group_modal(frame, "Add Group", form, true),
-        ModalState::EditGroup(_, form) => render_group_modal(frame, "Edit Group", form, false),
-        ModalState::AddHost(form) => render_host_modal(frame, "Add Host", form, &app.config.keys, true),
-        ModalState::EditHost(_, form) => render_host_modal(frame, "Edit Host", form, &app.config.keys, false),
-        ModalState::Confirm(message, _) => render_confirm_modal(frame, message),
-        ModalState::None => {}
+    let mut fields = Vec::new();
+    let area = match &app.modal_state {
+        ModalState::AddKey(form) => render_key_modal(frame, "Add SSH Key", form, true, &mut fields),
+        ModalState::GenerateKey(form) => render_key_gen_modal(frame, form, &mut fields),
+        ModalState::EditKey(_, form) => render_key_modal(frame, "Edit SSH Key", form, false, &mut fields),
+        ModalState::AddGroup(form) => render_group_modal(frame, "Add Group", form, true, &mut fields),
+        ModalState::EditGroup(_, form) => render_group_modal(frame, "Edit Group", form, false, &mut fields),
+        ModalState::AddHost(form) => render_host_modal(frame, "Add Host", form, &app.config.keys, true, &mut fields),
+        ModalState::EditHost(_, form) => render_host_modal(frame, "Edit Host", form, &app.config.keys, false, &mut fields),
+        ModalState::TagFilter(form) => render_tag_filter_modal(frame, form),
+        ModalState::Settings(form) => render_settings_modal(frame, form, &mut fields),
+        ModalState::Forwards(form) => render_forwards_modal(frame, form),
+        ModalState::Sessions(form) => render_sessions_modal(frame, form, app),
+        ModalState::Orphans(form) => render_orphans_modal(frame, form),
+        ModalState::Trash(form) => render_trash_modal(frame, form, app),
+        ModalState::Import(form) => render_import_modal(frame, form, &mut fields),
+        ModalState::ImportPreview(form) => render_import_preview_modal(frame, form),
+        ModalState::SharedInventory(form) => render_shared_inventory_modal(frame, form),
+        ModalState::ForwardConflict(form) => render_forward_conflict_modal(frame, form),
+        ModalState::ConfigRecovery(form) => render_config_recovery_modal(frame, form),
+        ModalState::Profiles(form) => render_profiles_modal(frame, form),
+        ModalState::LoginPrompt(form) => render_login_prompt_modal(frame, form, app),
+        ModalState::PastePreview(text) => render_paste_preview_modal(frame, text, app.terminal_panel.bracketed_paste()),
+        ModalState::RecoverInput(line) => render_recover_input_modal(frame, line),
+        ModalState::Confirm(form) => render_confirm_modal(frame, form),
+        ModalState::ReviewChanges(form) => render_review_changes_modal(frame, form),
+        ModalState::HostKey(form) => render_host_key_modal(frame, form),
+        ModalState::AuthorizedKeys(form) => render_authorized_keys_modal(frame, form),
+        ModalState::Identities(form) => render_identities_modal(frame, form),
+        ModalState::Commands(form) => render_command_picker_modal(frame, form),
+        ModalState::Broadcast(form) => render_broadcast_modal(frame, form, app),
+        ModalState::Recordings(form) => render_recordings_modal(frame, form),
+        ModalState::Themes(form) => render_themes_modal(frame, form),
+        ModalState::MoveHost(form) => render_move_host_modal(frame, form),
+        ModalState::Scp(form) => render_scp_modal(frame, form, app, &mut fields),
+        ModalState::ConfigEditor(form) => render_config_editor_modal(frame, form),
+        ModalState::Facts(form) => render_facts_modal(frame, form, app),
+        ModalState::Palette(form) => render_palette_modal(frame, form, app),
+        ModalState::GroupConnect => render_group_connect_modal(frame, app),
+        ModalState::None => return,
+    };
+    let buttons = render_modal_buttons(frame, &app.modal_state, area);
+    if let Some((editor, field)) = focused_editor(&app.modal_state) {
+        // Under the input row, the last one of the field
+        let input = fields.iter().rev().find(|(index, _)| *index == field).map(|(_, rect)| *rect);
+        if let (Some(completion), Some(input)) = (editor.completion(), input) {
+            render_completion(frame, completion, input);
+        }
+    }
+    *app.modal_geometry.borrow_mut() = ModalGeometry { area, fields, buttons };
+}
+
+/// The editor of the open form and the field it is editing
+fn focused_editor(state: &ModalState) -> Option<(&LineEditor, usize)> {
+    match state {
+        ModalState::AddKey(form) | ModalState::EditKey(_, form) => Some((&form.editor, form.field_focus)),
+        ModalState::GenerateKey(form) => Some((&form.editor, form.field_focus)),
+        ModalState::AddGroup(form) | ModalState::EditGroup(_, form) => Some((&form.editor, form.field_focus)),
+        ModalState::AddHost(form) | ModalState::EditHost(_, form) => Some((&form.editor, form.field_focus)),
+        ModalState::Settings(form) => Some((&form.editor, form.field_focus)),
+        ModalState::Scp(form) => Some((&form.editor, form.field_focus)),
+        ModalState::Import(form) => Some((&form.editor, form.field_focus)),
+        _ => None,
+    }
+}
+
+/// The dropdown of Tab completions, below the field at `field`
+fn render_completion(frame: &mut Frame, completion: &Completion, field: Rect) {
+    let theme = theme::current();
+    let screen = frame.size();
+    let top = field.bottom();
+    let height = (completion.choices.len() as u16).min(8).min(screen.bottom().saturating_sub(top));
+    if height == 0 {
+        return;
+    }
+    let area = Rect::new(field.x, top, field.width, height);
+    // Keep the selected choice in view
+    let first = (completion.selected + 1).saturating_sub(height as usize);
+    let lines: Vec<Line> = completion.choices.iter().enumerate()
+        .skip(first)
+        .take(height as usize)
+        .map(|(index, choice)| {
+            let style = if index == completion.selected {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().bg(theme.input_bg).fg(theme.input_fg)
+            };
+            Line::from(Span::styled(format!(" {:<width$}", choice, width = area.width as usize), style))
+        })
+        .collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+/// The label of the button that does what Enter does, if the modal has one
+fn submit_label(state: &ModalState) -> &'static str {
+    match state {
+        ModalState::AddKey(_) | ModalState::EditKey(..) | ModalState::AddGroup(_) | ModalState::EditGroup(..)
+            | ModalState::AddHost(_) | ModalState::EditHost(..) | ModalState::Settings(_) | ModalState::ConfigEditor(_) => "Save",
+        ModalState::GenerateKey(_) => "Generate",
+        ModalState::Import(_) => "Preview",
+        ModalState::ImportPreview(_) => "Import",
+        ModalState::SharedInventory(_) => "Take",
+        _ => "OK",
+    }
+}
+
+/// Draw Save and Cancel on the bottom border of the modal, returning where
+/// they are for clicks
+fn render_modal_buttons(frame: &mut Frame, state: &ModalState, area: Rect) -> Vec<(ModalButton, Rect)> {
+    let theme = theme::current();
+    let mut buttons = vec![(ModalButton::Save, format!("[ {} ]", submit_label(state)))];
+    // The config recovery prompt has to be answered
+    if !matches!(state, ModalState::ConfigRecovery(_)) {
+        buttons.push((ModalButton::Cancel, "[ Cancel ]".to_string()));
     }
+    let width: u16 = buttons.iter().map(|(_, label)| label.chars().count() as u16 + 1).sum();
+    if area.height < 3 || area.width < width + 3 {
+        return Vec::new();
+    }
+
+    let mut x = area.right() - 2 - width;
+    let y = area.bottom() - 1;
+    buttons.into_iter().map(|(button, label)| {
+        let rect = Rect::new(x + 1, y, label.chars().count() as u16, 1);
+        let style = match button {
+            ModalButton::Save => Style::default().bg(theme.selection_bg).fg(theme.selection_fg),
+            ModalButton::Cancel => Style::default().bg(theme.input_bg).fg(theme.input_fg),
+        };
+        frame.render_widget(Paragraph::new(label).style(style), rect);
+        x += rect.width + 1;
+        (button, rect)
+    }).collect()
+}
+
+/// The label row of a form field and the input row below it
+fn field_rows(inner: &[Rect], label_row: usize) -> Rect {
+    inner[label_row].union(inner[label_row + 1])
 }
 
-fn render_key_modal(frame: &mut Frame, title: &str, form: &KeyEditForm, _is_add: bool) {
+fn render_key_modal(frame: &mut Frame, title: &str, form: &KeyEditForm, _is_add: bool, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
     let area = centered_rect(60, 12, frame.size());
     
     // Clear the area
@@ -441,7 +1867,7 @@ fn render_key_modal(frame: &mut Frame, title: &str, form: &KeyEditForm, _is_add:
     let block = Block::default()
         .title(title)
         .borders(Borders::ALL)
-        .style(Style::default().bg(Color::DarkGray));
+        .style(Style::default().bg(theme.modal_bg));
     frame.render_widget(block, area);
     
     let inner = Layout::default()
@@ -461,127 +1887,262 @@ fn render_key_modal(frame: &mut Frame, title: &str, form: &KeyEditForm, _is_add:
     
     // Name field
     let name_style = if form.field_focus == 0 {
-        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
     } else {
         Style::default()
     };
     frame.render_widget(Paragraph::new("Name:").style(name_style), inner[0]);
-    let name_input = Paragraph::new(form.name.as_str())
-        .style(if form.field_focus == 0 {
-            Style::default().bg(Color::White).fg(Color::Black)
-        } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
-        });
-    frame.render_widget(name_input, inner[1]);
+    let name_input_style = if form.field_focus == 0 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.name, inner[1], name_input_style, form.field_focus == 0);
     
     // Path field
     let path_style = if form.field_focus == 1 {
-        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
     } else {
         Style::default()
     };
     frame.render_widget(Paragraph::new("Path:").style(path_style), inner[2]);
-    let path_input = Paragraph::new(form.path.as_str())
-        .style(if form.field_focus == 1 {
-            Style::default().bg(Color::White).fg(Color::Black)
-        } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
-        });
-    frame.render_widget(path_input, inner[3]);
+    let path_input_style = if form.field_focus == 1 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.path, inner[3], path_input_style, form.field_focus == 1);
     
     // Default field
     let default_style = if form.field_focus == 2 {
-        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
     } else {
         Style::default()
     };
     frame.render_widget(Paragraph::new("Is Default:").style(default_style), inner[4]);
     let default_input = Paragraph::new(if form.is_default { "Yes" } else { "No" })
         .style(if form.field_focus == 2 {
-            Style::default().bg(Color::White).fg(Color::Black)
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
         } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
         });
     frame.render_widget(default_input, inner[5]);
+    fields.extend((0..3).map(|field| (field, field_rows(&inner, field * 2))));
     
     // Help text
-    let help_text = "Tab/↑↓=navigate | Enter=save | Esc=cancel";
+    let help_text = if form.field_focus == 1 {
+        "Tab=complete path | ↑↓=navigate | Enter=save | Esc=cancel"
+    } else {
+        "Tab/↑↓=navigate | Enter=save | Esc=cancel"
+    };
     frame.render_widget(
         Paragraph::new(help_text)
-            .style(Style::default().fg(Color::DarkGray))
+            .style(Style::default().fg(theme.muted))
             .alignment(Alignment::Center),
         inner[7]
     );
+    area
 }
 
-fn render_group_modal(frame: &mut Frame, title: &str, form: &GroupEditForm, _is_add: bool) {
-    let area = centered_rect(60, 10, frame.size());
+fn render_key_gen_modal(frame: &mut Frame, form: &KeyGenForm, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, 14, frame.size());
     
-    // Clear the area
     frame.render_widget(Clear, area);
     
-    // Render modal background
     let block = Block::default()
-        .title(title)
+        .title("Generate SSH Key")
         .borders(Borders::ALL)
-        .style(Style::default().bg(Color::DarkGray));
+        .style(Style::default().bg(theme.modal_bg));
     frame.render_widget(block, area);
     
     let inner = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
-            Constraint::Length(1), // Name label
-            Constraint::Length(1), // Name input
-            Constraint::Length(1), // Color label
-            Constraint::Length(1), // Color input
+            Constraint::Length(1), // Type label
+            Constraint::Length(1), // Type input
+            Constraint::Length(1), // Path label
+            Constraint::Length(1), // Path input
+            Constraint::Length(1), // Comment label
+            Constraint::Length(1), // Comment input
+            Constraint::Length(1), // Passphrase label
+            Constraint::Length(1), // Passphrase input
             Constraint::Length(1), // Empty
             Constraint::Length(1), // Help text
         ])
         .split(area);
     
-    // Name field
-    let name_style = if form.field_focus == 0 {
-        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
-    } else {
-        Style::default()
+    let passphrase = "*".repeat(form.passphrase.chars().count());
+    let type_choice = match form.key_type {
+        keygen::KeyType::Ed25519 => "[ed25519]  rsa (4096 bits)",
+        keygen::KeyType::Rsa => " ed25519  [rsa (4096 bits)]",
     };
-    frame.render_widget(Paragraph::new("Name:").style(name_style), inner[0]);
-    let name_input = Paragraph::new(form.name.as_str())
-        .style(if form.field_focus == 0 {
-            Style::default().bg(Color::White).fg(Color::Black)
+    let rows = [
+        ("Type:", type_choice),
+        ("Path:", form.path.as_str()),
+        ("Comment (empty for user@hostname):", form.comment.as_str()),
+        ("Passphrase (empty for none):", passphrase.as_str()),
+    ];
+    for (i, (label, value)) in rows.into_iter().enumerate() {
+        let focused = form.field_focus == i;
+        let label_style = if focused {
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
         } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
-        });
-    frame.render_widget(name_input, inner[1]);
+            Style::default()
+        };
+        frame.render_widget(Paragraph::new(label).style(label_style), inner[i * 2]);
+        let input_style = if focused {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        };
+        // The key type is a choice rather than text
+        form.editor.render(frame, value, inner[i * 2 + 1], input_style, focused && i > 0);
+        fields.push((i, field_rows(&inner, i * 2)));
+    }
+    
+    let help_text = "Tab/↑↓=navigate | ←→/Space=type | Enter=generate | Esc=cancel";
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[9]
+    );
+    area
+}
+
+fn render_group_modal(frame: &mut Frame, title: &str, form: &GroupEditForm, _is_add: bool, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, 20, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Name label
+            Constraint::Length(1), // Name input
+            Constraint::Length(1), // Color label
+            Constraint::Length(1), // Color input
+            Constraint::Length(1), // Default user label
+            Constraint::Length(1), // Default user input
+            Constraint::Length(1), // Default port label
+            Constraint::Length(1), // Default port input
+            Constraint::Length(1), // Default key label
+            Constraint::Length(1), // Default key input
+            Constraint::Length(1), // SSH options label
+            Constraint::Length(1), // SSH options input
+            Constraint::Length(1), // Notes label
+            Constraint::Length(3), // Notes input
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let rows = [
+        ("Name (nest with /, e.g. clients/acme/prod):", &form.name),
+        ("Color:", &form.color),
+        ("Default user (hosts may override):", &form.user),
+        ("Default port:", &form.port),
+        ("Default key path:", &form.key_path),
+        ("SSH options (-o, separated by ;):", &form.options),
+    ];
     
-    // Color field
-    let color_style = if form.field_focus == 1 {
-        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+    for (i, (label, value)) in rows.iter().enumerate() {
+        fields.push((i, field_rows(&inner, i * 2)));
+        let label_style = if form.field_focus == i {
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        frame.render_widget(Paragraph::new(*label).style(label_style), inner[i * 2]);
+        
+        let input_style = if form.field_focus == i {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        };
+        if i == 1 {
+            frame.render_widget(Paragraph::new(color_picker_line(&form.color)).style(input_style), inner[3]);
+        } else {
+            form.editor.render(frame, value, inner[i * 2 + 1], input_style, form.field_focus == i);
+        }
+    }
+
+    // Notes (field 6), several lines
+    let notes_label_style = if form.field_focus == 6 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
     } else {
         Style::default()
     };
-    frame.render_widget(Paragraph::new("Color:").style(color_style), inner[2]);
-    let color_input = Paragraph::new(form.color.as_str())
-        .style(if form.field_focus == 1 {
-            Style::default().bg(Color::White).fg(Color::Black)
-        } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
-        });
-    frame.render_widget(color_input, inner[3]);
+    frame.render_widget(Paragraph::new("Notes (shown with each host of the group):").style(notes_label_style), inner[12]);
+    let notes_input_style = if form.field_focus == 6 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    frame.render_widget(Paragraph::new(last_lines(&form.notes, inner[13].height)).style(notes_input_style), inner[13]);
+    fields.push((6, field_rows(&inner, 12)));
     
     // Help text
-    let help_text = "Tab/↑↓=navigate | Enter=save | Esc=cancel";
+    let help_text = if form.field_focus == 1 {
+        "←→/h/l/Space=pick color | Tab/↑↓=navigate | Enter=save | Esc=cancel"
+    } else if form.field_focus == 4 {
+        "Tab=complete path | ↑↓=navigate | Enter=save | Esc=cancel"
+    } else if form.field_focus == 6 {
+        "Enter=new line | Tab/↑↓=navigate | Esc=cancel"
+    } else {
+        "Tab/↑↓=navigate | Enter=save | Esc=cancel"
+    };
     frame.render_widget(
         Paragraph::new(help_text)
-            .style(Style::default().fg(Color::DarkGray))
+            .style(Style::default().fg(theme.muted))
             .alignment(Alignment::Center),
-        inner[5]
+        inner[15]
     );
+    area
+}
+
+/// The last lines of a multi-line input that fit in `height`, keeping the
+/// line being typed in view
+fn last_lines(text: &str, height: u16) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let first_line = lines.len().saturating_sub(height as usize);
+    lines[first_line..].join("\n")
+}
+
+/// A swatch for every palette color with the picked one bracketed, then
+/// its name; a color from the config file outside the palette is added last
+fn color_picker_line(picked: &str) -> Line<'static> {
+    let picked = picked.trim();
+    let mut colors: Vec<&str> = theme::GROUP_COLORS.to_vec();
+    if !picked.is_empty() && !colors.iter().any(|color| color.eq_ignore_ascii_case(picked)) {
+        colors.push(picked);
+    }
+    let mut spans = Vec::new();
+    for color in colors {
+        let swatch = if color.eq_ignore_ascii_case(picked) { "[■]" } else { " ■ " };
+        let style = theme::parse_color(color).map_or(Style::default(), |fg| Style::default().fg(fg));
+        spans.push(Span::styled(swatch, style));
+    }
+    let name = if picked.is_empty() { "none" } else { picked };
+    spans.push(Span::raw(format!(" {}", name)));
+    Line::from(spans)
 }
 
-fn render_host_modal(frame: &mut Frame, title: &str, form: &HostEditForm, keys: &[SshKey], _is_add: bool) {
-    let area = centered_rect(70, 16, frame.size());
+fn render_host_modal(frame: &mut Frame, title: &str, form: &HostEditForm, keys: &[SshKey], _is_add: bool, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(70, 50, frame.size());
     
     // Clear the area
     frame.render_widget(Clear, area);
@@ -590,7 +2151,7 @@ fn render_host_modal(frame: &mut Frame, title: &str, form: &HostEditForm, keys:
     let block = Block::default()
         .title(title)
         .borders(Borders::ALL)
-        .style(Style::default().bg(Color::DarkGray));
+        .style(Style::default().bg(theme.modal_bg));
     frame.render_widget(block, area);
     
     let inner = Layout::default()
@@ -607,38 +2168,81 @@ fn render_host_modal(frame: &mut Frame, title: &str, form: &HostEditForm, keys:
             Constraint::Length(1), // User input
             Constraint::Length(1), // Key Path label
             Constraint::Length(1), // Key Path input
+            Constraint::Length(1), // Password label
+            Constraint::Length(1), // Password input
+            Constraint::Length(1), // Autoconnect label
+            Constraint::Length(1), // Autoconnect input
+            Constraint::Length(1), // Autoconnect command label
+            Constraint::Length(1), // Autoconnect command input
+            Constraint::Length(1), // Tags label
+            Constraint::Length(1), // Tags input
+            Constraint::Length(1), // Backend label
+            Constraint::Length(1), // Backend input
+            Constraint::Length(1), // Keep-alive label
+            Constraint::Length(1), // Keep-alive inputs
+            Constraint::Length(1), // Allowed commands label
+            Constraint::Length(3), // Allowed commands input
+            Constraint::Length(1), // Bell label
+            Constraint::Length(1), // Bell input
+            Constraint::Length(1), // Remote command label
+            Constraint::Length(1), // Remote command input
+            Constraint::Length(1), // Startup commands label
+            Constraint::Length(3), // Startup commands input
+            Constraint::Length(1), // Depends on label
+            Constraint::Length(1), // Depends on input
+            Constraint::Length(1), // Connection label
+            Constraint::Length(1), // Connection input
+            Constraint::Length(1), // SSH options label
+            Constraint::Length(1), // SSH options input
+            Constraint::Length(1), // Bandwidth limit label
+            Constraint::Length(1), // Bandwidth limit input
+            Constraint::Length(1), // Notes label
+            Constraint::Length(3), // Notes input
+            Constraint::Length(1), // Terminal label
+            Constraint::Length(1), // Terminal inputs
             Constraint::Length(1), // Empty
             Constraint::Length(1), // Help text
         ])
         .split(area);
     
-    // Render regular fields (Name, Host, Port, User)
+    // Render regular fields (Name, Host, Port, User); empty port and user
+    // show what is inherited from the group
+    let defaults = &form.group_defaults;
     let regular_fields = [
-        ("Name:", &form.name),
-        ("Host:", &form.host),
-        ("Port:", &form.port),
-        ("User:", &form.user),
+        ("Name:", &form.name, None),
+        ("Host:", &form.host, None),
+        ("Port:", &form.port, Some(defaults.port.map_or("22 (default)".to_string(), |port| format!("{} (from group)", port)))),
+        ("User:", &form.user, defaults.user.as_ref().map(|user| format!("{} (from group)", user))),
     ];
     
-    for (i, (label, value)) in regular_fields.iter().enumerate() {
+    for (i, (label, value, inherited)) in regular_fields.iter().enumerate() {
         let label_style = if form.field_focus == i {
-            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
         } else {
             Style::default()
         };
         frame.render_widget(Paragraph::new(*label).style(label_style), inner[i * 2]);
         
         let input_style = if form.field_focus == i {
-            Style::default().bg(Color::White).fg(Color::Black)
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
         } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
         };
-        frame.render_widget(Paragraph::new(value.as_str()).style(input_style), inner[i * 2 + 1]);
+        match inherited {
+            Some(inherited) if value.is_empty() => {
+                let placeholder = Line::from(Span::styled(inherited.as_str(), Style::default().fg(theme.muted)));
+                frame.render_widget(Paragraph::new(placeholder).style(input_style), inner[i * 2 + 1]);
+                if form.field_focus == i {
+                    form.editor.show_cursor(frame, value, inner[i * 2 + 1]);
+                }
+            },
+            _ => form.editor.render(frame, value, inner[i * 2 + 1], input_style, form.field_focus == i),
+        }
     }
     
     // Render SSH Key field (field 4) - either selector or manual input
     let key_label_style = if form.field_focus == 4 {
-        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
     } else {
         Style::default()
     };
@@ -659,74 +2263,2681 @@ fn render_host_modal(frame: &mut Frame, title: &str, form: &HostEditForm, keys:
         };
         
         let input_style = if form.field_focus == 4 {
-            Style::default().bg(Color::Blue).fg(Color::White)
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
         } else {
-            Style::default().bg(Color::Gray).fg(Color::White)
+            Style::default().bg(theme.input_bg).fg(theme.text)
         };
         frame.render_widget(Paragraph::new(display_text).style(input_style), inner[9]);
     } else {
         // Show manual key path input
         let input_style = if form.field_focus == 4 {
-            Style::default().bg(Color::White).fg(Color::Black)
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        };
+        if form.key_path.is_empty() {
+            let inherited = defaults.key_path.as_ref()
+                .map_or("default key".to_string(), |key_path| format!("{} (from group)", key_path));
+            let placeholder = Line::from(Span::styled(inherited, Style::default().fg(theme.muted)));
+            frame.render_widget(Paragraph::new(placeholder).style(input_style), inner[9]);
+            if form.field_focus == 4 {
+                form.editor.show_cursor(frame, &form.key_path, inner[9]);
+            }
+        } else {
+            form.editor.render(frame, &form.key_path, inner[9], input_style, form.field_focus == 4);
+        }
+    }
+
+    // Password field (field 5) - masked
+    let password_label_style = if form.field_focus == 5 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("Password (optional):").style(password_label_style), inner[10]);
+    let password_input_style = if form.field_focus == 5 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(
+        frame,
+        &"*".repeat(form.password.chars().count()),
+        inner[11],
+        password_input_style,
+        form.field_focus == 5
+    );
+
+    // Auto-connect fields (fields 6 and 7)
+    let autoconnect_fields = [
+        ("Auto-connect when reachable (y/n):", if form.autoconnect { "Yes" } else { "No" }),
+        ("Then run command:", form.autoconnect_command.as_str()),
+    ];
+    for (offset, (label, value)) in autoconnect_fields.iter().enumerate() {
+        let field = 6 + offset;
+        let label_style = if form.field_focus == field {
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        frame.render_widget(Paragraph::new(*label).style(label_style), inner[12 + offset * 2]);
+
+        let input_style = if form.field_focus == field {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        };
+        if field == 7 {
+            form.editor.render(frame, value, inner[13 + offset * 2], input_style, form.field_focus == field);
+        } else {
+            frame.render_widget(Paragraph::new(*value).style(input_style), inner[13 + offset * 2]);
+        }
+    }
+
+    // Tags field (field 8)
+    let tags_label_style = if form.field_focus == 8 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("Tags (comma separated):").style(tags_label_style), inner[16]);
+    let tags_input_style = if form.field_focus == 8 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.tags, inner[17], tags_input_style, form.field_focus == 8);
+
+    // SSH backend field (field 9)
+    let backend_label_style = if form.field_focus == 9 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("SSH backend:").style(backend_label_style), inner[18]);
+    let backend_text = match form.backend {
+        None => "▼ Default (from settings)",
+        Some(SshBackend::System) => "▼ System ssh",
+        Some(SshBackend::Embedded) => "▼ Embedded",
+    };
+    let backend_input_style = if form.field_focus == 9 {
+        Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.text)
+    };
+    frame.render_widget(Paragraph::new(backend_text).style(backend_input_style), inner[19]);
+
+    // Keep-alive fields (fields 10 and 11) share a row
+    let keepalive_label_style = if form.field_focus == 10 || form.field_focus == 11 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Keep-alive: idle seconds (empty=off) / bytes typed (empty=ssh's own; \\0, \\e, \\xNN):").style(keepalive_label_style),
+        inner[20]
+    );
+    let keepalive_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(30),
+            Constraint::Percentage(5),
+            Constraint::Percentage(65),
+        ])
+        .split(inner[21]);
+    for (field, value, area) in [
+        (10, form.keepalive_interval.as_str(), keepalive_row[0]),
+        (11, form.keepalive_payload.as_str(), keepalive_row[2]),
+    ] {
+        let input_style = if form.field_focus == field {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        };
+        form.editor.render(frame, value, area, input_style, form.field_focus == field);
+    }
+
+    // Allowed commands field (field 12), one command per line
+    let allowed_label_style = if form.field_focus == 12 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Allowed commands (one per line, empty=free typing):").style(allowed_label_style),
+        inner[22]
+    );
+    let allowed_input_style = if form.field_focus == 12 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    frame.render_widget(
+        Paragraph::new(last_lines(&form.allowed_commands, inner[23].height)).style(allowed_input_style),
+        inner[23]
+    );
+
+    // Bell field (field 13)
+    let bell_label_style = if form.field_focus == 13 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("Bell:").style(bell_label_style), inner[24]);
+    let bell_text = match form.bell {
+        None => "▼ Default (from settings)".to_string(),
+        Some(mode) => format!("▼ {}", bell_mode_text(mode)),
+    };
+    let bell_input_style = if form.field_focus == 13 {
+        Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.text)
+    };
+    frame.render_widget(Paragraph::new(bell_text).style(bell_input_style), inner[25]);
+
+    // Remote command field (field 14)
+    let remote_label_style = if form.field_focus == 14 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("Remote command (empty=login shell):").style(remote_label_style), inner[26]);
+    let remote_input_style = if form.field_focus == 14 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.remote_command, inner[27], remote_input_style, form.field_focus == 14);
+
+    // Startup commands field (field 15), one command per line
+    let startup_label_style = if form.field_focus == 15 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("Run after connecting (one command per line):").style(startup_label_style), inner[28]);
+    let startup_input_style = if form.field_focus == 15 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    frame.render_widget(
+        Paragraph::new(last_lines(&form.startup_commands, inner[29].height)).style(startup_input_style),
+        inner[29]
+    );
+
+    // Dependencies field (field 16)
+    let depends_label_style = if form.field_focus == 16 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Group connect after (comma separated host names):").style(depends_label_style),
+        inner[30]
+    );
+    let depends_input_style = if form.field_focus == 16 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.depends_on, inner[31], depends_input_style, form.field_focus == 16);
+
+    // Connection field (field 17)
+    let connection_label_style = if form.field_focus == 17 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Connection (empty for ssh, mosh, telnet, or a command using {host} {port} {user} {name}):").style(connection_label_style),
+        inner[32]
+    );
+    let connection_input_style = if form.field_focus == 17 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let connection_text = if form.connection.is_empty() && form.field_focus != 17 { "ssh" } else { form.connection.as_str() };
+    form.editor.render(frame, connection_text, inner[33], connection_input_style, form.field_focus == 17);
+
+    // Extra ssh arguments (field 18)
+    let ssh_options_label_style = if form.field_focus == 18 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Extra ssh arguments, quoted where they hold spaces (e.g. -4 -o Ciphers=aes256-ctr):").style(ssh_options_label_style),
+        inner[34]
+    );
+    let ssh_options_input_style = if form.field_focus == 18 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.ssh_options, inner[35], ssh_options_input_style, form.field_focus == 18);
+
+    // Bandwidth limit (field 19)
+    let bandwidth_label_style = if form.field_focus == 19 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Bandwidth limit in KB/s for output and scp (empty for none):").style(bandwidth_label_style),
+        inner[36]
+    );
+    let bandwidth_input_style = if form.field_focus == 19 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    form.editor.render(frame, &form.bandwidth_limit, inner[37], bandwidth_input_style, form.field_focus == 19);
+
+    // Notes (field 20), several lines
+    let notes_label_style = if form.field_focus == 20 {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Notes (shown in the host's detail card):").style(notes_label_style),
+        inner[38]
+    );
+    let notes_input_style = if form.field_focus == 20 {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    frame.render_widget(Paragraph::new(last_lines(&form.notes, inner[39].height)).style(notes_input_style), inner[39]);
+
+    // Terminal emulation (fields 21-23)
+    let terminal_label_style = if matches!(form.field_focus, 21..=23) {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Terminal: TERM (empty=xterm-256color) / locale (empty=unchanged) / size:").style(terminal_label_style),
+        inner[40]
+    );
+    let terminal_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(30),
+            Constraint::Percentage(5),
+            Constraint::Percentage(30),
+            Constraint::Percentage(5),
+            Constraint::Percentage(30),
+        ])
+        .split(inner[41]);
+    for (field, value, area) in [
+        (21, form.term.as_str(), terminal_row[0]),
+        (22, form.locale.as_str(), terminal_row[2]),
+    ] {
+        let input_style = if form.field_focus == field {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
         } else {
-            Style::default().bg(Color::Gray).fg(Color::Black)
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
         };
-        frame.render_widget(Paragraph::new(form.key_path.as_str()).style(input_style), inner[9]);
+        form.editor.render(frame, value, area, input_style, form.field_focus == field);
+    }
+    let size_text = match form.terminal_size {
+        TerminalSize::Fit => "▼ Fit panel",
+        TerminalSize::Fixed => "▼ Fixed 80x24",
+    };
+    let size_input_style = if form.field_focus == 23 {
+        Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.text)
+    };
+    frame.render_widget(Paragraph::new(size_text).style(size_input_style), terminal_row[4]);
+
+    // Fields with a row each start at their label; the keep-alive and
+    // terminal rows are split between several
+    for (field, label_row) in (0..10).chain(12..21).zip((0..20).step_by(2).chain((22..40).step_by(2))) {
+        fields.push((field, field_rows(&inner, label_row)));
     }
+    fields.extend([
+        (10, inner[20]),
+        (10, keepalive_row[0]),
+        (11, keepalive_row[2]),
+        (21, inner[40]),
+        (21, terminal_row[0]),
+        (22, terminal_row[2]),
+        (23, terminal_row[4]),
+    ]);
     
     // Help text
     let help_text = if form.use_key_selector && form.field_focus == 4 {
         "j/k/↑↓=select key | s=manual | Tab=next | Enter=save | Esc=cancel"
+    } else if form.field_focus == 4 {
+        "Tab=complete path | ↑↓=navigate | s=selector | Enter=save | Esc=cancel"
+    } else if form.field_focus == 9 {
+        "Space=cycle | d=default | s=system | e=embedded | Enter=save | Esc=cancel"
+    } else if form.field_focus == 13 {
+        "Space=cycle | d=default | n=ignore | v=visual | o=notify | s=sound | Enter=save"
+    } else if form.field_focus == 23 {
+        "Space=toggle | f=fit panel | x=fixed 80x24 | Enter=save | Esc=cancel"
+    } else if matches!(form.field_focus, 15 | 20) {
+        "Enter=new line | Tab/↑↓=navigate | Esc=cancel"
     } else {
         "Tab/↑↓=navigate | Enter=save | Esc=cancel"
     };
     frame.render_widget(
         Paragraph::new(help_text)
-            .style(Style::default().fg(Color::DarkGray))
+            .style(Style::default().fg(theme.muted))
             .alignment(Alignment::Center),
-        inner[11]
+        inner[43]
     );
+    area
 }
 
-fn render_confirm_modal(frame: &mut Frame, message: &str) {
-    let area = centered_rect(50, 8, frame.size());
+fn render_tag_filter_modal(frame: &mut Frame, form: &TagFilterForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.tags.len().min(12) as u16;
+    let area = centered_rect(50, list_height + 4, frame.size());
     
     // Clear the area
     frame.render_widget(Clear, area);
     
     // Render modal background
     let block = Block::default()
-        .title("Confirm")
+        .title("Filter Hosts by Tag")
         .borders(Borders::ALL)
-        .style(Style::default().bg(Color::DarkGray));
+        .style(Style::default().bg(theme.modal_bg));
     frame.render_widget(block, area);
     
     let inner = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
-            Constraint::Min(1), // Message
-            Constraint::Length(1), // Empty
+            Constraint::Min(1), // Tag list
             Constraint::Length(1), // Help text
         ])
         .split(area);
     
+    // Keep the cursor in view when there are more tags than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = form.tags.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, (tag, active))| {
+            let marker = if *active { "[x]" } else { "[ ]" };
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(format!("{} {}", marker, tag), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    let help_text = "↑↓=move | Space=toggle | c=clear | Enter=apply | Esc=cancel";
     frame.render_widget(
-        Paragraph::new(message)
-            .style(Style::default().fg(Color::White))
-            .alignment(Alignment::Center)
-            .wrap(ratatui::widgets::Wrap { trim: true }),
-        inner[0]
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
     );
+    area
+}
+
+fn render_authorized_keys_modal(frame: &mut Frame, form: &AuthorizedKeysForm) -> Rect {
+    let theme = theme::current();
+    // Both lists with a header each, at most 16 rows on screen
+    let rows = form.keys.len() + form.hosts.len() + 3;
+    let area = centered_rect(50, rows.min(16) as u16 + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Export authorized_keys")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Key and host lists
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let header = |text: &'static str| Line::from(Span::styled(text, Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)));
+    let item = |index: usize, name: &str, selected: bool| {
+        let marker = if selected { "[x]" } else { "[ ]" };
+        let style = if index == form.cursor {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().fg(theme.text)
+        };
+        Line::from(Span::styled(format!("{} {}", marker, name), style))
+    };
+    let mut lines = vec![header("Keys to export")];
+    lines.extend(form.keys.iter().enumerate().map(|(i, (name, selected))| item(i, name, *selected)));
+    lines.push(Line::from(""));
+    lines.push(header("Push to hosts (optional)"));
+    lines.extend(form.hosts.iter().enumerate().map(|(i, (name, selected))| item(form.keys.len() + i, name, *selected)));
+    
+    // Keep the cursor in view when the lists are longer than the modal
+    let cursor_line = if form.cursor < form.keys.len() { form.cursor + 1 } else { form.cursor + 3 };
+    let visible_rows = inner[0].height as usize;
+    let first_row = cursor_line.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = lines.into_iter().skip(first_row).take(visible_rows).collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
     
     // Help text
-    let help_text = "Enter=confirm | Esc=cancel";
+    let help_text = "↑↓=move | Space=toggle | Enter=export | Esc=cancel";
     frame.render_widget(
         Paragraph::new(help_text)
-            .style(Style::default().fg(Color::DarkGray))
+            .style(Style::default().fg(theme.muted))
             .alignment(Alignment::Center),
-        inner[2]
+        inner[1]
+    );
+    area
+}
+
+fn render_settings_modal(frame: &mut Frame, form: &SettingsForm, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, 50, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Settings")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Secrets backend label
+            Constraint::Length(1), // Secrets backend input
+            Constraint::Length(1), // Desktop notifications label
+            Constraint::Length(1), // Desktop notifications input
+            Constraint::Length(1), // Webhook label
+            Constraint::Length(1), // Webhook input
+            Constraint::Length(1), // Quiet hours label
+            Constraint::Length(1), // Quiet hours input
+            Constraint::Length(1), // Auto-reconnect label
+            Constraint::Length(1), // Auto-reconnect input
+            Constraint::Length(1), // SSH backend label
+            Constraint::Length(1), // SSH backend input
+            Constraint::Length(1), // Paste protection label
+            Constraint::Length(1), // Paste protection input
+            Constraint::Length(1), // Local echo label
+            Constraint::Length(1), // Local echo input
+            Constraint::Length(1), // Connection sharing label
+            Constraint::Length(1), // Connection sharing input
+            Constraint::Length(1), // Session recording label
+            Constraint::Length(1), // Session recording input
+            Constraint::Length(1), // Bell label
+            Constraint::Length(1), // Bell input
+            Constraint::Length(1), // Host order label
+            Constraint::Length(1), // Host order input
+            Constraint::Length(1), // Repeat filter label
+            Constraint::Length(1), // Repeat filter input
+            Constraint::Length(1), // Sticky prefix label
+            Constraint::Length(1), // Sticky prefix input
+            Constraint::Length(1), // Prompt timeout label
+            Constraint::Length(1), // Prompt timeout input
+            Constraint::Length(1), // Session wrapper label
+            Constraint::Length(1), // Session wrapper input
+            Constraint::Length(1), // Dashboard widgets label
+            Constraint::Length(1), // Dashboard widgets input
+            Constraint::Length(1), // Git remote label
+            Constraint::Length(1), // Git remote input
+            Constraint::Length(1), // Shared inventory label
+            Constraint::Length(1), // Shared inventory input
+            Constraint::Length(1), // Inventory token label
+            Constraint::Length(1), // Inventory token input
+            Constraint::Length(1), // Idle lock label
+            Constraint::Length(1), // Idle lock input
+            Constraint::Length(1), // Sidebar key label
+            Constraint::Length(1), // Sidebar key input
+            Constraint::Length(1), // Trash days label
+            Constraint::Length(1), // Trash days input
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let label_style = |field: usize| {
+        if form.field_focus == field {
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        }
+    };
+    let input_style = |field: usize| {
+        if form.field_focus == field {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        }
+    };
+    
+    // Secrets backend field
+    frame.render_widget(Paragraph::new("Password storage:").style(label_style(0)), inner[0]);
+    let backend_text = match form.secrets_backend {
+        SecretsBackend::File => "▼ Vault file (~/.config/sshtui/vault.json)",
+        SecretsBackend::Keyring => "▼ System keyring",
+    };
+    let backend_input = Paragraph::new(backend_text)
+        .style(if form.field_focus == 0 {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.text)
+        });
+    frame.render_widget(backend_input, inner[1]);
+    
+    // Notification fields
+    frame.render_widget(Paragraph::new("Desktop notifications (y/n):").style(label_style(1)), inner[2]);
+    frame.render_widget(
+        Paragraph::new(if form.desktop_notifications { "Yes" } else { "No" }).style(input_style(1)),
+        inner[3]
+    );
+    frame.render_widget(Paragraph::new("Webhook URL:").style(label_style(2)), inner[4]);
+    form.editor.render(frame, &form.webhook_url, inner[5], input_style(2), form.field_focus == 2);
+    
+    // Quiet hours: start and end share a row
+    let quiet_label_style = if form.field_focus == 3 || form.field_focus == 4 {
+        label_style(form.field_focus)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(
+        Paragraph::new("Quiet hours, errors only (HH:MM - HH:MM):").style(quiet_label_style),
+        inner[6]
+    );
+    let quiet_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(45),
+            Constraint::Percentage(10),
+            Constraint::Percentage(45),
+        ])
+        .split(inner[7]);
+    form.editor.render(frame, &form.quiet_hours_start, quiet_row[0], input_style(3), form.field_focus == 3);
+    frame.render_widget(Paragraph::new("-").alignment(Alignment::Center), quiet_row[1]);
+    form.editor.render(frame, &form.quiet_hours_end, quiet_row[2], input_style(4), form.field_focus == 4);
+    
+    // Auto-reconnect field
+    frame.render_widget(Paragraph::new("Auto-reconnect dropped sessions (y/n):").style(label_style(5)), inner[8]);
+    frame.render_widget(
+        Paragraph::new(if form.auto_reconnect { "Yes" } else { "No" }).style(input_style(5)),
+        inner[9]
+    );
+    
+    // SSH backend field
+    frame.render_widget(Paragraph::new("Default SSH backend:").style(label_style(6)), inner[10]);
+    let ssh_backend_text = match form.ssh_backend {
+        SshBackend::System => "▼ System ssh binary",
+        SshBackend::Embedded => "▼ Embedded (built-in SSH client)",
+    };
+    let ssh_backend_input = Paragraph::new(ssh_backend_text)
+        .style(if form.field_focus == 6 {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.text)
+        });
+    frame.render_widget(ssh_backend_input, inner[11]);
+    
+    // Paste protection field
+    frame.render_widget(Paragraph::new("Confirm multi-line pastes (y/n):").style(label_style(7)), inner[12]);
+    frame.render_widget(
+        Paragraph::new(if form.paste_protection { "Yes" } else { "No" }).style(input_style(7)),
+        inner[13]
+    );
+    
+    // Local echo prediction field
+    frame.render_widget(Paragraph::new("Predict local echo on slow links (y/n):").style(label_style(8)), inner[14]);
+    frame.render_widget(
+        Paragraph::new(if form.local_echo { "Yes" } else { "No" }).style(input_style(8)),
+        inner[15]
+    );
+    
+    // Connection sharing field
+    frame.render_widget(Paragraph::new("Share connections via ControlMaster (y/n):").style(label_style(9)), inner[16]);
+    frame.render_widget(
+        Paragraph::new(if form.connection_sharing { "Yes" } else { "No" }).style(input_style(9)),
+        inner[17]
+    );
+    
+    // Session recording field
+    frame.render_widget(Paragraph::new("Record sessions as asciicast files (y/n):").style(label_style(10)), inner[18]);
+    frame.render_widget(
+        Paragraph::new(if form.record_sessions { "Yes" } else { "No" }).style(input_style(10)),
+        inner[19]
+    );
+    
+    // Bell field
+    frame.render_widget(Paragraph::new("Bell from remote sessions:").style(label_style(11)), inner[20]);
+    let bell_input = Paragraph::new(format!("▼ {}", bell_mode_text(form.bell)))
+        .style(if form.field_focus == 11 {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.text)
+        });
+    frame.render_widget(bell_input, inner[21]);
+    
+    // Host order field
+    frame.render_widget(Paragraph::new("Host order:").style(label_style(12)), inner[22]);
+    let host_sort_input = Paragraph::new(format!("▼ {}", host_sort_text(form.host_sort)))
+        .style(if form.field_focus == 12 {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.text)
+        });
+    frame.render_widget(host_sort_input, inner[23]);
+    
+    // Accessibility fields
+    frame.render_widget(Paragraph::new("Ignore a key repeated within (ms, 0=off):").style(label_style(13)), inner[24]);
+    form.editor.render(frame, &form.repeat_filter_ms, inner[25], input_style(13), form.field_focus == 13);
+    frame.render_widget(Paragraph::new("Sticky Ctrl prefix key (empty=off):").style(label_style(14)), inner[26]);
+    frame.render_widget(Paragraph::new(form.sticky_prefix.as_str()).style(input_style(14)), inner[27]);
+    frame.render_widget(Paragraph::new("Keep timed prompts such as undo up for (seconds):").style(label_style(15)), inner[28]);
+    form.editor.render(frame, &form.prompt_timeout_secs, inner[29], input_style(15), form.field_focus == 15);
+
+    // Session wrapper field
+    frame.render_widget(Paragraph::new("Keep sessions running after quitting:").style(label_style(16)), inner[30]);
+    let session_wrapper_text = match form.session_wrapper {
+        SessionWrapper::None => "▼ No",
+        SessionWrapper::Tmux => "▼ In tmux",
+        SessionWrapper::Abduco => "▼ In abduco",
+    };
+    let session_wrapper_input = Paragraph::new(session_wrapper_text)
+        .style(if form.field_focus == 16 {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.text)
+        });
+    frame.render_widget(session_wrapper_input, inner[31]);
+
+    // Dashboard widgets field
+    frame.render_widget(Paragraph::new("Dashboard widgets, in order (comma-separated):").style(label_style(17)), inner[32]);
+    form.editor.render(frame, &form.dashboard_widgets, inner[33], input_style(17), form.field_focus == 17);
+
+    // Git sync field
+    frame.render_widget(Paragraph::new("Git remote to sync the config with:").style(label_style(18)), inner[34]);
+    form.editor.render(frame, &form.git_remote, inner[35], input_style(18), form.field_focus == 18);
+
+    // Shared inventory fields
+    frame.render_widget(Paragraph::new("Shared host inventory (sftp://user@host/path or https://...):").style(label_style(19)), inner[36]);
+    form.editor.render(frame, &form.inventory_sync_url, inner[37], input_style(19), form.field_focus == 19);
+    frame.render_widget(Paragraph::new("Inventory token (HTTP only):").style(label_style(20)), inner[38]);
+    form.editor.render(
+        frame,
+        &"*".repeat(form.inventory_sync_token.chars().count()),
+        inner[39],
+        input_style(20),
+        form.field_focus == 20
+    );
+
+    // Idle lock field
+    frame.render_widget(Paragraph::new("Lock open sessions after idle minutes (0=never):").style(label_style(21)), inner[40]);
+    form.editor.render(frame, &form.lock_after_mins, inner[41], input_style(21), form.field_focus == 21);
+
+    // Sidebar key field
+    frame.render_widget(Paragraph::new("Key to reach the sidebar during a session:").style(label_style(22)), inner[42]);
+    form.editor.render(frame, &form.sidebar_key, inner[43], input_style(22), form.field_focus == 22);
+
+    // Trash field
+    frame.render_widget(Paragraph::new("Days deleted hosts and groups stay in the trash (0=until emptied):").style(label_style(23)), inner[44]);
+    form.editor.render(frame, &form.trash_days, inner[45], input_style(23), form.field_focus == 23);
+
+    // Every field but the quiet hours has a label row and an input row
+    for field in (0..3).chain(5..24) {
+        let label_row = if field < 3 { field * 2 } else { field * 2 - 2 };
+        fields.push((field, field_rows(&inner, label_row)));
+    }
+    fields.extend([(3, inner[6]), (3, quiet_row[0]), (4, quiet_row[2])]);
+    
+    // Help text
+    let help_text = if form.field_focus == 0 {
+        "Space=toggle | f=file | k=keyring | Enter=save | Esc=cancel"
+    } else if form.field_focus == 6 {
+        "Space=toggle | s=system | e=embedded | Enter=save | Esc=cancel"
+    } else if form.field_focus == 11 {
+        "Space=cycle | n=ignore | v=visual | o=notify | s=sound | Enter=save | Esc=cancel"
+    } else if form.field_focus == 12 {
+        "Space=cycle | m=manual | a=alphabetical | r=recent | f=frequent | d=daily | Enter=save | Esc=cancel"
+    } else if form.field_focus == 14 {
+        "Type the key | Backspace=off | Enter=save | Esc=cancel"
+    } else if form.field_focus == 16 {
+        "Space=cycle | n=no | t=tmux | a=abduco | Enter=save | Esc=cancel"
+    } else if form.field_focus == 17 {
+        "welcome,stats,actions,favorites,host,recent,heatmap,health,focus,clock,quote | Enter=save"
+    } else if form.field_focus == 18 {
+        "Empty=off | Pull and push from the palette (Ctrl+L) | Enter=save"
+    } else if form.field_focus == 19 {
+        "Empty=off | Sync now from the palette (Ctrl+L) | Enter=save"
+    } else if form.field_focus == 20 {
+        "Sent as a bearer token, stored like host passwords | Enter=save"
+    } else if form.field_focus == 22 {
+        "F1-F12, Ctrl+<key> or Alt+<key>; Esc also returns to the session | Enter=save"
+    } else if form.field_focus == 23 {
+        "Press t in the Groups or Hosts panel to open the trash | Enter=save"
+    } else {
+        "Tab/↑↓=navigate | Enter=save | Esc=cancel"
+    };
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[47]
+    );
+    area
+}
+
+fn render_sessions_modal(frame: &mut Frame, form: &SessionsForm, app: &AppState) -> Rect {
+    let theme = theme::current();
+    let list_height = app.background_sessions.len().clamp(1, 10) as u16;
+    let area = centered_rect(50, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Background Sessions")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Session list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Keep the cursor in view when there are more sessions than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = app.background_sessions.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, session)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            let markers = session.markers();
+            let text = match session.panel.session_label() {
+                Some(label) => format!("{} - {}", session.host_name(), label),
+                None => session.host_name().to_string(),
+            };
+            let text = if markers.is_empty() { format!("  {}", text) } else { format!("{} {}", markers, text) };
+            Line::from(Span::styled(text, style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=resume | d=close | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_broadcast_modal(frame: &mut Frame, form: &BroadcastForm, app: &AppState) -> Rect {
+    let theme = theme::current();
+    if let Some(index) = form.viewing {
+        return render_broadcast_results(frame, form, app, index);
+    }
+
+    let list_height = app.broadcasts.entries.len().clamp(1, 10) as u16;
+    let area = centered_rect(70, list_height + 8, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Broadcast")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Command label
+            Constraint::Length(1), // Command input
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // History label
+            Constraint::Min(1), // History list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // New command for the selected group
+    let group = app.config.groups.get(app.selected_group);
+    let label = match (&form.targets, group) {
+        (Some(targets), _) => format!("Run on the {} host(s) of {}:", targets.hosts.len(), targets.label),
+        (None, Some(group)) => format!("Run on every host of {} ({}):", group.name, app.config.get_hosts_for_group(app.selected_group).len()),
+        (None, None) => "Run on every host of the selected group:".to_string(),
+    };
+    let on_input = form.cursor == 0;
+    let label_style = if on_input {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new(label).style(label_style), inner[0]);
+    let input_style = if on_input {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    frame.render_widget(Paragraph::new(form.command.as_str()).style(input_style), inner[1]);
+    
+    // Past broadcasts, newest first
+    frame.render_widget(Paragraph::new("History:"), inner[3]);
+    let visible_rows = inner[4].height as usize;
+    let first_row = form.cursor.saturating_sub(1).saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = if app.broadcasts.entries.is_empty() {
+        vec![Line::from(Span::styled("No broadcasts yet", Style::default().fg(theme.subtle)))]
+    } else {
+        app.broadcasts.entries.iter()
+            .rev()
+            .enumerate()
+            .skip(first_row)
+            .take(visible_rows)
+            .map(|(i, entry)| {
+                let style = if i + 1 == form.cursor {
+                    Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+                } else {
+                    Style::default().fg(theme.text)
+                };
+                let text = format!(
+                    "{}  {}: {}  ({})",
+                    entry.started_at.format("%Y-%m-%d %H:%M"),
+                    entry.group,
+                    entry.command,
+                    entry.summary()
+                );
+                Line::from(Span::styled(text, style))
+            })
+            .collect()
+    };
+    frame.render_widget(Paragraph::new(lines), inner[4]);
+    
+    // Help text
+    let help_text = if on_input {
+        "↑↓=move | Enter=run | Esc=cancel"
+    } else {
+        "↑↓=move | Enter=results | r=replay on group | s=replay on same hosts | Esc=cancel"
+    };
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[5]
+    );
+    area
+}
+
+/// Results matrix of a broadcast: each host's state and exit code, the
+/// selected host's output, and what changed since the previous run
+fn render_broadcast_results(frame: &mut Frame, form: &BroadcastForm, app: &AppState, index: usize) -> Rect {
+    let theme = theme::current();
+    let Some(entry) = app.broadcasts.entries.get(index) else {
+        return Rect::default();
+    };
+    let area = centered_rect(90, frame.size().height.saturating_sub(4).min(40), frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title(format!("Broadcast - {}", entry.command))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let diff = broadcast_diff_lines(app, index);
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Summary
+            Constraint::Length(diff.len().min(6) as u16), // Changes since the last run
+            Constraint::Min(3), // Hosts and output
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let running = app.broadcast_running(index);
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{} on {} ({} host(s)): {}{}",
+            entry.started_at.format("%Y-%m-%d %H:%M:%S"),
+            entry.group,
+            entry.targets.len(),
+            entry.summary(),
+            if running { format!(", up to {} at a time", app.config.settings.broadcast_parallelism.max(1)) } else { String::new() }
+        )),
+        inner[0]
+    );
+    frame.render_widget(Paragraph::new(diff), inner[1]);
+    
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+        .split(inner[2]);
+    
+    // One row per target with its state
+    let hosts_block = Block::default().title("Hosts").borders(Borders::ALL).border_style(Style::default().fg(theme.subtle));
+    let visible_rows = hosts_block.inner(columns[0]).height as usize;
+    let first_row = form.host.saturating_sub(visible_rows.saturating_sub(1));
+    let rows: Vec<Line> = entry.targets.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, host_name)| {
+            let (marker, status, color) = match entry.result_for(i) {
+                Some(result) if result.succeeded() => ("✓", "exit 0".to_string(), theme.success),
+                Some(result) => ("✗", result.exit_code.map_or("no exit code".to_string(), |code| format!("exit {}", code)), theme.error),
+                None if running => ("…", "running".to_string(), theme.accent),
+                None => ("–", "not run".to_string(), theme.muted),
+            };
+            let style = if i == form.host {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(color)
+            };
+            Line::from(Span::styled(format!("{} {}  {}", marker, host_name, status), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(rows).block(hosts_block), columns[0]);
+    
+    // Output of the selected host
+    let selected = entry.targets.get(form.host);
+    let output = match entry.result_for(form.host) {
+        Some(result) if result.output.is_empty() => Text::from(Span::styled("(no output)", Style::default().fg(theme.muted))),
+        Some(result) => Text::from(result.output.as_str()),
+        None => Text::from(Span::styled(if running { "Waiting for the host..." } else { "No result" }, Style::default().fg(theme.muted))),
+    };
+    let output_block = Block::default()
+        .title(format!("Output - {}", selected.map_or("", |host_name| host_name.as_str())))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.subtle));
+    frame.render_widget(
+        Paragraph::new(output).style(Style::default().fg(theme.text)).block(output_block).scroll((form.scroll, 0)),
+        columns[1]
+    );
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=host | PgUp/PgDn=scroll output | r=replay on group | s=replay on same hosts | Enter=back | Esc=close")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn broadcast_diff_lines(app: &AppState, index: usize) -> Vec<Line<'static>> {
+    let theme = theme::current();
+    let Some(diff) = app.broadcasts.diff(index) else {
+        return vec![Line::from(Span::styled("First run of this command on this group", Style::default().fg(theme.subtle)))];
+    };
+    if diff.is_empty() {
+        return vec![Line::from(Span::styled("Same hosts and results as the last run", Style::default().fg(theme.subtle)))];
+    }
+
+    let mut lines = vec![Line::from(Span::styled("Since the last run:", Style::default().add_modifier(Modifier::BOLD)))];
+    for (hosts, marker, note, color) in [
+        (&diff.added, "+", "new target", theme.success),
+        (&diff.removed, "-", "no longer a target", theme.error),
+        (&diff.changed, "~", "result changed", theme.accent),
+    ] {
+        for host in hosts {
+            lines.push(Line::from(Span::styled(format!("  {} {} ({})", marker, host, note), Style::default().fg(color))));
+        }
+    }
+    lines
+}
+
+fn render_command_picker_modal(frame: &mut Frame, form: &CommandPickerForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.commands.len().clamp(1, 10) as u16;
+    let area = centered_rect(60, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Allowed Commands")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Command list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Keep the cursor in view when there are more commands than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = form.commands.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, command)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(command.as_str(), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=run | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_group_connect_modal(frame: &mut Frame, app: &AppState) -> Rect {
+    let theme = theme::current();
+    let Some(run) = &app.group_connect else {
+        return Rect::default();
+    };
+    let list_height = run.steps.len().clamp(1, 15) as u16;
+    let area = centered_rect(60, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title(format!("Connecting {}", run.group))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Step list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let lines: Vec<Line> = run.steps.iter()
+        .map(|step| {
+            let (symbol, status, color) = match &step.status {
+                StepStatus::Waiting if step.host.depends_on.is_empty() => ("·", "waiting".to_string(), theme.subtle),
+                StepStatus::Waiting => ("·", format!("waiting for {}", app.config.host_names(&step.host.depends_on).join(", ")), theme.subtle),
+                StepStatus::Connecting => ("…", "connecting".to_string(), theme.accent),
+                StepStatus::Up => ("✔", "up".to_string(), theme.success),
+                StepStatus::Failed(reason) => ("✘", reason.clone(), theme.error),
+                StepStatus::Skipped(reason) => ("–", format!("skipped: {}", reason), theme.error),
+            };
+            Line::from(vec![
+                Span::styled(format!("{} {} ", symbol, step.host.name), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::styled(status, Style::default().fg(theme.text)),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    let help_text = if run.is_done() {
+        "Enter/Esc=close | Ctrl+G=sessions"
+    } else {
+        "Enter/Esc=hide (keeps connecting)"
+    };
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_forward_conflict_modal(frame: &mut Frame, form: &ForwardConflictForm) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, form.choices.len() as u16 + 7, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Tunnel Port Taken")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(2), // Message
+            Constraint::Length(1), // Empty
+            Constraint::Min(1), // Choices
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(format!("Tunnel {} failed: {}", form.forward, form.error))
+            .style(Style::default().fg(theme.text))
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[0]
+    );
+    
+    let lines: Vec<Line> = form.choices.iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(choice.label(&form.forward), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[2]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=select | Esc=leave closed")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_config_recovery_modal(frame: &mut Frame, form: &ConfigRecoveryForm) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, form.choices.len() as u16 + 8, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Config Damaged")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3), // Message
+            Constraint::Length(1), // Empty
+            Constraint::Min(1), // Choices
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(format!("config.json could not be read: {}", form.reason))
+            .style(Style::default().fg(theme.error))
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[0]
+    );
+    
+    let lines: Vec<Line> = form.choices.iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(choice.label(), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[2]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=select")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_login_prompt_modal(frame: &mut Frame, form: &LoginPromptForm, app: &AppState) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(50, 7, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let host_name = app.ssh_client.profile.as_ref().map_or("the host", |profile| profile.host.name.as_str());
+    let block = Block::default()
+        .title(format!("Login to {}", host_name))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Prompt
+            Constraint::Length(1), // Answer input
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(form.prompt.as_str()).style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+        inner[0]
+    );
+    form.editor.render(
+        frame,
+        &"*".repeat(form.answer.chars().count()),
+        inner[1],
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg),
+        true
+    );
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("Enter=send | Esc=answer in the terminal instead")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_profiles_modal(frame: &mut Frame, form: &ProfilesForm) -> Rect {
+    let theme = theme::current();
+    let list_height = (form.profiles.len() + 1).min(12) as u16;
+    let area = centered_rect(50, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Profiles")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Profile list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let current = Config::profile();
+    let mut rows: Vec<String> = form.profiles.iter()
+        .map(|name| if *name == current { format!("{} (open)", name) } else { name.clone() })
+        .collect();
+    let new_name = if form.cursor == form.profiles.len() { format!("{}▏", form.new_name) } else { form.new_name.clone() };
+    rows.push(format!("+ New profile: {}", new_name));
+
+    // Keep the cursor in view when there are more profiles than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = rows.into_iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, row)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else if i == form.profiles.len() {
+                Style::default().fg(theme.muted)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(row, style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    let help_text = if form.cursor == form.profiles.len() {
+        "Type a name | Enter=create and open | Esc=close"
+    } else {
+        "↑↓=move | Enter=open | Esc=close"
+    };
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_orphans_modal(frame: &mut Frame, form: &OrphansForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.sessions.len().clamp(1, 10) as u16;
+    let area = centered_rect(60, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Orphaned Sessions")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Session list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Keep the cursor in view when there are more sessions than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = form.sessions.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, session)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            let text = match session.started() {
+                Some(started) => format!("{} - started {}", session.host_label(), started.format("%Y-%m-%d %H:%M")),
+                None => session.host_label().to_string(),
+            };
+            Line::from(Span::styled(text, style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=attach | d=end | Esc=close")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_trash_modal(frame: &mut Frame, form: &TrashForm, app: &AppState) -> Rect {
+    let theme = theme::current();
+    let list_height = app.config.trash.len().clamp(1, 10) as u16;
+    let area = centered_rect(60, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let title = match app.config.settings.trash_days {
+        0 => "Trash".to_string(),
+        days => format!("Trash (kept {} days)", days),
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Trashed items
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Keep the cursor in view when there are more items than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = app.config.trash.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, entry)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            let text = format!("{} - deleted {}", entry.describe(), entry.deleted_at.format("%Y-%m-%d %H:%M"));
+            Line::from(Span::styled(text, style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=restore | d=delete for good | Esc=close")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_import_modal(frame: &mut Frame, form: &ImportForm, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, 10, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Import Hosts")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Format label
+            Constraint::Length(1), // Format choice
+            Constraint::Length(1), // Path label
+            Constraint::Length(1), // Path input
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let label_style = |focused: bool| if focused {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    let input_style = |focused: bool| if focused {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+
+    fields.push((0, field_rows(&inner, 0)));
+    frame.render_widget(Paragraph::new("Import from:").style(label_style(form.field_focus == 0)), inner[0]);
+    frame.render_widget(
+        Paragraph::new(format!("◀ {} ▶", form.format.label())).style(input_style(form.field_focus == 0)),
+        inner[1]
+    );
+
+    fields.push((1, field_rows(&inner, 2)));
+    frame.render_widget(Paragraph::new("File:").style(label_style(form.field_focus == 1)), inner[2]);
+    form.editor.render(frame, &form.path, inner[3], input_style(form.field_focus == 1), form.field_focus == 1);
+    
+    // Help text
+    let help_text = if form.field_focus == 0 {
+        "←/→=choose the tool | Tab=next field | Enter=preview | Esc=cancel"
+    } else {
+        "Tab=complete | ↑↓=navigate | Enter=preview | Esc=cancel"
+    };
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[5]
+    );
+    area
+}
+
+fn render_import_preview_modal(frame: &mut Frame, form: &ImportPreviewForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.lines.len().clamp(1, 20) as u16;
+    let area = centered_rect(70, list_height + 7, frame.size());
+    
+    frame.render_widget(Clear, area);
+    
+    let block = Block::default()
+        .title("Import Preview")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Subject
+            Constraint::Min(1), // Changes
+            Constraint::Length(1), // Buttons
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(format!("Add these from {}?", form.path))
+            .style(Style::default().fg(theme.text))
+            .alignment(Alignment::Center),
+        inner[0]
+    );
+
+    // Added lines start with +, skipped ones with =
+    let lines: Vec<Line> = form.lines.iter()
+        .skip(form.scroll)
+        .take(inner[1].height as usize)
+        .map(|line| {
+            let color = if line.starts_with('+') { theme.success } else { theme.subtle };
+            Line::from(Span::styled(line.as_str(), Style::default().fg(color)))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[1]);
+
+    let button_style = |selected: bool| if selected {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let buttons = Line::from(vec![
+        Span::styled(" Import ", button_style(form.confirm_selected)),
+        Span::raw("    "),
+        Span::styled(" Back ", button_style(!form.confirm_selected)),
+    ]);
+    frame.render_widget(Paragraph::new(buttons).alignment(Alignment::Center), inner[2]);
+    
+    frame.render_widget(
+        Paragraph::new("↑↓=scroll | ←/→=choose | Enter=select | Esc=back")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[4]
+    );
+    area
+}
+
+fn render_shared_inventory_modal(frame: &mut Frame, form: &SharedInventoryForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.lines.len().clamp(1, 20) as u16;
+    let area = centered_rect(70, list_height + 8, frame.size());
+    
+    frame.render_widget(Clear, area);
+    
+    let block = Block::default()
+        .title("Shared Inventory")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(2), // Question
+            Constraint::Min(1), // Commands
+            Constraint::Length(1), // Buttons
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(form.heading.as_str())
+            .style(Style::default().fg(theme.text))
+            .alignment(Alignment::Center)
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[0]
+    );
+
+    let lines: Vec<Line> = form.lines.iter()
+        .skip(form.scroll)
+        .take(inner[1].height as usize)
+        .map(|line| Line::from(Span::styled(line.as_str(), Style::default().fg(theme.error))))
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[1]);
+
+    let button_style = |selected: bool| if selected {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let buttons = Line::from(vec![
+        Span::styled(" Take ", button_style(form.confirm_selected)),
+        Span::raw("    "),
+        Span::styled(" Keep mine ", button_style(!form.confirm_selected)),
+    ]);
+    frame.render_widget(Paragraph::new(buttons).alignment(Alignment::Center), inner[2]);
+    
+    frame.render_widget(
+        Paragraph::new("↑↓=scroll | ←/→=choose | Enter=select | Esc=keep mine")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[4]
+    );
+    area
+}
+
+fn render_recordings_modal(frame: &mut Frame, form: &RecordingsForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.recordings.len().clamp(1, 10) as u16;
+    let area = centered_rect(60, list_height + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Recorded Sessions")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Recording list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Keep the cursor in view when there are more recordings than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = form.recordings.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, name)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(name.as_str(), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=play | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_themes_modal(frame: &mut Frame, form: &ThemeForm) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(40, form.themes.len() as u16 + 4, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Theme")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Theme list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let lines: Vec<Line> = form.themes.iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            let marker = if *name == form.original { " (current)" } else { "" };
+            Line::from(Span::styled(format!("{}{}", name, marker), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=preview | Enter=keep | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[1]
+    );
+    area
+}
+
+fn render_scp_modal(frame: &mut Frame, form: &ScpForm, app: &AppState, fields: &mut Vec<(usize, Rect)>) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(60, 12, frame.size());
+    let host_name = app.ssh_client.profile.as_ref().map(|profile| profile.host.name.as_str()).unwrap_or("");
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let title = if form.upload {
+        format!("Upload to {}", host_name)
+    } else {
+        format!("Download from {}", host_name)
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Local label
+            Constraint::Length(1), // Local input
+            Constraint::Length(1), // Remote label
+            Constraint::Length(1), // Remote input
+            Constraint::Length(1), // Empty
+            Constraint::Length(2), // How the copy logs in
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let (local_label, remote_label) = if form.upload {
+        ("Local file or directory:", "Remote destination (relative to home):")
+    } else {
+        ("Local destination:", "Remote file or directory (relative to home):")
+    };
+    let rows = [(local_label, &form.local), (remote_label, &form.remote)];
+    for (i, (label, value)) in rows.iter().enumerate() {
+        fields.push((i, field_rows(&inner, i * 2)));
+        let focused = form.field_focus == i;
+        let label_style = if focused {
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        frame.render_widget(Paragraph::new(*label).style(label_style), inner[i * 2]);
+        let input_style = if focused {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().bg(theme.input_bg).fg(theme.input_fg)
+        };
+        form.editor.render(frame, value, inner[i * 2 + 1], input_style, focused);
+    }
+    
+    let login = if form.master.is_some() {
+        "Copies over the session's shared connection."
+    } else {
+        "Logs in on its own with the host's key or ssh agent, without prompting; hosts that need a password or code are not supported."
+    };
+    frame.render_widget(
+        Paragraph::new(login)
+            .style(Style::default().fg(theme.muted))
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[5]
+    );
+
+    // Help text
+    frame.render_widget(
+        Paragraph::new("Tab/↑↓=navigate | Enter=copy in the background | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[6]
+    );
+    area
+}
+
+fn render_move_host_modal(frame: &mut Frame, form: &MoveHostForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.groups.len().clamp(1, 10) as u16;
+    let area = centered_rect(50, list_height + 6, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title(format!("Move or Copy '{}'", form.host_name))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Move/Copy choice
+            Constraint::Length(1), // Empty
+            Constraint::Min(1), // Group list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let button_style = |selected: bool| if selected {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let buttons = Line::from(vec![
+        Span::styled(" Move ", button_style(!form.copy)),
+        Span::raw("    "),
+        Span::styled(" Copy ", button_style(form.copy)),
+    ]);
+    frame.render_widget(Paragraph::new(buttons).alignment(Alignment::Center), inner[0]);
+    
+    // Keep the cursor in view when there are more groups than rows
+    let visible_rows = inner[2].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = form.groups.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, name)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(name.as_str(), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[2]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("←/→=move or copy | ↑↓=group | Enter=confirm | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_facts_modal(frame: &mut Frame, form: &FactsForm, app: &AppState) -> Rect {
+    let theme = theme::current();
+    let matches = app.fact_matches(&form.query);
+    let list_height = matches.len().clamp(1, 15) as u16;
+    let area = centered_rect(80, list_height + 7, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Search Hosts by Facts")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Query input
+            Constraint::Length(1), // Sync status
+            Constraint::Min(1), // Matching hosts
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let mut query = form.query.clone();
+    query.push('_');
+    frame.render_widget(
+        Paragraph::new(query).style(Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)),
+        inner[0]
+    );
+    
+    let status = match &app.facts_sync {
+        Some(sync) => format!("{} of {} host(s) match, collecting facts from {} more...", matches.len(), app.config.get_hosts_for_group(0).len(), sync.pending),
+        None => format!("{} of {} host(s) match", matches.len(), app.config.get_hosts_for_group(0).len()),
+    };
+    frame.render_widget(Paragraph::new(status).style(Style::default().fg(theme.subtle)), inner[1]);
+    
+    // Keep the cursor in view when there are more matches than rows
+    let visible_rows = inner[2].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let name_width = matches.iter().map(|host| host.name.chars().count()).max().unwrap_or(0);
+    let lines: Vec<Line> = matches.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, host)| {
+            let (style, facts_style) = if i == form.cursor {
+                let selected = Style::default().bg(theme.selection_bg).fg(theme.selection_fg);
+                (selected, selected)
+            } else {
+                (Style::default().fg(theme.text), Style::default().fg(theme.subtle))
+            };
+            let facts = app.facts.hosts.get(&host.id)
+                .map_or("no facts yet".to_string(), |entry| entry.facts.summary());
+            Line::from(vec![
+                Span::styled(format!("{:<width$}  ", host.name, width = name_width), style),
+                Span::styled(facts, facts_style),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[2]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("Type to search, e.g. ubuntu 20.04 | ↑↓=move | Enter=broadcast to matches | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[4]
+    );
+    area
+}
+
+fn render_palette_modal(frame: &mut Frame, form: &PaletteForm, app: &AppState) -> Rect {
+    let theme = theme::current();
+    let matches = app.palette_matches(&form.query);
+    let list_height = matches.len().clamp(1, 15) as u16;
+    let area = centered_rect(60, list_height + 5, frame.size());
+    
+    frame.render_widget(Clear, area);
+    
+    let block = Block::default()
+        .title("Actions")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Query input
+            Constraint::Min(1), // Matching actions
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let mut query = form.query.clone();
+    query.push('_');
+    frame.render_widget(
+        Paragraph::new(query).style(Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)),
+        inner[0]
+    );
+    
+    // Keep the cursor in view when there are more matches than rows
+    let visible_rows = inner[1].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let title_width = matches.iter().map(|action| action.title().chars().count()).max().unwrap_or(0);
+    let mut lines: Vec<Line> = matches.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, action)| {
+            let (style, keys_style) = if i == form.cursor {
+                let selected = Style::default().bg(theme.selection_bg).fg(theme.selection_fg);
+                (selected, selected)
+            } else {
+                (Style::default().fg(theme.text), Style::default().fg(theme.subtle))
+            };
+            Line::from(vec![
+                Span::styled(format!("{:<width$}  ", action.title(), width = title_width), style),
+                Span::styled(action.keys(), keys_style),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::styled("No matching action", Style::default().fg(theme.muted)));
+    }
+    frame.render_widget(Paragraph::new(lines), inner[1]);
+    
+    frame.render_widget(
+        Paragraph::new("Type to search | ↑↓=move | Enter=run | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_config_editor_modal(frame: &mut Frame, form: &ConfigEditorForm) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(96, frame.size().height.saturating_sub(2), frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let title = if form.dirty { "Config (config.json) [modified]" } else { "Config (config.json)" };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // File contents
+            Constraint::Length(1), // Status
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Line numbers, then the highlighted line
+    let rows = inner[0].height as usize;
+    let first_row = form.first_visible_row(rows);
+    let gutter = form.lines.len().to_string().len();
+    let lines: Vec<Line> = form.lines.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(rows)
+        .map(|(i, line)| {
+            let mut spans = vec![Span::styled(format!("{:>width$} ", i + 1, width = gutter), Style::default().fg(theme.muted))];
+            spans.extend(config_editor::highlight_json(line).spans);
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // Cursor
+    let x = inner[0].x + (gutter + 1 + form.col) as u16;
+    let y = inner[0].y + (form.row - first_row) as u16;
+    if x < inner[0].right() && y < inner[0].bottom() {
+        frame.buffer_mut().set_style(Rect::new(x, y, 1, 1), Style::default().bg(theme.selection_bg).fg(theme.selection_fg));
+    }
+    
+    let status = match &form.status {
+        Some(status) => Span::styled(status.as_str(), Style::default().fg(theme.error)),
+        None => Span::styled(format!("Line {}, column {}", form.row + 1, form.col + 1), Style::default().fg(theme.subtle)),
+    };
+    frame.render_widget(Paragraph::new(Line::from(status)), inner[1]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("Ctrl+S=check, save and reload | Esc=close")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[2]
+    );
+    area
+}
+
+fn render_identities_modal(frame: &mut Frame, form: &IdentityForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.identities.len().clamp(1, 10) as u16;
+    let area = centered_rect(60, list_height + 6, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Switch Identity")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Explanation
+            Constraint::Length(1), // Empty
+            Constraint::Min(1), // Identity list
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(format!("{} rejected the login. Retry with:", form.host_name))
+            .style(Style::default().fg(theme.text)),
+        inner[0]
+    );
+    
+    // Keep the cursor in view when there are more identities than rows
+    let visible_rows = inner[2].height as usize;
+    let first_row = form.cursor.saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = form.identities.iter()
+        .enumerate()
+        .skip(first_row)
+        .take(visible_rows)
+        .map(|(i, identity)| {
+            let style = if i == form.cursor {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(identity.label(), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner[2]);
+    
+    // Help text
+    frame.render_widget(
+        Paragraph::new("↑↓=move | Enter=retry and remember if it works | Esc=cancel")
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_forwards_modal(frame: &mut Frame, form: &ForwardsForm) -> Rect {
+    let theme = theme::current();
+    let list_height = form.forwards.len().clamp(1, 10) as u16;
+    let area = centered_rect(60, list_height + 6, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let title = if form.live { "Session Tunnels" } else { "Host Port Forwards" };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Forward list
+            Constraint::Length(1), // New forward label
+            Constraint::Length(1), // New forward input
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    // Keep the cursor in view when there are more forwards than rows
+    let visible_rows = inner[0].height as usize;
+    let first_row = form.cursor.min(form.forwards.len().saturating_sub(1)).saturating_sub(visible_rows.saturating_sub(1));
+    let lines: Vec<Line> = if form.forwards.is_empty() {
+        vec![Line::from(Span::styled("No forwards", Style::default().fg(theme.subtle)))]
+    } else {
+        form.forwards.iter()
+            .enumerate()
+            .skip(first_row)
+            .take(visible_rows)
+            .map(|(i, forward)| {
+                let style = if i == form.cursor {
+                    Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+                } else {
+                    Style::default().fg(theme.text)
+                };
+                Line::from(Span::styled(forward.to_string(), style))
+            })
+            .collect()
+    };
+    frame.render_widget(Paragraph::new(lines), inner[0]);
+    
+    // New forward input
+    let on_input = form.cursor == form.forwards.len();
+    let label_style = if on_input {
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    frame.render_widget(Paragraph::new("Add (-L 8080:db:5432, -R 9000:localhost:3000, -D 1080):").style(label_style), inner[1]);
+    let input_style = if on_input {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    frame.render_widget(Paragraph::new(form.new_forward.as_str()).style(input_style), inner[2]);
+    
+    // Help text
+    let help_text = if on_input {
+        "↑↓=move | Enter=add (empty: apply) | Esc=cancel"
+    } else {
+        "↑↓=move | d=remove | Enter=apply | Esc=cancel"
+    };
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[4]
+    );
+    area
+}
+
+fn render_paste_preview_modal(frame: &mut Frame, text: &str, bracketed: bool) -> Rect {
+    let theme = theme::current();
+    const MAX_PREVIEW_LINES: usize = 12;
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    let preview_height = lines.len().min(MAX_PREVIEW_LINES + 1) as u16;
+    let area = centered_rect(70, preview_height + 7, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Confirm Paste")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Summary
+            Constraint::Length(1), // How it is sent
+            Constraint::Length(1), // Empty
+            Constraint::Min(1), // Preview
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(format!("Paste {} lines into the remote shell?", lines.len()))
+            .style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+        inner[0]
+    );
+    let (mode_text, mode_color) = if bracketed {
+        ("Sent as one bracketed paste: nothing runs until you press Enter", theme.success)
+    } else {
+        ("The remote has not enabled bracketed paste: each line runs as it arrives", theme.error)
+    };
+    frame.render_widget(Paragraph::new(mode_text).style(Style::default().fg(mode_color)), inner[1]);
+    
+    let mut preview: Vec<Line> = lines.iter()
+        .take(MAX_PREVIEW_LINES)
+        .map(|line| Line::from(Span::styled(line.to_string(), Style::default().fg(Color::White))))
+        .collect();
+    if lines.len() > MAX_PREVIEW_LINES {
+        preview.push(Line::from(Span::styled(
+            format!("… and {} more lines", lines.len() - MAX_PREVIEW_LINES),
+            Style::default().fg(Color::Gray),
+        )));
+    }
+    frame.render_widget(Paragraph::new(preview).style(Style::default().bg(Color::Black)), inner[3]);
+    
+    // Help text
+    let help_text = "Enter=paste | Esc=cancel";
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[4]
+    );
+    area
+}
+
+fn render_recover_input_modal(frame: &mut Frame, line: &str) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(70, 7, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Unsent Input")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(1), // Summary
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Line
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new("The last session ended while this was being typed:")
+            .style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+        inner[0]
+    );
+    frame.render_widget(
+        Paragraph::new(line).style(Style::default().bg(theme.input_bg).fg(theme.input_fg)),
+        inner[2]
+    );
+    
+    // Help text
+    let help_text = "Enter=type it again (not run) | Esc=discard";
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_confirm_modal(frame: &mut Frame, form: &ConfirmForm) -> Rect {
+    let theme = theme::current();
+    let area = centered_rect(50, 9, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let block = Block::default()
+        .title("Confirm")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(1), // Message
+            Constraint::Length(1), // Buttons
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(form.message.as_str())
+            .style(Style::default().fg(theme.text))
+            .alignment(Alignment::Center)
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[0]
+    );
+
+    let button_style = |selected: bool| if selected {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let buttons = Line::from(vec![
+        Span::styled(" Yes ", button_style(form.confirm_selected)),
+        Span::raw("    "),
+        Span::styled(" No ", button_style(!form.confirm_selected)),
+    ]);
+    frame.render_widget(Paragraph::new(buttons).alignment(Alignment::Center), inner[1]);
+    
+    // Help text
+    let help_text = "←/→=choose | Enter=select | y/n | Esc=cancel";
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[3]
+    );
+    area
+}
+
+fn render_review_changes_modal(frame: &mut Frame, form: &ReviewChangesForm) -> Rect {
+    let theme = theme::current();
+    // One row per change, two when the values do not fit side by side
+    let rows = form.changes.len() as u16 * 2;
+    let area = centered_rect(70, rows.min(30) + 8, frame.size());
+    
+    frame.render_widget(Clear, area);
+    
+    let block = Block::default()
+        .title("Review Changes")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(2), // Subject
+            Constraint::Min(1), // Changes
+            Constraint::Length(1), // Buttons
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    frame.render_widget(
+        Paragraph::new(format!("Save these changes to {}?", form.subject))
+            .style(Style::default().fg(theme.text))
+            .alignment(Alignment::Center)
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[0]
+    );
+
+    let width = form.changes.iter().map(|change| change.field.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = form.changes.iter()
+        .map(|change| Line::from(vec![
+            Span::styled(format!("{:<width$}  ", change.field), Style::default().fg(theme.subtle)),
+            Span::styled(format!("- {}", change.old), Style::default().fg(theme.error)),
+            Span::raw("  "),
+            Span::styled(format!("+ {}", change.new), Style::default().fg(theme.success)),
+        ]))
+        .collect();
+    frame.render_widget(Paragraph::new(lines).wrap(ratatui::widgets::Wrap { trim: false }), inner[1]);
+
+    let button_style = |selected: bool| if selected {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let buttons = Line::from(vec![
+        Span::styled(" Save ", button_style(form.confirm_selected)),
+        Span::raw("    "),
+        Span::styled(" Back ", button_style(!form.confirm_selected)),
+    ]);
+    frame.render_widget(Paragraph::new(buttons).alignment(Alignment::Center), inner[2]);
+    
+    let help_text = "←/→=choose | Enter=select | Esc=back to the form";
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[4]
+    );
+    area
+}
+
+fn render_host_key_modal(frame: &mut Frame, form: &HostKeyForm) -> Rect {
+    let theme = theme::current();
+    let prompt = &form.prompt;
+    let observed = &prompt.observed;
+    let time = |time: &chrono::DateTime<chrono::Local>| time.format("%Y-%m-%d %H:%M").to_string();
+    let header = |text: String| Line::from(Span::styled(text, Style::default().fg(theme.text).add_modifier(Modifier::BOLD)));
+    let row = |label: &'static str, value: String, style: Style| Line::from(vec![
+        Span::styled(format!("  {:<13}", label), Style::default().fg(theme.subtle)),
+        Span::styled(value, style),
+    ]);
+    let plain = Style::default().fg(theme.text);
+    
+    let (title, warning, trust_label, cancel_label, mut details) = match &prompt.known {
+        Some(known) => {
+            let source = match known.source {
+                KeySource::KnownHosts { line } => format!("Known key (~/.ssh/known_hosts entry {})", line),
+                KeySource::Pinned => "Pinned key".to_string(),
+            };
+            // Values that differ from the known key are shown in red
+            let changed = |differs: bool| if differs { Style::default().fg(theme.error).add_modifier(Modifier::BOLD) } else { plain };
+            let unknown = || "unknown".to_string();
+            (
+                "⚠ HOST KEY CHANGED",
+                format!(
+                    "The key presented by {} ({}) does not match the key it is known by. The server may have been reinstalled, or someone may be intercepting the connection. The connection was blocked.",
+                    prompt.host_name, prompt.address()
+                ),
+                " Trust new key ",
+                " Cancel ",
+                vec![
+                    header(source),
+                    row("Type", known.key_type.clone(), plain),
+                    row("Fingerprint", known.fingerprint.clone(), plain),
+                    row("First seen", known.first_seen.as_ref().map_or_else(unknown, time), plain),
+                    row("Last seen", known.last_seen.as_ref().map_or_else(unknown, time), plain),
+                    Line::from(""),
+                    header("Presented key".to_string()),
+                    row("Type", observed.key_type.clone(), changed(observed.key_type != known.key_type)),
+                    row("Fingerprint", observed.fingerprint.clone(), changed(true)),
+                ],
+            )
+        },
+        None => (
+            "Unknown Host Key",
+            format!(
+                "sshtui has not connected to {} ({}) before. Check the fingerprint with the server's administrator before trusting it; it is then added to ~/.ssh/known_hosts.",
+                prompt.host_name, prompt.address()
+            ),
+            " Trust ",
+            " Deny ",
+            vec![
+                header("Presented key".to_string()),
+                row("Type", observed.key_type.clone(), plain),
+                row("Fingerprint", observed.fingerprint.clone(), plain),
+                row("SHA512", prompt.report.sha512.clone(), plain),
+                row("MD5", prompt.report.md5.clone(), plain),
+            ],
+        ),
+    };
+    if prompt.known.is_some() {
+        details.push(row("First seen", observed.first_seen.as_ref().map_or("never (new key)".to_string(), time), plain));
+    }
+
+    // What the server shows of itself, against what it showed when the
+    // known key was trusted
+    let report = &prompt.report;
+    let previous = prompt.previous_report.as_ref();
+    let unknown = |value: Option<&String>| value.cloned().unwrap_or_else(|| "unknown".to_string());
+    let methods = |report: &TrustReport| if report.auth_methods.is_empty() { "unknown".to_string() } else { report.auth_methods.join(", ") };
+    let server_rows = [
+        ("Version", unknown(report.banner.as_ref()), previous.map(|previous| unknown(previous.banner.as_ref()))),
+        ("Auth methods", methods(report), previous.map(methods)),
+        ("Cipher", unknown(report.cipher.as_ref()), previous.map(|previous| unknown(previous.cipher.as_ref()))),
+    ];
+    details.push(Line::from(""));
+    details.push(header(match previous {
+        Some(previous) => format!("Server (compared with {})", time(&previous.collected)),
+        None => "Server".to_string(),
+    }));
+    for (label, value, before) in server_rows {
+        match before {
+            Some(before) if before != value => details.push(row(
+                label,
+                format!("{} (was {})", value, before),
+                Style::default().fg(theme.error).add_modifier(Modifier::BOLD),
+            )),
+            _ => details.push(row(label, value, plain)),
+        }
+    }
+    if prompt.known.is_none() {
+        details.push(Line::from(""));
+        let art = hostkeys::randomart(&observed.key);
+        details.extend(art.lines().map(|line| Line::from(Span::styled(format!("  {}", line), Style::default().fg(theme.subtle)))));
+    }
+    
+    let area = centered_rect(80, details.len() as u16 + 10, frame.size());
+    
+    // Clear the area
+    frame.render_widget(Clear, area);
+    
+    // Render modal background
+    let border_color = if prompt.known.is_some() { theme.error } else { theme.text };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(border_color))
+        .style(Style::default().bg(theme.modal_bg));
+    frame.render_widget(block, area);
+    
+    let inner = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3), // Warning
+            Constraint::Length(1), // Empty
+            Constraint::Min(details.len() as u16), // Key details
+            Constraint::Length(1), // Buttons
+            Constraint::Length(1), // Empty
+            Constraint::Length(1), // Help text
+        ])
+        .split(area);
+    
+    let warning_color = if prompt.known.is_some() { theme.error } else { theme.accent };
+    frame.render_widget(
+        Paragraph::new(warning)
+            .style(Style::default().fg(warning_color).add_modifier(Modifier::BOLD))
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        inner[0]
+    );
+    frame.render_widget(Paragraph::new(details), inner[2]);
+    
+    let button_style = |selected: bool| if selected {
+        Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(theme.input_bg).fg(theme.input_fg)
+    };
+    let buttons = Line::from(vec![
+        Span::styled(trust_label, button_style(form.trust_selected)),
+        Span::raw("    "),
+        Span::styled(cancel_label, button_style(!form.trust_selected)),
+    ]);
+    frame.render_widget(Paragraph::new(buttons).alignment(Alignment::Center), inner[3]);
+    
+    // Help text
+    let help_text = "←/→=choose | Enter=select | Esc=cancel";
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .style(Style::default().fg(theme.muted))
+            .alignment(Alignment::Center),
+        inner[5]
     );
+    area
 }
 
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
@@ -0,0 +1,43 @@
+use crate::{AppState, MessageType};
+use crate::config::ConnectionKind;
+use std::env;
+
+/// Whether a `mosh` binary is on the PATH
+fn mosh_installed() -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| dir.join("mosh").is_file()))
+}
+
+impl AppState {
+    /// Switch the selected host between ssh and mosh sessions and save it.
+    /// mosh logs in over ssh with the same key, then keeps the session
+    /// alive across sleep, roaming and flaky networks.
+    pub fn toggle_selected_host_mosh(&mut self) {
+        let Some(host) = self.selected_visible_host() else {
+            return;
+        };
+        let kind = match host.kind {
+            ConnectionKind::Ssh => ConnectionKind::Mosh,
+            ConnectionKind::Mosh => ConnectionKind::Ssh,
+            _ => {
+                self.set_message(format!("'{}' runs its own command; edit its Connection field instead", host.name), MessageType::Info);
+                return;
+            },
+        };
+        let before = self.snapshot();
+        let Some(configured) = self.config.find_host_mut(host.id) else {
+            return;
+        };
+        configured.kind = kind.clone();
+        self.record_change(before, format!("Switched host '{}' to {}", host.name, kind.program()));
+        if let Err(e) = self.config.save() {
+            self.set_message(format!("Failed to save host: {}", e), MessageType::Error);
+            return;
+        }
+
+        if kind == ConnectionKind::Mosh && !mosh_installed() {
+            self.set_message(format!("'{}' now connects with mosh, but mosh is not installed here", host.name), MessageType::Error);
+        } else {
+            self.set_message(format!("'{}' now connects with {}", host.name, kind.program()), MessageType::Success);
+        }
+    }
+}
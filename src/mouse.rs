@@ -0,0 +1,102 @@
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+
+/// Which mouse events the remote application asked to receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseTracking {
+    /// Mouse events stay with sshtui
+    #[default]
+    Off,
+    /// Presses, releases and the wheel (DECSET 1000)
+    Press,
+    /// Also motion while a button is held (DECSET 1002)
+    ButtonMotion,
+    /// Also motion without a button (DECSET 1003)
+    AnyMotion,
+}
+
+/// Mouse modes the remote application enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseReporting {
+    pub tracking: MouseTracking,
+    /// Report in the SGR format (DECSET 1006) rather than the legacy
+    /// byte-encoded one
+    pub sgr: bool,
+}
+
+impl MouseReporting {
+    /// Apply a DECSET (`enabled`) or DECRST of `mode`; other modes are
+    /// ignored
+    pub fn set_mode(&mut self, mode: u16, enabled: bool) {
+        let tracking = match mode {
+            1000 => MouseTracking::Press,
+            1002 => MouseTracking::ButtonMotion,
+            1003 => MouseTracking::AnyMotion,
+            1006 => {
+                self.sgr = enabled;
+                return;
+            },
+            _ => return,
+        };
+        if enabled {
+            self.tracking = tracking;
+        } else if self.tracking == tracking {
+            self.tracking = MouseTracking::Off;
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.tracking != MouseTracking::Off
+    }
+
+    /// Escape sequence reporting `event` at the 0-based cell (`column`,
+    /// `row`) of the remote screen, or None if the application did not ask
+    /// for this kind of event
+    pub fn encode(&self, event: &MouseEvent, column: u16, row: u16) -> Option<Vec<u8>> {
+        if !self.enabled() {
+            return None;
+        }
+        let (button, released) = match event.kind {
+            MouseEventKind::Down(button) => (button_code(button), false),
+            MouseEventKind::Up(button) => (button_code(button), true),
+            MouseEventKind::Drag(button) if self.tracking != MouseTracking::Press => (button_code(button) + 32, false),
+            MouseEventKind::Moved if self.tracking == MouseTracking::AnyMotion => (3 + 32, false),
+            MouseEventKind::ScrollUp => (64, false),
+            MouseEventKind::ScrollDown => (65, false),
+            MouseEventKind::ScrollLeft => (66, false),
+            MouseEventKind::ScrollRight => (67, false),
+            _ => return None,
+        };
+        let mut code = button;
+        if event.modifiers.contains(KeyModifiers::SHIFT) {
+            code += 4;
+        }
+        if event.modifiers.contains(KeyModifiers::ALT) {
+            code += 8;
+        }
+        if event.modifiers.contains(KeyModifiers::CONTROL) {
+            code += 16;
+        }
+
+        let (x, y) = (u32::from(column) + 1, u32::from(row) + 1);
+        if self.sgr {
+            let end = if released { 'm' } else { 'M' };
+            return Some(format!("\x1b[<{};{};{}{}", code, x, y, end).into_bytes());
+        }
+
+        // The legacy format cannot say which button was released, and
+        // offsets every value by 32 in a single byte
+        if released {
+            code = (code & !3) | 3;
+        }
+        let byte = |value: u32| u8::try_from(value + 32).ok();
+        Some(vec![0x1b, b'[', b'M', byte(code)?, byte(x)?, byte(y)?])
+    }
+}
+
+fn button_code(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::{Config, Host, PortForward};
+use log::info;
+use tokio::process::Command;
+
+/// Seconds a master started by sshtui lingers once its last session closed
+const MASTER_PERSIST_SECS: u32 = 60;
+
+/// How a system-backend session uses OpenSSH connection sharing
+pub enum Sharing {
+    /// A master is already listening on the socket; ride on its connection
+    Reuse(String),
+    /// No master yet; start one so later sessions can share this connection.
+    /// It runs in the background, so closing this session is not held up by
+    /// others still using it
+    Master(String),
+}
+
+impl Sharing {
+    /// Options prepended to the ssh command line
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Sharing::Reuse(path) => vec![
+                "-S".to_string(), path.clone(),
+                "-o".to_string(), "ControlMaster=no".to_string(),
+            ],
+            Sharing::Master(path) => vec![
+                "-o".to_string(), "ControlMaster=auto".to_string(),
+                "-o".to_string(), format!("ControlPath={}", path),
+                "-o".to_string(), format!("ControlPersist={}", MASTER_PERSIST_SECS),
+            ],
+        }
+    }
+
+    /// Control socket of a reused master
+    pub fn reused_path(&self) -> Option<&str> {
+        match self {
+            Sharing::Reuse(path) => Some(path),
+            Sharing::Master(_) => None,
+        }
+    }
+}
+
+/// Work out whether a session to `host` can share an existing connection.
+/// The ControlPath comes from the user's ssh config (so masters opened by
+/// plain ssh are found), falling back to sshtui's own socket when sessions
+/// are allowed to become masters.
+pub async fn detect(host: &Host, create_masters: bool) -> Option<Sharing> {
+    let path = match configured_control_path(host).await {
+        Some(path) => path,
+        None if create_masters => default_control_path()?,
+        None => return None,
+    };
+
+    if master_running(host, &path).await {
+        info!("Reusing SSH master connection at {}", path);
+        Some(Sharing::Reuse(path))
+    } else if create_masters {
+        Some(Sharing::Master(path))
+    } else {
+        None
+    }
+}
+
+/// ControlPath the user's ssh config sets for the host, if any
+async fn configured_control_path(host: &Host) -> Option<String> {
+    let output = Command::new("ssh")
+        .arg("-G")
+        .args(destination_args(host))
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("controlpath "))
+        .map(str::trim)
+        .filter(|path| !path.is_empty() && *path != "none")
+        .map(String::from)
+}
+
+/// Socket sshtui uses when the ssh config does not name one; `%C` keeps it
+/// short enough for a Unix socket path
+fn default_control_path() -> Option<String> {
+    let dir = Config::config_dir().ok()?.join("control");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir.join("%C").to_string_lossy().into_owned())
+}
+
+async fn master_running(host: &Host, path: &str) -> bool {
+    Command::new("ssh")
+        .args(["-S", path, "-O", "check"])
+        .args(destination_args(host))
+        .output()
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Open (`operation` "forward") or close ("cancel") a tunnel through a
+/// master's control socket
+pub async fn forward_request(host: &Host, path: &str, operation: &str, forward: &PortForward) -> Result<(), String> {
+    let output = Command::new("ssh")
+        .args(["-S", path, "-O", operation, forward.flag(), &forward.spec()])
+        .args(destination_args(host))
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn destination_args(host: &Host) -> Vec<String> {
+    vec!["-p".to_string(), host.port.to_string(), format!("{}@{}", host.user, host.host)]
+}
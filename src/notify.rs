@@ -0,0 +1,89 @@
+use crate::config::NotificationSettings;
+use chrono::Local;
+use log::{debug, warn};
+use std::process::{Command, Stdio};
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// Deliver a notification through the configured desktop and webhook channels.
+/// During quiet hours anything below `Severity::Error` is dropped.
+pub fn send(settings: &NotificationSettings, severity: Severity, title: &str, body: &str) {
+    if severity < Severity::Error {
+        if let Some(quiet_hours) = &settings.quiet_hours {
+            if quiet_hours.contains(Local::now().time()) {
+                debug!("Quiet hours: suppressed notification '{}'", title);
+                return;
+            }
+        }
+    }
+
+    if settings.desktop {
+        send_desktop(title, body);
+    }
+    if let Some(url) = &settings.webhook_url {
+        send_webhook(url, severity, title, body);
+    }
+}
+
+fn send_desktop(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {:?} with title {:?}",
+            body, title
+        );
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(script);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg("--app-name=sshtui").arg(title).arg(body);
+        command
+    };
+
+    let result = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Err(e) = result {
+        warn!("Failed to show desktop notification: {}", e);
+    }
+}
+
+fn send_webhook(url: &str, severity: Severity, title: &str, body: &str) {
+    let url = url.to_string();
+    let payload = serde_json::json!({
+        "source": "sshtui",
+        "severity": severity.as_str(),
+        "title": title,
+        "body": body,
+        "timestamp": Local::now().to_rfc3339(),
+    })
+    .to_string();
+
+    // Keep network latency off the UI thread
+    thread::spawn(move || {
+        let result = ureq::post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&payload);
+        if let Err(e) = result {
+            warn!("Failed to deliver webhook notification: {}", e);
+        }
+    });
+}
@@ -0,0 +1,126 @@
+use crate::{AppState, FocusArea, MessageType};
+use crate::config::{Host, HostSort};
+use crate::group_tree::VirtualGroup;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+impl AppState {
+    /// Put `hosts` in the order of the host sort setting
+    pub fn sort_hosts(&self, hosts: &mut [Host]) {
+        match self.config.settings.host_sort {
+            HostSort::Manual => {},
+            HostSort::Alphabetical => hosts.sort_by_key(|host| host.name.to_lowercase()),
+            HostSort::Recent => {
+                let mut last_used = HashMap::new();
+                for entry in &self.history.entries {
+                    last_used.insert(entry.host_id, entry.connected_at);
+                }
+                // Hosts never connected to keep their config order at the end
+                hosts.sort_by_key(|host| Reverse(last_used.get(&host.id).copied()));
+            },
+            HostSort::Frequent => {
+                let mut uses: HashMap<Uuid, usize> = HashMap::new();
+                for entry in &self.history.entries {
+                    *uses.entry(entry.host_id).or_default() += 1;
+                }
+                hosts.sort_by_key(|host| Reverse(uses.get(&host.id).copied().unwrap_or(0)));
+            },
+            HostSort::Frecency => {
+                let scores = self.history.frecency();
+                hosts.sort_by_key(|host| Reverse(scores.get(&host.id).copied().unwrap_or(0)));
+            },
+        }
+    }
+
+    /// Move the selected key, group or host one place up or down and save
+    /// the new order
+    pub fn move_selected_item(&mut self, up: bool) {
+        let before = self.snapshot();
+        let moved = match self.focus_area {
+            FocusArea::Keys => {
+                let Some(target) = neighbour(self.selected_key, up, 0, self.config.keys.len()) else {
+                    return;
+                };
+                self.config.keys.swap(self.selected_key, target);
+                self.selected_key = target;
+                true
+            },
+            FocusArea::Groups => {
+                // "All" stays first, and nested groups move among the
+                // other groups of their parent
+                if self.selected_group == 0 {
+                    return;
+                }
+                let parent = self.config.groups[self.selected_group].parent_name().map(str::to_string);
+                let siblings: Vec<usize> = (1..self.config.groups.len())
+                    .filter(|&index| self.config.groups[index].parent_name() == parent.as_deref())
+                    .collect();
+                let Some(position) = siblings.iter().position(|&index| index == self.selected_group) else {
+                    return;
+                };
+                let Some(target) = neighbour(position, up, 0, siblings.len()).map(|position| siblings[position]) else {
+                    return;
+                };
+                self.config.groups.swap(self.selected_group, target);
+                self.selected_group = target;
+                true
+            },
+            FocusArea::Hosts => self.move_selected_host(up),
+        };
+
+        if moved {
+            let what = match self.focus_area {
+                FocusArea::Keys => "key",
+                FocusArea::Groups => "group",
+                FocusArea::Hosts => "host",
+            };
+            self.record_change(before, format!("Moved a {} {}", what, if up { "up" } else { "down" }));
+            if let Err(e) = self.config.save() {
+                self.set_message(format!("Failed to save the new order: {}", e), MessageType::Error);
+            }
+        }
+    }
+
+    fn move_selected_host(&mut self, up: bool) -> bool {
+        if self.virtual_group == Some(VirtualGroup::Recent) {
+            self.set_message("Recent hosts are listed by when they were last connected to".to_string(), MessageType::Info);
+            return false;
+        }
+        if self.config.settings.host_sort != HostSort::Manual {
+            self.set_message("Hosts are sorted automatically; pick manual order in Settings to move them".to_string(), MessageType::Info);
+            return false;
+        }
+        if self.selected_host_header().is_some() {
+            return false;
+        }
+        let hosts = self.visible_hosts();
+        let Some(target) = neighbour(self.selected_host, up, 0, hosts.len()) else {
+            return false;
+        };
+        let (host, other) = (&hosts[self.selected_host], &hosts[target]);
+
+        // Hosts only move within their own group; "m" moves them between groups
+        let Some(group) = self.config.groups.iter_mut()
+            .skip(1)
+            .find(|group| group.hosts.iter().any(|h| h.id == host.id)) else {
+            return false;
+        };
+        let from = group.hosts.iter().position(|h| h.id == host.id);
+        let to = group.hosts.iter().position(|h| h.id == other.id);
+        let (Some(from), Some(to)) = (from, to) else {
+            let message = format!("'{}' is in another group; press m to move hosts between groups", other.name);
+            self.set_message(message, MessageType::Info);
+            return false;
+        };
+        group.hosts.swap(from, to);
+        self.selected_host = target;
+        true
+    }
+}
+
+/// Index next to `index` in a list of `len` items, if it is at least `first`
+fn neighbour(index: usize, up: bool, first: usize, len: usize) -> Option<usize> {
+    let target = if up { index.checked_sub(1)? } else { index + 1 };
+    (target >= first && target < len).then_some(target)
+}
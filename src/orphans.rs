@@ -0,0 +1,98 @@
+use crate::{AppState, MessageType, ModalState, OrphansForm};
+use crate::config::SessionWrapper;
+use crate::session_wrapper::{self, WrappedSession};
+use tokio::sync::oneshot;
+
+/// Wrapped sessions being listed in the background
+pub struct OrphanScan {
+    /// Asked for from the sessions list, so finding none is worth saying
+    requested: bool,
+    receiver: oneshot::Receiver<Vec<WrappedSession>>,
+}
+
+impl AppState {
+    /// Look for tmux or abduco sessions left running by an earlier sshtui
+    pub fn scan_orphaned_sessions(&mut self, requested: bool) {
+        let wrapper = self.config.settings.session_wrapper;
+        if wrapper == SessionWrapper::None {
+            if requested {
+                self.set_message("No background sessions".to_string(), MessageType::Info);
+            }
+            return;
+        }
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let _ = sender.send(session_wrapper::orphaned(wrapper).await);
+        });
+        self.orphan_scan = Some(OrphanScan { requested, receiver });
+    }
+
+    /// Show orphaned sessions once they are listed, and attach to the one
+    /// picked from them
+    pub async fn poll_orphans(&mut self) {
+        if let Some(session) = self.pending_reattach.take() {
+            self.reattach(session).await;
+        }
+
+        let Some(scan) = &mut self.orphan_scan else {
+            return;
+        };
+        let Ok(sessions) = scan.receiver.try_recv() else {
+            return;
+        };
+        let requested = scan.requested;
+        self.orphan_scan = None;
+
+        if sessions.is_empty() {
+            if requested {
+                self.set_message("No background or orphaned sessions".to_string(), MessageType::Info);
+            }
+        } else if self.modal_state == ModalState::None && !self.ssh_client.is_connected() {
+            self.modal_state = ModalState::Orphans(OrphansForm { sessions, cursor: 0 });
+        } else {
+            self.set_message(
+                format!("{} session(s) left running by an earlier sshtui; Ctrl+G lists them", sessions.len()),
+                MessageType::Info
+            );
+        }
+    }
+
+    async fn reattach(&mut self, session: WrappedSession) {
+        if self.ssh_client.is_connecting() || self.ssh_client.is_connected() {
+            self.set_message("Disconnect or detach the live session first".to_string(), MessageType::Error);
+            return;
+        }
+        let host = self.config.get_hosts_for_group(0).into_iter().find(|host| session.is_for(host));
+        let Some(host) = host else {
+            self.set_message(
+                format!("'{}' is no longer in the config; its session is {}", session.host_label(), session.name),
+                MessageType::Error
+            );
+            return;
+        };
+
+        self.ssh_client.attach_to = Some(session);
+        let result = self.connect_to_host(host).await;
+        // Left over when the connection was refused before it started
+        self.ssh_client.attach_to = None;
+        if let Err(e) = result {
+            self.set_message(format!("Could not attach: {}", e), MessageType::Error);
+        }
+    }
+
+    /// End an orphaned session for good and drop it from the list
+    pub fn end_orphan(&mut self, mut form: OrphansForm) {
+        if form.cursor >= form.sessions.len() {
+            return;
+        }
+        let session = form.sessions.remove(form.cursor);
+        self.set_message(format!("Ended session {}", session.name), MessageType::Info);
+        tokio::spawn(async move {
+            session.end().await;
+        });
+        if !form.sessions.is_empty() {
+            form.cursor = form.cursor.min(form.sessions.len() - 1);
+            self.modal_state = ModalState::Orphans(form);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{AppState, MessageType, ModalState, PaletteForm};
+use crate::git_sync::GitAction;
+
+/// Something the app can do, as listed in the action palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteAction {
+    AddItem,
+    EditItem,
+    DeleteItem,
+    GenerateKey,
+    ExportAuthorizedKeys,
+    ExportAnsible,
+    ImportHosts,
+    DuplicateHost,
+    MoveHost,
+    ToggleMosh,
+    ToggleFavorite,
+    FilterHosts,
+    FilterByTag,
+    SearchFacts,
+    RunProbes,
+    CheckGroup,
+    PortForwards,
+    ReconnectAsBefore,
+    RetryConnection,
+    ReopenInSameDirectory,
+    ConnectGroup,
+    Broadcast,
+    BackgroundSessions,
+    ToggleRecording,
+    PlayRecording,
+    Undo,
+    Redo,
+    Trash,
+    Settings,
+    Themes,
+    EditConfig,
+    SwitchProfile,
+    PullConfig,
+    PushConfig,
+    SyncInventory,
+    RefreshEc2,
+}
+
+impl PaletteAction {
+    pub const ALL: [PaletteAction; 36] = [
+        PaletteAction::AddItem,
+        PaletteAction::EditItem,
+        PaletteAction::DeleteItem,
+        PaletteAction::GenerateKey,
+        PaletteAction::ExportAuthorizedKeys,
+        PaletteAction::ExportAnsible,
+        PaletteAction::ImportHosts,
+        PaletteAction::DuplicateHost,
+        PaletteAction::MoveHost,
+        PaletteAction::ToggleMosh,
+        PaletteAction::ToggleFavorite,
+        PaletteAction::FilterHosts,
+        PaletteAction::FilterByTag,
+        PaletteAction::SearchFacts,
+        PaletteAction::RunProbes,
+        PaletteAction::CheckGroup,
+        PaletteAction::PortForwards,
+        PaletteAction::ReconnectAsBefore,
+        PaletteAction::RetryConnection,
+        PaletteAction::ReopenInSameDirectory,
+        PaletteAction::ConnectGroup,
+        PaletteAction::Broadcast,
+        PaletteAction::BackgroundSessions,
+        PaletteAction::ToggleRecording,
+        PaletteAction::PlayRecording,
+        PaletteAction::Undo,
+        PaletteAction::Redo,
+        PaletteAction::Trash,
+        PaletteAction::Settings,
+        PaletteAction::Themes,
+        PaletteAction::EditConfig,
+        PaletteAction::SwitchProfile,
+        PaletteAction::PullConfig,
+        PaletteAction::PushConfig,
+        PaletteAction::SyncInventory,
+        PaletteAction::RefreshEc2,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            PaletteAction::AddItem => "Add key, group or host (focused panel)",
+            PaletteAction::EditItem => "Edit selected key, group or host",
+            PaletteAction::DeleteItem => "Delete selected key, group or host",
+            PaletteAction::GenerateKey => "Generate new SSH key",
+            PaletteAction::ExportAuthorizedKeys => "Export authorized_keys and push to hosts",
+            PaletteAction::ExportAnsible => "Export hosts as an Ansible inventory",
+            PaletteAction::ImportHosts => "Import hosts from Ansible, Termius, PuTTY or SecureCRT",
+            PaletteAction::DuplicateHost => "Duplicate selected host",
+            PaletteAction::MoveHost => "Move or copy host to another group",
+            PaletteAction::ToggleMosh => "Switch host between ssh and mosh",
+            PaletteAction::ToggleFavorite => "Star or unstar host as a favorite",
+            PaletteAction::FilterHosts => "Filter hosts by name",
+            PaletteAction::FilterByTag => "Filter hosts by tag",
+            PaletteAction::SearchFacts => "Search hosts by facts",
+            PaletteAction::RunProbes => "Run host probes now",
+            PaletteAction::CheckGroup => "Check which hosts of the group are reachable",
+            PaletteAction::PortForwards => "Port forwards",
+            PaletteAction::ReconnectAsBefore => "Reconnect to host exactly as last time",
+            PaletteAction::RetryConnection => "Retry last connection",
+            PaletteAction::ReopenInSameDirectory => "Reopen last session in the same directory",
+            PaletteAction::ConnectGroup => "Connect to every host of the group",
+            PaletteAction::Broadcast => "Broadcast a command to the group",
+            PaletteAction::BackgroundSessions => "List background sessions",
+            PaletteAction::ToggleRecording => "Toggle session recording",
+            PaletteAction::PlayRecording => "Play back a recorded session",
+            PaletteAction::Undo => "Undo last change",
+            PaletteAction::Redo => "Redo last undone change",
+            PaletteAction::Trash => "Restore deleted hosts and groups from the trash",
+            PaletteAction::Settings => "Open settings",
+            PaletteAction::Themes => "Pick color theme",
+            PaletteAction::EditConfig => "Edit raw config file",
+            PaletteAction::SwitchProfile => "Switch or create profile",
+            PaletteAction::PullConfig => "Pull config from git remote",
+            PaletteAction::PushConfig => "Push config to git remote",
+            PaletteAction::SyncInventory => "Sync shared host inventory now",
+            PaletteAction::RefreshEc2 => "Refresh the group's EC2 instances",
+        }
+    }
+
+    /// Key that does the same outside the palette, if any
+    pub fn keys(self) -> &'static str {
+        match self {
+            PaletteAction::AddItem => "Ctrl+N",
+            PaletteAction::EditItem | PaletteAction::DeleteItem => "",
+            PaletteAction::GenerateKey => "g",
+            PaletteAction::ExportAuthorizedKeys => "Ctrl+K",
+            PaletteAction::ExportAnsible => "",
+            PaletteAction::ImportHosts => "",
+            PaletteAction::DuplicateHost => "c",
+            PaletteAction::MoveHost => "m",
+            PaletteAction::ToggleMosh => "Shift+M",
+            PaletteAction::ToggleFavorite => "*",
+            PaletteAction::FilterHosts => "f",
+            PaletteAction::FilterByTag => "Ctrl+T",
+            PaletteAction::SearchFacts => "/",
+            PaletteAction::RunProbes => "p",
+            PaletteAction::CheckGroup => "Shift+C",
+            PaletteAction::PortForwards => "Ctrl+F",
+            PaletteAction::ReconnectAsBefore => "Ctrl+R",
+            PaletteAction::RetryConnection => "r",
+            PaletteAction::ReopenInSameDirectory => "Ctrl+O",
+            PaletteAction::ConnectGroup => "Ctrl+A",
+            PaletteAction::Broadcast => "Ctrl+B",
+            PaletteAction::BackgroundSessions => "Ctrl+G",
+            PaletteAction::ToggleRecording => "",
+            PaletteAction::PlayRecording => "Ctrl+P",
+            PaletteAction::Undo => "Ctrl+Z",
+            PaletteAction::Redo => "Ctrl+Shift+Z / Alt+Z",
+            PaletteAction::Trash => "t",
+            PaletteAction::Settings => "Ctrl+S",
+            PaletteAction::Themes => "Ctrl+Y",
+            PaletteAction::EditConfig => "Ctrl+X",
+            PaletteAction::SwitchProfile => "Ctrl+E",
+            PaletteAction::PullConfig | PaletteAction::PushConfig | PaletteAction::SyncInventory | PaletteAction::RefreshEc2 => "",
+        }
+    }
+}
+
+/// How well `query` matches `title`, lower being better: a prefix of the
+/// title, then of one of its words, then anywhere in it, then its letters
+/// in order ("gnk" finds "Generate new SSH key")
+fn score(query: &str, title: &str) -> Option<usize> {
+    let title = title.to_lowercase();
+    if title.starts_with(query) {
+        return Some(0);
+    }
+    if title.split_whitespace().any(|word| word.starts_with(query)) {
+        return Some(1);
+    }
+    if title.contains(query) {
+        return Some(2);
+    }
+    let mut letters = title.chars();
+    let in_order = query.chars().filter(|c| !c.is_whitespace()).all(|c| letters.any(|t| t == c));
+    in_order.then_some(3)
+}
+
+impl AppState {
+    pub fn open_palette(&mut self) {
+        self.modal_state = ModalState::Palette(PaletteForm { query: String::new(), cursor: 0 });
+    }
+
+    /// Actions matching the query, best first
+    pub fn palette_matches(&self, query: &str) -> Vec<PaletteAction> {
+        let query = query.trim().to_lowercase();
+        let mut matches: Vec<(usize, PaletteAction)> = PaletteAction::ALL.iter()
+            .filter_map(|action| score(&query, action.title()).map(|score| (score, *action)))
+            .collect();
+        // Stable, so equal matches keep the order of the list
+        matches.sort_by_key(|(score, _)| *score);
+        matches.into_iter().map(|(_, action)| action).collect()
+    }
+
+    /// Pick the highlighted action; it runs on the next tick
+    pub fn choose_palette_action(&mut self, form: PaletteForm) {
+        self.modal_state = ModalState::None;
+        self.pending_action = self.palette_matches(&form.query).get(form.cursor).copied();
+    }
+
+    /// Run the action picked from the palette
+    pub async fn poll_palette(&mut self) {
+        let Some(action) = self.pending_action.take() else {
+            return;
+        };
+        match action {
+            PaletteAction::AddItem => self.handle_add_button_press().await,
+            PaletteAction::EditItem => self.handle_edit_button_press().await,
+            PaletteAction::DeleteItem => self.handle_delete_button_press().await,
+            PaletteAction::GenerateKey => self.open_key_generation(),
+            PaletteAction::ExportAuthorizedKeys => self.open_authorized_keys(),
+            PaletteAction::ExportAnsible => self.export_ansible_inventory(),
+            PaletteAction::ImportHosts => self.open_import(),
+            PaletteAction::DuplicateHost => self.duplicate_selected_host(),
+            PaletteAction::MoveHost => self.open_move_host(),
+            PaletteAction::ToggleMosh => self.toggle_selected_host_mosh(),
+            PaletteAction::ToggleFavorite => self.toggle_selected_host_favorite(),
+            PaletteAction::FilterHosts => self.open_host_filter(),
+            PaletteAction::FilterByTag => self.open_tag_filter(),
+            PaletteAction::SearchFacts => self.open_facts(),
+            PaletteAction::RunProbes => self.refresh_probes(),
+            PaletteAction::CheckGroup => self.start_sweep(),
+            PaletteAction::PortForwards => self.open_forwards(),
+            PaletteAction::ReconnectAsBefore => self.reconnect_as_before().await,
+            PaletteAction::RetryConnection => self.retry_last_connection().await,
+            PaletteAction::ReopenInSameDirectory => self.reopen_in_same_directory().await,
+            PaletteAction::ConnectGroup => self.start_group_connect(),
+            PaletteAction::Broadcast => self.open_broadcasts(),
+            PaletteAction::BackgroundSessions => self.open_sessions(),
+            PaletteAction::ToggleRecording => self.toggle_recording(),
+            PaletteAction::PlayRecording => self.open_recordings(),
+            PaletteAction::Undo => self.undo_change(),
+            PaletteAction::Redo => self.redo_change(),
+            PaletteAction::Trash => self.open_trash(),
+            PaletteAction::Settings => self.open_settings(),
+            PaletteAction::Themes => self.open_themes(),
+            PaletteAction::EditConfig => self.open_config_editor(),
+            PaletteAction::SwitchProfile => self.open_profiles(),
+            PaletteAction::PullConfig => self.start_git_sync(GitAction::Pull),
+            PaletteAction::PushConfig => self.start_git_sync(GitAction::Push),
+            PaletteAction::SyncInventory => self.sync_inventory(),
+            PaletteAction::RefreshEc2 => self.refresh_ec2_group(),
+        }
+    }
+
+    /// Turn recording of the sessions opened from now on on or off
+    fn toggle_recording(&mut self) {
+        let record = !self.config.settings.record_sessions;
+        self.config.settings.record_sessions = record;
+        let _ = self.config.save();
+        let state = if record { "on" } else { "off" };
+        self.set_message(format!("Session recording turned {}", state), MessageType::Info);
+    }
+}
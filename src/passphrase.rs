@@ -0,0 +1,42 @@
+use anyhow::{bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use sshtui::config_crypto::PASSPHRASE_ENV;
+use std::io::Write;
+
+/// Read a passphrase from the terminal without echoing it, or from
+/// SSHTUI_CONFIG_PASSPHRASE when set
+pub fn prompt(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    eprint!("{}: ", prompt);
+    std::io::stderr().flush()?;
+    enable_raw_mode()?;
+    let result = read_hidden_line();
+    disable_raw_mode()?;
+    eprintln!();
+    result
+}
+
+fn read_hidden_line() -> Result<String> {
+    let mut line = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match (key.code, key.modifiers) {
+            (KeyCode::Enter, _) => return Ok(line),
+            (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => bail!("Cancelled"),
+            (KeyCode::Backspace, _) => {
+                line.pop();
+            },
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => line.push(c),
+            _ => {}
+        }
+    }
+}
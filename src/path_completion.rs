@@ -0,0 +1,136 @@
+use crate::{AppState, ModalState};
+use crate::line_editor::LineEditor;
+use crate::ssh::expand_key_path;
+use crossterm::event::KeyCode;
+
+/// Where Tab starts looking when the field is empty
+const KEY_DIRECTORY: &str = "~/.ssh/";
+
+/// Files in ~/.ssh that are never private keys
+const NOT_KEYS: [&str; 5] = ["authorized_keys", "config", "environment", "known_hosts", "known_hosts.old"];
+
+/// The paths `text` could be completed to, sorted, with `~` kept as typed
+/// and a `/` after directories. `keys_only` leaves out public keys and the
+/// other files ssh keeps next to private ones.
+pub fn complete(text: &str, keys_only: bool) -> Vec<String> {
+    let text = match text {
+        "" => KEY_DIRECTORY,
+        "~" => "~/",
+        text => text,
+    };
+    let (dir, prefix) = match text.rfind('/') {
+        Some(index) => text.split_at(index + 1),
+        None => ("", text),
+    };
+    let listed = if dir.is_empty() { ".".to_string() } else { expand_key_path(dir) };
+    let Ok(entries) = std::fs::read_dir(listed) else {
+        return Vec::new();
+    };
+
+    let mut choices: Vec<String> = entries.flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            if keys_only && !is_dir && (name.ends_with(".pub") || NOT_KEYS.contains(&name.as_str())) {
+                return None;
+            }
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    choices.sort();
+    choices
+}
+
+/// The longest text every choice starts with
+fn common_prefix(choices: &[String]) -> String {
+    let Some(first) = choices.first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for choice in &choices[1..] {
+        let same = prefix.char_indices()
+            .zip(choice.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(choice.len()), |((index, _), _)| index);
+        prefix = &prefix[..same];
+    }
+    prefix.to_string()
+}
+
+impl AppState {
+    /// The focused form field that takes a file path, and whether that is
+    /// a private key
+    fn focused_path(&mut self) -> Option<(&mut LineEditor, &mut String, bool)> {
+        match &mut self.modal_state {
+            ModalState::AddKey(form) | ModalState::EditKey(_, form) if form.field_focus == 1 => {
+                Some((&mut form.editor, &mut form.path, true))
+            },
+            // A new key's path names a file that is not there yet
+            ModalState::GenerateKey(form) if form.field_focus == 1 => Some((&mut form.editor, &mut form.path, false)),
+            ModalState::AddGroup(form) | ModalState::EditGroup(_, form) if form.field_focus == 4 => {
+                Some((&mut form.editor, &mut form.key_path, true))
+            },
+            ModalState::AddHost(form) | ModalState::EditHost(_, form) if form.field_focus == 4 && !form.use_key_selector => {
+                Some((&mut form.editor, &mut form.key_path, true))
+            },
+            ModalState::Scp(form) if form.field_focus == 0 => Some((&mut form.editor, &mut form.local, false)),
+            ModalState::Import(form) if form.field_focus == 1 => Some((&mut form.editor, &mut form.path, false)),
+            _ => None,
+        }
+    }
+
+    /// Tab in a path field completes it, listing the choices in a dropdown
+    /// when there are several; ↑/↓ or Tab pick one and Enter takes it.
+    /// Returns false for keys the form should handle as usual, such as Tab
+    /// once there is nothing left to complete.
+    pub fn handle_path_completion_key(&mut self, key: KeyCode) -> bool {
+        let Some((editor, text, keys_only)) = self.focused_path() else {
+            return false;
+        };
+
+        if let Some(completion) = editor.completion_mut() {
+            let count = completion.choices.len();
+            match key {
+                KeyCode::Tab | KeyCode::Down => completion.selected = (completion.selected + 1) % count,
+                KeyCode::BackTab | KeyCode::Up => completion.selected = (completion.selected + count - 1) % count,
+                KeyCode::Enter => {
+                    *text = completion.choices[completion.selected].clone();
+                    editor.reset();
+                },
+                KeyCode::Esc => editor.close_completion(),
+                _ => {
+                    editor.close_completion();
+                    return false;
+                },
+            }
+            return true;
+        }
+
+        if key != KeyCode::Tab {
+            return false;
+        }
+        let choices = complete(text, keys_only);
+        match choices.as_slice() {
+            [] => false,
+            [only] if only == text => false,
+            [only] => {
+                *text = only.clone();
+                editor.reset();
+                true
+            },
+            _ => {
+                let prefix = common_prefix(&choices);
+                if prefix.len() > text.len() {
+                    *text = prefix;
+                }
+                editor.reset();
+                editor.offer(choices);
+                true
+            },
+        }
+    }
+}
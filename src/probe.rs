@@ -0,0 +1,296 @@
+use crate::{AppState, FocusArea, MessageType};
+use crate::broadcast;
+use crate::config::{Host, ProbeConfig};
+use async_trait::async_trait;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+/// How long a TCP connect may take before a host counts as unreachable
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long an HTTP check, ssh command or custom command may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the probes configured on hosts are run
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Check whether a host accepts TCP connections on its SSH port
+async fn is_reachable(host: &str, port: u16) -> bool {
+    matches!(
+        tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host, port))).await,
+        Ok(Ok(_))
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeStatus {
+    Up,
+    /// Answering, but not as expected (e.g. a custom command exiting 1)
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub status: ProbeStatus,
+    /// One line explaining the status
+    pub detail: String,
+}
+
+impl ProbeResult {
+    fn new(status: ProbeStatus, detail: impl Into<String>) -> Self {
+        Self { status, detail: detail.into() }
+    }
+}
+
+/// Latest result of one of a host's probes
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    pub label: String,
+    pub result: ProbeResult,
+    pub checked_at: Instant,
+}
+
+/// A check run against a host on a schedule, shown as a badge next to it.
+/// `host` has its group defaults filled in.
+#[async_trait]
+pub trait Probe: Send + Sync {
+    /// Short name on the badge
+    fn label(&self) -> String;
+
+    async fn run(&self, host: &Host) -> ProbeResult;
+}
+
+/// TCP connect to the SSH port, or another port
+pub struct TcpPing {
+    pub port: Option<u16>,
+}
+
+#[async_trait]
+impl Probe for TcpPing {
+    fn label(&self) -> String {
+        match self.port {
+            Some(port) => format!("tcp:{}", port),
+            None => "tcp".to_string(),
+        }
+    }
+
+    async fn run(&self, host: &Host) -> ProbeResult {
+        let port = self.port.unwrap_or(host.port);
+        let started = Instant::now();
+        if is_reachable(&host.host, port).await {
+            ProbeResult::new(ProbeStatus::Up, format!("port {} open in {} ms", port, started.elapsed().as_millis()))
+        } else {
+            ProbeResult::new(ProbeStatus::Down, format!("port {} unreachable", port))
+        }
+    }
+}
+
+/// Command run on the host over a non-interactive ssh; up when it exits 0
+pub struct SshExec {
+    pub command: String,
+    pub key_path: Option<String>,
+    /// Extra `-o` options from the host's group
+    pub options: Vec<String>,
+}
+
+#[async_trait]
+impl Probe for SshExec {
+    fn label(&self) -> String {
+        "ssh".to_string()
+    }
+
+    async fn run(&self, host: &Host) -> ProbeResult {
+        let result = broadcast::run_on_host(host.clone(), self.key_path.clone(), self.options.clone(), self.command.clone(), CHECK_TIMEOUT).await;
+        let detail = first_line(&result.output);
+        match result.exit_code {
+            Some(0) => ProbeResult::new(ProbeStatus::Up, detail),
+            Some(code) => ProbeResult::new(ProbeStatus::Down, format!("exit {}: {}", code, detail)),
+            None => ProbeResult::new(ProbeStatus::Down, detail),
+        }
+    }
+}
+
+/// HTTP GET; up on the expected status, or any non-error status without one
+pub struct HttpCheck {
+    pub url: String,
+    pub status: Option<u16>,
+}
+
+#[async_trait]
+impl Probe for HttpCheck {
+    fn label(&self) -> String {
+        "http".to_string()
+    }
+
+    async fn run(&self, host: &Host) -> ProbeResult {
+        let url = self.url.replace("{host}", &host.host);
+        let expected = self.status;
+        let response = tokio::task::spawn_blocking(move || {
+            match ureq::get(&url).timeout(CHECK_TIMEOUT).call() {
+                Ok(response) => Ok(response.status()),
+                Err(ureq::Error::Status(status, _)) => Ok(status),
+                Err(e) => Err(e.to_string()),
+            }
+        }).await;
+
+        match response {
+            Ok(Ok(status)) if expected.map_or(status < 400, |expected| status == expected) => {
+                ProbeResult::new(ProbeStatus::Up, format!("HTTP {}", status))
+            },
+            Ok(Ok(status)) => ProbeResult::new(ProbeStatus::Degraded, format!("HTTP {}", status)),
+            Ok(Err(e)) => ProbeResult::new(ProbeStatus::Down, e),
+            Err(e) => ProbeResult::new(ProbeStatus::Down, e.to_string()),
+        }
+    }
+}
+
+/// User-defined check: a local shell command with `SSHTUI_HOST`,
+/// `SSHTUI_PORT` and `SSHTUI_USER` set. Exit 0 is up, 1 degraded, anything
+/// else down; the first line of output is the detail.
+pub struct CustomCommand {
+    pub command: String,
+}
+
+#[async_trait]
+impl Probe for CustomCommand {
+    fn label(&self) -> String {
+        self.command.split_whitespace().next().unwrap_or("cmd").to_string()
+    }
+
+    async fn run(&self, host: &Host) -> ProbeResult {
+        let output = Command::new("sh")
+            .args(["-c", &self.command])
+            .env("SSHTUI_HOST", &host.host)
+            .env("SSHTUI_PORT", host.port.to_string())
+            .env("SSHTUI_USER", &host.user)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+
+        match tokio::time::timeout(CHECK_TIMEOUT, output).await {
+            Ok(Ok(output)) => {
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                let status = match output.status.code() {
+                    Some(0) => ProbeStatus::Up,
+                    Some(1) => ProbeStatus::Degraded,
+                    _ => ProbeStatus::Down,
+                };
+                ProbeResult::new(status, first_line(&text))
+            },
+            Ok(Err(e)) => ProbeResult::new(ProbeStatus::Down, format!("Could not run: {}", e)),
+            Err(_) => ProbeResult::new(ProbeStatus::Down, "timed out"),
+        }
+    }
+}
+
+/// Gives a probe the name set in the config instead of its own label
+struct Named {
+    name: String,
+    probe: Box<dyn Probe>,
+}
+
+#[async_trait]
+impl Probe for Named {
+    fn label(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn run(&self, host: &Host) -> ProbeResult {
+        self.probe.run(host).await
+    }
+}
+
+fn first_line(text: &str) -> String {
+    text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default().to_string()
+}
+
+impl AppState {
+    /// The probes configured on a host, ready to run
+    fn probes_for(&self, host: &Host) -> Vec<Box<dyn Probe>> {
+        host.probes.iter()
+            .map(|config| {
+                let (name, probe): (_, Box<dyn Probe>) = match config {
+                    ProbeConfig::TcpPing { name, port } => (name, Box::new(TcpPing { port: *port })),
+                    ProbeConfig::SshExec { name, command } => {
+                        let options = self.config.group_of(host)
+                            .map(|group| group.defaults.options.clone())
+                            .unwrap_or_default();
+                        let key_path = self.config.effective_host(host).key_path
+                            .or_else(|| self.config.get_default_key().map(|key| key.path.clone()));
+                        (name, Box::new(SshExec { command: command.clone(), key_path, options }))
+                    },
+                    ProbeConfig::HttpCheck { name, url, status } => {
+                        (name, Box::new(HttpCheck { url: url.clone(), status: *status }))
+                    },
+                    ProbeConfig::Command { name, command } => (name, Box::new(CustomCommand { command: command.clone() })),
+                };
+                match name {
+                    Some(name) => Box::new(Named { name: name.clone(), probe }),
+                    None => probe,
+                }
+            })
+            .collect()
+    }
+
+    /// Collect finished probe results and run every host's probes again once
+    /// the interval has passed
+    pub fn poll_probes(&mut self) {
+        while let Ok((host_id, index, report)) = self.probe_receiver.try_recv() {
+            self.probes_running.remove(&(host_id, index));
+            let reports = self.probe_reports.entry(host_id).or_default();
+            if reports.len() <= index {
+                reports.resize(index + 1, None);
+            }
+            reports[index] = Some(report);
+        }
+
+        // Nothing is probed until the inventory is loaded and the hosts are looked at
+        if self.loading_for().is_some() {
+            return;
+        }
+        if self.probes_deferred {
+            if self.focus_area != FocusArea::Hosts {
+                return;
+            }
+            self.probes_deferred = false;
+        }
+        if self.last_probe_run.is_some_and(|run| run.elapsed() < PROBE_INTERVAL) {
+            return;
+        }
+        self.last_probe_run = Some(Instant::now());
+
+        for host in self.config.get_hosts_for_group(0) {
+            if host.probes.is_empty() {
+                self.probe_reports.remove(&host.id);
+                continue;
+            }
+            let probes = self.probes_for(&host);
+            let host = self.config.effective_host(&host);
+            self.probe_reports.entry(host.id).or_default().truncate(probes.len());
+            for (index, probe) in probes.into_iter().enumerate() {
+                // A probe still waiting on a slow host is not started twice
+                if !self.probes_running.insert((host.id, index)) {
+                    continue;
+                }
+                let host = host.clone();
+                let sender = self.probe_sender.clone();
+                tokio::spawn(async move {
+                    let result = probe.run(&host).await;
+                    let report = ProbeReport { label: probe.label(), result, checked_at: Instant::now() };
+                    let _ = sender.send((host.id, index, report));
+                });
+            }
+        }
+    }
+
+    /// Run the probes now instead of waiting for the interval
+    pub fn refresh_probes(&mut self) {
+        self.last_probe_run = None;
+        self.probes_deferred = false;
+        self.set_message("Running host probes...".to_string(), MessageType::Info);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{AppState, MessageType, ModalState, ProfilesForm};
+use crate::config::Config;
+use crate::config_crypto;
+
+impl AppState {
+    pub fn open_profiles(&mut self) {
+        let profiles = Config::profiles();
+        let current = Config::profile();
+        let cursor = profiles.iter().position(|name| *name == current).unwrap_or(0);
+        self.modal_state = ModalState::Profiles(ProfilesForm { profiles, cursor, new_name: String::new() });
+    }
+
+    /// Ask which profile to open when there is more than the default one
+    pub fn offer_profiles(&mut self) {
+        if Config::profiles().len() > 1 && matches!(self.modal_state, ModalState::None) {
+            self.open_profiles();
+        }
+    }
+
+    /// Open the highlighted profile, or create the one named on the last
+    /// row with the current settings and no keys, groups or hosts
+    pub fn choose_profile(&mut self, form: ProfilesForm) {
+        let result = match form.profiles.get(form.cursor) {
+            Some(name) => self.open_profile(name),
+            None => self.create_profile(&form),
+        };
+        match result {
+            Ok(message) => {
+                self.modal_state = ModalState::None;
+                self.set_message(message, MessageType::Success);
+            },
+            Err(e) => self.set_message(e, MessageType::Error),
+        }
+    }
+
+    /// Sessions and keys belong to the profile they were opened from
+    fn check_can_switch_profile(&self) -> Result<(), String> {
+        if self.ssh_client.is_connected() || self.ssh_client.is_connecting() || !self.background_sessions.is_empty() {
+            return Err("Close every session before switching profiles".to_string());
+        }
+        if config_crypto::is_enabled() {
+            return Err("The profile is encrypted; start sshtui with --profile to open another".to_string());
+        }
+        Ok(())
+    }
+
+    fn open_profile(&mut self, name: &str) -> Result<String, String> {
+        if name == Config::profile() {
+            return Ok(format!("Profile '{}' is already open", name));
+        }
+        self.check_can_switch_profile()?;
+        let config = Config::load_profile(name).map_err(|e| e.to_string())?.unwrap_or_default();
+        Config::set_profile(name);
+        self.use_config(config);
+        Ok(format!("Switched to profile '{}'", name))
+    }
+
+    fn create_profile(&mut self, form: &ProfilesForm) -> Result<String, String> {
+        let name = form.new_name.trim();
+        if !Config::is_valid_profile_name(name) {
+            return Err("Profile names take letters, digits, '-' and '_'".to_string());
+        }
+        if form.profiles.iter().any(|profile| profile == name) {
+            return Err(format!("Profile '{}' already exists", name));
+        }
+        self.check_can_switch_profile()?;
+        let config = Config { settings: self.config.settings.clone(), ..Config::default() };
+        let previous = Config::profile();
+        Config::set_profile(name);
+        if let Err(e) = config.save() {
+            Config::set_profile(&previous);
+            return Err(e.to_string());
+        }
+        self.use_config(config);
+        Ok(format!("Created profile '{}'", name))
+    }
+
+    /// Show another profile's keys, groups and hosts, or ones pulled from
+    /// elsewhere, forgetting what was picked, filtered or checked before
+    pub fn use_config(&mut self, config: Config) {
+        self.config = config;
+        self.selected_key = 0;
+        self.selected_group = 0;
+        self.virtual_group = None;
+        self.selected_host = 0;
+        self.host_header = None;
+        self.collapsed_groups.clear();
+        self.collapsed_folders.clear();
+        self.host_filter.clear();
+        self.host_filter_editing = false;
+        self.tag_filter.clear();
+        self.reachability.clear();
+        self.probe_reports.clear();
+        self.last_probe_run = None;
+        self.command_widgets.clear();
+        self.clear_history();
+        self.apply_theme();
+    }
+}
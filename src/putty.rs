@@ -0,0 +1,251 @@
+use crate::config::{ConnectionKind, Group, Host};
+use crate::import::{self, Importer};
+use crate::ssh::expand_key_path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// PuTTY has no groups, so its sessions go in one named after it
+const GROUP: &str = "PuTTY";
+
+/// Where PuTTY keeps sessions in the Windows registry
+const SESSIONS_KEY: &str = "\\Software\\SimonTatham\\PuTTY\\Sessions\\";
+
+/// Reads PuTTY sessions: a `reg export` of them from Windows, or the
+/// ~/.putty/sessions directory PuTTY uses elsewhere
+pub struct PuttySessions;
+
+/// Session names are stored with spaces and other characters as %XX
+fn unescape(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes.get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A registry value as text: strings unquoted, dwords as decimal numbers
+fn reg_value(value: &str) -> Option<String> {
+    if let Some(hex) = value.strip_prefix("dword:") {
+        return u32::from_str_radix(hex.trim(), 16).ok().map(|number| number.to_string());
+    }
+    let text = value.strip_prefix('"')?.strip_suffix('"')?;
+    Some(text.replace("\\\\", "\\").replace("\\\"", "\""))
+}
+
+/// The sessions of a .reg file, by name
+fn parse_reg(text: &str) -> Vec<(String, HashMap<String, String>)> {
+    let mut sessions: Vec<(String, HashMap<String, String>)> = Vec::new();
+    let mut in_session = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(key) = line.strip_prefix('[').and_then(|key| key.strip_suffix(']')) {
+            let name = key.split_once(SESSIONS_KEY).map(|(_, name)| name).filter(|name| !name.contains('\\'));
+            in_session = name.is_some();
+            if let Some(name) = name {
+                sessions.push((unescape(name), HashMap::new()));
+            }
+            continue;
+        }
+        if !in_session {
+            continue;
+        }
+        let Some((_, settings)) = sessions.last_mut() else {
+            continue;
+        };
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        if let (Some(name), Some(value)) = (reg_value(name), reg_value(value)) {
+            settings.insert(name, value);
+        }
+    }
+    sessions
+}
+
+/// Export files from `reg export` are UTF-16 with a byte order mark
+fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xff, 0xfe, rest @ ..] => {
+            let units: Vec<u16> = rest.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        },
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// A saved session as a host; None for the defaults PuTTY keeps as a
+/// session, sessions without a host name and protocols other than ssh and
+/// telnet. PuTTY's .ppk keys are left out, since ssh cannot read them.
+fn session_host(name: &str, settings: &HashMap<String, String>) -> Option<Host> {
+    if name == "Default Settings" {
+        return None;
+    }
+    let setting = |key: &str| settings.get(key).map(String::as_str).unwrap_or("");
+    let kind = match setting("Protocol") {
+        "" | "ssh" => ConnectionKind::Ssh,
+        "telnet" => ConnectionKind::Telnet,
+        _ => return None,
+    };
+    // The host name may carry the user, as in "root@example.com"
+    let (user, address) = match setting("HostName").trim().rsplit_once('@') {
+        Some((user, address)) => (user, address),
+        None => (setting("UserName"), setting("HostName").trim()),
+    };
+    if address.is_empty() {
+        return None;
+    }
+    let key_path = Some(setting("PublicKeyFile").to_string())
+        .filter(|path| !path.is_empty() && !path.to_ascii_lowercase().ends_with(".ppk"));
+    Some(Host {
+        user: user.to_string(),
+        port: setting("PortNumber").parse().unwrap_or(0),
+        key_path,
+        kind,
+        ..Host::new(name, address)
+    })
+}
+
+impl Importer for PuttySessions {
+    fn parse(&self, _path: &str, text: &str) -> Result<Vec<Group>, String> {
+        let sessions = parse_reg(text);
+        if sessions.is_empty() && !text.contains(SESSIONS_KEY) {
+            return Err("No PuTTY sessions in the file; export HKEY_CURRENT_USER\\Software\\SimonTatham\\PuTTY\\Sessions".to_string());
+        }
+        let hosts = sessions.iter()
+            .filter_map(|(name, settings)| session_host(name, settings))
+            .map(|host| (GROUP.to_string(), host))
+            .collect();
+        Ok(import::into_groups(hosts))
+    }
+
+    /// A directory holds a file of `Key=Value` lines per session, as
+    /// ~/.putty/sessions does; anything else is read as a .reg export
+    fn read(&self, path: &str) -> Result<Vec<Group>, String> {
+        let full_path = expand_key_path(path);
+        if !Path::new(&full_path).is_dir() {
+            let bytes = fs::read(&full_path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+            return self.parse(path, &decode(&bytes));
+        }
+
+        let entries = fs::read_dir(&full_path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        let mut sessions: Vec<(String, HashMap<String, String>)> = entries.flatten()
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| {
+                let text = fs::read_to_string(entry.path()).ok()?;
+                let settings = text.lines()
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect();
+                Some((unescape(&entry.file_name().to_string_lossy()), settings))
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.0.cmp(&b.0));
+        let hosts = sessions.iter()
+            .filter_map(|(name, settings)| session_host(name, settings))
+            .map(|host| (GROUP.to_string(), host))
+            .collect();
+        Ok(import::into_groups(hosts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions]
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\Default%20Settings]
+"HostName"=""
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\web%20server]
+"HostName"="deploy@web.example.com"
+"PortNumber"=dword:00000916
+"Protocol"="ssh"
+"PublicKeyFile"="C:\\keys\\web.ppk"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\switch]
+"HostName"="10.0.0.2"
+"UserName"="admin"
+"Protocol"="telnet"
+"PublicKeyFile"="/home/me/.ssh/id_ed25519"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\console]
+"HostName"="COM1"
+"Protocol"="serial"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\switch\Nested]
+"HostName"="ignored"
+"#;
+
+    #[test]
+    fn unescapes_session_names() {
+        assert_eq!(unescape("web%20server"), "web server");
+        assert_eq!(unescape("100%25%2Fdone"), "100%/done");
+        // A stray % is kept as it is
+        assert_eq!(unescape("50%"), "50%");
+        assert_eq!(unescape("%zz"), "%zz");
+    }
+
+    #[test]
+    fn reads_registry_values() {
+        assert_eq!(reg_value("\"C:\\\\keys\\\\a \\\"b\\\"\"").as_deref(), Some("C:\\keys\\a \"b\""));
+        assert_eq!(reg_value("dword:00000016").as_deref(), Some("22"));
+        assert_eq!(reg_value("hex:01,02"), None);
+    }
+
+    #[test]
+    fn parses_sessions_of_a_reg_export() {
+        let sessions = parse_reg(EXPORT);
+        let names: Vec<&str> = sessions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Default Settings", "web server", "switch", "console"]);
+        assert_eq!(sessions[1].1["PortNumber"], "2326");
+        assert_eq!(sessions[1].1["PublicKeyFile"], "C:\\keys\\web.ppk");
+        // The nested key's value must not land in the switch session
+        assert_eq!(sessions[2].1["HostName"], "10.0.0.2");
+    }
+
+    #[test]
+    fn imports_ssh_and_telnet_sessions() {
+        let groups = PuttySessions.parse("sessions.reg", EXPORT).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, GROUP);
+        let hosts = &groups[0].hosts;
+        assert_eq!(hosts.len(), 2);
+        assert_eq!((hosts[0].name.as_str(), hosts[0].user.as_str(), hosts[0].host.as_str()), ("web server", "deploy", "web.example.com"));
+        assert_eq!(hosts[0].port, 2326);
+        assert_eq!(hosts[0].key_path, None);
+        assert_eq!(hosts[1].kind, ConnectionKind::Telnet);
+        assert_eq!(hosts[1].user, "admin");
+        assert_eq!(hosts[1].key_path.as_deref(), Some("/home/me/.ssh/id_ed25519"));
+    }
+
+    #[test]
+    fn decodes_utf16_exports() {
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend("[a]".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode(&bytes), "[a]");
+        assert_eq!(decode(b"\xef\xbb\xbf[a]"), "[a]");
+    }
+
+    #[test]
+    fn rejects_files_without_sessions() {
+        assert!(PuttySessions.parse("other.reg", "[HKEY_CURRENT_USER\\Software\\Other]\n").is_err());
+    }
+}
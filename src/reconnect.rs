@@ -0,0 +1,94 @@
+use crate::{AppState, MessageType};
+use std::time::{Duration, Instant};
+
+/// Attempts made before giving up on a dropped session
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first attempt; doubled for every following one
+const BASE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectState {
+    pub attempt: u32,
+    /// When the next attempt starts; `None` while an attempt is in flight
+    pub next_attempt_at: Option<Instant>,
+}
+
+impl AppState {
+    /// Queue the next reconnect attempt after a session dropped unexpectedly
+    pub fn schedule_reconnect(&mut self) {
+        if !self.config.settings.auto_reconnect || self.ssh_client.profile.is_none() {
+            return;
+        }
+
+        let attempt = self.reconnect.map_or(1, |state| state.attempt + 1);
+        if attempt > MAX_ATTEMPTS {
+            self.reconnect = None;
+            self.terminal_panel.set_banner(None);
+            self.set_message(
+                format!("Gave up reconnecting after {} attempts (press r to retry)", MAX_ATTEMPTS),
+                MessageType::Error
+            );
+            return;
+        }
+
+        let delay = BASE_DELAY * 2u32.pow(attempt - 1);
+        self.reconnect = Some(ReconnectState {
+            attempt,
+            next_attempt_at: Some(Instant::now() + delay),
+        });
+        self.terminal_panel.set_banner(Some(format!("Reconnecting… attempt {}/{}", attempt, MAX_ATTEMPTS)));
+    }
+
+    /// Start the pending reconnect attempt once its backoff delay has passed
+    pub async fn poll_reconnect(&mut self) {
+        let Some(state) = self.reconnect else {
+            return;
+        };
+        let Some(next_attempt_at) = state.next_attempt_at else {
+            return;
+        };
+        if Instant::now() < next_attempt_at || self.ssh_client.is_connecting() || self.ssh_client.is_connected() {
+            return;
+        }
+
+        self.reconnect = Some(ReconnectState { next_attempt_at: None, ..state });
+        if let Some(profile) = self.ssh_client.profile.clone() {
+            let _ = self.connect_with_profile(profile).await;
+        }
+
+        // The attempt could not even be started; back off and try again
+        if !self.ssh_client.is_connecting() {
+            self.schedule_reconnect();
+        }
+    }
+
+    /// Called once a session got past its login; clears any reconnect in
+    /// progress, so attempts that never log in keep backing off
+    pub fn finish_reconnect(&mut self) {
+        if let Some(state) = self.reconnect.take() {
+            self.terminal_panel.set_banner(None);
+            self.set_message(
+                format!("Reconnected after {} attempt(s)", state.attempt),
+                MessageType::Success
+            );
+        }
+    }
+
+    pub fn cancel_reconnect(&mut self) {
+        self.reconnect = None;
+        self.terminal_panel.set_banner(None);
+    }
+
+    /// Manually retry the most recent connection
+    pub async fn retry_last_connection(&mut self) {
+        self.cancel_reconnect();
+        match self.ssh_client.profile.clone() {
+            Some(profile) => {
+                let _ = self.connect_with_profile(profile).await;
+            },
+            None => {
+                self.set_message("No previous connection to retry".to_string(), MessageType::Error);
+            }
+        }
+    }
+}
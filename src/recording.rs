@@ -0,0 +1,294 @@
+use crate::{AppState, MessageType, ModalState, RecordingsForm};
+use crate::config::{Config, Host};
+use crate::terminal_panel::RawTerminalPanel;
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use crossterm::event::KeyCode;
+use ratatui::layout::Rect;
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Seconds skipped by Left/Right during playback
+const SEEK_STEP: f64 = 5.0;
+
+/// Writes a live session's output as an asciicast v2 file
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+    /// Start of a UTF-8 sequence split across two reads
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn start(host: &Host, width: u16, height: u16) -> Result<Self> {
+        let dir = recordings_dir()?;
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create recordings directory: {:?}", dir))?;
+
+        let name: String = host.name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}-{}.cast", name, Local::now().format("%Y%m%d-%H%M%S")));
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create recording: {:?}", path))?;
+
+        let mut writer = BufWriter::new(file);
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": Local::now().timestamp(),
+            "title": host.name,
+            "env": { "TERM": host.term.as_deref().unwrap_or("xterm-256color") },
+        });
+        writeln!(writer, "{}", header)?;
+        Ok(Self { writer, started: Instant::now(), pending: Vec::new() })
+    }
+
+    /// Append session output
+    pub fn output(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // Keep an incomplete trailing sequence for the next read
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let bytes: Vec<u8> = self.pending.drain(..complete).collect();
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &String::from_utf8_lossy(&bytes))
+    }
+
+    /// Note that the session was resized
+    pub fn resize(&mut self, width: u16, height: u16) -> Result<()> {
+        self.event("r", &format!("{}x{}", width, height))
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> Result<()> {
+        let time = self.started.elapsed().as_secs_f64();
+        writeln!(self.writer, "{}", json!([time, kind, data]))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn recordings_dir() -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join("recordings"))
+}
+
+/// File names of the saved recordings, newest first
+fn list_recordings() -> Result<Vec<String>> {
+    let dir = recordings_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut recordings = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read recordings directory: {:?}", dir))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".cast") {
+            let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok();
+            recordings.push((modified, name));
+        }
+    }
+    recordings.sort_by(|a, b| b.cmp(a));
+    Ok(recordings.into_iter().map(|(_, name)| name).collect())
+}
+
+/// Output events of a recording, as (seconds from start, data)
+fn load_cast(name: &str) -> Result<Vec<(f64, String)>> {
+    let path = recordings_dir()?.join(name);
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read recording: {:?}", path))?;
+
+    let mut lines = contents.lines();
+    let header: Value = serde_json::from_str(lines.next().unwrap_or_default())
+        .with_context(|| "Recording has no asciicast header")?;
+    if header["version"] != 2 {
+        bail!("Only asciicast v2 recordings can be played");
+    }
+
+    let mut events = Vec::new();
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let event: Value = serde_json::from_str(line)
+            .with_context(|| "Recording has a malformed event")?;
+        if let (Some(time), Some("o"), Some(data)) = (event[0].as_f64(), event[1].as_str(), event[2].as_str()) {
+            events.push((time, data.to_string()));
+        }
+    }
+    Ok(events)
+}
+
+/// A recording being replayed in its own terminal panel
+pub struct Playback {
+    name: String,
+    events: Vec<(f64, String)>,
+    pub panel: RawTerminalPanel,
+    /// Index of the first event not drawn yet
+    next_event: usize,
+    /// Playback time when it was last started, paused or moved
+    position: f64,
+    /// When playback last started; None while paused
+    resumed_at: Option<Instant>,
+}
+
+impl Playback {
+    fn new(name: String, events: Vec<(f64, String)>, bounds: Rect) -> Self {
+        let mut panel = RawTerminalPanel::new(bounds);
+        panel.set_active(true);
+        let mut playback = Self {
+            name,
+            events,
+            panel,
+            next_event: 0,
+            position: 0.0,
+            resumed_at: Some(Instant::now()),
+        };
+        playback.tick();
+        playback
+    }
+
+    fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |(time, _)| *time)
+    }
+
+    fn current_time(&self) -> f64 {
+        let elapsed = self.resumed_at.map_or(0.0, |resumed_at| resumed_at.elapsed().as_secs_f64());
+        (self.position + elapsed).min(self.duration())
+    }
+
+    /// Draw the events that are due and refresh the status banner
+    pub fn tick(&mut self) {
+        let now = self.current_time();
+        while let Some((time, data)) = self.events.get(self.next_event) {
+            if *time > now {
+                break;
+            }
+            self.panel.write_ssh_data(data.as_bytes());
+            self.next_event += 1;
+        }
+        if self.next_event == self.events.len() && self.resumed_at.is_some() {
+            self.position = self.duration();
+            self.resumed_at = None;
+        }
+
+        let state = if self.resumed_at.is_some() { "▶" } else { "⏸" };
+        self.panel.set_banner(Some(format!(
+            "{} {}  {} / {}  Space=pause ←→=seek Home=restart Esc=stop",
+            state,
+            self.name,
+            format_time(now),
+            format_time(self.duration()),
+        )));
+    }
+
+    fn toggle_pause(&mut self) {
+        if self.resumed_at.is_some() {
+            self.position = self.current_time();
+            self.resumed_at = None;
+        } else {
+            if self.next_event == self.events.len() {
+                self.seek(0.0);
+            }
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+
+    /// Jump to `target` seconds; going back redraws from the start
+    fn seek(&mut self, target: f64) {
+        let target = target.clamp(0.0, self.duration());
+        if target < self.current_time() {
+            let bounds = self.panel.bounds();
+            self.panel = RawTerminalPanel::new(bounds);
+            self.panel.set_active(true);
+            self.next_event = 0;
+        }
+        self.position = target;
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(Instant::now());
+        }
+        self.tick();
+    }
+}
+
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl AppState {
+    /// Start recording the live session when recording is turned on
+    pub fn start_recording(&mut self, host: &Host) {
+        self.recorder = None;
+        if !self.config.settings.record_sessions {
+            return;
+        }
+        let (width, height) = self.terminal_panel.get_size();
+        match Recorder::start(host, width, height) {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(e) => self.set_message(format!("Could not start recording: {}", e), MessageType::Error),
+        }
+    }
+
+    pub fn record_output(&mut self, data: &[u8]) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.output(data) {
+                self.recorder = None;
+                self.set_message(format!("Recording stopped: {}", e), MessageType::Error);
+            }
+        }
+    }
+
+    pub fn open_recordings(&mut self) {
+        match list_recordings() {
+            Ok(recordings) if recordings.is_empty() => {
+                self.set_message("No recordings yet. Turn on session recording in settings (Ctrl+S).".to_string(), MessageType::Info);
+            },
+            Ok(recordings) => {
+                self.modal_state = ModalState::Recordings(RecordingsForm { recordings, cursor: 0 });
+            },
+            Err(e) => self.set_message(format!("Could not list recordings: {}", e), MessageType::Error),
+        }
+    }
+
+    pub fn play_recording(&mut self, form: RecordingsForm) {
+        let Some(name) = form.recordings.get(form.cursor) else {
+            return;
+        };
+        match load_cast(name) {
+            Ok(events) => {
+                self.playback = Some(Playback::new(name.clone(), events, self.terminal_panel.bounds()));
+            },
+            Err(e) => self.set_message(format!("Could not play {}: {}", name, e), MessageType::Error),
+        }
+    }
+
+    /// Playback controls; returns false when nothing is being played
+    pub fn handle_playback_key(&mut self, key: KeyCode) -> bool {
+        let Some(playback) = &mut self.playback else {
+            return false;
+        };
+        match key {
+            KeyCode::Char(' ') => playback.toggle_pause(),
+            KeyCode::Left => playback.seek(playback.current_time() - SEEK_STEP),
+            KeyCode::Right => playback.seek(playback.current_time() + SEEK_STEP),
+            KeyCode::Home => playback.seek(0.0),
+            KeyCode::End => playback.seek(playback.duration()),
+            KeyCode::Esc | KeyCode::Char('q') => self.playback = None,
+            _ => {}
+        }
+        true
+    }
+
+    pub fn poll_playback(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.tick();
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{AppState, MessageType, ModalState, ScpForm};
+use crate::line_editor::LineEditor;
+use crate::config::Host;
+use crate::multiplex;
+use crate::ssh::expand_key_path;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+/// How often a running transfer updates the message bar
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An scp copy running in the background
+pub struct Transfer {
+    /// Such as "Uploading report.pdf to web-1:/tmp"
+    label: String,
+    /// The local file a download is writing, whose size shows progress
+    growing: Option<PathBuf>,
+    /// Size of what an upload sends, when it is a file
+    total: Option<u64>,
+    started: Instant,
+    last_report: Instant,
+    receiver: oneshot::Receiver<Result<(), String>>,
+}
+
+/// Sizes in the largest unit that keeps them above 1, such as "12.3 MB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// `user@host:path` as scp expects it; IPv6 addresses need brackets
+fn remote_spec(host: &Host, path: &str) -> String {
+    if host.host.contains(':') {
+        format!("{}@[{}]:{}", host.user, host.host, path)
+    } else {
+        format!("{}@{}:{}", host.user, host.host, path)
+    }
+}
+
+/// Copy with scp, over the session's master connection when there is one
+/// and otherwise with the host's key, without prompting
+async fn run_scp(host: Host, key_path: Option<String>, options: Vec<String>, master: Option<String>, from: String, to: String) -> Result<(), String> {
+    let mut scp = Command::new("scp");
+    scp.args(["-r", "-q", "-P", &host.port.to_string()]);
+    // ssh keeps the first value given for an option: the host's, then the group's
+    scp.args(host.scp_option_args());
+    if let Some(path) = &master {
+        scp.args(["-o", &format!("ControlPath={}", path), "-o", "ControlMaster=no"]);
+    }
+    if let Some(key_path) = &key_path {
+        scp.args(["-i", key_path]);
+    }
+    for option in &options {
+        scp.args(["-o", option]);
+    }
+    scp.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+    // scp takes the limit in Kbit/s
+    if let Some(limit) = host.bandwidth_limit {
+        scp.args(["-l", &(u64::from(limit) * 8).to_string()]);
+    }
+    scp.arg(&from).arg(&to).stdin(Stdio::null());
+
+    match scp.output().await {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(if error.is_empty() { format!("scp exited with {}", output.status) } else { error })
+        },
+        Err(e) => Err(format!("Could not run scp: {}", e)),
+    }
+}
+
+impl AppState {
+    /// Ask for the paths of a copy to (`upload`) or from the live session's host
+    pub async fn open_scp(&mut self, upload: bool) {
+        let Some(profile) = &self.ssh_client.profile else {
+            return;
+        };
+        if !profile.host.kind.uses_ssh() {
+            self.set_message("Copying files needs a host reached over ssh".to_string(), MessageType::Error);
+            return;
+        }
+        // Copies cannot prompt, so without a master they need a key or agent
+        let master = multiplex::detect(&profile.host, self.config.settings.connection_sharing).await
+            .and_then(|sharing| sharing.reused_path().map(str::to_string));
+        self.modal_state = ModalState::Scp(ScpForm {
+            upload,
+            local: if upload { String::new() } else { ".".to_string() },
+            remote: if upload { ".".to_string() } else { String::new() },
+            // The source is filled in first
+            field_focus: if upload { 0 } else { 1 },
+            editor: LineEditor::default(),
+            master,
+        });
+    }
+
+    pub fn start_scp(&mut self, form: ScpForm) {
+        let Some(profile) = self.ssh_client.profile.clone() else {
+            return;
+        };
+        let (local, remote) = (form.local.trim(), form.remote.trim());
+        if local.is_empty() || remote.is_empty() {
+            self.set_message("Enter both a local and a remote path".to_string(), MessageType::Error);
+            return;
+        }
+        let local_path = PathBuf::from(expand_key_path(local));
+        if form.upload && !local_path.exists() {
+            self.set_message(format!("{} does not exist", local_path.display()), MessageType::Error);
+            return;
+        }
+
+        let host = profile.host;
+        let options = self.config.group_of(&host)
+            .map(|group| group.defaults.options.clone())
+            .unwrap_or_default();
+        let remote_path = remote_spec(&host, remote);
+        let local_arg = local_path.to_string_lossy().into_owned();
+        let (from, to, label, growing, total) = if form.upload {
+            let total = local_path.metadata().ok().filter(|meta| meta.is_file()).map(|meta| meta.len());
+            (local_arg, remote_path, format!("Uploading {} to {}:{}", local, host.name, remote), None, total)
+        } else {
+            // A download into a directory keeps the remote file's name
+            let growing = if local_path.is_dir() {
+                Path::new(remote).file_name().map(|name| local_path.join(name))
+            } else {
+                Some(local_path.clone())
+            };
+            (remote_path, local_arg, format!("Downloading {}:{} to {}", host.name, remote, local), growing, None)
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        let key_path = profile.key_path.clone();
+        let master = form.master.clone();
+        tokio::spawn(async move {
+            let _ = sender.send(run_scp(host, key_path, options, master, from, to).await);
+        });
+        self.set_message(format!("{}...", label), MessageType::Info);
+        let now = Instant::now();
+        self.scp_transfers.push(Transfer { label, growing, total, started: now, last_report: now, receiver });
+    }
+
+    /// Report progress of running copies and the outcome of finished ones
+    pub fn poll_scp_transfers(&mut self) {
+        let mut index = 0;
+        while index < self.scp_transfers.len() {
+            let transfer = &mut self.scp_transfers[index];
+            let elapsed = transfer.started.elapsed().as_secs();
+            match transfer.receiver.try_recv() {
+                Ok(result) => {
+                    let transfer = self.scp_transfers.remove(index);
+                    match result {
+                        Ok(()) => self.set_message(format!("{}: done in {}s", transfer.label, elapsed), MessageType::Success),
+                        Err(e) => self.set_message(format!("{}: failed: {}", transfer.label, e), MessageType::Error),
+                    }
+                    continue;
+                },
+                Err(oneshot::error::TryRecvError::Empty) if transfer.last_report.elapsed() >= REPORT_INTERVAL => {
+                    transfer.last_report = Instant::now();
+                    let copied = transfer.growing.as_ref()
+                        .and_then(|path| path.metadata().ok())
+                        .map(|meta| meta.len());
+                    let message = match (copied, transfer.total) {
+                        (Some(copied), _) => format!("{}... {} so far, {}s", transfer.label, format_size(copied), elapsed),
+                        (None, Some(total)) => format!("{}... {}, {}s", transfer.label, format_size(total), elapsed),
+                        (None, None) => format!("{}... {}s", transfer.label, elapsed),
+                    };
+                    self.set_message(message, MessageType::Info);
+                },
+                Err(oneshot::error::TryRecvError::Empty) => {},
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    let transfer = self.scp_transfers.remove(index);
+                    self.set_message(format!("{}: stopped", transfer.label), MessageType::Error);
+                    continue;
+                },
+            }
+            index += 1;
+        }
+    }
+}
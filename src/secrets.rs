@@ -0,0 +1,133 @@
+use crate::config::{Config, Host, SecretsBackend};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Service name used for entries in the OS keyring
+const KEYRING_SERVICE: &str = "sshtui";
+
+/// Account the token of an HTTP inventory sync endpoint is stored under
+pub const INVENTORY_SYNC_ACCOUNT: &str = "inventory-sync-token";
+
+/// Account name a host's password is stored under
+pub fn host_account(host: &Host) -> String {
+    format!("{}@{}:{}", host.user, host.host, host.port)
+}
+
+pub fn get(backend: SecretsBackend, account: &str) -> Result<Option<String>> {
+    match backend {
+        SecretsBackend::File => Ok(load_vault()?.remove(account)),
+        SecretsBackend::Keyring => {
+            let entry = keyring::Entry::new(KEYRING_SERVICE, account)?;
+            match entry.get_password() {
+                Ok(secret) => Ok(Some(secret)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(e).context("Failed to read from system keyring"),
+            }
+        }
+    }
+}
+
+pub fn set(backend: SecretsBackend, account: &str, secret: &str) -> Result<()> {
+    match backend {
+        SecretsBackend::File => {
+            let mut vault = load_vault()?;
+            vault.insert(account.to_string(), secret.to_string());
+            save_vault(&vault)
+        },
+        SecretsBackend::Keyring => {
+            let entry = keyring::Entry::new(KEYRING_SERVICE, account)?;
+            entry.set_password(secret).context("Failed to write to system keyring")
+        }
+    }
+}
+
+pub fn delete(backend: SecretsBackend, account: &str) -> Result<()> {
+    match backend {
+        SecretsBackend::File => {
+            let mut vault = load_vault()?;
+            if vault.remove(account).is_some() {
+                save_vault(&vault)?;
+            }
+            Ok(())
+        },
+        SecretsBackend::Keyring => {
+            let entry = keyring::Entry::new(KEYRING_SERVICE, account)?;
+            match entry.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(e).context("Failed to delete from system keyring"),
+            }
+        }
+    }
+}
+
+/// Move the passwords of every configured host, and the inventory sync
+/// token, from one backend to another. Returns the number of secrets moved.
+pub fn migrate(config: &Config, from: SecretsBackend, to: SecretsBackend) -> Result<usize> {
+    if from == to {
+        return Ok(0);
+    }
+
+    let mut moved = 0;
+    let accounts = config.groups.iter()
+        .skip(1)
+        .flat_map(|group| group.hosts.iter().map(host_account))
+        .chain([INVENTORY_SYNC_ACCOUNT.to_string()]);
+    for account in accounts {
+        if let Some(secret) = get(from, &account)? {
+            set(to, &account, &secret)?;
+            delete(from, &account)?;
+            moved += 1;
+        }
+    }
+    Ok(moved)
+}
+
+fn vault_path() -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join("vault.json"))
+}
+
+fn load_vault() -> Result<HashMap<String, String>> {
+    let path = vault_path()?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read vault file: {:?}", path))?;
+    serde_json::from_str(&contents)
+        .with_context(|| "Failed to parse vault JSON")
+}
+
+fn save_vault(vault: &HashMap<String, String>) -> Result<()> {
+    let path = vault_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory: {:?}", parent))?;
+    }
+
+    let contents = serde_json::to_string_pretty(vault)
+        .with_context(|| "Failed to serialize vault")?;
+
+    // Created afresh, readable by the owner only from the start, and
+    // renamed over the vault
+    let temp_path = path.with_extension("json.tmp");
+    let _ = fs::remove_file(&temp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .with_context(|| format!("Failed to write vault file: {:?}", temp_path))?;
+    fs::rename(&temp_path, &path)
+        .with_context(|| format!("Failed to replace vault file: {:?}", path))
+}
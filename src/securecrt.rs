@@ -0,0 +1,130 @@
+use crate::config::{ConnectionKind, Group, Host, GROUP_SEPARATOR};
+use crate::import::{self, Importer};
+use roxmltree::{Document, Node};
+
+/// Group for sessions outside any SecureCRT folder
+const UNGROUPED: &str = "SecureCRT";
+
+/// Reads the XML SecureCRT exports its settings as, where each session is
+/// a `<key>` under `<key name="Sessions">` and folders are keys around them
+pub struct SecureCrtXml;
+
+/// The value of a session's `<string>` or `<dword>` setting
+fn setting<'a>(session: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    session.children()
+        .find(|child| matches!(child.tag_name().name(), "string" | "dword") && child.attribute("name") == Some(name))
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// A key holding connection settings rather than other sessions
+fn is_session(key: Node) -> bool {
+    setting(key, "Hostname").is_some() || setting(key, "Protocol Name").is_some()
+}
+
+/// A session as a host; None without a host name, or for protocols other
+/// than ssh and telnet such as serial lines and RDP
+fn session_host(name: &str, session: Node) -> Option<Host> {
+    let address = setting(session, "Hostname")?;
+    let (kind, port) = match setting(session, "Protocol Name").unwrap_or("SSH2") {
+        "SSH2" | "SSH1" => (ConnectionKind::Ssh, setting(session, "[SSH2] Port").or_else(|| setting(session, "[SSH1] Port"))),
+        "Telnet" => (ConnectionKind::Telnet, setting(session, "Port")),
+        _ => return None,
+    };
+    // Identity files may be followed by "::rawkey" or "::cert"
+    let key_path = setting(session, "Identity Filename V2")
+        .map(|path| path.split("::").next().unwrap_or(path).to_string());
+    Some(Host {
+        user: setting(session, "Username").unwrap_or("").to_string(),
+        port: port.and_then(|port| port.parse().ok()).unwrap_or(0),
+        key_path,
+        kind,
+        ..Host::new(name, address)
+    })
+}
+
+/// The sessions in `folder` and the folders nested in it, with the group
+/// each goes in
+fn read_folder(folder: Node, path: &[&str], hosts: &mut Vec<(String, Host)>) {
+    for key in folder.children().filter(|child| child.has_tag_name("key")) {
+        let Some(name) = key.attribute("name") else {
+            continue;
+        };
+        if is_session(key) {
+            let group = if path.is_empty() { UNGROUPED.to_string() } else { path.join(&GROUP_SEPARATOR.to_string()) };
+            if let Some(host) = session_host(name, key) {
+                hosts.push((group, host));
+            }
+        } else {
+            let nested: Vec<&str> = path.iter().copied().chain([name]).collect();
+            read_folder(key, &nested, hosts);
+        }
+    }
+}
+
+impl Importer for SecureCrtXml {
+    fn parse(&self, _path: &str, text: &str) -> Result<Vec<Group>, String> {
+        let document = Document::parse(text).map_err(|e| format!("Not an XML export: {}", e))?;
+        let sessions = document.descendants()
+            .find(|node| node.has_tag_name("key") && node.attribute("name") == Some("Sessions"))
+            .ok_or("No Sessions in the file; export them from SecureCRT's Tools menu")?;
+        let mut hosts = Vec::new();
+        read_folder(sessions, &[], &mut hosts);
+        Ok(import::into_groups(hosts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<VanDyke version="3.0">
+  <key name="Sessions">
+    <key name="Default">
+      <string name="Hostname"></string>
+      <string name="Protocol Name">SSH2</string>
+    </key>
+    <key name="Clients">
+      <key name="Acme">
+        <key name="db">
+          <string name="Hostname">db.acme.test</string>
+          <string name="Username">postgres</string>
+          <dword name="[SSH2] Port">2200</dword>
+          <string name="Identity Filename V2">/keys/acme::rawkey</string>
+        </key>
+      </key>
+    </key>
+    <key name="switch">
+      <string name="Hostname">10.0.0.2</string>
+      <string name="Protocol Name">Telnet</string>
+      <dword name="Port">23</dword>
+    </key>
+    <key name="console">
+      <string name="Protocol Name">Serial</string>
+    </key>
+  </key>
+</VanDyke>
+"#;
+
+    #[test]
+    fn imports_sessions_by_folder() {
+        let groups = SecureCrtXml.parse("export.xml", EXPORT).unwrap();
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["Clients/Acme", UNGROUPED]);
+        let db = &groups[0].hosts[0];
+        assert_eq!((db.name.as_str(), db.host.as_str(), db.user.as_str(), db.port), ("db", "db.acme.test", "postgres", 2200));
+        assert_eq!(db.key_path.as_deref(), Some("/keys/acme"));
+        // Sessions without a host name and serial lines are left out
+        assert_eq!(groups[1].hosts.len(), 1);
+        assert_eq!(groups[1].hosts[0].kind, ConnectionKind::Telnet);
+        assert_eq!(groups[1].hosts[0].port, 23);
+    }
+
+    #[test]
+    fn rejects_exports_without_sessions() {
+        assert!(SecureCrtXml.parse("export.xml", "<VanDyke><key name=\"Options\"/></VanDyke>").is_err());
+        assert!(SecureCrtXml.parse("export.xml", "not xml").is_err());
+    }
+}
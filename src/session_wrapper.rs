@@ -0,0 +1,140 @@
+use crate::config::{Host, SessionWrapper};
+use chrono::{DateTime, Local, TimeZone};
+use log::warn;
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Start of the names of wrapped sessions, so the user's own are left alone
+const PREFIX: &str = "sshtui-";
+
+/// tmux server the sessions run on, apart from the user's default one
+const TMUX_SOCKET: &str = "sshtui";
+
+/// A session running inside tmux or abduco, which outlives sshtui
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedSession {
+    pub wrapper: SessionWrapper,
+    /// `sshtui-<host>-<unix time started>`
+    pub name: String,
+}
+
+/// A host name as it appears in session names; tmux and abduco take little
+/// more than letters and digits
+fn name_part(host_name: &str) -> String {
+    host_name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+impl WrappedSession {
+    /// A fresh session for `host`
+    pub fn new(wrapper: SessionWrapper, host: &Host) -> Self {
+        let name = format!("{}{}-{}", PREFIX, name_part(&host.name), Local::now().timestamp());
+        Self { wrapper, name }
+    }
+
+    /// The host the session was started for, as far as its name tells
+    pub fn host_label(&self) -> &str {
+        let rest = self.name.strip_prefix(PREFIX).unwrap_or(&self.name);
+        rest.rsplit_once('-').map_or(rest, |(label, _)| label)
+    }
+
+    pub fn is_for(&self, host: &Host) -> bool {
+        self.host_label() == name_part(&host.name)
+    }
+
+    pub fn started(&self) -> Option<DateTime<Local>> {
+        let secs = self.name.rsplit_once('-')?.1.parse().ok()?;
+        Local.timestamp_opt(secs, 0).single()
+    }
+
+    /// Program and arguments that run `program` inside a new session
+    pub fn command(&self, program: &str, args: &[String]) -> (String, Vec<String>) {
+        let mut wrapped: Vec<String> = match self.wrapper {
+            // The session is drawn in sshtui's own panel, so tmux keeps out
+            // of the way: no status bar, and every key goes to the session
+            SessionWrapper::Tmux => ["-L", TMUX_SOCKET, "-f", "/dev/null", "new-session", "-s", &self.name]
+                .map(String::from)
+                .to_vec(),
+            SessionWrapper::Abduco => vec!["-c".to_string(), self.name.clone()],
+            SessionWrapper::None => return (program.to_string(), args.to_vec()),
+        };
+        wrapped.push(program.to_string());
+        wrapped.extend(args.iter().cloned());
+        if self.wrapper == SessionWrapper::Tmux {
+            for option in [["status", "off"], ["prefix", "None"]] {
+                wrapped.extend([";", "set-option", "-g", option[0], option[1]].map(String::from));
+            }
+        }
+        (self.wrapper_program().to_string(), wrapped)
+    }
+
+    /// Program and arguments that attach to the running session
+    pub fn attach_command(&self) -> (String, Vec<String>) {
+        let args = match self.wrapper {
+            SessionWrapper::Tmux => vec!["-L", TMUX_SOCKET, "attach-session", "-t", &self.name],
+            _ => vec!["-a", &self.name],
+        };
+        (self.wrapper_program().to_string(), args.into_iter().map(String::from).collect())
+    }
+
+    fn wrapper_program(&self) -> &'static str {
+        match self.wrapper {
+            SessionWrapper::Abduco => "abduco",
+            _ => "tmux",
+        }
+    }
+
+    /// End the session and what runs in it
+    pub async fn end(&self) {
+        let mut command = match self.wrapper {
+            SessionWrapper::Tmux => {
+                let mut tmux = Command::new("tmux");
+                tmux.args(["-L", TMUX_SOCKET, "kill-session", "-t", &self.name]);
+                tmux
+            },
+            // abduco has no command for it; its server process keeps the
+            // arguments it was started with
+            SessionWrapper::Abduco => {
+                let mut pkill = Command::new("pkill");
+                pkill.args(["-f", &format!("^abduco -c {}( |$)", self.name)]);
+                pkill
+            },
+            SessionWrapper::None => return,
+        };
+        let result = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status().await;
+        if let Err(e) = result {
+            warn!("Could not end session {}: {}", self.name, e);
+        }
+    }
+}
+
+/// Wrapped sessions nobody is attached to, such as those still running
+/// after sshtui quit, newest first
+pub async fn orphaned(wrapper: SessionWrapper) -> Vec<WrappedSession> {
+    let output = match wrapper {
+        SessionWrapper::Tmux => Command::new("tmux")
+            .args(["-L", TMUX_SOCKET, "list-sessions", "-F", "#{session_attached} #{session_name}"])
+            .output().await,
+        SessionWrapper::Abduco => Command::new("abduco").output().await,
+        SessionWrapper::None => return Vec::new(),
+    };
+    // No server or no sessions also end up here
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut sessions: Vec<WrappedSession> = text.lines()
+        .filter_map(|line| match wrapper {
+            SessionWrapper::Tmux => line.split_once(' ').filter(|(attached, _)| *attached == "0").map(|(_, name)| name),
+            // "<status> <date>\t<name>", where '*' marks attached sessions and
+            // '+' ones whose program has exited
+            _ => line.rsplit_once('\t').filter(|_| line.starts_with(' ')).map(|(_, name)| name),
+        })
+        .filter(|name| name.starts_with(PREFIX))
+        .map(|name| WrappedSession { wrapper, name: name.to_string() })
+        .collect();
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.started()));
+    sessions
+}
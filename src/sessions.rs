@@ -0,0 +1,221 @@
+use crate::{AppState, MessageType, ModalState, SessionsForm};
+use crate::forwarding::Tunnel;
+use crate::notify;
+use crate::recording::Recorder;
+use crate::ssh::{self, SshClient, SshEvent};
+use crate::terminal_panel::RawTerminalPanel;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// A session kept running while the user does something else
+pub struct BackgroundSession {
+    pub client: SshClient,
+    pub panel: RawTerminalPanel,
+    receiver: Option<mpsc::Receiver<SshEvent>>,
+    pub tunnels: Vec<Tunnel>,
+    recorder: Option<Recorder>,
+    last_traffic: Instant,
+    /// Shown beside the live session while the terminal area is split
+    pub in_split: bool,
+}
+
+impl BackgroundSession {
+    pub fn host_name(&self) -> &str {
+        self.client.get_host().map_or("?", |host| host.name.as_str())
+    }
+
+    /// Unread-output and bell badges
+    pub fn markers(&self) -> String {
+        panel_markers(&self.panel)
+    }
+
+    /// Resize the session's PTY, and its recording along with it
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.client.resize_pty(width, height);
+        if let Some(recorder) = &mut self.recorder {
+            let _ = recorder.resize(width, height);
+        }
+    }
+}
+
+/// Badges for a panel: `●` for unseen output, `🔔` for a bell
+pub fn panel_markers(panel: &RawTerminalPanel) -> String {
+    let mut markers = Vec::new();
+    if panel.has_activity() {
+        markers.push("●");
+    }
+    if panel.has_bell() {
+        markers.push("🔔");
+    }
+    markers.join(" ")
+}
+
+impl AppState {
+    /// Send the live session to the background so the sidebar can be used,
+    /// or another host connected, while it keeps running
+    pub fn detach_session(&mut self) {
+        if !self.ssh_client.is_connected() {
+            return;
+        }
+
+        let session = self.take_live_session();
+        self.sidebar_focused = false;
+        self.set_message(
+            format!("Session to {} detached (Ctrl+G to list sessions)", session.host_name()),
+            MessageType::Info
+        );
+        self.background_sessions.push(session);
+    }
+
+    /// Bring a background session to the front, detaching the live one
+    pub fn resume_session(&mut self, index: usize) {
+        if index >= self.background_sessions.len() {
+            return;
+        }
+        if self.ssh_client.is_connecting() {
+            self.set_message("Wait for the pending connection first".to_string(), MessageType::Error);
+            return;
+        }
+        let session = self.background_sessions.remove(index);
+        self.detach_session();
+        self.cancel_reconnect();
+
+        self.bring_to_front(session);
+        let host_name = self.ssh_client.get_host().map(|host| host.name.clone()).unwrap_or_default();
+        self.set_message(format!("Resumed session to {}", host_name), MessageType::Success);
+    }
+
+    /// The live session as a background one, leaving an empty panel
+    pub fn take_live_session(&mut self) -> BackgroundSession {
+        let bounds = self.terminal_panel.bounds();
+        let mut panel = std::mem::replace(&mut self.terminal_panel, RawTerminalPanel::new(bounds));
+        panel.set_active(false);
+        panel.set_viewed(false);
+        BackgroundSession {
+            client: std::mem::take(&mut self.ssh_client),
+            panel,
+            receiver: self.ssh_event_receiver.take(),
+            tunnels: std::mem::take(&mut self.tunnels),
+            recorder: self.recorder.take(),
+            last_traffic: self.last_session_traffic,
+            in_split: false,
+        }
+    }
+
+    /// Make `session` the live one, in place of whatever was live
+    pub fn bring_to_front(&mut self, mut session: BackgroundSession) {
+        session.panel.set_active(true);
+        session.panel.set_viewed(true);
+        self.sidebar_focused = false;
+        self.terminal_panel = session.panel;
+        self.ssh_client = session.client;
+        self.ssh_event_receiver = session.receiver;
+        self.tunnels = session.tunnels;
+        self.recorder = session.recorder;
+        self.last_session_traffic = session.last_traffic;
+        // The window may have been resized while the session was away
+        let (width, height) = self.terminal_panel.get_size();
+        self.ssh_client.resize_pty(width, height);
+    }
+
+    pub fn close_background_session(&mut self, index: usize) {
+        if index >= self.background_sessions.len() {
+            return;
+        }
+        let session = self.background_sessions.remove(index);
+        if let Some(host_id) = session.client.get_host().map(|host| host.id) {
+            self.record_session_end(host_id);
+        }
+        self.set_message(format!("Closed session to {}", session.host_name()), MessageType::Info);
+        let mut client = session.client;
+        tokio::spawn(async move {
+            let _ = client.disconnect().await;
+        });
+    }
+
+    /// Stop the ssh processes of the live and background sessions before
+    /// the app exits, so none are left running without a terminal
+    pub fn stop_sessions(&mut self) {
+        // Readers waiting for room in a full channel see the end once the
+        // channel is gone
+        self.ssh_event_receiver = None;
+        self.ssh_client.stop();
+        for session in &mut self.background_sessions {
+            session.receiver = None;
+            session.client.stop();
+        }
+    }
+
+    pub fn open_sessions(&mut self) {
+        if self.background_sessions.is_empty() {
+            self.scan_orphaned_sessions(true);
+            return;
+        }
+        self.modal_state = ModalState::Sessions(SessionsForm { cursor: 0 });
+    }
+
+    /// Feed output to background sessions, keep them warm and drop the ones
+    /// that ended
+    pub async fn poll_background_sessions(&mut self) {
+        let mut ended = Vec::new();
+        let mut logged_in = Vec::new();
+        for session in &mut self.background_sessions {
+            let events = match &mut session.receiver {
+                Some(receiver) => ssh::take_events(receiver, ssh::OUTPUT_PER_FRAME),
+                None => Vec::new(),
+            };
+
+            for event in events {
+                match &event {
+                    SshEvent::Data(data) => {
+                        session.panel.write_ssh_data(data);
+                        if let Some(recorder) = &mut session.recorder {
+                            if recorder.output(data).is_err() {
+                                session.recorder = None;
+                            }
+                        }
+                        session.last_traffic = Instant::now();
+                    },
+                    // Sent to the background while still logging in
+                    SshEvent::Ready => logged_in.extend(session.client.profile.clone()),
+                    SshEvent::Disconnected { .. } | SshEvent::Error(_) | SshEvent::AuthFailed(_) => {
+                        ended.extend(session.client.get_host().map(|host| (host.id, host.name.clone())));
+                    },
+                    SshEvent::ForwardFailed { forward, error } => {
+                        if let Some(tunnel) = session.tunnels.iter_mut().find(|tunnel| tunnel.forward.same_listener(forward)) {
+                            tunnel.error = Some(error.clone());
+                        }
+                    },
+                    SshEvent::Connected { .. } | SshEvent::HostKeyPrompt(_) | SshEvent::LoginPrompt(_) => {}
+                }
+                session.client.handle_event(event);
+            }
+
+            let keepalive = session.client.profile.as_ref().and_then(|profile| profile.host.keepalive.clone());
+            if let Some(keepalive) = keepalive {
+                if session.client.is_connected() && session.last_traffic.elapsed() >= Duration::from_secs(keepalive.interval_secs) {
+                    session.last_traffic = Instant::now();
+                    let _ = session.client.send_input(&keepalive.payload_bytes()).await;
+                }
+            }
+        }
+
+        for profile in logged_in {
+            self.record_connection(profile);
+        }
+        if ended.is_empty() {
+            return;
+        }
+        self.background_sessions.retain(|session| session.client.is_connected());
+        for (host_id, host_name) in ended {
+            self.record_session_end(host_id);
+            notify::send(
+                &self.config.settings.notifications,
+                notify::Severity::Warning,
+                "SSH session closed",
+                &format!("Background session to {} closed", host_name),
+            );
+            self.set_message(format!("Background session to {} closed", host_name), MessageType::Info);
+        }
+    }
+}
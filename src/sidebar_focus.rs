@@ -0,0 +1,72 @@
+use crate::{AppState, MessageType};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+/// Read a key such as `F12`, `Ctrl+B` or `Alt+S` from the settings
+pub fn parse_key(text: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text.trim();
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        if lower.starts_with("ctrl+") {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = &rest[5..];
+        } else if lower.starts_with("alt+") {
+            modifiers |= KeyModifiers::ALT;
+            rest = &rest[4..];
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_whitespace() => KeyCode::Char(c.to_ascii_lowercase()),
+        _ => {
+            let number = rest.strip_prefix(['F', 'f'])?.parse::<u8>().ok()?;
+            if !(1..=12).contains(&number) {
+                return None;
+            }
+            KeyCode::F(number)
+        },
+    };
+    // A plain character would take that character away from the session
+    if matches!(code, KeyCode::Char(_)) && modifiers.is_empty() {
+        return None;
+    }
+    Some((code, modifiers))
+}
+
+impl AppState {
+    /// Whether keys go to the live session rather than the sidebar
+    pub fn session_has_keys(&self) -> bool {
+        self.ssh_client.is_connected() && !self.sidebar_focused
+    }
+
+    /// Whether a key press is the configured sidebar key
+    pub fn is_sidebar_key(&self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let code = match code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        parse_key(&self.config.settings.sidebar_key) == Some((code, modifiers - KeyModifiers::SHIFT))
+    }
+
+    /// Move the keyboard from the live session to the sidebar, or back
+    pub fn toggle_sidebar_focus(&mut self) {
+        if self.sidebar_focused {
+            self.focus_session();
+            return;
+        }
+        self.sidebar_focused = true;
+        self.terminal_panel.set_active(false);
+        let message = format!("Sidebar focused, session still open: {} or Esc returns to it", self.config.settings.sidebar_key);
+        self.set_message(message, MessageType::Info);
+    }
+
+    /// Give the keyboard back to the live session
+    pub fn focus_session(&mut self) {
+        self.sidebar_focused = false;
+        self.terminal_panel.set_active(true);
+        self.clear_message();
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{AppState, MessageType};
+use ratatui::prelude::*;
+
+/// How the terminal area is shared by the live session and a background one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// Side by side
+    Vertical,
+    /// One above the other
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Split {
+    pub direction: SplitDirection,
+    /// The live session has the right or bottom half, after switching panes
+    live_second: bool,
+    /// Scrolling either pane scrolls the other to the same place, to
+    /// compare the output of two similar hosts
+    scroll_locked: bool,
+}
+
+impl AppState {
+    /// Index of the background session shown in the split pane
+    pub fn split_pane(&self) -> Option<usize> {
+        self.split?;
+        self.background_sessions.iter().position(|session| session.in_split)
+    }
+
+    /// Split the terminal area to show the most recent background session
+    /// beside the live one, or switch the split's direction; the same
+    /// direction again closes it
+    pub fn toggle_split(&mut self, direction: SplitDirection) {
+        if let Some(split) = self.split.filter(|_| self.split_pane().is_some()) {
+            if split.direction == direction {
+                self.close_split();
+            } else {
+                self.split = Some(Split { direction, ..split });
+            }
+            return;
+        }
+        let Some(session) = self.background_sessions.last_mut() else {
+            let hint = format!("Detach a session first ({} then Ctrl+G) to show it beside the live one", self.config.settings.sidebar_key);
+            self.set_message(hint, MessageType::Info);
+            return;
+        };
+        session.in_split = true;
+        session.panel.set_viewed(true);
+        let host_name = session.host_name().to_string();
+        self.split = Some(Split { direction, live_second: false, scroll_locked: false });
+        self.set_message(format!("Split with {} (Alt+O switches panes, Alt+L locks their scrolling)", host_name), MessageType::Info);
+    }
+
+    /// Send the split pane's session back to the background
+    pub fn close_split(&mut self) {
+        self.split = None;
+        for session in self.background_sessions.iter_mut().filter(|session| session.in_split) {
+            session.in_split = false;
+            session.panel.set_viewed(false);
+        }
+    }
+
+    /// Swap the live session with the one in the split pane, so keys go to
+    /// the other pane; both stay where they are on screen
+    pub fn focus_other_pane(&mut self) {
+        let Some(index) = self.split_pane() else {
+            return;
+        };
+        if self.ssh_client.is_connecting() {
+            return;
+        }
+        let session = self.background_sessions.remove(index);
+        let mut live = self.take_live_session();
+        live.in_split = true;
+        live.panel.set_viewed(true);
+        self.background_sessions.insert(index, live);
+        self.bring_to_front(session);
+        if let Some(split) = &mut self.split {
+            split.live_second = !split.live_second;
+        }
+    }
+
+    /// Lock or unlock the scroll positions of the two panes
+    pub fn toggle_scroll_lock(&mut self) {
+        if self.split_pane().is_none() {
+            self.set_message("Split the terminal area first (Alt+\\ or Alt+-)".to_string(), MessageType::Info);
+            return;
+        }
+        let Some(split) = &mut self.split else {
+            return;
+        };
+        split.scroll_locked = !split.scroll_locked;
+        if split.scroll_locked {
+            self.sync_scroll(false);
+            self.set_message("Split panes scroll together (Alt+L to unlock)".to_string(), MessageType::Info);
+        } else {
+            self.set_message("Split panes scroll on their own".to_string(), MessageType::Info);
+        }
+    }
+
+    /// Scroll the live panel, or the split pane, up into its scrollback or
+    /// down with a negative count; with the lock on the other pane follows
+    pub fn scroll_panes(&mut self, lines: isize, in_pane: bool) {
+        match self.split_pane().filter(|_| in_pane) {
+            Some(index) => self.background_sessions[index].panel.scroll_view(lines),
+            None => self.terminal_panel.scroll_view(lines),
+        }
+        self.sync_scroll(in_pane);
+    }
+
+    /// Bring the live panel back to its latest output, as typing does
+    pub fn follow_output(&mut self) {
+        if self.terminal_panel.scroll_offset() > 0 {
+            self.scroll_panes(isize::MIN, false);
+        }
+    }
+
+    /// Give the other pane the scroll position of the one just scrolled
+    fn sync_scroll(&mut self, from_pane: bool) {
+        let Some(index) = self.split_pane().filter(|_| self.split.is_some_and(|split| split.scroll_locked)) else {
+            return;
+        };
+        let pane = &mut self.background_sessions[index].panel;
+        if from_pane {
+            self.terminal_panel.set_scroll_offset(pane.scroll_offset());
+        } else {
+            pane.set_scroll_offset(self.terminal_panel.scroll_offset());
+        }
+    }
+
+    /// Bounds of the live panel and of the split pane within `area`
+    pub fn split_bounds(&self, area: Rect) -> (Rect, Option<Rect>) {
+        let (Some(split), Some(_)) = (self.split, self.split_pane()) else {
+            return (area, None);
+        };
+        let halves = Layout::default()
+            .direction(match split.direction {
+                SplitDirection::Vertical => Direction::Horizontal,
+                SplitDirection::Horizontal => Direction::Vertical,
+            })
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        if split.live_second {
+            (halves[1], Some(halves[0]))
+        } else {
+            (halves[0], Some(halves[1]))
+        }
+    }
+
+    /// Size the split pane's panel and PTY to its half of the terminal
+    /// area, and close the split once no live session is beside it
+    pub fn layout_split(&mut self, pane: Option<Rect>) {
+        let Some(index) = self.split_pane() else {
+            return;
+        };
+        let live = self.ssh_client.is_connected() || self.ssh_client.is_connecting() || self.reconnect.is_some();
+        let Some(pane) = pane.filter(|_| live) else {
+            self.close_split();
+            return;
+        };
+        let session = &mut self.background_sessions[index];
+        let previous_size = session.panel.get_size();
+        session.panel.set_bounds(pane);
+        let (width, height) = session.panel.get_size();
+        if (width, height) != previous_size {
+            session.resize(width, height);
+        }
+    }
+}
@@ -1,47 +1,402 @@
-use anyhow::{Result, anyhow};
-use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use crate::error::{Error, Result};
 use tokio::sync::mpsc;
-use crate::config::Host;
+use crate::config::{self, ConnectionKind, ForwardKind, Host, PortForward, SessionWrapper, SshBackend};
+use crate::hostkeys::{self, HostKeyPrompt, Verification};
+use crate::multiplex::{self, Sharing};
+use crate::session_wrapper::WrappedSession;
+use crate::ssh_embedded::{self, AuthFailure};
+use serde::{Deserialize, Serialize};
 use log::{error, info, warn};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use portable_pty::{CommandBuilder, PtySize, PtyPair};
-use std::io::{Read, Write};
+use portable_pty::{CommandBuilder, PtySize};
+use std::io::{self, Read, Write};
 use std::thread;
 use std::sync::Mutex as StdMutex;
-use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
-// Global PTY writer storage
-lazy_static! {
-    static ref GLOBAL_PTY_WRITER: Arc<StdMutex<Option<Box<dyn Write + Send>>>> = Arc::new(StdMutex::new(None));
+/// Where a session's input goes: the PTY master of the ssh process, or the
+/// channel of an embedded session. Cleared when the session ends.
+pub type PtyWriter = Arc<StdMutex<Option<Box<dyn Write + Send>>>>;
+
+/// Queue of input for a session. Keystrokes, passwords and prompt answers
+/// all go through it, so they reach the session in the order they were sent.
+type InputQueue = std::sync::mpsc::Sender<Vec<u8>>;
+
+/// The process of a system-backend session and the thread reading its output
+#[derive(Default)]
+struct SessionProcess {
+    child: Option<Box<dyn portable_pty::Child + Send + Sync>>,
+    reader: Option<thread::JoinHandle<()>>,
+    /// Kept to resize the PTY with the panel
+    master: Option<Box<dyn portable_pty::MasterPty + Send>>,
+}
+
+type ProcessHandle = Arc<StdMutex<SessionProcess>>;
+
+/// Paces a stream to a host's bandwidth limit the way `pv -L` does: after
+/// each chunk it says how long to hold off before reading the next
+pub struct Throttle {
+    bytes_per_sec: f64,
+    started: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    pub fn new(kb_per_sec: u32) -> Self {
+        Self { bytes_per_sec: f64::from(kb_per_sec.max(1)) * 1024.0, started: Instant::now(), bytes: 0 }
+    }
+
+    /// Count `bytes` passed on and return the pause that keeps the rate
+    /// under the limit. Idle time earns no credit, so a burst after a quiet
+    /// spell is paced too.
+    pub fn delay(&mut self, bytes: usize) -> Duration {
+        self.bytes += bytes as u64;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec);
+        let elapsed = self.started.elapsed();
+        if due > elapsed {
+            return due - elapsed;
+        }
+        self.started = Instant::now();
+        self.bytes = 0;
+        Duration::ZERO
+    }
+}
+
+/// Kill the session's process if it still runs, reap it, and wait for its
+/// output reader to finish; a no-op once the process has been stopped
+fn stop_process(process: &ProcessHandle) {
+    let SessionProcess { child, reader, .. } = std::mem::take(&mut *process.lock().unwrap());
+    if let Some(mut child) = child {
+        if !matches!(child.try_wait(), Ok(Some(_))) {
+            info!("Stopping SSH process {:?}", child.process_id());
+            if let Err(e) = child.kill() {
+                warn!("Could not kill SSH process: {}", e);
+            }
+        }
+        let _ = child.wait();
+    }
+    // The reader sees the PTY close once the process is gone
+    if let Some(reader) = reader {
+        let deadline = Instant::now() + READER_JOIN_TIMEOUT;
+        while !reader.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if reader.is_finished() {
+            let _ = reader.join();
+        } else {
+            warn!("PTY reader still running after the SSH process stopped");
+        }
+    }
+}
+
+/// Traffic and timing of a session, for the status bar
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    pub connected_at: Option<Instant>,
+    pub bytes_received: u64,
+    /// Shared with the clones of the client that type tunnel commands
+    bytes_sent: Arc<AtomicU64>,
+    /// Time the last TCP handshake with the host took
+    pub latency: Option<Duration>,
+}
+
+impl SessionStats {
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct SshClient {
     pub connected: bool,
     pub connecting: bool,
+    /// The login is over and the session is running, as opposed to ssh
+    /// still asking for a password, passphrase or host key
+    pub logged_in: bool,
     pub host: Option<Host>,
+    /// Profile used for the current (or last attempted) connection
+    pub profile: Option<ConnectionProfile>,
+    /// Control channel of an embedded-backend session (input, resizes and tunnels)
+    session_input: Option<mpsc::UnboundedSender<SessionInput>>,
+    /// Whether the last input sent ended a line; ssh only honours `~`
+    /// escapes at the start of a line
+    line_start: Arc<AtomicBool>,
+    /// Let system-backend sessions become ControlMaster for later ones
+    pub share_connections: bool,
+    /// Control socket of the master the live session is multiplexed over
+    shared_control_path: Option<String>,
+    /// Run system-backend sessions inside tmux or abduco
+    pub session_wrapper: SessionWrapper,
+    /// Wrapped session the next `connect` attaches to instead of starting one
+    pub attach_to: Option<WrappedSession>,
+    /// Wrapped session the live connection runs in; ended on disconnect
+    wrapped: Option<WrappedSession>,
+    /// Events of the live session, for failures reported after the fact
+    events: Option<mpsc::Sender<SshEvent>>,
+    /// Input writer of the session; every connection gets its own so
+    /// several sessions can run side by side
+    writer: PtyWriter,
+    /// Input for the session, written by a thread of its own
+    input: Option<InputQueue>,
+    /// Process of the session, stopped on disconnect and when the app exits
+    process: ProcessHandle,
+    pub stats: SessionStats,
 }
 
-pub enum SshEvent {
-    Connected { host: Host },
-    Data(Vec<u8>),
-    Error(String),
-    Disconnected,
+/// Keystrokes and pasted keys arriving within this time of the first one
+/// reach the session in a single write
+const INPUT_BATCH: Duration = Duration::from_millis(4);
+
+/// Time given to ssh to show its `~C` prompt before the command is typed
+const ESCAPE_PROMPT_DELAY: Duration = Duration::from_millis(200);
+
+/// Bytes of output kept to recognise a rejected login when ssh exits
+const AUTH_FAILURE_TAIL: usize = 512;
+
+/// Longest line of output checked for ssh's forwarding errors
+const MAX_LINE: usize = 256;
+
+/// Words in the prompts of second factors servers ask for after the key or
+/// password, such as Google Authenticator's "Verification code:" or Duo's
+/// "Passcode or option (1-3):"; matched as whole words
+const LOGIN_PROMPT_WORDS: [&str; 6] = ["verification code", "one-time password", "one time password", "otp", "passcode", "token"];
+
+/// Time the output reader gets to notice its process is gone
+const READER_JOIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Events a session can have waiting for the app. Once that many are
+/// waiting the session stops reading output, which holds back ssh and the
+/// server with it until the app catches up.
+pub const EVENT_BUFFER: usize = 16;
+
+/// Output taken from a session per frame, so a flood of it (catting a
+/// large file) cannot keep the interface from drawing and reading keys
+pub const OUTPUT_PER_FRAME: usize = 64 * 1024;
+
+/// Channel a session reports its output and state changes on
+pub fn event_channel() -> (mpsc::Sender<SshEvent>, mpsc::Receiver<SshEvent>) {
+    mpsc::channel(EVENT_BUFFER)
 }
 
-impl Default for SshClient {
-    fn default() -> Self {
-        Self {
-            connected: false,
-            connecting: false,
-            host: None,
+/// Take the events waiting on a session's channel, stopping once about
+/// `max_output` bytes of output were taken; the rest waits for the next
+/// frame. Output arriving in a row is joined into one event.
+pub fn take_events(receiver: &mut mpsc::Receiver<SshEvent>, max_output: usize) -> Vec<SshEvent> {
+    let mut events: Vec<SshEvent> = Vec::new();
+    let mut output = 0;
+    while output < max_output {
+        let Ok(event) = receiver.try_recv() else {
+            break;
+        };
+        if let SshEvent::Data(data) = &event {
+            output += data.len();
+            if let Some(SshEvent::Data(previous)) = events.last_mut() {
+                previous.extend_from_slice(data);
+                continue;
+            }
+        }
+        events.push(event);
+    }
+    events
+}
+
+/// Everything needed to launch an ssh session: the host as it was at connect
+/// time plus the exact arguments and environment handed to the `ssh` binary.
+/// Recorded in the history so a session can be replayed verbatim later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub host: Host,
+    pub key_path: Option<String>,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    #[serde(default)]
+    pub backend: SshBackend,
+}
+
+impl ConnectionProfile {
+    /// `options` are extra `-o` options, e.g. from the host's group
+    pub fn new(host: Host, key_path: Option<&str>, backend: SshBackend, options: &[String]) -> Self {
+        let key_path = key_path.map(expand_key_path);
+
+        // ssh keeps the first value it is given for an option, so the host's
+        // own arguments go first, then the group's, then the defaults
+        let mut ssh_args = host.ssh_option_args();
+        if let Some(keepalive) = host.keepalive.as_ref().filter(|keepalive| !keepalive.through_session()) {
+            ssh_args.push("-o".to_string());
+            ssh_args.push(format!("ServerAliveInterval={}", keepalive.interval_secs));
+        }
+        if let Some(key_path) = &key_path {
+            ssh_args.push("-i".to_string());
+            ssh_args.push(key_path.clone());
+        }
+        for option in options {
+            ssh_args.push("-o".to_string());
+            ssh_args.push(option.clone());
+        }
+        // Host keys are verified against known_hosts before ssh starts; ssh
+        // checks them again with its defaults
+        for option in [
+            "ServerAliveInterval=30",
+            "ServerAliveCountMax=3",
+        ] {
+            ssh_args.push("-o".to_string());
+            ssh_args.push(option.to_string());
+        }
+
+        let args = match &host.kind {
+            ConnectionKind::Ssh => {
+                let mut args = ssh_args;
+                for forward in &host.forwards {
+                    args.push(forward.flag().to_string());
+                    args.push(forward.spec());
+                }
+                args.push("-t".to_string()); // Force pseudo-terminal allocation
+                args.push(format!("{}@{}", host.user, host.host));
+                args.push("-p".to_string());
+                args.push(host.port.to_string());
+                if let Some(command) = &host.remote_command {
+                    args.push(command.clone());
+                }
+                args
+            },
+            ConnectionKind::Mosh => {
+                // mosh splits its --ssh command like a shell would
+                let mut args = vec![
+                    format!("--ssh=ssh -p {} {}", host.port, config::join_arguments(&ssh_args)),
+                    format!("{}@{}", host.user, host.host),
+                ];
+                if let Some(command) = &host.remote_command {
+                    args.extend(["--".to_string(), "sh".to_string(), "-c".to_string(), command.clone()]);
+                }
+                args
+            },
+            ConnectionKind::Telnet => {
+                let mut args = Vec::new();
+                if !host.user.is_empty() {
+                    args.push("-l".to_string());
+                    args.push(host.user.clone());
+                }
+                args.push(host.host.clone());
+                args.push(host.port.to_string());
+                args
+            },
+            ConnectionKind::Custom(command) => {
+                let command = command
+                    .replace("{host}", &host.host)
+                    .replace("{port}", &host.port.to_string())
+                    .replace("{user}", &host.user)
+                    .replace("{name}", &host.name);
+                vec!["-c".to_string(), command]
+            },
+        };
+
+        let term = host.term.clone().unwrap_or_else(|| "xterm-256color".to_string());
+        let mut env = vec![("TERM".to_string(), term)];
+        if let Some(locale) = &host.locale {
+            env.push(("LANG".to_string(), locale.clone()));
+            env.push(("LC_ALL".to_string(), locale.clone()));
         }
+
+        Self { host, key_path, args, env, backend }
     }
 }
 
+/// Expand a leading tilde in a key path
+pub fn expand_key_path(key_path: &str) -> String {
+    if key_path.starts_with('~') {
+        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+        key_path.replacen('~', &home, 1)
+    } else {
+        key_path.to_string()
+    }
+}
+
+/// Input headed for an embedded-backend session
+pub enum SessionInput {
+    Data(Vec<u8>),
+    Resize(u16, u16),
+    Forward(PortForward),
+    CancelForward(PortForward),
+}
+
+/// Lets an embedded-backend session sit behind the global PTY writer, so
+/// input is sent the same way for both backends
+struct SessionWriter(mpsc::UnboundedSender<SessionInput>);
+
+impl Write for SessionWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(SessionInput::Data(buf.to_vec()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "SSH session closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Start the thread writing a session's input. Whatever arrives within
+/// `INPUT_BATCH` of the first input goes along with it in one write, so a
+/// fast typist or a paste arriving as key presses costs one write rather
+/// than one per character. A failed write is reported as an error of the
+/// session and stops the queue.
+fn spawn_input_writer(writer: PtyWriter, events: mpsc::Sender<SshEvent>) -> InputQueue {
+    let (input_tx, input_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        while let Ok(mut batch) = input_rx.recv() {
+            let deadline = Instant::now() + INPUT_BATCH;
+            while let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                match input_rx.recv_timeout(wait) {
+                    Ok(more) => batch.extend_from_slice(&more),
+                    Err(_) => break,
+                }
+            }
+            // Input sent before the session is up, or after it ended, has
+            // nowhere to go
+            let result = match writer.lock().unwrap().as_mut() {
+                Some(writer) => writer.write_all(&batch).and_then(|_| writer.flush()),
+                None => Ok(()),
+            };
+            if let Err(e) = result {
+                error!("Could not send input: {}", e);
+                let _ = events.blocking_send(SshEvent::Error(Error::io("Could not send input", e)));
+                break;
+            }
+        }
+    });
+    input_tx
+}
+
+/// Replace (or clear) the writer a session's input is sent to
+pub fn set_pty_writer(target: &PtyWriter, writer: Option<Box<dyn Write + Send>>) {
+    let mut guard = target.lock().unwrap();
+    *guard = writer;
+}
+
+pub enum SshEvent {
+    /// The session started; ssh may still be logging in. `control_path` is
+    /// set when the session reuses an existing master.
+    Connected { host: Box<Host>, control_path: Option<String> },
+    /// The login is over: the server accepted the credentials and the
+    /// session shows its own output. May come before `Connected`.
+    Ready,
+    Data(Vec<u8>),
+    Error(Error),
+    /// `clean` when the remote ended the session itself, as with `exit`,
+    /// rather than the connection dropping
+    Disconnected { clean: bool },
+    /// A tunnel of the live session could not be opened
+    ForwardFailed { forward: PortForward, error: String },
+    /// The server key is unknown or changed; nothing was sent to the server
+    HostKeyPrompt(Box<HostKeyPrompt>),
+    /// The server rejected the credentials offered
+    AuthFailed(String),
+    /// ssh waits for a second factor, such as a verification code, which
+    /// the terminal panel may not be showing yet
+    LoginPrompt(String),
+}
+
 impl SshClient {
     pub fn new() -> Self {
         Default::default()
@@ -49,39 +404,131 @@ impl SshClient {
 
     pub async fn connect(
         &mut self,
-        host: Host,
-        key_path: &str,
-        event_sender: mpsc::UnboundedSender<SshEvent>,
+        profile: ConnectionProfile,
+        password: Option<String>,
+        event_sender: mpsc::Sender<SshEvent>,
         terminal_width: u16,
         terminal_height: u16,
     ) -> Result<()> {
         if self.connecting {
-            return Err(anyhow!("Already connecting"));
+            return Err(Error::Ssh("Already connecting".to_string()));
         }
 
+        let host = profile.host.clone();
         info!("Starting SSH connection to {}@{}:{}", host.user, host.host, host.port);
         self.connecting = true;
+        self.logged_in = false;
+        // Output can arrive before the session counts as connected
+        self.stats = SessionStats::default();
         self.host = Some(host.clone());
+        self.profile = Some(profile.clone());
 
-        let host_clone = host.clone();
-        let key_path = key_path.to_string();
         let sender = event_sender.clone();
+        // Attaching to a wrapped session needs neither a login nor the backend
+        let wrapped = match self.attach_to.take() {
+            Some(session) => Some((session, true)),
+            None if profile.backend == SshBackend::System && self.session_wrapper != SessionWrapper::None => {
+                Some((WrappedSession::new(self.session_wrapper, &host), false))
+            },
+            None => None,
+        };
+        let attaching = matches!(wrapped, Some((_, true)));
+        self.wrapped = wrapped.as_ref().map(|(session, _)| session.clone());
+        let session_input = match profile.backend {
+            SshBackend::Embedded if !attaching => Some(mpsc::unbounded_channel()),
+            _ => None,
+        };
+        self.session_input = session_input.as_ref().map(|(input_tx, _)| input_tx.clone());
+        self.line_start.store(true, Ordering::Relaxed);
+        self.shared_control_path = None;
+        self.events = Some(event_sender.clone());
+        self.writer = PtyWriter::default();
+        let writer = self.writer.clone();
+        let input = spawn_input_writer(writer.clone(), event_sender.clone());
+        self.input = Some(input.clone());
+        self.process = ProcessHandle::default();
+        let process = self.process.clone();
+        let share_connections = self.share_connections;
         
         tokio::spawn(async move {
-            match Self::establish_connection_static(
-                host_clone.clone(),
-                &key_path,
-                terminal_width,
-                terminal_height,
-                sender.clone(),
-            ).await {
-                Ok(_) => {
+            // Nothing is sent to a server until its key is trusted; telnet
+            // and custom commands have no host key to check
+            let verification = match host.kind.uses_ssh() && !attaching {
+                true => Some(hostkeys::verify(&host, profile.backend).await),
+                false => None,
+            };
+            let fingerprint = match verification {
+                None => None,
+                Some(Verification::Trusted(fingerprint)) => Some(fingerprint),
+                Some(Verification::Prompt(prompt)) => {
+                    let _ = sender.send(SshEvent::HostKeyPrompt(prompt)).await;
+                    return;
+                },
+                Some(Verification::Unavailable(e)) if session_input.is_some() => {
+                    let _ = sender.send(SshEvent::Error(Error::Ssh(format!("Could not verify the host key: {}", e)))).await;
+                    return;
+                },
+                // ssh checks known_hosts itself and asks in the terminal
+                Some(Verification::Unavailable(_)) => {
+                    warn!("Leaving host key verification of {} to ssh", host.host);
+                    None
+                },
+            };
+
+            let embedded = session_input.is_some();
+            let result = match session_input {
+                Some((input_tx, input_rx)) => {
+                    let result = ssh_embedded::establish_connection(
+                        &profile,
+                        password,
+                        terminal_width,
+                        terminal_height,
+                        sender.clone(),
+                        input_rx,
+                        writer.clone(),
+                        fingerprint,
+                    ).await;
+                    if result.is_ok() {
+                        set_pty_writer(&writer, Some(Box::new(SessionWriter(input_tx))));
+                    }
+                    result.map(|_| None).map_err(|e| match e.downcast::<AuthFailure>() {
+                        Ok(failure) => Error::Auth(failure.to_string()),
+                        Err(e) => Error::Ssh(e.to_string()),
+                    })
+                },
+                None => Self::establish_connection_static(
+                    &profile,
+                    password,
+                    terminal_width,
+                    terminal_height,
+                    share_connections,
+                    wrapped,
+                    sender.clone(),
+                    writer,
+                    input,
+                    process,
+                ).await,
+            };
+            match result {
+                Ok(control_path) => {
                     info!("SSH connection established");
-                    let _ = sender.send(SshEvent::Connected { host: host_clone });
+                    // Embedded sessions are only up once authenticated, and
+                    // an attached or multiplexed one logged in long ago.
+                    // Telnet, mosh and custom commands count as up once
+                    // started, as their output cannot be followed.
+                    let logged_in = embedded || attaching || control_path.is_some() || !host.kind.is_ssh();
+                    let _ = sender.send(SshEvent::Connected { host: Box::new(host), control_path }).await;
+                    if logged_in {
+                        let _ = sender.send(SshEvent::Ready).await;
+                    }
                 },
                 Err(e) => {
                     error!("SSH connection failed: {}", e);
-                    let _ = sender.send(SshEvent::Error(e.to_string()));
+                    let event = match e {
+                        Error::Auth(message) => SshEvent::AuthFailed(message),
+                        e => SshEvent::Error(e),
+                    };
+                    let _ = sender.send(event).await;
                 }
             }
         });
@@ -89,19 +536,26 @@ impl SshClient {
         Ok(())
     }
 
+    /// `wrapped` is the tmux or abduco session to run ssh in, and whether
+    /// it is already running and only attached to
+    #[allow(clippy::too_many_arguments)]
     async fn establish_connection_static(
-        host: Host,
-        key_path: &str,
+        profile: &ConnectionProfile,
+        password: Option<String>,
         terminal_width: u16,
         terminal_height: u16,
-        sender: mpsc::UnboundedSender<SshEvent>,
-    ) -> Result<()> {
-        // Expand tilde in key path
-        let key_path = if key_path.starts_with('~') {
-            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-            key_path.replacen('~', &home, 1)
-        } else {
-            key_path.to_string()
+        share_connections: bool,
+        wrapped: Option<(WrappedSession, bool)>,
+        sender: mpsc::Sender<SshEvent>,
+        writer: PtyWriter,
+        input: InputQueue,
+        process: ProcessHandle,
+    ) -> Result<Option<String>> {
+        // Share a master connection when one is available
+        let sharing = match (&profile.host.kind, &wrapped) {
+            (_, Some((_, true))) => None,
+            (ConnectionKind::Ssh, _) => multiplex::detect(&profile.host, share_connections).await,
+            _ => None,
         };
 
         // Use portable-pty for proper PTY handling
@@ -113,145 +567,300 @@ impl SshClient {
             pixel_height: 0,
         };
         
-        let pty_pair = pty_system.openpty(pty_size)?;
+        let pty_pair = pty_system.openpty(pty_size).map_err(|e| Error::Pty(e.to_string()))?;
         
-        // Build SSH command
-        let mut cmd = CommandBuilder::new("ssh");
-        cmd.arg("-i");
-        cmd.arg(&key_path);
-        cmd.arg("-o");
-        cmd.arg("StrictHostKeyChecking=no");
-        cmd.arg("-o");
-        cmd.arg("UserKnownHostsFile=/dev/null");
-        cmd.arg("-o");
-        cmd.arg("ServerAliveInterval=30");
-        cmd.arg("-o");
-        cmd.arg("ServerAliveCountMax=3");
-        cmd.arg("-t"); // Force pseudo-terminal allocation
-        cmd.arg(format!("{}@{}", host.user, host.host));
-        cmd.arg("-p");
-        cmd.arg(host.port.to_string());
-        cmd.env("TERM", "xterm-256color");
-        cmd.env("COLUMNS", &terminal_width.to_string());
-        cmd.env("LINES", &terminal_height.to_string());
+        // Build the command from the recorded profile
+        let attaching = matches!(wrapped, Some((_, true)));
+        let mut args = sharing.as_ref().map(Sharing::args).unwrap_or_default();
+        args.extend(profile.args.iter().cloned());
+        let (program, args) = match &wrapped {
+            Some((session, true)) => session.attach_command(),
+            Some((session, false)) => session.command(profile.host.kind.program(), &args),
+            None => (profile.host.kind.program().to_string(), args),
+        };
+        let mut cmd = CommandBuilder::new(&program);
+        cmd.args(&args);
+        for (name, value) in &profile.env {
+            cmd.env(name, value);
+        }
+        // tmux refuses to start inside another tmux unless told it is wanted
+        cmd.env_remove("TMUX");
+        cmd.env("COLUMNS", terminal_width.to_string());
+        cmd.env("LINES", terminal_height.to_string());
         
         // Spawn the SSH process in the PTY
-        let child = pty_pair.slave.spawn_command(cmd)?;
+        let child = pty_pair.slave.spawn_command(cmd)
+            .map_err(|e| Error::Pty(format!("Could not start {}: {}", program, e)))?;
         info!("SSH process spawned with PID: {:?}", child.process_id());
+        process.lock().unwrap().child = Some(child);
         
         // Get the PTY master for reading/writing  
-        let mut pty_reader = pty_pair.master.try_clone_reader()?;
-        let pty_writer = pty_pair.master.take_writer()?;
+        let mut pty_reader = pty_pair.master.try_clone_reader().map_err(|e| Error::Pty(e.to_string()))?;
+        let pty_writer = pty_pair.master.take_writer().map_err(|e| Error::Pty(e.to_string()))?;
+        process.lock().unwrap().master = Some(pty_pair.master);
         
-        // Store the PTY writer in the global storage
-        {
-            let mut global_writer = GLOBAL_PTY_WRITER.lock().unwrap();
-            *global_writer = Some(Box::new(pty_writer));
-        }
+        // Input for the session goes to the PTY master from now on
+        set_pty_writer(&writer, Some(Box::new(pty_writer)));
         
         // Set up PTY output reading in a background thread
         let sender_clone = sender.clone();
-        thread::spawn(move || {
+        let mut throttle = profile.host.bandwidth_limit.map(Throttle::new);
+        // An attached or multiplexed session is logged in already
+        let logged_in = attaching || sharing.as_ref().and_then(Sharing::reused_path).is_some();
+        let mut login = Login::new(&profile.host, password, logged_in);
+        // The exit status of tmux or abduco says nothing about the session
+        let wrapped = wrapped.is_some();
+        let kind = profile.host.kind.clone();
+        let reader_process = process.clone();
+        let reader = thread::spawn(move || {
             let mut buffer = [0u8; 8192];
+            // Last output seen, to tell a rejected login from a closed session
+            let mut tail = Vec::new();
+            // Output since the last newline, for tunnels ssh could not open
+            // and second-factor prompts
+            let mut line = Vec::new();
+            let mut prompted = false;
             loop {
                 match pty_reader.read(&mut buffer) {
                     Ok(0) => {
                         info!("PTY EOF - connection closed");
-                        // Clear the global writer on disconnect
-                        {
-                            let mut global_writer = GLOBAL_PTY_WRITER.lock().unwrap();
-                            *global_writer = None;
-                        }
-                        let _ = sender_clone.send(SshEvent::Disconnected);
+                        // Clear the writer on disconnect
+                        set_pty_writer(&writer, None);
+                        let event = match auth_failure(&tail) {
+                            Some(message) => SshEvent::AuthFailed(message),
+                            None => SshEvent::Disconnected { clean: !wrapped && exited_cleanly(&reader_process, &kind) },
+                        };
+                        let _ = sender_clone.blocking_send(event);
                         break;
                     },
                     Ok(n) => {
-                        let _ = sender_clone.send(SshEvent::Data(buffer[..n].to_vec()));
+                        tail.extend_from_slice(&buffer[..n]);
+                        tail.drain(..tail.len().saturating_sub(AUTH_FAILURE_TAIL));
+                        for &byte in &buffer[..n] {
+                            if byte == b'\n' {
+                                if let Some((forward, error)) = forward_failure(&String::from_utf8_lossy(&line)) {
+                                    let _ = sender_clone.blocking_send(SshEvent::ForwardFailed { forward, error });
+                                }
+                                line.clear();
+                                prompted = false;
+                            } else if line.len() < MAX_LINE {
+                                line.push(byte);
+                            }
+                        }
+                        // Only ssh asks for a second factor, before the
+                        // session is up; later prompts belong to the remote
+                        if !prompted && !login.done {
+                            if let Some(prompt) = login_prompt(&line) {
+                                let _ = sender_clone.blocking_send(SshEvent::LoginPrompt(prompt));
+                                prompted = true;
+                            }
+                        }
+                        let step = login.output(&line);
+                        if let LoginStep::Password(password) = &step {
+                            let _ = input.send(format!("{}\n", password).into_bytes());
+                        }
+                        // Waits while the app has a full channel to catch up on
+                        let _ = sender_clone.blocking_send(SshEvent::Data(buffer[..n].to_vec()));
+                        if matches!(step, LoginStep::Done) {
+                            let _ = sender_clone.blocking_send(SshEvent::Ready);
+                        }
+                        // Not reading holds ssh back, and the server with it
+                        if let Some(throttle) = &mut throttle {
+                            thread::sleep(throttle.delay(n));
+                        }
                     },
                     Err(e) => {
                         error!("PTY read error: {}", e);
-                        // Clear the global writer on error
-                        {
-                            let mut global_writer = GLOBAL_PTY_WRITER.lock().unwrap();
-                            *global_writer = None;
-                        }
-                        let _ = sender_clone.send(SshEvent::Error(format!("PTY read error: {}", e)));
+                        // Clear the writer on error
+                        set_pty_writer(&writer, None);
+                        let _ = sender_clone.blocking_send(SshEvent::Error(Error::io("PTY read error", e)));
                         break;
                     }
                 }
             }
         });
+        process.lock().unwrap().reader = Some(reader);
         
         // Wait a moment for connection to establish
         tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
         
-        Ok(())
+        Ok(sharing.as_ref().and_then(Sharing::reused_path).map(String::from))
     }
 
     pub fn handle_event(&mut self, event: SshEvent) {
         match event {
-            SshEvent::Connected { host } => {
+            SshEvent::Connected { host, control_path } => {
                 info!("SSH connected to {}", host.name);
                 self.connected = true;
                 self.connecting = false;
-                self.host = Some(host);
+                self.host = Some(*host);
+                self.shared_control_path = control_path;
+                self.stats.connected_at = Some(Instant::now());
+            },
+            SshEvent::Ready => {
+                self.logged_in = true;
             },
-            SshEvent::Disconnected => {
+            SshEvent::Disconnected { .. } => {
                 info!("SSH disconnected");
+                stop_process(&self.process);
                 self.connected = false;
                 self.connecting = false;
+                self.logged_in = false;
                 self.host = None;
+                self.shared_control_path = None;
             },
-            SshEvent::Error(err) => {
-                error!("SSH error: {}", err);
+            SshEvent::Error(_) | SshEvent::AuthFailed(_) => {
+                match &event {
+                    SshEvent::Error(err) => error!("SSH error: {}", err),
+                    SshEvent::AuthFailed(reason) => error!("SSH error: {}", reason),
+                    _ => {},
+                }
+                stop_process(&self.process);
                 self.connected = false;
                 self.connecting = false;
+                self.logged_in = false;
+                self.shared_control_path = None;
+            },
+            SshEvent::HostKeyPrompt(_) => {
+                self.connecting = false;
             },
-            SshEvent::Data(_) => {
-                // Data events are handled by the terminal panel directly
+            SshEvent::Data(data) => {
+                // The output itself is handled by the app directly
+                self.stats.bytes_received += data.len() as u64;
+            },
+            SshEvent::ForwardFailed { .. } | SshEvent::LoginPrompt(_) => {
+                // Tunnel events and prompts are handled by the app directly
             }
         }
     }
 
+    /// Type the answer to a login prompt, which ssh may ask for before the
+    /// session counts as connected
+    pub fn answer_prompt(&self, answer: &str) -> Result<()> {
+        self.queue_input(format!("{}\n", answer).into_bytes())
+    }
+
+    /// Send input to the session. It is written a few milliseconds later
+    /// together with whatever else is sent meanwhile; a write that fails
+    /// arrives as an `SshEvent::Error`.
     pub async fn send_input(&self, data: &[u8]) -> Result<()> {
-        if self.connected {
-            let global_writer = GLOBAL_PTY_WRITER.clone();
-            let data = data.to_vec();
-            tokio::task::spawn_blocking(move || {
-                if let Ok(mut writer_guard) = global_writer.lock() {
-                    if let Some(writer) = writer_guard.as_mut() {
-                        writer.write_all(&data)?;
-                        writer.flush()?;
-                        return Ok(());
+        if !self.connected {
+            return Err(Error::Ssh("SSH not connected".to_string()));
+        }
+        if let Some(&last) = data.last() {
+            self.line_start.store(last == b'\r' || last == b'\n', Ordering::Relaxed);
+        }
+        self.stats.bytes_sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+        self.queue_input(data.to_vec())
+    }
+
+    fn queue_input(&self, data: Vec<u8>) -> Result<()> {
+        match &self.input {
+            Some(input) => input.send(data).map_err(|_| Error::Ssh("SSH session closed".to_string())),
+            None => Err(Error::Ssh("SSH not connected".to_string())),
+        }
+    }
+
+    /// Open and close tunnels on the live session. Embedded sessions handle
+    /// them natively; the system ssh binary is driven through its `~C`
+    /// command line.
+    pub fn update_forwards(&self, start: &[PortForward], cancel: &[PortForward]) -> Result<()> {
+        if !self.connected {
+            return Err(Error::Ssh("SSH not connected".to_string()));
+        }
+        if self.host.as_ref().is_some_and(|host| !host.kind.is_ssh()) {
+            return Err(Error::config("Tunnels need an ssh session"));
+        }
+
+        if let Some(session_input) = &self.session_input {
+            let requests = cancel.iter().cloned().map(SessionInput::CancelForward)
+                .chain(start.iter().cloned().map(SessionInput::Forward));
+            for request in requests {
+                session_input.send(request).map_err(|_| Error::Ssh("SSH session closed".to_string()))?;
+            }
+            return Ok(());
+        }
+
+        // Multiplexed sessions have no `~C`; ask the master instead
+        if let (Some(path), Some(host)) = (self.shared_control_path.clone(), self.host.clone()) {
+            let requests: Vec<(&'static str, PortForward)> = cancel.iter().map(|forward| ("cancel", forward.clone()))
+                .chain(start.iter().map(|forward| ("forward", forward.clone())))
+                .collect();
+            let events = self.events.clone();
+            tokio::spawn(async move {
+                for (operation, forward) in requests {
+                    if let Err(error) = multiplex::forward_request(&host, &path, operation, &forward).await {
+                        if let Some(events) = &events {
+                            let _ = events.send(SshEvent::ForwardFailed { forward, error }).await;
+                        }
                     }
                 }
-                Err(anyhow!("No PTY writer available"))
-            }).await?
-        } else {
-            Err(anyhow!("SSH not connected"))
+            });
+            return Ok(());
         }
-    }
 
-    pub async fn resize_pty(&self, _width: u16, _height: u16) -> Result<()> {
-        // For the SSH command-line approach, PTY resizing is more complex
-        // This would require sending SIGWINCH to the SSH process
-        // For now, we'll implement a simple version
+        if !self.line_start.load(Ordering::Relaxed) {
+            return Err(Error::Ssh("ssh only accepts tunnel changes at the start of a line; press Enter at an empty prompt first".to_string()));
+        }
+        let commands: Vec<String> = cancel.iter()
+            .map(PortForward::cancel_command)
+            .chain(start.iter().map(PortForward::to_string))
+            .collect();
+        let client = self.clone();
+        tokio::spawn(async move {
+            for command in commands {
+                let _ = client.send_input(b"~C").await;
+                tokio::time::sleep(ESCAPE_PROMPT_DELAY).await;
+                let _ = client.send_input(format!("{}\r", command).as_bytes()).await;
+                tokio::time::sleep(ESCAPE_PROMPT_DELAY).await;
+            }
+        });
         Ok(())
     }
 
+    pub fn resize_pty(&self, width: u16, height: u16) {
+        if let Some(session_input) = &self.session_input {
+            let _ = session_input.send(SessionInput::Resize(width, height));
+            return;
+        }
+        // The system ssh binary passes the new size on to the server when
+        // its PTY changes size
+        if let Some(master) = &self.process.lock().unwrap().master {
+            let size = PtySize { rows: height, cols: width, pixel_width: 0, pixel_height: 0 };
+            if let Err(e) = master.resize(size) {
+                warn!("Could not resize the PTY: {}", e);
+            }
+        }
+    }
+
     pub async fn disconnect(&mut self) -> Result<()> {
-        // Clear the global PTY writer
-        {
-            let mut global_writer = GLOBAL_PTY_WRITER.lock().unwrap();
-            *global_writer = None;
+        // Clear the PTY writer; for embedded sessions this also drops the
+        // last input sender, which closes the channel
+        set_pty_writer(&self.writer, None);
+        let process = self.process.clone();
+        let _ = tokio::task::spawn_blocking(move || stop_process(&process)).await;
+        if let Some(session) = self.wrapped.take() {
+            session.end().await;
         }
+        self.session_input = None;
+        self.input = None;
+        self.shared_control_path = None;
+        self.events = None;
         self.connected = false;
         self.connecting = false;
+        self.logged_in = false;
         self.host = None;
 
         Ok(())
     }
 
+    /// Kill the session's ssh process and wait for it, for when the app
+    /// exits. A wrapped session keeps running: only its client is stopped.
+    pub fn stop(&self) {
+        set_pty_writer(&self.writer, None);
+        stop_process(&self.process);
+    }
+
     pub fn is_connected(&self) -> bool {
         self.connected
     }
@@ -260,7 +869,238 @@ impl SshClient {
         self.connecting
     }
 
+    pub fn is_logged_in(&self) -> bool {
+        self.connected && self.logged_in
+    }
+
     pub fn get_host(&self) -> Option<&Host> {
         self.host.as_ref()
     }
 }
+
+/// The second-factor prompt ssh is waiting on at the end of `line`, the
+/// output since the last newline
+fn login_prompt(line: &[u8]) -> Option<String> {
+    let text = prompt_text(line);
+    if !text.ends_with(':') {
+        return None;
+    }
+    let lowercase = text.to_lowercase();
+    let words: Vec<&str> = lowercase.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    let asks_for_code = LOGIN_PROMPT_WORDS.iter().any(|phrase| {
+        let phrase: Vec<&str> = phrase.split(|c: char| !c.is_alphanumeric()).collect();
+        words.windows(phrase.len()).any(|window| window == phrase.as_slice())
+    });
+    asks_for_code.then_some(text)
+}
+
+/// Whether the session's process ended on its own terms once its output
+/// closed: ssh exits with the remote shell's status, and with 255 when the
+/// connection failed; other programs exit with 0
+fn exited_cleanly(process: &ProcessHandle, kind: &ConnectionKind) -> bool {
+    let deadline = Instant::now() + READER_JOIN_TIMEOUT;
+    loop {
+        let status = match process.lock().unwrap().child.as_mut().map(|child| child.try_wait()) {
+            Some(Ok(Some(status))) => status,
+            Some(Ok(None)) if Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            },
+            // Stopped by sshtui, or still running
+            _ => return false,
+        };
+        return match kind {
+            ConnectionKind::Ssh => status.exit_code() != 255,
+            _ => status.success(),
+        };
+    }
+}
+
+/// The text of `line`, the output since the last newline, without control
+/// characters, as a prompt would be compared
+fn prompt_text(line: &[u8]) -> String {
+    let text: String = String::from_utf8_lossy(line).chars().filter(|c| !c.is_control()).collect();
+    text.trim().to_string()
+}
+
+/// Whether `line`, the output since the last newline, ends in what looks
+/// like a shell prompt: `$`, `#`, `%` or `>`, and at most a space after it,
+/// once escape sequences such as colors and window titles are left out
+fn is_shell_prompt(line: &[u8]) -> bool {
+    let raw = String::from_utf8_lossy(line);
+    let mut text = String::new();
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI runs up to a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                },
+                // OSC, such as a window title, ends with BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                },
+                _ => {}
+            },
+            c if c.is_control() => {}
+            c => text.push(c),
+        }
+    }
+    let text = text.strip_suffix(' ').unwrap_or(&text);
+    // Something has to come before the sign, as in "user@host:~$"
+    text.len() > 1 && text.ends_with(['$', '#', '%', '>'])
+}
+
+/// What a system-backend session's output says about its login
+#[derive(Debug, PartialEq)]
+enum LoginStep {
+    /// Still logging in, or logged in a while ago
+    Waiting,
+    /// ssh asks for the password; type this
+    Password(String),
+    /// The session just showed a shell prompt
+    Done,
+}
+
+/// Follows the login of a system-backend session from its output. An ssh
+/// session is taken to be logging in until the last line of its output
+/// looks like a shell prompt; a banner, "Last login" or a chunk ending
+/// halfway through a line does not count. Until then the stored password
+/// is typed, once, at ssh's own password prompt. Other kinds of sessions
+/// are never taken to be past their login this way, and only the prompts
+/// naming the user and host get the password.
+struct Login {
+    /// ssh's password prompts for the host: "user@host's password:" for
+    /// password authentication, "(user@host) Password:" or a bare
+    /// "Password:" for keyboard-interactive
+    password_prompts: [String; 2],
+    /// Stored password, typed once
+    password: Option<String>,
+    /// Whether the session runs ssh itself, so its output can be read
+    ssh: bool,
+    done: bool,
+}
+
+impl Login {
+    fn new(host: &Host, password: Option<String>, done: bool) -> Self {
+        Self {
+            password_prompts: [
+                format!("{}@{}'s password:", host.user, host.host),
+                format!("({}@{}) Password:", host.user, host.host),
+            ],
+            password: password.filter(|_| !done),
+            ssh: host.kind.is_ssh(),
+            done,
+        }
+    }
+
+    /// Follow the output since the last newline after each chunk
+    fn output(&mut self, line: &[u8]) -> LoginStep {
+        if self.done {
+            return LoginStep::Waiting;
+        }
+        let text = prompt_text(line);
+        if text.is_empty() {
+            return LoginStep::Waiting;
+        }
+        if (self.ssh && text == "Password:") || self.password_prompts.iter().any(|prompt| text.ends_with(prompt.as_str())) {
+            return self.password.take().map_or(LoginStep::Waiting, LoginStep::Password);
+        }
+        if !self.ssh || !is_shell_prompt(line) {
+            return LoginStep::Waiting;
+        }
+        self.done = true;
+        self.password = None;
+        LoginStep::Done
+    }
+}
+
+/// A tunnel ssh reports it could not open, from a line of its output. Only
+/// the listening side and port are known, so the forward returned matches
+/// the real one with `PortForward::same_listener`.
+fn forward_failure(line: &str) -> Option<(PortForward, String)> {
+    let (kind, port, error) = if let Some((_, port)) = line.split_once("cannot listen to port: ") {
+        (ForwardKind::Local, port, "could not listen on the port")
+    } else if let Some((_, port)) = line.split_once("remote port forwarding failed for listen port ") {
+        (ForwardKind::Remote, port, "the server could not listen on the port")
+    } else {
+        return None;
+    };
+    let bind_port = port.trim().parse().ok()?;
+    Some((PortForward { kind, bind_port, target: None }, error.to_string()))
+}
+
+/// ssh's "Permission denied (publickey,...)" line, if that is how the
+/// session ended
+fn auth_failure(tail: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(tail);
+    text.lines()
+        .rev()
+        .find(|line| line.contains("Permission denied ("))
+        .map(|line| line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login_to(kind: ConnectionKind) -> Login {
+        let mut host = Host::new("web", "10.0.0.5");
+        host.user = "ops".to_string();
+        host.kind = kind;
+        Login::new(&host, Some("secret".to_string()), false)
+    }
+
+    #[test]
+    fn recognises_shell_prompts() {
+        assert!(is_shell_prompt(b"ops@web:~$ "));
+        assert!(is_shell_prompt(b"[root@web ~]#"));
+        assert!(is_shell_prompt(b"web% "));
+        assert!(is_shell_prompt(b"PS C:\\Users\\ops> "));
+        // Colors and a window title around the prompt
+        assert!(is_shell_prompt(b"\x1b]0;ops@web: ~\x07\x1b[01;32mops@web\x1b[00m:\x1b[01;34m~\x1b[00m$ "));
+        assert!(is_shell_prompt(b"\x1b]2;title\x1b\\ops@web$ \x1b[?2004h"));
+    }
+
+    #[test]
+    fn banners_and_split_lines_are_not_prompts() {
+        assert!(!is_shell_prompt(b"Trying 10.0.0.5..."));
+        assert!(!is_shell_prompt(b"Welcome to Ubuntu 22.04.3 LT"));
+        assert!(!is_shell_prompt(b"Last login: Mon Oct 12 09:14:03 2026 from 10.0.0.1"));
+        assert!(!is_shell_prompt(b"$"));
+        assert!(!is_shell_prompt(b""));
+    }
+
+    #[test]
+    fn ssh_login_ends_at_a_shell_prompt() {
+        let mut login = login_to(ConnectionKind::Ssh);
+        assert_eq!(login.output(b"Welcome to Ubuntu 22.04.3 LT"), LoginStep::Waiting);
+        assert_eq!(login.output(b"ops@10.0.0.5's password: "), LoginStep::Password("secret".to_string()));
+        assert_eq!(login.output(b"Last login: Mon Oct 12 09:14:03"), LoginStep::Waiting);
+        assert_eq!(login.output(b"ops@web:~$ "), LoginStep::Done);
+        // Prompts from the remote shell are left to the user
+        assert_eq!(login.output(b"ops@10.0.0.5's password: "), LoginStep::Waiting);
+        assert_eq!(login.output(b"ops@web:~$ "), LoginStep::Waiting);
+    }
+
+    #[test]
+    fn other_kinds_are_not_followed() {
+        let mut login = login_to(ConnectionKind::Telnet);
+        assert_eq!(login.output(b"Trying 10.0.0.5..."), LoginStep::Waiting);
+        assert_eq!(login.output(b"Password:"), LoginStep::Waiting);
+        assert_eq!(login.output(b"ops@web:~$ "), LoginStep::Waiting);
+
+        let mut login = login_to(ConnectionKind::Mosh);
+        assert_eq!(login.output(b"ops@10.0.0.5's password: "), LoginStep::Password("secret".to_string()));
+        assert_eq!(login.output(b"ops@web:~$ "), LoginStep::Waiting);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::config::{ForwardKind, PortForward};
+use crate::hostkeys;
+use crate::ssh::{self, ConnectionProfile, PtyWriter, SessionInput, SshEvent, Throttle};
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use russh::client::{self, AuthResult};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::{load_secret_key, PrivateKeyWithHashAlg, PublicKey};
+use russh::ChannelMsg;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// The server turned down every credential offered, as opposed to the
+/// connection failing
+#[derive(Debug)]
+pub struct AuthFailure(pub String);
+
+impl fmt::Display for AuthFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AuthFailure {}
+
+type Session = client::Handle<ClientHandler>;
+
+/// Only accepts the server key that was verified before connecting
+struct ClientHandler {
+    fingerprint: Option<String>,
+}
+
+impl client::Handler for ClientHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, server_public_key: &russh::keys::PublicKey) -> Result<bool, Self::Error> {
+        Ok(self.fingerprint.as_deref() == Some(hostkeys::fingerprint(server_public_key).as_str()))
+    }
+}
+
+/// Open a session with the built-in SSH client. Returns once the remote shell
+/// is running; output and input are then pumped by a background task until
+/// either side closes the channel.
+#[allow(clippy::too_many_arguments)]
+pub async fn establish_connection(
+    profile: &ConnectionProfile,
+    password: Option<String>,
+    terminal_width: u16,
+    terminal_height: u16,
+    sender: mpsc::Sender<SshEvent>,
+    mut input: mpsc::UnboundedReceiver<SessionInput>,
+    writer: PtyWriter,
+    fingerprint: Option<String>,
+) -> Result<()> {
+    let host = &profile.host;
+    let keepalive_secs = host.keepalive.as_ref()
+        .filter(|keepalive| !keepalive.through_session())
+        .map_or(30, |keepalive| keepalive.interval_secs);
+    let config = Arc::new(client::Config {
+        keepalive_interval: Some(Duration::from_secs(keepalive_secs)),
+        keepalive_max: 3,
+        ..Default::default()
+    });
+
+    let mut session = client::connect(config, (host.host.as_str(), host.port), ClientHandler { fingerprint })
+        .await
+        .with_context(|| format!("Could not connect to {}:{}", host.host, host.port))?;
+
+    // Try the key first, then the stored password
+    let mut tried = Vec::new();
+    let mut server_methods = Vec::new();
+    let mut authenticated = false;
+    if let Some(key_path) = &profile.key_path {
+        let hash_alg = session.best_supported_rsa_hash().await?.flatten();
+        // A public key stands for the matching key held by ssh-agent
+        let result = if key_path.ends_with(".pub") {
+            let public_key = PublicKey::read_openssh_file(std::path::Path::new(key_path))
+                .with_context(|| format!("Could not load SSH key {}", key_path))?;
+            let mut agent = AgentClient::connect_env().await
+                .context("Could not reach ssh-agent")?;
+            session
+                .authenticate_publickey_with(&host.user, public_key, hash_alg, &mut agent)
+                .await
+                .map_err(|e| anyhow!("ssh-agent could not sign in: {}", e))?
+        } else {
+            let key = load_secret_key(key_path, None)
+                .with_context(|| format!("Could not load SSH key {}", key_path))?;
+            session
+                .authenticate_publickey(&host.user, PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg))
+                .await?
+        };
+        tried.push(format!("key {}", key_path));
+        match result {
+            AuthResult::Success => authenticated = true,
+            AuthResult::Failure { remaining_methods, .. } => {
+                server_methods = remaining_methods.iter().map(String::from).collect();
+            }
+        }
+    }
+    if !authenticated {
+        if let Some(password) = password {
+            let result = session.authenticate_password(&host.user, password).await?;
+            tried.push("password".to_string());
+            match result {
+                AuthResult::Success => authenticated = true,
+                AuthResult::Failure { remaining_methods, .. } => {
+                    server_methods = remaining_methods.iter().map(String::from).collect();
+                }
+            }
+        }
+    }
+    if !authenticated {
+        let tried = if tried.is_empty() { "no key or password".to_string() } else { tried.join(", ") };
+        let accepted = if server_methods.is_empty() { "unknown".to_string() } else { server_methods.join(", ") };
+        return Err(AuthFailure(format!(
+            "Authentication failed for {}@{} (tried {}; server accepts {})",
+            host.user, host.host, tried, accepted
+        )).into());
+    }
+
+    let term = profile.env.iter()
+        .find(|(name, _)| name == "TERM")
+        .map_or("xterm-256color", |(_, value)| value.as_str());
+    let mut channel = session.channel_open_session().await?;
+    channel.request_pty(false, term, terminal_width as u32, terminal_height as u32, 0, 0, &[]).await?;
+    for (name, value) in profile.env.iter().filter(|(name, _)| name != "TERM") {
+        channel.set_env(false, name.as_str(), value.as_str()).await?;
+    }
+    match &host.remote_command {
+        Some(command) => channel.exec(true, command.as_str()).await?,
+        None => channel.request_shell(true).await?,
+    }
+    info!("Embedded SSH session opened to {}@{}:{}", host.user, host.host, host.port);
+
+    let profile = profile.clone();
+
+    tokio::spawn(async move {
+        // Tunnels hold the session handle too, so it outlives the channel
+        let session = Arc::new(session);
+        let mut tunnels: HashMap<u16, JoinHandle<()>> = HashMap::new();
+        let mut throttle = profile.host.bandwidth_limit.map(Throttle::new);
+        // Sent by the server when the remote shell exits, but not when the
+        // connection drops
+        let mut exited = false;
+        for forward in &profile.host.forwards {
+            start_forward(&session, &mut tunnels, forward.clone(), &sender).await;
+        }
+        loop {
+            tokio::select! {
+                message = channel.wait() => match message {
+                    Some(ChannelMsg::Data { data }) | Some(ChannelMsg::ExtendedData { data, .. }) => {
+                        let _ = sender.send(SshEvent::Data(data.to_vec())).await;
+                        if let Some(throttle) = &mut throttle {
+                            tokio::time::sleep(throttle.delay(data.len())).await;
+                        }
+                    },
+                    Some(ChannelMsg::ExitStatus { .. }) | Some(ChannelMsg::ExitSignal { .. }) => exited = true,
+                    Some(ChannelMsg::Eof) | Some(ChannelMsg::Close) | None => break,
+                    Some(_) => {}
+                },
+                command = input.recv() => match command {
+                    Some(SessionInput::Data(data)) => {
+                        if let Err(e) = channel.data(&data[..]).await {
+                            error!("Embedded SSH write error: {}", e);
+                            break;
+                        }
+                    },
+                    Some(SessionInput::Resize(width, height)) => {
+                        let _ = channel.window_change(width as u32, height as u32, 0, 0).await;
+                    },
+                    Some(SessionInput::Forward(forward)) => {
+                        start_forward(&session, &mut tunnels, forward, &sender).await;
+                    },
+                    Some(SessionInput::CancelForward(forward)) => {
+                        if let Some(tunnel) = tunnels.remove(&forward.bind_port) {
+                            tunnel.abort();
+                        }
+                    },
+                    // The client went away; close the channel
+                    None => {
+                        exited = true;
+                        let _ = channel.close().await;
+                        break;
+                    }
+                }
+            }
+        }
+
+        for tunnel in tunnels.into_values() {
+            tunnel.abort();
+        }
+        info!("Embedded SSH session closed");
+        ssh::set_pty_writer(&writer, None);
+        let _ = sender.send(SshEvent::Disconnected { clean: exited }).await;
+    });
+
+    Ok(())
+}
+
+/// Start a tunnel on the session; only local forwards are supported natively
+async fn start_forward(
+    session: &Arc<Session>,
+    tunnels: &mut HashMap<u16, JoinHandle<()>>,
+    forward: PortForward,
+    sender: &mpsc::Sender<SshEvent>,
+) {
+    if forward.kind != ForwardKind::Local {
+        let _ = sender.send(SshEvent::ForwardFailed {
+            forward,
+            error: "only local forwards are supported by the embedded backend".to_string(),
+        }).await;
+        return;
+    }
+
+    let tunnel = tokio::spawn(run_local_forward(session.clone(), forward.clone(), sender.clone()));
+    if let Some(previous) = tunnels.insert(forward.bind_port, tunnel) {
+        previous.abort();
+    }
+}
+
+/// Accept connections on a local port and carry each one through the session
+/// to the forward's target
+async fn run_local_forward(session: Arc<Session>, forward: PortForward, sender: mpsc::Sender<SshEvent>) {
+    let Some((target_host, target_port)) = forward.target_addr() else {
+        let _ = sender.send(SshEvent::ForwardFailed { forward, error: "invalid target".to_string() }).await;
+        return;
+    };
+    let listener = match TcpListener::bind(("127.0.0.1", forward.bind_port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            let _ = sender.send(SshEvent::ForwardFailed { forward, error: e.to_string() }).await;
+            return;
+        }
+    };
+
+    while let Ok((mut stream, origin)) = listener.accept().await {
+        let session = session.clone();
+        let target_host = target_host.clone();
+        tokio::spawn(async move {
+            let channel = session.channel_open_direct_tcpip(
+                target_host.as_str(),
+                target_port as u32,
+                origin.ip().to_string(),
+                origin.port() as u32,
+            ).await;
+            match channel {
+                Ok(channel) => {
+                    let mut channel_stream = channel.into_stream();
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut channel_stream).await;
+                },
+                Err(e) => error!("Could not open tunnel to {}:{}: {}", target_host, target_port, e),
+            }
+        });
+    }
+}
@@ -0,0 +1,128 @@
+use crate::AppState;
+use crate::broadcast::BroadcastHistory;
+use crate::config::Config;
+use crate::facts::FactsIndex;
+use crate::history::History;
+use crate::input_recovery::UnsentInput;
+use crate::inventory_sync::SyncMarks;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// Everything read from disk before hosts can be listed
+pub struct Inventory {
+    pub config: Config,
+    pub history: History,
+    pub broadcasts: BroadcastHistory,
+    pub facts: FactsIndex,
+    pub sync_marks: SyncMarks,
+    pub unsent_input: UnsentInput,
+    /// Why the config file could not be parsed; `config` is empty then
+    pub corrupt_config: Option<String>,
+}
+
+impl Inventory {
+    /// Read the config and the stores kept next to it; only the config has
+    /// to load, the others start empty when they cannot be read. A config
+    /// file that reads but does not parse is left for the user to recover.
+    pub fn load() -> Result<Self> {
+        let (config, corrupt_config) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) if e.is_corrupt_config() => {
+                let reason = std::error::Error::source(&e).map_or_else(|| e.to_string(), |source| source.to_string());
+                warn!("Config file does not parse: {}", reason);
+                (Config::default(), Some(reason))
+            },
+            Err(e) => return Err(e.into()),
+        };
+        let mut history = History::load().unwrap_or_else(|e| {
+            warn!("Could not load connection history: {}", e);
+            History::default()
+        });
+        history.adopt_ids(&config);
+        let broadcasts = BroadcastHistory::load().unwrap_or_else(|e| {
+            warn!("Could not load broadcast history: {}", e);
+            BroadcastHistory::default()
+        });
+        let facts = FactsIndex::load().unwrap_or_else(|e| {
+            warn!("Could not load facts index: {}", e);
+            FactsIndex::default()
+        });
+        let sync_marks = SyncMarks::load().unwrap_or_else(|e| {
+            warn!("Could not load inventory sync state: {}", e);
+            SyncMarks::default()
+        });
+        let unsent_input = UnsentInput::load().unwrap_or_else(|e| {
+            warn!("Could not load unsent input: {}", e);
+            UnsentInput::default()
+        });
+        Ok(Self { config, history, broadcasts, facts, sync_marks, unsent_input, corrupt_config })
+    }
+
+    /// Stand-in shown until the real inventory is read; never saved
+    fn placeholder() -> Self {
+        Self {
+            config: Config::default(),
+            history: History::default(),
+            broadcasts: BroadcastHistory::default(),
+            facts: FactsIndex::default(),
+            sync_marks: SyncMarks::default(),
+            unsent_input: UnsentInput::default(),
+            corrupt_config: None,
+        }
+    }
+}
+
+/// Inventory being read on a blocking thread while the first frames are drawn
+pub struct Startup {
+    started: Instant,
+    receiver: oneshot::Receiver<Result<Inventory>>,
+}
+
+impl AppState {
+    /// State that draws straight away and reads the inventory in the background
+    pub fn loading() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        tokio::task::spawn_blocking(move || {
+            let _ = sender.send(Inventory::load());
+        });
+        let mut app = Self::new(Inventory::placeholder());
+        app.startup = Some(Startup { started: Instant::now(), receiver });
+        app
+    }
+
+    /// How long the inventory has been loading, while it still is. Keys other
+    /// than quitting are ignored meanwhile, so the placeholder is never saved.
+    pub fn loading_for(&self) -> Option<Duration> {
+        self.startup.as_ref().map(|startup| startup.started.elapsed())
+    }
+
+    /// Swap in the inventory once it is read; a config that cannot be read
+    /// ends the app as it would have before the TUI started
+    pub fn poll_startup(&mut self) -> Result<()> {
+        let Some(startup) = &mut self.startup else {
+            return Ok(());
+        };
+        let inventory = match startup.receiver.try_recv() {
+            Ok(inventory) => inventory?,
+            Err(oneshot::error::TryRecvError::Empty) => return Ok(()),
+            Err(oneshot::error::TryRecvError::Closed) => return Err(anyhow!("Loading the config stopped unexpectedly")),
+        };
+        info!("Inventory loaded in {} ms", startup.started.elapsed().as_millis());
+        self.startup = None;
+        self.config = inventory.config;
+        self.empty_old_trash();
+        self.history = inventory.history;
+        self.broadcasts = inventory.broadcasts;
+        self.facts = inventory.facts;
+        self.sync_marks = inventory.sync_marks;
+        self.unsent_input = inventory.unsent_input;
+        if let Some(reason) = inventory.corrupt_config {
+            self.offer_config_recovery(reason);
+        }
+        self.apply_theme();
+        self.scan_orphaned_sessions(false);
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use crate::AppState;
+use crate::config::ConnectionKind;
+use crate::dashboard::format_duration;
+use crate::probe::{Probe, ProbeStatus, TcpPing};
+use crate::scp::format_size;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// How often the latency to the connected host is measured
+const LATENCY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Latest latency measurement, or the one running, and the host it is for
+pub struct LatencyCheck {
+    host_name: String,
+    started: Instant,
+    /// Unset once the result is in
+    receiver: Option<oneshot::Receiver<Option<Duration>>>,
+}
+
+/// Time on the clock, such as "00:04:27"
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+impl AppState {
+    /// Details of the live connection for the status bar: address,
+    /// duration, traffic, key and latency
+    pub fn connection_details(&self) -> Option<Vec<String>> {
+        if !self.ssh_client.is_connected() {
+            return None;
+        }
+        let host = &self.ssh_client.profile.as_ref()?.host;
+        let stats = &self.ssh_client.stats;
+        let mut details = Vec::new();
+        if host.kind.uses_ssh() {
+            details.push(format!("{}@{}:{}", host.user, host.host, host.port));
+        } else {
+            details.push(host.name.clone());
+        }
+        if let Some(connected_at) = stats.connected_at {
+            details.push(format_elapsed(connected_at.elapsed()));
+        }
+        details.push(format!("↑{} ↓{}", format_size(stats.bytes_sent()), format_size(stats.bytes_received)));
+        if host.kind.uses_ssh() {
+            let key = self.ssh_client.profile.as_ref()
+                .and_then(|profile| profile.key_path.as_deref())
+                .map(|path| Path::new(path).file_name().map_or_else(|| path.to_string(), |name| name.to_string_lossy().to_string()))
+                .unwrap_or_else(|| "agent".to_string());
+            details.push(format!("key {}", key));
+            match stats.latency {
+                Some(latency) if latency < Duration::from_millis(10) => {
+                    details.push(format!("{:.1} ms", latency.as_secs_f64() * 1000.0));
+                },
+                Some(latency) => details.push(format!("{} ms", latency.as_millis())),
+                None => details.push("– ms".to_string()),
+            }
+            if let Some(keepalive) = host.keepalive.as_ref().filter(|_| host.kind != ConnectionKind::Mosh) {
+                details.push(format!("keep-alive {}s", keepalive.interval_secs));
+            }
+        }
+        if let Some(idle) = self.idle_time() {
+            details.push(format!("idle {}", format_duration(chrono::Duration::seconds(idle.as_secs() as i64))));
+        }
+        Some(details)
+    }
+
+    /// Time a TCP handshake with the connected host every ten seconds. The
+    /// ssh session itself cannot be pinged, and a host reached through a
+    /// jump host may not answer directly; its latency then stays unknown.
+    pub fn poll_latency(&mut self) {
+        if let Some(check) = &mut self.latency_check {
+            if let Some(latency) = check.receiver.as_mut().and_then(|receiver| receiver.try_recv().ok()) {
+                check.receiver = None;
+                if self.ssh_client.get_host().is_some_and(|host| host.name == check.host_name) {
+                    self.ssh_client.stats.latency = latency;
+                }
+            }
+        }
+
+        if !self.ssh_client.is_connected() {
+            self.latency_check = None;
+            return;
+        }
+        let Some(host) = self.ssh_client.profile.as_ref().map(|profile| profile.host.clone()) else {
+            return;
+        };
+        if !host.kind.uses_ssh() {
+            return;
+        }
+        // Another session brought to the front is measured right away
+        let due = self.latency_check.as_ref().is_none_or(|check| {
+            check.host_name != host.name || (check.receiver.is_none() && check.started.elapsed() >= LATENCY_INTERVAL)
+        });
+        if !due {
+            return;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let host_name = host.name.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = TcpPing { port: None }.run(&host).await;
+            let _ = sender.send((result.status == ProbeStatus::Up).then(|| started.elapsed()));
+        });
+        self.latency_check = Some(LatencyCheck { host_name, started: Instant::now(), receiver: Some(receiver) });
+    }
+}
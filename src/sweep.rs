@@ -0,0 +1,102 @@
+use crate::{AppState, MessageType};
+use crate::config::ConnectionKind;
+use crate::probe::{Probe, ProbeStatus, TcpPing};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Where a host stands in a reachability check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepStatus {
+    Checking,
+    /// Accepted a TCP connection in this long
+    Reachable(Duration),
+    Unreachable,
+}
+
+/// A TCP check of every listed host at once, e.g. before a maintenance
+/// window. Kept when done so the host list goes on showing its results.
+pub struct Sweep {
+    /// What was checked, such as the group's name
+    pub label: String,
+    pub results: HashMap<Uuid, SweepStatus>,
+    receiver: mpsc::UnboundedReceiver<(Uuid, SweepStatus)>,
+}
+
+impl Sweep {
+    pub fn is_done(&self) -> bool {
+        !self.results.values().any(|status| *status == SweepStatus::Checking)
+    }
+}
+
+impl AppState {
+    /// Connect to the port of every visible host of the selected group at
+    /// once and note which answer, and how fast
+    pub fn start_sweep(&mut self) {
+        if self.sweep.as_ref().is_some_and(|sweep| !sweep.is_done()) {
+            self.set_message("Hosts are still being checked".to_string(), MessageType::Info);
+            return;
+        }
+        let label = match self.virtual_group {
+            Some(view) => view.name().to_string(),
+            None => match self.config.groups.get(self.selected_group) {
+                Some(group) => group.name.clone(),
+                None => return,
+            },
+        };
+        // Custom commands have no address of their own to check
+        let hosts: Vec<_> = self.visible_hosts().into_iter()
+            .filter(|host| !matches!(host.kind, ConnectionKind::Custom(_)))
+            .collect();
+        if hosts.is_empty() {
+            self.set_message(format!("'{}' has no hosts to check", label), MessageType::Error);
+            return;
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut results = HashMap::new();
+        for host in hosts {
+            results.insert(host.id, SweepStatus::Checking);
+            let host = self.config.effective_host(&host);
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let started = Instant::now();
+                let result = TcpPing { port: None }.run(&host).await;
+                let status = match result.status {
+                    ProbeStatus::Up => SweepStatus::Reachable(started.elapsed()),
+                    _ => SweepStatus::Unreachable,
+                };
+                let _ = sender.send((host.id, status));
+            });
+        }
+        self.set_message(format!("Checking {} host(s) of '{}'...", results.len(), label), MessageType::Info);
+        self.sweep = Some(Sweep { label, results, receiver });
+    }
+
+    /// Fill in the hosts that answered or timed out, and sum up once all have
+    pub fn poll_sweep(&mut self) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+        if sweep.is_done() {
+            return;
+        }
+        while let Ok((host_id, status)) = sweep.receiver.try_recv() {
+            sweep.results.insert(host_id, status);
+        }
+        if !sweep.is_done() {
+            return;
+        }
+        let reachable = sweep.results.values().filter(|status| matches!(status, SweepStatus::Reachable(_))).count();
+        let total = sweep.results.len();
+        let message = format!("{} of {} host(s) of '{}' reachable", reachable, total, sweep.label);
+        let message_type = if reachable == total { MessageType::Success } else { MessageType::Error };
+        self.set_message(message, message_type);
+    }
+
+    /// The last check's result for a host, if it was part of it
+    pub fn sweep_status(&self, host_id: Uuid) -> Option<SweepStatus> {
+        self.sweep.as_ref()?.results.get(&host_id).copied()
+    }
+}
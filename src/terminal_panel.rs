@@ -1,34 +1,107 @@
+use crate::mouse::MouseReporting;
 use ratatui::style::Color;
 use ratatui::prelude::*;
+use std::cell::RefCell;
 use std::collections::VecDeque;
-use std::io::{stdout, Write};
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 use vte::{Params, Parser, Perform};
 
+/// Predictions the server has not echoed within this time are dropped
+const PREDICTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lines kept after they scroll off the top of the panel
+const SCROLLBACK_LINES: usize = 5000;
+
+/// Colors of a panel's frame, taken from the interface's theme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelColors {
+    /// Border of the active panel, without a group color, and the banner
+    pub accent: Color,
+    /// Border of the other panels
+    pub subtle: Color,
+}
+
 /// A terminal panel that can display raw SSH output within specific UI bounds
 /// while allowing the TUI framework to control the rest of the screen
 pub struct RawTerminalPanel {
     /// Panel bounds within the overall terminal
     bounds: Rect,
+    /// Room the layout gives the panel; `bounds` is smaller with a fixed size
+    area: Rect,
+    /// Columns and rows the session is held at instead of filling `area`
+    fixed_size: Option<(u16, u16)>,
     /// Current cursor position within the panel (relative to panel origin)
     cursor_x: u16,
     cursor_y: u16,
     /// Terminal content buffer - each line is a vector of styled characters
     lines: Vec<Vec<StyledChar>>,
+    /// Lines scrolled off the top of the screen, oldest first
+    scrollback: VecDeque<Vec<StyledChar>>,
+    /// How many lines of scrollback the view is moved up by; 0 follows the
+    /// output
+    scroll_offset: usize,
+    /// Each line as last drawn, rebuilt only after it changes (`None`)
+    rendered_lines: RefCell<Vec<Option<Line<'static>>>>,
     /// VTE parser for handling ANSI escape sequences
     parser: Parser,
     /// Current text style
     current_style: Style,
     /// Whether the panel is currently focused/active
     is_active: bool,
-    /// Buffer for accumulating data before processing
-    input_buffer: Vec<u8>,
+    /// Status line drawn over the top of the panel (e.g. while reconnecting)
+    banner: Option<String>,
+    /// Whether the user is currently looking at the panel
+    viewed: bool,
+    /// New output arrived since the panel was last viewed
+    activity: bool,
+    /// The remote side rang the bell since the panel was last viewed
+    bell: bool,
+    /// A bell arrived that the owner has not handled yet (`take_bell`)
+    rang: bool,
+    /// Visual bell: the panel is drawn inverted until then
+    flash_until: Option<Instant>,
+    /// Title set by the remote shell (OSC 0/2)
+    title: Option<String>,
+    /// Working directory reported by the remote shell (OSC 7)
+    cwd: Option<String>,
+    /// Remote application asked for pastes to be bracketed (DECSET 2004)
+    bracketed_paste: bool,
+    /// Mouse events the remote application wants (DECSET 1000/1002/1003/1006)
+    mouse: MouseReporting,
+    /// Cursor keys send application sequences (DECCKM, DECSET 1)
+    application_cursor: bool,
+    /// The remote application has not hidden the cursor (DECTCEM, DECSET 25)
+    cursor_visible: bool,
+    /// Text reaching the right edge continues on the next line (DECAWM,
+    /// DECSET 7); otherwise it overwrites the last column
+    autowrap: bool,
+    /// Cursor positions count from the top of the scroll region (DECOM,
+    /// DECSET 6)
+    origin_mode: bool,
+    /// First and last rows that scroll (DECSTBM); the whole panel when unset
+    scroll_region: Option<(u16, u16)>,
+    /// The session shares another process's master connection
+    multiplexed: bool,
+    /// Border of the active panel, in the color of the host's group
+    border_color: Option<Color>,
+    /// Typed characters waiting for the remote echo, oldest first; drawn
+    /// ahead of it with local echo prediction on
+    predictions: VecDeque<(char, Instant)>,
+    /// Draw the predictions (the local echo setting)
+    show_predictions: bool,
+    /// The remote has echoed a prediction since the last Enter, so the
+    /// pending ones are shown (keeps password prompts hidden)
+    predictions_confirmed: bool,
 }
 
 #[derive(Clone, Debug)]
 struct StyledChar {
     ch: char,
     style: Style,
+    /// Second cell of a wide character (CJK, most emoji), drawn by the
+    /// character in the cell before it
+    wide_tail: bool,
 }
 
 impl Default for StyledChar {
@@ -36,10 +109,47 @@ impl Default for StyledChar {
         Self {
             ch: ' ',
             style: Style::default(),
+            wide_tail: false,
         }
     }
 }
 
+/// Spans for the first `width` cells of a line, one per run of a style
+fn styled_line(line: &[StyledChar], width: usize) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut current_span_text = String::new();
+    let mut current_span_style = Style::default();
+    let mut after_wide = false;
+
+    for styled_char in line.iter().take(width) {
+        // The wide character before it already covers this cell; a tail
+        // whose character was overwritten shows as a blank
+        let ch = match (styled_char.wide_tail, after_wide) {
+            (true, true) => {
+                after_wide = false;
+                continue;
+            },
+            (true, false) => ' ',
+            (false, _) => styled_char.ch,
+        };
+        after_wide = ch.width() == Some(2);
+
+        // If style changes, flush current span and start new one
+        if styled_char.style != current_span_style && !current_span_text.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut current_span_text), current_span_style));
+        }
+
+        current_span_style = styled_char.style;
+        current_span_text.push(ch);
+    }
+
+    // Flush remaining text
+    if !current_span_text.is_empty() {
+        spans.push(Span::styled(current_span_text, current_span_style));
+    }
+    Line::from(spans)
+}
+
 impl RawTerminalPanel {
     pub fn new(bounds: Rect) -> Self {
         let height = bounds.height as usize;
@@ -57,27 +167,233 @@ impl RawTerminalPanel {
 
         Self {
             bounds,
+            area: bounds,
+            fixed_size: None,
             cursor_x: 0,
             cursor_y: 0,
             lines,
+            scrollback: VecDeque::new(),
+            scroll_offset: 0,
+            rendered_lines: RefCell::new(vec![None; height]),
             parser: Parser::new(),
             current_style: Style::default(),
             is_active: false,
-            input_buffer: Vec::new(),
+            banner: None,
+            viewed: true,
+            activity: false,
+            bell: false,
+            rang: false,
+            flash_until: None,
+            title: None,
+            cwd: None,
+            bracketed_paste: false,
+            mouse: MouseReporting::default(),
+            application_cursor: false,
+            cursor_visible: true,
+            autowrap: true,
+            origin_mode: false,
+            scroll_region: None,
+            multiplexed: false,
+            border_color: None,
+            predictions: VecDeque::new(),
+            show_predictions: false,
+            predictions_confirmed: false,
         }
     }
 
-    pub fn set_bounds(&mut self, bounds: Rect) {
+    pub fn set_bounds(&mut self, area: Rect) {
+        self.area = area;
+        // A fixed size sits in the top left corner, cut down if it does not fit
+        let bounds = match self.fixed_size {
+            Some((columns, rows)) => Rect {
+                width: area.width.min(columns + 2),
+                height: area.height.min(rows + 2),
+                ..area
+            },
+            None => area,
+        };
         if self.bounds != bounds {
             self.bounds = bounds;
             self.resize_buffer();
         }
     }
 
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    /// Hold the session at this many columns and rows, or None to fill the
+    /// panel
+    pub fn set_fixed_size(&mut self, size: Option<(u16, u16)>) {
+        self.fixed_size = size;
+        self.set_bounds(self.area);
+    }
+
     pub fn set_active(&mut self, active: bool) {
         self.is_active = active;
     }
 
+    pub fn set_banner(&mut self, banner: Option<String>) {
+        self.banner = banner;
+    }
+
+    /// Mark whether the panel is being looked at; viewing it clears the
+    /// activity and bell markers
+    pub fn set_viewed(&mut self, viewed: bool) {
+        self.viewed = viewed;
+        if viewed {
+            self.activity = false;
+            self.bell = false;
+        }
+    }
+
+    pub fn has_activity(&self) -> bool {
+        self.activity
+    }
+
+    pub fn has_bell(&self) -> bool {
+        self.bell
+    }
+
+    /// Whether a bell rang since the last call
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.rang)
+    }
+
+    /// Drop the bell marker, for sessions whose bells are ignored
+    pub fn clear_bell(&mut self) {
+        self.bell = false;
+    }
+
+    /// Invert the panel briefly as a visual bell
+    pub fn flash(&mut self) {
+        self.flash_until = Some(Instant::now() + Duration::from_millis(150));
+    }
+
+    /// Remote working directory as last reported via OSC 7
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    /// Label describing the session: the remote title if one was set,
+    /// otherwise the working directory
+    pub fn session_label(&self) -> Option<&str> {
+        self.title.as_deref().or(self.cwd.as_deref())
+    }
+
+    /// Forget the title, working directory and modes of the previous session
+    pub fn reset_session_info(&mut self) {
+        self.title = None;
+        self.cwd = None;
+        self.bracketed_paste = false;
+        self.mouse = MouseReporting::default();
+        self.application_cursor = false;
+        self.cursor_visible = true;
+        self.autowrap = true;
+        self.origin_mode = false;
+        self.scroll_region = None;
+        self.scroll_offset = 0;
+        self.reset_predictions();
+    }
+
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    pub fn application_cursor(&self) -> bool {
+        self.application_cursor
+    }
+
+    pub fn mouse_reporting(&self) -> MouseReporting {
+        self.mouse
+    }
+
+    /// Cell of the remote screen under a point of the window, if the point
+    /// lies inside the panel's border
+    pub fn cell_at(&self, column: u16, row: u16) -> Option<(u16, u16)> {
+        let inner = Rect {
+            x: self.bounds.x + 1,
+            y: self.bounds.y + 1,
+            width: self.bounds.width.saturating_sub(2),
+            height: self.bounds.height.saturating_sub(2),
+        };
+        let inside = column >= inner.x && column < inner.x + inner.width
+            && row >= inner.y && row < inner.y + inner.height;
+        inside.then(|| (column - inner.x, row - inner.y))
+    }
+
+    pub fn set_multiplexed(&mut self, multiplexed: bool) {
+        self.multiplexed = multiplexed;
+    }
+
+    pub fn set_border_color(&mut self, color: Option<Color>) {
+        self.border_color = color;
+    }
+
+    /// Lines the view is scrolled up into the scrollback
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    /// Scroll the view up into the scrollback, or down with a negative
+    /// count; stops at the oldest line and at the live screen
+    pub fn scroll_view(&mut self, lines: isize) {
+        self.set_scroll_offset(self.scroll_offset.saturating_add_signed(lines));
+    }
+
+    pub fn set_scroll_offset(&mut self, offset: usize) {
+        self.scroll_offset = offset.min(self.scrollback.len());
+    }
+
+    /// Note a typed character, drawn at the cursor until the remote echoes
+    /// it when `show` (local echo prediction) is on
+    pub fn predict(&mut self, ch: char, show: bool) {
+        self.show_predictions = show;
+        if self.predictions.front().is_some_and(|(_, typed_at)| typed_at.elapsed() > PREDICTION_TIMEOUT) {
+            self.reset_predictions();
+        }
+        self.predictions.push_back((ch, Instant::now()));
+    }
+
+    /// Drop pending predictions; the next ones stay hidden until the remote
+    /// echoes one of them
+    pub fn reset_predictions(&mut self) {
+        self.predictions.clear();
+        self.predictions_confirmed = false;
+    }
+
+    /// Match printed output against the oldest prediction
+    fn confirm_prediction(&mut self, ch: char) {
+        match self.predictions.front() {
+            Some((predicted, _)) if *predicted == ch => {
+                self.predictions.pop_front();
+                self.predictions_confirmed = true;
+            },
+            Some(_) => self.reset_predictions(),
+            None => {}
+        }
+    }
+
+    /// Whether the remote has echoed what was typed since the last Enter
+    pub fn remote_echoes(&self) -> bool {
+        self.predictions_confirmed
+    }
+
+    /// Whether a typed character went unechoed for long enough to tell the
+    /// remote is not echoing, as at a password prompt
+    pub fn echo_missing(&self) -> bool {
+        !self.predictions_confirmed && self.predictions.front().is_some_and(|(_, typed_at)| typed_at.elapsed() > PREDICTION_TIMEOUT)
+    }
+
+    /// Predictions to draw, if they are trusted and not stale
+    fn visible_predictions(&self) -> Option<String> {
+        let (_, oldest) = self.predictions.front()?;
+        if !self.show_predictions || !self.predictions_confirmed || oldest.elapsed() > PREDICTION_TIMEOUT {
+            return None;
+        }
+        Some(self.predictions.iter().map(|(ch, _)| ch).collect())
+    }
+
     /// Resize the internal buffer to match new bounds
     fn resize_buffer(&mut self) {
         let new_height = self.bounds.height as usize;
@@ -100,6 +416,9 @@ impl RawTerminalPanel {
         }
 
         self.lines = new_lines;
+        *self.rendered_lines.get_mut() = vec![None; new_height];
+        // As in xterm, a new size brings back the full scroll region
+        self.scroll_region = None;
 
         // Adjust cursor position if needed
         if self.cursor_x >= self.bounds.width {
@@ -112,59 +431,40 @@ impl RawTerminalPanel {
 
     /// Process SSH output data - this is where the raw terminal magic happens
     pub fn write_ssh_data(&mut self, data: &[u8]) {
-        // Store data temporarily and process it with VTE parser
-        self.input_buffer.extend_from_slice(data);
-        
-        // Process all buffered data
-        let buffer_copy = self.input_buffer.clone();
-        self.input_buffer.clear();
-        
-        // Process each byte through VTE parser
-        for byte in buffer_copy {
-            // We need to handle the borrowing issue by separating parser from self
-            let mut temp_parser = std::mem::replace(&mut self.parser, Parser::new());
-            temp_parser.advance(self, byte);
-            self.parser = temp_parser;
-        }
-    }
-    
-    fn write_char_at_cursor(&mut self, ch: char) {
-        let inner_width = (self.bounds.width.saturating_sub(2)) as usize;
-        let inner_height = (self.bounds.height.saturating_sub(2)) as usize;
-        
-        if (self.cursor_y as usize) < self.lines.len() && (self.cursor_x as usize) < inner_width {
-            let line = &mut self.lines[self.cursor_y as usize];
-            if (self.cursor_x as usize) < line.len() {
-                line[self.cursor_x as usize] = StyledChar {
-                    ch,
-                    style: self.current_style,
-                };
-            }
+        if !self.viewed && !data.is_empty() {
+            self.activity = true;
         }
 
-        self.cursor_x += 1;
-        if self.cursor_x >= inner_width as u16 {
-            // Line wrap
-            self.cursor_x = 0;
-            self.cursor_y += 1;
-            if self.cursor_y >= inner_height as u16 {
-                self.scroll_up();
-                self.cursor_y = inner_height.saturating_sub(1) as u16;
-            }
+        // The parser is taken out of the panel while it drives it, once for
+        // the whole chunk
+        let mut parser = std::mem::take(&mut self.parser);
+        for &byte in data {
+            parser.advance(self, byte);
         }
+        self.parser = parser;
     }
 
     /// Render the terminal panel content to the screen
     /// This integrates with the TUI framework but writes raw content to our panel area
-    pub fn render(&self, frame: &mut Frame) {
+    pub fn render(&self, frame: &mut Frame, colors: PanelColors) {
         // Create block for the terminal panel
+        let mut status = if self.multiplexed { " (multiplexed)" } else { "" }.to_string();
+        if self.scroll_offset > 0 {
+            status.push_str(&format!(" [{} lines up]", self.scroll_offset));
+        }
+        let title = match (self.title.as_deref(), self.cwd.as_deref()) {
+            // A title set by the remote shell names the panel, as it would a terminal window
+            (Some(title), _) => format!("{}{}", title, status),
+            (None, Some(cwd)) => format!("SSH Terminal{} - {}", status, cwd),
+            (None, None) => format!("SSH Terminal{}", status),
+        };
         let block = ratatui::widgets::Block::default()
             .borders(ratatui::widgets::Borders::ALL)
-            .title("SSH Terminal")
+            .title(title)
             .border_style(if self.is_active {
-                Style::default().fg(Color::Yellow)
+                Style::default().fg(self.border_color.unwrap_or(colors.accent))
             } else {
-                Style::default().fg(Color::Gray)
+                Style::default().fg(colors.subtle)
             });
 
         // Calculate inner area for terminal content first
@@ -173,52 +473,63 @@ impl RawTerminalPanel {
         // Render block
         frame.render_widget(block, self.bounds);
         
-        // Render terminal content line by line
-        for (y, line) in self.lines.iter().enumerate() {
-            if y >= inner.height as usize {
-                break;
+        // Scrolled up, the view starts in the scrollback and shows no cursor
+        if self.scroll_offset > 0 {
+            let first = self.scrollback.len() - self.scroll_offset;
+            let rows = self.scrollback.range(first..).chain(self.lines.iter());
+            for (y, line) in rows.enumerate().take(inner.height as usize) {
+                frame.buffer_mut().set_line(inner.x, inner.y + y as u16, &styled_line(line, inner.width as usize), inner.width);
             }
+            return;
+        }
 
-            let mut spans = Vec::new();
-            let mut current_span_text = String::new();
-            let mut current_span_style = Style::default();
-
-            for (x, styled_char) in line.iter().enumerate() {
-                if x >= inner.width as usize {
-                    break;
-                }
-
-                // If style changes, flush current span and start new one
-                if styled_char.style != current_span_style && !current_span_text.is_empty() {
-                    spans.push(Span::styled(current_span_text, current_span_style));
-                    current_span_text = String::new();
-                }
+        // Render terminal content line by line, rebuilding the spans of
+        // the lines that changed since the last frame
+        let mut rendered_lines = self.rendered_lines.borrow_mut();
+        for (y, line) in self.lines.iter().enumerate().take(inner.height as usize) {
+            let Some(rendered) = rendered_lines.get_mut(y) else {
+                break;
+            };
+            let rendered = rendered.get_or_insert_with(|| styled_line(line, inner.width as usize));
+            frame.buffer_mut().set_line(inner.x, inner.y + y as u16, rendered, inner.width);
+        }
+        drop(rendered_lines);
 
-                current_span_style = styled_char.style;
-                current_span_text.push(styled_char.ch);
+        // Render predicted characters after the cursor, underlined so they
+        // read as not yet confirmed
+        let mut cursor_x = self.cursor_x;
+        if let Some(predicted) = self.visible_predictions() {
+            if self.cursor_y < inner.height && self.cursor_x < inner.width {
+                let width = (predicted.width() as u16).min(inner.width - self.cursor_x);
+                let predicted_widget = ratatui::widgets::Paragraph::new(predicted)
+                    .style(Style::default().fg(Color::Gray).add_modifier(Modifier::UNDERLINED));
+                frame.render_widget(predicted_widget, Rect {
+                    x: inner.x + self.cursor_x,
+                    y: inner.y + self.cursor_y,
+                    width,
+                    height: 1,
+                });
+                cursor_x = (self.cursor_x + width).min(inner.width - 1);
             }
+        }
 
-            // Flush remaining text
-            if !current_span_text.is_empty() {
-                spans.push(Span::styled(current_span_text, current_span_style));
-            }
+        // Render banner over the first line
+        if let Some(banner) = &self.banner {
+            let banner_widget = ratatui::widgets::Paragraph::new(banner.as_str())
+                .style(Style::default().bg(colors.accent).fg(Color::Black).add_modifier(Modifier::BOLD))
+                .alignment(Alignment::Center);
+            frame.render_widget(banner_widget, Rect { height: 1.min(inner.height), ..inner });
+        }
 
-            // Render this line
-            let line_widget = ratatui::widgets::Paragraph::new(Line::from(spans));
-            let line_area = Rect {
-                x: inner.x,
-                y: inner.y + y as u16,
-                width: inner.width,
-                height: 1,
-            };
-            
-            frame.render_widget(line_widget, line_area);
+        // Visual bell
+        if self.flash_until.is_some_and(|until| Instant::now() < until) {
+            frame.buffer_mut().set_style(self.bounds, Style::default().add_modifier(Modifier::REVERSED));
         }
 
-        // Render cursor if active
-        if self.is_active && self.cursor_y < inner.height && self.cursor_x < inner.width {
+        // Render cursor if active and not hidden by the remote application
+        if self.is_active && self.cursor_visible && self.cursor_y < inner.height && cursor_x < inner.width {
             let cursor_area = Rect {
-                x: inner.x + self.cursor_x,
+                x: inner.x + cursor_x,
                 y: inner.y + self.cursor_y,
                 width: 1,
                 height: 1,
@@ -240,6 +551,7 @@ impl RawTerminalPanel {
 
     /// Clear the terminal content
     pub fn clear(&mut self) {
+        self.touch_all_lines();
         for line in &mut self.lines {
             for styled_char in line {
                 *styled_char = StyledChar::default();
@@ -249,35 +561,120 @@ impl RawTerminalPanel {
         self.cursor_y = 0;
     }
 
-    /// Scroll the terminal content up by one line
+    /// Have a line's spans rebuilt on the next frame
+    fn touch_line(&mut self, row: usize) {
+        if let Some(rendered) = self.rendered_lines.get_mut().get_mut(row) {
+            *rendered = None;
+        }
+    }
+
+    /// Have every line rebuilt on the next frame, after clearing or
+    /// scrolling
+    fn touch_all_lines(&mut self) {
+        self.rendered_lines.get_mut().fill(None);
+    }
+
+    /// First and last rows that scroll, the whole panel unless the remote
+    /// application set a region
+    fn scroll_region(&self) -> (u16, u16) {
+        let last_row = self.bounds.height.saturating_sub(3);
+        self.scroll_region.unwrap_or((0, last_row))
+    }
+
+    /// Scroll the lines of the scroll region up by one line
     fn scroll_up(&mut self) {
-        // Move all lines up
-        for i in 1..self.lines.len() {
+        self.touch_all_lines();
+        let (top, bottom) = self.scroll_region();
+        let bottom = (bottom as usize).min(self.lines.len().saturating_sub(1));
+        // As in xterm, only lines leaving the top of the screen are kept
+        if top == 0 && bottom > 0 {
+            self.keep_in_scrollback(self.lines[0].clone());
+        }
+        // Move the region's lines up
+        for i in (top as usize + 1)..=bottom {
             self.lines[i - 1] = self.lines[i].clone();
         }
         
-        // Clear the last line
-        if let Some(last_line) = self.lines.last_mut() {
+        // Clear the region's last line
+        if let Some(last_line) = self.lines.get_mut(bottom) {
             for styled_char in last_line {
                 *styled_char = StyledChar::default();
             }
         }
     }
 
+    /// Add a line to the scrollback, keeping a scrolled-up view on the
+    /// same text
+    fn keep_in_scrollback(&mut self, line: Vec<StyledChar>) {
+        self.scrollback.push_back(line);
+        if self.scrollback.len() > SCROLLBACK_LINES {
+            self.scrollback.pop_front();
+        }
+        if self.scroll_offset > 0 {
+            self.scroll_offset = (self.scroll_offset + 1).min(self.scrollback.len());
+        }
+    }
+
+    /// Scroll the lines of the scroll region down by one line, opening a
+    /// blank line at its top
+    fn scroll_down(&mut self) {
+        self.touch_all_lines();
+        let (top, bottom) = self.scroll_region();
+        let bottom = (bottom as usize).min(self.lines.len().saturating_sub(1));
+        let Some(region) = self.lines.get_mut(top as usize..=bottom) else {
+            return;
+        };
+        region.rotate_right(1);
+        for styled_char in region[0].iter_mut() {
+            *styled_char = StyledChar::default();
+        }
+    }
+
+    /// Move to the previous line, scrolling the region down at its top
+    /// margin (reverse index)
+    fn reverse_line_feed(&mut self) {
+        let (top, _) = self.scroll_region();
+        if self.cursor_y == top {
+            self.scroll_down();
+        } else {
+            self.cursor_y = self.cursor_y.saturating_sub(1);
+        }
+    }
+
+    /// Move to the next line, scrolling the region at its bottom margin
+    fn line_feed(&mut self) {
+        let (_, bottom) = self.scroll_region();
+        let last_row = self.bounds.height.saturating_sub(3);
+        if self.cursor_y == bottom {
+            self.scroll_up();
+        } else if self.cursor_y < last_row {
+            self.cursor_y += 1;
+        }
+    }
+
+    /// Blank the other half of a wide character when the cell at `x` is
+    /// about to be overwritten, so no half of it is left behind
+    fn break_wide_char(&mut self, y: usize, x: usize) {
+        let Some(line) = self.lines.get_mut(y) else {
+            return;
+        };
+        if x > 0 && line.get(x).is_some_and(|cell| cell.wide_tail) {
+            line[x - 1] = StyledChar::default();
+        }
+        if line.get(x + 1).is_some_and(|cell| cell.wide_tail) {
+            line[x + 1] = StyledChar::default();
+        }
+    }
+
     /// Write a character at the current cursor position
     fn write_char(&mut self, ch: char) {
         let inner_width = self.bounds.width.saturating_sub(2) as usize;
-        let inner_height = self.bounds.height.saturating_sub(2) as usize;
 
         match ch {
             '\n' => {
                 // Newline - move to next line
                 self.cursor_x = 0;
-                self.cursor_y += 1;
-                if self.cursor_y >= inner_height as u16 {
-                    self.scroll_up();
-                    self.cursor_y = inner_height.saturating_sub(1) as u16;
-                }
+                self.line_feed();
             },
             '\r' => {
                 // Carriage return - move to start of line
@@ -292,26 +689,56 @@ impl RawTerminalPanel {
                 // Skip other control characters
             },
             _ => {
-                // Regular character - write it
-                if (self.cursor_y as usize) < self.lines.len() && (self.cursor_x as usize) < inner_width {
-                    let line = &mut self.lines[self.cursor_y as usize];
-                    if (self.cursor_x as usize) < line.len() {
-                        line[self.cursor_x as usize] = StyledChar {
+                // Regular character - write it, a wide one over two cells.
+                // Combining marks have no cell of their own and are dropped.
+                let width = match ch.width() {
+                    Some(0) | None => return,
+                    Some(width) => width as u16,
+                };
+                let inner_width = inner_width as u16;
+                if width > inner_width {
+                    return;
+                }
+                if self.cursor_x + width > inner_width {
+                    // A wide character does not fit in the last column
+                    if self.autowrap {
+                        self.cursor_x = 0;
+                        self.line_feed();
+                    } else {
+                        self.cursor_x = inner_width - width;
+                    }
+                }
+
+                let (x, y) = (self.cursor_x as usize, self.cursor_y as usize);
+                self.touch_line(y);
+                for cell in x..x + width as usize {
+                    self.break_wide_char(y, cell);
+                }
+                if let Some(line) = self.lines.get_mut(y) {
+                    if x + (width as usize) <= line.len() {
+                        line[x] = StyledChar {
                             ch,
                             style: self.current_style,
+                            wide_tail: false,
                         };
+                        if width == 2 {
+                            line[x + 1] = StyledChar {
+                                ch: ' ',
+                                style: self.current_style,
+                                wide_tail: true,
+                            };
+                        }
                     }
                 }
 
-                self.cursor_x += 1;
-                if self.cursor_x >= inner_width as u16 {
+                if self.cursor_x + width < inner_width {
+                    self.cursor_x += width;
+                } else if self.autowrap {
                     // Line wrap
                     self.cursor_x = 0;
-                    self.cursor_y += 1;
-                    if self.cursor_y >= inner_height as u16 {
-                        self.scroll_up();
-                        self.cursor_y = inner_height.saturating_sub(1) as u16;
-                    }
+                    self.line_feed();
+                } else {
+                    self.cursor_x = inner_width - 1;
                 }
             }
         }
@@ -321,6 +748,7 @@ impl RawTerminalPanel {
 /// Implement the VTE Perform trait to handle ANSI escape sequences
 impl Perform for RawTerminalPanel {
     fn print(&mut self, c: char) {
+        self.confirm_prediction(c);
         self.write_char(c);
     }
 
@@ -329,12 +757,17 @@ impl Perform for RawTerminalPanel {
             b'\n' => self.write_char('\n'),
             b'\r' => self.write_char('\r'),
             b'\t' => self.write_char('\t'),
-            0x08 => {
-                // Backspace
-                if self.cursor_x > 0 {
-                    self.cursor_x -= 1;
+            // Bell
+            0x07 => {
+                self.rang = true;
+                if !self.viewed {
+                    self.bell = true;
                 }
             },
+            // Backspace
+            0x08 if self.cursor_x > 0 => {
+                self.cursor_x -= 1;
+            },
             _ => {} // Ignore other control characters for now
         }
     }
@@ -351,38 +784,67 @@ impl Perform for RawTerminalPanel {
         // End DCS sequence
     }
 
-    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {
-        // Handle OSC sequences (like setting window title) if needed
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        match params {
+            // Icon name and window title
+            [b"0" | b"2", title @ ..] if !title.is_empty() => {
+                let title = title.iter()
+                    .map(|part| String::from_utf8_lossy(part))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                // Control characters would garble the panel border
+                let title: String = title.chars().filter(|c| !c.is_control()).collect();
+                let title = title.trim();
+                self.title = if title.is_empty() { None } else { Some(title.to_string()) };
+            },
+            // Current working directory as a file:// URL
+            [b"7", url, ..] => {
+                if let Some(cwd) = parse_osc7_url(&String::from_utf8_lossy(url)) {
+                    self.cwd = Some(cwd);
+                }
+            },
+            _ => {}
+        }
     }
 
-    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, c: char) {
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, c: char) {
         match c {
             'A' => {
                 // Cursor up
-                let n = params.iter().next().unwrap_or(&[1])[0] as u16;
+                let n = params.iter().next().unwrap_or(&[1])[0];
                 self.cursor_y = self.cursor_y.saturating_sub(n);
             },
             'B' => {
                 // Cursor down
-                let n = params.iter().next().unwrap_or(&[1])[0] as u16;
+                let n = params.iter().next().unwrap_or(&[1])[0];
                 self.cursor_y = (self.cursor_y + n).min(self.bounds.height.saturating_sub(3));
             },
             'C' => {
                 // Cursor forward
-                let n = params.iter().next().unwrap_or(&[1])[0] as u16;
+                let n = params.iter().next().unwrap_or(&[1])[0];
                 self.cursor_x = (self.cursor_x + n).min(self.bounds.width.saturating_sub(3));
             },
             'D' => {
                 // Cursor back
-                let n = params.iter().next().unwrap_or(&[1])[0] as u16;
+                let n = params.iter().next().unwrap_or(&[1])[0];
                 self.cursor_x = self.cursor_x.saturating_sub(n);
             },
             'H' | 'f' => {
-                // Cursor position
-                let row = params.iter().next().unwrap_or(&[1])[0] as u16;
-                let col = params.iter().nth(1).unwrap_or(&[1])[0] as u16;
-                self.cursor_y = (row.saturating_sub(1)).min(self.bounds.height.saturating_sub(3));
-                self.cursor_x = (col.saturating_sub(1)).min(self.bounds.width.saturating_sub(3));
+                // Cursor position, from the top of the scroll region in origin mode
+                let row = params.iter().next().unwrap_or(&[1])[0];
+                let col = params.iter().nth(1).unwrap_or(&[1])[0];
+                self.move_cursor_to(row.saturating_sub(1), col.saturating_sub(1));
+            },
+            'r' if intermediates.is_empty() => {
+                // Set scroll region (DECSTBM); the cursor goes home
+                let last_row = self.bounds.height.saturating_sub(3);
+                let top = params.iter().next().map_or(1, |param| param[0]).max(1) - 1;
+                let bottom = params.iter().nth(1).map_or(0, |param| param[0]);
+                let bottom = if bottom == 0 { last_row } else { (bottom - 1).min(last_row) };
+                if top < bottom {
+                    self.scroll_region = if (top, bottom) == (0, last_row) { None } else { Some((top, bottom)) };
+                    self.move_cursor_to(0, 0);
+                }
             },
             'J' => {
                 // Clear screen
@@ -404,61 +866,190 @@ impl Perform for RawTerminalPanel {
                 }
             },
             'K' => {
+                self.touch_line(self.cursor_y as usize);
                 // Clear line
                 let n = params.iter().next().unwrap_or(&[0])[0];
                 match n {
-                    0 => {
-                        // Clear from cursor to end of line
-                        if (self.cursor_y as usize) < self.lines.len() {
-                            let line = &mut self.lines[self.cursor_y as usize];
-                            for x in (self.cursor_x as usize)..line.len() {
-                                line[x] = StyledChar::default();
-                            }
+                    // Clear from cursor to end of line
+                    0 if (self.cursor_y as usize) < self.lines.len() => {
+                        let line = &mut self.lines[self.cursor_y as usize];
+                        for styled_char in line.iter_mut().skip(self.cursor_x as usize) {
+                            *styled_char = StyledChar::default();
                         }
                     },
-                    1 => {
-                        // Clear from start of line to cursor
-                        if (self.cursor_y as usize) < self.lines.len() {
-                            let line = &mut self.lines[self.cursor_y as usize];
-                            for x in 0..=(self.cursor_x as usize).min(line.len().saturating_sub(1)) {
-                                line[x] = StyledChar::default();
-                            }
+                    // Clear from start of line to cursor
+                    1 if (self.cursor_y as usize) < self.lines.len() => {
+                        let line = &mut self.lines[self.cursor_y as usize];
+                        for x in 0..=(self.cursor_x as usize).min(line.len().saturating_sub(1)) {
+                            line[x] = StyledChar::default();
                         }
                     },
-                    2 => {
-                        // Clear entire line
-                        if (self.cursor_y as usize) < self.lines.len() {
-                            let line = &mut self.lines[self.cursor_y as usize];
-                            for styled_char in line {
-                                *styled_char = StyledChar::default();
-                            }
+                    // Clear entire line
+                    2 if (self.cursor_y as usize) < self.lines.len() => {
+                        let line = &mut self.lines[self.cursor_y as usize];
+                        for styled_char in line {
+                            *styled_char = StyledChar::default();
                         }
                     },
                     _ => {}
                 }
             },
+            'S' | 'T' if intermediates.is_empty() => {
+                // Scroll the region up or down, leaving the cursor where it is
+                let n = params.iter().next().unwrap_or(&[1])[0].max(1);
+                for _ in 0..n.min(self.bounds.height) {
+                    if c == 'S' {
+                        self.scroll_up();
+                    } else {
+                        self.scroll_down();
+                    }
+                }
+            },
+            'L' | 'M' => {
+                // Insert or delete lines at the cursor, within the scroll region
+                let n = params.iter().next().unwrap_or(&[1])[0].max(1);
+                self.shift_lines(n as usize, c == 'L');
+            },
+            '@' | 'P' => {
+                // Insert or delete characters at the cursor
+                let n = params.iter().next().unwrap_or(&[1])[0].max(1);
+                self.shift_chars(n as usize, c == '@');
+            },
+            'X' => {
+                self.touch_line(self.cursor_y as usize);
+                // Erase characters from the cursor on, without moving the rest
+                let n = params.iter().next().unwrap_or(&[1])[0].max(1) as usize;
+                let inner_width = self.bounds.width.saturating_sub(2) as usize;
+                if let Some(line) = self.lines.get_mut(self.cursor_y as usize) {
+                    let end = (self.cursor_x as usize + n).min(inner_width).min(line.len());
+                    for styled_char in line.iter_mut().take(end).skip(self.cursor_x as usize) {
+                        *styled_char = StyledChar::default();
+                    }
+                }
+            },
             'm' => {
                 // Set graphics rendition (colors, bold, etc.)
                 self.handle_sgr(params);
             },
+            // DEC private modes (DECSET / DECRST)
+            'h' | 'l' if intermediates == b"?" => {
+                let enabled = c == 'h';
+                for param in params.iter() {
+                    match param.first() {
+                        Some(&1) => self.application_cursor = enabled,
+                        Some(&6) => {
+                            self.origin_mode = enabled;
+                            self.move_cursor_to(0, 0);
+                        },
+                        Some(&7) => self.autowrap = enabled,
+                        Some(&25) => self.cursor_visible = enabled,
+                        Some(&2004) => self.bracketed_paste = enabled,
+                        Some(&mode) => self.mouse.set_mode(mode, enabled),
+                        None => {}
+                    }
+                }
+            },
             _ => {
                 // Ignore other CSI sequences for now
             }
         }
     }
 
-    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {
-        // Handle ESC sequences if needed
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        if !intermediates.is_empty() {
+            return;
+        }
+        match byte {
+            // Index (IND): down a line, scrolling at the bottom margin
+            b'D' => self.line_feed(),
+            // Next line (NEL): as index, back at the first column
+            b'E' => {
+                self.cursor_x = 0;
+                self.line_feed();
+            },
+            // Reverse index (RI): up a line, scrolling at the top margin
+            b'M' => self.reverse_line_feed(),
+            _ => {}
+        }
     }
 }
 
 impl RawTerminalPanel {
+    /// Put the cursor at a zero-based row and column, counting rows from
+    /// the top of the scroll region and keeping within it in origin mode
+    fn move_cursor_to(&mut self, row: u16, column: u16) {
+        let (top, bottom) = if self.origin_mode {
+            self.scroll_region()
+        } else {
+            (0, self.bounds.height.saturating_sub(3))
+        };
+        self.cursor_y = (top + row).min(bottom);
+        self.cursor_x = column.min(self.bounds.width.saturating_sub(3));
+    }
+
+    /// Open `n` blank lines at the cursor, pushing the ones below off the
+    /// bottom of the scroll region, or remove `n` lines there and pull the
+    /// rest up. Nothing happens with the cursor outside the region.
+    fn shift_lines(&mut self, n: usize, insert: bool) {
+        self.touch_all_lines();
+        let (top, bottom) = self.scroll_region();
+        if self.cursor_y < top || self.cursor_y > bottom {
+            return;
+        }
+        let bottom = (bottom as usize).min(self.lines.len().saturating_sub(1));
+        let Some(region) = self.lines.get_mut(self.cursor_y as usize..=bottom) else {
+            return;
+        };
+        let n = n.min(region.len());
+        let blank_rows = if insert {
+            region.rotate_right(n);
+            0..n
+        } else {
+            region.rotate_left(n);
+            region.len() - n..region.len()
+        };
+        for row in &mut region[blank_rows] {
+            for styled_char in row.iter_mut() {
+                *styled_char = StyledChar::default();
+            }
+        }
+        self.cursor_x = 0;
+    }
+
+    /// Insert `n` blanks at the cursor, pushing the rest of the line off
+    /// the right edge, or delete `n` characters and pull the rest left
+    fn shift_chars(&mut self, n: usize, insert: bool) {
+        self.touch_line(self.cursor_y as usize);
+        let inner_width = self.bounds.width.saturating_sub(2) as usize;
+        let start = self.cursor_x as usize;
+        let Some(line) = self.lines.get_mut(self.cursor_y as usize) else {
+            return;
+        };
+        let end = inner_width.min(line.len());
+        if start >= end {
+            return;
+        }
+        let rest = &mut line[start..end];
+        let n = n.min(rest.len());
+        let blanks = if insert {
+            rest.rotate_right(n);
+            0..n
+        } else {
+            rest.rotate_left(n);
+            rest.len() - n..rest.len()
+        };
+        for styled_char in &mut rest[blanks] {
+            *styled_char = StyledChar::default();
+        }
+    }
+
     fn clear_from_cursor(&mut self) {
+        self.touch_all_lines();
         // Clear from cursor to end of current line
         if (self.cursor_y as usize) < self.lines.len() {
             let line = &mut self.lines[self.cursor_y as usize];
-            for x in (self.cursor_x as usize)..line.len() {
-                line[x] = StyledChar::default();
+            for styled_char in line.iter_mut().skip(self.cursor_x as usize) {
+                *styled_char = StyledChar::default();
             }
         }
 
@@ -471,6 +1062,7 @@ impl RawTerminalPanel {
     }
 
     fn clear_to_cursor(&mut self) {
+        self.touch_all_lines();
         // Clear all lines above current line
         for y in 0..(self.cursor_y as usize) {
             if y < self.lines.len() {
@@ -555,3 +1147,119 @@ impl RawTerminalPanel {
         }
     }
 }
+
+/// Extract the path from an OSC 7 `file://host/path` URL
+fn parse_osc7_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = input.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let (b'%', Some(byte)) = (bytes[i], escaped) {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A panel with room for `columns` by `rows` inside its border
+    fn panel(columns: u16, rows: u16) -> RawTerminalPanel {
+        RawTerminalPanel::new(Rect::new(0, 0, columns + 2, rows + 2))
+    }
+
+    /// The text of each row inside the border, without trailing blanks
+    fn screen(panel: &RawTerminalPanel) -> Vec<String> {
+        let columns = panel.bounds.width.saturating_sub(2) as usize;
+        let rows = panel.bounds.height.saturating_sub(2) as usize;
+        panel.lines.iter()
+            .take(rows)
+            .map(|line| {
+                let text: String = line.iter().take(columns).filter(|cell| !cell.wide_tail).map(|cell| cell.ch).collect();
+                text.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    fn numbered(rows: u16) -> RawTerminalPanel {
+        let mut panel = panel(8, rows);
+        let text: Vec<String> = (1..=rows).map(|row| row.to_string()).collect();
+        panel.write_ssh_data(text.join("\r\n").as_bytes());
+        panel
+    }
+
+    #[test]
+    fn inserted_and_deleted_lines_stay_in_the_scroll_region() {
+        let mut panel = numbered(5);
+        // Region on rows 2-4, cursor on row 2
+        panel.write_ssh_data(b"\x1b[2;4r\x1b[2;1H\x1b[L");
+        assert_eq!(screen(&panel), ["1", "", "2", "3", "5"]);
+        panel.write_ssh_data(b"\x1b[2M");
+        assert_eq!(screen(&panel), ["1", "3", "", "", "5"]);
+        // Outside the region nothing moves
+        panel.write_ssh_data(b"\x1b[5;1H\x1b[L");
+        assert_eq!(screen(&panel), ["1", "3", "", "", "5"]);
+    }
+
+    #[test]
+    fn line_feed_at_the_region_bottom_scrolls_only_the_region() {
+        let mut panel = numbered(5);
+        panel.write_ssh_data(b"\x1b[2;4r\x1b[4;1H\nx");
+        assert_eq!(screen(&panel), ["1", "3", "4", "x", "5"]);
+    }
+
+    #[test]
+    fn inserted_and_deleted_characters_shift_the_rest_of_the_line() {
+        let mut panel = panel(8, 2);
+        panel.write_ssh_data(b"abcdef\x1b[1;3H\x1b[2@");
+        assert_eq!(screen(&panel)[0], "ab  cdef");
+        panel.write_ssh_data(b"\x1b[3P");
+        assert_eq!(screen(&panel)[0], "abdef");
+        // Pushed off the right edge
+        panel.write_ssh_data(b"\x1b[1;1H\x1b[5@");
+        assert_eq!(screen(&panel)[0], "     abd");
+    }
+
+    #[test]
+    fn origin_mode_addresses_rows_from_the_region_top() {
+        let mut panel = panel(8, 5);
+        panel.write_ssh_data(b"\x1b[2;4r\x1b[?6h\x1b[1;1HA\x1b[9;1HB");
+        assert_eq!(screen(&panel), ["", "A", "", "B", ""]);
+        panel.write_ssh_data(b"\x1b[?6l\x1b[1;1HC");
+        assert_eq!(screen(&panel), ["C", "A", "", "B", ""]);
+    }
+
+    #[test]
+    fn autowrap_moves_to_the_next_line_unless_turned_off() {
+        let mut wrapping = panel(4, 3);
+        wrapping.write_ssh_data(b"abcdef");
+        assert_eq!(screen(&wrapping), ["abcd", "ef", ""]);
+
+        let mut clipped = panel(4, 3);
+        clipped.write_ssh_data(b"\x1b[?7labcdef");
+        assert_eq!(screen(&clipped), ["abcf", "", ""]);
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let mut panel = panel(5, 2);
+        panel.write_ssh_data("a日本".as_bytes());
+        assert_eq!(screen(&panel), ["a日本", ""]);
+        // The third does not fit in the last column and wraps
+        panel.write_ssh_data("語".as_bytes());
+        assert_eq!(screen(&panel), ["a日本", "語"]);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::{ConnectionKind, Group, Host, GROUP_SEPARATOR};
+use crate::import::{self, Importer};
+
+/// Group for hosts Termius keeps outside any group
+const UNGROUPED: &str = "Termius";
+
+/// Reads the CSV Termius exports hosts as
+pub struct TermiusCsv;
+
+/// Column headers lowercased with everything but letters dropped, so
+/// "Hostname/IP" is "hostnameip"
+fn normalize(header: &str) -> String {
+    header.chars().filter(char::is_ascii_alphabetic).map(|c| c.to_ascii_lowercase()).collect()
+}
+
+/// Where each column Termius may write is, by any of the headers it has used
+struct Columns {
+    group: Option<usize>,
+    label: Option<usize>,
+    address: usize,
+    port: Option<usize>,
+    user: Option<usize>,
+    tags: Option<usize>,
+    protocol: Option<usize>,
+}
+
+impl Columns {
+    fn find(headers: &csv::StringRecord) -> Result<Self, String> {
+        let headers: Vec<String> = headers.iter().map(normalize).collect();
+        let column = |names: &[&str]| headers.iter().position(|header| names.contains(&header.as_str()));
+        Ok(Columns {
+            group: column(&["groups", "group"]),
+            label: column(&["label", "alias", "name"]),
+            address: column(&["hostnameip", "hostname", "host", "address", "ip"])
+                .ok_or("No Hostname/IP column; is this a Termius CSV export?")?,
+            port: column(&["port"]),
+            user: column(&["username", "user"]),
+            tags: column(&["tags"]),
+            protocol: column(&["protocol"]),
+        })
+    }
+}
+
+impl Importer for TermiusCsv {
+    /// Rows without an address, and ones for protocols other than ssh, mosh
+    /// and telnet, are left out. Nested groups are written "Parent/Child"
+    /// as in sshtui; passwords are not imported.
+    fn parse(&self, _path: &str, text: &str) -> Result<Vec<Group>, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(text.trim_start_matches('\u{feff}').as_bytes());
+        let columns = Columns::find(reader.headers().map_err(|e| e.to_string())?)?;
+
+        let mut hosts = Vec::new();
+        for (number, row) in reader.records().enumerate() {
+            // The header is line 1
+            let row = row.map_err(|e| format!("Line {}: {}", number + 2, e))?;
+            let field = |index: Option<usize>| index.and_then(|index| row.get(index)).unwrap_or("");
+            let address = field(Some(columns.address));
+            if address.is_empty() {
+                continue;
+            }
+            let kind = match field(columns.protocol).to_ascii_lowercase().as_str() {
+                "" | "ssh" => ConnectionKind::Ssh,
+                "mosh" => ConnectionKind::Mosh,
+                "telnet" => ConnectionKind::Telnet,
+                _ => continue,
+            };
+            let name = match field(columns.label) {
+                "" => address,
+                label => label,
+            };
+            let group = field(columns.group)
+                .split(GROUP_SEPARATOR)
+                .map(str::trim)
+                .filter(|level| !level.is_empty())
+                .collect::<Vec<_>>()
+                .join(&GROUP_SEPARATOR.to_string());
+            let host = Host {
+                user: field(columns.user).to_string(),
+                port: field(columns.port).parse().unwrap_or(0),
+                tags: field(columns.tags)
+                    .split([',', ';'])
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                kind,
+                ..Host::new(name, address)
+            };
+            hosts.push((if group.is_empty() { UNGROUPED.to_string() } else { group }, host));
+        }
+        Ok(import::into_groups(hosts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_headers() {
+        assert_eq!(normalize("Hostname/IP"), "hostnameip");
+        assert_eq!(normalize(" User Name "), "username");
+    }
+
+    #[test]
+    fn imports_hosts_by_group() {
+        let csv = "\u{feff}Groups,Label,Tags,Hostname/IP,Protocol,Port,Username\n\
+            Prod / Web,web1,\"app, eu\",10.0.0.1,ssh,2222,deploy\n\
+            ,laptop,,192.168.1.5,,,me\n\
+            Prod,router,,10.0.0.254,telnet,23,\n\
+            Prod,rdp box,,10.0.0.9,rdp,,\n\
+            Prod,no address,,,ssh,,\n";
+        let groups = TermiusCsv.parse("hosts.csv", csv).unwrap();
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["Prod", "Prod/Web", UNGROUPED]);
+        let web = &groups[1].hosts[0];
+        assert_eq!((web.name.as_str(), web.host.as_str(), web.user.as_str(), web.port), ("web1", "10.0.0.1", "deploy", 2222));
+        assert_eq!(web.tags, ["app", "eu"]);
+        assert_eq!(groups[0].hosts.len(), 1);
+        assert_eq!(groups[0].hosts[0].kind, ConnectionKind::Telnet);
+        assert_eq!(groups[2].hosts[0].port, 0);
+    }
+
+    #[test]
+    fn names_hosts_without_a_label_after_their_address() {
+        let groups = TermiusCsv.parse("hosts.csv", "hostname,user\nexample.com,root\n").unwrap();
+        assert_eq!(groups[0].hosts[0].name, "example.com");
+        assert_eq!(groups[0].hosts[0].user, "root");
+    }
+
+    #[test]
+    fn rejects_csv_without_an_address_column() {
+        assert!(TermiusCsv.parse("hosts.csv", "name,port\nweb,22\n").is_err());
+    }
+}
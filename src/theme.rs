@@ -0,0 +1,175 @@
+use crate::config::CustomTheme;
+use ratatui::style::Color;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// Colors used to draw the interface, by role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub accent: Color, // Titles, focused borders and focused labels
+    pub text: Color, // Regular text
+    pub subtle: Color, // Labels and unfocused borders
+    pub muted: Color, // Help text and disabled items
+    pub modal_bg: Color, // Background of popups
+    pub input_bg: Color, // Unfocused input fields
+    pub input_fg: Color,
+    pub input_focused_bg: Color, // The field being edited
+    pub input_focused_fg: Color,
+    pub selection_bg: Color, // Selected list items and focused choices
+    pub selection_fg: Color,
+    pub success: Color,
+    pub error: Color,
+    pub info: Color, // Tags, counts and links
+    pub highlight: Color, // Secondary headings
+}
+
+/// The theme used when settings name none, or one that cannot be resolved
+pub const DEFAULT: Theme = Theme {
+    accent: Color::Yellow,
+    text: Color::White,
+    subtle: Color::Gray,
+    muted: Color::DarkGray,
+    modal_bg: Color::DarkGray,
+    input_bg: Color::Gray,
+    input_fg: Color::Black,
+    input_focused_bg: Color::White,
+    input_focused_fg: Color::Black,
+    selection_bg: Color::Blue,
+    selection_fg: Color::White,
+    success: Color::Green,
+    error: Color::Red,
+    info: Color::Cyan,
+    highlight: Color::Magenta,
+};
+
+const DARK: Theme = Theme {
+    accent: Color::LightCyan,
+    text: Color::Gray,
+    subtle: Color::DarkGray,
+    muted: Color::Rgb(98, 98, 98),
+    modal_bg: Color::Rgb(28, 28, 28),
+    input_bg: Color::Rgb(58, 58, 58),
+    input_fg: Color::Gray,
+    input_focused_bg: Color::Rgb(88, 88, 88),
+    input_focused_fg: Color::White,
+    selection_bg: Color::Rgb(0, 95, 135),
+    selection_fg: Color::White,
+    success: Color::LightGreen,
+    error: Color::LightRed,
+    info: Color::LightBlue,
+    highlight: Color::LightMagenta,
+};
+
+const LIGHT: Theme = Theme {
+    accent: Color::Blue,
+    text: Color::Black,
+    subtle: Color::DarkGray,
+    muted: Color::Gray,
+    modal_bg: Color::Rgb(238, 238, 238),
+    input_bg: Color::Rgb(208, 208, 208),
+    input_fg: Color::Black,
+    input_focused_bg: Color::White,
+    input_focused_fg: Color::Black,
+    selection_bg: Color::Rgb(135, 175, 215),
+    selection_fg: Color::Black,
+    success: Color::Rgb(0, 135, 0),
+    error: Color::Rgb(175, 0, 0),
+    info: Color::Rgb(0, 95, 175),
+    highlight: Color::Rgb(135, 0, 135),
+};
+
+const SOLARIZED: Theme = Theme {
+    accent: Color::Rgb(181, 137, 0), // yellow
+    text: Color::Rgb(147, 161, 161), // base1
+    subtle: Color::Rgb(131, 148, 150), // base0
+    muted: Color::Rgb(88, 110, 117), // base01
+    modal_bg: Color::Rgb(7, 54, 66), // base02
+    input_bg: Color::Rgb(88, 110, 117), // base01
+    input_fg: Color::Rgb(253, 246, 227), // base3
+    input_focused_bg: Color::Rgb(238, 232, 213), // base2
+    input_focused_fg: Color::Rgb(0, 43, 54), // base03
+    selection_bg: Color::Rgb(38, 139, 210), // blue
+    selection_fg: Color::Rgb(253, 246, 227), // base3
+    success: Color::Rgb(133, 153, 0), // green
+    error: Color::Rgb(220, 50, 47), // red
+    info: Color::Rgb(42, 161, 152), // cyan
+    highlight: Color::Rgb(211, 54, 130), // magenta
+};
+
+/// Names of the themes that ship with sshtui
+pub const BUILTIN_THEMES: [&str; 4] = ["default", "dark", "light", "solarized"];
+
+/// Colors offered for groups; the config also takes "#rrggbb" and any other
+/// name ratatui knows
+pub const GROUP_COLORS: [&str; 14] = [
+    "red", "green", "yellow", "blue", "magenta", "cyan", "lightred",
+    "lightgreen", "lightyellow", "lightblue", "lightmagenta", "lightcyan", "gray", "white",
+];
+
+/// A group's `color`, such as "lightblue" or "#ff8800"; None when it is
+/// empty or not a color
+pub fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    Color::from_str(value).ok()
+}
+
+static CURRENT: RwLock<Theme> = RwLock::new(DEFAULT);
+
+/// The theme the interface is drawn with
+pub fn current() -> Theme {
+    *CURRENT.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Draw with `theme` from the next frame on
+pub fn set(theme: Theme) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = theme;
+}
+
+fn builtin(name: &str) -> Option<Theme> {
+    match name {
+        "default" => Some(DEFAULT),
+        "dark" => Some(DARK),
+        "light" => Some(LIGHT),
+        "solarized" => Some(SOLARIZED),
+        _ => None,
+    }
+}
+
+/// Look up a theme by name; custom themes start from their base theme
+/// (default when unset) with the listed colors replaced
+pub fn resolve(name: &str, custom_themes: &[CustomTheme]) -> Result<Theme, String> {
+    let Some(custom) = custom_themes.iter().find(|theme| theme.name == name) else {
+        return builtin(name).ok_or_else(|| format!("Unknown theme '{}'", name));
+    };
+
+    let base = custom.base.as_deref().unwrap_or("default");
+    let mut theme = builtin(base)
+        .ok_or_else(|| format!("Theme '{}' is based on unknown theme '{}'", name, base))?;
+    for (role, value) in &custom.colors {
+        let color = Color::from_str(value)
+            .map_err(|_| format!("Theme '{}' has an invalid color for {}: '{}'", name, role, value))?;
+        let slot = match role.as_str() {
+            "accent" => &mut theme.accent,
+            "text" => &mut theme.text,
+            "subtle" => &mut theme.subtle,
+            "muted" => &mut theme.muted,
+            "modal_bg" => &mut theme.modal_bg,
+            "input_bg" => &mut theme.input_bg,
+            "input_fg" => &mut theme.input_fg,
+            "input_focused_bg" => &mut theme.input_focused_bg,
+            "input_focused_fg" => &mut theme.input_focused_fg,
+            "selection_bg" => &mut theme.selection_bg,
+            "selection_fg" => &mut theme.selection_fg,
+            "success" => &mut theme.success,
+            "error" => &mut theme.error,
+            "info" => &mut theme.info,
+            "highlight" => &mut theme.highlight,
+            _ => return Err(format!("Theme '{}' sets unknown color '{}'", name, role)),
+        };
+        *slot = color;
+    }
+    Ok(theme)
+}
@@ -0,0 +1,53 @@
+use crate::{AppState, MessageType, ModalState, ThemeForm};
+use crate::theme::{self, BUILTIN_THEMES};
+
+impl AppState {
+    /// Draw with the theme chosen in settings, falling back to the default
+    pub fn apply_theme(&mut self) {
+        let name = self.config.settings.theme.clone();
+        if let Err(e) = self.preview_theme(&name) {
+            theme::set(theme::DEFAULT);
+            self.set_message(e, MessageType::Error);
+        }
+    }
+
+    /// Switch the interface to a theme without saving the choice
+    pub fn preview_theme(&self, name: &str) -> Result<(), String> {
+        theme::set(theme::resolve(name, &self.config.settings.custom_themes)?);
+        Ok(())
+    }
+
+    pub fn open_themes(&mut self) {
+        let mut themes: Vec<String> = BUILTIN_THEMES.iter().map(|name| name.to_string()).collect();
+        for custom in &self.config.settings.custom_themes {
+            if !themes.contains(&custom.name) {
+                themes.push(custom.name.clone());
+            }
+        }
+        let original = self.config.settings.theme.clone();
+        let cursor = themes.iter().position(|name| *name == original).unwrap_or(0);
+        self.modal_state = ModalState::Themes(ThemeForm { themes, cursor, original });
+    }
+
+    /// Keep the previewed theme
+    pub fn choose_theme(&mut self, form: ThemeForm) {
+        let Some(name) = form.themes.get(form.cursor) else {
+            return;
+        };
+        if let Err(e) = self.preview_theme(name) {
+            self.apply_theme();
+            self.set_message(e, MessageType::Error);
+            return;
+        }
+        self.config.settings.theme = name.clone();
+        let _ = self.config.save();
+        self.set_message(format!("Theme set to '{}'", name), MessageType::Success);
+    }
+
+    /// Go back to the theme that was in use before the picker opened
+    pub fn cancel_theme_preview(&mut self, form: &ThemeForm) {
+        if self.preview_theme(&form.original).is_err() {
+            theme::set(theme::DEFAULT);
+        }
+    }
+}
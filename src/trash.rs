@@ -0,0 +1,126 @@
+use crate::{AppState, MessageType, ModalState, TrashForm};
+use crate::config::{Host, Trashed, TrashedItem};
+use crate::secrets;
+use chrono::{Duration, Local};
+
+impl AppState {
+    /// Keep a deleted host or group in the trash instead of losing it
+    pub fn move_to_trash(&mut self, item: Trashed) {
+        self.config.add_to_trash(item);
+    }
+
+    /// Delete for good whatever has been in the trash longer than the
+    /// settings keep it
+    pub fn empty_old_trash(&mut self) {
+        let days = self.config.settings.trash_days;
+        if days == 0 {
+            return;
+        }
+        let cutoff = Local::now() - Duration::days(days.into());
+        let expired: Vec<&TrashedItem> = self.config.trash.iter().filter(|entry| entry.deleted_at <= cutoff).collect();
+        if expired.is_empty() {
+            return;
+        }
+        let accounts = self.trashed_accounts(&expired);
+        let count = expired.len();
+        let before = self.snapshot_with_passwords(&accounts);
+        self.config.trash.retain(|entry| entry.deleted_at > cutoff);
+        self.forget_passwords(&accounts);
+        self.record_change(before, format!("Emptied {} old entries from the trash", count));
+        let _ = self.config.save();
+    }
+
+    /// Accounts of the stored passwords of the hosts in trashed items, which
+    /// are kept while they can still be restored
+    fn trashed_accounts(&self, entries: &[&TrashedItem]) -> Vec<String> {
+        // A host still in the config may log in to the same account
+        let in_use: Vec<String> = self.config.get_hosts_for_group(0).iter().map(secrets::host_account).collect();
+        entries
+            .iter()
+            .flat_map(|entry| -> Vec<&Host> {
+                match &entry.item {
+                    Trashed::Host { host, .. } => vec![host],
+                    Trashed::Group { group } => group.hosts.iter().collect(),
+                }
+            })
+            .map(secrets::host_account)
+            .filter(|account| !in_use.contains(account))
+            .collect()
+    }
+
+    fn forget_passwords(&self, accounts: &[String]) {
+        let backend = self.config.settings.secrets_backend;
+        for account in accounts {
+            let _ = secrets::delete(backend, account);
+        }
+    }
+
+    pub fn open_trash(&mut self) {
+        self.empty_old_trash();
+        if self.config.trash.is_empty() {
+            self.set_message("The trash is empty".to_string(), MessageType::Info);
+            return;
+        }
+        self.modal_state = ModalState::Trash(TrashForm { cursor: 0 });
+    }
+
+    /// Keep the trash open on the entry next to the one just taken out
+    fn reopen_trash(&mut self, cursor: usize) {
+        if !self.config.trash.is_empty() {
+            let cursor = cursor.min(self.config.trash.len() - 1);
+            self.modal_state = ModalState::Trash(TrashForm { cursor });
+        }
+    }
+
+    /// Put the entry under the cursor back where it was deleted from
+    pub fn restore_from_trash(&mut self, form: TrashForm) {
+        let Some(entry) = self.config.trash.get(form.cursor).cloned() else {
+            return;
+        };
+        let before = self.snapshot();
+        let restored = match entry.item.clone() {
+            Trashed::Host { group, host } => self.config.add_host_to_group(&group, *host).map_err(|e| e.to_string()),
+            Trashed::Group { group } => {
+                match self.config.groups.iter().position(|existing| existing.name == group.name) {
+                    // Recreated empty as the parent of a nested group left behind
+                    Some(index) if index > 0 && self.config.groups[index].hosts.is_empty() => {
+                        self.config.groups[index] = *group;
+                        Ok(())
+                    },
+                    Some(_) => Err(format!("A group named '{}' exists again; rename it first", group.name)),
+                    None => {
+                        self.config.add_group(*group);
+                        self.config.ensure_parent_groups();
+                        Ok(())
+                    },
+                }
+            },
+        };
+        if let Err(e) = restored {
+            self.set_message(format!("Could not restore {}: {}", entry.describe(), e), MessageType::Error);
+            self.reopen_trash(form.cursor);
+            return;
+        }
+
+        self.config.trash.remove(form.cursor);
+        self.record_change(before, format!("Restored {}", entry.describe()));
+        let _ = self.config.save();
+        self.set_message(format!("Restored {}", entry.describe()), MessageType::Success);
+        self.reopen_trash(form.cursor);
+    }
+
+    /// Delete the entry under the cursor for good, with its hosts' passwords
+    pub fn delete_from_trash(&mut self, form: TrashForm) {
+        if form.cursor >= self.config.trash.len() {
+            return;
+        }
+        let accounts = self.trashed_accounts(&[&self.config.trash[form.cursor]]);
+        let before = self.snapshot_with_passwords(&accounts);
+        let entry = self.config.trash.remove(form.cursor);
+        self.forget_passwords(&accounts);
+        self.record_change(before, format!("Deleted {} for good", entry.describe()));
+        let _ = self.config.save();
+        self.set_message(format!("Deleted {} for good", entry.describe()), MessageType::Info);
+        self.reopen_trash(form.cursor);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::config::{Host, SshBackend};
+use crate::hostkeys::{self, Route};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local};
+use log::warn;
+use russh::client::{self, AuthResult};
+use russh::keys::{HashAlg, PublicKey};
+use russh::Disconnect;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+/// How long each probe of the server may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Identification sent to the server while reading its greeting
+const PROBE_ID: &str = "SSH-2.0-sshtui_probe";
+
+/// User name the auth methods are asked for, so the real one is not sent
+/// before the key is trusted. OpenSSH answers the same for unknown users.
+const PROBE_USER: &str = "sshtui-probe";
+
+/// Largest KEXINIT packet read; real ones are a few KB
+const MAX_PACKET: usize = 64 * 1024;
+
+const SSH_MSG_KEXINIT: u8 = 20;
+
+/// Cipher preference of the OpenSSH client without a `Ciphers` setting
+const OPENSSH_CIPHERS: [&str; 6] = [
+    "chacha20-poly1305@openssh.com",
+    "aes128-ctr",
+    "aes192-ctr",
+    "aes256-ctr",
+    "aes128-gcm@openssh.com",
+    "aes256-gcm@openssh.com",
+];
+
+/// What a server showed of itself before its key was trusted, kept with the
+/// pin so a later key change can be compared against it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustReport {
+    pub collected: DateTime<Local>,
+    /// Identification line, such as "SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13"
+    pub banner: Option<String>,
+    pub sha256: String,
+    pub sha512: String,
+    /// Colon-separated hex, as older servers and `ssh-keygen -E md5` print it
+    pub md5: String,
+    /// Ways the server offers to log in, such as "publickey" and "password"
+    #[serde(default)]
+    pub auth_methods: Vec<String>,
+    /// Cipher the session's backend and the server settle on
+    pub cipher: Option<String>,
+}
+
+/// Gather the report for `key`, which the host presented. Probes that fail
+/// leave their part of the report empty.
+pub async fn collect(host: &Host, key: &PublicKey, backend: SshBackend) -> TrustReport {
+    let route = hostkeys::route(host).await;
+    let (banner, ciphers) = match tokio::time::timeout(PROBE_TIMEOUT, greeting(&route)).await {
+        Ok(Ok((banner, ciphers))) => (Some(banner), ciphers),
+        Ok(Err(e)) => {
+            warn!("Could not read the greeting of {}: {}", host.host, e);
+            (None, Vec::new())
+        },
+        Err(_) => (None, Vec::new()),
+    };
+    let auth_methods = match tokio::time::timeout(PROBE_TIMEOUT, auth_methods(&route, key)).await {
+        Ok(Ok(methods)) => methods,
+        Ok(Err(e)) => {
+            warn!("Could not list the auth methods of {}: {}", host.host, e);
+            Vec::new()
+        },
+        Err(_) => Vec::new(),
+    };
+
+    TrustReport {
+        collected: Local::now(),
+        banner,
+        sha256: key.fingerprint(HashAlg::Sha256).to_string(),
+        sha512: key.fingerprint(HashAlg::Sha512).to_string(),
+        md5: md5_fingerprint(key),
+        auth_methods,
+        cipher: negotiated_cipher(backend, &ciphers),
+    }
+}
+
+fn md5_fingerprint(key: &PublicKey) -> String {
+    let Ok(bytes) = key.to_bytes() else {
+        return String::new();
+    };
+    let digest = md5::compute(bytes);
+    let hex: Vec<String> = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("MD5:{}", hex.join(":"))
+}
+
+/// The first cipher of the backend's preference the server offers, as the
+/// handshake picks it
+fn negotiated_cipher(backend: SshBackend, offered: &[String]) -> Option<String> {
+    let preferred: Vec<String> = match backend {
+        SshBackend::System => OPENSSH_CIPHERS.iter().map(|cipher| cipher.to_string()).collect(),
+        SshBackend::Embedded => russh::Preferred::default().cipher.iter()
+            .map(|cipher| cipher.as_ref().to_string())
+            .collect(),
+    };
+    preferred.into_iter().find(|cipher| offered.contains(cipher))
+}
+
+/// The server's identification line and the ciphers it offers, from the
+/// plaintext start of a handshake that goes no further
+async fn greeting(route: &Route) -> Result<(String, Vec<String>)> {
+    let mut stream = route.connect().await?;
+    stream.write_all(format!("{}\r\n", PROBE_ID).as_bytes()).await?;
+    let mut reader = BufReader::new(stream);
+
+    // Servers may send other lines before the identification
+    let banner = loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("connection closed");
+        }
+        if line.starts_with("SSH-") {
+            break line.trim_end().to_string();
+        }
+    };
+
+    let length = reader.read_u32().await? as usize;
+    if length > MAX_PACKET {
+        bail!("handshake packet too large");
+    }
+    let mut packet = vec![0; length];
+    reader.read_exact(&mut packet).await?;
+    // Padding length, message number and a 16 byte cookie come first
+    if packet.len() < 18 || packet[1] != SSH_MSG_KEXINIT {
+        return Ok((banner, Vec::new()));
+    }
+    let mut lists = &packet[18..];
+    // Key exchange and host key algorithms, then client-to-server ciphers
+    name_list(&mut lists)?;
+    name_list(&mut lists)?;
+    Ok((banner, name_list(&mut lists)?))
+}
+
+/// Read one comma separated name-list off the front of `data`
+fn name_list(data: &mut &[u8]) -> Result<Vec<String>> {
+    let length = data.get(..4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        .ok_or_else(|| anyhow!("truncated handshake packet"))?;
+    let list = data.get(4..4 + length).ok_or_else(|| anyhow!("truncated handshake packet"))?;
+    let names = String::from_utf8_lossy(list).split(',')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    *data = &data[4 + length..];
+    Ok(names)
+}
+
+/// Accepts the presented key only, for a probe made before it is trusted
+struct KeyMatcher(PublicKey);
+
+impl client::Handler for KeyMatcher {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, server_public_key: &PublicKey) -> Result<bool, Self::Error> {
+        Ok(*server_public_key == self.0)
+    }
+}
+
+/// Methods the server lists after a "none" login attempt
+async fn auth_methods(route: &Route, key: &PublicKey) -> Result<Vec<String>> {
+    let config = Arc::new(client::Config::default());
+    let mut session = client::connect_stream(config, route.connect().await?, KeyMatcher(key.clone())).await?;
+    let methods = match session.authenticate_none(PROBE_USER).await? {
+        AuthResult::Success => vec!["none".to_string()],
+        AuthResult::Failure { remaining_methods, .. } => remaining_methods.iter().map(String::from).collect(),
+    };
+    let _ = session.disconnect(Disconnect::ByApplication, "", "en").await;
+    Ok(methods)
+}
@@ -1,11 +1,21 @@
 use crate::{AppState, FocusArea, FocusSubArea, MessageType};
+use crate::config::{Config, Group, Host, DEFAULT_PROFILE};
 use crate::dashboard;
+use crate::group_tree::{GroupLine, VirtualGroup};
+use crate::host_headers::HostRow;
+use crate::theme;
+use crate::terminal_panel::PanelColors;
+use crate::probe::ProbeStatus;
+use crate::sweep::SweepStatus;
+use std::time::Duration;
+use uuid::Uuid;
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, Paragraph, Clear},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
 };
 
 pub fn render(frame: &mut Frame, app: &mut AppState) {
+    let theme = theme::current();
     let size = frame.size();
     
     // Update app layout based on current terminal size
@@ -17,17 +27,33 @@ pub fn render(frame: &mut Frame, app: &mut AppState) {
         .constraints([
             Constraint::Length(1),     // Title
             Constraint::Min(0),        // Main content
-            Constraint::Length(1),     // Message
+            Constraint::Length(1),     // Status bar
             Constraint::Length(1),     // Help
         ])
         .split(size);
     
-    // Render title
-    let title = Paragraph::new("🦀 SSH TUI Manager (Rust)")
-        .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+    // Render title, naming the profile unless it is the default one
+    let profile = Config::profile();
+    let title = if profile == DEFAULT_PROFILE {
+        "🦀 SSH TUI Manager (Rust)".to_string()
+    } else {
+        format!("🦀 SSH TUI Manager (Rust) - {}", profile)
+    };
+    let title = Paragraph::new(title)
+        .style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD))
         .alignment(Alignment::Center);
     frame.render_widget(title, main_layout[0]);
     
+    // Splash while the inventory is read in the background
+    if let Some(elapsed) = app.loading_for() {
+        render_loading(frame, main_layout[1], elapsed);
+        frame.render_widget(
+            Paragraph::new("Ctrl+Q=quit").style(Style::default().fg(theme.muted)).alignment(Alignment::Center),
+            main_layout[3]
+        );
+        return;
+    }
+    
     // Main content layout: Sidebar + Terminal panel
     let content_layout = Layout::default()
         .direction(Direction::Horizontal)
@@ -41,31 +67,64 @@ pub fn render(frame: &mut Frame, app: &mut AppState) {
     render_sidebar(frame, app, content_layout[0]);
     
     // Render terminal panel
-    if app.ssh_client.is_connected() || app.ssh_client.is_connecting() {
-        app.terminal_panel.render(frame);
+    let panel_colors = PanelColors { accent: theme::current().accent, subtle: theme::current().subtle };
+    if let Some(playback) = &app.playback {
+        playback.panel.render(frame, panel_colors);
+    } else if app.ssh_client.is_connected() || app.ssh_client.is_connecting() || app.reconnect.is_some() {
+        app.terminal_panel.render(frame, panel_colors);
+        if let Some(index) = app.split_pane() {
+            app.background_sessions[index].panel.render(frame, panel_colors);
+        }
     } else {
         // Render dashboard when not connected
         render_dashboard_panel(frame, app, content_layout[1]);
     }
     
-    // Render message
-    render_message(frame, app, main_layout[2]);
+    // Render status bar
+    render_status_bar(frame, app, main_layout[2]);
     
     // Render help
     render_help(frame, app, main_layout[3]);
+
+    // Say how to undo or redo the last change
+    if !app.ssh_client.is_connected() {
+        render_undo_toast(frame, app, main_layout[1]);
+    }
     
     // Render modal if active
     crate::modal::render_modal(frame, app);
+
+    // Hide everything the sessions show while they are locked
+    render_session_lock(frame, app, content_layout[1]);
+}
+
+fn render_loading(frame: &mut Frame, area: Rect, elapsed: Duration) {
+    let theme = theme::current();
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    let frame_index = (elapsed.as_millis() / 100) as usize % SPINNER.len();
+    let text = format!("{} Loading keys, groups and hosts...", SPINNER[frame_index]);
+    let text_area = Rect { y: area.y + area.height / 2, height: area.height.min(1), ..area };
+    frame.render_widget(
+        Paragraph::new(text)
+            .style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD))
+            .alignment(Alignment::Center),
+        text_area
+    );
 }
 
 fn render_sidebar(frame: &mut Frame, app: &AppState, area: Rect) {
-    // Split sidebar into three panels
+    // Split sidebar into three panels, plus background sessions and tunnels
+    // while there are any
+    let sessions_height = if app.background_sessions.is_empty() { 0 } else { app.background_sessions.len() as u16 + 2 };
+    let tunnels_height = if app.tunnels.is_empty() { 0 } else { app.tunnels.len() as u16 + 2 };
     let sidebar_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(8),  // SSH Keys panel
             Constraint::Length(8),  // Groups panel
             Constraint::Min(0),     // Hosts panel
+            Constraint::Length(sessions_height), // Sessions panel
+            Constraint::Length(tunnels_height), // Tunnels panel
         ])
         .split(area);
     
@@ -77,18 +136,70 @@ fn render_sidebar(frame: &mut Frame, app: &AppState, area: Rect) {
     
     // Render Hosts panel
     render_hosts_panel(frame, app, sidebar_layout[2]);
+    
+    // Render Sessions panel
+    if !app.background_sessions.is_empty() {
+        render_sessions_panel(frame, app, sidebar_layout[3]);
+    }
+    
+    // Render Tunnels panel
+    if !app.tunnels.is_empty() {
+        render_tunnels_panel(frame, app, sidebar_layout[4]);
+    }
+}
+
+fn render_sessions_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
+    let block = Block::default()
+        .title("Sessions (Ctrl+G)")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.subtle));
+    
+    let items: Vec<ListItem> = app.background_sessions.iter().map(|session| {
+        let markers = session.markers();
+        if markers.is_empty() {
+            ListItem::new(format!("  {}", session.host_name()))
+        } else {
+            ListItem::new(format!("{} {}", markers, session.host_name()))
+                .style(Style::default().add_modifier(Modifier::BOLD))
+        }
+    }).collect();
+    
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn render_tunnels_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
+    let block = Block::default()
+        .title("Tunnels")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.subtle));
+    
+    let items: Vec<ListItem> = app.tunnels.iter().map(|tunnel| {
+        match (&tunnel.error, tunnel.requested_port) {
+            (None, Some(requested)) => ListItem::new(format!("● {} ({} was taken)", tunnel.forward, requested))
+                .style(Style::default().fg(theme.success)),
+            (None, None) => ListItem::new(format!("● {}", tunnel.forward))
+                .style(Style::default().fg(theme.success)),
+            (Some(error), _) => ListItem::new(format!("✗ {} ({})", tunnel.forward, error))
+                .style(Style::default().fg(theme.error)),
+        }
+    }).collect();
+    
+    frame.render_widget(List::new(items).block(block), area);
 }
 
 fn render_keys_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
     let is_focused = app.focus_area == FocusArea::Keys;
     
     let block = Block::default()
         .title("SSH Keys")
         .borders(Borders::ALL)
         .border_style(if is_focused {
-            Style::default().fg(Color::Yellow)
+            Style::default().fg(theme.accent)
         } else {
-            Style::default().fg(Color::Gray)
+            Style::default().fg(theme.subtle)
         });
     
     let inner = block.inner(area);
@@ -96,19 +207,25 @@ fn render_keys_panel(frame: &mut Frame, app: &AppState, area: Rect) {
     
     if app.config.keys.is_empty() {
         let empty_msg = Paragraph::new("No SSH keys yet.\nPress Ctrl+N to add one.")
-            .style(Style::default().fg(Color::White))
+            .style(Style::default().fg(theme.text))
             .alignment(Alignment::Center);
         frame.render_widget(empty_msg, inner);
     } else {
         let items: Vec<ListItem> = app.config.keys.iter().enumerate().map(|(i, key)| {
-            let content = if key.is_default {
+            let name = if key.is_default {
                 format!("⭐ {}", key.name)
             } else {
                 key.name.clone()
             };
+            // Type and fingerprint of the file, or a warning when it is unusable
+            let details = match app.key_details(&key.path) {
+                Some(details) => Span::styled(format!("  {}", details.summary()), Style::default().fg(theme.muted)),
+                None => Span::styled("  ⚠ not a usable key", Style::default().fg(theme.error)),
+            };
+            let content = Line::from(vec![Span::raw(name), details]);
             
             let style = if i == app.selected_key && is_focused && app.focus_sub_area == FocusSubArea::Items {
-                Style::default().bg(Color::Blue).fg(Color::White)
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
             } else {
                 Style::default()
             };
@@ -134,32 +251,57 @@ fn render_keys_panel(frame: &mut Frame, app: &AppState, area: Rect) {
 }
 
 fn render_groups_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
     let is_focused = app.focus_area == FocusArea::Groups;
     
     let block = Block::default()
         .title("Groups")
         .borders(Borders::ALL)
         .border_style(if is_focused {
-            Style::default().fg(Color::Yellow)
+            Style::default().fg(theme.accent)
         } else {
-            Style::default().fg(Color::Gray)
+            Style::default().fg(theme.subtle)
         });
     
     let inner = block.inner(area);
     frame.render_widget(block, area);
     
-    let items: Vec<ListItem> = app.config.groups.iter().enumerate().map(|(i, group)| {
-        let host_count = if i == 0 && group.name == "All" {
-            // Count all hosts from real groups
-            app.config.groups.iter().skip(1).map(|g| g.hosts.len()).sum()
-        } else {
-            group.hosts.len()
+    let rows = app.group_rows();
+    let current_row = app.current_group_row();
+    let selected_row = rows.iter().position(|row| row.group == current_row);
+    let items: Vec<ListItem> = rows.into_iter().map(|row| {
+        let selected = row.group == current_row && is_focused && app.focus_sub_area == FocusSubArea::Items;
+        let i = match row.group {
+            GroupLine::Group(i) => i,
+            GroupLine::Virtual(view) => {
+                let icon = match view {
+                    VirtualGroup::Favorites => "★",
+                    VirtualGroup::Recent => "🕘",
+                };
+                let content = format!("{} {} ({})", icon, view.name(), app.virtual_group_hosts(view).len());
+                let style = if selected {
+                    Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+                } else {
+                    Style::default().fg(theme.highlight)
+                };
+                return ListItem::new(content).style(style);
+            },
         };
+        let group = &app.config.groups[i];
+        // Nested groups' hosts count towards the groups they are in
+        let host_count = app.config.get_hosts_for_group(i).len();
         
-        let content = format!("{} ({})", group.name, host_count);
+        let marker = match (row.folder, row.collapsed) {
+            (false, _) => "",
+            (true, false) => "▾ ",
+            (true, true) => "▸ ",
+        };
+        let content = format!("{}{}{} ({})", "  ".repeat(row.depth), marker, group.short_name(), host_count);
         
-        let style = if i == app.selected_group && is_focused && app.focus_sub_area == FocusSubArea::Items {
-            Style::default().bg(Color::Blue).fg(Color::White)
+        let style = if selected {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else if i > 0 {
+            group_style(group)
         } else {
             Style::default()
         };
@@ -177,50 +319,142 @@ fn render_groups_panel(frame: &mut Frame, app: &AppState, area: Rect) {
         height: inner.height.saturating_sub(1),
     };
     
-    frame.render_widget(list, list_area);
+    // Scrolled to keep the selected group in view
+    let mut state = ListState::default().with_selected(selected_row);
+    frame.render_stateful_widget(list, list_area, &mut state);
     
     // Render action buttons
     render_action_buttons(frame, app, FocusArea::Groups, inner);
 }
 
 fn render_hosts_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
     let is_focused = app.focus_area == FocusArea::Hosts;
     
+    // A single group's hosts are framed in its color
+    let color = app.config.groups.get(app.selected_group)
+        .filter(|_| app.selected_group > 0)
+        .and_then(|group| theme::parse_color(&group.color));
     let block = Block::default()
-        .title("Hosts")
+        .title(Span::styled("Hosts", color.map_or(Style::default(), |color| Style::default().fg(color))))
         .borders(Borders::ALL)
-        .border_style(if is_focused {
-            Style::default().fg(Color::Yellow)
-        } else {
-            Style::default().fg(Color::Gray)
+        .border_style(match color {
+            Some(color) if is_focused => Style::default().fg(color),
+            _ if is_focused => Style::default().fg(theme.accent),
+            _ => Style::default().fg(theme.subtle),
         });
     
-    let inner = block.inner(area);
+    let mut inner = block.inner(area);
     frame.render_widget(block, area);
     
-    let hosts = app.config.get_hosts_for_group(app.selected_group);
+    // Tag filter bar
+    if !app.tag_filter.is_empty() {
+        let mut spans = vec![Span::styled("Tags: ", Style::default().fg(theme.subtle))];
+        for tag in &app.tag_filter {
+            spans.push(Span::styled(format!("#{}", tag), Style::default().fg(theme.info).add_modifier(Modifier::BOLD)));
+            spans.push(Span::raw(" "));
+        }
+        let filter_area = Rect { height: 1, ..inner };
+        frame.render_widget(Paragraph::new(Line::from(spans)), filter_area);
+        inner.y += 1;
+        inner.height = inner.height.saturating_sub(1);
+    }
     
-    if hosts.is_empty() {
-        let empty_msg = if app.selected_group == 0 && !app.config.groups.is_empty() && app.config.groups[0].name == "All" {
+    // Filter box, shown while typing and while a filter is set
+    if app.host_filter_editing || !app.host_filter.is_empty() {
+        let cursor = if app.host_filter_editing { "▏" } else { "" };
+        let style = if app.host_filter_editing {
+            Style::default().bg(theme.input_focused_bg).fg(theme.input_focused_fg)
+        } else {
+            Style::default().fg(theme.info).add_modifier(Modifier::BOLD)
+        };
+        let line = Line::from(vec![
+            Span::styled("Filter: ", Style::default().fg(theme.subtle)),
+            Span::styled(format!("{}{}", app.host_filter, cursor), style),
+        ]);
+        frame.render_widget(Paragraph::new(line), Rect { height: 1, ..inner });
+        inner.y += 1;
+        inner.height = inner.height.saturating_sub(1);
+    }
+    
+    let hosts = app.visible_hosts();
+    // Folded groups still show their headers
+    let nothing_to_list = if app.grouped_host_view() { app.host_rows().is_empty() } else { hosts.is_empty() };
+    
+    if nothing_to_list {
+        let empty_msg = if !app.host_filter.is_empty() {
+            Paragraph::new("No hosts match the filter.\nPress f and Esc to clear it.")
+        } else if !app.tag_filter.is_empty() {
+            Paragraph::new("No hosts match the tag filter.\nPress Ctrl+T to change it.")
+        } else if app.virtual_group == Some(VirtualGroup::Favorites) {
+            Paragraph::new("No favorite hosts.\nPress * on a host to star it.")
+        } else if app.virtual_group == Some(VirtualGroup::Recent) {
+            Paragraph::new("No recent hosts.\nHosts you connect to are listed here.")
+        } else if app.selected_group == 0 && !app.config.groups.is_empty() && app.config.groups[0].name == "All" {
             Paragraph::new("No hosts in any group.\nAdd hosts to specific groups\nto see them here.")
         } else {
             Paragraph::new("No hosts in this group.\nPress [+] to add one.")
-        }.style(Style::default().fg(Color::DarkGray))
+        }.style(Style::default().fg(theme.muted))
         .alignment(Alignment::Center);
         
         frame.render_widget(empty_msg, inner);
     } else {
-        let items: Vec<ListItem> = hosts.iter().enumerate().map(|(i, host)| {
-            let content = format!("{}\n  {}@{}:{}", host.name, host.user, host.host, host.port);
+        let selected_header = app.selected_host_header();
+        let host_item = |i: usize, host: &Host| {
+            let star = if host.favorite { "★ " } else { "" };
+            let name_line = if host.tags.is_empty() {
+                format!("{}{}", star, host.name)
+            } else {
+                let tags: Vec<String> = host.tags.iter().map(|tag| format!("#{}", tag)).collect();
+                format!("{}{} {}", star, host.name, tags.join(" "))
+            };
+            // Flag hosts whose live or background session has unseen output or a bell
+            let markers = app.host_markers(&host.name).unwrap_or_default();
+            let name_line = if markers.is_empty() {
+                name_line
+            } else {
+                format!("{} {}", markers, name_line)
+            };
+            let effective = app.config.effective_host(host);
+            let marker = app.config.group_of(host).map_or(Style::default(), group_style);
+            let mut first_line = vec![Span::styled("▍", marker), Span::raw(name_line)];
+            first_line.extend(probe_badges(app, host.id));
+            first_line.extend(sweep_badge(app, host.id));
+            let content = Text::from(vec![
+                Line::from(first_line),
+                Line::from(format!("   {}", effective.address())),
+            ]);
             
-            let style = if i == app.selected_host && is_focused && app.focus_sub_area == FocusSubArea::Items {
-                Style::default().bg(Color::Blue).fg(Color::White)
+            let style = if i == app.selected_host && selected_header.is_none() && is_focused && app.focus_sub_area == FocusSubArea::Items {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else if !markers.is_empty() {
+                Style::default().add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
             };
             
             ListItem::new(content).style(style)
-        }).collect();
+        };
+        
+        let items: Vec<ListItem> = if app.grouped_host_view() {
+            // "All" lists each group under a header that folds it
+            app.host_rows().into_iter().map(|row| match row {
+                HostRow::Header { group, hosts: count, collapsed } => {
+                    let arrow = if collapsed { "▶" } else { "▼" };
+                    let text = format!("{} {} ({})", arrow, app.config.groups[group].name, count);
+                    let style = if selected_header == Some(group) && is_focused && app.focus_sub_area == FocusSubArea::Items {
+                        Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+                    } else {
+                        let color = theme::parse_color(&app.config.groups[group].color).unwrap_or(theme.accent);
+                        Style::default().fg(color).add_modifier(Modifier::BOLD)
+                    };
+                    ListItem::new(text).style(style)
+                },
+                HostRow::Host(i) => host_item(i, &hosts[i]),
+            }).collect()
+        } else {
+            hosts.iter().enumerate().map(|(i, host)| host_item(i, host)).collect()
+        };
         
         let list = List::new(items);
         
@@ -239,7 +473,44 @@ fn render_hosts_panel(frame: &mut Frame, app: &AppState, area: Rect) {
     }
 }
 
+/// Text in the group's color, when it names one
+fn group_style(group: &Group) -> Style {
+    theme::parse_color(&group.color).map_or(Style::default(), |color| Style::default().fg(color))
+}
+
+/// One colored badge per finished probe of the host
+fn probe_badges(app: &AppState, host_id: Uuid) -> Vec<Span<'static>> {
+    let Some(reports) = app.probe_reports.get(&host_id) else {
+        return Vec::new();
+    };
+    reports.iter()
+        .flatten()
+        .map(|report| Span::styled(format!(" ●{}", report.label), Style::default().fg(probe_color(report.result.status))))
+        .collect()
+}
+
+/// The host's result in the last reachability check (Shift+C)
+fn sweep_badge(app: &AppState, host_id: Uuid) -> Option<Span<'static>> {
+    let theme = theme::current();
+    let (text, color) = match app.sweep_status(host_id)? {
+        SweepStatus::Checking => (" …".to_string(), theme.muted),
+        SweepStatus::Reachable(rtt) => (format!(" ✓{} ms", rtt.as_millis()), theme.success),
+        SweepStatus::Unreachable => (" ✗unreachable".to_string(), theme.error),
+    };
+    Some(Span::styled(text, Style::default().fg(color)))
+}
+
+pub fn probe_color(status: ProbeStatus) -> Color {
+    let theme = theme::current();
+    match status {
+        ProbeStatus::Up => theme.success,
+        ProbeStatus::Degraded => theme.accent,
+        ProbeStatus::Down => theme.error,
+    }
+}
+
 fn render_action_buttons(frame: &mut Frame, app: &AppState, panel_focus: FocusArea, area: Rect) {
+    let theme = theme::current();
     let is_panel_focused = app.focus_area == panel_focus;
     
     if !is_panel_focused {
@@ -256,21 +527,21 @@ fn render_action_buttons(frame: &mut Frame, app: &AppState, panel_focus: FocusAr
     
     // Create button texts with focus highlighting
     let add_style = if app.focus_sub_area == FocusSubArea::AddButton {
-        Style::default().bg(Color::Green).fg(Color::Black).add_modifier(Modifier::BOLD)
+        Style::default().bg(theme.success).fg(Color::Black).add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::Green)
+        Style::default().fg(theme.success)
     };
     
     let edit_style = if app.focus_sub_area == FocusSubArea::EditButton {
-        Style::default().bg(Color::Blue).fg(Color::White).add_modifier(Modifier::BOLD)
+        Style::default().bg(theme.selection_bg).fg(theme.selection_fg).add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::Blue)
+        Style::default().fg(theme.selection_bg)
     };
     
     let delete_style = if app.focus_sub_area == FocusSubArea::DeleteButton {
-        Style::default().bg(Color::Red).fg(Color::White).add_modifier(Modifier::BOLD)
+        Style::default().bg(theme.error).fg(theme.selection_fg).add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::Red)
+        Style::default().fg(theme.error)
     };
     
     // Check if buttons should be enabled
@@ -278,13 +549,13 @@ fn render_action_buttons(frame: &mut Frame, app: &AppState, panel_focus: FocusAr
         FocusArea::Keys => (!app.config.keys.is_empty(), !app.config.keys.is_empty()),
         FocusArea::Groups => (app.config.groups.len() > 1, app.config.groups.len() > 1),
         FocusArea::Hosts => {
-            let hosts = app.config.get_hosts_for_group(app.selected_group);
+            let hosts = app.visible_hosts();
             (!hosts.is_empty(), !hosts.is_empty())
         },
     };
     
-    let edit_style = if has_edit_items { edit_style } else { Style::default().fg(Color::DarkGray) };
-    let delete_style = if has_delete_items { delete_style } else { Style::default().fg(Color::DarkGray) };
+    let edit_style = if has_edit_items { edit_style } else { Style::default().fg(theme.muted) };
+    let delete_style = if has_delete_items { delete_style } else { Style::default().fg(theme.muted) };
     
     let buttons = Paragraph::new(
         Line::from(vec![
@@ -300,51 +571,168 @@ fn render_action_buttons(frame: &mut Frame, app: &AppState, panel_focus: FocusAr
 }
 
 fn render_dashboard_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
+    // A host picked in the Hosts panel is described instead of the dashboard
+    let selected_host = app.selected_visible_host().filter(|_| app.focus_area == FocusArea::Hosts);
     let block = Block::default()
-        .title("🖥️ Dashboard")
+        .title(if selected_host.is_some() { "🔎 Host Details" } else { "🖥️ Dashboard" })
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Gray));
+        .border_style(Style::default().fg(theme.subtle));
     
     let inner = block.inner(area);
     frame.render_widget(block, area);
     
     // Render the colorful dashboard
-    let dashboard_content = dashboard::render_dashboard(app, inner.width, inner.height);
+    let dashboard_content = match &selected_host {
+        Some(host) => dashboard::render_host_card(app, host),
+        None => dashboard::render_dashboard(app, inner.width, inner.height),
+    };
+    // Untrimmed, so heatmap columns and command output keep their indentation
     let dashboard_widget = Paragraph::new(dashboard_content)
-        .wrap(ratatui::widgets::Wrap { trim: true });
+        .wrap(ratatui::widgets::Wrap { trim: false });
     
     frame.render_widget(dashboard_widget, inner);
 }
 
-fn render_message(frame: &mut Frame, app: &AppState, area: Rect) {
+/// Details of the live connection, with the latest message beside them;
+/// only the message while not connected
+fn render_status_bar(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
+    let Some(details) = app.connection_details() else {
+        // Out of sessions, the bar tells when the shared inventory was synced
+        let Some(sync_status) = app.inventory_sync_status() else {
+            render_message(frame, app, area, Alignment::Center);
+            return;
+        };
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(sync_status.chars().count() as u16 + 1), Constraint::Min(0)])
+            .split(area);
+        frame.render_widget(Paragraph::new(sync_status).style(Style::default().fg(theme.muted)), halves[0]);
+        render_message(frame, app, halves[1], Alignment::Right);
+        return;
+    };
+
+    let mut spans = vec![Span::styled("● ", Style::default().fg(theme.success))];
+    for (index, detail) in details.into_iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::styled(" │ ", Style::default().fg(theme.muted)));
+        }
+        spans.push(Span::styled(detail, Style::default().fg(theme.text)));
+    }
+    let details = Line::from(spans);
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(details.width() as u16 + 1), Constraint::Min(0)])
+        .split(area);
+    frame.render_widget(Paragraph::new(details), halves[0]);
+    render_message(frame, app, halves[1], Alignment::Right);
+}
+
+fn render_message(frame: &mut Frame, app: &AppState, area: Rect, alignment: Alignment) {
+    let theme = theme::current();
     if !app.message.is_empty() {
         let style = match app.message_type {
-            MessageType::Success => Style::default().fg(Color::Green),
-            MessageType::Error => Style::default().fg(Color::Red),
-            MessageType::Info => Style::default().fg(Color::Yellow),
+            MessageType::Success => Style::default().fg(theme.success),
+            MessageType::Error => Style::default().fg(theme.error),
+            MessageType::Info => Style::default().fg(theme.accent),
         };
         
         let message = Paragraph::new(app.message.as_str())
             .style(style)
-            .alignment(Alignment::Center);
+            .alignment(alignment);
         
         frame.render_widget(message, area);
     }
 }
 
+/// Blur the terminal area of locked sessions, keeping only the outline of
+/// their text, and say how to resume them
+fn render_session_lock(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
+    let Some(lock) = &app.session_lock else {
+        return;
+    };
+    let buffer = frame.buffer_mut();
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            let cell = buffer.get_mut(x, y);
+            if cell.symbol != " " {
+                cell.set_symbol("░");
+            }
+            cell.set_style(Style::default().fg(theme.muted).bg(Color::Reset).add_modifier(Modifier::DIM));
+        }
+    }
+
+    let mut lines = vec![Line::from(Span::styled("🔒 Sessions locked while you were away", Style::default().fg(theme.accent).add_modifier(Modifier::BOLD))), Line::from("")];
+    if app.lock_needs_passphrase() {
+        lines.push(Line::from(format!("Config passphrase: {}", "*".repeat(lock.passphrase.chars().count()))));
+        if lock.wrong_passphrase {
+            lines.push(Line::from(Span::styled("Wrong passphrase", Style::default().fg(theme.error))));
+        }
+        lines.push(Line::from(Span::styled("Enter=resume", Style::default().fg(theme.muted))));
+    } else {
+        lines.push(Line::from("Press any key to resume"));
+    }
+    let width = 48.min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let box_area = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    frame.render_widget(Clear, box_area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.accent)).style(Style::default().bg(theme.modal_bg))),
+        box_area
+    );
+}
+
+fn render_undo_toast(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = theme::current();
+    let Some(toast) = app.undo_toast() else {
+        return;
+    };
+
+    let text = toast.text.as_str();
+    let width = (text.chars().count() as u16 + 4).min(area.width);
+    let height = 3.min(area.height);
+    let toast_area = Rect {
+        x: area.x + area.width - width,
+        y: area.y + area.height - height,
+        width,
+        height,
+    };
+
+    frame.render_widget(Clear, toast_area);
+    let toast = Paragraph::new(text)
+        .style(Style::default().fg(theme.text))
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.accent)));
+    frame.render_widget(toast, toast_area);
+}
+
 fn render_help(frame: &mut Frame, app: &AppState, area: Rect) {
-    let help_text = if app.ssh_client.is_connected() {
-        "SSH Connected: Type to interact | Ctrl+Q=disconnect | All keys sent to remote host"
+    let theme = theme::current();
+    let sidebar_key = &app.config.settings.sidebar_key;
+    let help_text = if app.ssh_client.is_connected() && app.sidebar_focused {
+        format!("Sidebar: ↑/↓=navigate | Tab=next panel | Ctrl+G=detach | Ctrl+F=tunnels | {}/Esc=back to session | Session stays open", sidebar_key)
+    } else if app.ssh_client.is_connected() {
+        format!("SSH Connected: Type to interact | Ctrl+Q=disconnect | {}=sidebar, detach and tunnels | Alt+\\=split | All other keys sent to remote host", sidebar_key)
+    } else if app.reconnect.is_some() {
+        "Reconnecting: Ctrl+Q=stop reconnecting | r=retry now".to_string()
     } else {
         match app.focus_area {
-            FocusArea::Keys => "Keys: ↑/↓=navigate | Tab=next panel | Enter=set default | [+/E/D] or Ctrl+N=add/edit/delete",
-            FocusArea::Groups => "Groups: ↑/↓=navigate | Tab=next panel | [+/E/D] or Ctrl+N=add/edit/delete",
-            FocusArea::Hosts => "Hosts: ↑/↓=navigate | Tab=next panel | Enter=connect | [+/E/D] or Ctrl+N=add/edit/delete",
-        }
+            FocusArea::Keys => "Keys: ↑/↓=navigate | Tab=next panel | Enter=set default | g=generate key | Ctrl+L=all actions | Ctrl+K=export authorized_keys | [+/E/D] or Ctrl+N=add/edit/delete",
+            FocusArea::Groups => "Groups: ↑/↓=navigate | ←/→=fold/unfold sub-groups | Tab=next panel | Ctrl+L=all actions | [+/E/D] or Ctrl+N=add/edit/delete",
+            FocusArea::Hosts => "Hosts: ↑/↓=navigate | Tab=next panel | Enter=connect | Ctrl+L=all actions | Ctrl+R=reconnect as before | Ctrl+T=tag filter | Ctrl+F=forwards | Ctrl+G=sessions | [+/E/D] or Ctrl+N=add/edit/delete",
+        }.to_string()
     };
     
     let help = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::DarkGray))
+        .style(Style::default().fg(theme.muted))
         .alignment(Alignment::Center);
     
     frame.render_widget(help, area);
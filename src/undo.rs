@@ -0,0 +1,237 @@
+use crate::{AppState, ConfirmAction, MessageType};
+use crate::config::{Group, SshKey, Trashed, TrashedItem};
+use crate::secrets;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Changes kept for undo; older ones are forgotten
+const HISTORY_LIMIT: usize = 50;
+
+/// The groups, hosts and keys, and the trash, as they were before a change
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    groups: Vec<Group>,
+    keys: Vec<SshKey>,
+    trash: Vec<TrashedItem>,
+    passwords: Passwords,
+}
+
+/// Stored passwords the change touched, by account, None where there was none
+#[derive(Clone, Default)]
+struct Passwords(Vec<(String, Option<String>)>);
+
+impl fmt::Debug for Passwords {
+    // Only the accounts, never the passwords
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter().map(|(account, _)| account)).finish()
+    }
+}
+
+/// A change to the groups, hosts or keys, with what they were on the other
+/// side of it
+#[derive(Debug, Clone)]
+struct ConfigChange {
+    /// Such as "Deleted host 'web'"
+    description: String,
+    snapshot: Snapshot,
+}
+
+/// Shown in the corner after a change, saying how to take it back
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub text: String,
+    pub expires_at: Instant,
+}
+
+/// Changes that Ctrl+Z undoes and Ctrl+Shift+Z (or Alt+Z) redoes, newest last
+#[derive(Debug, Clone, Default)]
+pub struct UndoHistory {
+    done: Vec<ConfigChange>,
+    undone: Vec<ConfigChange>,
+    toast: Option<Toast>,
+}
+
+impl AppState {
+    /// The groups, hosts and keys as they are now, to record a change
+    /// against once it has been made
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            groups: self.config.groups.clone(),
+            keys: self.config.keys.clone(),
+            trash: self.config.trash.clone(),
+            passwords: Passwords::default(),
+        }
+    }
+
+    /// Like `snapshot`, for a change that also sets or deletes the stored
+    /// passwords of `accounts`
+    pub fn snapshot_with_passwords(&self, accounts: &[String]) -> Snapshot {
+        Snapshot { passwords: self.stored_passwords(accounts), ..self.snapshot() }
+    }
+
+    fn stored_passwords(&self, accounts: &[String]) -> Passwords {
+        let backend = self.config.settings.secrets_backend;
+        let mut stored = Vec::new();
+        for account in accounts {
+            if !stored.iter().any(|(seen, _)| seen == account) {
+                stored.push((account.clone(), secrets::get(backend, account).ok().flatten()));
+            }
+        }
+        Passwords(stored)
+    }
+
+    /// Remember a change made since `before` so it can be undone
+    pub fn record_change(&mut self, before: Snapshot, description: String) {
+        self.undo.undone.clear();
+        self.undo.done.push(ConfigChange { description, snapshot: before });
+        if self.undo.done.len() > HISTORY_LIMIT {
+            self.undo.done.remove(0);
+        }
+    }
+
+    /// Forget every change, for a config loaded from elsewhere
+    pub fn clear_history(&mut self) {
+        self.undo = UndoHistory::default();
+    }
+
+    fn show_toast(&mut self, text: String) {
+        let window = Duration::from_secs(self.config.settings.accessibility.prompt_timeout_secs);
+        self.undo.toast = Some(Toast { text, expires_at: Instant::now() + window });
+    }
+
+    /// The toast to show, while it has not timed out
+    pub fn undo_toast(&self) -> Option<&Toast> {
+        self.undo.toast.as_ref().filter(|toast| Instant::now() < toast.expires_at)
+    }
+
+    /// Carry out a delete the user confirmed; hosts and groups go to the
+    /// trash, and any delete can be undone
+    pub fn perform_delete(&mut self, action: ConfirmAction) {
+        // Kept out of this change so undoing it does not bring them back
+        self.empty_old_trash();
+        let before = self.snapshot();
+        let description = match action {
+            ConfirmAction::DeleteKey(index) => {
+                if index >= self.config.keys.len() {
+                    return;
+                }
+                let key = self.config.keys[index].clone();
+                self.config.remove_key(key.id);
+                if self.selected_key >= self.config.keys.len() && self.selected_key > 0 {
+                    self.selected_key = self.config.keys.len() - 1;
+                }
+                format!("SSH key '{}'", key.name)
+            },
+            ConfirmAction::DeleteGroup(index) => {
+                if index == 0 || index >= self.config.groups.len() {
+                    return;
+                }
+                let group = self.config.groups[index].clone();
+                self.config.remove_group(group.id);
+                if self.selected_group >= self.config.groups.len() && self.selected_group > 0 {
+                    self.selected_group = self.config.groups.len() - 1;
+                }
+                self.selected_host = 0;
+                let description = format!("group '{}'", group.name);
+                self.move_to_trash(Trashed::Group { group: Box::new(group) });
+                description
+            },
+            ConfirmAction::DeleteHost(visible_index) => {
+                let hosts = self.visible_hosts();
+                if visible_index >= hosts.len() || !self.hosts_editable() {
+                    return;
+                }
+                let host = hosts[visible_index].clone();
+                // The host may belong to a group nested in the selected one
+                let Some(group_name) = self.config.group_of(&host).map(|group| group.name.clone()) else {
+                    return;
+                };
+                if self.config.remove_host(host.id).is_err() {
+                    self.set_message("Failed to delete host".to_string(), MessageType::Error);
+                    return;
+                }
+                if self.selected_host >= hosts.len() - 1 && self.selected_host > 0 {
+                    self.selected_host = hosts.len().saturating_sub(2);
+                }
+                // Its stored password stays until it leaves the trash
+                let description = format!("host '{}'", host.name);
+                self.move_to_trash(Trashed::Host { group: group_name, host: Box::new(host) });
+                description
+            },
+            ConfirmAction::TakeSharedInventory | ConfirmAction::EmptyEc2Group => return,
+        };
+
+        let _ = self.config.save();
+        self.set_message(format!("Deleted {} (Ctrl+Z to undo)", description), MessageType::Success);
+        self.show_toast(format!("Deleted {} — Ctrl+Z to undo, t for the trash", description));
+        self.record_change(before, format!("Deleted {}", description));
+    }
+
+    /// Take back the most recent change to the groups, hosts or keys
+    pub fn undo_change(&mut self) {
+        let Some(change) = self.undo.done.pop() else {
+            self.set_message("Nothing to undo".to_string(), MessageType::Info);
+            return;
+        };
+        let description = change.description.clone();
+        let (redo, failure) = self.restore(change);
+        self.undo.undone.push(redo);
+        match failure {
+            Some(failure) => self.set_message(format!("Undid: {}, but {}", description, failure), MessageType::Error),
+            None => self.set_message(format!("Undid: {}", description), MessageType::Success),
+        }
+        self.show_toast(format!("Undid: {} — Ctrl+Shift+Z or Alt+Z to redo", description));
+    }
+
+    /// Make the most recently undone change again
+    pub fn redo_change(&mut self) {
+        let Some(change) = self.undo.undone.pop() else {
+            self.set_message("Nothing to redo".to_string(), MessageType::Info);
+            return;
+        };
+        let description = change.description.clone();
+        let (undo, failure) = self.restore(change);
+        self.undo.done.push(undo);
+        match failure {
+            Some(failure) => self.set_message(format!("Redid: {}, but {}", description, failure), MessageType::Error),
+            None => self.set_message(format!("Redid: {}", description), MessageType::Success),
+        }
+        self.show_toast(format!("Redid: {} — Ctrl+Z to undo", description));
+    }
+
+    /// Put the change's side of the groups, hosts, keys and passwords in
+    /// place, returning the side that was there so it can be put back in turn,
+    /// along with why a password could not be put back
+    fn restore(&mut self, change: ConfigChange) -> (ConfigChange, Option<String>) {
+        let Snapshot { groups, keys, trash, passwords } = change.snapshot;
+        let accounts: Vec<String> = passwords.0.iter().map(|(account, _)| account.clone()).collect();
+        let other_side = Snapshot {
+            groups: std::mem::replace(&mut self.config.groups, groups),
+            keys: std::mem::replace(&mut self.config.keys, keys),
+            trash: std::mem::replace(&mut self.config.trash, trash),
+            passwords: self.stored_passwords(&accounts),
+        };
+
+        let backend = self.config.settings.secrets_backend;
+        let mut failure = None;
+        for (account, password) in &passwords.0 {
+            let result = match password {
+                Some(password) => secrets::set(backend, account, password),
+                None => secrets::delete(backend, account),
+            };
+            if let Err(e) = result {
+                failure = Some(format!("the password for {} could not be put back: {}", account, e));
+            }
+        }
+
+        self.selected_key = self.selected_key.min(self.config.keys.len().saturating_sub(1));
+        if self.selected_group >= self.config.groups.len() {
+            self.selected_group = self.config.groups.len().saturating_sub(1);
+            self.virtual_group = None;
+        }
+        self.selected_host = self.selected_host.min(self.visible_hosts().len().saturating_sub(1));
+        let _ = self.config.save();
+
+        (ConfigChange { description: change.description, snapshot: other_side }, failure)
+    }
+}